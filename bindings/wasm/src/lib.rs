@@ -1,4 +1,4 @@
-use js_sys::Uint8Array;
+use js_sys::{Array, Object, Reflect, Uint8Array};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -6,5 +6,54 @@ pub fn disassemble(bytes: Uint8Array) -> String {
     let bytes = bytes.to_vec();
     let disassembler = disassembler::disassemble::Disassembler::new(bytes);
 
-    return disassembler.to_string();
+    disassembler.to_string()
+}
+
+/// Disassembles `bytes`, decoding at most `max_instructions` instructions and
+/// keeping at most `max_labels` labels and `max_strings` strings
+///
+/// A limit of `undefined` is no limit. Bytes past the instruction limit are
+/// left as data and the listing starts with a comment saying so.
+#[wasm_bindgen]
+pub fn disassemble_with_limits(
+    bytes: Uint8Array,
+    max_instructions: Option<usize>,
+    max_labels: Option<usize>,
+    max_strings: Option<usize>,
+) -> String {
+    let limits = disassembler::limits::Limits {
+        max_instructions,
+        max_labels,
+        max_strings,
+        ..Default::default()
+    };
+    let disassembler = disassembler::disassemble::Disassembler::with_limits(bytes.to_vec(), limits);
+
+    disassembler.to_string()
+}
+
+/// Lists the bundled example programs as `{ name, title, description }` objects
+#[wasm_bindgen]
+pub fn examples() -> Array {
+    disassembler::samples::SAMPLES
+        .iter()
+        .map(|sample| {
+            let object = Object::new();
+            for (key, value) in [
+                ("name", sample.name),
+                ("title", sample.title),
+                ("description", sample.description),
+            ] {
+                // setting a property on a fresh plain object cannot fail
+                let _ = Reflect::set(&object, &key.into(), &value.into());
+            }
+            JsValue::from(object)
+        })
+        .collect()
+}
+
+/// Returns the bytes of the example program called `name`
+#[wasm_bindgen]
+pub fn example(name: &str) -> Option<Uint8Array> {
+    disassembler::samples::find(name).map(|sample| Uint8Array::from(sample.bytes))
 }
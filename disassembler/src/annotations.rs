@@ -0,0 +1,311 @@
+use std::fmt::{self, Display};
+
+use crate::comment::{CommentSource, CommentType};
+use crate::consts::Address;
+use crate::region::NamedRegion;
+use crate::table::ElementSize;
+
+/// The version of the format written by
+/// [`Disassembler::annotations`](crate::disassemble::Disassembler::annotations)
+pub const ANNOTATIONS_VERSION: u32 = 1;
+
+/// A name for an address
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Symbol {
+    /// The address named
+    pub address: Address,
+    /// The name
+    pub name: String,
+    /// True if the analysis made the name up, in which case importing it
+    /// does nothing; the analysis will find it again
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub generated: bool,
+}
+
+/// A comment on an address
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommentEntry {
+    /// The address commented on
+    pub address: Address,
+    /// Where the comment goes relative to the instruction
+    pub placement: CommentType,
+    /// The comment, without the leading `;`
+    pub text: String,
+    /// Who wrote the comment, the user unless given
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub source: CommentSource,
+}
+
+/// A range of bytes that is data, not code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DataRange {
+    /// The first address of the data
+    pub start: Address,
+    /// The address just past the data
+    pub end: Address,
+    /// True to list the data as `dw` words rather than bytes
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub words: bool,
+    /// True to list the data as `dd` doublewords, whatever `words` says
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub dwords: bool,
+}
+
+impl DataRange {
+    /// The data `start..end`, listed in elements of `element`
+    pub fn new(start: Address, end: Address, element: ElementSize) -> DataRange {
+        DataRange {
+            start,
+            end,
+            words: element == ElementSize::Word,
+            dwords: element == ElementSize::Dword,
+        }
+    }
+
+    /// The size of the elements the data is listed in
+    pub fn element(&self) -> ElementSize {
+        if self.dwords {
+            ElementSize::Dword
+        } else if self.words {
+            ElementSize::Word
+        } else {
+            ElementSize::Byte
+        }
+    }
+}
+
+/// Everything a user has said about a program, in an interchange format
+/// other tools can read and write without knowing this crate
+///
+/// Serialized with the `serde` feature it looks like this, in JSON:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "symbols": [
+///     { "address": 256, "name": "start" },
+///     { "address": 265, "name": "FUNC_0x0109", "generated": true }
+///   ],
+///   "comments": [
+///     { "address": 256, "placement": "PRE", "text": "entry point" }
+///   ],
+///   "data": [
+///     { "start": 384, "end": 416 },
+///     { "start": 416, "end": 448, "words": true }
+///   ],
+///   "code": [300],
+///   "regions": [
+///     { "name": "font data", "start": 384, "end": 512 }
+///   ]
+/// }
+/// ```
+///
+/// Addresses are offsets in the program's segment, so the first byte of a
+/// COM file is 256. Every list may be left out, as may `generated`,
+/// `words` and a comment's `source`. A file with a `version` newer than
+/// [`ANNOTATIONS_VERSION`] is refused rather than half understood.
+///
+/// Analysis comments are left out, since analysing the program again
+/// recreates them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Annotations {
+    /// The version of the format, see [`ANNOTATIONS_VERSION`]
+    pub version: u32,
+    /// Names for addresses
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub symbols: Vec<Symbol>,
+    /// Comments written by the user or a script
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub comments: Vec<CommentEntry>,
+    /// Ranges marked as data
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub data: Vec<DataRange>,
+    /// Addresses marked as the start of an instruction
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub code: Vec<Address>,
+    /// Named regions
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub regions: Vec<NamedRegion>,
+}
+
+impl Default for Annotations {
+    fn default() -> Self {
+        Annotations {
+            version: ANNOTATIONS_VERSION,
+            symbols: Vec::new(),
+            comments: Vec::new(),
+            data: Vec::new(),
+            code: Vec::new(),
+            regions: Vec::new(),
+        }
+    }
+}
+
+/// Why annotations could not be imported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnnotationError {
+    /// The annotations were written by a newer version of the format
+    UnsupportedVersion(u32),
+    /// A data range or region ends before it starts
+    EmptyRange(Address, Address),
+    /// A symbol has no name
+    EmptyName(Address),
+}
+
+impl Display for AnnotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnnotationError::UnsupportedVersion(version) => write!(
+                f,
+                "annotations version {version} is newer than the supported version {ANNOTATIONS_VERSION}"
+            ),
+            AnnotationError::EmptyRange(start, end) => {
+                write!(f, "the range 0x{start:04x}-0x{end:04x} is empty")
+            }
+            AnnotationError::EmptyName(address) => {
+                write!(f, "the symbol at 0x{address:04x} has no name")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AnnotationError {}
+
+impl Annotations {
+    /// Checks the annotations can be imported, without importing them
+    pub fn validate(&self) -> Result<(), AnnotationError> {
+        if self.version > ANNOTATIONS_VERSION {
+            return Err(AnnotationError::UnsupportedVersion(self.version));
+        }
+        if let Some(symbol) = self.symbols.iter().find(|symbol| symbol.name.is_empty()) {
+            return Err(AnnotationError::EmptyName(symbol.address));
+        }
+        let ranges = self
+            .data
+            .iter()
+            .map(|range| (range.start, range.end))
+            .chain(self.regions.iter().map(|region| (region.start, region.end)));
+        for (start, end) in ranges {
+            if start >= end {
+                return Err(AnnotationError::EmptyRange(start, end));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassemble::Disassembler;
+
+    // call 0x104 / ret / ret / db 1, 2, 3, 4, 5
+    const PROGRAM: &[u8] = &[0xE8, 0x01, 0x00, 0xC3, 0xC3, 1, 2, 3, 4, 5];
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Export and import
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn word_ranges_are_split_out_of_data_ranges() {
+        let mut disassembler = Disassembler::new(PROGRAM.to_vec());
+        disassembler.mark_data(0x105, 0x10A);
+        disassembler.mark_words(0x106, 0x108);
+        disassembler.reanalyze();
+        assert_eq!(
+            disassembler.annotations().data,
+            vec![
+                DataRange::new(0x105, 0x106, ElementSize::Byte),
+                DataRange::new(0x106, 0x108, ElementSize::Word),
+                DataRange::new(0x108, 0x10A, ElementSize::Byte),
+            ]
+        );
+    }
+
+    #[test]
+    fn annotations_round_trip() {
+        let mut original = Disassembler::new(PROGRAM.to_vec());
+        original.rename_label(0x104, "helper");
+        original.mark_words(0x105, 0x109);
+        original.comment_list.0.push(crate::comment::Comment::new(
+            CommentType::PRE,
+            "table of offsets".into(),
+            0x105,
+        ));
+        original.regions.insert(NamedRegion {
+            name: "tables".into(),
+            start: 0x105,
+            end: 0x10A,
+        });
+        original.reanalyze();
+
+        let annotations = original.annotations();
+        let mut copy = Disassembler::new(PROGRAM.to_vec());
+        copy.import_annotations(&annotations).unwrap();
+        assert_eq!(copy.annotations(), annotations);
+        assert_eq!(copy.to_string(), original.to_string());
+
+        // importing twice changes nothing
+        copy.import_annotations(&annotations).unwrap();
+        assert_eq!(copy.annotations(), annotations);
+    }
+
+    #[test]
+    fn generated_symbols_are_not_imported() {
+        let mut disassembler = Disassembler::new(PROGRAM.to_vec());
+        let annotations = Annotations {
+            symbols: vec![
+                Symbol {
+                    address: 0x104,
+                    name: "FUNC_old".into(),
+                    generated: true,
+                },
+                Symbol {
+                    address: 0x100,
+                    name: "start".into(),
+                    generated: false,
+                },
+            ],
+            ..Annotations::default()
+        };
+        disassembler.import_annotations(&annotations).unwrap();
+        assert_eq!(
+            disassembler.labels.get_by_address(0x104).unwrap().name,
+            "FUNC_0x0104"
+        );
+        assert_eq!(
+            disassembler.labels.get_by_address(0x100).unwrap().name,
+            "start"
+        );
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Validation
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn invalid_annotations_import_nothing() {
+        let mut disassembler = Disassembler::new(PROGRAM.to_vec());
+        let before = disassembler.to_string();
+        let newer = Annotations {
+            version: ANNOTATIONS_VERSION + 1,
+            code: vec![0x105],
+            ..Annotations::default()
+        };
+        assert_eq!(
+            disassembler.import_annotations(&newer),
+            Err(AnnotationError::UnsupportedVersion(ANNOTATIONS_VERSION + 1))
+        );
+        let backwards = Annotations {
+            data: vec![DataRange::new(0x108, 0x105, ElementSize::Byte)],
+            ..Annotations::default()
+        };
+        assert_eq!(
+            disassembler.import_annotations(&backwards),
+            Err(AnnotationError::EmptyRange(0x108, 0x105))
+        );
+        assert_eq!(disassembler.to_string(), before);
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use iced_x86::{Instruction, Mnemonic, OpKind, Register};
+
+use crate::consts::{Address, COM_OFFSET};
+use crate::disassemble::is_data;
+use crate::syscall::{SyscallList, SyscallType};
+use crate::table::ElementSize;
+
+/// The registers a `mov reg, imm16` is taken to load a pointer into
+const POINTER_REGISTERS: [Register; 3] = [Register::SI, Register::DI, Register::BX];
+
+/// The DOS calls that take a buffer in DS:DX
+const BUFFER_CALLS: [SyscallType; 5] = [
+    SyscallType::DisplayString,
+    SyscallType::BufferedKeyboardInput,
+    SyscallType::SetDiskTransferAddress,
+    SyscallType::ReadFileOrDevice,
+    SyscallType::WriteFileOrDevice,
+];
+
+/// Memory past the end of the file the program uses, declared with `resb`, `resw` or `resd`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Reservation {
+    /// The first address
+    pub start: Address,
+    /// The size of the elements
+    pub element: ElementSize,
+    /// The number of elements
+    pub count: usize,
+}
+
+impl Reservation {
+    /// The address just past the reserved memory
+    pub fn end(&self) -> u32 {
+        self.start as u32 + (self.count * self.element.bytes()) as u32
+    }
+
+    /// The NASM directive reserving the memory
+    pub fn directive(&self) -> &'static str {
+        match self.element {
+            ElementSize::Byte => "resb",
+            ElementSize::Word => "resw",
+            ElementSize::Dword => "resd",
+        }
+    }
+}
+
+impl Display for Reservation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.directive(), self.count)
+    }
+}
+
+/// The addresses past `program_end` that `instruction` refers to and the
+/// size it accesses them with, if it is known
+fn uninitialized_operands(
+    instruction: &Instruction,
+    program_end: u32,
+) -> Option<(Address, Option<ElementSize>)> {
+    let past_end = |address: u64| address >= program_end as u64 && address < 0x10000;
+    let has_memory =
+        (0..instruction.op_count()).any(|operand| instruction.op_kind(operand) == OpKind::Memory);
+    // indexed operands are left out, as bytes of data decoded as code are
+    // full of them
+    if has_memory {
+        if instruction.memory_base() != Register::None
+            || instruction.memory_index() != Register::None
+        {
+            return None;
+        }
+        let address = instruction.memory_displacement64();
+        let element = match instruction.memory_size().size() {
+            1 => Some(ElementSize::Byte),
+            2 => Some(ElementSize::Word),
+            _ => None,
+        };
+        return past_end(address).then_some((address as Address, element));
+    }
+    let loads_pointer = matches!(instruction.mnemonic(), Mnemonic::Mov | Mnemonic::Lea)
+        && instruction.op0_kind() == OpKind::Register
+        && POINTER_REGISTERS.contains(&instruction.op0_register())
+        && instruction.op1_kind() == OpKind::Immediate16;
+    let address = instruction.immediate16() as u64;
+    (loads_pointer && past_end(address)).then_some((address as Address, None))
+}
+
+/// Finds the memory past the end of the file that the program refers to
+///
+/// References are memory operands with no base or index register, pointers
+/// loaded into SI, DI or BX, and buffers passed to DOS in DX. Each address referred to starts a
+/// reservation running to the next one, in words if it is only accessed as
+/// a word and the gap is even; the last covers one element.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::bss::find_reservations;
+/// use disassembler::disassemble::Disassembler;
+///
+/// // mov [0x200], ax / mov si, 0x210 / ret
+/// let data = vec![0xA3, 0x00, 0x02, 0xBE, 0x10, 0x02, 0xC3];
+/// let disassembler = Disassembler::new(data);
+/// let reservations = find_reservations(
+///     &disassembler.instructions.0,
+///     &disassembler.syscall_list,
+///     disassembler.data.len(),
+/// );
+/// assert_eq!(reservations[0].to_string(), "resw 8");
+/// assert_eq!(reservations[1].to_string(), "resb 1");
+/// ```
+pub fn find_reservations(
+    instructions: &[Instruction],
+    syscalls: &SyscallList,
+    program_len: usize,
+) -> Vec<Reservation> {
+    let program_end = COM_OFFSET as u32 + program_len as u32;
+    // every address referred to and whether anything reads it as other than a word
+    let mut referenced: BTreeMap<Address, bool> = BTreeMap::new();
+    for instruction in instructions
+        .iter()
+        .filter(|instruction| !is_data(instruction))
+    {
+        if let Some((address, element)) = uninitialized_operands(instruction, program_end) {
+            *referenced.entry(address).or_insert(false) |= element != Some(ElementSize::Word);
+        }
+    }
+    for syscall in &syscalls.0 {
+        if let Some(dx) = syscall.dx
+            && BUFFER_CALLS.contains(&syscall.number)
+            && dx as u32 >= program_end
+        {
+            referenced.insert(dx, true);
+        }
+    }
+
+    let starts: Vec<(Address, bool)> = referenced.into_iter().collect();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(index, &(start, bytes))| {
+            let gap = starts
+                .get(index + 1)
+                .map_or(if bytes { 1 } else { 2 }, |&(next, _)| {
+                    (next - start) as usize
+                });
+            if bytes || !gap.is_multiple_of(2) {
+                Reservation {
+                    start,
+                    element: ElementSize::Byte,
+                    count: gap,
+                }
+            } else {
+                Reservation {
+                    start,
+                    element: ElementSize::Word,
+                    count: gap / 2,
+                }
+            }
+        })
+        .collect()
+}
+
+/// Reserves the `count` bytes from `start` on their own, splitting the
+/// reservations they overlap and filling any gap between them and the rest,
+/// so the reservations still run on from one to the next
+pub(crate) fn reserve(reservations: &mut Vec<Reservation>, start: Address, count: usize) {
+    let end = start as u32 + count as u32;
+    let sized = |start: u32, bytes: u32, element: ElementSize| {
+        if bytes.is_multiple_of(element.bytes() as u32) {
+            Reservation {
+                start: start as Address,
+                element,
+                count: bytes as usize / element.bytes(),
+            }
+        } else {
+            Reservation {
+                start: start as Address,
+                element: ElementSize::Byte,
+                count: bytes as usize,
+            }
+        }
+    };
+    let mut kept = Vec::new();
+    for reservation in reservations.drain(..) {
+        let (first, last) = (reservation.start as u32, reservation.end());
+        if last <= start as u32 || first >= end {
+            kept.push(reservation);
+            continue;
+        }
+        if first < start as u32 {
+            kept.push(sized(first, start as u32 - first, reservation.element));
+        }
+        if last > end {
+            kept.push(sized(end, last - end, reservation.element));
+        }
+    }
+    kept.push(sized(start as u32, count as u32, ElementSize::Byte));
+    kept.sort_by_key(|reservation| reservation.start);
+    for reservation in kept {
+        if let Some(last) = reservations.last()
+            && last.end() < reservation.start as u32
+        {
+            let gap = sized(
+                last.end(),
+                reservation.start as u32 - last.end(),
+                ElementSize::Byte,
+            );
+            reservations.push(gap);
+        }
+        reservations.push(reservation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassemble::Disassembler;
+
+    fn reservations(data: Vec<u8>) -> Vec<Reservation> {
+        let disassembler = Disassembler::new(data);
+        find_reservations(
+            &disassembler.instructions.0,
+            &disassembler.syscall_list,
+            disassembler.data.len(),
+        )
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Finding the memory used
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn read_buffers_passed_in_dx_are_reserved() {
+        let data = vec![
+            0xB4, 0x3F, // mov ah, 0x3f
+            0xBA, 0x00, 0x03, // mov dx, 0x300
+            0xCD, 0x21, // int 21h
+            0xC3, // ret
+        ];
+        assert_eq!(
+            reservations(data),
+            vec![Reservation {
+                start: 0x300,
+                element: ElementSize::Byte,
+                count: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn addresses_inside_the_file_are_not_reserved() {
+        // mov al, [0x104] / ret / db 7
+        let data = vec![0xA0, 0x04, 0x01, 0xC3, 0x07];
+        assert!(reservations(data).is_empty());
+    }
+
+    #[test]
+    fn odd_gaps_after_words_are_bytes() {
+        let data = vec![
+            0xFF, 0x06, 0x00, 0x02, // inc word [0x200]
+            0xC6, 0x06, 0x03, 0x02, 0x01, // mov byte [0x203], 1
+            0xC3, // ret
+        ];
+        let found = reservations(data);
+        assert_eq!(
+            (found[0].element, found[0].count, found[0].end()),
+            (ElementSize::Byte, 3, 0x203)
+        );
+        assert_eq!((found[1].element, found[1].count), (ElementSize::Byte, 1));
+    }
+}
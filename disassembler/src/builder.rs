@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+
+use iced_x86::{Encoder, Instruction, Register};
+
+use crate::consts::{Address, COM_OFFSET};
+
+/// How a label's address is written into an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reference {
+    /// As the address itself, as in `mov dx, msg`
+    Absolute,
+    /// As a word counted from the end of the instruction, as in `call`
+    Near,
+    /// As a byte counted from the end of the instruction, as in `jmp short`
+    Short,
+}
+
+/// A label used before the program is complete
+#[derive(Debug, Clone)]
+struct Fixup {
+    /// Where in the program the address goes
+    offset: usize,
+    /// The address just past the instruction
+    end: Address,
+    /// The label referred to
+    label: String,
+    /// How the address is written
+    reference: Reference,
+}
+
+/// Builds COM programs for tests out of instructions, labels and strings
+///
+/// Labels can be used before they are placed and are filled in by
+/// [`build`](ComBuilder::build), so a test reads like the assembly it
+/// stands for instead of a list of bytes.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::builder::ComBuilder;
+/// use disassembler::disassemble::Disassembler;
+/// use iced_x86::Register;
+///
+/// let program = ComBuilder::new()
+///     .mov_address(Register::DX, "msg")
+///     .dos(0x09)
+///     .ret()
+///     .label("msg")
+///     .string("Hi$")
+///     .build();
+/// assert_eq!(program, [0xBA, 0x08, 0x01, 0xB4, 0x09, 0xCD, 0x21, 0xC3, b'H', b'i', b'$']);
+///
+/// let disassembler = Disassembler::new(program);
+/// assert_eq!(disassembler.string_constant_list.0[0].value, "Hi$");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ComBuilder {
+    /// The program so far
+    code: Vec<u8>,
+    /// The labels placed, by name
+    labels: BTreeMap<String, Address>,
+    /// The label references to fill in
+    fixups: Vec<Fixup>,
+}
+
+impl ComBuilder {
+    /// Starts an empty program
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The address the next instruction goes at
+    pub fn address(&self) -> Address {
+        COM_OFFSET + self.code.len() as Address
+    }
+
+    /// Names the address the next instruction goes at
+    ///
+    /// # Panics
+    ///
+    /// Panics if the label is already placed.
+    pub fn label(mut self, name: &str) -> Self {
+        let address = self.address();
+        assert!(
+            self.labels.insert(name.to_string(), address).is_none(),
+            "label {name} placed twice"
+        );
+        self
+    }
+
+    /// Adds raw bytes
+    pub fn bytes(mut self, bytes: &[u8]) -> Self {
+        self.code.extend_from_slice(bytes);
+        self
+    }
+
+    /// Adds the bytes of `text`, with no terminator
+    pub fn string(self, text: &str) -> Self {
+        self.bytes(text.as_bytes())
+    }
+
+    /// Adds `instruction`, encoded at the address it goes at
+    ///
+    /// # Panics
+    ///
+    /// Panics if iced cannot encode the instruction in 16-bit code.
+    pub fn instruction(mut self, instruction: Instruction) -> Self {
+        let mut encoder = Encoder::new(16);
+        if let Err(err) = encoder.encode(&instruction, self.address() as u64) {
+            panic!("cannot encode {instruction}: {err}");
+        }
+        self.code.extend(encoder.take_buffer());
+        self
+    }
+
+    /// Adds `mov register, value` for an 8-bit or 16-bit general register
+    ///
+    /// # Panics
+    ///
+    /// Panics if `register` is not a general register.
+    pub fn mov(self, register: Register, value: u16) -> Self {
+        let number = register.number() as u8;
+        if register.is_gpr8() {
+            self.bytes(&[0xB0 + number, value as u8])
+        } else if register.is_gpr16() {
+            let [low, high] = value.to_le_bytes();
+            self.bytes(&[0xB8 + number, low, high])
+        } else {
+            panic!("mov to {register:?} is not supported")
+        }
+    }
+
+    /// Adds `mov register, label` for a 16-bit general register
+    ///
+    /// # Panics
+    ///
+    /// Panics if `register` is not a 16-bit general register.
+    pub fn mov_address(self, register: Register, label: &str) -> Self {
+        assert!(register.is_gpr16(), "mov to {register:?} is not supported");
+        self.bytes(&[0xB8 + register.number() as u8])
+            .reference(label, Reference::Absolute)
+    }
+
+    /// Adds `int number`
+    pub fn int(self, number: u8) -> Self {
+        self.bytes(&[0xCD, number])
+    }
+
+    /// Adds `mov ah, function` and `int 21h`, a DOS call
+    pub fn dos(self, function: u8) -> Self {
+        self.mov(Register::AH, function as u16).int(0x21)
+    }
+
+    /// Adds `call label`
+    pub fn call(self, label: &str) -> Self {
+        self.bytes(&[0xE8]).reference(label, Reference::Near)
+    }
+
+    /// Adds a near `jmp label`
+    pub fn jmp(self, label: &str) -> Self {
+        self.bytes(&[0xE9]).reference(label, Reference::Near)
+    }
+
+    /// Adds `jmp short label`
+    pub fn jmp_short(self, label: &str) -> Self {
+        self.bytes(&[0xEB]).reference(label, Reference::Short)
+    }
+
+    /// Adds `ret`
+    pub fn ret(self) -> Self {
+        self.bytes(&[0xC3])
+    }
+
+    /// Adds room for a reference to `label`, filled in when the program is
+    /// built
+    fn reference(mut self, label: &str, reference: Reference) -> Self {
+        let offset = self.code.len();
+        let size = match reference {
+            Reference::Short => 1,
+            Reference::Absolute | Reference::Near => 2,
+        };
+        self.code.resize(offset + size, 0);
+        self.fixups.push(Fixup {
+            offset,
+            end: self.address(),
+            label: label.to_string(),
+            reference,
+        });
+        self
+    }
+
+    /// Fills in the labels and returns the program
+    ///
+    /// # Panics
+    ///
+    /// Panics if a label used is never placed, or a short jump does not
+    /// reach its label.
+    pub fn build(mut self) -> Vec<u8> {
+        for fixup in &self.fixups {
+            let Some(&target) = self.labels.get(&fixup.label) else {
+                panic!("label {} is used but never placed", fixup.label);
+            };
+            let distance = target.wrapping_sub(fixup.end);
+            match fixup.reference {
+                Reference::Absolute => {
+                    self.code[fixup.offset..fixup.offset + 2].copy_from_slice(&target.to_le_bytes())
+                }
+                Reference::Near => self.code[fixup.offset..fixup.offset + 2]
+                    .copy_from_slice(&distance.to_le_bytes()),
+                Reference::Short => {
+                    let Ok(distance) = i8::try_from(distance as i16) else {
+                        panic!("a short jump cannot reach {}", fixup.label);
+                    };
+                    self.code[fixup.offset] = distance as u8;
+                }
+            }
+        }
+        self.code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_are_filled_in_either_side_of_their_use() {
+        let program = ComBuilder::new()
+            .label("start")
+            .call("print")
+            .jmp_short("start")
+            .label("print")
+            .jmp("start")
+            .build();
+        assert_eq!(program, [0xE8, 0x02, 0x00, 0xEB, 0xFB, 0xE9, 0xF8, 0xFF]);
+    }
+
+    #[test]
+    fn registers_pick_their_move() {
+        let program = ComBuilder::new()
+            .mov(Register::CL, 4)
+            .mov(Register::BX, 0x1234)
+            .build();
+        assert_eq!(program, [0xB1, 0x04, 0xBB, 0x34, 0x12]);
+    }
+
+    #[test]
+    #[should_panic(expected = "label missing is used but never placed")]
+    fn unplaced_labels_panic() {
+        ComBuilder::new().call("missing").build();
+    }
+}
@@ -4,7 +4,8 @@ use crate::consts::Address;
 
 /// an enum representing the type of comment
 /// that can be added to the disassembly
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CommentType {
     /// A comment Before the instruction
     PRE,
@@ -14,9 +15,48 @@ pub enum CommentType {
     INLINE,
 }
 
+/// where a comment came from
+///
+/// Comments at the same address and of the same type are listed in the
+/// order of the variants, analysis first and scripts last.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CommentSource {
+    /// Added by the named analysis pass, such as `strings`; recreated on reanalysis
+    Analysis(String),
+    /// Written by the user
+    #[default]
+    User,
+    /// Added by the named script or plugin
+    Script(String),
+}
+
+impl CommentSource {
+    /// creates the source for an analysis pass
+    pub fn analysis(pass: &str) -> CommentSource {
+        CommentSource::Analysis(pass.to_string())
+    }
+
+    /// returns true if the comment was added by an analysis pass
+    pub fn is_analysis(&self) -> bool {
+        matches!(self, CommentSource::Analysis(_))
+    }
+}
+
+impl Display for CommentSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommentSource::Analysis(pass) => write!(f, "{pass}"),
+            CommentSource::User => write!(f, "user"),
+            CommentSource::Script(name) => write!(f, "script: {name}"),
+        }
+    }
+}
+
 /// a struct representing a comment
 /// that can be added to the disassembly
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Comment {
     /// the type of comment
     pub comment_type: CommentType,
@@ -24,10 +64,12 @@ pub struct Comment {
     pub comment_text: String,
     /// the address of the comment
     pub address: Address,
+    /// where the comment came from
+    pub source: CommentSource,
 }
 
 impl Comment {
-    /// creates a new comment
+    /// creates a new comment written by the user
     /// # Arguments
     /// * `comment_type` - the type of comment
     /// * `comment_text` - the comment text
@@ -36,8 +78,30 @@ impl Comment {
             comment_type,
             comment_text,
             address,
+            source: CommentSource::User,
         }
     }
+
+    /// sets where the comment came from
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::comment::{Comment, CommentSource, CommentType};
+    ///
+    /// let comment = Comment::new(CommentType::PRE, "Start of string data".into(), 0x120)
+    ///     .with_source(CommentSource::analysis("strings"));
+    /// assert_eq!(comment.with_provenance(), "; [strings] Start of string data");
+    /// ```
+    pub fn with_source(mut self, source: CommentSource) -> Comment {
+        self.source = source;
+        self
+    }
+
+    /// displays the comment with its source, as `; [<source>] <comment_text>`
+    pub fn with_provenance(&self) -> String {
+        format!("; [{}] {}", self.source, self.comment_text)
+    }
 }
 
 impl Display for Comment {
@@ -49,7 +113,8 @@ impl Display for Comment {
 }
 
 /// a struct representing a list of comments
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CommentList(pub Vec<Comment>);
 
 impl CommentList {
@@ -72,7 +137,34 @@ impl CommentList {
         CommentList(Vec::new())
     }
 
-    /// gets the comments for a given address
+    /// sorts the comments by address, then type, then source
+    ///
+    /// Comments that tie keep the order they were added in, so the same
+    /// analysis always lists them the same way.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::comment::{Comment, CommentList, CommentSource, CommentType};
+    ///
+    /// let mut comment_list = CommentList::new();
+    /// comment_list.0.push(Comment::new(CommentType::PRE, "mine".into(), 0x104));
+    /// comment_list.0.push(
+    ///     Comment::new(CommentType::PRE, "found".into(), 0x104)
+    ///         .with_source(CommentSource::analysis("strings")),
+    /// );
+    /// comment_list.0.push(Comment::new(CommentType::INLINE, "first".into(), 0x100));
+    /// comment_list.sort();
+    /// let texts: Vec<&str> = comment_list.0.iter().map(|c| c.comment_text.as_str()).collect();
+    /// assert_eq!(texts, ["first", "found", "mine"]);
+    /// ```
+    pub fn sort(&mut self) {
+        self.0.sort_by(|a, b| {
+            (a.address, a.comment_type, &a.source).cmp(&(b.address, b.comment_type, &b.source))
+        });
+    }
+
+    /// gets the comments for a given address, ordered by type, then source
     /// # Arguments
     ///
     /// * `address` - the address of the comment
@@ -97,10 +189,13 @@ impl CommentList {
     ///
     /// ```
     pub fn get_comments(&self, address: Address) -> Vec<&Comment> {
-        self.0
+        let mut comments: Vec<&Comment> = self
+            .0
             .iter()
             .filter(|comment| comment.address == address)
-            .collect()
+            .collect();
+        comments.sort_by(|a, b| (a.comment_type, &a.source).cmp(&(b.comment_type, &b.source)));
+        comments
     }
 }
 
@@ -177,4 +272,54 @@ mod tests {
         assert_eq!(x, y);
         assert_ne!(x, z);
     }
+    // ──────────────────────────────────────────────────────────────────────────
+    // 6.  Provenance
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn provenance_names_the_source() {
+        let user = cmt(0x100, CommentType::PRE, "mine");
+        assert_eq!(user.source, CommentSource::User);
+        assert_eq!(user.with_provenance(), "; [user] mine");
+        assert_eq!(user.to_string(), "; mine", "plain display is unchanged");
+
+        let scripted = user.with_source(CommentSource::Script("rename.lua".into()));
+        assert_eq!(scripted.with_provenance(), "; [script: rename.lua] mine");
+        assert!(!scripted.source.is_analysis());
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 7.  Ordering does not depend on the order comments were added in
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn sorting_is_independent_of_insertion_order() {
+        let comments = [
+            cmt(0x200, CommentType::PRE, "user"),
+            cmt(0x100, CommentType::POST, "after"),
+            cmt(0x200, CommentType::PRE, "labels").with_source(CommentSource::analysis("labels")),
+            cmt(0x100, CommentType::PRE, "before"),
+            cmt(0x200, CommentType::PRE, "script").with_source(CommentSource::Script("x".into())),
+        ];
+        let mut forward = CommentList(comments.to_vec());
+        let mut backward = CommentList(comments.iter().rev().cloned().collect());
+        forward.sort();
+        backward.sort();
+        assert_eq!(forward, backward);
+        let texts: Vec<&str> = forward.0.iter().map(|c| c.comment_text.as_str()).collect();
+        assert_eq!(texts, ["before", "after", "labels", "user", "script"]);
+    }
+
+    #[test]
+    fn get_comments_orders_by_type_then_source() {
+        let list = CommentList(vec![
+            cmt(0x100, CommentType::INLINE, "inline"),
+            cmt(0x100, CommentType::PRE, "user"),
+            cmt(0x100, CommentType::PRE, "found").with_source(CommentSource::analysis("rules")),
+        ]);
+        let texts: Vec<&str> = list
+            .get_comments(0x100)
+            .iter()
+            .map(|c| c.comment_text.as_str())
+            .collect();
+        assert_eq!(texts, ["found", "user", "inline"]);
+    }
 }
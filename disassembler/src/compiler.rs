@@ -0,0 +1,374 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use iced_x86::{Instruction, Mnemonic, OpKind, Register};
+
+use crate::consts::{Address, AddressExt};
+use crate::disassemble::{Disassembler, is_data};
+use crate::syscall::SyscallType;
+
+/// How many instructions from the entry point count as startup code
+const STARTUP: usize = 16;
+
+/// How many functions must use a calling convention before it counts
+const CONVENTION_USES: usize = 2;
+
+/// The score at which a guess stops being a fallback to hand-written
+/// assembly
+const CONFIDENT: u32 = 30;
+
+/// What a program was most likely written with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Toolchain {
+    /// Written directly in assembly
+    Assembly,
+    /// Compiled by Turbo Pascal, which could write COM files up to 3.0
+    TurboPascal,
+    /// Compiled by Turbo C in the tiny model
+    TurboC,
+    /// Compiled by Microsoft C in the tiny model
+    MicrosoftC,
+    /// Compiled by QuickBASIC and converted from an EXE
+    QuickBasic,
+    /// Built as an EXE and converted with EXE2BIN or a similar tool
+    ExeToCom,
+    /// Nothing to tell from, as the program has no code
+    Unknown,
+}
+
+impl Display for Toolchain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Toolchain::Assembly => write!(f, "hand-written assembly"),
+            Toolchain::TurboPascal => write!(f, "Turbo Pascal"),
+            Toolchain::TurboC => write!(f, "Turbo C"),
+            Toolchain::MicrosoftC => write!(f, "Microsoft C"),
+            Toolchain::QuickBasic => write!(f, "QuickBASIC"),
+            Toolchain::ExeToCom => write!(f, "a converted EXE"),
+            Toolchain::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Text a runtime library leaves in every program it is linked into, the
+/// toolchain it points to and how much it counts for
+const STRINGS: &[(&str, Toolchain, u32)] = &[
+    ("BORLAND Inc", Toolchain::TurboPascal, 60),
+    ("Turbo Pascal", Toolchain::TurboPascal, 60),
+    ("Runtime error ", Toolchain::TurboPascal, 30),
+    ("Borland Intl", Toolchain::TurboC, 60),
+    ("Turbo-C", Toolchain::TurboC, 60),
+    ("Abnormal program termination", Toolchain::TurboC, 30),
+    ("Null pointer assignment", Toolchain::TurboC, 20),
+    ("Null pointer assignment", Toolchain::MicrosoftC, 20),
+    ("MS Run-Time Library", Toolchain::MicrosoftC, 60),
+    ("R6001", Toolchain::MicrosoftC, 40),
+    ("QuickBASIC", Toolchain::QuickBasic, 60),
+    ("RETURN without GOSUB", Toolchain::QuickBasic, 40),
+    ("Out of DATA", Toolchain::QuickBasic, 40),
+];
+
+/// One thing pointing at a toolchain
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Clue {
+    /// The toolchain the clue points to
+    pub toolchain: Toolchain,
+    /// How much the clue counts for
+    pub weight: u32,
+    /// Where the clue was found, if it is in one place
+    pub address: Option<Address>,
+    /// What was found
+    pub reason: String,
+}
+
+/// A guess at what a program was written with, and how sure it is
+///
+/// Made from the runtime library's strings, the shape of the startup code
+/// and the calling convention functions use: Pascal functions pop their
+/// arguments with `ret N`, C callers with `add sp, N`. A program showing
+/// none of these is taken to be hand-written assembly, and one without any
+/// code at all is [unknown](Toolchain::Unknown). Knowing the
+/// toolchain tells which library routines to expect and which signatures
+/// are worth matching.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Identification {
+    /// The most likely toolchain
+    pub toolchain: Toolchain,
+    /// How sure the guess is, from 0 to 100, or `None` for
+    /// [`Toolchain::Unknown`]
+    pub confidence: Option<u8>,
+    /// Everything found, for every toolchain
+    pub clues: Vec<Clue>,
+}
+
+impl Identification {
+    /// Guesses the toolchain of the analysed program
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::compiler::{Identification, Toolchain};
+    /// use disassembler::disassemble::Disassembler;
+    ///
+    /// let hello = disassembler::samples::find("hello").unwrap();
+    /// let guess = Identification::detect(&Disassembler::new(hello.bytes.to_vec()));
+    /// assert_eq!(guess.toolchain, Toolchain::Assembly);
+    /// ```
+    pub fn detect(disassembler: &Disassembler) -> Identification {
+        let mut clues = Vec::new();
+
+        for &(text, toolchain, weight) in STRINGS {
+            let found = disassembler
+                .data
+                .windows(text.len())
+                .position(|window| window == text.as_bytes());
+            if let Some(offset) = found {
+                clues.push(Clue {
+                    toolchain,
+                    weight,
+                    address: Address::from_file_offset(offset),
+                    reason: format!("contains \"{text}\""),
+                });
+            }
+        }
+
+        let code: Vec<&Instruction> = disassembler
+            .instructions
+            .0
+            .iter()
+            .filter(|instruction| !is_data(instruction))
+            .collect();
+        if code.is_empty() {
+            return Identification {
+                toolchain: Toolchain::Unknown,
+                confidence: None,
+                clues,
+            };
+        }
+        startup_clues(disassembler, &code, &mut clues);
+        convention_clues(&code, &mut clues);
+
+        let mut scores: BTreeMap<Toolchain, u32> = BTreeMap::new();
+        for clue in &clues {
+            *scores.entry(clue.toolchain).or_default() += clue.weight;
+        }
+        if scores.values().all(|&score| score < CONFIDENT) {
+            clues.push(Clue {
+                toolchain: Toolchain::Assembly,
+                weight: 40,
+                address: None,
+                reason: "no runtime library or compiler calling convention".to_string(),
+            });
+            *scores.entry(Toolchain::Assembly).or_default() += 40;
+        }
+
+        let total: u32 = scores.values().sum();
+        let (toolchain, best) = scores.iter().fold(
+            (Toolchain::Assembly, 0),
+            |(best, high), (&toolchain, &score)| {
+                if score > high {
+                    (toolchain, score)
+                } else {
+                    (best, high)
+                }
+            },
+        );
+        Identification {
+            toolchain,
+            confidence: Some((best.min(100) * best / total.max(1)) as u8),
+            clues,
+        }
+    }
+}
+
+/// Looks for the DOS version check C startup code makes and the segment
+/// arithmetic of EXE conversion stubs
+fn startup_clues(disassembler: &Disassembler, code: &[&Instruction], clues: &mut Vec<Clue>) {
+    let startup = &code[..code.len().min(STARTUP)];
+    let version = startup.iter().find(|instruction| {
+        disassembler
+            .syscall_list
+            .get_by_address(instruction.ip() as Address)
+            .is_some_and(|syscall| syscall.number == SyscallType::GetDosVersion)
+    });
+    if let Some(version) = version {
+        for toolchain in [Toolchain::TurboC, Toolchain::MicrosoftC] {
+            clues.push(Clue {
+                toolchain,
+                weight: 15,
+                address: Some(version.ip() as Address),
+                reason: "asks for the DOS version at startup".to_string(),
+            });
+        }
+    }
+
+    // mov reg, cs ... add reg, paragraphs: the stub working out where the
+    // EXE's segments landed
+    let relocation = startup.iter().enumerate().find_map(|(index, instruction)| {
+        let copies_cs = instruction.mnemonic() == Mnemonic::Mov
+            && instruction.op0_kind() == OpKind::Register
+            && instruction.op1_kind() == OpKind::Register
+            && instruction.op1_register() == Register::CS;
+        let register = instruction.op0_register();
+        let adds = startup[index + 1..].iter().any(|next| {
+            next.mnemonic() == Mnemonic::Add
+                && next.op0_kind() == OpKind::Register
+                && next.op0_register() == register
+                && matches!(
+                    next.op1_kind(),
+                    OpKind::Immediate16 | OpKind::Immediate8to16
+                )
+        });
+        (copies_cs && adds).then_some(instruction.ip() as Address)
+    });
+    if let Some(address) = relocation {
+        clues.push(Clue {
+            toolchain: Toolchain::ExeToCom,
+            weight: 50,
+            address: Some(address),
+            reason: "adds paragraphs to CS at startup to find its segments".to_string(),
+        });
+    }
+}
+
+/// Counts functions that pop their own arguments and callers that pop
+/// them, among programs that set up BP stack frames
+fn convention_clues(code: &[&Instruction], clues: &mut Vec<Clue>) {
+    let frames = code
+        .windows(2)
+        .filter(|pair| {
+            pair[0].mnemonic() == Mnemonic::Push
+                && pair[0].op0_register() == Register::BP
+                && pair[1].mnemonic() == Mnemonic::Mov
+                && pair[1].op0_register() == Register::BP
+                && pair[1].op1_register() == Register::SP
+        })
+        .count();
+    if frames < CONVENTION_USES {
+        return;
+    }
+    let callee_pops = code
+        .iter()
+        .filter(|instruction| {
+            instruction.mnemonic() == Mnemonic::Ret && instruction.op0_kind() == OpKind::Immediate16
+        })
+        .count();
+    let caller_pops = code
+        .windows(2)
+        .filter(|pair| {
+            pair[0].mnemonic() == Mnemonic::Call
+                && pair[1].mnemonic() == Mnemonic::Add
+                && pair[1].op0_kind() == OpKind::Register
+                && pair[1].op0_register() == Register::SP
+        })
+        .count();
+    if callee_pops >= CONVENTION_USES {
+        clues.push(Clue {
+            toolchain: Toolchain::TurboPascal,
+            weight: 20,
+            address: None,
+            reason: format!("{callee_pops} functions pop their arguments with ret N"),
+        });
+    }
+    if caller_pops >= CONVENTION_USES {
+        for toolchain in [Toolchain::TurboC, Toolchain::MicrosoftC] {
+            clues.push(Clue {
+                toolchain,
+                weight: 15,
+                address: None,
+                reason: format!("{caller_pops} calls are followed by add sp to pop arguments"),
+            });
+        }
+    }
+}
+
+impl Display for Identification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.toolchain)?;
+        if let Some(confidence) = self.confidence {
+            write!(f, " ({confidence}% confidence)")?;
+        }
+        let reasons: Vec<&str> = self
+            .clues
+            .iter()
+            .filter(|clue| clue.toolchain == self.toolchain)
+            .map(|clue| clue.reason.as_str())
+            .collect();
+        if !reasons.is_empty() {
+            write!(f, ": {}", reasons.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guess(data: Vec<u8>) -> Identification {
+        Identification::detect(&Disassembler::new(data))
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Identification
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn runtime_strings_and_ret_n_point_to_turbo_pascal() {
+        let mut data = vec![
+            0x6A, 0x01, // push 1
+            0xE8, 0x09, 0x00, // call 0x10e
+            0x6A, 0x02, // push 2
+            0xE8, 0x04, 0x00, // call 0x10e
+            0xCD, 0x20, // int 20h
+            0x90, 0x90, // nop
+            0x55, // 0x10e: push bp
+            0x89, 0xE5, // mov bp, sp
+            0x5D, // pop bp
+            0xC2, 0x02, 0x00, // ret 2
+            0x55, // push bp
+            0x89, 0xE5, // mov bp, sp
+            0x5D, // pop bp
+            0xC2, 0x04, 0x00, // ret 4
+        ];
+        data.extend(b"Copyright (C) 1985 BORLAND Inc");
+        let identification = guess(data);
+        assert_eq!(identification.toolchain, Toolchain::TurboPascal);
+        assert_eq!(identification.confidence, Some(80));
+        assert_eq!(
+            identification.to_string(),
+            "Turbo Pascal (80% confidence): contains \"BORLAND Inc\", \
+             2 functions pop their arguments with ret N"
+        );
+    }
+
+    #[test]
+    fn segment_arithmetic_at_startup_is_a_converted_exe() {
+        let data = vec![
+            0x8C, 0xC8, // mov ax, cs
+            0x05, 0x10, 0x00, // add ax, 0x10
+            0x8E, 0xD8, // mov ds, ax
+            0xCD, 0x20, // int 20h
+        ];
+        let identification = guess(data);
+        assert_eq!(identification.toolchain, Toolchain::ExeToCom);
+        assert_eq!(identification.clues[0].address, Some(0x100));
+    }
+
+    #[test]
+    fn programs_without_clues_are_assembly() {
+        let identification = guess(vec![0xB4, 0x4C, 0xCD, 0x21]);
+        assert_eq!(identification.toolchain, Toolchain::Assembly);
+        assert_eq!(identification.confidence, Some(40));
+    }
+
+    #[test]
+    fn empty_programs_are_unknown() {
+        let identification = guess(Vec::new());
+        assert_eq!(identification.toolchain, Toolchain::Unknown);
+        assert_eq!(identification.confidence, None);
+        assert_eq!(identification.to_string(), "unknown");
+    }
+}
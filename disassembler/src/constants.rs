@@ -0,0 +1,225 @@
+use std::fmt::{self, Display};
+
+use crate::consts::COM_OFFSET;
+
+/// An error in a constant table, with the line it was found on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstantError {
+    /// The 1-based line number of the error
+    pub line: usize,
+    /// What is wrong with the line
+    pub message: String,
+}
+
+impl Display for ConstantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ConstantError {}
+
+/// A 16-bit value with a well-known meaning
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KnownConstant {
+    /// The value
+    pub value: u16,
+    /// What the value means, shown as a comment wherever it is used
+    pub meaning: String,
+}
+
+/// The values the built-in table explains
+const BUILTIN: &[(u16, &str)] = &[
+    (0xA000, "EGA/VGA graphics memory segment"),
+    (0xB000, "monochrome text memory segment"),
+    (0xB800, "colour text memory segment"),
+    (0xF000, "BIOS ROM segment"),
+    (0x1234, "warm reboot flag, stored at 0040:0072"),
+    (0xAA55, "boot sector signature"),
+    (0x5A4D, "EXE header signature 'MZ'"),
+    (0x0A0D, "CR, LF"),
+    (
+        0x34DC,
+        "low word of the PIT clock, 1193180 Hz with 0x12 as the high word",
+    ),
+    (0x04A9, "PIT divisor for ~1000 Hz"),
+    (0x2E9C, "PIT divisor for ~100 Hz"),
+    (0x0201, "game port"),
+    (0x0220, "Sound Blaster base port"),
+    (0x0388, "AdLib index/status port"),
+    (0x0389, "AdLib data port"),
+    (0x03C8, "VGA DAC write index port"),
+    (0x03C9, "VGA DAC data port"),
+    (0x03D4, "CRTC index port"),
+    (0x03D5, "CRTC data port"),
+    (0x03DA, "VGA input status port, bit 3 is vertical retrace"),
+];
+
+/// A table of values with well-known meanings, such as video memory
+/// segments and hardware ports
+///
+/// The analysis comments every 16-bit immediate found in the table. A value
+/// listed twice means what its last entry says, so a table loaded on top of
+/// [`ConstantTable::builtin`] can replace built-in meanings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConstantTable(pub Vec<KnownConstant>);
+
+impl Default for ConstantTable {
+    fn default() -> Self {
+        ConstantTable::builtin()
+    }
+}
+
+impl ConstantTable {
+    /// The table of constants known without loading anything
+    pub fn builtin() -> Self {
+        ConstantTable(
+            BUILTIN
+                .iter()
+                .map(|&(value, meaning)| KnownConstant {
+                    value,
+                    meaning: meaning.to_string(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Parses a constant table, one `VALUE = meaning` line per constant
+    ///
+    /// Values are hex with a `0x` prefix or decimal. Lines starting with `#`
+    /// or `//` are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::constants::ConstantTable;
+    ///
+    /// let table = ConstantTable::parse("# ports\n0x3F8 = COM1 serial port\n").unwrap();
+    /// assert_eq!(table.get(0x3F8), Some("COM1 serial port"));
+    /// ```
+    pub fn parse(text: &str) -> Result<ConstantTable, ConstantError> {
+        let mut constants = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| ConstantError {
+                line: index + 1,
+                message,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+                continue;
+            }
+            let (value, meaning) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected `VALUE = meaning`, found `{line}`")))?;
+            let value = value.trim();
+            let value = match value.strip_prefix("0x") {
+                Some(hex) => u16::from_str_radix(hex, 16),
+                None => value.parse(),
+            }
+            .map_err(|err| error(format!("invalid value `{value}`: {err}")))?;
+            let meaning = meaning.trim();
+            if meaning.is_empty() {
+                return Err(error(format!("0x{value:04x} has no meaning")));
+            }
+            constants.push(KnownConstant {
+                value,
+                meaning: meaning.to_string(),
+            });
+        }
+        Ok(ConstantTable(constants))
+    }
+
+    /// Adds the constants of `other`, replacing the meanings of values
+    /// already in the table
+    pub fn extend(&mut self, other: ConstantTable) {
+        self.0.extend(other.0);
+    }
+
+    /// The meaning of `value`, if the table has one
+    pub fn get(&self, value: u16) -> Option<&str> {
+        self.0
+            .iter()
+            .rev()
+            .find(|constant| constant.value == value)
+            .map(|constant| constant.meaning.as_str())
+    }
+
+    /// Explains `value` used as an immediate in a program of `length` bytes
+    ///
+    /// Values missing from the table whose two bytes are letters are shown
+    /// as the characters they hold in memory, low byte first, unless they
+    /// could be an address inside the program.
+    pub fn describe(&self, value: u16, length: usize) -> Option<String> {
+        if let Some(meaning) = self.get(value) {
+            return Some(meaning.to_string());
+        }
+        let in_program =
+            (COM_OFFSET as usize..COM_OFFSET as usize + length).contains(&(value as usize));
+        let [low, high] = value.to_le_bytes();
+        if in_program || !low.is_ascii_alphabetic() || !high.is_ascii_alphabetic() {
+            return None;
+        }
+        Some(format!("'{}{}'", low as char, high as char))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comment::CommentSource;
+    use crate::disassemble::Disassembler;
+
+    fn explained(program: &[u8]) -> Vec<(u16, String)> {
+        let disassembler = Disassembler::new(program.to_vec());
+        disassembler
+            .comment_list
+            .0
+            .iter()
+            .filter(|comment| comment.source == CommentSource::analysis("constants"))
+            .map(|comment| (comment.address, comment.comment_text.clone()))
+            .collect()
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Tables
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn later_entries_replace_earlier_meanings() {
+        let mut table = ConstantTable::builtin();
+        table.extend(ConstantTable::parse("47104 = video").unwrap());
+        assert_eq!(table.get(0xB800), Some("video"));
+    }
+
+    #[test]
+    fn table_errors_report_the_line() {
+        let err = ConstantTable::parse("0xB800 = video\n\n0xZZ = nothing").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert!(ConstantTable::parse("0x10 =").is_err());
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Annotation
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn known_values_and_letter_pairs_are_explained() {
+        // mov ax, 0xb800 / cmp ax, 'MZ' / mov bx, 0x0102 / push 'hh' / ret
+        let program = [
+            0xB8, 0x00, 0xB8, 0x3D, 0x4D, 0x5A, 0xBB, 0x02, 0x01, 0x68, 0x68, 0x68, 0xC3,
+        ];
+        assert_eq!(
+            explained(&program),
+            vec![
+                (0x100, "colour text memory segment".to_string()),
+                (0x103, "EXE header signature 'MZ'".to_string()),
+            ]
+        );
+
+        // cmp ax, 'AB' is explained by its letters
+        assert_eq!(
+            explained(&[0x3D, 0x41, 0x42, 0xC3]),
+            vec![(0x100, "'AB'".to_string())]
+        );
+    }
+}
@@ -6,3 +6,74 @@ pub const SIZE: u32 = 16;
 
 /// A type alias for any address in the program
 pub type Address = u16;
+
+/// Range and overflow-checked arithmetic for [`Address`]es
+///
+/// Addresses are offsets in a 64 KiB segment, so adding a length can run
+/// off the end and an end address one past 0xFFFF does not fit. These
+/// helpers make the edge cases explicit instead of wrapping or panicking.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::consts::{Address, AddressExt};
+///
+/// assert_eq!(Address::range(0x100, 0x104).count(), 4);
+/// assert_eq!(0xFFFE.iter_to(0xFFFF).count(), 2);
+/// assert_eq!(0xFFF0.checked_add_len(0x10), None);
+/// assert_eq!(0xFFF0.saturating_add_len(0x20), 0xFFFF);
+/// assert_eq!(0x0105.file_offset(), Some(5));
+/// assert_eq!(0x0080.file_offset(), None);
+/// assert_eq!(Address::from_file_offset(5), Some(0x0105));
+/// ```
+pub trait AddressExt: Sized {
+    /// The addresses from `start` up to but not including `end`, none if
+    /// `end` is not past `start`
+    fn range(start: Self, end: Self) -> std::ops::Range<Self>;
+
+    /// The addresses from this one up to and including `last`, which may
+    /// be 0xFFFF
+    fn iter_to(self, last: Self) -> std::ops::RangeInclusive<Self>;
+
+    /// The address `len` bytes on, or `None` past the end of the segment
+    fn checked_add_len(self, len: usize) -> Option<Self>;
+
+    /// The address `len` bytes on, stopping at the end of the segment
+    fn saturating_add_len(self, len: usize) -> Self;
+
+    /// Where in a COM file the byte loaded at this address is, or `None`
+    /// below [`COM_OFFSET`]
+    fn file_offset(self) -> Option<usize>;
+
+    /// The address the byte at `offset` in a COM file is loaded at, or
+    /// `None` if it does not fit in the segment
+    fn from_file_offset(offset: usize) -> Option<Self>;
+}
+
+impl AddressExt for Address {
+    fn range(start: Address, end: Address) -> std::ops::Range<Address> {
+        start..end
+    }
+
+    fn iter_to(self, last: Address) -> std::ops::RangeInclusive<Address> {
+        self..=last
+    }
+
+    fn checked_add_len(self, len: usize) -> Option<Address> {
+        Address::try_from(len)
+            .ok()
+            .and_then(|len| self.checked_add(len))
+    }
+
+    fn saturating_add_len(self, len: usize) -> Address {
+        self.checked_add_len(len).unwrap_or(Address::MAX)
+    }
+
+    fn file_offset(self) -> Option<usize> {
+        self.checked_sub(COM_OFFSET).map(usize::from)
+    }
+
+    fn from_file_offset(offset: usize) -> Option<Address> {
+        COM_OFFSET.checked_add_len(offset)
+    }
+}
@@ -0,0 +1,310 @@
+use std::fmt::{self, Display};
+
+use crate::consts::Address;
+use crate::disassemble::Disassembler;
+use crate::syscall::{Syscall, SyscallType};
+
+/// A field of a buffer DOS fills in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Field {
+    /// The offset of the field in the buffer
+    pub offset: u16,
+    /// The size of the field in bytes
+    pub size: u16,
+    /// The name of the field, used in its label after `country_`
+    pub name: &'static str,
+}
+
+const fn field(offset: u16, size: u16, name: &'static str) -> Field {
+    Field { offset, size, name }
+}
+
+/// The 34 bytes of country info int 21h AH=38h returns, as DOS 3 and
+/// later lay them out
+pub const COUNTRY_INFO: [Field; 12] = [
+    // 0 for month, day, year, 1 for day, month, year, 2 for year, month, day
+    field(0x00, 2, "date_format"),
+    field(0x02, 5, "currency"),
+    field(0x07, 2, "thousands_separator"),
+    field(0x09, 2, "decimal_separator"),
+    field(0x0B, 2, "date_separator"),
+    field(0x0D, 2, "time_separator"),
+    field(0x0F, 1, "currency_format"),
+    field(0x10, 1, "currency_digits"),
+    // bit 0 set for a 24-hour clock
+    field(0x11, 1, "time_format"),
+    // a far pointer to the routine that uppercases characters above 0x7f
+    field(0x12, 4, "case_map"),
+    field(0x16, 2, "list_separator"),
+    field(0x18, 10, "reserved"),
+];
+
+/// The header int 21h AH=65h AL=01h puts before the [country
+/// info](COUNTRY_INFO)
+pub const EXTENDED_INFO_HEADER: [Field; 4] = [
+    field(0x00, 1, "info_id"),
+    field(0x01, 2, "info_size"),
+    field(0x03, 2, "id"),
+    field(0x05, 2, "code_page"),
+];
+
+/// What int 21h AH=65h AL=02h to 07h returns: the function number and a
+/// far pointer to the table asked for
+pub const TABLE_POINTER: [Field; 2] = [field(0x00, 1, "info_id"), field(0x01, 4, "table")];
+
+/// A country or code page call and what AL asked it for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CountryOperation {
+    /// AH=38h reading the country info into DS:DX, for the current country
+    /// if AL=0, the country in BX if AL=FFh, and the country AL otherwise
+    GetCountryInfo {
+        /// AL, if it was tracked
+        country: Option<u8>,
+    },
+    /// AH=38h with DX=FFFFh, changing the current country
+    SetCountry {
+        /// AL, if it was tracked
+        country: Option<u8>,
+    },
+    /// AH=65h, with the function in AL
+    Extended {
+        /// AL
+        function: u8,
+    },
+    /// AH=66h AL=01h
+    GetCodePage,
+    /// AH=66h AL=02h
+    SetCodePage,
+}
+
+impl Display for CountryOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            CountryOperation::GetCountryInfo { country } => match country {
+                Some(0x00) => write!(f, "get the current country's info"),
+                Some(0xFF) => write!(f, "get the info of the country in bx"),
+                Some(country) => write!(f, "get the info of country {country}"),
+                None => write!(f, "get the info of the country in al"),
+            },
+            CountryOperation::SetCountry { country } => match country {
+                Some(0xFF) => write!(f, "set the country to the one in bx"),
+                Some(country) => write!(f, "set the country to {country}"),
+                None => write!(f, "set the country to the one in al"),
+            },
+            CountryOperation::Extended { function } => match function {
+                0x01 => write!(f, "get the extended country info"),
+                0x02 => write!(f, "get a pointer to the uppercase table"),
+                0x04 => write!(f, "get a pointer to the filename uppercase table"),
+                0x05 => write!(f, "get a pointer to the filename terminator table"),
+                0x06 => write!(f, "get a pointer to the collating sequence table"),
+                0x07 => write!(f, "get a pointer to the DBCS lead byte table"),
+                0x20 => write!(f, "uppercase the character in dl"),
+                0x21 => write!(f, "uppercase the cx bytes at ds:dx"),
+                0x22 => write!(f, "uppercase the zero-terminated string at ds:dx"),
+                0x23 => write!(f, "check whether dl means yes or no"),
+                function => write!(f, "extended country function 0x{function:02x}"),
+            },
+            CountryOperation::GetCodePage => {
+                write!(
+                    f,
+                    "get the active code page into bx and the system's into dx"
+                )
+            }
+            CountryOperation::SetCodePage => write!(f, "set the active code page to bx"),
+        }
+    }
+}
+
+impl CountryOperation {
+    /// The fields of the buffer the call fills in, if it takes one
+    pub fn fields(&self) -> Vec<Field> {
+        match *self {
+            CountryOperation::GetCountryInfo { .. } => COUNTRY_INFO.to_vec(),
+            CountryOperation::Extended { function: 0x01 } => {
+                let offset = EXTENDED_INFO_HEADER
+                    .iter()
+                    .map(|field| field.size)
+                    .sum::<u16>();
+                EXTENDED_INFO_HEADER
+                    .into_iter()
+                    .chain(COUNTRY_INFO.into_iter().map(|field| Field {
+                        offset: offset + field.offset,
+                        ..field
+                    }))
+                    .collect()
+            }
+            CountryOperation::Extended {
+                function: 0x02 | 0x04..=0x07,
+            } => TABLE_POINTER.to_vec(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// An int 21h call for country info, case mapping or code pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CountryCall {
+    /// The address of the int 21h
+    pub address: Address,
+    /// What the call does
+    pub operation: CountryOperation,
+    /// The buffer it fills in, if it takes one and the address was tracked
+    pub buffer: Option<Address>,
+}
+
+impl CountryCall {
+    /// The address of each field of the buffer the call fills in
+    pub fn fields(&self) -> Vec<(Address, Field)> {
+        let Some(buffer) = self.buffer else {
+            return Vec::new();
+        };
+        self.operation
+            .fields()
+            .into_iter()
+            .filter_map(|field| Some((buffer.checked_add(field.offset)?, field)))
+            .collect()
+    }
+
+    /// The call an int 21h made, unless it is no country or code page call
+    /// or AL was not tracked where it picks the function
+    fn from_syscall(syscall: &Syscall) -> Option<CountryCall> {
+        let (operation, buffer) = match syscall.number {
+            SyscallType::GetOrSetCountryInfo if syscall.dx == Some(0xFFFF) => (
+                CountryOperation::SetCountry {
+                    country: syscall.al,
+                },
+                None,
+            ),
+            SyscallType::GetOrSetCountryInfo => (
+                CountryOperation::GetCountryInfo {
+                    country: syscall.al,
+                },
+                syscall.dx,
+            ),
+            SyscallType::GetExtendedCountryInfo => (
+                CountryOperation::Extended {
+                    function: syscall.al?,
+                },
+                syscall.di,
+            ),
+            SyscallType::GetOrSetCodePage => match syscall.al? {
+                0x01 => (CountryOperation::GetCodePage, None),
+                0x02 => (CountryOperation::SetCodePage, None),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let buffer = buffer.filter(|_| !operation.fields().is_empty());
+        Some(CountryCall {
+            address: syscall.address,
+            operation,
+            buffer,
+        })
+    }
+}
+
+impl Display for CountryCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.operation)?;
+        if let Some(buffer) = self.buffer {
+            write!(f, " into 0x{buffer:04x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds the int 21h calls for country info (AH=38h), extended country info
+/// and case mapping (AH=65h) and code pages (AH=66h)
+///
+/// The function AL picks is taken from the last value loaded into it, and
+/// the buffer from DX for AH=38h and from DI for AH=65h, as COM programs
+/// leave ES pointing at their own segment. The
+/// [disassembler](Disassembler) labels the buffer's fields, such as
+/// `country_date_format`.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::country::{CountryOperation, find_country_calls};
+/// use disassembler::disassemble::Disassembler;
+///
+/// let data = vec![
+///     0xB8, 0x00, 0x38, // mov ax, 0x3800
+///     0xBA, 0x00, 0x02, // mov dx, 0x200
+///     0xCD, 0x21, // int 21h
+///     0xC3, // ret
+/// ];
+/// let calls = find_country_calls(&Disassembler::new(data));
+/// assert_eq!(calls[0].operation, CountryOperation::GetCountryInfo { country: Some(0) });
+/// assert_eq!(calls[0].to_string(), "get the current country's info into 0x0200");
+/// ```
+pub fn find_country_calls(disassembler: &Disassembler) -> Vec<CountryCall> {
+    disassembler
+        .syscall_list
+        .0
+        .iter()
+        .filter_map(CountryCall::from_syscall)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixpoint::settle;
+
+    #[test]
+    fn the_fields_cover_the_buffers() {
+        let end = |fields: &[Field]| {
+            fields.iter().fold(0, |offset, field| {
+                assert_eq!(field.offset, offset, "{}", field.name);
+                offset + field.size
+            })
+        };
+        assert_eq!(end(&COUNTRY_INFO), 34);
+        let extended = CountryOperation::Extended { function: 0x01 }.fields();
+        assert_eq!(end(&extended), 41);
+    }
+
+    #[test]
+    fn buffers_past_the_end_are_reserved_field_by_field() {
+        let data = vec![
+            0xB8, 0x01, 0x65, // mov ax, 0x6501
+            0xBF, 0x00, 0x02, // mov di, 0x200
+            0xB9, 0x29, 0x00, // mov cx, 41
+            0xCD, 0x21, // int 21h
+            0xC3, // ret
+        ];
+        let listing = Disassembler::new(data).to_string();
+        assert!(
+            listing.contains("int 0x21 ; get the extended country info into 0x0200"),
+            "{listing}"
+        );
+        assert!(
+            listing.contains("country_code_page: ; data\n    resb 2\ncountry_date_format: ; data\n    resb 2\ncountry_currency: ; data\n    resb 5"),
+            "{listing}"
+        );
+    }
+
+    #[test]
+    fn buffers_in_the_file_are_split_into_rows_by_field() {
+        let mut data = vec![
+            0xB4, 0x38, // mov ah, 0x38
+            0xB0, 0x00, // mov al, 0
+            0xBA, 0x0A, 0x01, // mov dx, 0x10a
+            0xCD, 0x21, // int 21h
+            0xC3, // ret
+        ];
+        data.extend([0; 34]);
+        let mut disassembler = Disassembler::new(data);
+        settle(&mut disassembler);
+        let listing = disassembler.to_string();
+        assert!(
+            listing.contains("country_date_format: ; data\n    db 0,0\ncountry_currency: ; data\n"),
+            "{listing}"
+        );
+        assert!(listing.contains("country_reserved: ; data"), "{listing}");
+    }
+}
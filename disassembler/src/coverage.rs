@@ -0,0 +1,226 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Display};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+use crate::emulate::Trace;
+
+/// A half-open range of addresses, `start..end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// The first address in the region
+    pub start: Address,
+    /// The address just past the region
+    pub end: Address,
+}
+
+impl Region {
+    /// The number of bytes in the region
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start) as usize
+    }
+
+    /// Returns true if the region contains no bytes
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "0x{:04x}-0x{:04x} ({} bytes)",
+            self.start,
+            self.end,
+            self.len()
+        )
+    }
+}
+
+/// Merges a sorted set of addresses into contiguous regions
+fn regions(addresses: &BTreeSet<Address>) -> Vec<Region> {
+    let mut regions: Vec<Region> = Vec::new();
+    for &address in addresses {
+        match regions.last_mut() {
+            Some(region) if region.end == address => region.end = address + 1,
+            _ => regions.push(Region {
+                start: address,
+                end: address.saturating_add(1),
+            }),
+        }
+    }
+    regions
+}
+
+/// A comparison of what static analysis classified as code with what actually ran
+///
+/// # Example
+///
+/// ```
+/// use disassembler::coverage::CoverageReport;
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::emulate::Emulator;
+///
+/// // ret / nop
+/// let data = vec![0xC3, 0x90];
+/// let mut emulator = Emulator::new(&data);
+/// emulator.run(100);
+///
+/// let report = CoverageReport::new(&Disassembler::new(data), &emulator.trace);
+/// assert_eq!(report.never_executed[0].start, 0x101);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageReport {
+    /// Bytes the static analysis decoded as instructions
+    pub static_code_bytes: usize,
+    /// Bytes belonging to instructions that were executed
+    pub executed_bytes: usize,
+    /// Executed bytes the static analysis classified as data
+    pub executed_as_data: Vec<Region>,
+    /// Executed instructions that start inside a statically decoded instruction
+    pub misaligned: Vec<Address>,
+    /// Statically decoded code that never ran
+    pub never_executed: Vec<Region>,
+}
+
+impl CoverageReport {
+    /// Compares the instructions of `disassembler` with the addresses executed in `trace`
+    pub fn new(disassembler: &Disassembler, trace: &Trace) -> Self {
+        let mut static_code = BTreeSet::new();
+        let mut static_data = BTreeSet::new();
+        let mut static_starts = BTreeSet::new();
+        for instruction in &disassembler.instructions.0 {
+            let start = instruction.ip() as Address;
+            let bytes = (instruction.ip()..instruction.next_ip()).map(|address| address as Address);
+            if is_data(instruction) {
+                static_data.extend(bytes);
+            } else {
+                static_starts.insert(start);
+                static_code.extend(bytes);
+            }
+        }
+
+        let mut executed = BTreeSet::new();
+        for &address in &trace.executed {
+            let Some(instruction) = disassembler.decode_at(address) else {
+                continue;
+            };
+            executed.extend(
+                (instruction.ip()..instruction.next_ip()).map(|address| address as Address),
+            );
+        }
+
+        let misaligned = trace
+            .executed
+            .iter()
+            .copied()
+            .filter(|address| static_code.contains(address) && !static_starts.contains(address))
+            .collect();
+
+        CoverageReport {
+            static_code_bytes: static_code.len(),
+            executed_bytes: executed.len(),
+            executed_as_data: regions(&executed.intersection(&static_data).copied().collect()),
+            misaligned,
+            never_executed: regions(&static_code.difference(&executed).copied().collect()),
+        }
+    }
+
+    /// Returns true if static analysis and execution disagree anywhere it matters
+    ///
+    /// Code that never ran is not counted, since one run rarely reaches every path.
+    pub fn has_conflicts(&self) -> bool {
+        !self.executed_as_data.is_empty() || !self.misaligned.is_empty()
+    }
+}
+
+impl Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = if self.static_code_bytes == 0 {
+            0.0
+        } else {
+            let never: usize = self.never_executed.iter().map(Region::len).sum();
+            100.0 * (self.static_code_bytes - never) as f64 / self.static_code_bytes as f64
+        };
+        writeln!(
+            f,
+            "static code: {} bytes, executed: {} bytes, {percent:.1}% of static code executed",
+            self.static_code_bytes, self.executed_bytes
+        )?;
+        for region in &self.executed_as_data {
+            writeln!(f, "  executed but classified as data: {region}")?;
+        }
+        for address in &self.misaligned {
+            writeln!(
+                f,
+                "  executed instruction at 0x{address:04x} starts inside a decoded instruction"
+            )?;
+        }
+        for region in &self.never_executed {
+            writeln!(f, "  classified as code but never executed: {region}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulate::Emulator;
+
+    fn report(data: Vec<u8>, traced: bool) -> CoverageReport {
+        let mut emulator = Emulator::new(&data);
+        emulator.run(1_000);
+        let disassembler = if traced {
+            Disassembler::with_trace(data, &emulator.trace)
+        } else {
+            Disassembler::new(data)
+        };
+        CoverageReport::new(&disassembler, &emulator.trace)
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Region merging
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn consecutive_addresses_merge() {
+        let addresses = [0x100, 0x101, 0x102, 0x200].into_iter().collect();
+        let merged = regions(&addresses);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].len(), 3);
+        assert_eq!(
+            merged[1],
+            Region {
+                start: 0x200,
+                end: 0x201
+            }
+        );
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Comparisons
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn jumping_into_an_instruction_is_misaligned() {
+        // jmp short 0x103 / db 0xB8 (mov ax, imm16 swallowing the ret) / ret
+        let data = vec![0xEB, 0x01, 0xB8, 0xC3, 0x90];
+        let report = report(data, false);
+        assert_eq!(report.misaligned, vec![0x103]);
+        assert!(report.has_conflicts());
+    }
+
+    #[test]
+    fn trace_assisted_disassembly_has_no_conflicts() {
+        let data = vec![0xEB, 0x01, 0xB8, 0xC3, 0x90];
+        let report = report(data, true);
+        assert!(!report.has_conflicts(), "{report}");
+        assert_eq!(
+            report.never_executed,
+            vec![Region {
+                start: 0x104,
+                end: 0x105
+            }]
+        );
+    }
+}
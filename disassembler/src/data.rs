@@ -0,0 +1,223 @@
+use crate::consts::{Address, AddressExt};
+use crate::program::{ItemKind, Program};
+use crate::string::text_directive;
+use crate::style::Style;
+use crate::table::ElementSize;
+
+/// The most bytes listed on one row of numbers, as in the listing
+const ROW_BYTES: usize = 8;
+
+/// How the values of a data item are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Encoding {
+    /// Numbers, with `db`, `dw` or `dd`
+    Numbers,
+    /// Text in quotes, with the bytes that cannot be quoted as numbers
+    Text,
+    /// Memory past the end of the file, with `resb`, `resw` or `resd`
+    Reserved,
+}
+
+/// A run of data and the directives it is declared with
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DataItem {
+    /// The first address of the data
+    pub start: Address,
+    /// The address just past the data, which may be 0x10000
+    pub end: u32,
+    /// The size of the elements
+    pub element: ElementSize,
+    /// How the elements are written
+    pub encoding: Encoding,
+    /// The name of the first address, if it has one
+    pub label: Option<String>,
+}
+
+impl DataItem {
+    /// Collects the data in `program` into items, in address order
+    ///
+    /// Each string is one item, as is each piece of memory past the end of
+    /// the file. Rows of numbers are merged into one item for as long as
+    /// their element size stays the same and no symbol names an address
+    /// between them, bytes left over after the last whole element included.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::data::{DataItem, Encoding};
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::program::Program;
+    /// use disassembler::style::Style;
+    ///
+    /// // mov dx, 0x108 / mov ah, 9 / int 21h / ret / "Hi$"
+    /// let data = vec![0xBA, 0x08, 0x01, 0xB4, 0x09, 0xCD, 0x21, 0xC3, b'H', b'i', b'$'];
+    /// let program = Program::build(&Disassembler::new(data));
+    ///
+    /// let items = DataItem::collect(&program);
+    /// assert_eq!(items[0].encoding, Encoding::Text);
+    /// assert_eq!(items[0].directives(&program, &Style::default()), ["db \"Hi$\""]);
+    /// ```
+    pub fn collect(program: &Program) -> Vec<DataItem> {
+        let mut items: Vec<DataItem> = Vec::new();
+        for item in &program.items {
+            let label = program.symbol(item.start).map(|label| label.name.clone());
+            let (element, encoding) = match &item.kind {
+                ItemKind::Instruction(_) => continue,
+                ItemKind::Data { element } => (*element, Encoding::Numbers),
+                ItemKind::String(_) => (ElementSize::Byte, Encoding::Text),
+            };
+            // the bytes left over after a run of words or doublewords are
+            // listed with it
+            let leftover = element == ElementSize::Byte
+                && items.last().is_some_and(|last| {
+                    ((item.end - item.start as u32) as usize) < last.element.bytes()
+                });
+            if let Some(last) = items.last_mut()
+                && encoding == Encoding::Numbers
+                && label.is_none()
+                && last.encoding == Encoding::Numbers
+                && (last.element == element || leftover)
+                && last.end == item.start as u32
+                && last.len().is_multiple_of(last.element.bytes())
+            {
+                last.end = item.end;
+                continue;
+            }
+            items.push(DataItem {
+                start: item.start,
+                end: item.end,
+                element,
+                encoding,
+                label,
+            });
+        }
+        for reservation in &program.reservations {
+            items.push(DataItem {
+                start: reservation.start,
+                end: reservation.end(),
+                element: reservation.element,
+                encoding: Encoding::Reserved,
+                label: program
+                    .symbol(reservation.start)
+                    .map(|label| label.name.clone()),
+            });
+        }
+        items
+    }
+
+    /// The number of bytes the item covers
+    pub fn len(&self) -> usize {
+        (self.end - self.start as u32) as usize
+    }
+
+    /// Returns true if the item covers no bytes
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of whole elements in the item
+    pub fn count(&self) -> usize {
+        self.len() / self.element.bytes()
+    }
+
+    /// The directives declaring the item, one per row, written in `style`
+    ///
+    /// Numbers are listed eight bytes to a row, with bytes left over after
+    /// the last whole element on a `db` row of their own. Text is one row.
+    pub fn directives(&self, program: &Program, style: &Style) -> Vec<String> {
+        if self.encoding == Encoding::Reserved {
+            let directive = match self.element {
+                ElementSize::Byte => "resb",
+                ElementSize::Word => "resw",
+                ElementSize::Dword => "resd",
+            };
+            return vec![format!("{} {}", style.mnemonic(directive), self.count())];
+        }
+        let bytes = self
+            .start
+            .file_offset()
+            .and_then(|start| program.bytes.get(start..start + self.len()))
+            .unwrap_or_default();
+        if self.encoding == Encoding::Text {
+            return vec![text_directive(bytes.iter().copied(), style)];
+        }
+
+        let size = self.element.bytes();
+        let whole = bytes.len() - bytes.len() % size;
+        let mut rows: Vec<String> = bytes[..whole]
+            .chunks(ROW_BYTES)
+            .map(|row| numbers(row, self.element, style))
+            .collect();
+        if whole < bytes.len() {
+            rows.push(numbers(&bytes[whole..], ElementSize::Byte, style));
+        }
+        rows
+    }
+}
+
+/// A row of `bytes` read as little-endian elements of `element`
+fn numbers(bytes: &[u8], element: ElementSize, style: &Style) -> String {
+    let size = element.bytes();
+    let values: Vec<String> = bytes
+        .chunks_exact(size)
+        .map(|value| {
+            let number = value
+                .iter()
+                .rev()
+                .fold(0u64, |number, &byte| number << 8 | byte as u64);
+            style.hex(number, size * 2)
+        })
+        .collect();
+    format!(
+        "{} {}",
+        style.mnemonic(element.directive()),
+        values.join(",")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassemble::Disassembler;
+
+    #[test]
+    fn rows_are_typed_by_their_marks() {
+        // ret / a far pointer / two words / a byte
+        let data = vec![0xC3, 0x34, 0x12, 0x00, 0xF0, 0x01, 0x00, 0x02, 0x00, 0xFF];
+        let mut disassembler = Disassembler::new(data);
+        disassembler.mark_dwords(0x101, 0x105);
+        disassembler.mark_words(0x105, 0x10A);
+        disassembler.reanalyze();
+        let program = Program::build(&disassembler);
+
+        let items = DataItem::collect(&program);
+        let style = Style::default();
+        assert_eq!(items.len(), 2);
+        assert_eq!(
+            (items[0].element, items[0].count()),
+            (ElementSize::Dword, 1)
+        );
+        assert_eq!(items[0].directives(&program, &style), ["dd 0xf0001234"]);
+        assert_eq!((items[1].element, items[1].count()), (ElementSize::Word, 2));
+        assert_eq!(
+            items[1].directives(&program, &style),
+            ["dw 0x0001,0x0002", "db 0xff"]
+        );
+    }
+
+    #[test]
+    fn reservations_are_items() {
+        // mov [0x200], ax / ret
+        let data = vec![0xA3, 0x00, 0x02, 0xC3];
+        let program = Program::build(&Disassembler::new(data));
+        let items = DataItem::collect(&program);
+        let reserved = items
+            .iter()
+            .find(|item| item.encoding == Encoding::Reserved)
+            .unwrap();
+        assert_eq!(reserved.start, 0x200);
+        assert_eq!(reserved.directives(&program, &Style::default()), ["resw 1"]);
+    }
+}
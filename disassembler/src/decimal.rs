@@ -0,0 +1,297 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{FlowControl, Instruction, Mnemonic, OpKind, Register};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+use crate::label::LabelType;
+use crate::syscall::SyscallType;
+
+/// How many instructions apart the parts of an idiom may be
+const WINDOW: usize = 6;
+
+/// What a run of decimal or BCD arithmetic converts between
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DecimalIdiom {
+    /// Splits a number into decimal digits and adds `'0'` to each, with
+    /// `aam` or a `div` by 10
+    ToDecimal,
+    /// Turns ASCII digits back into a number, subtracting `'0'` and
+    /// combining them with `aad`
+    FromDecimal,
+    /// Turns a nibble into `'0'`-`'9'` or `'A'`-`'F'` with the `daa` or
+    /// `das` trick
+    ToHexDigit,
+}
+
+impl Display for DecimalIdiom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecimalIdiom::ToDecimal => write!(f, "binary→ASCII decimal conversion"),
+            DecimalIdiom::FromDecimal => write!(f, "ASCII decimal→binary conversion"),
+            DecimalIdiom::ToHexDigit => write!(f, "binary→ASCII hex digit conversion"),
+        }
+    }
+}
+
+/// A number conversion found in the code
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Conversion {
+    /// The instruction doing the conversion: the `aam`, `aad`, `div`, `daa`
+    /// or `das`
+    pub address: Address,
+    /// What it converts
+    pub idiom: DecimalIdiom,
+    /// The function the conversion is part of, if any
+    pub function: Option<Address>,
+    /// True if the function also prints, making it a `print_number` helper
+    pub prints: bool,
+}
+
+impl Conversion {
+    /// The name the function containing the conversion is given
+    pub fn function_name(&self) -> &'static str {
+        match (self.idiom, self.prints) {
+            (DecimalIdiom::ToDecimal, true) => "print_number",
+            (DecimalIdiom::ToDecimal, false) => "to_decimal",
+            (DecimalIdiom::FromDecimal, _) => "parse_number",
+            (DecimalIdiom::ToHexDigit, true) => "print_hex",
+            (DecimalIdiom::ToHexDigit, false) => "to_hex",
+        }
+    }
+}
+
+/// Returns true if `instruction` adds `'0'` to a digit or pair of digits
+fn adds_ascii_zero(instruction: &Instruction) -> bool {
+    matches!(instruction.mnemonic(), Mnemonic::Add | Mnemonic::Or)
+        && immediate(instruction).is_some_and(|value| value == 0x30 || value == 0x3030)
+}
+
+/// Returns true if `instruction` takes `'0'` off a digit or pair of digits
+fn removes_ascii_zero(instruction: &Instruction) -> bool {
+    match instruction.mnemonic() {
+        Mnemonic::Sub => {
+            immediate(instruction).is_some_and(|value| value == 0x30 || value == 0x3030)
+        }
+        Mnemonic::And => {
+            immediate(instruction).is_some_and(|value| value == 0x0F || value == 0x0F0F)
+        }
+        _ => false,
+    }
+}
+
+/// The immediate second operand of `instruction`
+fn immediate(instruction: &Instruction) -> Option<u16> {
+    match instruction.op1_kind() {
+        OpKind::Immediate8 => Some(instruction.immediate8() as u16),
+        OpKind::Immediate16 | OpKind::Immediate8to16 => Some(instruction.immediate16()),
+        _ => None,
+    }
+}
+
+/// Returns true if `instruction` is `mnemonic al, value`
+fn is_al_op(instruction: &Instruction, mnemonic: Mnemonic, value: u16) -> bool {
+    instruction.mnemonic() == mnemonic
+        && instruction.op0_kind() == OpKind::Register
+        && instruction.op0_register() == Register::AL
+        && immediate(instruction) == Some(value)
+}
+
+/// Recognises the conversion, if any, that the instruction at `index` does
+fn idiom_at(code: &[&Instruction], index: usize) -> Option<DecimalIdiom> {
+    let instruction = code[index];
+    let before = &code[index.saturating_sub(WINDOW)..index];
+    let after = code.get(index + 1..).unwrap_or_default();
+    let after = &after[..after.len().min(WINDOW)];
+    match instruction.mnemonic() {
+        // aam divides by its immediate, 10 unless another base is given
+        Mnemonic::Aam if instruction.immediate8() == 10 => after
+            .iter()
+            .any(|next| adds_ascii_zero(next))
+            .then_some(DecimalIdiom::ToDecimal),
+        Mnemonic::Div if instruction.op0_kind() == OpKind::Register => {
+            let divisor = instruction.op0_register();
+            let by_ten = before.iter().rev().any(|previous| {
+                previous.mnemonic() == Mnemonic::Mov
+                    && previous.op0_kind() == OpKind::Register
+                    && previous.op0_register() == divisor
+                    && immediate(previous) == Some(10)
+            });
+            (by_ten && after.iter().any(|next| adds_ascii_zero(next)))
+                .then_some(DecimalIdiom::ToDecimal)
+        }
+        Mnemonic::Aad if instruction.immediate8() == 10 => before
+            .iter()
+            .any(|previous| removes_ascii_zero(previous))
+            .then_some(DecimalIdiom::FromDecimal),
+        // add al, 90h / daa / adc al, 40h / daa
+        Mnemonic::Daa => {
+            let opens = index
+                .checked_sub(1)
+                .is_some_and(|previous| is_al_op(code[previous], Mnemonic::Add, 0x90));
+            let closes = after
+                .first()
+                .is_some_and(|next| is_al_op(next, Mnemonic::Adc, 0x40))
+                && after
+                    .get(1)
+                    .is_some_and(|next| next.mnemonic() == Mnemonic::Daa);
+            (opens && closes).then_some(DecimalIdiom::ToHexDigit)
+        }
+        // cmp al, 10 / sbb al, 69h / das
+        Mnemonic::Das => index
+            .checked_sub(1)
+            .is_some_and(|previous| is_al_op(code[previous], Mnemonic::Sbb, 0x69))
+            .then_some(DecimalIdiom::ToHexDigit),
+        _ => None,
+    }
+}
+
+/// Finds routines that convert numbers to and from decimal or hex text
+///
+/// Each conversion is attributed to the function it is in, and the function
+/// counts as printing if it also writes characters or strings with int 21h
+/// or int 29h before it returns.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::decimal::{find_conversions, DecimalIdiom};
+/// use disassembler::disassemble::Disassembler;
+///
+/// // aam / add ax, 0x3030 / ret
+/// let data = vec![0xD4, 0x0A, 0x05, 0x30, 0x30, 0xC3];
+/// let conversions = find_conversions(&Disassembler::new(data));
+/// assert_eq!(conversions[0].idiom, DecimalIdiom::ToDecimal);
+/// ```
+pub fn find_conversions(disassembler: &Disassembler) -> Vec<Conversion> {
+    let code: Vec<&Instruction> = disassembler
+        .instructions
+        .0
+        .iter()
+        .filter(|instruction| !is_data(instruction))
+        .collect();
+    let mut functions: Vec<Address> = disassembler
+        .labels
+        .0
+        .iter()
+        .filter(|label| label.label_type == LabelType::FUNCTION)
+        .map(|label| label.address)
+        .collect();
+    functions.sort_unstable();
+    functions.dedup();
+
+    // the instructions from the start of a function to its first return
+    let body = |start: Address| {
+        let first = code.partition_point(|instruction| instruction.ip() < start as u64);
+        let length = code[first..]
+            .iter()
+            .position(|instruction| instruction.flow_control() == FlowControl::Return)
+            .map_or(code.len() - first, |ret| ret + 1);
+        &code[first..first + length]
+    };
+
+    let mut found = Vec::new();
+    for index in 0..code.len() {
+        let Some(idiom) = idiom_at(&code, index) else {
+            continue;
+        };
+        let address = code[index].ip() as Address;
+        let function = functions
+            .iter()
+            .rev()
+            .find(|&&start| start <= address)
+            .copied()
+            .filter(|&start| {
+                body(start)
+                    .last()
+                    .is_some_and(|last| last.ip() >= address as u64)
+            });
+        let prints = function.is_some_and(|start| {
+            body(start).iter().any(|instruction| {
+                let ip = instruction.ip() as Address;
+                let output = disassembler
+                    .syscall_list
+                    .get_by_address(ip)
+                    .is_some_and(|syscall| {
+                        matches!(
+                            syscall.number,
+                            SyscallType::CharacterOutput | SyscallType::DisplayString
+                        )
+                    });
+                output
+                    || (instruction.mnemonic() == Mnemonic::Int && instruction.immediate8() == 0x29)
+            })
+        });
+        found.push(Conversion {
+            address,
+            idiom,
+            function,
+            prints,
+        });
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Idioms
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn division_by_ten_in_a_printing_function_is_print_number() {
+        let data = vec![
+            0xE8, 0x01, 0x00, // call 0x104
+            0xC3, // ret
+            0xBB, 0x0A, 0x00, // 0x104: mov bx, 10
+            0x31, 0xD2, // xor dx, dx
+            0xF7, 0xF3, // div bx
+            0x80, 0xC2, 0x30, // add dl, 0x30
+            0xB4, 0x02, // mov ah, 2
+            0xCD, 0x21, // int 21h
+            0xC3, // ret
+        ];
+        let disassembler = Disassembler::new(data);
+        let conversions = find_conversions(&disassembler);
+        assert_eq!(
+            conversions,
+            vec![Conversion {
+                address: 0x109,
+                idiom: DecimalIdiom::ToDecimal,
+                function: Some(0x104),
+                prints: true,
+            }]
+        );
+        assert_eq!(
+            disassembler.labels.get_by_address(0x104).unwrap().name,
+            "print_number_0x0104"
+        );
+    }
+
+    #[test]
+    fn hex_digit_tricks_are_recognised() {
+        // add al, 0x90 / daa / adc al, 0x40 / daa / ret
+        let daa = vec![0x04, 0x90, 0x27, 0x14, 0x40, 0x27, 0xC3];
+        // cmp al, 10 / sbb al, 0x69 / das / ret
+        let das = vec![0x3C, 0x0A, 0x1C, 0x69, 0x2F, 0xC3];
+        for (data, address) in [(daa, 0x102), (das, 0x104)] {
+            let conversions = find_conversions(&Disassembler::new(data));
+            assert_eq!(conversions.len(), 1);
+            assert_eq!(conversions[0].address, address);
+            assert_eq!(conversions[0].idiom, DecimalIdiom::ToHexDigit);
+            assert_eq!(conversions[0].function, None);
+        }
+    }
+
+    #[test]
+    fn aam_without_ascii_is_just_arithmetic() {
+        // aam / ret, and aad after taking '0' off both digits
+        assert!(find_conversions(&Disassembler::new(vec![0xD4, 0x0A, 0xC3])).is_empty());
+        let parse = vec![0x2D, 0x30, 0x30, 0xD5, 0x0A, 0xC3];
+        let conversions = find_conversions(&Disassembler::new(parse));
+        assert_eq!(conversions[0].idiom, DecimalIdiom::FromDecimal);
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt::{self, Display};
+
+use crate::consts::COM_OFFSET;
+use crate::disassemble::Disassembler;
+
+/// The size below which a file can hardly be a program: `mov ah,0x4c` alone
+/// takes two bytes and `int 0x21` two more, though `ret` and `int 0x20`
+/// exit in fewer
+pub const MIN_PROGRAM: usize = 3;
+
+/// Why a file has little or no code to list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Degenerate {
+    /// The file has no bytes at all
+    Empty,
+    /// The file is shorter than [`MIN_PROGRAM`]
+    Short {
+        /// The size of the file
+        size: usize,
+    },
+    /// Nothing valid decodes at the entry point, so the whole file is
+    /// listed as data
+    DataOnly {
+        /// The size of the file
+        size: usize,
+    },
+}
+
+impl Display for Degenerate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Degenerate::Empty => write!(f, "the file is empty, so there is nothing to disassemble"),
+            Degenerate::Short { size } => write!(
+                f,
+                "the file is only {size} byte{}, too short for a program that does more than exit",
+                if *size == 1 { "" } else { "s" }
+            ),
+            Degenerate::DataOnly { size } => write!(
+                f,
+                "no valid instruction at 0x{COM_OFFSET:04x}, where a COM program starts, so all {size} bytes are listed as data"
+            ),
+        }
+    }
+}
+
+/// Tells whether the program is empty, too short to do much, or data with
+/// no code at its entry point
+///
+/// An empty file gets a listing with only its header, and a file whose
+/// first bytes do not decode is listed as `db` throughout, as execution
+/// could not get anywhere else. Marking code by hand decodes it anyway.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::degenerate::{Degenerate, find_degenerate};
+/// use disassembler::disassemble::Disassembler;
+///
+/// assert_eq!(find_degenerate(&Disassembler::new(Vec::new())), Some(Degenerate::Empty));
+/// // ret
+/// assert_eq!(
+///     find_degenerate(&Disassembler::new(vec![0xC3])),
+///     Some(Degenerate::Short { size: 1 })
+/// );
+/// // mov ah, 0x4c / int 0x21
+/// assert_eq!(find_degenerate(&Disassembler::new(vec![0xB4, 0x4C, 0xCD, 0x21])), None);
+/// ```
+pub fn find_degenerate(disassembler: &Disassembler) -> Option<Degenerate> {
+    let size = disassembler.data.len();
+    if size == 0 {
+        Some(Degenerate::Empty)
+    } else if disassembler.is_data_only() {
+        Some(Degenerate::DataOnly { size })
+    } else if size < MIN_PROGRAM {
+        Some(Degenerate::Short { size })
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassemble::is_data;
+
+    #[test]
+    fn data_at_the_entry_point_is_listed_as_data() {
+        let disassembler = Disassembler::new(vec![0xFF, 0xFF, 0xFF, 0xFF, 0xC3]);
+        assert_eq!(
+            find_degenerate(&disassembler),
+            Some(Degenerate::DataOnly { size: 5 })
+        );
+        assert!(disassembler.instructions.0.iter().all(is_data));
+
+        let listing = disassembler.to_string();
+        assert!(
+            listing.contains("; no valid instruction at 0x0100"),
+            "{listing}"
+        );
+        assert!(listing.contains("db 0xff,0xff,0xff,0xff,0xc3"), "{listing}");
+    }
+
+    #[test]
+    fn empty_files_still_explain_themselves() {
+        let listing = Disassembler::new(Vec::new()).to_string();
+        assert!(listing.contains("; the file is empty"), "{listing}");
+    }
+
+    #[test]
+    fn marked_code_is_decoded_anyway() {
+        let mut disassembler = Disassembler::new(vec![0xFF, 0xFF, 0xC3]);
+        disassembler.mark_code(0x102);
+        disassembler.reanalyze();
+        assert!(!disassembler.is_data_only());
+        assert_eq!(disassembler.instructions.0.last().unwrap().ip(), 0x102);
+    }
+}
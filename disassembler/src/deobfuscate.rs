@@ -0,0 +1,371 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{ConditionCode, FlowControl, Instruction, Mnemonic, OpKind};
+
+use crate::comment::{Comment, CommentSource, CommentType};
+use crate::consts::{Address, COM_OFFSET};
+use crate::disassemble::{Disassembler, is_data};
+
+/// How many times the idioms are searched for again after applying fixes
+const MAX_ROUNDS: usize = 16;
+
+/// The longest run of bytes after a jump that is treated as junk
+const MAX_JUNK: u64 = 16;
+
+/// A rewrite the normalizer applied to undo an obfuscation idiom
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Transformation {
+    /// A branch into the middle of a decoded instruction; decoding now starts at the target
+    OverlappingJump {
+        /// The address of the branch
+        branch: Address,
+        /// The address it jumps to
+        target: Address,
+    },
+    /// A conditional jump whose outcome is fixed by the instruction before it
+    OpaquePredicate {
+        /// The address of the conditional jump
+        address: Address,
+        /// Whether the jump is always taken, rather than never
+        always_taken: bool,
+    },
+    /// Bytes after an unconditional or always-taken jump that never run, now shown as data
+    JunkBytes {
+        /// The first junk byte
+        start: Address,
+        /// The address just past the junk
+        end: Address,
+    },
+}
+
+impl Display for Transformation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Transformation::OverlappingJump { branch, target } => write!(
+                f,
+                "branch at 0x{branch:04x} jumps into the middle of an instruction at 0x{target:04x}"
+            ),
+            Transformation::OpaquePredicate {
+                address,
+                always_taken,
+            } => write!(
+                f,
+                "opaque predicate at 0x{address:04x}: always {}",
+                if always_taken {
+                    "taken"
+                } else {
+                    "falls through"
+                }
+            ),
+            Transformation::JunkBytes { start, end } => {
+                write!(f, "junk bytes 0x{start:04x}-0x{end:04x} are never executed")
+            }
+        }
+    }
+}
+
+/// The result of normalizing a program
+#[derive(Debug, Clone)]
+pub struct Normalized {
+    /// The cleaned analysis, with each transformation applied and commented
+    pub disassembler: Disassembler,
+    /// Every transformation applied, in the order they were found
+    pub transformations: Vec<Transformation>,
+}
+
+/// Flag values known to hold after an instruction
+#[derive(Debug, Clone, Copy, Default)]
+struct KnownFlags {
+    cf: Option<bool>,
+    zf: Option<bool>,
+    sf: Option<bool>,
+    of: Option<bool>,
+    pf: Option<bool>,
+}
+
+impl KnownFlags {
+    /// The flags `instruction` sets to a fixed value whatever its inputs
+    fn after(instruction: &Instruction) -> KnownFlags {
+        let same_registers = instruction.op_count() == 2
+            && instruction.op0_kind() == OpKind::Register
+            && instruction.op1_kind() == OpKind::Register
+            && instruction.op0_register() == instruction.op1_register();
+        match instruction.mnemonic() {
+            // x ^ x, x - x and comparing x with x all give zero
+            Mnemonic::Xor | Mnemonic::Sub | Mnemonic::Cmp if same_registers => KnownFlags {
+                cf: Some(false),
+                zf: Some(true),
+                sf: Some(false),
+                of: Some(false),
+                pf: Some(true),
+            },
+            Mnemonic::Stc => KnownFlags {
+                cf: Some(true),
+                ..KnownFlags::default()
+            },
+            Mnemonic::Clc => KnownFlags {
+                cf: Some(false),
+                ..KnownFlags::default()
+            },
+            _ => KnownFlags::default(),
+        }
+    }
+
+    /// Whether a jump with `condition` is taken, if the known flags decide it
+    fn taken(&self, condition: ConditionCode) -> Option<bool> {
+        let signed_less = || Some(self.sf? != self.of?);
+        match condition {
+            ConditionCode::o => self.of,
+            ConditionCode::no => self.of.map(|of| !of),
+            ConditionCode::b => self.cf,
+            ConditionCode::ae => self.cf.map(|cf| !cf),
+            ConditionCode::e => self.zf,
+            ConditionCode::ne => self.zf.map(|zf| !zf),
+            ConditionCode::be => Some(self.cf? || self.zf?),
+            ConditionCode::a => Some(!self.cf? && !self.zf?),
+            ConditionCode::s => self.sf,
+            ConditionCode::ns => self.sf.map(|sf| !sf),
+            ConditionCode::p => self.pf,
+            ConditionCode::np => self.pf.map(|pf| !pf),
+            ConditionCode::l => signed_less(),
+            ConditionCode::ge => signed_less().map(|less| !less),
+            ConditionCode::le => Some(self.zf? || signed_less()?),
+            ConditionCode::g => Some(!self.zf? && !signed_less()?),
+            ConditionCode::None => None,
+        }
+    }
+}
+
+/// The target of `instruction` if it is a near branch into the program
+fn branch_target(disassembler: &Disassembler, instruction: &Instruction) -> Option<Address> {
+    let is_branch = matches!(
+        instruction.flow_control(),
+        FlowControl::UnconditionalBranch | FlowControl::ConditionalBranch | FlowControl::Call
+    ) && matches!(instruction.op0_kind(), OpKind::NearBranch16);
+    let target = instruction.near_branch_target();
+    let end = COM_OFFSET as u64 + disassembler.data.len() as u64;
+    (is_branch && (COM_OFFSET as u64..end).contains(&target)).then_some(target as Address)
+}
+
+/// Finds the idioms in the current analysis, skipping those already applied
+fn find(disassembler: &Disassembler, applied: &[Transformation]) -> Vec<Transformation> {
+    let instructions = &disassembler.instructions.0;
+    let mut found = Vec::new();
+    let push = |transformation: Transformation, found: &mut Vec<Transformation>| {
+        if !applied.contains(&transformation) && !found.contains(&transformation) {
+            found.push(transformation);
+        }
+    };
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        let Some(target) = branch_target(disassembler, instruction) else {
+            continue;
+        };
+
+        let containing = instructions
+            .partition_point(|other| other.ip() <= target as u64)
+            .checked_sub(1)
+            .map(|index| &instructions[index]);
+        if let Some(containing) = containing
+            && containing.ip() < target as u64
+            && (target as u64) < containing.next_ip()
+            && !is_data(containing)
+        {
+            push(
+                Transformation::OverlappingJump {
+                    branch: instruction.ip() as Address,
+                    target,
+                },
+                &mut found,
+            );
+        }
+
+        let mut skips_to_target = instruction.flow_control() == FlowControl::UnconditionalBranch;
+        if instruction.flow_control() == FlowControl::ConditionalBranch
+            && let Some(previous) = index.checked_sub(1).map(|index| &instructions[index])
+            && let Some(taken) = KnownFlags::after(previous).taken(instruction.condition_code())
+        {
+            push(
+                Transformation::OpaquePredicate {
+                    address: instruction.ip() as Address,
+                    always_taken: taken,
+                },
+                &mut found,
+            );
+            skips_to_target = taken;
+        }
+
+        // short forward hops over bytes that still decode as code
+        let gap = instruction.next_ip()..target as u64;
+        let junk_decodes_as_code = instructions
+            .iter()
+            .filter(|other| gap.contains(&other.ip()))
+            .any(|other| !is_data(other));
+        let referenced = disassembler
+            .labels
+            .0
+            .iter()
+            .any(|label| gap.contains(&(label.address as u64)));
+        if skips_to_target
+            && !gap.is_empty()
+            && gap.end - gap.start <= MAX_JUNK
+            && junk_decodes_as_code
+            && !referenced
+        {
+            push(
+                Transformation::JunkBytes {
+                    start: gap.start as Address,
+                    end: target,
+                },
+                &mut found,
+            );
+        }
+    }
+    found
+}
+
+/// Undoes common obfuscation idioms, returning a cleaned copy of the analysis
+///
+/// Branches into the middle of instructions make decoding start at the
+/// target, conditional jumps fixed by the instruction before them (such as
+/// `xor ax, ax` / `jz`) are identified, and bytes skipped by unconditional or
+/// always-taken jumps are shown as data. Fixes are applied with
+/// [`Disassembler::mark_code`] and [`Disassembler::mark_data`] and the search
+/// repeats until nothing new is found. Each transformation is commented in
+/// the cleaned listing.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::deobfuscate::{normalize, Transformation};
+/// use disassembler::disassemble::Disassembler;
+///
+/// // xor ax, ax / jz +1 / db 0xE8 (swallows the ret as a call) / ret
+/// let disassembler = Disassembler::new(vec![0x31, 0xC0, 0x74, 0x01, 0xE8, 0xC3]);
+/// let normalized = normalize(&disassembler);
+/// assert!(normalized.transformations.contains(&Transformation::JunkBytes {
+///     start: 0x104,
+///     end: 0x105,
+/// }));
+/// ```
+pub fn normalize(disassembler: &Disassembler) -> Normalized {
+    let mut cleaned = disassembler.clone();
+    let mut transformations = Vec::new();
+    for _ in 0..MAX_ROUNDS {
+        let found = find(&cleaned, &transformations);
+        if found.is_empty() {
+            break;
+        }
+        for transformation in &found {
+            match *transformation {
+                Transformation::OverlappingJump { target, .. } => cleaned.mark_code(target),
+                Transformation::JunkBytes { start, end } => {
+                    cleaned.mark_data(start, end);
+                    cleaned.mark_code(end);
+                }
+                Transformation::OpaquePredicate { .. } => {}
+            }
+        }
+        cleaned.reanalyze();
+        transformations.extend(found);
+    }
+
+    for transformation in &transformations {
+        let address = match *transformation {
+            Transformation::OverlappingJump { branch, .. } => branch,
+            Transformation::OpaquePredicate { address, .. } => address,
+            Transformation::JunkBytes { start, .. } => start,
+        };
+        cleaned.comment_list.0.push(
+            Comment::new(CommentType::PRE, transformation.to_string(), address)
+                .with_source(CommentSource::analysis("deobfuscate")),
+        );
+    }
+
+    Normalized {
+        disassembler: cleaned,
+        transformations,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Opaque predicates
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn known_flags_decide_conditions() {
+        let zero = KnownFlags::after(&Disassembler::new(vec![0x31, 0xC0]).instructions.0[0]);
+        assert_eq!(zero.taken(ConditionCode::e), Some(true));
+        assert_eq!(zero.taken(ConditionCode::a), Some(false));
+        assert_eq!(zero.taken(ConditionCode::le), Some(true));
+
+        let carry = KnownFlags::after(&Disassembler::new(vec![0xF9]).instructions.0[0]);
+        assert_eq!(carry.taken(ConditionCode::b), Some(true));
+        assert_eq!(carry.taken(ConditionCode::be), Some(true));
+        assert_eq!(carry.taken(ConditionCode::e), None);
+    }
+
+    #[test]
+    fn never_taken_predicate_keeps_the_fall_through() {
+        // xor ax, ax / jnz +1 / nop / ret
+        let normalized = normalize(&Disassembler::new(vec![0x31, 0xC0, 0x75, 0x01, 0x90, 0xC3]));
+        assert_eq!(
+            normalized.transformations,
+            vec![Transformation::OpaquePredicate {
+                address: 0x102,
+                always_taken: false
+            }]
+        );
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Overlapping jumps and junk bytes
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn jump_into_an_instruction_resynchronises() {
+        // jmp short 0x103 / mov ax, 0x21cd hides int 21h / ret
+        let data = vec![0xEB, 0x01, 0xB8, 0xCD, 0x21, 0xC3];
+        let normalized = normalize(&Disassembler::new(data));
+        assert!(
+            normalized
+                .transformations
+                .contains(&Transformation::OverlappingJump {
+                    branch: 0x100,
+                    target: 0x103
+                })
+        );
+
+        let cleaned = &normalized.disassembler;
+        let int = cleaned
+            .instructions
+            .0
+            .iter()
+            .find(|instruction| instruction.ip() == 0x103)
+            .expect("decoding starts at the jump target");
+        assert_eq!(int.mnemonic(), Mnemonic::Int);
+        assert!(
+            cleaned
+                .comment_list
+                .get_comments(0x100)
+                .iter()
+                .any(|comment| comment.comment_text.contains("middle of an instruction"))
+        );
+    }
+
+    #[test]
+    fn jumps_over_referenced_bytes_are_left_alone() {
+        // jmp short 0x104 / call 0x102 target inside the gap / ret
+        let data = vec![0xEB, 0x02, 0x90, 0xC3, 0xE8, 0xFB, 0xFF, 0xC3];
+        let normalized = normalize(&Disassembler::new(data));
+        assert!(
+            !normalized
+                .transformations
+                .iter()
+                .any(|transformation| matches!(transformation, Transformation::JunkBytes { .. }))
+        );
+    }
+}
@@ -1,17 +1,60 @@
-use crate::comment::{Comment, CommentList, CommentType};
-use crate::consts::{Address, COM_OFFSET, SIZE};
+use crate::annotations::{
+    ANNOTATIONS_VERSION, AnnotationError, Annotations, CommentEntry, DataRange, Symbol,
+};
+use crate::bss::{Reservation, find_reservations, reserve};
+use crate::comment::{Comment, CommentList, CommentSource, CommentType};
+use crate::constants::ConstantTable;
+use crate::consts::{Address, AddressExt, COM_OFFSET, SIZE};
+use crate::country::find_country_calls;
+use crate::decimal::find_conversions;
+use crate::degenerate::find_degenerate;
+#[cfg(feature = "emulator")]
+use crate::emulate::Trace;
+use crate::equates::{EquateKind, equates, undefined_labels};
+use crate::errors::{find_error_branches, find_error_routines};
+use crate::flow::ControlFlowGraph;
+use crate::freespace::{FreeKind, FreeSpace};
+use crate::handlers::{HandlerProblem, find_handlers};
+use crate::keyboard::find_key_compares;
 use crate::label::{Label, LabelList, LabelType};
+use crate::limits::{Deadline, LimitHit, LimitKind, Limits};
+use crate::managers::MemoryManagers;
+use crate::memory::MemoryMap;
+use crate::mouse::find_mouse_calls;
+use crate::network::find_network_calls;
+use crate::overrides::find_size_overrides;
+use crate::padding::Padding;
+use crate::progress::{Progress, Reporter};
+use crate::query::{AddressInfo, AddressKind};
+#[cfg(feature = "signatures")]
+use crate::region::NamedRegion;
+use crate::region::RegionList;
+use crate::routines::find_routines;
+use crate::rules::{RuleMatch, RuleSet};
+use crate::session::AuxFile;
+#[cfg(feature = "signatures")]
+use crate::signatures::{Detection, SignaturePack};
+use crate::stack::{INITIAL_SP, StackModel};
 use crate::string::{StringConstant, StringConstantList};
+use crate::style::Style;
+use crate::syntax::{SyntaxIssue, check_syntax};
 use crate::syscall::{Syscall, SyscallList, SyscallType};
+use crate::table::{ElementSize, Table};
+use crate::timer::find_timer_rates;
+use crate::version::find_version_checks;
+#[cfg(feature = "emulator")]
+use crate::watch::WatchReport;
+use crate::wraparound::find_wraparounds;
 use iced_x86::{
-    Decoder, DecoderOptions, Encoder, Formatter, Instruction, Mnemonic, NasmFormatter, OpKind,
-    Register,
+    Decoder, DecoderOptions, Formatter, Instruction, Mnemonic, NasmFormatter, OpKind, Register,
+    SymbolResolver, SymbolResult,
 };
-use std::collections::hash_map;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{self, Display};
 use std::io::{self, Cursor, Write};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A wrapper type around Vec<Instruction> for implementing Display
 pub struct InstructionList(pub Vec<Instruction>);
 
@@ -24,23 +67,70 @@ impl InstructionList {
     pub fn new() -> Self {
         InstructionList(Vec::new())
     }
+
+    /// The index of the instruction starting at `address`
+    ///
+    /// Instructions are kept in address order with their start and length,
+    /// so this is a binary search rather than a decode.
+    pub fn index_at(&self, address: Address) -> Option<usize> {
+        self.0
+            .binary_search_by_key(&(address as u64), Instruction::ip)
+            .ok()
+    }
+
+    /// The instruction whose bytes include `address`, wherever it starts
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    ///
+    /// // mov ax, 0x4c00 / int 21h
+    /// let disassembler = Disassembler::new(vec![0xB8, 0x00, 0x4C, 0xCD, 0x21]);
+    /// let instructions = &disassembler.instructions;
+    /// assert_eq!(instructions.containing(0x102).map(|i| i.ip()), Some(0x100));
+    /// assert_eq!(instructions.index_at(0x103), Some(1));
+    /// assert_eq!(instructions.index_at(0x102), None);
+    /// ```
+    pub fn containing(&self, address: Address) -> Option<&Instruction> {
+        let index = self
+            .0
+            .partition_point(|instruction| instruction.ip() <= address as u64);
+        index
+            .checked_sub(1)
+            .map(|index| &self.0[index])
+            .filter(|instruction| (address as u64) < instruction.next_ip())
+    }
 }
 
 impl Display for InstructionList {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for instruction in self.0.iter() {
-            write!(f, "{}\n", instruction)?;
+            writeln!(f, "{}", instruction)?;
         }
         Ok(())
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A struct for disassembling a binary file
 ///
 /// This struct contains a list of labels, instructions, and other relevant data
 /// for disassembling a binary file.
 /// It provides methods for disassembling the binary data and formatting the output.
+///
+/// After every analysis the labels, comments and string constants are sorted
+/// by address, so the listing and anything serialized from it come out the
+/// same for the same input, whatever order the passes found things in. See
+/// [`LabelList::sort`], [`CommentList::sort`] and [`StringConstantList::sort`]
+/// for how ties are broken.
+///
+/// The analysis itself is deterministic too: no pass draws random numbers or
+/// walks a hash map, so there is nothing to seed, and the same bytes, trace
+/// and [`Limits`] always give the same result. The one exception is
+/// [`Limits::max_time`], where how far the analysis gets depends on the
+/// machine.
 pub struct Disassembler {
     /// A list of labels in the disassembled code
     pub labels: LabelList,
@@ -50,15 +140,56 @@ pub struct Disassembler {
     pub data: Vec<u8>,
     /// A list of syscalls in the disassembled code
     pub syscall_list: SyscallList,
-    /// A hashmap to track register values
-    pub register_tracker: hash_map::HashMap<Register, u16>,
+    /// The values registers are known to hold, ordered by register
+    pub register_tracker: BTreeMap<Register, u16>,
     /// a list of comments in the disassembled code
     pub comment_list: CommentList,
     /// A list of string constants in the disassembled code
     pub string_constant_list: StringConstantList,
+    /// Named regions, shown as banners in the listing and kept across reanalysis
+    pub regions: RegionList,
+    /// Addresses executed in an emulator trace, each starting an instruction
+    executed: BTreeSet<Address>,
+    /// Labels for jump and call targets seen in an emulator trace
+    observed_labels: Vec<Label>,
+    /// Regions marked as data by the user, from start to end
+    data_marks: BTreeMap<Address, Address>,
+    /// Data regions shown as words or doublewords, from start to end
+    sized_marks: BTreeMap<Address, (Address, ElementSize)>,
+    /// Addresses marked as the start of code by the user
+    code_marks: BTreeSet<Address>,
+    /// Label names chosen by the user
+    label_names: BTreeMap<Address, String>,
+    /// The values explained wherever they appear as immediates
+    constants: ConstantTable,
+    /// The range edited since the last analysis, if any
+    dirty: Option<(Address, Address)>,
+    /// Byte pattern rule matches annotated in the listing
+    rule_matches: Vec<RuleMatch>,
+    /// Instructions seen using watched buffers in an emulator run, by the
+    /// buffer's start and end
+    dynamic_references: BTreeMap<(Address, Address), BTreeSet<Address>>,
+    /// Tables listed as arrays
+    tables: Vec<Table>,
+    /// Companion files mapped into the address space
+    aux_files: Vec<AuxFile>,
+    /// How numbers, addresses and generated label names are written
+    style: Style,
+    /// Memory past the end of the file the program uses
+    reservations: Vec<Reservation>,
+    /// Caps on how much work the analysis may do
+    limits: Limits,
+    /// The limit that stopped the last sweep, if one did
+    decode_limit: Option<LimitHit>,
+    /// The limits that cut the last analysis pass short
+    analysis_limits: Vec<LimitHit>,
+    /// Told how far each analysis has got
+    #[cfg_attr(feature = "serde", serde(skip))]
+    progress: Reporter,
 }
 
 /// Options for the disassembler
+#[derive(Debug, Clone, Copy)]
 pub struct DisassemblerOptions {
     /// Whether to write labels
     pub write_labels: bool,
@@ -72,6 +203,24 @@ pub struct DisassemblerOptions {
     pub write_bytes: bool,
     /// Whether to write misc comments
     pub misc_comments: bool,
+    /// Whether to prefix comments with where they came from, e.g. `; [strings] ...`
+    pub comment_sources: bool,
+    /// Whether to note where jumps into chains of jumps end up, e.g.
+    /// `; → ultimately 0x0312 (print_msg)`
+    pub jump_chains: bool,
+    /// Whether to write the filler bytes in front of labels as `align` or
+    /// `times` directives, which assemble to the same bytes, rather than as
+    /// `nop` instructions and data
+    pub padding_directives: bool,
+    /// Whether the names from [`equates`] are defined in front of the
+    /// listing, usually in an include file: addresses the listing has no line
+    /// for and immediates with a known meaning are written by name, and
+    /// memory past the end of the file is left to the include file
+    pub equates: bool,
+    /// Whether to write the instructions NASM would assemble to other bytes,
+    /// as found by [`check_syntax`], as `db` with their bytes and the
+    /// instruction in a comment
+    pub exact_encodings: bool,
 }
 
 impl Default for DisassemblerOptions {
@@ -83,6 +232,11 @@ impl Default for DisassemblerOptions {
             syscall_comments: false,
             write_bytes: false,
             misc_comments: true,
+            comment_sources: false,
+            jump_chains: false,
+            padding_directives: true,
+            equates: false,
+            exact_encodings: false,
         }
     }
 }
@@ -107,104 +261,1834 @@ impl Disassembler {
     /// let disassembler = Disassembler::new(data);
     /// ```
     pub fn new(data: Vec<u8>) -> Self {
+        Self::with_limits(data, Limits::default())
+    }
+
+    /// Creates a disassembler whose analysis stops early at `limits`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::limits::{LimitKind, Limits};
+    ///
+    /// let limits = Limits { max_instructions: Some(2), ..Limits::default() };
+    /// // nop / nop / nop / ret
+    /// let disassembler = Disassembler::with_limits(vec![0x90, 0x90, 0x90, 0xC3], limits);
+    /// assert_eq!(disassembler.limits_hit()[0].kind, LimitKind::Instructions);
+    /// assert_eq!(disassembler.limits_hit()[0].address, Some(0x102));
+    /// ```
+    pub fn with_limits(data: Vec<u8>, limits: Limits) -> Self {
+        Self::with_progress(data, limits, Reporter::default())
+    }
+
+    /// Creates a disassembler whose analysis stops early at `limits` and
+    /// reports how far it has got to `progress`, here and whenever the
+    /// program is analysed again
+    ///
+    /// See [`Reporter`] for an example.
+    pub fn with_progress(data: Vec<u8>, limits: Limits, progress: Reporter) -> Self {
+        Self::build(data, BTreeSet::new(), Vec::new(), limits, progress)
+    }
+
+    /// Analyses `data` with what an emulator trace saw and the given limits
+    fn build(
+        data: Vec<u8>,
+        executed: BTreeSet<Address>,
+        observed_labels: Vec<Label>,
+        limits: Limits,
+        progress: Reporter,
+    ) -> Self {
         let mut disassembler = Disassembler {
             labels: LabelList::new(),
             instructions: InstructionList::new(),
             data,
             syscall_list: SyscallList::new(),
-            register_tracker: hash_map::HashMap::new(),
+            register_tracker: BTreeMap::new(),
             comment_list: CommentList::new(),
             string_constant_list: StringConstantList::new(),
+            regions: RegionList::new(),
+            executed,
+            observed_labels,
+            data_marks: BTreeMap::new(),
+            sized_marks: BTreeMap::new(),
+            code_marks: BTreeSet::new(),
+            label_names: BTreeMap::new(),
+            constants: ConstantTable::builtin(),
+            dirty: None,
+            rule_matches: Vec::new(),
+            dynamic_references: BTreeMap::new(),
+            tables: Vec::new(),
+            aux_files: Vec::new(),
+            style: Style::default(),
+            reservations: Vec::new(),
+            limits,
+            decode_limit: None,
+            analysis_limits: Vec::new(),
+            progress,
         };
         disassembler.disassemble();
-        disassembler.search_labels();
 
-        disassembler
+        disassembler
+    }
+
+    fn find_string_constant(&mut self, address: Address) {
+        let mut out = String::new();
+        // a string stops at the end of the segment, so its end address fits
+        let room = usize::from(Address::MAX - address);
+        for &byte in self.bytes_at(address).unwrap_or_default().iter().take(room) {
+            if byte == 0x24 {
+                out.push('$');
+                break;
+            } else if byte == 0x00 {
+                break;
+            }
+            out.push(byte as char);
+        }
+
+        if !out.is_empty() {
+            let string_constant = StringConstant {
+                start: address,
+                end: address.saturating_add_len(out.chars().count()),
+                value: out,
+            };
+            self.string_constant_list.0.push(string_constant);
+        }
+    }
+
+    /// Maps a companion file into the address space and analyses the program again
+    ///
+    /// Strings and jump targets the program expects at those addresses then
+    /// resolve to the file's contents. Bytes of the program itself take
+    /// precedence, and a file mapped later takes precedence over earlier ones.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::session::AuxFile;
+    ///
+    /// // mov dx, 0x2000 / mov ah, 9 / int 21h
+    /// let mut disassembler = Disassembler::new(vec![0xBA, 0x00, 0x20, 0xB4, 0x09, 0xCD, 0x21]);
+    /// assert!(disassembler.string_constant_list.0.is_empty());
+    ///
+    /// disassembler.map_file(AuxFile {
+    ///     name: "TEXT.BIN".to_string(),
+    ///     address: 0x2000,
+    ///     data: b"Hello$".to_vec(),
+    /// });
+    /// assert_eq!(disassembler.string_constant_list.0[0].value, "Hello$");
+    /// ```
+    pub fn map_file(&mut self, file: AuxFile) {
+        self.aux_files.push(file);
+        self.rebuild();
+    }
+
+    /// The companion files mapped into the address space, in mapping order
+    pub fn aux_files(&self) -> &[AuxFile] {
+        &self.aux_files
+    }
+
+    /// The bytes from `address` to the end of the program or mapped file containing it
+    pub fn bytes_at(&self, address: Address) -> Option<&[u8]> {
+        let program = address
+            .file_offset()
+            .and_then(|offset| self.data.get(offset..))
+            .filter(|bytes| !bytes.is_empty());
+        program.or_else(|| {
+            self.aux_files
+                .iter()
+                .rev()
+                .find_map(|file| file.bytes_at(address))
+        })
+    }
+
+    /// Where the bytes of `instruction` are in the program file, if they are
+    /// in it rather than in a mapped companion file
+    pub fn file_range(&self, instruction: &Instruction) -> Option<std::ops::Range<usize>> {
+        let start = (instruction.ip() as Address).file_offset()?;
+        let end = start + instruction.len();
+        (end <= self.data.len()).then_some(start..end)
+    }
+
+    /// The bytes `instruction` was decoded from, as they are in the program
+    /// or a mapped companion file
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    ///
+    /// // nop / jmp short 0x100
+    /// let disassembler = Disassembler::new(vec![0x90, 0xEB, 0xFD]);
+    /// let jump = &disassembler.instructions.0[1];
+    /// assert_eq!(disassembler.original_bytes(jump), Some(&[0xEB, 0xFD][..]));
+    /// assert_eq!(disassembler.file_range(jump), Some(1..3));
+    /// ```
+    pub fn original_bytes(&self, instruction: &Instruction) -> Option<&[u8]> {
+        self.bytes_at(instruction.ip() as Address)?
+            .get(..instruction.len())
+    }
+
+    /// Reads the zero-terminated string at `address`, if it is inside the program
+    pub fn read_asciiz(&self, address: Address) -> Option<String> {
+        let bytes = self.bytes_at(address)?;
+        let end = bytes.iter().position(|&byte| byte == 0)?;
+        Some(bytes[..end].iter().map(|&byte| byte as char).collect())
+    }
+
+    fn create_syscall_comments(&mut self, syscall: &Syscall) {
+        let s_type = syscall.number;
+        if s_type == SyscallType::DisplayString
+            && let Some(address) = self.register_tracker.get(&Register::DX).copied()
+        {
+            self.find_string_constant(address);
+            let language = self
+                .string_constant_list
+                .0
+                .iter()
+                .find(|string| string.start == address)
+                .and_then(StringConstant::language);
+            let comment = Comment {
+                comment_type: CommentType::PRE,
+                comment_text: match language {
+                    Some(language) => format!("Start of string data ({language})"),
+                    None => "Start of string data".to_string(),
+                },
+                address,
+                source: CommentSource::analysis("strings"),
+            };
+            self.comment_list.0.push(comment);
+        }
+    }
+
+    /// Creates a disassembler that uses an emulator trace of the program to tell code from data
+    ///
+    /// Every executed address starts an instruction: bytes that a linear sweep
+    /// would decode as an instruction running into executed code are emitted as
+    /// `db` data instead. Targets of indirect jumps and calls seen at runtime
+    /// become labels.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::emulate::Emulator;
+    ///
+    /// // jmp short 0x104 / db 0xB8, 0x00 / ret
+    /// let data = vec![0xEB, 0x02, 0xB8, 0x00, 0xC3];
+    /// let mut emulator = Emulator::new(&data);
+    /// emulator.run(100);
+    ///
+    /// let disassembler = Disassembler::with_trace(data, &emulator.trace);
+    /// assert_eq!(disassembler.instructions.0.len(), 3);
+    /// ```
+    #[cfg(feature = "emulator")]
+    pub fn with_trace(data: Vec<u8>, trace: &Trace) -> Self {
+        Self::with_trace_and_limits(data, trace, Limits::default())
+    }
+
+    /// Creates a disassembler from an emulator trace whose analysis stops
+    /// early at `limits`, see [`Disassembler::with_trace`]
+    #[cfg(feature = "emulator")]
+    pub fn with_trace_and_limits(data: Vec<u8>, trace: &Trace, limits: Limits) -> Self {
+        let observed_labels = trace
+            .jump_targets
+            .iter()
+            .map(|&address| (address, LabelType::LABEL))
+            .chain(
+                trace
+                    .call_targets
+                    .iter()
+                    .map(|&address| (address, LabelType::FUNCTION)),
+            )
+            .map(|(address, label_type)| Label {
+                address,
+                label_type,
+                name: generated_name(&Style::default(), label_type, address),
+            })
+            .collect();
+        Self::build(
+            data,
+            trace.executed.clone(),
+            observed_labels,
+            limits,
+            Reporter::default(),
+        )
+    }
+
+    /// Decodes the instruction starting at `address`, independent of the instruction list
+    ///
+    /// Returns `None` if `address` is outside the program and any mapped
+    /// file, or the bytes there are not a complete, valid instruction.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use iced_x86::Mnemonic;
+    ///
+    /// // mov ax, 0x21cd - or, one byte in, int 0x21
+    /// let disassembler = Disassembler::new(vec![0xB8, 0xCD, 0x21]);
+    /// let instruction = disassembler.decode_at(0x101).unwrap();
+    /// assert_eq!(instruction.mnemonic(), Mnemonic::Int);
+    /// ```
+    pub fn decode_at(&self, address: Address) -> Option<Instruction> {
+        let bytes = self.bytes_at(address)?;
+        let mut decoder = Decoder::with_ip(SIZE, bytes, address as u64, DecoderOptions::NONE);
+        let instruction = decoder.decode();
+        (!instruction.is_invalid()).then_some(instruction)
+    }
+
+    /// Decodes the instructions from `start` up to `end` with a linear sweep
+    ///
+    /// Decoding starts at `start` whatever the instruction list says is there,
+    /// so a frontend can reinterpret bytes the analysis classified as data.
+    /// Bytes that do not decode are returned as one byte `db` instructions.
+    /// The last instruction may extend past `end`.
+    pub fn decode_range(&self, start: Address, end: Address) -> Vec<Instruction> {
+        let mut instructions = Vec::new();
+        let mut address = start;
+        while address < end
+            && let Some(bytes) = self.bytes_at(address)
+        {
+            let instruction = self.decode_at(address).or_else(|| {
+                let mut instruction = Instruction::with_declare_byte(&bytes[..1]).ok()?;
+                instruction.set_len(1);
+                instruction.set_ip(address as u64);
+                Some(instruction)
+            });
+            let Some(instruction) = instruction else {
+                break;
+            };
+            let Ok(next) = Address::try_from(instruction.next_ip()) else {
+                instructions.push(instruction);
+                break;
+            };
+            instructions.push(instruction);
+            address = next;
+        }
+        instructions
+    }
+
+    /// The addresses of the instructions that refer to `address`
+    ///
+    /// An instruction refers to an address it uses as an immediate, such as
+    /// `mov dx, 0x10c` loading a string, as a memory displacement or as a
+    /// near branch target.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    ///
+    /// // mov dx, 0x106 / mov ah, 9 / int 21h / db "hi$"
+    /// let data = vec![0xBA, 0x06, 0x01, 0xB4, 0x09, 0xCD, 0x21, b'h', b'i', b'$'];
+    /// let disassembler = Disassembler::new(data);
+    /// assert_eq!(disassembler.references(0x106), vec![0x100]);
+    /// ```
+    pub fn references(&self, address: Address) -> Vec<Address> {
+        self.code()
+            .filter(|instruction| operand_addresses(instruction).any(|used| used == address as u64))
+            .map(|instruction| instruction.ip() as Address)
+            .collect()
+    }
+
+    /// The addresses of the instructions an emulator run saw reading or
+    /// writing a watched buffer containing `address`, added with
+    /// [`Disassembler::add_dynamic_references`]
+    ///
+    /// These catch accesses through pointers and indexes, which static
+    /// [`Disassembler::references`] cannot see.
+    pub fn dynamic_references(&self, address: Address) -> Vec<Address> {
+        self.dynamic_references
+            .iter()
+            .filter(|((start, end), _)| (*start..*end).contains(&address))
+            .flat_map(|(_, references)| references)
+            .copied()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Adds the instructions that used each buffer in `report` as dynamic
+    /// references to it, see [`Disassembler::dynamic_references`]
+    #[cfg(feature = "emulator")]
+    pub fn add_dynamic_references(&mut self, report: &WatchReport) {
+        for buffer in &report.0 {
+            self.dynamic_references
+                .entry((buffer.start, buffer.end))
+                .or_default()
+                .extend(buffer.reads.keys().chain(buffer.writes.keys()));
+        }
+    }
+
+    /// Every address an instruction refers to, see [`Disassembler::references`]
+    pub fn referenced_addresses(&self) -> BTreeSet<Address> {
+        self.code()
+            .flat_map(operand_addresses)
+            .filter_map(|address| Address::try_from(address).ok())
+            .collect()
+    }
+
+    /// Describes what is at `address`: what kind of byte it is, the item
+    /// containing it, and that item's label, comments and references
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::query::AddressKind;
+    ///
+    /// // call 0x104 / ret / ret
+    /// let disassembler = Disassembler::new(vec![0xE8, 0x01, 0x00, 0xC3, 0xC3]);
+    /// let info = disassembler.query(0x104);
+    /// assert_eq!(info.kind, AddressKind::Code);
+    /// assert_eq!(info.label.unwrap().name, "FUNC_0x0104");
+    /// assert_eq!(info.references, vec![0x100]);
+    /// assert_eq!(disassembler.query(0x101).start, 0x100);
+    /// ```
+    pub fn query(&self, address: Address) -> AddressInfo {
+        let string = self
+            .string_constant_list
+            .0
+            .iter()
+            .find(|string| string.start <= address && address < string.end)
+            .cloned();
+        let instruction = self.instructions.containing(address).copied();
+        let reservation = self
+            .reservations
+            .iter()
+            .find(|reservation| {
+                reservation.start <= address && (address as u32) < reservation.end()
+            })
+            .copied();
+        let padding = instruction.is_some()
+            && FreeSpace::analyze(self).0.iter().any(|region| {
+                region.kind == FreeKind::Padding
+                    && region.start <= address
+                    && (address as u32) < region.end
+            });
+
+        let kind = match (&string, instruction) {
+            (Some(_), _) => AddressKind::String,
+            (None, Some(_)) if padding => AddressKind::Padding,
+            (None, Some(instruction)) if is_data(&instruction) => AddressKind::Data,
+            (None, Some(_)) => AddressKind::Code,
+            (None, None) if reservation.is_some() => AddressKind::Uninitialized,
+            (None, None) => AddressKind::Unmapped,
+        };
+        let start = match (&string, instruction, reservation) {
+            (Some(string), _, _) => string.start,
+            (None, Some(instruction), _) => instruction.ip() as Address,
+            (None, None, Some(reservation)) => reservation.start,
+            (None, None, None) => address,
+        };
+        AddressInfo {
+            address,
+            kind,
+            start,
+            instruction,
+            string,
+            reservation,
+            label: self.labels.get_by_address(start).cloned(),
+            comments: self
+                .comment_list
+                .get_comments(start)
+                .into_iter()
+                .cloned()
+                .collect(),
+            references: self.references(start),
+            dynamic_references: self.dynamic_references(address),
+        }
+    }
+
+    /// The instructions outside string constants, whose bytes are
+    /// decoded too but never run
+    pub(crate) fn code(&self) -> impl Iterator<Item = &Instruction> {
+        self.instructions.0.iter().filter(|instruction| {
+            let address = instruction.ip();
+            !self
+                .string_constant_list
+                .0
+                .iter()
+                .any(|string| string.start as u64 <= address && address < string.end as u64)
+        })
+    }
+
+    /// Marks `start..end` as data
+    ///
+    /// Code marks inside the region are dropped and overlapping regions are
+    /// merged. The change takes effect on the next call to
+    /// [`Disassembler::reanalyze`].
+    pub fn mark_data(&mut self, start: Address, end: Address) {
+        if start >= end {
+            return;
+        }
+        let (mut start, mut end) = (start, end);
+        // merge with any region the new one overlaps; adjacent regions stay
+        // apart so each starts its own row of data
+        let overlapping: Vec<Address> = self
+            .data_marks
+            .range(..end)
+            .filter(|&(_, &mark_end)| mark_end > start)
+            .map(|(&mark_start, _)| mark_start)
+            .collect();
+        for mark_start in overlapping {
+            let mark_end = self.data_marks.remove(&mark_start).unwrap_or(mark_start);
+            start = start.min(mark_start);
+            end = end.max(mark_end);
+        }
+        self.data_marks.insert(start, end);
+        self.code_marks
+            .retain(|&address| address < start || address >= end);
+        self.touch(start, end);
+    }
+
+    /// Marks `start..end` as data shown as `dw` words
+    ///
+    /// Like [`Disassembler::mark_data`], but the region is listed as words
+    /// counted from `start`; a trailing odd byte is shown as `db`.
+    pub fn mark_words(&mut self, start: Address, end: Address) {
+        self.mark_elements(start, end, ElementSize::Word);
+    }
+
+    /// Marks `start..end` as data shown as `dd` doublewords
+    ///
+    /// Like [`Disassembler::mark_words`], with bytes left over at the end
+    /// shown as `db`.
+    pub fn mark_dwords(&mut self, start: Address, end: Address) {
+        self.mark_elements(start, end, ElementSize::Dword);
+    }
+
+    /// Marks `start..end` as data listed in elements of `element`, replacing
+    /// the element size of any region it overlaps
+    pub fn mark_elements(&mut self, start: Address, end: Address, element: ElementSize) {
+        if start >= end {
+            return;
+        }
+        self.sized_marks
+            .retain(|&mark_start, &mut (mark_end, _)| mark_end <= start || mark_start >= end);
+        if element != ElementSize::Byte {
+            self.sized_marks.insert(start, (end, element));
+        }
+        self.mark_data(start, end);
+    }
+
+    /// Marks `address` as the start of an instruction
+    ///
+    /// A data region containing `address` ends there. The change takes effect
+    /// on the next call to [`Disassembler::reanalyze`].
+    pub fn mark_code(&mut self, address: Address) {
+        let mut end = address.saturating_add(1);
+        if let Some((&mark_start, &mark_end)) = self.data_marks.range(..=address).next_back()
+            && mark_end > address
+        {
+            self.data_marks.remove(&mark_start);
+            if mark_start < address {
+                self.data_marks.insert(mark_start, address);
+            }
+            end = mark_end;
+        }
+        if let Some((&mark_start, &(mark_end, element))) =
+            self.sized_marks.range(..=address).next_back()
+            && mark_end > address
+        {
+            self.sized_marks.remove(&mark_start);
+            if mark_start < address {
+                self.sized_marks.insert(mark_start, (address, element));
+            }
+        }
+        self.code_marks.insert(address);
+        self.touch(address, end);
+    }
+
+    /// Names the label at `address`, creating one if there is none
+    ///
+    /// The name survives reanalysis, even if the label is rediscovered.
+    pub fn rename_label(&mut self, address: Address, name: impl Into<String>) {
+        self.label_names.insert(address, name.into());
+        self.apply_label_name(address);
+    }
+
+    /// The labels, comments and marks the user made, in the interchange
+    /// format described at [`Annotations`]
+    ///
+    /// Every label is listed as a symbol, with the names the analysis made
+    /// up marked as generated.
+    pub fn annotations(&self) -> Annotations {
+        let mut data = Vec::new();
+        for (&start, &end) in &self.data_marks {
+            let mut cursor = start;
+            for (&sized_start, &(sized_end, element)) in self.sized_marks.range(start..end) {
+                if sized_start > cursor {
+                    data.push(DataRange::new(cursor, sized_start, ElementSize::Byte));
+                }
+                data.push(DataRange::new(sized_start, sized_end, element));
+                cursor = sized_end;
+            }
+            if cursor < end {
+                data.push(DataRange::new(cursor, end, ElementSize::Byte));
+            }
+        }
+        Annotations {
+            version: ANNOTATIONS_VERSION,
+            symbols: self
+                .labels
+                .0
+                .iter()
+                .map(|label| Symbol {
+                    address: label.address,
+                    name: label.name.clone(),
+                    generated: !self.label_names.contains_key(&label.address),
+                })
+                .collect(),
+            comments: self
+                .comment_list
+                .0
+                .iter()
+                .filter(|comment| !comment.source.is_analysis())
+                .map(|comment| CommentEntry {
+                    address: comment.address,
+                    placement: comment.comment_type,
+                    text: comment.comment_text.clone(),
+                    source: comment.source.clone(),
+                })
+                .collect(),
+            data,
+            code: self.code_marks.iter().copied().collect(),
+            regions: self.regions.0.clone(),
+        }
+    }
+
+    /// Adds `annotations` to what the user has said about the program and
+    /// analyses it again
+    ///
+    /// Symbols the analysis generated are skipped, and comments already
+    /// present are not added twice. Nothing is imported if the annotations
+    /// do not pass [`Annotations::validate`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    ///
+    /// // call 0x104 / ret / ret
+    /// let program = vec![0xE8, 0x01, 0x00, 0xC3, 0xC3];
+    /// let mut original = Disassembler::new(program.clone());
+    /// original.rename_label(0x104, "helper");
+    /// original.mark_data(0x103, 0x104);
+    /// original.reanalyze();
+    ///
+    /// let mut copy = Disassembler::new(program);
+    /// copy.import_annotations(&original.annotations()).unwrap();
+    /// assert_eq!(copy.to_string(), original.to_string());
+    /// ```
+    pub fn import_annotations(&mut self, annotations: &Annotations) -> Result<(), AnnotationError> {
+        annotations.validate()?;
+        for range in &annotations.data {
+            self.mark_elements(range.start, range.end, range.element());
+        }
+        for &address in &annotations.code {
+            self.mark_code(address);
+        }
+        for region in &annotations.regions {
+            self.regions.insert(region.clone());
+        }
+        for entry in &annotations.comments {
+            let comment = Comment::new(entry.placement, entry.text.clone(), entry.address)
+                .with_source(entry.source.clone());
+            if !self.comment_list.0.contains(&comment) {
+                self.comment_list.0.push(comment);
+            }
+        }
+        self.comment_list.sort();
+        self.reanalyze();
+        for symbol in annotations
+            .symbols
+            .iter()
+            .filter(|symbol| !symbol.generated)
+        {
+            self.rename_label(symbol.address, symbol.name.clone());
+        }
+        Ok(())
+    }
+
+    /// Scans the program with `rules` and annotates every match
+    ///
+    /// Each match gets a comment above its first byte naming the rule and the
+    /// matched range, and the rule's label if it has one. Annotations survive
+    /// reanalysis; labels chosen by the user take precedence. Returns the
+    /// matches in address order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::rules::RuleSet;
+    ///
+    /// let rules = RuleSet::parse("rule exit {\n bytes = B4 4C CD 21\n label = quit\n}").unwrap();
+    /// let mut disassembler = Disassembler::new(vec![0x90, 0xB4, 0x4C, 0xCD, 0x21]);
+    /// let matches = disassembler.apply_rules(&rules);
+    ///
+    /// assert_eq!(matches.len(), 1);
+    /// assert_eq!(disassembler.labels.get_by_address(0x101).unwrap().name, "quit");
+    /// ```
+    pub fn apply_rules(&mut self, rules: &RuleSet) -> Vec<RuleMatch> {
+        let matches = rules.scan(&self.data);
+        self.annotate_rule_matches(&matches);
+        self.rule_matches.extend(matches.iter().cloned());
+        let named: Vec<Address> = self.label_names.keys().copied().collect();
+        for address in named {
+            self.apply_label_name(address);
+        }
+        matches
+    }
+
+    /// Scans the program for the viruses in `pack` and names the region each
+    /// one takes up, such as `Vienna virus (appended)`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::signatures::SignaturePack;
+    ///
+    /// let pack = SignaturePack::parse("virus Demo {\n bytes = EB FE\n infection = prepend\n length = 2\n}").unwrap();
+    /// let mut disassembler = Disassembler::new(vec![0xEB, 0xFE, 0xC3]);
+    /// disassembler.apply_signatures(&pack);
+    ///
+    /// assert_eq!(disassembler.regions.0[0].name, "Demo virus (prepended)");
+    /// ```
+    #[cfg(feature = "signatures")]
+    pub fn apply_signatures(&mut self, pack: &SignaturePack) -> Vec<Detection> {
+        let detections = pack.scan(&self.data);
+        for detection in &detections {
+            self.regions.insert(NamedRegion {
+                name: format!("{} virus ({})", detection.virus, detection.infection),
+                start: detection.start,
+                end: detection.end,
+            });
+        }
+        detections
+    }
+
+    /// Lists `tables` as `db`, `dw` or `dd` arrays
+    ///
+    /// Each table is marked as data, labelled with a name giving its element
+    /// size, such as `word_table_0x0200`, and commented with its length.
+    /// Labels and comments are kept across reanalysis.
+    pub fn mark_tables(&mut self, tables: &[Table]) {
+        for table in tables {
+            self.mark_elements(table.start, table.end(), table.element);
+            self.tables.retain(|other| other.start != table.start);
+            self.tables.push(table.clone());
+        }
+        self.reanalyze();
+    }
+
+    /// Comments instructions that move the stack, and the memory resize
+    /// before them if the program sets up its own stack
+    fn annotate_stack(&mut self) {
+        let model = StackModel::analyze(&self.instructions.0, &self.syscall_list);
+        for stack_move in &model.moves {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::PRE,
+                comment_text: stack_move.to_string(),
+                address: stack_move.address,
+                source: CommentSource::analysis("stack"),
+            });
+        }
+        if model.has_own_stack() {
+            for &address in &model.resizes {
+                self.comment_list.0.push(Comment {
+                    comment_type: CommentType::PRE,
+                    comment_text: "shrinks the program's memory to set up its own stack"
+                        .to_string(),
+                    address,
+                    source: CommentSource::analysis("stack"),
+                });
+            }
+        }
+    }
+
+    /// Comments memory calls with what they ask for and the shifts
+    /// converting their sizes between bytes and paragraphs
+    fn annotate_memory(&mut self) {
+        let map = MemoryMap::analyze(self);
+        let comments = map
+            .calls
+            .iter()
+            .map(|call| (call.address, call.to_string()))
+            .chain(
+                map.conversions
+                    .iter()
+                    .map(|conversion| (conversion.address, conversion.to_string())),
+            );
+        for (address, comment_text) in comments {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::INLINE,
+                comment_text,
+                address,
+                source: CommentSource::analysis("memory"),
+            });
+        }
+    }
+
+    /// Adds the comments and labels of the marked tables to the listing
+    fn annotate_tables(&mut self) {
+        for table in &self.tables {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::PRE,
+                comment_text: table.to_string(),
+                address: table.start,
+                source: CommentSource::analysis("tables"),
+            });
+            match self
+                .labels
+                .0
+                .iter_mut()
+                .find(|label| label.address == table.start)
+            {
+                Some(label) => label.name = table.label_name(&self.style),
+                None => self.labels.0.push(Label {
+                    address: table.start,
+                    label_type: LabelType::LABEL,
+                    name: table.label_name(&self.style),
+                }),
+            }
+        }
+    }
+
+    /// Labels the memory past the end of the file the program uses, unless
+    /// a mapped file covers it
+    fn annotate_reservations(&mut self) {
+        self.reservations =
+            find_reservations(&self.instructions.0, &self.syscall_list, self.data.len())
+                .into_iter()
+                .filter(|reservation| {
+                    !self
+                        .aux_files
+                        .iter()
+                        .any(|aux| aux.contains(reservation.start))
+                })
+                .collect();
+        for reservation in &self.reservations {
+            if self.labels.get_by_address(reservation.start).is_none() {
+                self.labels.0.push(Label {
+                    address: reservation.start,
+                    label_type: LabelType::DATA,
+                    name: self.style.label_name("bss", reservation.start),
+                });
+            }
+        }
+    }
+
+    /// Comments country and code page calls with what they do, and labels
+    /// the fields of the buffers they fill in, reserving those past the end
+    /// of the file one by one
+    fn annotate_country(&mut self) {
+        let program_end = COM_OFFSET as u32 + self.data.len() as u32;
+        for call in find_country_calls(self) {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::INLINE,
+                comment_text: call.to_string(),
+                address: call.address,
+                source: CommentSource::analysis("country"),
+            });
+            for (address, field) in call.fields() {
+                if address as u32 >= program_end {
+                    reserve(&mut self.reservations, address, field.size as usize);
+                }
+                if self.labels.get_by_address(address).is_some() {
+                    continue;
+                }
+                let name = format!("country_{}", field.name);
+                let name = if self.labels.0.iter().any(|label| label.name == name) {
+                    self.style.label_name(&name, address)
+                } else {
+                    name
+                };
+                self.labels.0.push(Label {
+                    address,
+                    label_type: LabelType::DATA,
+                    name,
+                });
+            }
+        }
+    }
+
+    /// Comments number conversions and names the functions doing them
+    fn annotate_conversions(&mut self) {
+        for conversion in find_conversions(self) {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::INLINE,
+                comment_text: conversion.idiom.to_string(),
+                address: conversion.address,
+                source: CommentSource::analysis("decimal"),
+            });
+            let Some(function) = conversion.function else {
+                continue;
+            };
+            let name = self.style.label_name(conversion.function_name(), function);
+            // the first conversion found names the function
+            if let Some(label) = self.labels.0.iter_mut().find(|label| {
+                label.address == function
+                    && label.name == generated_name(&self.style, LabelType::FUNCTION, function)
+            }) {
+                label.name = name;
+            }
+        }
+    }
+
+    /// Names small helper functions recognised by their shape and comments
+    /// what they do
+    fn annotate_routines(&mut self) {
+        for routine in find_routines(self) {
+            let generated = generated_name(&self.style, LabelType::FUNCTION, routine.address);
+            // a name from an earlier pass, such as print_number, is more specific
+            let Some(label) = self
+                .labels
+                .0
+                .iter_mut()
+                .find(|label| label.address == routine.address && label.name == generated)
+            else {
+                continue;
+            };
+            label.name = self.style.label_name(routine.kind.name(), routine.address);
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::PRE,
+                comment_text: routine.kind.to_string(),
+                address: routine.address,
+                source: CommentSource::analysis("routines"),
+            });
+        }
+    }
+
+    /// Names the interrupt handlers the program installs and comments what
+    /// they do wrong
+    fn annotate_handlers(&mut self) {
+        for handler in find_handlers(self) {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::PRE,
+                comment_text: handler.to_string(),
+                address: handler.address,
+                source: CommentSource::analysis("handlers"),
+            });
+            for problem in &handler.problems {
+                let address = match *problem {
+                    HandlerProblem::Clobbers(_) => handler.address,
+                    HandlerProblem::NearReturn(address)
+                    | HandlerProblem::UncheckedDosCall(address)
+                    | HandlerProblem::Unbalanced { address, .. } => address,
+                };
+                self.comment_list.0.push(Comment {
+                    comment_type: CommentType::PRE,
+                    comment_text: format!("handler {problem}"),
+                    address,
+                    source: CommentSource::analysis("handlers"),
+                });
+            }
+            let prefix = match handler.vector {
+                Some(vector) => format!("int{}_handler", self.style.digits(vector as u64, 2)),
+                None => "int_handler".to_string(),
+            };
+            let name = self.style.label_name(&prefix, handler.address);
+            match self
+                .labels
+                .0
+                .iter_mut()
+                .find(|label| label.address == handler.address)
+            {
+                Some(label) => {
+                    if label.name == generated_name(&self.style, label.label_type, label.address) {
+                        label.name = name;
+                    }
+                }
+                None => self.labels.0.push(Label {
+                    address: handler.address,
+                    label_type: LabelType::FUNCTION,
+                    name,
+                }),
+            }
+        }
+    }
+
+    /// Comments the instructions whose addresses wrap around the segment
+    fn annotate_wraparounds(&mut self) {
+        for finding in find_wraparounds(self) {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::INLINE,
+                comment_text: finding.to_string(),
+                address: finding.address,
+                source: CommentSource::analysis("wraparound"),
+            });
+        }
+    }
+
+    /// Comments comparisons against characters and keys with what they
+    /// stand for
+    fn annotate_keyboard(&mut self) {
+        for compare in find_key_compares(self) {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::INLINE,
+                comment_text: compare.key.to_string(),
+                address: compare.address,
+                source: CommentSource::analysis("keyboard"),
+            });
+        }
+    }
+
+    /// Comments checks of the DOS version with the oldest one they let
+    /// through
+    fn annotate_versions(&mut self) {
+        for check in find_version_checks(self) {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::INLINE,
+                comment_text: check.to_string(),
+                address: check.address,
+                source: CommentSource::analysis("version"),
+            });
+        }
+    }
+
+    /// Comments the calls to the network interrupts with what they ask for
+    fn annotate_network(&mut self) {
+        for found in find_network_calls(self) {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::INLINE,
+                comment_text: found.call.to_string(),
+                address: found.address,
+                source: CommentSource::analysis("network"),
+            });
+        }
+    }
+
+    /// Comments the EMS and XMS calls and labels the variable holding the
+    /// XMS entry point
+    fn annotate_managers(&mut self) {
+        let managers = MemoryManagers::analyze(self);
+        for call in &managers.calls {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::INLINE,
+                comment_text: call.to_string(),
+                address: call.address,
+                source: CommentSource::analysis("managers"),
+            });
+        }
+        let Some(entry) = managers.xms_entry else {
+            return;
+        };
+        if entry as u32 >= COM_OFFSET as u32 + self.data.len() as u32 {
+            reserve(&mut self.reservations, entry, 4);
+        }
+        let name = if self.labels.0.iter().any(|label| label.name == "xms_entry") {
+            self.style.label_name("xms_entry", entry)
+        } else {
+            "xms_entry".to_string()
+        };
+        // a name from the reservations or the label search says nothing
+        let generated = [
+            self.style.label_name("bss", entry),
+            generated_name(&self.style, LabelType::DATA, entry),
+        ];
+        match self
+            .labels
+            .0
+            .iter_mut()
+            .find(|label| label.address == entry)
+        {
+            Some(label) if generated.contains(&label.name) => label.name = name,
+            Some(_) => {}
+            None => self.labels.0.push(Label {
+                address: entry,
+                label_type: LabelType::DATA,
+                name,
+            }),
+        }
+    }
+
+    /// Comments the mouse driver calls and labels the event handlers they
+    /// install, so they are decoded as functions
+    fn annotate_mouse(&mut self) {
+        let mut handlers = Vec::new();
+        for call in find_mouse_calls(self) {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::INLINE,
+                comment_text: call.to_string(),
+                address: call.address,
+                source: CommentSource::analysis("mouse"),
+            });
+            if let Some(handler) = call.handler
+                && !handlers.contains(&handler)
+            {
+                handlers.push(handler);
+            }
+        }
+        for handler in handlers {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::PRE,
+                comment_text: "mouse event handler, called far with the events in ax".to_string(),
+                address: handler,
+                source: CommentSource::analysis("mouse"),
+            });
+            let name = self.style.label_name("mouse_handler", handler);
+            match self
+                .labels
+                .0
+                .iter_mut()
+                .find(|label| label.address == handler)
+            {
+                Some(label) => {
+                    if label.name == generated_name(&self.style, label.label_type, label.address) {
+                        label.name = name;
+                        label.label_type = LabelType::FUNCTION;
+                    }
+                }
+                None => self.labels.0.push(Label {
+                    address: handler,
+                    label_type: LabelType::FUNCTION,
+                    name,
+                }),
+            }
+        }
+    }
+
+    /// Comments the writes reprogramming the timer with the rate int 8 fires
+    /// at and whether the old handler still gets the ticks
+    fn annotate_timer(&mut self) {
+        for rate in find_timer_rates(self) {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::INLINE,
+                comment_text: rate.to_string(),
+                address: rate.address,
+                source: CommentSource::analysis("timer"),
+            });
+        }
+    }
+
+    /// Explains at the top of the listing why an empty, short or data-only
+    /// file has little code
+    fn annotate_degenerate(&mut self) {
+        if let Some(degenerate) = find_degenerate(self) {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::PRE,
+                comment_text: degenerate.to_string(),
+                address: COM_OFFSET,
+                source: CommentSource::analysis("degenerate"),
+            });
+        }
+    }
+
+    /// Comments the instructions made 32-bit by a size prefix
+    fn annotate_size_overrides(&mut self) {
+        for wide in find_size_overrides(self) {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::INLINE,
+                comment_text: wide.to_string(),
+                address: wide.address,
+                source: CommentSource::analysis("overrides"),
+            });
+        }
+    }
+
+    /// Comments the 16-bit immediates with a well-known meaning
+    fn annotate_constants(&mut self) {
+        let mut comments = Vec::new();
+        for instruction in self.code() {
+            for operand in 0..instruction.op_count() {
+                if instruction.op_kind(operand) != OpKind::Immediate16 {
+                    continue;
+                }
+                let value = instruction.immediate16();
+                let meaning = match instruction.mnemonic() {
+                    // elsewhere two letters are more likely a coincidence
+                    Mnemonic::Mov | Mnemonic::Cmp => {
+                        self.constants.describe(value, self.data.len())
+                    }
+                    _ => self.constants.get(value).map(str::to_string),
+                };
+                if let Some(meaning) = meaning {
+                    comments.push(Comment {
+                        comment_type: CommentType::INLINE,
+                        comment_text: meaning,
+                        address: instruction.ip() as Address,
+                        source: CommentSource::analysis("constants"),
+                    });
+                }
+            }
+        }
+        self.comment_list.0.extend(comments);
+    }
+
+    /// The memory past the end of the file the program uses, listed after
+    /// the code with `resb` and `resw` so it keeps its addresses when
+    /// reassembled
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    ///
+    /// // inc word [0x200] / ret
+    /// let disassembler = Disassembler::new(vec![0xFF, 0x06, 0x00, 0x02, 0xC3]);
+    /// assert_eq!(disassembler.reservations()[0].start, 0x200);
+    /// let listing = disassembler.to_string();
+    /// assert!(listing.contains("inc word [bss_0x0200]"));
+    /// assert!(listing.contains("absolute 0x0200\nbss_0x0200: ; data\n    resw 1"));
+    /// ```
+    pub fn reservations(&self) -> &[Reservation] {
+        &self.reservations
+    }
+
+    /// Adds the comments and labels of rule `matches` to the listing
+    fn annotate_rule_matches(&mut self, matches: &[RuleMatch]) {
+        for rule_match in matches {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::PRE,
+                comment_text: rule_match.to_string(),
+                address: rule_match.start,
+                source: CommentSource::analysis("rules"),
+            });
+            let Some(name) = &rule_match.label else {
+                continue;
+            };
+            let mut found = false;
+            for label in self
+                .labels
+                .0
+                .iter_mut()
+                .filter(|l| l.address == rule_match.start)
+            {
+                label.name.clone_from(name);
+                found = true;
+            }
+            if !found {
+                self.labels.0.push(Label {
+                    address: rule_match.start,
+                    label_type: LabelType::LABEL,
+                    name: name.clone(),
+                });
+            }
+        }
+    }
+
+    /// Comments `jc` checks after DOS calls with the errors they catch and
+    /// labels their handlers and routines that print a message and exit
+    fn annotate_error_handling(&mut self) {
+        let branches = find_error_branches(self);
+        let routines = find_error_routines(self);
+        let mut named: Vec<(Address, String)> = branches
+            .iter()
+            .map(|branch| {
+                (
+                    branch.handler,
+                    self.style.label_name("error", branch.handler),
+                )
+            })
+            .collect();
+        named.extend(routines.iter().map(|routine| {
+            (
+                routine.address,
+                self.style.label_name("error_exit", routine.address),
+            )
+        }));
+        for branch in &branches {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::PRE,
+                comment_text: branch.to_string(),
+                address: branch.branch,
+                source: CommentSource::analysis("errors"),
+            });
+        }
+        for routine in &routines {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::PRE,
+                comment_text: routine.to_string(),
+                address: routine.address,
+                source: CommentSource::analysis("errors"),
+            });
+        }
+        // routines come last, so their names win over plain handlers
+        for (address, name) in named {
+            match self
+                .labels
+                .0
+                .iter_mut()
+                .find(|label| label.address == address)
+            {
+                Some(label) => label.name = name,
+                None => self.labels.0.push(Label {
+                    address,
+                    label_type: LabelType::LABEL,
+                    name,
+                }),
+            }
+        }
+    }
+
+    /// Applies the user's name for `address` to the label list
+    fn apply_label_name(&mut self, address: Address) {
+        let Some(name) = self.label_names.get(&address) else {
+            return;
+        };
+        let mut found = false;
+        for label in self.labels.0.iter_mut().filter(|l| l.address == address) {
+            label.name.clone_from(name);
+            found = true;
+        }
+        if !found {
+            self.labels.0.push(Label {
+                address,
+                label_type: LabelType::LABEL,
+                name: name.clone(),
+            });
+        }
+    }
+
+    /// Records that `start..end` must be decoded again
+    fn touch(&mut self, start: Address, end: Address) {
+        self.dirty = Some(match self.dirty {
+            Some((dirty_start, dirty_end)) => (dirty_start.min(start), dirty_end.max(end)),
+            None => (start, end),
+        });
+    }
+
+    /// Brings the analysis up to date with the marks made since it last ran
+    ///
+    /// Only instructions from the one containing the first edited address up
+    /// to where decoding lines up with the previous result again are decoded
+    /// anew. Labels, syscalls, comments and string constants are then rebuilt
+    /// from the instruction list, which needs no decoding.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use iced_x86::Mnemonic;
+    ///
+    /// // jmp short 0x103 / db 0xB8 / ret
+    /// let mut disassembler = Disassembler::new(vec![0xEB, 0x01, 0xB8, 0xC3]);
+    /// disassembler.mark_code(0x103);
+    /// disassembler.reanalyze();
+    ///
+    /// let ret = &disassembler.instructions.0[2];
+    /// assert_eq!((ret.ip(), ret.mnemonic()), (0x103, Mnemonic::Ret));
+    /// ```
+    pub fn reanalyze(&mut self) {
+        let Some((start, end)) = self.dirty.take() else {
+            return;
+        };
+        let old = std::mem::take(&mut self.instructions.0);
+        let first = old
+            .partition_point(|instruction| instruction.ip() <= start as u64)
+            .saturating_sub(1);
+        let from = old
+            .get(first)
+            .map_or(COM_OFFSET, |instruction| instruction.ip() as Address);
+
+        let deadline = Deadline::start(&self.limits);
+        let mut resume = old.len();
+        let (window, limit) = self.sweep(from, deadline, |address| {
+            if address < end {
+                return false;
+            }
+            match old.binary_search_by_key(&(address as u64), Instruction::ip) {
+                Ok(index) => {
+                    resume = index;
+                    true
+                }
+                Err(_) => false,
+            }
+        });
+
+        let mut instructions = old[..first].to_vec();
+        instructions.extend(window);
+        instructions.extend_from_slice(&old[resume..]);
+        self.instructions.0 = instructions;
+        self.decode_limit = limit;
+        self.rebuild_until(deadline);
+    }
+
+    fn disassemble(&mut self) {
+        let deadline = Deadline::start(&self.limits);
+        let (instructions, limit) = self.sweep(COM_OFFSET, deadline, |_| false);
+        self.instructions.0 = instructions;
+        self.decode_limit = limit;
+        self.rebuild_until(deadline);
+    }
+
+    /// Reports how far every later analysis has got to `progress`, such as
+    /// the runs of [`settle`](crate::fixpoint::settle)
+    pub fn set_progress(&mut self, progress: Reporter) {
+        self.progress = progress;
+    }
+
+    /// Where how far an analysis has got is reported
+    pub fn progress(&self) -> &Reporter {
+        &self.progress
+    }
+
+    /// Whether the program is listed as data throughout, because nothing
+    /// valid decodes at its entry point and no code was marked
+    ///
+    /// See [`find_degenerate`].
+    pub fn is_data_only(&self) -> bool {
+        !self.data.is_empty() && self.code_marks.is_empty() && self.decode_at(COM_OFFSET).is_none()
+    }
+
+    /// The caps on how much work the analysis may do
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// The limits that cut the analysis short, empty if it ran to completion
+    pub fn limits_hit(&self) -> Vec<LimitHit> {
+        self.decode_limit
+            .iter()
+            .chain(&self.analysis_limits)
+            .copied()
+            .collect()
+    }
+
+    /// The values explained wherever they appear as immediates
+    pub fn constants(&self) -> &ConstantTable {
+        &self.constants
+    }
+
+    /// Adds `table` to the values explained wherever they appear as
+    /// immediates, replacing the meanings of values already known
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::constants::ConstantTable;
+    /// use disassembler::disassemble::Disassembler;
+    ///
+    /// // mov dx, 0x3f8
+    /// let mut disassembler = Disassembler::new(vec![0xBA, 0xF8, 0x03]);
+    /// disassembler.add_constants(ConstantTable::parse("0x3F8 = COM1 serial port").unwrap());
+    /// assert!(disassembler.to_string().contains("mov dx,0x3f8 ; COM1 serial port"));
+    /// ```
+    pub fn add_constants(&mut self, table: ConstantTable) {
+        self.constants.extend(table);
+        self.rebuild();
+    }
+
+    /// How numbers, addresses and generated label names are written
+    pub fn style(&self) -> &Style {
+        &self.style
+    }
+
+    /// Changes how numbers, addresses and generated label names are written
+    ///
+    /// Generated labels are renamed at once; names the user chose are kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::style::{Case, Style};
+    ///
+    /// // call 0x10b / int 20h
+    /// let mut disassembler = Disassembler::new(vec![0xE8, 0x08, 0x00, 0xCD, 0x20]);
+    /// disassembler.set_style(Style { hex_case: Case::Upper, ..Style::default() });
+    /// assert!(disassembler.to_string().contains("call FUNC_0x010B"));
+    /// ```
+    pub fn set_style(&mut self, style: Style) {
+        self.style = style;
+        self.rebuild();
+    }
+
+    /// Recomputes everything derived from the instruction list
+    fn rebuild(&mut self) {
+        self.rebuild_until(Deadline::start(&self.limits));
     }
 
-    fn find_string_constant(&mut self, address: Address) {
-        let index = (address - COM_OFFSET) as usize;
-        let mut out = String::new();
-        for i in index..self.data.len() {
-            if self.data[i] == 0x24 {
-                out.push('$');
+    /// Recomputes everything derived from the instruction list, skipping
+    /// the remaining passes once `deadline` passes
+    fn rebuild_until(&mut self, deadline: Deadline) {
+        self.analysis_limits.clear();
+        self.register_tracker.clear();
+        self.syscall_list.0.clear();
+        self.comment_list
+            .0
+            .retain(|comment| !comment.source.is_analysis());
+        self.string_constant_list.0.clear();
+        self.labels.0.clear();
+
+        let mut out_of_time = false;
+        let instructions = std::mem::take(&mut self.instructions.0);
+        for instruction in instructions
+            .iter()
+            .filter(|instruction| !is_data(instruction))
+        {
+            if deadline.passed() {
+                out_of_time = true;
                 break;
-            } else if self.data[i] == 0x00 {
+            }
+            self.analyze(instruction);
+        }
+        self.instructions.0 = instructions;
+
+        self.search_labels();
+        for label in &self.observed_labels {
+            if self.labels.get_by_address(label.address).is_none() {
+                self.labels.0.push(Label {
+                    name: generated_name(&self.style, label.label_type, label.address),
+                    ..label.clone()
+                });
+            }
+        }
+        let passes: [fn(&mut Self); 19] = [
+            Self::annotate_error_handling,
+            Self::annotate_conversions,
+            Self::annotate_routines,
+            Self::annotate_handlers,
+            Self::annotate_stack,
+            Self::annotate_memory,
+            Self::annotate_tables,
+            Self::annotate_reservations,
+            Self::annotate_country,
+            Self::annotate_constants,
+            Self::annotate_wraparounds,
+            Self::annotate_size_overrides,
+            Self::annotate_keyboard,
+            Self::annotate_versions,
+            Self::annotate_network,
+            Self::annotate_managers,
+            Self::annotate_mouse,
+            Self::annotate_timer,
+            Self::annotate_degenerate,
+        ];
+        for (done, pass) in passes.iter().enumerate() {
+            out_of_time = out_of_time || deadline.passed();
+            if out_of_time {
                 break;
             }
-            out.push(self.data[i] as char);
+            pass(self);
+            self.progress.report(Progress::Pass {
+                done: done + 1,
+                total: passes.len(),
+            });
+        }
+        if out_of_time {
+            self.analysis_limits.push(LimitHit {
+                kind: LimitKind::Time,
+                address: None,
+            });
         }
+        let rule_matches = std::mem::take(&mut self.rule_matches);
+        self.annotate_rule_matches(&rule_matches);
+        self.rule_matches = rule_matches;
+        let named: Vec<Address> = self.label_names.keys().copied().collect();
+        for address in named {
+            self.apply_label_name(address);
+        }
+        self.labels.sort();
+        self.string_constant_list.sort();
+        self.apply_size_limits();
+        for hit in self.limits_hit() {
+            self.comment_list.0.push(Comment {
+                comment_type: CommentType::PRE,
+                comment_text: hit.to_string(),
+                address: COM_OFFSET,
+                source: CommentSource::analysis("limits"),
+            });
+        }
+        self.comment_list.sort();
+    }
 
-        if out.len() > 0 {
-            let string_constant = StringConstant {
-                start: address,
-                end: address + out.len() as u16,
-                value: out,
+    /// Drops the labels and strings past the limits, keeping the lowest
+    /// addresses and every label the user named
+    fn apply_size_limits(&mut self) {
+        if let Some(max) = self.limits.max_labels
+            && self.labels.0.len() > max
+        {
+            let named = &self.label_names;
+            let mut kept = 0;
+            self.labels.0.retain(|label| {
+                let keep = kept < max || named.contains_key(&label.address);
+                kept += 1;
+                keep
+            });
+            self.analysis_limits.push(LimitHit {
+                kind: LimitKind::Labels,
+                address: None,
+            });
+        }
+        if let Some(max) = self.limits.max_strings
+            && self.string_constant_list.0.len() > max
+        {
+            self.string_constant_list.0.truncate(max);
+            self.analysis_limits.push(LimitHit {
+                kind: LimitKind::Strings,
+                address: None,
+            });
+        }
+    }
+
+    /// Emits `bytes` starting at `address` as `db` pseudo instructions
+    fn push_data(instructions: &mut Vec<Instruction>, bytes: &[u8], address: Address) {
+        let mut address = address;
+        for chunk in bytes.chunks(8) {
+            let Ok(mut instruction) = Instruction::with_declare_byte(chunk) else {
+                continue;
             };
-            self.string_constant_list.0.push(string_constant);
+            instruction.set_len(chunk.len());
+            instruction.set_ip(address as u64);
+            instructions.push(instruction);
+            address = address.wrapping_add(chunk.len() as u16);
         }
     }
 
-    fn create_syscall_comments(&mut self, syscall: &Syscall) {
-        let s_type = syscall.number;
-        if s_type == SyscallType::DisplayString {
-            if let Some(address) = self.register_tracker.get(&Register::DX).copied() {
-                self.find_string_constant(address);
-                let comment = Comment {
-                    comment_type: CommentType::PRE,
-                    comment_text: "Start of string data".to_string(),
-                    address,
-                };
-                self.comment_list.0.push(comment);
+    /// Emits `bytes` starting at `address` as `dw` or `dd` pseudo
+    /// instructions, as `element` says
+    ///
+    /// Bytes left over at the end are emitted as `db`.
+    fn push_elements(
+        instructions: &mut Vec<Instruction>,
+        bytes: &[u8],
+        address: Address,
+        element: ElementSize,
+    ) {
+        let size = element.bytes();
+        let mut address = address;
+        for chunk in bytes.chunks(8) {
+            let whole = chunk.len() - chunk.len() % size;
+            let declared = match element {
+                ElementSize::Byte => Instruction::with_declare_byte(chunk),
+                ElementSize::Word => Instruction::with_declare_word(
+                    &chunk[..whole]
+                        .chunks_exact(2)
+                        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                        .collect::<Vec<u16>>(),
+                ),
+                ElementSize::Dword => Instruction::with_declare_dword(
+                    &chunk[..whole]
+                        .chunks_exact(4)
+                        .map(|quad| u32::from_le_bytes([quad[0], quad[1], quad[2], quad[3]]))
+                        .collect::<Vec<u32>>(),
+                ),
+            };
+            let Ok(mut instruction) = declared else {
+                Self::push_data(instructions, chunk, address);
+                address = address.wrapping_add(chunk.len() as u16);
+                continue;
+            };
+            instruction.set_len(whole);
+            instruction.set_ip(address as u64);
+            instructions.push(instruction);
+            address = address.wrapping_add(whole as u16);
+            if whole < chunk.len() {
+                Self::push_data(instructions, &chunk[whole..], address);
+                address = address.wrapping_add((chunk.len() - whole) as u16);
             }
         }
     }
 
-    fn disassemble(&mut self) {
-        let new_data = self.data.clone();
-        let mut decoder = Decoder::with_ip(SIZE, &new_data, 0x100, DecoderOptions::NONE);
-
-        while decoder.can_decode() {
-            let instruction = decoder.decode();
-            // check if the Ah reg is being set
-            if instruction.mnemonic() == Mnemonic::Mov {
-                let regis = instruction.op0_register();
-                if instruction.op1_kind() == OpKind::Immediate8 {
-                    self.register_tracker
-                        .insert(regis, instruction.immediate8() as u16);
-                } else if instruction.op1_kind() == OpKind::Immediate16 {
-                    self.register_tracker
-                        .insert(regis, instruction.immediate16() as u16);
-                } else if instruction.op1_kind() == OpKind::Register {
-                    if let Some(value) = self.register_tracker.get(&instruction.op1_register()) {
-                        self.register_tracker.insert(regis, *value);
-                    } else {
-                        self.register_tracker.insert(regis, 0);
-                    }
+    /// Emits the marked data `bytes` starting at `address`, as words or
+    /// doublewords where they were marked so
+    fn push_marked(&self, instructions: &mut Vec<Instruction>, bytes: &[u8], address: Address) {
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let at = address.wrapping_add(offset as u16);
+            let remaining = bytes.len() - offset;
+            let sized = self
+                .sized_marks
+                .range(..=at)
+                .next_back()
+                .filter(|&(&start, &(end, element))| {
+                    end > at && ((at - start) as usize).is_multiple_of(element.bytes())
+                })
+                .map(|(_, &(end, element))| (((end - at) as usize).min(remaining), element));
+            let length = match sized {
+                Some((length, element)) => {
+                    Self::push_elements(instructions, &bytes[offset..offset + length], at, element);
+                    length
+                }
+                None => {
+                    let length = self
+                        .sized_marks
+                        .range(at.saturating_add(1)..)
+                        .next()
+                        .map_or(remaining, |(&start, _)| {
+                            ((start - at) as usize).min(remaining)
+                        });
+                    Self::push_data(instructions, &bytes[offset..offset + length], at);
+                    length
                 }
+            };
+            offset += length;
+        }
+    }
+
+    /// Returns true if an instruction must start at `address`
+    fn is_boundary(&self, address: Address) -> bool {
+        self.executed.contains(&address) || self.code_marks.contains(&address)
+    }
+
+    /// Decodes the program with a linear sweep from `start`
+    ///
+    /// Data marks are emitted as data. An instruction that does not start on
+    /// an executed or code-marked address but overlaps one is replaced by data
+    /// so decoding resynchronises on the real code, as is one running into a
+    /// data mark. The sweep ends before the first address `stop` accepts.
+    ///
+    /// Once the instruction limit is reached or `deadline` passes, the rest
+    /// of the program is emitted as data and the limit is returned.
+    fn sweep(
+        &self,
+        start: Address,
+        deadline: Deadline,
+        mut stop: impl FnMut(Address) -> bool,
+    ) -> (Vec<Instruction>, Option<LimitHit>) {
+        let mut instructions = Vec::new();
+        if start == COM_OFFSET && self.is_data_only() {
+            Self::push_data(&mut instructions, &self.data, start);
+            return (instructions, None);
+        }
+        let mut address = start;
+        while let Some(offset) = address
+            .checked_sub(COM_OFFSET)
+            .map(usize::from)
+            .filter(|&offset| offset < self.data.len())
+        {
+            if stop(address) {
+                break;
+            }
+            let kind = if self
+                .limits
+                .max_instructions
+                .is_some_and(|max| instructions.len() >= max)
+            {
+                Some(LimitKind::Instructions)
+            } else if deadline.passed() {
+                Some(LimitKind::Time)
+            } else {
+                None
+            };
+            if let Some(kind) = kind {
+                Self::push_data(&mut instructions, &self.data[offset..], address);
+                let hit = LimitHit {
+                    kind,
+                    address: Some(address),
+                };
+                return (instructions, Some(hit));
             }
+            let remaining = self.data.len() - offset;
 
-            if instruction.mnemonic() == Mnemonic::Int {
-                if instruction.op0_kind() == OpKind::Immediate8 {
-                    if instruction.immediate8() == 0x21 {
-                        let sys_call_type = SyscallType::from_u16(
-                            *self.register_tracker.get(&Register::AH).unwrap_or(&0),
-                        );
-                        if sys_call_type.is_none() {
-                            continue;
-                        }
-                        let syscalltype = sys_call_type.unwrap();
-                        let syscall = Syscall {
-                            number: syscalltype,
-                            address: instruction.ip() as Address,
-                        };
-                        self.create_syscall_comments(&syscall);
-                        self.syscall_list.0.push(syscall);
-                    }
+            if let Some((_, &mark_end)) = self.data_marks.range(..=address).next_back()
+                && mark_end > address
+            {
+                let length = ((mark_end - address) as usize).min(remaining);
+                self.push_marked(
+                    &mut instructions,
+                    &self.data[offset..offset + length],
+                    address,
+                );
+                address = address.wrapping_add(length as u16);
+                continue;
+            }
+
+            let instruction = Decoder::with_ip(
+                SIZE,
+                &self.data[offset..],
+                address as u64,
+                DecoderOptions::NONE,
+            )
+            .decode();
+            let after = address.saturating_add(1);
+            let in_instruction = |next: &&Address| (**next as u64) < instruction.next_ip();
+            let next_data = self.data_marks.range(after..).next().map(|(mark, _)| mark);
+            let next_boundary = [
+                self.executed.range(after..).next(),
+                self.code_marks.range(after..).next(),
+            ]
+            .into_iter()
+            .flatten()
+            .filter(|_| !self.is_boundary(address))
+            .min();
+            if let Some(&next) = [next_data, next_boundary]
+                .into_iter()
+                .flatten()
+                .filter(in_instruction)
+                .min()
+            {
+                let length = (next - address) as usize;
+                Self::push_data(
+                    &mut instructions,
+                    &self.data[offset..offset + length],
+                    address,
+                );
+                address = next;
+                continue;
+            }
+
+            let Ok(next) = Address::try_from(instruction.next_ip()) else {
+                instructions.push(instruction);
+                break;
+            };
+            instructions.push(instruction);
+            address = next;
+            self.progress.report(Progress::Decoded {
+                bytes: (offset + instruction.len()).min(self.data.len()),
+                total: self.data.len(),
+            });
+        }
+        (instructions, None)
+    }
+
+    /// Tracks register values and records syscalls for a decoded instruction
+    fn analyze(&mut self, instruction: &Instruction) {
+        // check if the Ah reg is being set
+        if instruction.mnemonic() == Mnemonic::Mov {
+            let regis = instruction.op0_register();
+            if instruction.op1_kind() == OpKind::Immediate8 {
+                self.register_tracker
+                    .insert(regis, instruction.immediate8() as u16);
+            } else if instruction.op1_kind() == OpKind::Immediate16 {
+                self.register_tracker
+                    .insert(regis, instruction.immediate16());
+            } else if instruction.op1_kind() == OpKind::Register {
+                if let Some(value) = self.register_tracker.get(&instruction.op1_register()) {
+                    self.register_tracker.insert(regis, *value);
+                } else {
+                    self.register_tracker.insert(regis, 0);
                 }
             }
+            // keep the byte halves in step, so `mov ax, 0x4c00` sets AH
+            if let Some((high, low)) = byte_halves(regis)
+                && let Some(value) = self.register_tracker.get(&regis).copied()
+            {
+                self.register_tracker.insert(high, value >> 8);
+                self.register_tracker.insert(low, value & 0xFF);
+            }
+        }
 
-            self.instructions.0.push(instruction.clone());
+        if instruction.mnemonic() == Mnemonic::Int
+            && instruction.op0_kind() == OpKind::Immediate8
+            && instruction.immediate8() == 0x21
+        {
+            let sys_call_type =
+                SyscallType::from_u16(*self.register_tracker.get(&Register::AH).unwrap_or(&0));
+            if sys_call_type.is_none() {
+                return;
+            }
+            let syscalltype = sys_call_type.unwrap();
+            let syscall = Syscall {
+                number: syscalltype,
+                address: instruction.ip() as Address,
+                dx: self.register_tracker.get(&Register::DX).copied(),
+                al: self
+                    .register_tracker
+                    .get(&Register::AL)
+                    .map(|&value| value as u8),
+                di: self.register_tracker.get(&Register::DI).copied(),
+                bx: self.register_tracker.get(&Register::BX).copied(),
+            };
+            self.create_syscall_comments(&syscall);
+            self.syscall_list.0.push(syscall);
         }
     }
 
@@ -215,7 +2099,7 @@ impl Disassembler {
                     let label = Label {
                         address: instruction.near_branch_target() as Address,
                         label_type: LabelType::LABEL,
-                        name: format!("_start"),
+                        name: "_start".to_string(),
                     };
                     self.labels.0.push(label);
 
@@ -223,22 +2107,25 @@ impl Disassembler {
                         comment_type: CommentType::PRE,
                         comment_text: "Start of program".to_string(),
                         address: instruction.near_branch_target() as Address,
+                        source: CommentSource::analysis("labels"),
                     };
 
                     self.comment_list.0.push(comment);
                 } else {
+                    let address = instruction.near_branch_target() as Address;
                     let label = Label {
-                        address: instruction.near_branch_target() as Address,
+                        address,
                         label_type: LabelType::LABEL,
-                        name: format!("LABEL_0x{:04x}", instruction.near_branch_target()),
+                        name: generated_name(&self.style, LabelType::LABEL, address),
                     };
                     self.labels.0.push(label);
                 }
             } else if instruction.is_call_near() {
+                let address = instruction.near_branch_target() as Address;
                 let label = Label {
-                    address: instruction.near_branch_target() as Address,
+                    address,
                     label_type: LabelType::FUNCTION,
-                    name: format!("FUNC_0x{:x}", instruction.near_branch_target()),
+                    name: generated_name(&self.style, LabelType::FUNCTION, address),
                 };
                 self.labels.0.push(label);
             }
@@ -272,39 +2159,127 @@ impl Disassembler {
         f: &mut W,
         opts: DisassemblerOptions,
     ) -> io::Result<()> {
-        let mut formatter = NasmFormatter::new();
+        let style = &self.style;
+        let symbols = if opts.equates {
+            let constants = equates(self)
+                .into_iter()
+                .filter(|equate| equate.kind == EquateKind::Constant)
+                .map(|equate| (equate.value, equate.name))
+                .collect();
+            ListingSymbols {
+                addresses: undefined_labels(self),
+                constants,
+                style: *style,
+            }
+        } else {
+            let addresses = self
+                .reservations
+                .iter()
+                .filter_map(|reservation| {
+                    let label = self.labels.get_by_address(reservation.start)?;
+                    Some((reservation.start, label.name.clone()))
+                })
+                .collect();
+            ListingSymbols {
+                addresses,
+                constants: BTreeMap::new(),
+                style: *style,
+            }
+        };
+        let mut formatter = NasmFormatter::with_options(Some(Box::new(symbols)), None);
         formatter.options_mut().set_digit_separator("'");
-        formatter.options_mut().set_hex_prefix("0x");
-        formatter.options_mut().set_hex_suffix("");
-        formatter
-            .options_mut()
-            .set_number_base(iced_x86::NumberBase::Hexadecimal);
+        style.configure(formatter.options_mut());
+
+        let chains: BTreeMap<Address, Address> = if opts.jump_chains {
+            ControlFlowGraph::build(self)
+                .jump_chains()
+                .into_iter()
+                .map(|chain| (chain.from, chain.destination))
+                .collect()
+        } else {
+            BTreeMap::new()
+        };
 
-        let mut encoder = Encoder::new(SIZE);
+        if opts.misc_comments {
+            self.write_model(f)?;
+            // with no instructions there is nothing to hang comments on
+            if self.instructions.0.is_empty() {
+                for comment in self.comment_list.get_comments(COM_OFFSET) {
+                    writeln!(f, "{comment}")?;
+                }
+            }
+        }
 
         let mut indent = false;
+        let mut regions: Vec<_> = self.regions.0.iter().collect();
+        regions.sort_by_key(|region| region.start);
+        let mut regions = regions.into_iter().peekable();
+        let padding: BTreeMap<Address, Padding> = if opts.padding_directives {
+            Padding::detect(self)
+                .into_iter()
+                .map(|padding| (padding.start, padding))
+                .collect()
+        } else {
+            BTreeMap::new()
+        };
+
+        let substitutes: BTreeMap<Address, SyntaxIssue> = if opts.exact_encodings {
+            check_syntax(self)
+                .into_iter()
+                .map(|issue| (issue.address, issue))
+                .collect()
+        } else {
+            BTreeMap::new()
+        };
+
+        // the end of a string or padding listed as one directive, whose rows
+        // are not listed again
+        let mut listed_until = 0;
         for instruction in &self.instructions.0 {
+            if instruction.ip() < listed_until {
+                continue;
+            }
+            while let Some(region) =
+                regions.next_if(|region| region.start as u64 <= instruction.ip())
+            {
+                if opts.misc_comments {
+                    writeln!(f, "{region}")?;
+                    indent = false;
+                }
+            }
             let string_constant = self
                 .string_constant_list
                 .get_string_constant(instruction.ip() as Address);
+            let text = string_constant.filter(|string| {
+                instruction.ip() as Address == string.start && self.lists_as_text(string)
+            });
+            let filler = padding.get(&(instruction.ip() as Address));
+            let substitute = substitutes.get(&(instruction.ip() as Address));
 
             let label = self.labels.get_by_address(instruction.ip() as Address);
             let comments = self.comment_list.get_comments(instruction.ip() as Address);
+            let render = |comment: &Comment| {
+                if opts.comment_sources {
+                    comment.with_provenance()
+                } else {
+                    comment.to_string()
+                }
+            };
             for comment in comments.clone() {
                 if opts.misc_comments && comment.comment_type == CommentType::PRE {
                     if indent {
                         write!(f, "    ")?;
                     }
-                    write!(f, "{}\n", comment)?;
+                    writeln!(f, "{}", render(comment))?;
                 }
             }
 
-            if let Some(label) = label {
-                if opts.write_labels {
-                    writeln!(f, "{label}")?;
+            if let Some(label) = label
+                && opts.write_labels
+            {
+                writeln!(f, "{label}")?;
 
-                    indent = true;
-                }
+                indent = true;
             }
             if indent && opts.write_indent {
                 write!(f, "    ")?;
@@ -313,22 +2288,42 @@ impl Disassembler {
                 indent = false;
             }
 
-            if let Some(string_constant) = string_constant {
-                if instruction.ip() as Address == string_constant.start {
-                    write!(f, "; {}\n", string_constant.as_db_statement())?
-                }
+            if let Some(string_constant) = string_constant
+                && instruction.ip() as Address == string_constant.start
+                && text.is_none()
+            {
+                writeln!(f, "; {}", string_constant.db_statement(style))?
             }
 
-            if instruction.is_jmp_short() || instruction.is_call_near() {
+            if let Some(string) = text {
+                write!(f, "{}", string.db_statement(style))?;
+                listed_until = string.end as u64;
+            } else if let Some(filler) = filler {
+                write!(f, "{}", filler.directive(style))?;
+                listed_until = filler.end as u64;
+            } else if let Some(issue) = substitute {
+                let bytes: Vec<String> = issue
+                    .bytes
+                    .iter()
+                    .map(|&byte| style.hex(byte as u64, 2))
+                    .collect();
+                write!(
+                    f,
+                    "{} {} ; {}",
+                    style.mnemonic("db"),
+                    bytes.join(","),
+                    issue.nasm
+                )?;
+            } else if instruction.is_jmp_short() || instruction.is_call_near() {
                 let address = self
                     .labels
                     .get_by_address(instruction.near_branch_target() as Address);
 
                 if let Some(label) = address {
                     if instruction.is_jmp_short() {
-                        write!(f, "jmp {} ; label", label.name)?;
+                        write!(f, "{} {} ; label", style.mnemonic("jmp"), label.name)?;
                     } else {
-                        write!(f, "call {} ; function", label.name)?;
+                        write!(f, "{} {} ; function", style.mnemonic("call"), label.name)?;
                     }
                 } else {
                     write!(f, "{}", instruction)?;
@@ -337,7 +2332,7 @@ impl Disassembler {
                 if instruction.op0_kind() == OpKind::Immediate8 {
                     if instruction.immediate8() == 0x21 {
                         let mut temp = String::new();
-                        formatter.format(&instruction, &mut temp);
+                        formatter.format(instruction, &mut temp);
                         if opts.syscall_comments {
                             self.syscall_list
                                 .get_by_address(instruction.ip() as Address)
@@ -349,31 +2344,48 @@ impl Disassembler {
                     }
                 } else {
                     let mut temp = String::new();
-                    formatter.format(&instruction, &mut temp);
+                    formatter.format(instruction, &mut temp);
                     write!(f, "{}", temp)?;
                 }
             } else {
                 let mut temp = String::new();
-                formatter.format(&instruction, &mut temp);
+                formatter.format(instruction, &mut temp);
                 write!(f, "{}", temp)?;
             }
 
             if opts.offset_comments {
-                write!(f, " ; 0x{:04x}", instruction.ip())?;
+                write!(f, " ; {}", style.address(instruction.ip() as Address))?;
             }
 
             if opts.write_bytes {
                 write!(f, " ; bytes: ")?;
-                let _ = encoder.encode(&instruction, 0x100);
-                let bytes = encoder.take_buffer();
-                for byte in bytes.iter() {
-                    write!(f, "{:02x}", byte)?;
+                let bytes = match (text, filler) {
+                    (_, Some(filler)) => filler.bytes(self),
+                    (Some(string), None) => string
+                        .start
+                        .file_offset()
+                        .and_then(|start| {
+                            self.data
+                                .get(start..start + (string.end - string.start) as usize)
+                        })
+                        .unwrap_or_default(),
+                    (None, None) => self.original_bytes(instruction).unwrap_or_default(),
+                };
+                for &byte in bytes {
+                    write!(f, "{}", style.digits(byte as u64, 2))?;
                 }
             }
 
             for comment in comments.clone() {
                 if opts.misc_comments && comment.comment_type == CommentType::INLINE {
-                    write!(f, "{}", comment)?;
+                    write!(f, " {}", render(comment))?;
+                }
+            }
+
+            if let Some(&destination) = chains.get(&(instruction.ip() as Address)) {
+                write!(f, " ; → ultimately {}", style.address(destination))?;
+                if let Some(label) = self.labels.get_by_address(destination) {
+                    write!(f, " ({})", label.name)?;
                 }
             }
 
@@ -387,7 +2399,7 @@ impl Disassembler {
                     if indent {
                         write!(f, "    ")?;
                     }
-                    write!(f, "{}", comment)?;
+                    write!(f, "{}", render(comment))?;
                 }
             }
 
@@ -395,10 +2407,142 @@ impl Disassembler {
                 writeln!(f)?;
             }
         }
+
+        // with equates the reserved memory is named in the include file
+        if let Some(first) = self.reservations.first()
+            && !opts.equates
+        {
+            writeln!(f)?;
+            if opts.misc_comments {
+                writeln!(f, "; uninitialized memory past the end of the file")?;
+            }
+            writeln!(f, "absolute {}", style.address(first.start))?;
+            for reservation in &self.reservations {
+                if let Some(label) = self.labels.get_by_address(reservation.start)
+                    && opts.write_labels
+                {
+                    writeln!(f, "{label}")?;
+                }
+                if opts.write_indent {
+                    write!(f, "    ")?;
+                }
+                writeln!(
+                    f,
+                    "{} {}",
+                    style.mnemonic(reservation.directive()),
+                    reservation.count
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Disassembler {
+    /// Returns true if `string` lies exactly over rows of data with nothing
+    /// named or commented inside it, so the listing can declare it as text
+    /// without losing anything
+    fn lists_as_text(&self, string: &StringConstant) -> bool {
+        let Some(first) = self.instructions.index_at(string.start) else {
+            return false;
+        };
+        let end = string.end as u64;
+        let mut cursor = string.start as u64;
+        for row in &self.instructions.0[first..] {
+            if cursor >= end {
+                break;
+            }
+            if !is_data(row) || row.ip() != cursor {
+                return false;
+            }
+            cursor = row.next_ip();
+        }
+        cursor == end
+            && (string.start.saturating_add(1)..string.end).all(|address| {
+                self.labels.get_by_address(address).is_none()
+                    && self.comment_list.get_comments(address).is_empty()
+            })
+    }
+
+    /// Writes the header describing the machine a COM program starts in
+    fn write_model<W: Write>(&self, f: &mut W) -> io::Result<()> {
+        let style = &self.style;
+        writeln!(
+            f,
+            "; COM program: CS = DS = ES = SS, loaded at {}",
+            style.address(COM_OFFSET)
+        )?;
+        writeln!(
+            f,
+            "; SP = {} on entry, with a word of 0 on top so ret exits through int 20h at {}",
+            style.address(INITIAL_SP),
+            style.address(0)
+        )?;
+        let model = StackModel::analyze(&self.instructions.0, &self.syscall_list);
+        if model.has_own_stack() {
+            let moves: Vec<String> = model
+                .moves
+                .iter()
+                .map(|stack_move| style.address(stack_move.address))
+                .collect();
+            writeln!(
+                f,
+                "; the program resizes its memory with int 21h/4Ah and moves the stack at {}",
+                moves.join(", ")
+            )?;
+        }
         Ok(())
     }
 }
 
+/// Writes references to addresses the listing has no line for, such as
+/// memory past the end of the file, with their labels, immediates with a
+/// known meaning with the constant's name, and other immediates as the
+/// style's radix says
+struct ListingSymbols {
+    addresses: BTreeMap<Address, String>,
+    constants: BTreeMap<u16, String>,
+    style: Style,
+}
+
+impl SymbolResolver for ListingSymbols {
+    fn symbol(
+        &mut self,
+        instruction: &Instruction,
+        _operand: u32,
+        instruction_operand: Option<u32>,
+        address: u64,
+        _address_size: u32,
+    ) -> Option<SymbolResult<'_>> {
+        let operand = instruction_operand?;
+        // immediates are only addresses when they are loaded as pointers
+        let is_pointer = match instruction.op_kind(operand) {
+            OpKind::Memory => true,
+            OpKind::Immediate16 => {
+                matches!(instruction.mnemonic(), Mnemonic::Mov | Mnemonic::Lea)
+                    && matches!(
+                        instruction.op0_register(),
+                        Register::SI | Register::DI | Register::BX | Register::DX
+                    )
+            }
+            _ => false,
+        };
+        let name = Address::try_from(address).ok().and_then(|value| {
+            self.addresses
+                .get(&value)
+                .filter(|_| is_pointer)
+                .or_else(|| self.constants.get(&value))
+        });
+        match name {
+            Some(name) => Some(SymbolResult::with_str(address, name.as_str())),
+            None => Some(SymbolResult::with_string(
+                address,
+                self.style.immediate(instruction, operand)?,
+            )),
+        }
+    }
+}
+
 impl Display for Disassembler {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // Pick whatever defaults you feel are “normal”.
@@ -417,9 +2561,52 @@ impl Display for Disassembler {
     }
 }
 
+/// The name given to a jump or call target found by analysis
+fn generated_name(style: &Style, label_type: LabelType, address: Address) -> String {
+    let prefix = match label_type {
+        LabelType::FUNCTION => "FUNC",
+        LabelType::LABEL | LabelType::DATA => "LABEL",
+    };
+    style.label_name(prefix, address)
+}
+
+/// The high and low byte registers making up `register`, if it has them
+fn byte_halves(register: Register) -> Option<(Register, Register)> {
+    match register {
+        Register::AX => Some((Register::AH, Register::AL)),
+        Register::BX => Some((Register::BH, Register::BL)),
+        Register::CX => Some((Register::CH, Register::CL)),
+        Register::DX => Some((Register::DH, Register::DL)),
+        _ => None,
+    }
+}
+
+/// Returns true if `instruction` is a `db`, `dw` or `dd` row of data rather than code
+pub fn is_data(instruction: &Instruction) -> bool {
+    matches!(
+        instruction.mnemonic(),
+        Mnemonic::Db | Mnemonic::Dw | Mnemonic::Dd
+    )
+}
+
+/// The addresses `instruction` uses as a 16-bit immediate, a memory
+/// displacement or a near branch target
+pub(crate) fn operand_addresses(instruction: &Instruction) -> impl Iterator<Item = u64> + '_ {
+    let is_code = !is_data(instruction);
+    (0..instruction.op_count())
+        .filter(move |_| is_code)
+        .filter_map(|operand| match instruction.op_kind(operand) {
+            OpKind::Immediate16 => Some(instruction.immediate16() as u64),
+            OpKind::Memory => Some(instruction.memory_displacement64()),
+            OpKind::NearBranch16 => Some(instruction.near_branch_target()),
+            _ => None,
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::builder::ComBuilder;
     // use std::io::Write;            // for Cursor
     // use std::io::Cursor;
 
@@ -435,13 +2622,13 @@ mod tests {
     ///  │10A C3       │        ret
     ///  └─────────────┘
     fn sample_program() -> Vec<u8> {
-        vec![
-            0xEB, 0x04, // jmp short START (→0x106)
-            0x90, 0x90, 0x90, 0x90, // padding NOPs
-            0xB4, 0x09, // mov ah, 09h
-            0xCD, 0x21, // int 21h
-            0xC3, // ret
-        ]
+        ComBuilder::new()
+            .jmp_short("start")
+            .bytes(&[0x90; 4]) // padding NOPs
+            .label("start")
+            .dos(0x09)
+            .ret()
+            .build()
     }
 
     fn build_disassembler() -> Disassembler {
@@ -499,6 +2686,43 @@ mod tests {
     // ──────────────────────────────────────────────────────────────────────────
     // 4.  Stream formatting – smoke‑test every option
     // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn listed_bytes_are_the_original_encoding() {
+        let d = Disassembler::new(vec![
+            0x90, // nop
+            0x01, 0xD8, // add ax, bx, also encodable as 03 C3
+            0xEB, 0xFE, // jmp $, whose displacement depends on where it is
+        ]);
+        let opts = DisassemblerOptions {
+            write_bytes: true,
+            ..DisassemblerOptions::default()
+        };
+        let mut buf = Vec::<u8>::new();
+        d.disassemble_stream(&mut buf, opts).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert!(out.contains("; bytes: 01d8"), "{out}");
+        assert!(out.contains("; bytes: ebfe"), "{out}");
+    }
+
+    #[test]
+    fn data_is_declared_with_typed_directives() {
+        let mut d = Disassembler::new(vec![
+            0xBA, 0x08, 0x01, // mov dx, 0x108
+            0xB4, 0x09, // mov ah, 9
+            0xCD, 0x21, // int 21h
+            0xC3, // ret
+            b'H', b'i', b'$', // 0x108
+            0x00, 0x00, 0x00, 0xF0, // 0x10B: far pointer
+        ]);
+        d.mark_data(0x108, 0x10B);
+        d.mark_dwords(0x10B, 0x10F);
+        d.reanalyze();
+        let out = d.to_string();
+        assert!(out.contains("\ndb \"Hi$\"\n"), "{out}");
+        assert!(!out.contains("; db"), "{out}");
+        assert!(out.contains("dd 0xf000'0000"), "{out}");
+    }
+
     #[test]
     fn disassemble_stream_emits_expected_text() {
         let d = build_disassembler();
@@ -509,6 +2733,11 @@ mod tests {
             syscall_comments: true,
             write_bytes: true,
             misc_comments: true,
+            comment_sources: false,
+            jump_chains: false,
+            padding_directives: true,
+            equates: false,
+            exact_encodings: false,
         };
 
         let mut buf = Vec::<u8>::new();
@@ -539,4 +2768,321 @@ mod tests {
             "INT 21h line should contain a semicolon-separated syscall name/value"
         );
     }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 5.  Trace-assisted decoding
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    #[cfg(feature = "emulator")]
+    fn trace_turns_skipped_bytes_into_data() {
+        use crate::emulate::Emulator;
+
+        // jmp over a string whose bytes would swallow the following code
+        let data = vec![
+            0xEB, 0x03, // jmp short 0x105
+            b'H', b'i', 0xB8, // data, 0xB8 = mov ax, imm16
+            0xBB, 0x0C, 0x01, // mov bx, 0x10c
+            0xFF, 0xD3, // call bx
+            0xC3, // ret
+            0x90, // padding
+            0xC3, // 0x10c: ret
+        ];
+        let mut emulator = Emulator::new(&data);
+        emulator.run(100);
+        let d = Disassembler::with_trace(data.clone(), &emulator.trace);
+
+        // 'i' (0x69) decodes as a four byte imul running into the mov at 0x105
+        let data_instruction = d
+            .instructions
+            .0
+            .iter()
+            .find(|i| i.mnemonic() == Mnemonic::Db)
+            .expect("overlapping bytes must become data");
+        assert_eq!(data_instruction.ip(), 0x103);
+        assert_eq!(data_instruction.len(), 2);
+        assert!(
+            d.instructions.0.iter().any(|i| i.ip() == 0x105),
+            "decoding must resynchronise on the executed mov"
+        );
+        let function = d.labels.get_by_address(0x10C).expect("call target label");
+        assert_eq!(function.label_type, LabelType::FUNCTION);
+
+        let linear = Disassembler::new(data);
+        assert!(!linear.instructions.0.iter().any(|i| i.ip() == 0x105));
+    }
+    // ──────────────────────────────────────────────────────────────────────────
+    // 6.  On-demand decoding
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn decode_at_ignores_the_instruction_list() {
+        let d = build_disassembler();
+
+        // 0x101 is the displacement byte of the opening jmp: 04 90 = add al, 0x90
+        let instruction = d.decode_at(0x101).expect("bytes at 0x101 decode");
+        assert_eq!(instruction.mnemonic(), Mnemonic::Add);
+        assert!(d.decode_at(0xFF).is_none(), "below the program");
+        assert!(d.decode_at(0x10B).is_none(), "past the end of the program");
+
+        // int 21h cut short by the end of the data
+        let truncated = Disassembler::new(vec![0xCD]);
+        assert!(truncated.decode_at(0x100).is_none());
+    }
+
+    #[test]
+    fn decode_range_covers_every_byte() {
+        // nop / ret / an int 21h cut short by the end of the data
+        let d = Disassembler::new(vec![0x90, 0xC3, 0xCD]);
+        let instructions = d.decode_range(0x100, 0x200);
+
+        let ips: Vec<u64> = instructions.iter().map(Instruction::ip).collect();
+        assert_eq!(ips, vec![0x100, 0x101, 0x102]);
+        assert_eq!(instructions[1].mnemonic(), Mnemonic::Ret);
+        assert_eq!(instructions[2].mnemonic(), Mnemonic::Db);
+        assert_eq!(d.decode_range(0x101, 0x102).len(), 1);
+        assert_eq!(d.decode_range(0x102, 0x102), Vec::new());
+    }
+    // ──────────────────────────────────────────────────────────────────────────
+    // 7.  User edits and reanalysis
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn reanalysis_matches_a_full_sweep() {
+        let mut d = build_disassembler();
+        d.mark_data(0x102, 0x106);
+        d.reanalyze();
+        assert_eq!(
+            d.instructions.0,
+            d.sweep(COM_OFFSET, Deadline::start(&Limits::default()), |_| false)
+                .0
+        );
+        assert_eq!(d.instructions.0[1].mnemonic(), Mnemonic::Db);
+        assert_eq!(d.instructions.0[2].ip(), 0x106);
+
+        // code inside the data region splits it
+        d.mark_code(0x104);
+        d.reanalyze();
+        assert_eq!(
+            d.instructions.0,
+            d.sweep(COM_OFFSET, Deadline::start(&Limits::default()), |_| false)
+                .0
+        );
+        let ips: Vec<u64> = d.instructions.0.iter().map(Instruction::ip).collect();
+        assert_eq!(ips, vec![0x100, 0x102, 0x104, 0x105, 0x106, 0x108, 0x10A]);
+        assert_eq!(d.syscall_list.0.len(), 1, "syscalls are rebuilt");
+    }
+
+    #[test]
+    fn code_mark_cuts_an_overlapping_instruction() {
+        // mov ax, 0x21cd hides an int 21h one byte in
+        let mut d = Disassembler::new(vec![0xB4, 0x4C, 0xB8, 0xCD, 0x21]);
+        assert!(d.syscall_list.0.is_empty());
+
+        d.mark_code(0x103);
+        d.reanalyze();
+        assert_eq!(d.instructions.0[1].mnemonic(), Mnemonic::Db);
+        assert_eq!(d.instructions.0[2].mnemonic(), Mnemonic::Int);
+        assert_eq!(d.syscall_list.0[0].number, SyscallType::TerminateWithCode);
+    }
+
+    #[test]
+    fn renamed_labels_survive_reanalysis() {
+        let mut d = build_disassembler();
+        d.rename_label(0x106, "main");
+        assert_eq!(d.labels.get_by_address(0x106).unwrap().name, "main");
+
+        d.mark_data(0x102, 0x106);
+        d.reanalyze();
+        assert_eq!(d.labels.get_by_address(0x106).unwrap().name, "main");
+    }
+    // ──────────────────────────────────────────────────────────────────────────
+    // 8.  Named regions
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn regions_render_as_banners() {
+        use crate::region::NamedRegion;
+
+        let mut d = build_disassembler();
+        d.regions.insert(NamedRegion {
+            name: "padding".to_string(),
+            start: 0x102,
+            end: 0x106,
+        });
+        d.mark_data(0x102, 0x106);
+        d.reanalyze();
+
+        let mut buf = Vec::<u8>::new();
+        d.disassemble_stream(&mut buf, DisassemblerOptions::default())
+            .unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        let banner = out
+            .lines()
+            .position(|line| line == "; padding (0x0102-0x0106)")
+            .expect("the banner survives reanalysis");
+        assert!(out.lines().nth(banner + 1).unwrap().starts_with("; ="));
+    }
+    // ──────────────────────────────────────────────────────────────────────────
+    // 9.  Comment provenance
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn reanalysis_keeps_user_comments_and_recreates_analysis_ones() {
+        let mut d = build_disassembler();
+        d.comment_list.0.push(Comment::new(
+            CommentType::PRE,
+            "entry point".to_string(),
+            0x106,
+        ));
+        d.mark_data(0x102, 0x106);
+        d.reanalyze();
+
+        let texts: Vec<&str> = d
+            .comment_list
+            .get_comments(0x106)
+            .iter()
+            .map(|comment| comment.comment_text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["Start of program", "entry point"]);
+
+        let opts = DisassemblerOptions {
+            comment_sources: true,
+            ..DisassemblerOptions::default()
+        };
+        let mut buf = Vec::<u8>::new();
+        d.disassemble_stream(&mut buf, opts).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert!(out.contains("; [user] entry point"));
+        assert!(out.contains("; [labels] Start of program"));
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 10.  Querying addresses
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn query_describes_the_containing_instruction() {
+        let d = build_disassembler();
+        let info = d.query(0x107);
+        assert_eq!(info.kind, AddressKind::Code);
+        assert_eq!(info.start, 0x106);
+        assert_eq!(info.label.map(|label| label.name), Some("_start".into()));
+        assert_eq!(info.references, vec![0x100]);
+        assert_eq!(info.comments[0].comment_text, "Start of program");
+        assert_eq!(
+            d.query(0x107).to_string(),
+            "0x0107: code starting at 0x0106 (_start), referenced at 0x0100"
+        );
+    }
+
+    #[test]
+    fn query_tells_padding_and_unmapped_memory_apart() {
+        let d = build_disassembler();
+        assert_eq!(d.query(0x103).kind, AddressKind::Padding);
+        let outside = d.query(0x300);
+        assert_eq!(outside.kind, AddressKind::Unmapped);
+        assert_eq!(outside.instruction, None);
+    }
+
+    #[test]
+    fn strings_stop_at_the_end_of_the_segment() {
+        // mov dx, 0xfff8 / mov ah, 9 / int 21h
+        let mut d = Disassembler::new(vec![0xBA, 0xF8, 0xFF, 0xB4, 0x09, 0xCD, 0x21]);
+        d.map_file(AuxFile {
+            name: "TEXT.BIN".to_string(),
+            address: 0xFFF8,
+            data: b"ABCDEFGH$".to_vec(),
+        });
+        let string = &d.string_constant_list.0[0];
+        assert_eq!(string.value, "ABCDEFG");
+        assert_eq!((string.start, string.end), (0xFFF8, 0xFFFF));
+    }
+
+    #[test]
+    fn query_finds_strings_and_reserved_memory() {
+        let data = vec![
+            0xBA, 0x08, 0x01, // mov dx, 0x108
+            0xB4, 0x09, 0xCD, 0x21, // mov ah, 9 / int 21h
+            0xC3, // ret
+            b'o', b'k', b'$', // 0x108
+        ];
+        let mut d = Disassembler::new(data);
+        let info = d.query(0x109);
+        assert_eq!((info.kind, info.start), (AddressKind::String, 0x108));
+        assert_eq!(info.references, vec![0x100]);
+
+        // inc word [0x200] / ret
+        d = Disassembler::new(vec![0xFF, 0x06, 0x00, 0x02, 0xC3]);
+        let info = d.query(0x201);
+        assert_eq!((info.kind, info.start), (AddressKind::Uninitialized, 0x200));
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 11.  Resource limits
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn instruction_limit_leaves_the_rest_as_data() {
+        let limits = Limits {
+            max_instructions: Some(3),
+            ..Limits::default()
+        };
+        let d = Disassembler::with_limits(sample_program(), limits);
+        assert_eq!(
+            d.limits_hit(),
+            vec![LimitHit {
+                kind: LimitKind::Instructions,
+                address: Some(d.instructions.0[3].ip() as Address),
+            }]
+        );
+        assert!(d.instructions.0[3..].iter().all(is_data));
+        let covered: usize = d.instructions.0.iter().map(Instruction::len).sum();
+        assert_eq!(covered, d.data.len(), "every byte is still listed");
+        assert!(
+            d.to_string()
+                .contains("analysis stopped at the instruction limit")
+        );
+    }
+
+    #[test]
+    fn label_and_string_limits_keep_the_lowest_addresses() {
+        let data = vec![
+            0xE8, 0x04, 0x00, // call 0x107
+            0xE8, 0x02, 0x00, // call 0x108
+            0xC3, // ret
+            0xC3, // 0x107: ret
+            0xC3, // 0x108: ret
+        ];
+        let limits = Limits {
+            max_labels: Some(1),
+            max_strings: Some(0),
+            ..Limits::default()
+        };
+        let d = Disassembler::with_limits(data, limits);
+        let labels: Vec<Address> = d.labels.0.iter().map(|label| label.address).collect();
+        assert_eq!(labels, vec![0x107]);
+        let kinds: Vec<LimitKind> = d.limits_hit().iter().map(|hit| hit.kind).collect();
+        assert_eq!(kinds, vec![LimitKind::Labels]);
+    }
+
+    #[test]
+    fn no_limits_by_default() {
+        let d = build_disassembler();
+        assert!(d.limits_hit().is_empty());
+        assert_eq!(*d.limits(), Limits::default());
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 12.  Determinism
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn analysing_twice_gives_identical_results() {
+        for sample in crate::samples::SAMPLES {
+            let first = Disassembler::new(sample.bytes.to_vec());
+            let second = Disassembler::new(sample.bytes.to_vec());
+            // Debug shows every field in iteration order, so a map iterated
+            // in a per-instance order would show up here
+            assert_eq!(
+                format!("{first:?}"),
+                format!("{second:?}"),
+                "{}",
+                sample.name
+            );
+            assert_eq!(first.to_string(), second.to_string(), "{}", sample.name);
+        }
+    }
 }
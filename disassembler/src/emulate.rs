@@ -0,0 +1,2445 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt::{self, Display};
+
+use iced_x86::{
+    ConditionCode, Decoder, DecoderOptions, Formatter, Instruction, InstructionInfoFactory,
+    Mnemonic, NasmFormatter, OpAccess, OpKind, Register,
+};
+
+use crate::consts::{Address, COM_OFFSET, SIZE};
+use crate::files::FileSystem;
+use crate::keys::Keystroke;
+use crate::probe::{Probe, ProbeHit, Trigger};
+use crate::screen::{COLUMNS, ROWS, Screen};
+
+/// Carry flag
+pub const CF: u16 = 0x0001;
+/// Parity flag
+pub const PF: u16 = 0x0004;
+/// Auxiliary carry flag
+pub const AF: u16 = 0x0010;
+/// Zero flag
+pub const ZF: u16 = 0x0040;
+/// Sign flag
+pub const SF: u16 = 0x0080;
+/// Trap flag
+pub const TF: u16 = 0x0100;
+/// Interrupt enable flag
+pub const IF: u16 = 0x0200;
+/// Direction flag
+pub const DF: u16 = 0x0400;
+/// Overflow flag
+pub const OF: u16 = 0x0800;
+
+/// The segment the program (and its PSP) is loaded at
+pub const LOAD_SEGMENT: u16 = 0x1000;
+
+/// The segment just past the memory DOS gives the program, as stored in its PSP
+const TOP_OF_MEMORY: u16 = 0xA000;
+
+/// The segment holding the interrupt stubs the emulator services itself
+///
+/// Every vector in the interrupt table initially points at `BIOS_SEGMENT:n`,
+/// so programs that hook a vector and chain to the previous handler still
+/// end up in the emulator's DOS/BIOS implementation.
+pub const BIOS_SEGMENT: u16 = 0xF000;
+
+/// The segment of the colour text screen
+pub const TEXT_SEGMENT: u16 = 0xB800;
+
+/// The segment of the BIOS data area, which holds the video mode and cursor
+const BIOS_DATA_SEGMENT: u16 = 0x0040;
+
+/// The light grey on black programs get when they do not choose colours
+const DEFAULT_ATTRIBUTE: u8 = 0x07;
+
+/// The size of the emulated address space
+const MEMORY_SIZE: usize = 0x10_0000;
+
+/// The register file of the emulated 8086
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Registers {
+    /// Accumulator
+    pub ax: u16,
+    /// Base register
+    pub bx: u16,
+    /// Count register
+    pub cx: u16,
+    /// Data register
+    pub dx: u16,
+    /// Source index
+    pub si: u16,
+    /// Destination index
+    pub di: u16,
+    /// Base pointer
+    pub bp: u16,
+    /// Stack pointer
+    pub sp: u16,
+    /// Code segment
+    pub cs: u16,
+    /// Data segment
+    pub ds: u16,
+    /// Extra segment
+    pub es: u16,
+    /// Stack segment
+    pub ss: u16,
+    /// Instruction pointer
+    pub ip: u16,
+    /// Flags register
+    pub flags: u16,
+}
+
+impl Registers {
+    /// The value of the register called `name`, such as `ax` or `DS`
+    ///
+    /// Only whole 16-bit registers can be read.
+    pub fn get(&self, name: &str) -> Result<u16, String> {
+        let mut registers = *self;
+        registers.field(name).map(|register| *register)
+    }
+
+    /// Sets the register called `name`, such as `ax` or `DS`
+    ///
+    /// Only whole 16-bit registers can be set.
+    pub fn set(&mut self, name: &str, value: u16) -> Result<(), String> {
+        *self.field(name)? = value;
+        Ok(())
+    }
+
+    fn field(&mut self, name: &str) -> Result<&mut u16, String> {
+        let register = match name.to_ascii_lowercase().as_str() {
+            "ax" => &mut self.ax,
+            "bx" => &mut self.bx,
+            "cx" => &mut self.cx,
+            "dx" => &mut self.dx,
+            "si" => &mut self.si,
+            "di" => &mut self.di,
+            "bp" => &mut self.bp,
+            "sp" => &mut self.sp,
+            "cs" => &mut self.cs,
+            "ds" => &mut self.ds,
+            "es" => &mut self.es,
+            "ss" => &mut self.ss,
+            "ip" => &mut self.ip,
+            "flags" => &mut self.flags,
+            _ => return Err(format!("unknown register `{name}`")),
+        };
+        Ok(register)
+    }
+}
+
+impl Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "AX={:04X} BX={:04X} CX={:04X} DX={:04X} SP={:04X} BP={:04X} SI={:04X} DI={:04X}",
+            self.ax, self.bx, self.cx, self.dx, self.sp, self.bp, self.si, self.di
+        )?;
+        write!(
+            f,
+            "DS={:04X} ES={:04X} SS={:04X} CS={:04X} IP={:04X} FLAGS={:04X}",
+            self.ds, self.es, self.ss, self.cs, self.ip, self.flags
+        )
+    }
+}
+
+/// Why the emulator stopped running
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StopReason {
+    /// The program terminated through int 20h, int 21h or a TSR call, with this exit code
+    Exited(u8),
+    /// The instruction budget ran out
+    BudgetExhausted,
+    /// A `hlt` instruction was executed
+    Halted,
+    /// An instruction the emulator does not implement was reached
+    Unsupported {
+        /// The offset of the instruction
+        address: Address,
+        /// The instruction in NASM syntax
+        instruction: String,
+    },
+    /// A division by zero or a quotient overflow with no handler installed
+    DivideError {
+        /// The offset of the `div`/`idiv` instruction
+        address: Address,
+    },
+    /// A replayed run made a service call the recording does not have, see
+    /// [`Emulator::replay`]
+    Diverged {
+        /// How many instructions had run before the call
+        instruction: u64,
+    },
+}
+
+impl Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Exited(code) => write!(f, "exited with code {code}"),
+            StopReason::BudgetExhausted => write!(f, "instruction budget exhausted"),
+            StopReason::Halted => write!(f, "halted"),
+            StopReason::Unsupported {
+                address,
+                instruction,
+            } => write!(
+                f,
+                "unsupported instruction `{instruction}` at 0x{address:04x}"
+            ),
+            StopReason::DivideError { address } => {
+                write!(f, "divide error at 0x{address:04x}")
+            }
+            StopReason::Diverged { instruction } => write!(
+                f,
+                "left the recorded run at a service call after {instruction} instructions"
+            ),
+        }
+    }
+}
+
+/// What the emulator observed about the program while running it
+///
+/// Only instructions in the program's own segment, past the PSP, are recorded.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Trace {
+    /// Offsets of every instruction that was executed
+    pub executed: BTreeSet<Address>,
+    /// Targets of jumps through a register or memory operand
+    pub jump_targets: BTreeSet<Address>,
+    /// Targets of calls through a register or memory operand
+    pub call_targets: BTreeSet<Address>,
+    /// Reads and writes of watched memory, in the order they happened, see
+    /// [`Emulator::watch`]
+    pub accesses: Vec<MemoryAccess>,
+    /// Every time a probe fired, in order, see [`Emulator::probe`]
+    pub probe_hits: Vec<ProbeHit>,
+}
+
+/// One read or write of watched memory by an instruction of the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    /// The instruction making the access
+    pub instruction: Address,
+    /// The first byte accessed
+    pub address: Address,
+    /// How many bytes were accessed
+    pub size: usize,
+    /// Whether the memory was written rather than read
+    pub write: bool,
+}
+
+/// One DOS or BIOS service call and the registers it returned, see
+/// [`Emulator::record`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServiceCall {
+    /// How many instructions had run before the call
+    pub instruction: u64,
+    /// The interrupt called
+    pub vector: u8,
+    /// The registers the program called the service with
+    pub before: Registers,
+    /// The registers the service returned
+    pub after: Registers,
+}
+
+/// What calling a single function with [`Emulator::call`] did
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CallResult {
+    /// Why the call ended early, or `None` if the function returned
+    pub stopped: Option<StopReason>,
+    /// The registers when the call ended
+    pub registers: Registers,
+    /// Everything the function printed
+    pub output: Vec<u8>,
+    /// How many instructions the function ran
+    pub instructions: u64,
+}
+
+/// The return address [`Emulator::call`] pushes: the `int 20h` in the PSP,
+/// so a function that returns is caught before anything runs there
+const CALL_RETURN: u16 = 0x0000;
+
+/// Something that keeps the current instruction from completing
+enum Fault {
+    /// The instruction (or one of its operands) is not implemented
+    Unsupported,
+    /// Division by zero or quotient overflow
+    Divide,
+    /// The program asked to terminate
+    Exit(u8),
+    /// `hlt`
+    Halt,
+}
+
+/// A small 8086 emulator for running COM programs
+///
+/// The program is loaded at `LOAD_SEGMENT:0100` behind a minimal PSP, with
+/// every segment register pointing at the PSP like DOS does. Console output
+/// through int 21h and int 10h is captured in [`Emulator::output`] and
+/// keyboard input is served from the keys given to [`Emulator::with_keys`].
+/// The program runs in 80x25 colour text mode: console output and int 10h
+/// calls draw on the screen at `TEXT_SEGMENT` like the BIOS does, alongside
+/// what the program writes there itself, see [`Emulator::screen`].
+/// File calls fail unless the program is given [`Emulator::files`].
+///
+/// # Example
+///
+/// ```
+/// use disassembler::emulate::{Emulator, StopReason};
+///
+/// // mov dl, 'A' / mov ah, 2 / int 21h / ret
+/// let program = [0xB2, 0x41, 0xB4, 0x02, 0xCD, 0x21, 0xC3];
+/// let mut emulator = Emulator::new(&program);
+/// assert_eq!(emulator.run(100), StopReason::Exited(0));
+/// assert_eq!(emulator.output, b"A");
+/// ```
+pub struct Emulator {
+    /// The current register values
+    pub registers: Registers,
+    /// Everything the program printed
+    pub output: Vec<u8>,
+    /// The number of instructions executed so far
+    pub instructions_executed: u64,
+    /// Addresses executed and indirect branch targets taken so far
+    pub trace: Trace,
+    /// The files the program can create, open, read, write, delete and
+    /// search for through int 21h, or `None` for file calls to fail
+    pub files: Option<FileSystem>,
+    memory: Vec<u8>,
+    input: VecDeque<Keystroke>,
+    /// The instruction count when the last key was read, which the next
+    /// key's delay counts from
+    last_key: u64,
+    /// The scan code int 21h returns on the read after the zero for a key
+    /// without a character
+    pending_scan: Option<u8>,
+    /// Ranges of the program's segment whose accesses are recorded, from
+    /// start to end
+    watchpoints: Vec<(Address, Address)>,
+    /// The service calls made so far, while recording
+    recording: Option<Vec<ServiceCall>>,
+    /// The service calls still to come, while replaying
+    replaying: Option<VecDeque<ServiceCall>>,
+    /// Actions to take on events while running
+    probes: Vec<Probe>,
+    /// Where int 21h/4Eh and 4Fh put the files they find, as segment and offset
+    dta: (u16, u16),
+}
+
+impl Emulator {
+    /// Loads a COM program into a fresh machine
+    pub fn new(program: &[u8]) -> Self {
+        let mut emulator = Emulator {
+            registers: Registers {
+                sp: 0xFFFE,
+                cs: LOAD_SEGMENT,
+                ds: LOAD_SEGMENT,
+                es: LOAD_SEGMENT,
+                ss: LOAD_SEGMENT,
+                ip: COM_OFFSET,
+                flags: IF | 0x0002,
+                ..Default::default()
+            },
+            output: Vec::new(),
+            instructions_executed: 0,
+            trace: Trace::default(),
+            files: None,
+            memory: vec![0; MEMORY_SIZE],
+            input: VecDeque::new(),
+            last_key: 0,
+            pending_scan: None,
+            watchpoints: Vec::new(),
+            recording: None,
+            replaying: None,
+            probes: Vec::new(),
+            // the default DTA is the command line in the PSP
+            dta: (LOAD_SEGMENT, 0x80),
+        };
+
+        for vector in 0..=0xFFu16 {
+            emulator.write_u16(0, vector * 4, vector);
+            emulator.write_u16(0, vector * 4 + 2, BIOS_SEGMENT);
+            // an iret, so the stub is also harmless when disassembled
+            emulator.write_u8(BIOS_SEGMENT, vector, 0xCF);
+        }
+
+        // PSP: int 20h at offset 0, top of memory, empty command line
+        emulator.write_u8(LOAD_SEGMENT, 0x00, 0xCD);
+        emulator.write_u8(LOAD_SEGMENT, 0x01, 0x20);
+        emulator.write_u16(LOAD_SEGMENT, 0x02, TOP_OF_MEMORY);
+        emulator.write_u8(LOAD_SEGMENT, 0x80, 0x00);
+        emulator.write_u8(LOAD_SEGMENT, 0x81, 0x0D);
+
+        let length = program.len().min(0x1_0000 - COM_OFFSET as usize - 2);
+        for (offset, byte) in program[..length].iter().enumerate() {
+            emulator.write_u8(LOAD_SEGMENT, COM_OFFSET + offset as u16, *byte);
+        }
+        // a near `ret` from the entry point lands on the int 20h in the PSP
+        emulator.write_u16(LOAD_SEGMENT, 0xFFFE, 0x0000);
+
+        // 80x25 colour text mode, on a cleared screen
+        emulator.write_u8(BIOS_DATA_SEGMENT, 0x49, 0x03);
+        emulator.write_u16(BIOS_DATA_SEGMENT, 0x4A, COLUMNS as u16);
+        emulator.clear_screen();
+
+        emulator
+    }
+
+    /// Queues the keys typing the bytes of `input` for keyboard input calls
+    pub fn with_input(self, input: &[u8]) -> Self {
+        let keys: Vec<Keystroke> = input
+            .iter()
+            .map(|&byte| Keystroke::from_ascii(byte))
+            .collect();
+        self.with_keys(&keys)
+    }
+
+    /// Queues keys to be pressed for keyboard input calls, see
+    /// [`crate::keys::parse_keys`]
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::emulate::Emulator;
+    /// use disassembler::keys::parse_keys;
+    ///
+    /// // mov ah, 0 / int 16h / mov [0x200], ax / ret
+    /// let program = [0xB4, 0x00, 0xCD, 0x16, 0xA3, 0x00, 0x02, 0xC3];
+    /// let mut emulator = Emulator::new(&program).with_keys(&parse_keys("{f1}").unwrap());
+    /// emulator.run(100);
+    /// assert_eq!(emulator.read_u16(emulator.registers.ds, 0x200), 0x3B00);
+    /// ```
+    pub fn with_keys(mut self, keys: &[Keystroke]) -> Self {
+        self.input.extend(keys);
+        self
+    }
+
+    fn linear(segment: u16, offset: u16) -> usize {
+        ((segment as usize) << 4).wrapping_add(offset as usize) & (MEMORY_SIZE - 1)
+    }
+
+    /// Reads a byte from `segment:offset`
+    pub fn read_u8(&self, segment: u16, offset: u16) -> u8 {
+        self.memory[Self::linear(segment, offset)]
+    }
+
+    /// Reads a little endian word from `segment:offset`
+    pub fn read_u16(&self, segment: u16, offset: u16) -> u16 {
+        u16::from_le_bytes([
+            self.read_u8(segment, offset),
+            self.read_u8(segment, offset.wrapping_add(1)),
+        ])
+    }
+
+    /// Writes a byte to `segment:offset`
+    pub fn write_u8(&mut self, segment: u16, offset: u16, value: u8) {
+        self.memory[Self::linear(segment, offset)] = value;
+    }
+
+    /// Writes a little endian word to `segment:offset`
+    pub fn write_u16(&mut self, segment: u16, offset: u16, value: u16) {
+        let [low, high] = value.to_le_bytes();
+        self.write_u8(segment, offset, low);
+        self.write_u8(segment, offset.wrapping_add(1), high);
+    }
+
+    /// Runs until the program stops or `budget` instructions have been executed
+    pub fn run(&mut self, budget: u64) -> StopReason {
+        let limit = self.instructions_executed.saturating_add(budget);
+        while self.instructions_executed < limit {
+            if let Some(reason) = self.step() {
+                return reason;
+            }
+        }
+        StopReason::BudgetExhausted
+    }
+
+    /// Records every read and write instructions of the program make to
+    /// `start..end` in the program's segment in [`Trace::accesses`]
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::emulate::Emulator;
+    ///
+    /// // inc byte [0x200] / ret
+    /// let mut emulator = Emulator::new(&[0xFE, 0x06, 0x00, 0x02, 0xC3]);
+    /// emulator.watch(0x200, 0x210);
+    /// emulator.run(100);
+    ///
+    /// let access = emulator.trace.accesses[0];
+    /// assert_eq!((access.instruction, access.address), (0x100, 0x200));
+    /// ```
+    pub fn watch(&mut self, start: Address, end: Address) {
+        self.watchpoints.push((start, end));
+    }
+
+    /// Starts logging every DOS and BIOS service call with the registers
+    /// it returned, to be collected with [`Emulator::take_recording`]
+    ///
+    /// With the keyboard input and the starting state, the log is enough to
+    /// run the program again exactly, see [`Emulator::replay`].
+    pub fn record(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// The service calls logged since [`Emulator::record`], which stops
+    /// the logging
+    pub fn take_recording(&mut self) -> Vec<ServiceCall> {
+        self.recording.take().unwrap_or_default()
+    }
+
+    /// Plays back service calls logged by [`Emulator::record`]
+    ///
+    /// Each service returns the registers it returned when recorded, so
+    /// timer and clock reads give the same answers as they did then. A call
+    /// made at a different point, to a different interrupt or with a
+    /// different AX than recorded stops the run with [`StopReason::Diverged`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::emulate::{Emulator, StopReason};
+    ///
+    /// // mov ah, 0 / int 1ah / mov [0x200], dx / ret
+    /// let program = [0xB4, 0x00, 0xCD, 0x1A, 0x89, 0x16, 0x00, 0x02, 0xC3];
+    /// let mut first = Emulator::new(&program);
+    /// first.record();
+    /// first.run(100);
+    /// let mut calls = first.take_recording();
+    /// assert_eq!(calls.len(), 2);
+    ///
+    /// // as if the clock had read 1234h when the run was recorded
+    /// calls[0].after.dx = 0x1234;
+    /// let mut again = Emulator::new(&program);
+    /// again.replay(calls);
+    /// assert_eq!(again.run(100), StopReason::Exited(0));
+    /// assert_eq!(again.read_u16(again.registers.ds, 0x200), 0x1234);
+    /// ```
+    pub fn replay(&mut self, calls: Vec<ServiceCall>) {
+        self.replaying = Some(calls.into());
+    }
+
+    /// Takes the actions of `probe` whenever its event happens in the
+    /// program's own code, recording each time in [`Trace::probe_hits`]
+    ///
+    /// Breakpoints and interrupt calls fire before the instruction runs, so
+    /// registers the probe sets are what the instruction sees; writes fire
+    /// after it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::emulate::Emulator;
+    /// use disassembler::probe::{Probe, Trigger};
+    ///
+    /// // mov al, 1 / mov [0x200], al / ret
+    /// let mut emulator = Emulator::new(&[0xB0, 0x01, 0xA2, 0x00, 0x02, 0xC3]);
+    /// emulator.probe(Probe {
+    ///     name: "force".to_string(),
+    ///     trigger: Trigger::Break(0x102),
+    ///     log: Some("storing {al}".to_string()),
+    ///     set: vec![("ax".to_string(), 7)],
+    ///     comment: None,
+    /// });
+    /// emulator.run(100);
+    /// assert_eq!(emulator.read_u8(emulator.registers.ds, 0x200), 7);
+    /// assert_eq!(emulator.trace.probe_hits[0].log.as_deref(), Some("storing 0x07"));
+    /// ```
+    pub fn probe(&mut self, probe: Probe) {
+        if let Trigger::Write { start, end } = probe.trigger {
+            self.watch(start, end);
+        }
+        self.probes.push(probe);
+    }
+
+    /// The text screen as it is now, with what the program printed and
+    /// wrote to video memory
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::emulate::Emulator;
+    ///
+    /// // mov ah, 2 / mov dx, 0x0103 / int 10h / mov ah, 0eh / mov al, '*' / int 10h / ret
+    /// let program = [0xB4, 0x02, 0xBA, 0x03, 0x01, 0xCD, 0x10, 0xB4, 0x0E, 0xB0, 0x2A, 0xCD, 0x10, 0xC3];
+    /// let mut emulator = Emulator::new(&program);
+    /// emulator.run(100);
+    /// let screen = emulator.screen();
+    /// assert_eq!(screen.text(), "\n   *\n");
+    /// assert_eq!(screen.cursor, (1, 4));
+    /// ```
+    pub fn screen(&self) -> Screen {
+        let memory = self.read_bytes(TEXT_SEGMENT, 0, COLUMNS * ROWS * 2);
+        Screen::from_memory(&memory, self.cursor())
+    }
+
+    /// Reads `len` bytes from `segment:offset`
+    pub fn read_bytes(&self, segment: u16, offset: u16, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| self.read_u8(segment, offset.wrapping_add(i as u16)))
+            .collect()
+    }
+
+    /// Writes `bytes` to `segment:offset`
+    pub fn write_bytes(&mut self, segment: u16, offset: u16, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.write_u8(segment, offset.wrapping_add(i as u16), *byte);
+        }
+    }
+
+    /// Calls the near function at `address` in the program's segment and
+    /// runs it until it returns, the program stops or `budget` instructions
+    /// have been executed
+    ///
+    /// The function starts with the registers and memory as they are, so
+    /// set up its arguments first; whatever it writes is left in memory to
+    /// be read back afterwards. This tests one routine, such as a checksum,
+    /// without running the program up to it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::emulate::{Emulator, LOAD_SEGMENT};
+    ///
+    /// let program = [
+    ///     0xC3, // ret
+    ///     0x30, 0xC0, // 0x101: xor al, al
+    ///     0x02, 0x04, // add al, [si]
+    ///     0x46, // inc si
+    ///     0xE2, 0xFB, // loop 0x103
+    ///     0xC3, // ret
+    /// ];
+    /// let mut emulator = Emulator::new(&program);
+    /// emulator.write_bytes(LOAD_SEGMENT, 0x200, &[1, 2, 3]);
+    /// emulator.registers.si = 0x200;
+    /// emulator.registers.cx = 3;
+    ///
+    /// let result = emulator.call(0x101, 100);
+    /// assert_eq!(result.stopped, None);
+    /// assert_eq!(result.registers.ax & 0xFF, 6);
+    /// ```
+    pub fn call(&mut self, address: Address, budget: u64) -> CallResult {
+        let printed = self.output.len();
+        let started = self.instructions_executed;
+        let sp = self.registers.sp;
+        self.push(CALL_RETURN);
+        self.registers.cs = LOAD_SEGMENT;
+        self.registers.ip = address;
+
+        let mut stopped = None;
+        while self.registers.cs != LOAD_SEGMENT
+            || self.registers.ip != CALL_RETURN
+            || self.registers.sp != sp
+        {
+            if self.instructions_executed - started >= budget {
+                stopped = Some(StopReason::BudgetExhausted);
+                break;
+            }
+            if let Some(reason) = self.step() {
+                stopped = Some(reason);
+                break;
+            }
+        }
+        CallResult {
+            stopped,
+            registers: self.registers,
+            output: self.output[printed..].to_vec(),
+            instructions: self.instructions_executed - started,
+        }
+    }
+
+    /// Decodes the instruction at CS:IP without executing it
+    pub fn current_instruction(&self) -> Instruction {
+        let Registers { cs, ip, .. } = self.registers;
+        let bytes: Vec<u8> = (0..16)
+            .map(|i| self.read_u8(cs, ip.wrapping_add(i)))
+            .collect();
+        Decoder::with_ip(SIZE, &bytes, ip as u64, DecoderOptions::NONE).decode()
+    }
+
+    /// Executes a single instruction, returning why the program stopped if it did
+    pub fn step(&mut self) -> Option<StopReason> {
+        let address = self.registers.ip;
+
+        // servicing an interrupt is not counted as an instruction of the program
+        if self.registers.cs == BIOS_SEGMENT && address <= 0xFF {
+            let vector = address as u8;
+            let before = self.registers;
+            let result = self.service_interrupt(vector);
+            let instruction = self.instructions_executed;
+            if let Some(replaying) = &mut self.replaying {
+                match replaying.pop_front() {
+                    Some(call)
+                        if call.instruction == instruction
+                            && call.vector == vector
+                            && call.before.ax == before.ax =>
+                    {
+                        self.registers = call.after;
+                    }
+                    _ => return Some(StopReason::Diverged { instruction }),
+                }
+            }
+            if let Some(recording) = &mut self.recording {
+                recording.push(ServiceCall {
+                    instruction,
+                    vector,
+                    before,
+                    after: self.registers,
+                });
+            }
+            self.return_from_service();
+            return result.err().map(|fault| self.stop(fault, address, None));
+        }
+
+        let probed = !self.probes.is_empty() && self.registers.cs == LOAD_SEGMENT;
+        if probed {
+            let probes = std::mem::take(&mut self.probes);
+            let instruction = self.current_instruction();
+            for probe in &probes {
+                if probe.fires_before(&instruction, &self.registers) {
+                    probe.fire(self, address, address);
+                }
+            }
+            self.probes = probes;
+        }
+        // a probe may have moved IP
+        let address = self.registers.ip;
+        let accessed = self.trace.accesses.len();
+
+        let instruction = self.current_instruction();
+        self.instructions_executed += 1;
+        if instruction.is_invalid() {
+            return Some(self.stop(Fault::Unsupported, address, Some(&instruction)));
+        }
+
+        let in_program = self.registers.cs == LOAD_SEGMENT && address >= COM_OFFSET;
+        if in_program {
+            self.trace.executed.insert(address);
+            if !self.watchpoints.is_empty() {
+                self.record_accesses(&instruction);
+            }
+        }
+
+        self.registers.ip = instruction.next_ip() as u16;
+        let result = self.execute(&instruction);
+        if in_program && self.registers.cs == LOAD_SEGMENT && result.is_ok() {
+            let indirect = matches!(instruction.op0_kind(), OpKind::Register | OpKind::Memory);
+            match instruction.mnemonic() {
+                Mnemonic::Jmp if indirect => {
+                    self.trace.jump_targets.insert(self.registers.ip);
+                }
+                Mnemonic::Call if indirect => {
+                    self.trace.call_targets.insert(self.registers.ip);
+                }
+                _ => {}
+            }
+        }
+        if probed && self.trace.accesses.len() > accessed {
+            let probes = std::mem::take(&mut self.probes);
+            let writes: Vec<MemoryAccess> = self.trace.accesses[accessed..]
+                .iter()
+                .filter(|access| access.write)
+                .copied()
+                .collect();
+            for access in writes {
+                for probe in &probes {
+                    if probe.fires_on_write(access.address, access.size) {
+                        probe.fire(self, address, access.address);
+                    }
+                }
+            }
+            self.probes = probes;
+        }
+        match result {
+            Ok(()) => None,
+            Err(Fault::Divide) => {
+                // int 0 goes to a handler if the program installed one
+                self.registers.ip = address;
+                if self.read_u16(0, 2) == BIOS_SEGMENT {
+                    Some(StopReason::DivideError { address })
+                } else {
+                    self.interrupt(0);
+                    None
+                }
+            }
+            Err(fault) => {
+                self.registers.ip = address;
+                Some(self.stop(fault, address, Some(&instruction)))
+            }
+        }
+    }
+
+    fn stop(
+        &self,
+        fault: Fault,
+        address: Address,
+        instruction: Option<&Instruction>,
+    ) -> StopReason {
+        match fault {
+            Fault::Exit(code) => StopReason::Exited(code),
+            Fault::Halt => StopReason::Halted,
+            Fault::Divide => StopReason::DivideError { address },
+            Fault::Unsupported => {
+                let mut text = String::new();
+                match instruction {
+                    Some(instruction) => NasmFormatter::new().format(instruction, &mut text),
+                    None => text = format!("int 0x{:02x}", address),
+                }
+                StopReason::Unsupported {
+                    address,
+                    instruction: text,
+                }
+            }
+        }
+    }
+
+    // ─── flags ───────────────────────────────────────────────────────────────
+
+    fn flag(&self, flag: u16) -> bool {
+        self.registers.flags & flag != 0
+    }
+
+    fn set_flag(&mut self, flag: u16, value: bool) {
+        if value {
+            self.registers.flags |= flag;
+        } else {
+            self.registers.flags &= !flag;
+        }
+    }
+
+    fn set_result_flags(&mut self, result: u32, size: u32) {
+        let mask = mask(size);
+        self.set_flag(ZF, result & mask == 0);
+        self.set_flag(SF, result & sign(size) != 0);
+        self.set_flag(PF, (result as u8).count_ones().is_multiple_of(2));
+    }
+
+    fn condition(&self, condition: ConditionCode) -> bool {
+        let cf = self.flag(CF);
+        let zf = self.flag(ZF);
+        let sf = self.flag(SF);
+        let of = self.flag(OF);
+        match condition {
+            ConditionCode::None => true,
+            ConditionCode::o => of,
+            ConditionCode::no => !of,
+            ConditionCode::b => cf,
+            ConditionCode::ae => !cf,
+            ConditionCode::e => zf,
+            ConditionCode::ne => !zf,
+            ConditionCode::be => cf || zf,
+            ConditionCode::a => !cf && !zf,
+            ConditionCode::s => sf,
+            ConditionCode::ns => !sf,
+            ConditionCode::p => self.flag(PF),
+            ConditionCode::np => !self.flag(PF),
+            ConditionCode::l => sf != of,
+            ConditionCode::ge => sf == of,
+            ConditionCode::le => zf || sf != of,
+            ConditionCode::g => !zf && sf == of,
+        }
+    }
+
+    // ─── registers and operands ──────────────────────────────────────────────
+
+    fn register(&self, register: Register) -> Result<u16, Fault> {
+        let r = &self.registers;
+        Ok(match register {
+            Register::AL => r.ax & 0xFF,
+            Register::CL => r.cx & 0xFF,
+            Register::DL => r.dx & 0xFF,
+            Register::BL => r.bx & 0xFF,
+            Register::AH => r.ax >> 8,
+            Register::CH => r.cx >> 8,
+            Register::DH => r.dx >> 8,
+            Register::BH => r.bx >> 8,
+            Register::AX => r.ax,
+            Register::CX => r.cx,
+            Register::DX => r.dx,
+            Register::BX => r.bx,
+            Register::SP => r.sp,
+            Register::BP => r.bp,
+            Register::SI => r.si,
+            Register::DI => r.di,
+            Register::ES => r.es,
+            Register::CS => r.cs,
+            Register::SS => r.ss,
+            Register::DS => r.ds,
+            _ => return Err(Fault::Unsupported),
+        })
+    }
+
+    fn set_register(&mut self, register: Register, value: u16) -> Result<(), Fault> {
+        let r = &mut self.registers;
+        let low = |word: &mut u16| *word = (*word & 0xFF00) | (value & 0xFF);
+        let high = |word: &mut u16| *word = (*word & 0x00FF) | (value << 8);
+        match register {
+            Register::AL => low(&mut r.ax),
+            Register::CL => low(&mut r.cx),
+            Register::DL => low(&mut r.dx),
+            Register::BL => low(&mut r.bx),
+            Register::AH => high(&mut r.ax),
+            Register::CH => high(&mut r.cx),
+            Register::DH => high(&mut r.dx),
+            Register::BH => high(&mut r.bx),
+            Register::AX => r.ax = value,
+            Register::CX => r.cx = value,
+            Register::DX => r.dx = value,
+            Register::BX => r.bx = value,
+            Register::SP => r.sp = value,
+            Register::BP => r.bp = value,
+            Register::SI => r.si = value,
+            Register::DI => r.di = value,
+            Register::ES => r.es = value,
+            Register::CS => r.cs = value,
+            Register::SS => r.ss = value,
+            Register::DS => r.ds = value,
+            _ => return Err(Fault::Unsupported),
+        }
+        Ok(())
+    }
+
+    /// The segment and offset a memory operand refers to
+    fn effective_address(&self, instruction: &Instruction) -> Result<(u16, u16), Fault> {
+        let mut offset = instruction.memory_displacement32() as u16;
+        for register in [instruction.memory_base(), instruction.memory_index()] {
+            if register != Register::None {
+                offset = offset.wrapping_add(self.register(register)?);
+            }
+        }
+        Ok((self.register(instruction.memory_segment())?, offset))
+    }
+
+    /// The size in bytes of operand `operand`
+    fn operand_size(&self, instruction: &Instruction, operand: u32) -> u32 {
+        match instruction.op_kind(operand) {
+            OpKind::Register => instruction.op_register(operand).size() as u32,
+            OpKind::Memory => instruction.memory_size().size() as u32,
+            _ if operand > 0 => self.operand_size(instruction, 0),
+            _ => 2,
+        }
+    }
+
+    fn read_operand(&self, instruction: &Instruction, operand: u32) -> Result<u16, Fault> {
+        match instruction.op_kind(operand) {
+            OpKind::Register => self.register(instruction.op_register(operand)),
+            OpKind::Memory => {
+                let (segment, offset) = self.effective_address(instruction)?;
+                match instruction.memory_size().size() {
+                    1 => Ok(self.read_u8(segment, offset) as u16),
+                    2 => Ok(self.read_u16(segment, offset)),
+                    _ => Err(Fault::Unsupported),
+                }
+            }
+            OpKind::Immediate8
+            | OpKind::Immediate8_2nd
+            | OpKind::Immediate16
+            | OpKind::Immediate8to16 => Ok(instruction.immediate(operand) as u16),
+            _ => Err(Fault::Unsupported),
+        }
+    }
+
+    fn write_operand(
+        &mut self,
+        instruction: &Instruction,
+        operand: u32,
+        value: u16,
+    ) -> Result<(), Fault> {
+        match instruction.op_kind(operand) {
+            OpKind::Register => self.set_register(instruction.op_register(operand), value),
+            OpKind::Memory => {
+                let (segment, offset) = self.effective_address(instruction)?;
+                match instruction.memory_size().size() {
+                    1 => self.write_u8(segment, offset, value as u8),
+                    2 => self.write_u16(segment, offset, value),
+                    _ => return Err(Fault::Unsupported),
+                }
+                Ok(())
+            }
+            _ => Err(Fault::Unsupported),
+        }
+    }
+
+    /// Reads a far pointer (offset, segment) from a memory operand
+    fn read_far_pointer(&self, instruction: &Instruction) -> Result<(u16, u16), Fault> {
+        if instruction.memory_size().size() != 4 {
+            return Err(Fault::Unsupported);
+        }
+        let (segment, offset) = self.effective_address(instruction)?;
+        Ok((
+            self.read_u16(segment, offset),
+            self.read_u16(segment, offset.wrapping_add(2)),
+        ))
+    }
+
+    /// Records the accesses `instruction` is about to make to watched memory
+    ///
+    /// A repeated string instruction is taken to run for all of CX.
+    fn record_accesses(&mut self, instruction: &Instruction) {
+        let mut factory = InstructionInfoFactory::new();
+        let info = factory.info(instruction);
+        for used in info.used_memory() {
+            if self.register(used.segment()).ok() != Some(LOAD_SEGMENT) {
+                continue;
+            }
+            let mut offset = used.displacement() as u16;
+            for register in [used.base(), used.index()] {
+                if register != Register::None {
+                    offset = offset.wrapping_add(self.register(register).unwrap_or(0));
+                }
+            }
+            // repeated string instructions leave the size to the instruction
+            let element = match used.memory_size().size() {
+                0 => instruction.memory_size().size().max(1),
+                size => size,
+            };
+            let mut size = element;
+            if instruction.has_rep_prefix() || instruction.has_repne_prefix() {
+                size *= self.registers.cx as usize;
+                if self.flag(DF) {
+                    offset = offset.wrapping_sub((size - element.min(size)) as u16);
+                }
+            }
+            let (start, end) = (offset as usize, offset as usize + size);
+            let read = matches!(
+                used.access(),
+                OpAccess::Read | OpAccess::CondRead | OpAccess::ReadWrite | OpAccess::ReadCondWrite
+            );
+            let write = matches!(
+                used.access(),
+                OpAccess::Write
+                    | OpAccess::CondWrite
+                    | OpAccess::ReadWrite
+                    | OpAccess::ReadCondWrite
+            );
+            let watched = self
+                .watchpoints
+                .iter()
+                .any(|&(from, to)| start < to as usize && (from as usize) < end);
+            if !watched {
+                continue;
+            }
+            for (happened, write) in [(read, false), (write, true)] {
+                if happened {
+                    self.trace.accesses.push(MemoryAccess {
+                        instruction: instruction.ip() as Address,
+                        address: offset,
+                        size,
+                        write,
+                    });
+                }
+            }
+        }
+    }
+
+    // ─── stack ───────────────────────────────────────────────────────────────
+
+    fn push(&mut self, value: u16) {
+        self.registers.sp = self.registers.sp.wrapping_sub(2);
+        self.write_u16(self.registers.ss, self.registers.sp, value);
+    }
+
+    fn pop(&mut self) -> u16 {
+        let value = self.read_u16(self.registers.ss, self.registers.sp);
+        self.registers.sp = self.registers.sp.wrapping_add(2);
+        value
+    }
+
+    /// Performs a hardware-style interrupt through the interrupt table
+    fn interrupt(&mut self, vector: u8) {
+        self.push(self.registers.flags);
+        self.push(self.registers.cs);
+        self.push(self.registers.ip);
+        self.set_flag(IF, false);
+        self.set_flag(TF, false);
+        self.registers.ip = self.read_u16(0, vector as u16 * 4);
+        self.registers.cs = self.read_u16(0, vector as u16 * 4 + 2);
+    }
+
+    /// Returns from a serviced interrupt, handing CF and ZF back to the caller
+    /// the way DOS does
+    fn return_from_service(&mut self) {
+        self.registers.ip = self.pop();
+        self.registers.cs = self.pop();
+        let saved = self.pop();
+        self.registers.flags = (saved & !(CF | ZF)) | (self.registers.flags & (CF | ZF));
+    }
+
+    // ─── execution ───────────────────────────────────────────────────────────
+
+    fn arithmetic(&mut self, mnemonic: Mnemonic, a: u32, b: u32, size: u32) -> u32 {
+        let mask = mask(size);
+        let carry = self.flag(CF) as u32;
+        let result = match mnemonic {
+            Mnemonic::Add | Mnemonic::Adc => {
+                let carry = if mnemonic == Mnemonic::Adc { carry } else { 0 };
+                let result = a + b + carry;
+                self.set_flag(CF, result > mask);
+                self.set_flag(OF, (a ^ result) & (b ^ result) & sign(size) != 0);
+                self.set_flag(AF, (a ^ b ^ result) & 0x10 != 0);
+                result
+            }
+            Mnemonic::Sub | Mnemonic::Sbb | Mnemonic::Cmp | Mnemonic::Neg => {
+                let borrow = if mnemonic == Mnemonic::Sbb { carry } else { 0 };
+                let result = a.wrapping_sub(b).wrapping_sub(borrow);
+                self.set_flag(CF, a < b + borrow);
+                self.set_flag(OF, (a ^ b) & (a ^ result) & sign(size) != 0);
+                self.set_flag(AF, (a ^ b ^ result) & 0x10 != 0);
+                result
+            }
+            Mnemonic::And | Mnemonic::Test => a & b,
+            Mnemonic::Or => a | b,
+            _ => a ^ b,
+        };
+        if matches!(
+            mnemonic,
+            Mnemonic::And | Mnemonic::Test | Mnemonic::Or | Mnemonic::Xor
+        ) {
+            self.set_flag(CF, false);
+            self.set_flag(OF, false);
+            self.set_flag(AF, false);
+        }
+        self.set_result_flags(result, size);
+        result & mask
+    }
+
+    fn shift(&mut self, mnemonic: Mnemonic, value: u32, count: u32, size: u32) -> u32 {
+        let count = count & 0x1F;
+        if count == 0 {
+            return value;
+        }
+        let mask = mask(size);
+        let sign = sign(size);
+        let bits = size * 8;
+        let mut result = value;
+        let mut carry = self.flag(CF);
+        for _ in 0..count {
+            match mnemonic {
+                Mnemonic::Shl | Mnemonic::Sal => {
+                    carry = result & sign != 0;
+                    result = (result << 1) & mask;
+                }
+                Mnemonic::Shr => {
+                    carry = result & 1 != 0;
+                    result >>= 1;
+                }
+                Mnemonic::Sar => {
+                    carry = result & 1 != 0;
+                    result = (result >> 1) | (result & sign);
+                }
+                Mnemonic::Rol => {
+                    carry = result & sign != 0;
+                    result = ((result << 1) | carry as u32) & mask;
+                }
+                Mnemonic::Ror => {
+                    carry = result & 1 != 0;
+                    result = (result >> 1) | ((carry as u32) << (bits - 1));
+                }
+                Mnemonic::Rcl => {
+                    let out = result & sign != 0;
+                    result = ((result << 1) | carry as u32) & mask;
+                    carry = out;
+                }
+                _ => {
+                    let out = result & 1 != 0;
+                    result = (result >> 1) | ((carry as u32) << (bits - 1));
+                    carry = out;
+                }
+            }
+        }
+        self.set_flag(CF, carry);
+        let msb = result & sign != 0;
+        let overflow = match mnemonic {
+            Mnemonic::Shr => value & sign != 0,
+            Mnemonic::Sar => false,
+            Mnemonic::Ror | Mnemonic::Rcr => msb != (result & (sign >> 1) != 0),
+            _ => msb != carry,
+        };
+        self.set_flag(OF, overflow);
+        if matches!(
+            mnemonic,
+            Mnemonic::Shl | Mnemonic::Sal | Mnemonic::Shr | Mnemonic::Sar
+        ) {
+            self.set_result_flags(result, size);
+        }
+        result
+    }
+
+    fn multiply(&mut self, instruction: &Instruction) -> Result<(), Fault> {
+        let size = self.operand_size(instruction, 0);
+        let signed = instruction.mnemonic() == Mnemonic::Imul;
+
+        if instruction.op_count() > 1 {
+            // the 186 two and three operand forms of imul
+            let (a, b) = if instruction.op_count() == 3 {
+                (
+                    self.read_operand(instruction, 1)?,
+                    self.read_operand(instruction, 2)?,
+                )
+            } else {
+                (
+                    self.read_operand(instruction, 0)?,
+                    self.read_operand(instruction, 1)?,
+                )
+            };
+            let product = a as i16 as i32 * b as i16 as i32;
+            self.write_operand(instruction, 0, product as u16)?;
+            let overflow = product != product as i16 as i32;
+            self.set_flag(CF, overflow);
+            self.set_flag(OF, overflow);
+            return Ok(());
+        }
+
+        let source = self.read_operand(instruction, 0)?;
+        let overflow = if size == 1 {
+            let al = self.registers.ax & 0xFF;
+            let product = if signed {
+                (al as u8 as i8 as i16 * source as u8 as i8 as i16) as u16
+            } else {
+                al * source
+            };
+            self.registers.ax = product;
+            if signed {
+                product as i16 != product as u8 as i8 as i16
+            } else {
+                product > 0xFF
+            }
+        } else {
+            let product = if signed {
+                (self.registers.ax as i16 as i32 * source as i16 as i32) as u32
+            } else {
+                self.registers.ax as u32 * source as u32
+            };
+            self.registers.ax = product as u16;
+            self.registers.dx = (product >> 16) as u16;
+            if signed {
+                product as i32 != product as u16 as i16 as i32
+            } else {
+                product > 0xFFFF
+            }
+        };
+        self.set_flag(CF, overflow);
+        self.set_flag(OF, overflow);
+        Ok(())
+    }
+
+    fn divide(&mut self, instruction: &Instruction) -> Result<(), Fault> {
+        let size = self.operand_size(instruction, 0);
+        let divisor = self.read_operand(instruction, 0)?;
+        if divisor == 0 {
+            return Err(Fault::Divide);
+        }
+        let signed = instruction.mnemonic() == Mnemonic::Idiv;
+
+        if size == 1 {
+            let dividend = self.registers.ax;
+            let (quotient, remainder) = if signed {
+                let dividend = dividend as i16 as i32;
+                let divisor = divisor as u8 as i8 as i32;
+                let quotient = dividend / divisor;
+                if quotient != quotient as i8 as i32 {
+                    return Err(Fault::Divide);
+                }
+                (quotient as u16 & 0xFF, (dividend % divisor) as u16 & 0xFF)
+            } else {
+                let quotient = dividend / divisor;
+                if quotient > 0xFF {
+                    return Err(Fault::Divide);
+                }
+                (quotient, dividend % divisor)
+            };
+            self.registers.ax = (remainder << 8) | quotient;
+        } else {
+            let dividend = ((self.registers.dx as u32) << 16) | self.registers.ax as u32;
+            let (quotient, remainder) = if signed {
+                let dividend = dividend as i32 as i64;
+                let divisor = divisor as i16 as i64;
+                let quotient = dividend / divisor;
+                if quotient != quotient as i16 as i64 {
+                    return Err(Fault::Divide);
+                }
+                (quotient as u16, (dividend % divisor) as u16)
+            } else {
+                let quotient = dividend / divisor as u32;
+                if quotient > 0xFFFF {
+                    return Err(Fault::Divide);
+                }
+                (quotient as u16, (dividend % divisor as u32) as u16)
+            };
+            self.registers.ax = quotient;
+            self.registers.dx = remainder;
+        }
+        Ok(())
+    }
+
+    /// Executes one iteration of a string instruction
+    fn string_step(&mut self, mnemonic: Mnemonic, source_segment: u16) -> Result<(), Fault> {
+        let size: u16 = match mnemonic {
+            Mnemonic::Movsb
+            | Mnemonic::Stosb
+            | Mnemonic::Lodsb
+            | Mnemonic::Cmpsb
+            | Mnemonic::Scasb => 1,
+            _ => 2,
+        };
+        let delta = if self.flag(DF) {
+            size.wrapping_neg()
+        } else {
+            size
+        };
+        let Registers { si, di, es, .. } = self.registers;
+        let read = |emulator: &Self, segment, offset| {
+            if size == 1 {
+                emulator.read_u8(segment, offset) as u16
+            } else {
+                emulator.read_u16(segment, offset)
+            }
+        };
+        let accumulator = if size == 1 {
+            self.registers.ax & 0xFF
+        } else {
+            self.registers.ax
+        };
+
+        match mnemonic {
+            Mnemonic::Movsb | Mnemonic::Movsw => {
+                let value = read(self, source_segment, si);
+                if size == 1 {
+                    self.write_u8(es, di, value as u8);
+                } else {
+                    self.write_u16(es, di, value);
+                }
+            }
+            Mnemonic::Stosb | Mnemonic::Stosw => {
+                if size == 1 {
+                    self.write_u8(es, di, accumulator as u8);
+                } else {
+                    self.write_u16(es, di, accumulator);
+                }
+            }
+            Mnemonic::Lodsb => self.set_register(Register::AL, read(self, source_segment, si))?,
+            Mnemonic::Lodsw => self.registers.ax = read(self, source_segment, si),
+            Mnemonic::Cmpsb | Mnemonic::Cmpsw => {
+                let a = read(self, source_segment, si) as u32;
+                let b = read(self, es, di) as u32;
+                self.arithmetic(Mnemonic::Cmp, a, b, size as u32);
+            }
+            Mnemonic::Scasb | Mnemonic::Scasw => {
+                let b = read(self, es, di) as u32;
+                self.arithmetic(Mnemonic::Cmp, accumulator as u32, b, size as u32);
+            }
+            _ => return Err(Fault::Unsupported),
+        }
+
+        if matches!(
+            mnemonic,
+            Mnemonic::Movsb
+                | Mnemonic::Movsw
+                | Mnemonic::Lodsb
+                | Mnemonic::Lodsw
+                | Mnemonic::Cmpsb
+                | Mnemonic::Cmpsw
+        ) {
+            self.registers.si = si.wrapping_add(delta);
+        }
+        if !matches!(mnemonic, Mnemonic::Lodsb | Mnemonic::Lodsw) {
+            self.registers.di = di.wrapping_add(delta);
+        }
+        Ok(())
+    }
+
+    fn string_instruction(&mut self, instruction: &Instruction) -> Result<(), Fault> {
+        let mnemonic = instruction.mnemonic();
+        let source_segment = self.register(instruction.memory_segment())?;
+        let compares = matches!(
+            mnemonic,
+            Mnemonic::Cmpsb | Mnemonic::Cmpsw | Mnemonic::Scasb | Mnemonic::Scasw
+        );
+
+        if !(instruction.has_rep_prefix() || instruction.has_repne_prefix()) {
+            return self.string_step(mnemonic, source_segment);
+        }
+        while self.registers.cx != 0 {
+            self.string_step(mnemonic, source_segment)?;
+            self.registers.cx = self.registers.cx.wrapping_sub(1);
+            if compares && self.flag(ZF) == instruction.has_repne_prefix() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, instruction: &Instruction) -> Result<(), Fault> {
+        let mnemonic = instruction.mnemonic();
+        let size = self.operand_size(instruction, 0);
+        if size > 2
+            && !matches!(
+                mnemonic,
+                Mnemonic::Jmp | Mnemonic::Call | Mnemonic::Lds | Mnemonic::Les
+            )
+        {
+            return Err(Fault::Unsupported);
+        }
+
+        match mnemonic {
+            Mnemonic::Nop | Mnemonic::Wait | Mnemonic::Cli | Mnemonic::Sti => {
+                if mnemonic == Mnemonic::Cli || mnemonic == Mnemonic::Sti {
+                    self.set_flag(IF, mnemonic == Mnemonic::Sti);
+                }
+            }
+            Mnemonic::Mov => {
+                let value = self.read_operand(instruction, 1)?;
+                self.write_operand(instruction, 0, value)?;
+            }
+            Mnemonic::Lea => {
+                let (_, offset) = self.effective_address(instruction)?;
+                self.write_operand(instruction, 0, offset)?;
+            }
+            Mnemonic::Lds | Mnemonic::Les => {
+                let (offset, segment) = self.read_far_pointer(instruction)?;
+                self.write_operand(instruction, 0, offset)?;
+                let register = if mnemonic == Mnemonic::Lds {
+                    Register::DS
+                } else {
+                    Register::ES
+                };
+                self.set_register(register, segment)?;
+            }
+            Mnemonic::Xchg => {
+                let a = self.read_operand(instruction, 0)?;
+                let b = self.read_operand(instruction, 1)?;
+                self.write_operand(instruction, 0, b)?;
+                self.write_operand(instruction, 1, a)?;
+            }
+            Mnemonic::Xlatb => {
+                let value = self.read_operand(instruction, 0)?;
+                self.set_register(Register::AL, value)?;
+            }
+            Mnemonic::Push => {
+                let value = self.read_operand(instruction, 0)?;
+                self.push(value);
+            }
+            Mnemonic::Pop => {
+                let value = self.pop();
+                self.write_operand(instruction, 0, value)?;
+            }
+            Mnemonic::Pushf => self.push(self.registers.flags),
+            Mnemonic::Popf => self.registers.flags = (self.pop() & 0x0FD5) | 0x0002,
+            Mnemonic::Pusha => {
+                let sp = self.registers.sp;
+                let Registers {
+                    ax,
+                    cx,
+                    dx,
+                    bx,
+                    bp,
+                    si,
+                    di,
+                    ..
+                } = self.registers;
+                for value in [ax, cx, dx, bx, sp, bp, si, di] {
+                    self.push(value);
+                }
+            }
+            Mnemonic::Popa => {
+                self.registers.di = self.pop();
+                self.registers.si = self.pop();
+                self.registers.bp = self.pop();
+                self.pop();
+                self.registers.bx = self.pop();
+                self.registers.dx = self.pop();
+                self.registers.cx = self.pop();
+                self.registers.ax = self.pop();
+            }
+            Mnemonic::Lahf => {
+                let flags = self.registers.flags & 0xFF;
+                self.set_register(Register::AH, flags)?;
+            }
+            Mnemonic::Sahf => {
+                let ah = self.registers.ax >> 8;
+                self.registers.flags = (self.registers.flags & 0xFF00) | (ah & 0xD5) | 0x0002;
+            }
+            Mnemonic::Add
+            | Mnemonic::Adc
+            | Mnemonic::Sub
+            | Mnemonic::Sbb
+            | Mnemonic::And
+            | Mnemonic::Or
+            | Mnemonic::Xor => {
+                let a = self.read_operand(instruction, 0)? as u32;
+                let b = self.read_operand(instruction, 1)? as u32 & mask(size);
+                let result = self.arithmetic(mnemonic, a, b, size);
+                self.write_operand(instruction, 0, result as u16)?;
+            }
+            Mnemonic::Cmp | Mnemonic::Test => {
+                let a = self.read_operand(instruction, 0)? as u32;
+                let b = self.read_operand(instruction, 1)? as u32 & mask(size);
+                self.arithmetic(mnemonic, a, b, size);
+            }
+            Mnemonic::Inc | Mnemonic::Dec => {
+                let carry = self.flag(CF);
+                let a = self.read_operand(instruction, 0)? as u32;
+                let operation = if mnemonic == Mnemonic::Inc {
+                    Mnemonic::Add
+                } else {
+                    Mnemonic::Sub
+                };
+                let result = self.arithmetic(operation, a, 1, size);
+                self.set_flag(CF, carry);
+                self.write_operand(instruction, 0, result as u16)?;
+            }
+            Mnemonic::Neg => {
+                let a = self.read_operand(instruction, 0)? as u32;
+                let result = self.arithmetic(Mnemonic::Neg, 0, a, size);
+                self.write_operand(instruction, 0, result as u16)?;
+            }
+            Mnemonic::Not => {
+                let a = self.read_operand(instruction, 0)?;
+                self.write_operand(instruction, 0, !a)?;
+            }
+            Mnemonic::Shl
+            | Mnemonic::Sal
+            | Mnemonic::Shr
+            | Mnemonic::Sar
+            | Mnemonic::Rol
+            | Mnemonic::Ror
+            | Mnemonic::Rcl
+            | Mnemonic::Rcr => {
+                let value = self.read_operand(instruction, 0)? as u32;
+                let count = self.read_operand(instruction, 1)? as u32 & 0xFF;
+                let result = self.shift(mnemonic, value, count, size);
+                self.write_operand(instruction, 0, result as u16)?;
+            }
+            Mnemonic::Mul | Mnemonic::Imul => self.multiply(instruction)?,
+            Mnemonic::Div | Mnemonic::Idiv => self.divide(instruction)?,
+            Mnemonic::Cbw => {
+                self.registers.ax = self.registers.ax as u8 as i8 as i16 as u16;
+            }
+            Mnemonic::Cwd => {
+                self.registers.dx = if self.registers.ax & 0x8000 != 0 {
+                    0xFFFF
+                } else {
+                    0
+                };
+            }
+            Mnemonic::Aam => {
+                let base = instruction.immediate8() as u16;
+                if base == 0 {
+                    return Err(Fault::Divide);
+                }
+                let al = self.registers.ax & 0xFF;
+                self.registers.ax = ((al / base) << 8) | (al % base);
+                self.set_result_flags(self.registers.ax as u32, 1);
+            }
+            Mnemonic::Aad => {
+                let base = instruction.immediate8() as u16;
+                let Registers { ax, .. } = self.registers;
+                let al = ((ax >> 8).wrapping_mul(base).wrapping_add(ax & 0xFF)) & 0xFF;
+                self.registers.ax = al;
+                self.set_result_flags(al as u32, 1);
+            }
+            Mnemonic::Clc => self.set_flag(CF, false),
+            Mnemonic::Stc => self.set_flag(CF, true),
+            Mnemonic::Cmc => self.set_flag(CF, !self.flag(CF)),
+            Mnemonic::Cld => self.set_flag(DF, false),
+            Mnemonic::Std => self.set_flag(DF, true),
+            Mnemonic::Movsb
+            | Mnemonic::Movsw
+            | Mnemonic::Stosb
+            | Mnemonic::Stosw
+            | Mnemonic::Lodsb
+            | Mnemonic::Lodsw
+            | Mnemonic::Cmpsb
+            | Mnemonic::Cmpsw
+            | Mnemonic::Scasb
+            | Mnemonic::Scasw => self.string_instruction(instruction)?,
+            Mnemonic::Jmp => match instruction.op0_kind() {
+                OpKind::NearBranch16 => self.registers.ip = instruction.near_branch16(),
+                OpKind::FarBranch16 => {
+                    self.registers.cs = instruction.far_branch_selector();
+                    self.registers.ip = instruction.far_branch16();
+                }
+                OpKind::Memory if size == 4 => {
+                    let (offset, segment) = self.read_far_pointer(instruction)?;
+                    self.registers.cs = segment;
+                    self.registers.ip = offset;
+                }
+                _ if size == 2 => self.registers.ip = self.read_operand(instruction, 0)?,
+                _ => return Err(Fault::Unsupported),
+            },
+            Mnemonic::Call => {
+                let return_address = self.registers.ip;
+                match instruction.op0_kind() {
+                    OpKind::NearBranch16 => {
+                        self.push(return_address);
+                        self.registers.ip = instruction.near_branch16();
+                    }
+                    OpKind::FarBranch16 => {
+                        self.push(self.registers.cs);
+                        self.push(return_address);
+                        self.registers.cs = instruction.far_branch_selector();
+                        self.registers.ip = instruction.far_branch16();
+                    }
+                    OpKind::Memory if size == 4 => {
+                        let (offset, segment) = self.read_far_pointer(instruction)?;
+                        self.push(self.registers.cs);
+                        self.push(return_address);
+                        self.registers.cs = segment;
+                        self.registers.ip = offset;
+                    }
+                    _ if size == 2 => {
+                        let target = self.read_operand(instruction, 0)?;
+                        self.push(return_address);
+                        self.registers.ip = target;
+                    }
+                    _ => return Err(Fault::Unsupported),
+                }
+            }
+            Mnemonic::Ret | Mnemonic::Retf => {
+                self.registers.ip = self.pop();
+                if mnemonic == Mnemonic::Retf {
+                    self.registers.cs = self.pop();
+                }
+                if instruction.op_count() == 1 {
+                    let extra = instruction.immediate16();
+                    self.registers.sp = self.registers.sp.wrapping_add(extra);
+                }
+            }
+            Mnemonic::Iret => {
+                self.registers.ip = self.pop();
+                self.registers.cs = self.pop();
+                self.registers.flags = (self.pop() & 0x0FD5) | 0x0002;
+            }
+            Mnemonic::Int => self.interrupt(instruction.immediate8()),
+            Mnemonic::Int3 => self.interrupt(3),
+            Mnemonic::Into => {
+                if self.flag(OF) {
+                    self.interrupt(4);
+                }
+            }
+            Mnemonic::Loop | Mnemonic::Loope | Mnemonic::Loopne => {
+                self.registers.cx = self.registers.cx.wrapping_sub(1);
+                let taken = self.registers.cx != 0
+                    && match mnemonic {
+                        Mnemonic::Loope => self.flag(ZF),
+                        Mnemonic::Loopne => !self.flag(ZF),
+                        _ => true,
+                    };
+                if taken {
+                    self.registers.ip = instruction.near_branch16();
+                }
+            }
+            Mnemonic::Jcxz => {
+                if self.registers.cx == 0 {
+                    self.registers.ip = instruction.near_branch16();
+                }
+            }
+            _ if instruction.is_jcc_short_or_near() => {
+                if self.condition(instruction.condition_code()) {
+                    self.registers.ip = instruction.near_branch16();
+                }
+            }
+            Mnemonic::Enter => {
+                let bytes = instruction.immediate16();
+                self.push(self.registers.bp);
+                self.registers.bp = self.registers.sp;
+                self.registers.sp = self.registers.sp.wrapping_sub(bytes);
+            }
+            Mnemonic::Leave => {
+                self.registers.sp = self.registers.bp;
+                self.registers.bp = self.pop();
+            }
+            // there is no hardware behind the ports, so reads see a floating bus
+            Mnemonic::In => self.write_operand(instruction, 0, 0xFFFF)?,
+            Mnemonic::Out => {}
+            Mnemonic::Hlt => return Err(Fault::Halt),
+            _ => return Err(Fault::Unsupported),
+        }
+        Ok(())
+    }
+
+    // ─── DOS and BIOS services ───────────────────────────────────────────────
+
+    /// Whether a key has been pressed and not read yet
+    fn key_ready(&self) -> bool {
+        self.pending_scan.is_some()
+            || self
+                .input
+                .front()
+                .is_some_and(|key| self.instructions_executed >= self.last_key + key.delay)
+    }
+
+    /// Reads the next key, waiting out its delay if it has not been pressed yet
+    fn read_keystroke(&mut self) -> Keystroke {
+        self.last_key = self.instructions_executed;
+        // a program waiting for a key once the input ran out gets Enter
+        self.input.pop_front().unwrap_or(Keystroke::ENTER)
+    }
+
+    /// Reads the next character for int 21h, where keys without one read as
+    /// a zero and then their scan code
+    fn read_key(&mut self) -> u8 {
+        if let Some(scan) = self.pending_scan.take() {
+            return scan;
+        }
+        let key = self.read_keystroke();
+        if key.ascii == 0 {
+            self.pending_scan = Some(key.scan);
+        }
+        key.ascii
+    }
+
+    fn service_interrupt(&mut self, vector: u8) -> Result<(), Fault> {
+        match vector {
+            0x20 | 0x27 => Err(Fault::Exit(0)),
+            0x21 => self.dos_service(),
+            0x10 => {
+                self.video_service();
+                Ok(())
+            }
+            0x16 => {
+                self.keyboard_service();
+                Ok(())
+            }
+            0x1A => {
+                // no time passes in the emulator
+                self.registers.cx = 0;
+                self.registers.dx = 0;
+                Ok(())
+            }
+            // every other vector behaves like an empty handler
+            _ => Ok(()),
+        }
+    }
+
+    fn dos_service(&mut self) -> Result<(), Fault> {
+        let Registers {
+            ax, bx, cx, dx, ds, ..
+        } = self.registers;
+        let al = ax as u8;
+        match ax >> 8 {
+            0x00 => return Err(Fault::Exit(0)),
+            0x01 => {
+                let key = self.read_key();
+                self.print(key);
+                self.set_register(Register::AL, key as u16)?;
+            }
+            0x02 => self.print(dx as u8),
+            0x06 if dx as u8 != 0xFF => self.print(dx as u8),
+            0x06 => {
+                let key = self.key_ready().then(|| self.read_key());
+                self.set_flag(ZF, key.is_none());
+                self.set_register(Register::AL, key.unwrap_or(0) as u16)?;
+            }
+            0x07 | 0x08 => {
+                let key = self.read_key();
+                self.set_register(Register::AL, key as u16)?;
+            }
+            0x09 => {
+                let mut offset = dx;
+                loop {
+                    let byte = self.read_u8(ds, offset);
+                    if byte == b'$' {
+                        break;
+                    }
+                    self.print(byte);
+                    offset = offset.wrapping_add(1);
+                    if offset == dx {
+                        break;
+                    }
+                }
+            }
+            0x0A => {
+                let capacity = self.read_u8(ds, dx);
+                let mut line = Vec::new();
+                loop {
+                    // keys without a character are ignored when typing a line
+                    let key = self.read_keystroke().ascii;
+                    if key == b'\r' {
+                        break;
+                    }
+                    if key != 0 && (line.len() as u8) < capacity.saturating_sub(1) {
+                        line.push(key);
+                    }
+                }
+                for &byte in line.iter().chain(b"\r") {
+                    self.print(byte);
+                }
+                self.write_u8(ds, dx.wrapping_add(1), line.len() as u8);
+                for (i, byte) in line.iter().chain(b"\r").enumerate() {
+                    self.write_u8(ds, dx.wrapping_add(2 + i as u16), *byte);
+                }
+            }
+            0x0B => {
+                let status = if self.key_ready() { 0xFF } else { 0x00 };
+                self.set_register(Register::AL, status)?;
+            }
+            0x1A => self.dta = (ds, dx),
+            0x25 => {
+                self.write_u16(0, al as u16 * 4, dx);
+                self.write_u16(0, al as u16 * 4 + 2, ds);
+            }
+            0x2A | 0x2C => {
+                self.registers.cx = 0;
+                self.registers.dx = 0;
+            }
+            0x30 => {
+                // DOS 5.0
+                self.registers.ax = 0x0005;
+                self.registers.bx = 0;
+                self.registers.cx = 0;
+            }
+            0x2F => (self.registers.es, self.registers.bx) = self.dta,
+            0x31 => return Err(Fault::Exit(al)),
+            0x35 => {
+                self.registers.bx = self.read_u16(0, al as u16 * 4);
+                self.registers.es = self.read_u16(0, al as u16 * 4 + 2);
+            }
+            0x40 if bx == 1 || bx == 2 => {
+                for i in 0..cx {
+                    let byte = self.read_u8(ds, dx.wrapping_add(i));
+                    self.print(byte);
+                }
+                self.registers.ax = cx;
+                self.set_flag(CF, false);
+            }
+            0x3C..=0x42 | 0x4E | 0x4F if self.files.is_some() => self.file_service(),
+            0x4A if self.registers.es == LOAD_SEGMENT => {
+                // the program's block runs from its PSP to the top of memory
+                let available = TOP_OF_MEMORY - LOAD_SEGMENT;
+                if bx <= available {
+                    self.set_flag(CF, false);
+                } else {
+                    self.registers.ax = 0x0008;
+                    self.registers.bx = available;
+                    self.set_flag(CF, true);
+                }
+            }
+            0x4C => return Err(Fault::Exit(al)),
+            0x4D => self.registers.ax = 0,
+            // anything else touching files, memory or processes fails with
+            // "function number invalid"
+            _ => {
+                self.registers.ax = 0x0001;
+                self.set_flag(CF, true);
+            }
+        }
+        Ok(())
+    }
+
+    /// Serves the int 21h file functions from [`Emulator::files`], setting
+    /// CF and the error in AX when they fail
+    fn file_service(&mut self) {
+        let Some(mut files) = self.files.take() else {
+            return;
+        };
+        let Registers {
+            ax, bx, cx, dx, ds, ..
+        } = self.registers;
+        let al = ax as u8;
+        let path = self.read_asciiz(ds, dx);
+        let result = match ax >> 8 {
+            0x3C => files.create(&path).map(|handle| self.registers.ax = handle),
+            0x3D => files
+                .open(&path, al)
+                .map(|handle| self.registers.ax = handle),
+            0x3E => files.close(bx),
+            0x3F => files.read(bx, cx).map(|bytes| {
+                self.write_bytes(ds, dx, &bytes);
+                self.registers.ax = bytes.len() as u16;
+            }),
+            0x40 => {
+                let bytes = self.read_bytes(ds, dx, cx as usize);
+                files
+                    .write(bx, &bytes)
+                    .map(|written| self.registers.ax = written)
+            }
+            0x41 => files.delete(&path),
+            0x42 => {
+                let offset = ((cx as u32) << 16 | dx as u32) as i32;
+                files.seek(bx, al, offset).map(|position| {
+                    self.registers.ax = position as u16;
+                    self.registers.dx = (position >> 16) as u16;
+                })
+            }
+            ah => {
+                let found = if ah == 0x4E {
+                    files.find_first(&path)
+                } else {
+                    files.find_next()
+                };
+                found.map(|found| {
+                    let (segment, offset) = self.dta;
+                    let mut entry = [0u8; 0x2B];
+                    // an archived file, written at midnight on 1 January 1980
+                    entry[0x15] = 0x20;
+                    entry[0x1A..0x1E].copy_from_slice(&found.size.to_le_bytes());
+                    entry[0x1E..0x1E + found.name.len()].copy_from_slice(found.name.as_bytes());
+                    self.write_bytes(segment, offset, &entry);
+                    self.registers.ax = 0;
+                })
+            }
+        };
+        self.files = Some(files);
+        if let Err(code) = result {
+            self.registers.ax = code;
+        }
+        self.set_flag(CF, result.is_err());
+    }
+
+    /// The zero-terminated string at `segment:offset`, as DOS takes file names
+    fn read_asciiz(&self, segment: u16, offset: u16) -> String {
+        (0..128u16)
+            .map(|i| self.read_u8(segment, offset.wrapping_add(i)))
+            .take_while(|&byte| byte != 0)
+            .map(char::from)
+            .collect()
+    }
+
+    fn video_service(&mut self) {
+        let Registers {
+            ax,
+            bx,
+            bp,
+            cx,
+            dx,
+            es,
+            ..
+        } = self.registers;
+        let al = ax as u8;
+        let [dl, dh] = dx.to_le_bytes();
+        let [bl, bh] = bx.to_le_bytes();
+        let [cl, ch] = cx.to_le_bytes();
+        match ax >> 8 {
+            0x00 => {
+                self.write_u8(BIOS_DATA_SEGMENT, 0x49, al & 0x7F);
+                // bit 7 keeps what is on the screen
+                if al & 0x80 == 0 {
+                    self.clear_screen();
+                }
+                self.set_cursor(0, 0);
+            }
+            0x02 => self.set_cursor(dh, dl),
+            0x03 => {
+                let (row, column) = self.cursor();
+                self.registers.cx = 0x0607;
+                self.registers.dx = u16::from_le_bytes([column, row]);
+            }
+            0x06 | 0x07 => self.scroll(al, ax >> 8 == 0x06, bh, (ch, cl), (dh, dl)),
+            0x08 => {
+                let (row, column) = self.cursor();
+                let (character, attribute) = self.cell(row, column);
+                self.registers.ax = u16::from_le_bytes([character, attribute]);
+            }
+            0x09 | 0x0A => {
+                self.output.extend(std::iter::repeat_n(al, cx as usize));
+                let attribute = (ax >> 8 == 0x09).then_some(bl);
+                let (row, column) = self.cursor();
+                let start = row as usize * COLUMNS + column as usize;
+                for index in (start..start + cx as usize).take_while(|&i| i < COLUMNS * ROWS) {
+                    let (row, column) = ((index / COLUMNS) as u8, (index % COLUMNS) as u8);
+                    self.put_cell(row, column, al, attribute);
+                }
+            }
+            0x0E => self.print(al),
+            0x0F => {
+                // 80 column colour text mode, page 0
+                self.registers.ax = 0x5003;
+                self.registers.bx &= 0x00FF;
+            }
+            0x13 => {
+                // with bit 1 of AL set, characters and attributes alternate,
+                // and with bit 0 the cursor is left after the string
+                let stride = if al & 0x02 != 0 { 2 } else { 1 };
+                let cursor = self.cursor();
+                self.set_cursor(dh, dl);
+                for i in 0..cx {
+                    let offset = bp.wrapping_add(i * stride);
+                    let byte = self.read_u8(es, offset);
+                    let attribute = if stride == 2 {
+                        self.read_u8(es, offset.wrapping_add(1))
+                    } else {
+                        bl
+                    };
+                    self.output.push(byte);
+                    self.teletype(byte, Some(attribute));
+                }
+                if al & 0x01 == 0 {
+                    self.set_cursor(cursor.0, cursor.1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // ─── text screen ─────────────────────────────────────────────────────────
+
+    /// Prints `byte` to the console: captures it in the output and draws it
+    /// on the screen
+    fn print(&mut self, byte: u8) {
+        self.output.push(byte);
+        self.teletype(byte, None);
+    }
+
+    /// The row and column of the cursor, from the BIOS data area
+    fn cursor(&self) -> (u8, u8) {
+        let [column, row] = self.read_u16(BIOS_DATA_SEGMENT, 0x50).to_le_bytes();
+        (row, column)
+    }
+
+    fn set_cursor(&mut self, row: u8, column: u8) {
+        self.write_u16(BIOS_DATA_SEGMENT, 0x50, u16::from_le_bytes([column, row]));
+    }
+
+    /// The character and attribute at `row`, `column`, or a blank if that is
+    /// off the screen
+    fn cell(&self, row: u8, column: u8) -> (u8, u8) {
+        if row as usize >= ROWS || column as usize >= COLUMNS {
+            return (b' ', DEFAULT_ATTRIBUTE);
+        }
+        let offset = (row as u16 * COLUMNS as u16 + column as u16) * 2;
+        (
+            self.read_u8(TEXT_SEGMENT, offset),
+            self.read_u8(TEXT_SEGMENT, offset + 1),
+        )
+    }
+
+    /// Writes `character` at `row`, `column`, keeping the attribute there
+    /// unless given one; writes off the screen are dropped
+    fn put_cell(&mut self, row: u8, column: u8, character: u8, attribute: Option<u8>) {
+        if row as usize >= ROWS || column as usize >= COLUMNS {
+            return;
+        }
+        let offset = (row as u16 * COLUMNS as u16 + column as u16) * 2;
+        self.write_u8(TEXT_SEGMENT, offset, character);
+        if let Some(attribute) = attribute {
+            self.write_u8(TEXT_SEGMENT, offset + 1, attribute);
+        }
+    }
+
+    fn clear_screen(&mut self) {
+        let last = ((ROWS - 1) as u8, (COLUMNS - 1) as u8);
+        self.scroll(0, true, DEFAULT_ATTRIBUTE, (0, 0), last);
+    }
+
+    /// Writes `byte` at the cursor and moves it on like int 10h/0Eh,
+    /// acting on bell, backspace, line feed and carriage return and
+    /// scrolling the screen up when the cursor passes the bottom
+    fn teletype(&mut self, byte: u8, attribute: Option<u8>) {
+        let (mut row, mut column) = self.cursor();
+        match byte {
+            0x07 => {}
+            0x08 => column = column.saturating_sub(1),
+            b'\n' => row = row.saturating_add(1),
+            b'\r' => column = 0,
+            _ => {
+                self.put_cell(row, column, byte, attribute);
+                column = column.saturating_add(1);
+            }
+        }
+        if column as usize >= COLUMNS {
+            column = 0;
+            row = row.saturating_add(1);
+        }
+        if row as usize >= ROWS {
+            let last = ((ROWS - 1) as u8, (COLUMNS - 1) as u8);
+            self.scroll(1, true, DEFAULT_ATTRIBUTE, (0, 0), last);
+            row = last.0;
+        }
+        self.set_cursor(row, column);
+    }
+
+    /// Scrolls the window from `top_left` to `bottom_right` up or down by
+    /// `lines`, filling the rows uncovered with blanks in `attribute`; no
+    /// lines, or more than the window has, clears it, like int 10h/06h
+    fn scroll(
+        &mut self,
+        lines: u8,
+        up: bool,
+        attribute: u8,
+        (top, left): (u8, u8),
+        (bottom, right): (u8, u8),
+    ) {
+        let bottom = bottom.min((ROWS - 1) as u8);
+        let right = right.min((COLUMNS - 1) as u8);
+        if top > bottom || left > right {
+            return;
+        }
+        let height = bottom - top + 1;
+        let lines = if lines == 0 || lines > height {
+            height
+        } else {
+            lines
+        };
+        for i in 0..height {
+            let (row, source) = if up {
+                (top + i, (i + lines < height).then(|| top + i + lines))
+            } else {
+                (bottom - i, (i + lines < height).then(|| bottom - i - lines))
+            };
+            for column in left..=right {
+                let (character, attribute) = match source {
+                    Some(source) => self.cell(source, column),
+                    None => (b' ', attribute),
+                };
+                self.put_cell(row, column, character, Some(attribute));
+            }
+        }
+    }
+
+    fn keyboard_service(&mut self) {
+        match self.registers.ax >> 8 {
+            0x00 | 0x10 => {
+                let key = self.read_keystroke();
+                self.registers.ax = u16::from_le_bytes([key.ascii, key.scan]);
+            }
+            0x01 | 0x11 => {
+                let key = self
+                    .key_ready()
+                    .then(|| self.input.front().copied())
+                    .flatten();
+                self.set_flag(ZF, key.is_none());
+                if let Some(key) = key {
+                    self.registers.ax = u16::from_le_bytes([key.ascii, key.scan]);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn mask(size: u32) -> u32 {
+    if size == 1 { 0xFF } else { 0xFFFF }
+}
+
+fn sign(size: u32) -> u32 {
+    if size == 1 { 0x80 } else { 0x8000 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(program: &[u8]) -> (Emulator, StopReason) {
+        let mut emulator = Emulator::new(program);
+        let reason = emulator.run(10_000);
+        (emulator, reason)
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Loading
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn program_is_loaded_behind_psp() {
+        let emulator = Emulator::new(&[0x90, 0xC3]);
+        assert_eq!(emulator.registers.ip, 0x100);
+        assert_eq!(emulator.registers.cs, LOAD_SEGMENT);
+        assert_eq!(emulator.read_u8(LOAD_SEGMENT, 0x100), 0x90);
+        assert_eq!(emulator.read_u16(LOAD_SEGMENT, 0x00), 0x20CD);
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Console output and termination
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn display_string_is_captured() {
+        let program = [
+            0xBA, 0x0C, 0x01, // mov dx, msg
+            0xB4, 0x09, // mov ah, 09h
+            0xCD, 0x21, // int 21h
+            0xB8, 0x03, 0x4C, // mov ax, 4C03h
+            0xCD, 0x21, // int 21h
+            b'H', b'i', b'!', b'$',
+        ];
+        let (emulator, reason) = run(&program);
+        assert_eq!(reason, StopReason::Exited(3));
+        assert_eq!(emulator.output, b"Hi!");
+    }
+
+    #[test]
+    fn ret_from_entry_point_exits_through_psp() {
+        let (_, reason) = run(&[0xC3]);
+        assert_eq!(reason, StopReason::Exited(0));
+    }
+
+    #[test]
+    fn bios_teletype_is_captured() {
+        let program = [
+            0xB4, 0x0E, // mov ah, 0Eh
+            0xB0, b'x', // mov al, 'x'
+            0xCD, 0x10, // int 10h
+            0xCD, 0x20, // int 20h
+        ];
+        let (emulator, _) = run(&program);
+        assert_eq!(emulator.output, b"x");
+    }
+
+    #[test]
+    fn screen_shows_direct_writes_scrolling_and_printing() {
+        let program = [
+            0xB8, 0x00, 0xB8, // mov ax, 0B800h
+            0x8E, 0xC0, // mov es, ax
+            0x26, 0xC7, 0x06, 0xA0, 0x00, 0x58, 0x1F, // mov word [es:0A0h], 1F58h
+            0xB8, 0x01, 0x06, // mov ax, 0601h
+            0xB7, 0x07, // mov bh, 07h
+            0x31, 0xC9, // xor cx, cx
+            0xBA, 0x4F, 0x18, // mov dx, 184Fh
+            0xCD, 0x10, // int 10h
+            0xB4, 0x09, // mov ah, 09h
+            0xBA, 0x20, 0x01, // mov dx, msg
+            0xCD, 0x21, // int 21h
+            0xC3, // ret
+            b'o', b'k', b'$', // 0x120: msg
+        ];
+        let (emulator, _) = run(&program);
+        let screen = emulator.screen();
+        assert_eq!(screen.text(), "ok\n");
+        assert_eq!(screen.cells[0], (b'o', 0x1F), "printing keeps the colours");
+        assert_eq!(screen.cells[80], (b' ', 0x07));
+        assert_eq!(screen.cursor, (0, 2));
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 3.  Arithmetic and control flow
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn loop_and_call_compute_sum() {
+        let program = [
+            0x31, 0xC0, // xor ax, ax
+            0xB9, 0x0A, 0x00, // mov cx, 10
+            0xE8, 0x03, 0x00, // call add_cx
+            0xE2, 0xFB, // loop -5
+            0xC3, // ret
+            0x01, 0xC8, // add_cx: add ax, cx
+            0xC3, // ret
+        ];
+        let (emulator, reason) = run(&program);
+        assert_eq!(reason, StopReason::Exited(0));
+        assert_eq!(emulator.registers.ax, 55);
+    }
+
+    #[test]
+    fn flags_follow_compare() {
+        let program = [
+            0xB0, 0x05, // mov al, 5
+            0x3C, 0x07, // cmp al, 7
+            0xC3, // ret
+        ];
+        let (emulator, _) = run(&program);
+        assert_ne!(emulator.registers.flags & CF, 0, "5 < 7 must borrow");
+        assert_eq!(emulator.registers.flags & ZF, 0);
+        assert_ne!(emulator.registers.flags & SF, 0);
+    }
+
+    #[test]
+    fn rep_stosb_fills_memory() {
+        let program = [
+            0xBF, 0x00, 0x02, // mov di, 200h
+            0xB9, 0x04, 0x00, // mov cx, 4
+            0xB0, 0xAA, // mov al, 0AAh
+            0xF3, 0xAA, // rep stosb
+            0xC3, // ret
+        ];
+        let (emulator, _) = run(&program);
+        assert_eq!(emulator.registers.cx, 0);
+        assert_eq!(emulator.read_u16(LOAD_SEGMENT, 0x202), 0xAAAA);
+        assert_eq!(emulator.read_u8(LOAD_SEGMENT, 0x204), 0);
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 4.  Stopping
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn budget_stops_endless_loop() {
+        let mut emulator = Emulator::new(&[0xEB, 0xFE]); // jmp $
+        assert_eq!(emulator.run(50), StopReason::BudgetExhausted);
+        assert_eq!(emulator.instructions_executed, 50);
+    }
+
+    #[test]
+    fn divide_by_zero_is_reported() {
+        let program = [
+            0x31, 0xDB, // xor bx, bx
+            0xF7, 0xF3, // div bx
+        ];
+        let (_, reason) = run(&program);
+        assert_eq!(reason, StopReason::DivideError { address: 0x102 });
+    }
+
+    #[test]
+    fn keyboard_input_is_served_from_queue() {
+        let program = [
+            0xB4, 0x08, // mov ah, 08h
+            0xCD, 0x21, // int 21h
+            0xB4, 0x4C, // mov ah, 4Ch
+            0xCD, 0x21, // int 21h
+        ];
+        let mut emulator = Emulator::new(&program).with_input(b"\x07");
+        assert_eq!(emulator.run(100), StopReason::Exited(7));
+    }
+
+    #[test]
+    fn polled_keys_wait_for_their_delay() {
+        let program = [
+            0x31, 0xC9, // xor cx, cx
+            0x41, // 0x102: inc cx
+            0xB4, 0x01, // mov ah, 01h
+            0xCD, 0x16, // int 16h
+            0x74, 0xF9, // jz 0x102
+            0xB4, 0x00, // mov ah, 00h
+            0xCD, 0x16, // int 16h
+            0xC3, // ret
+        ];
+        let key = |delay| Keystroke {
+            delay,
+            ..Keystroke::from_ascii(b'a')
+        };
+        let mut emulator = Emulator::new(&program).with_keys(&[key(0)]);
+        emulator.run(1000);
+        assert_eq!(emulator.registers.cx, 1);
+        assert_eq!(emulator.registers.ax, 0x1E61);
+
+        let mut emulator = Emulator::new(&program).with_keys(&[key(40)]);
+        emulator.run(1000);
+        assert_eq!(emulator.registers.cx, 10);
+        assert_eq!(emulator.registers.ax, 0x1E61);
+    }
+
+    #[test]
+    fn dos_reads_keys_without_characters_as_two_bytes() {
+        let program = [
+            0xB4, 0x07, // mov ah, 07h
+            0xCD, 0x21, // int 21h
+            0x88, 0xC3, // mov bl, al
+            0xCD, 0x21, // int 21h
+            0x88, 0xC7, // mov bh, al
+            0xC3, // ret
+        ];
+        let up = Keystroke {
+            ascii: 0,
+            scan: 0x48,
+            delay: 0,
+        };
+        let mut emulator = Emulator::new(&program).with_keys(&[up]);
+        emulator.run(100);
+        assert_eq!(emulator.registers.bx, 0x4800);
+    }
+
+    #[test]
+    fn shrinking_the_program_block_succeeds() {
+        let program = |paragraphs: u16| {
+            let [low, high] = paragraphs.to_le_bytes();
+            [
+                0xBB, low, high, // mov bx, paragraphs
+                0xB4, 0x4A, // mov ah, 4Ah
+                0xCD, 0x21, // int 21h
+                0xB8, 0x00, 0x4C, // mov ax, 4C00h
+                0x14, 0x00, // adc al, 0
+                0xCD, 0x21, // int 21h
+            ]
+        };
+        assert_eq!(run(&program(0x1000)).1, StopReason::Exited(0));
+        assert_eq!(run(&program(0xFFFF)).1, StopReason::Exited(1));
+    }
+
+    #[test]
+    fn file_calls_use_the_file_system() {
+        let program = [
+            0xB4, 0x3C, // mov ah, 3Ch
+            0xBA, 0x1A, 0x01, // mov dx, name
+            0xCD, 0x21, // int 21h
+            0x93, // xchg bx, ax
+            0xB4, 0x40, // mov ah, 40h
+            0xB9, 0x02, 0x00, // mov cx, 2
+            0xCD, 0x21, // int 21h
+            0xB4, 0x4E, // mov ah, 4Eh
+            0xBA, 0x20, 0x01, // mov dx, pattern
+            0xCD, 0x21, // int 21h
+            0xB4, 0x4C, // mov ah, 4Ch
+            0xCD, 0x21, // int 21h
+            b'O', b'U', b'T', b'.', b'X', 0, // 0x11a: name
+            b'*', b'.', b'X', 0, // 0x120: pattern
+        ];
+        let (_, reason) = run(&program);
+        assert_eq!(reason, StopReason::Exited(1), "without files, calls fail");
+
+        let mut emulator = Emulator::new(&program);
+        emulator.files = Some(FileSystem::default());
+        assert_eq!(emulator.run(100), StopReason::Exited(0));
+        let files = emulator.files.as_ref().unwrap();
+        assert_eq!(files.files["OUT.X"], b"OU");
+        assert_eq!(emulator.read_u16(LOAD_SEGMENT, 0x80 + 0x1A), 2);
+        assert_eq!(
+            emulator.read_bytes(LOAD_SEGMENT, 0x80 + 0x1E, 6),
+            b"OUT.X\0"
+        );
+
+        let mut emulator = Emulator::new(&program);
+        let mut files = FileSystem::default();
+        files.read_only = true;
+        emulator.files = Some(files);
+        assert_eq!(
+            emulator.run(100),
+            StopReason::Exited(crate::files::NO_MORE_FILES as u8)
+        );
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 5.  Calling single functions
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn calls_return_with_the_function_results() {
+        let program = [
+            0xC3, // ret
+            0xB4, 0x02, // 0x101: mov ah, 2
+            0xB2, 0x3E, // mov dl, '>'
+            0xCD, 0x21, // int 21h
+            0x89, 0x1E, 0x00, 0x02, // mov [0x200], bx
+            0xC3, // ret
+        ];
+        let mut emulator = Emulator::new(&program);
+        emulator.registers.set("BX", 0xBEEF).unwrap();
+        let result = emulator.call(0x101, 100);
+        assert_eq!(result.stopped, None);
+        assert_eq!(result.output, b">");
+        assert_eq!(result.instructions, 5);
+        assert_eq!(result.registers.sp, 0xFFFE);
+        assert_eq!(emulator.read_bytes(LOAD_SEGMENT, 0x200, 2), [0xEF, 0xBE]);
+        assert!(emulator.registers.set("eax", 0).is_err());
+    }
+
+    #[test]
+    fn calls_that_never_return_report_why() {
+        // jmp $ / mov ah, 4ch / int 21h
+        let mut emulator = Emulator::new(&[0xEB, 0xFE, 0xB4, 0x4C, 0xCD, 0x21]);
+        let result = emulator.call(0x100, 50);
+        assert_eq!(result.stopped, Some(StopReason::BudgetExhausted));
+        assert_eq!(result.instructions, 50);
+        assert_eq!(
+            emulator.call(0x102, 50).stopped,
+            Some(StopReason::Exited(0))
+        );
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 6.  Tracing
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn trace_records_executed_and_indirect_targets() {
+        let program = [
+            0xBB, 0x06, 0x01, // mov bx, 106h
+            0xFF, 0xE3, // jmp bx
+            0x90, // (skipped)
+            0xC3, // ret
+        ];
+        let (emulator, _) = run(&program);
+        let executed: Vec<Address> = emulator.trace.executed.iter().copied().collect();
+        assert_eq!(executed, vec![0x100, 0x103, 0x106]);
+        assert!(emulator.trace.jump_targets.contains(&0x106));
+        assert!(emulator.trace.call_targets.is_empty());
+    }
+}
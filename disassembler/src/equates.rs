@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use iced_x86::OpKind;
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+use crate::style::Style;
+
+/// What an equate names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EquateKind {
+    /// An address the listing refers to
+    Address,
+    /// A value with a well-known meaning
+    Constant,
+}
+
+/// A name given to a value with `equ`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Equate {
+    /// The name
+    pub name: String,
+    /// The value named
+    pub value: u16,
+    /// Whether the value is an address or a constant
+    pub kind: EquateKind,
+    /// Written after the value, what a constant means
+    pub comment: Option<String>,
+}
+
+impl Equate {
+    /// The `equ` line defining the name, written in `style`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::equates::{Equate, EquateKind};
+    /// use disassembler::style::Style;
+    ///
+    /// let equate = Equate {
+    ///     name: "COLOUR_TEXT_MEMORY_SEGMENT".to_string(),
+    ///     value: 0xB800,
+    ///     kind: EquateKind::Constant,
+    ///     comment: Some("colour text memory segment".to_string()),
+    /// };
+    /// assert_eq!(
+    ///     equate.line(&Style::default()),
+    ///     "COLOUR_TEXT_MEMORY_SEGMENT equ 0xb800 ; colour text memory segment"
+    /// );
+    /// ```
+    pub fn line(&self, style: &Style) -> String {
+        let mut line = format!(
+            "{} {} {}",
+            self.name,
+            style.mnemonic("equ"),
+            style.hex(self.value as u64, 4)
+        );
+        if let Some(comment) = &self.comment {
+            line.push_str(" ; ");
+            line.push_str(comment);
+        }
+        line
+    }
+}
+
+/// The names a listing uses without defining them
+///
+/// These are the labels the listing has no line to put in front of, such as
+/// memory past the end of the file, the PSP, and jump targets inside other
+/// instructions, and a name for every value from the
+/// [`ConstantTable`](crate::constants::ConstantTable) the code uses as an
+/// immediate. A listing written with
+/// [`DisassemblerOptions::equates`](crate::disassemble::DisassemblerOptions::equates)
+/// refers to both by name, so it assembles with these defined in front of
+/// it, usually in an include file.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::equates::equates;
+///
+/// // mov ax, 0xb800 / mov [0x200], ax / ret
+/// let data = vec![0xB8, 0x00, 0xB8, 0xA3, 0x00, 0x02, 0xC3];
+/// let names: Vec<String> = equates(&Disassembler::new(data))
+///     .into_iter()
+///     .map(|equate| equate.name)
+///     .collect();
+/// assert_eq!(names, ["bss_0x0200", "COLOUR_TEXT_MEMORY_SEGMENT"]);
+/// ```
+pub fn equates(disassembler: &Disassembler) -> Vec<Equate> {
+    let mut found: Vec<Equate> = undefined_labels(disassembler)
+        .into_iter()
+        .map(|(value, name)| Equate {
+            name,
+            value,
+            kind: EquateKind::Address,
+            comment: None,
+        })
+        .collect();
+
+    for (value, meaning) in constants_used(disassembler) {
+        let mut name = constant_name(&meaning);
+        if found.iter().any(|equate| equate.name == name) {
+            name = format!("{name}_{value:04X}");
+        }
+        found.push(Equate {
+            name,
+            value,
+            kind: EquateKind::Constant,
+            comment: Some(meaning),
+        });
+    }
+    found
+}
+
+/// The labels at addresses no line of the listing starts at, by address
+pub(crate) fn undefined_labels(disassembler: &Disassembler) -> BTreeMap<Address, String> {
+    let mut labels = BTreeMap::new();
+    for label in &disassembler.labels.0 {
+        if disassembler.instructions.index_at(label.address).is_none() {
+            labels
+                .entry(label.address)
+                .or_insert_with(|| label.name.clone());
+        }
+    }
+    labels
+}
+
+/// The values from the constant table the code uses as 16-bit immediates,
+/// with their meanings
+pub(crate) fn constants_used(disassembler: &Disassembler) -> BTreeMap<u16, String> {
+    let constants = disassembler.constants();
+    let mut used = BTreeMap::new();
+    for instruction in &disassembler.instructions.0 {
+        if is_data(instruction) {
+            continue;
+        }
+        for operand in 0..instruction.op_count() {
+            if instruction.op_kind(operand) != OpKind::Immediate16 {
+                continue;
+            }
+            let value = instruction.immediate16();
+            if let Some(meaning) = constants.get(value) {
+                used.insert(value, meaning.to_string());
+            }
+        }
+    }
+    used
+}
+
+/// Makes an assembler name out of what a constant means, such as
+/// `VGA_DAC_DATA_PORT` from "VGA DAC data port"
+///
+/// Anything after a comma is left out, as is anything that is not a letter
+/// or digit.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::equates::constant_name;
+///
+/// assert_eq!(constant_name("PIT divisor for ~100 Hz"), "PIT_DIVISOR_FOR_100_HZ");
+/// assert_eq!(constant_name("warm reboot flag, stored at 0040:0072"), "WARM_REBOOT_FLAG");
+/// assert_eq!(constant_name("1st port"), "_1ST_PORT");
+/// ```
+pub fn constant_name(meaning: &str) -> String {
+    let head = meaning.split(',').next().unwrap_or_default();
+    let words: Vec<String> = head
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_ascii_uppercase())
+        .collect();
+    let name = words.join("_");
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{name}")
+    } else {
+        name
+    }
+}
+
+/// Writes `equates` as an include file, one `equ` line each, in `style`
+pub fn write_equates<W: Write>(f: &mut W, equates: &[Equate], style: &Style) -> io::Result<()> {
+    writeln!(
+        f,
+        "; Generated by DosDisassm: names the listing uses but does not define"
+    )?;
+    writeln!(
+        f,
+        "; Edit the listing, not this file, which is written again on export"
+    )?;
+    let (labels, constants): (Vec<&Equate>, Vec<&Equate>) = equates
+        .iter()
+        .partition(|equate| equate.kind == EquateKind::Address);
+    if !labels.is_empty() {
+        writeln!(f)?;
+        writeln!(f, "; addresses")?;
+        for equate in labels {
+            writeln!(f, "{}", equate.line(style))?;
+        }
+    }
+    if !constants.is_empty() {
+        writeln!(f)?;
+        writeln!(f, "; constants")?;
+        for equate in constants {
+            writeln!(f, "{}", equate.line(style))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassemble::DisassemblerOptions;
+
+    #[test]
+    fn the_listing_uses_the_names_it_does_not_define() {
+        // mov ax, 0xb800 / mov [0x200], ax / ret
+        let data = vec![0xB8, 0x00, 0xB8, 0xA3, 0x00, 0x02, 0xC3];
+        let disassembler = Disassembler::new(data);
+        let opts = DisassemblerOptions {
+            equates: true,
+            ..DisassemblerOptions::default()
+        };
+        let mut listing = Vec::new();
+        disassembler.disassemble_stream(&mut listing, opts).unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+        assert!(
+            listing.contains("mov ax,COLOUR_TEXT_MEMORY_SEGMENT"),
+            "{listing}"
+        );
+        assert!(listing.contains("[bss_0x0200]"), "{listing}");
+        assert!(!listing.contains("absolute"), "{listing}");
+
+        let mut include = Vec::new();
+        write_equates(&mut include, &equates(&disassembler), &Style::default()).unwrap();
+        let include = String::from_utf8(include).unwrap();
+        assert!(include.contains("\nbss_0x0200 equ 0x0200\n"), "{include}");
+    }
+}
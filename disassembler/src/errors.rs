@@ -0,0 +1,244 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{FlowControl, Mnemonic, OpKind, RflagsBits};
+
+use crate::consts::Address;
+use crate::disassemble::Disassembler;
+use crate::syscall::{SyscallType, error_name};
+
+/// How many instructions after a call the carry flag check may follow
+const CHECK_WINDOW: usize = 3;
+
+/// How many instructions an error routine may run before it exits
+const ROUTINE_LENGTH: usize = 12;
+
+/// A `jc` after an int 21h call, taken when the call fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErrorBranch {
+    /// The address of the int 21h call
+    pub syscall: Address,
+    /// The function called
+    pub number: SyscallType,
+    /// The address of the `jc`
+    pub branch: Address,
+    /// Where the `jc` jumps to
+    pub handler: Address,
+}
+
+impl Display for ErrorBranch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "on error from {:?}:", self.number)?;
+        for (index, &code) in self.number.error_codes().iter().enumerate() {
+            let separator = if index == 0 { "" } else { "," };
+            write!(
+                f,
+                "{separator} {code} {}",
+                error_name(code).unwrap_or("unknown")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A routine that prints a message with AH=09h and exits with AH=4Ch
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErrorRoutine {
+    /// The start of the routine
+    pub address: Address,
+    /// The message printed, if DS:DX pointed into the program
+    pub message: Option<String>,
+    /// The address of the exit call
+    pub exit: Address,
+}
+
+impl Display for ErrorRoutine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "error routine: prints {message:?} and exits"),
+            None => write!(f, "error routine: prints a message and exits"),
+        }
+    }
+}
+
+/// Finds `jc` checks right after int 21h calls that report errors in the carry flag
+///
+/// The check may follow the call after a few instructions that leave the
+/// carry flag alone, such as moving the returned handle.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::errors::find_error_branches;
+///
+/// // mov ah, 0x3d / int 21h / jc 0x107 / ret / ret
+/// let data = vec![0xB4, 0x3D, 0xCD, 0x21, 0x72, 0x01, 0xC3, 0xC3];
+/// let branches = find_error_branches(&Disassembler::new(data));
+/// assert_eq!(branches[0].handler, 0x107);
+/// ```
+pub fn find_error_branches(disassembler: &Disassembler) -> Vec<ErrorBranch> {
+    let instructions = &disassembler.instructions.0;
+    let mut found = Vec::new();
+    for syscall in &disassembler.syscall_list.0 {
+        if syscall.number.error_codes().is_empty() {
+            continue;
+        }
+        let Some(index) = disassembler.instructions.index_at(syscall.address) else {
+            continue;
+        };
+        for next in instructions[index + 1..].iter().take(CHECK_WINDOW) {
+            if next.mnemonic() == Mnemonic::Jb && next.op0_kind() == OpKind::NearBranch16 {
+                found.push(ErrorBranch {
+                    syscall: syscall.address,
+                    number: syscall.number,
+                    branch: next.ip() as Address,
+                    handler: next.near_branch_target() as Address,
+                });
+                break;
+            }
+            if next.flow_control() != FlowControl::Next
+                || next.rflags_modified() & RflagsBits::CF != 0
+            {
+                break;
+            }
+        }
+    }
+    found
+}
+
+/// Finds routines that print a message and exit
+///
+/// Routines are looked for at error handlers and at the targets of jumps and
+/// calls: from there the straight-line code must display a string with
+/// AH=09h and then terminate with AH=4Ch.
+pub fn find_error_routines(disassembler: &Disassembler) -> Vec<ErrorRoutine> {
+    let instructions = &disassembler.instructions.0;
+    let mut starts: Vec<Address> = instructions
+        .iter()
+        .filter(|instruction| {
+            matches!(
+                instruction.flow_control(),
+                FlowControl::UnconditionalBranch
+                    | FlowControl::ConditionalBranch
+                    | FlowControl::Call
+            ) && instruction.op0_kind() == OpKind::NearBranch16
+        })
+        .map(|instruction| instruction.near_branch_target() as Address)
+        .collect();
+    starts.sort_unstable();
+    starts.dedup();
+
+    let mut found = Vec::new();
+    for start in starts {
+        let Some(index) = disassembler.instructions.index_at(start) else {
+            continue;
+        };
+        let mut message = None;
+        for instruction in instructions[index..].iter().take(ROUTINE_LENGTH) {
+            let syscall = disassembler
+                .syscall_list
+                .get_by_address(instruction.ip() as Address);
+            match syscall.map(|syscall| syscall.number) {
+                Some(SyscallType::DisplayString) => message = syscall.map(|syscall| syscall.dx),
+                Some(SyscallType::TerminateWithCode) if message.is_some() => {
+                    found.push(ErrorRoutine {
+                        address: start,
+                        message: message.flatten().and_then(|address| {
+                            disassembler
+                                .string_constant_list
+                                .get_string_constant(address)
+                                .map(|string| string.value.trim_end_matches('$').to_string())
+                        }),
+                        exit: instruction.ip() as Address,
+                    });
+                    break;
+                }
+                _ => {}
+            }
+            if instruction.flow_control() != FlowControl::Next
+                && instruction.flow_control() != FlowControl::Interrupt
+            {
+                break;
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Opens `A.TXT`, jumping to an error routine that prints `no file$` and exits
+    fn program() -> Vec<u8> {
+        let mut data = vec![
+            0xBA, 0x17, 0x01, // mov dx, 0x117
+            0xB4, 0x3D, 0xCD, 0x21, // mov ah, 0x3d / int 21h
+            0x89, 0xC3, // mov bx, ax
+            0x72, 0x01, // jc 0x10c
+            0xC3, // ret
+            0xBA, 0x1D, 0x01, // 0x10c: mov dx, 0x11d
+            0xB4, 0x09, 0xCD, 0x21, // mov ah, 9 / int 21h
+            0xB4, 0x4C, 0xCD, 0x21, // mov ah, 0x4c / int 21h
+        ];
+        data.extend(b"A.TXT\0no file$");
+        data
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Carry flag checks
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn jc_after_a_call_is_an_error_branch() {
+        let branches = find_error_branches(&Disassembler::new(program()));
+        assert_eq!(
+            branches,
+            vec![ErrorBranch {
+                syscall: 0x105,
+                number: SyscallType::OpenFile2,
+                branch: 0x109,
+                handler: 0x10C,
+            }]
+        );
+        assert!(
+            branches[0]
+                .to_string()
+                .starts_with("on error from OpenFile2: 2 file not found, 3 path not found")
+        );
+    }
+
+    #[test]
+    fn carry_changed_before_the_jc_is_not_a_check() {
+        let mut data = program();
+        data[7] = 0xF8; // mov bx, ax -> clc / nop
+        data[8] = 0x90;
+        assert!(find_error_branches(&Disassembler::new(data)).is_empty());
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Error routines
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn print_and_exit_is_an_error_routine() {
+        let disassembler = Disassembler::new(program());
+        assert_eq!(
+            find_error_routines(&disassembler),
+            vec![ErrorRoutine {
+                address: 0x10C,
+                message: Some("no file".to_string()),
+                exit: 0x115,
+            }]
+        );
+        let label = disassembler.labels.get_by_address(0x10C).expect("a label");
+        assert_eq!(label.name, "error_exit_0x010c");
+        assert!(
+            disassembler
+                .comment_list
+                .get_comments(0x109)
+                .iter()
+                .any(|comment| comment.comment_text.starts_with("on error from OpenFile2"))
+        );
+    }
+}
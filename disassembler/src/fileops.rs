@@ -0,0 +1,333 @@
+use std::fmt::{self, Display};
+
+use crate::consts::Address;
+use crate::disassemble::Disassembler;
+use crate::syscall::{Syscall, SyscallType};
+
+/// The handles DOS opens for every program: stdin, stdout, stderr, the
+/// auxiliary device and the printer
+const STANDARD_HANDLES: u16 = 5;
+
+/// What a program does to a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FileAction {
+    /// Creates the file, emptying it if it is there
+    Create,
+    /// Opens the file
+    Open,
+    /// Closes the file
+    Close,
+    /// Reads from the file
+    Read,
+    /// Writes to the file
+    Write,
+    /// Moves the file pointer
+    Seek,
+    /// Deletes the file
+    Delete,
+    /// Renames the file
+    Rename,
+    /// Reads or changes the file's attributes
+    Attributes,
+    /// Reads or changes the file's date and time
+    Timestamp,
+    /// Searches for files matching the name
+    Find,
+    /// Runs the file as a program
+    Execute,
+}
+
+impl Display for FileAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FileAction::Create => "create",
+            FileAction::Open => "open",
+            FileAction::Close => "close",
+            FileAction::Read => "read",
+            FileAction::Write => "write",
+            FileAction::Seek => "seek",
+            FileAction::Delete => "delete",
+            FileAction::Rename => "rename",
+            FileAction::Attributes => "attributes",
+            FileAction::Timestamp => "timestamp",
+            FileAction::Find => "find",
+            FileAction::Execute => "execute",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// How a file is opened, from the low bits of AL for int 21h AH=3Dh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AccessMode {
+    /// Only for reading
+    Read,
+    /// Only for writing
+    Write,
+    /// For reading and writing
+    ReadWrite,
+}
+
+impl Display for AccessMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessMode::Read => write!(f, "read"),
+            AccessMode::Write => write!(f, "write"),
+            AccessMode::ReadWrite => write!(f, "read/write"),
+        }
+    }
+}
+
+/// One int 21h call doing something to a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileCall {
+    /// The address of the int 21h
+    pub address: Address,
+    /// What it does
+    pub action: FileAction,
+    /// How an open asks for the file, if AL was tracked
+    pub mode: Option<AccessMode>,
+}
+
+impl Display for FileCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.action)?;
+        if let Some(mode) = self.mode {
+            write!(f, " ({mode})")?;
+        }
+        Ok(())
+    }
+}
+
+/// A file and everything the program does to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileTouched {
+    /// The name the program passes, or `None` for calls on handles and
+    /// names that could not be read
+    pub name: Option<String>,
+    /// The calls, in address order
+    pub calls: Vec<FileCall>,
+}
+
+impl Display for FileTouched {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{name}: ")?,
+            None => write!(f, "by handle or unknown name: ")?,
+        }
+        let mut shown: Vec<String> = Vec::new();
+        for call in &self.calls {
+            let call = call.to_string();
+            if !shown.contains(&call) {
+                shown.push(call);
+            }
+        }
+        write!(f, "{}", shown.join(", "))
+    }
+}
+
+/// The files a program touches, with the names it passes to DOS resolved
+/// where they are strings in the program
+///
+/// Calls naming a file, in a zero-terminated string at DS:DX or in a file
+/// control block, are grouped by the name, ignoring case. Calls on handles
+/// cannot be tied to a name without running the program, so they are
+/// grouped together, leaving out the standard handles DOS opens for every
+/// program, such as stdout.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::fileops::FileActivity;
+///
+/// let data = vec![
+///     0xB8, 0x02, 0x3D, // mov ax, 0x3d02
+///     0xBA, 0x0B, 0x01, // mov dx, 0x10b
+///     0xCD, 0x21, // int 21h
+///     0xB4, 0x3E, // mov ah, 0x3e
+///     0xC3, // ret
+///     b'A', b'.', b'D', b'A', b'T', 0,
+/// ];
+/// let activity = FileActivity::analyze(&Disassembler::new(data));
+/// assert_eq!(activity.files[0].to_string(), "A.DAT: open (read/write)");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileActivity {
+    /// The files named, in the order the program first touches them, then
+    /// the calls on handles
+    pub files: Vec<FileTouched>,
+}
+
+impl FileActivity {
+    /// Finds the file calls in the program and groups them by file
+    pub fn analyze(disassembler: &Disassembler) -> FileActivity {
+        let mut files: Vec<FileTouched> = Vec::new();
+        let mut by_handle = Vec::new();
+        for syscall in &disassembler.syscall_list.0 {
+            let Some((action, naming)) = file_action(syscall.number) else {
+                continue;
+            };
+            let call = FileCall {
+                address: syscall.address,
+                action,
+                mode: open_mode(syscall),
+            };
+            let name = match naming {
+                Naming::Path => syscall
+                    .dx
+                    .and_then(|address| disassembler.read_asciiz(address))
+                    .filter(|name| is_name(name)),
+                Naming::ControlBlock => syscall
+                    .dx
+                    .and_then(|address| control_block_name(disassembler, address)),
+                Naming::Handle => {
+                    if syscall.bx.is_some_and(|handle| handle < STANDARD_HANDLES) {
+                        continue;
+                    }
+                    None
+                }
+            };
+            let Some(name) = name else {
+                by_handle.push(call);
+                continue;
+            };
+            match files.iter_mut().find(|file| {
+                file.name
+                    .as_ref()
+                    .is_some_and(|known| known.eq_ignore_ascii_case(&name))
+            }) {
+                Some(file) => file.calls.push(call),
+                None => files.push(FileTouched {
+                    name: Some(name),
+                    calls: vec![call],
+                }),
+            }
+        }
+        if !by_handle.is_empty() {
+            files.push(FileTouched {
+                name: None,
+                calls: by_handle,
+            });
+        }
+        for file in &mut files {
+            file.calls.sort_by_key(|call| call.address);
+        }
+        FileActivity { files }
+    }
+}
+
+/// Where a file call takes the file from
+enum Naming {
+    /// A zero-terminated path at DS:DX
+    Path,
+    /// A file control block at DS:DX
+    ControlBlock,
+    /// A handle in BX
+    Handle,
+}
+
+/// What a call does to a file and how it names it, if it is a file call
+fn file_action(number: SyscallType) -> Option<(FileAction, Naming)> {
+    Some(match number {
+        SyscallType::CreateFile | SyscallType::CreateNewFile => (FileAction::Create, Naming::Path),
+        SyscallType::OpenFile2 => (FileAction::Open, Naming::Path),
+        SyscallType::DeleteFile2 => (FileAction::Delete, Naming::Path),
+        SyscallType::RenameFile2 => (FileAction::Rename, Naming::Path),
+        SyscallType::GetOrSetFileAttr => (FileAction::Attributes, Naming::Path),
+        SyscallType::FindFirstFile2 => (FileAction::Find, Naming::Path),
+        SyscallType::ExecuteProgram => (FileAction::Execute, Naming::Path),
+        SyscallType::CloseFile2 => (FileAction::Close, Naming::Handle),
+        SyscallType::ReadFileOrDevice => (FileAction::Read, Naming::Handle),
+        SyscallType::WriteFileOrDevice => (FileAction::Write, Naming::Handle),
+        SyscallType::MoveFilePointer => (FileAction::Seek, Naming::Handle),
+        SyscallType::GetOrSetFileDateTime => (FileAction::Timestamp, Naming::Handle),
+        SyscallType::CreateOrTruncateFile => (FileAction::Create, Naming::ControlBlock),
+        SyscallType::OpenFile => (FileAction::Open, Naming::ControlBlock),
+        SyscallType::CloseFile => (FileAction::Close, Naming::ControlBlock),
+        SyscallType::SequentialRead | SyscallType::RandomRead | SyscallType::RandomBlockRead => {
+            (FileAction::Read, Naming::ControlBlock)
+        }
+        SyscallType::SequentialWrite | SyscallType::RandomWrite | SyscallType::RandomBlockWrite => {
+            (FileAction::Write, Naming::ControlBlock)
+        }
+        SyscallType::DeleteFile => (FileAction::Delete, Naming::ControlBlock),
+        SyscallType::RenameFile => (FileAction::Rename, Naming::ControlBlock),
+        SyscallType::FindFirstFile => (FileAction::Find, Naming::ControlBlock),
+        _ => return None,
+    })
+}
+
+/// How an int 21h AH=3Dh call opens its file
+fn open_mode(syscall: &Syscall) -> Option<AccessMode> {
+    if syscall.number != SyscallType::OpenFile2 {
+        return None;
+    }
+    match syscall.al? & 0x07 {
+        0 => Some(AccessMode::Read),
+        1 => Some(AccessMode::Write),
+        2 => Some(AccessMode::ReadWrite),
+        _ => None,
+    }
+}
+
+/// Returns true if `name` looks like a file name rather than whatever
+/// bytes DX happened to point at
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 128 && name.bytes().all(|byte| (0x21..0x7F).contains(&byte))
+}
+
+/// The `NAME.EXT` in the file control block at `address`, which may be an
+/// extended one starting with 0xFF
+fn control_block_name(disassembler: &Disassembler, address: Address) -> Option<String> {
+    let bytes = disassembler.bytes_at(address)?;
+    let start = if bytes.first() == Some(&0xFF) { 8 } else { 1 };
+    let field = |range: std::ops::Range<usize>| -> Option<String> {
+        let text: String = bytes.get(range)?.iter().map(|&byte| byte as char).collect();
+        Some(text.trim_end().to_string())
+    };
+    let name = field(start..start + 8)?;
+    let extension = field(start + 8..start + 11)?;
+    let name = if extension.is_empty() {
+        name
+    } else {
+        format!("{name}.{extension}")
+    };
+    is_name(&name).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calls_are_grouped_by_name_and_handle() {
+        let data = vec![
+            0xB4, 0x3C, // mov ah, 0x3c
+            0xBA, 0x1B, 0x01, // mov dx, 0x11b
+            0xCD, 0x21, // int 21h
+            0xBB, 0x05, 0x00, // mov bx, 5
+            0xB4, 0x40, // mov ah, 0x40
+            0xCD, 0x21, // int 21h
+            0xBB, 0x01, 0x00, // mov bx, 1
+            0xCD, 0x21, // int 21h (stdout, left out)
+            0xB4, 0x13, // mov ah, 0x13
+            0xBA, 0x21, 0x01, // mov dx, 0x121
+            0xCD, 0x21, // int 21h
+            0xC3, // ret
+            b'o', b'u', b't', b'.', b'l', 0, // 0x11b
+            0, b'O', b'U', b'T', b' ', b' ', b' ', b' ', b' ', b'L', b' ', b' ', // 0x121
+        ];
+        let activity = FileActivity::analyze(&Disassembler::new(data));
+        let files: Vec<String> = activity.files.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            files,
+            ["out.l: create, delete", "by handle or unknown name: write"]
+        );
+    }
+}
@@ -0,0 +1,427 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{self, Display};
+
+/// DOS error: function number invalid
+pub const INVALID_FUNCTION: u16 = 0x01;
+/// DOS error: file not found
+pub const FILE_NOT_FOUND: u16 = 0x02;
+/// DOS error: path not found
+pub const PATH_NOT_FOUND: u16 = 0x03;
+/// DOS error: too many open files
+pub const TOO_MANY_OPEN_FILES: u16 = 0x04;
+/// DOS error: access denied
+pub const ACCESS_DENIED: u16 = 0x05;
+/// DOS error: invalid handle
+pub const INVALID_HANDLE: u16 = 0x06;
+/// DOS error: no more files
+pub const NO_MORE_FILES: u16 = 0x12;
+
+/// The first handle given to a file, after the five DOS opens for every program
+const FIRST_HANDLE: u16 = 5;
+
+/// How many files a program may have open at once, as with `FILES=20`
+const MAX_OPEN: usize = 15;
+
+/// A file a program has open
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OpenFile {
+    /// The file's name
+    name: String,
+    /// Where the next read or write starts
+    position: usize,
+    /// Whether the file was opened for writing
+    writable: bool,
+}
+
+/// A file found by [`FileSystem::find_first`] or [`FileSystem::find_next`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundFile {
+    /// The file's name
+    pub name: String,
+    /// The file's length in bytes
+    pub size: u32,
+}
+
+/// How a file differs after a run, see [`FileSystem::changes`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FileChange {
+    /// The program created the file
+    Created(String),
+    /// The program changed what is in the file
+    Changed(String),
+    /// The program deleted the file
+    Deleted(String),
+}
+
+impl Display for FileChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileChange::Created(name) => write!(f, "created {name}"),
+            FileChange::Changed(name) => write!(f, "changed {name}"),
+            FileChange::Deleted(name) => write!(f, "deleted {name}"),
+        }
+    }
+}
+
+/// The files a program sees through int 21h in the emulator
+///
+/// The file system is a single directory held in memory, so nothing a
+/// program does reaches the host: frontends that mount a real directory
+/// copy its files in and decide themselves whether to save the
+/// [`FileSystem::changes`] back. Names are upper case 8.3 names without a
+/// drive or directory. With `read_only` set, creating, deleting and opening
+/// files for writing fail with access denied.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::files::{ACCESS_DENIED, FileSystem};
+///
+/// let mut files = FileSystem::default();
+/// files.files.insert("README.TXT".to_string(), b"hello".to_vec());
+///
+/// let handle = files.open("c:\\readme.txt", 0).unwrap();
+/// assert_eq!(files.read(handle, 100), Ok(b"hello".to_vec()));
+///
+/// files.read_only = true;
+/// assert_eq!(files.create("NEW.TXT"), Err(ACCESS_DENIED));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileSystem {
+    /// What is in each file, by name
+    pub files: BTreeMap<String, Vec<u8>>,
+    /// Whether programs may only read files
+    pub read_only: bool,
+    /// The files open, by handle less [`FIRST_HANDLE`]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    open: Vec<Option<OpenFile>>,
+    /// The files still to be returned by [`FileSystem::find_next`]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    search: VecDeque<String>,
+}
+
+impl FileSystem {
+    /// Creates the file at `path`, or empties it if it exists, and opens it
+    /// for writing
+    pub fn create(&mut self, path: &str) -> Result<u16, u16> {
+        let name = name(path)?;
+        if self.read_only {
+            return Err(ACCESS_DENIED);
+        }
+        self.files.insert(name.clone(), Vec::new());
+        self.add_handle(name, true)
+    }
+
+    /// Opens the file at `path` with the access in the low bits of `mode`,
+    /// as given to int 21h/3Dh in AL
+    pub fn open(&mut self, path: &str, mode: u8) -> Result<u16, u16> {
+        let name = name(path)?;
+        let writable = match mode & 0x07 {
+            0 => false,
+            1 | 2 => true,
+            _ => return Err(INVALID_FUNCTION),
+        };
+        if !self.files.contains_key(&name) {
+            return Err(FILE_NOT_FOUND);
+        }
+        if writable && self.read_only {
+            return Err(ACCESS_DENIED);
+        }
+        self.add_handle(name, writable)
+    }
+
+    /// Closes `handle`
+    pub fn close(&mut self, handle: u16) -> Result<(), u16> {
+        if handle < FIRST_HANDLE {
+            return Ok(());
+        }
+        self.open
+            .get_mut((handle - FIRST_HANDLE) as usize)
+            .and_then(Option::take)
+            .map(|_| ())
+            .ok_or(INVALID_HANDLE)
+    }
+
+    /// Reads up to `count` bytes from `handle`
+    pub fn read(&mut self, handle: u16, count: u16) -> Result<Vec<u8>, u16> {
+        let (file, contents) = self.handle(handle)?;
+        let start = file.position.min(contents.len());
+        let end = (start + count as usize).min(contents.len());
+        let bytes = contents[start..end].to_vec();
+        file.position = end.max(file.position);
+        Ok(bytes)
+    }
+
+    /// Writes `bytes` to `handle`, returning how many were written
+    ///
+    /// Writing nothing cuts the file off at the current position, like DOS.
+    pub fn write(&mut self, handle: u16, bytes: &[u8]) -> Result<u16, u16> {
+        let (file, contents) = self.handle(handle)?;
+        if !file.writable {
+            return Err(ACCESS_DENIED);
+        }
+        let start = file.position;
+        if bytes.is_empty() {
+            contents.resize(start, 0);
+            return Ok(0);
+        }
+        let end = start + bytes.len();
+        if contents.len() < end {
+            contents.resize(end, 0);
+        }
+        contents[start..end].copy_from_slice(bytes);
+        file.position = end;
+        Ok(bytes.len() as u16)
+    }
+
+    /// Moves `handle` by `offset` from the start (`origin` 0), the current
+    /// position (1) or the end (2), returning the new position
+    pub fn seek(&mut self, handle: u16, origin: u8, offset: i32) -> Result<u32, u16> {
+        let (file, contents) = self.handle(handle)?;
+        let base = match origin {
+            0 => 0,
+            1 => file.position as i64,
+            2 => contents.len() as i64,
+            _ => return Err(INVALID_FUNCTION),
+        };
+        let position = u32::try_from(base + offset as i64).map_err(|_| INVALID_FUNCTION)?;
+        file.position = position as usize;
+        Ok(position)
+    }
+
+    /// Deletes the file at `path`
+    pub fn delete(&mut self, path: &str) -> Result<(), u16> {
+        let name = name(path)?;
+        if !self.files.contains_key(&name) {
+            return Err(FILE_NOT_FOUND);
+        }
+        if self.read_only {
+            return Err(ACCESS_DENIED);
+        }
+        self.files.remove(&name);
+        Ok(())
+    }
+
+    /// Starts a search for the files matching `pattern`, which may use the
+    /// `*` and `?` wildcards, returning the first
+    pub fn find_first(&mut self, pattern: &str) -> Result<FoundFile, u16> {
+        let pattern = name(pattern)?;
+        self.search = self
+            .files
+            .keys()
+            .filter(|name| matches(&pattern, name))
+            .cloned()
+            .collect();
+        self.find_next()
+    }
+
+    /// The next file matching the pattern given to [`FileSystem::find_first`]
+    pub fn find_next(&mut self) -> Result<FoundFile, u16> {
+        while let Some(name) = self.search.pop_front() {
+            // a file deleted since the search started is skipped
+            if let Some(contents) = self.files.get(&name) {
+                let size = contents.len() as u32;
+                return Ok(FoundFile { name, size });
+            }
+        }
+        Err(NO_MORE_FILES)
+    }
+
+    /// How the files differ from `before`, such as the file system a run
+    /// started with
+    pub fn changes(&self, before: &FileSystem) -> Vec<FileChange> {
+        let mut changes = Vec::new();
+        for (name, contents) in &self.files {
+            match before.files.get(name) {
+                None => changes.push(FileChange::Created(name.clone())),
+                Some(old) if old != contents => changes.push(FileChange::Changed(name.clone())),
+                Some(_) => {}
+            }
+        }
+        for name in before.files.keys() {
+            if !self.files.contains_key(name) {
+                changes.push(FileChange::Deleted(name.clone()));
+            }
+        }
+        changes
+    }
+
+    fn add_handle(&mut self, name: String, writable: bool) -> Result<u16, u16> {
+        let file = OpenFile {
+            name,
+            position: 0,
+            writable,
+        };
+        let slot = match self.open.iter().position(Option::is_none) {
+            Some(slot) => slot,
+            None if self.open.len() < MAX_OPEN => {
+                self.open.push(None);
+                self.open.len() - 1
+            }
+            None => return Err(TOO_MANY_OPEN_FILES),
+        };
+        self.open[slot] = Some(file);
+        Ok(FIRST_HANDLE + slot as u16)
+    }
+
+    /// The open file behind `handle` and what is in it
+    fn handle(&mut self, handle: u16) -> Result<(&mut OpenFile, &mut Vec<u8>), u16> {
+        let file = handle
+            .checked_sub(FIRST_HANDLE)
+            .and_then(|slot| self.open.get_mut(slot as usize))
+            .and_then(Option::as_mut)
+            .ok_or(INVALID_HANDLE)?;
+        let contents = self.files.get_mut(&file.name).ok_or(INVALID_HANDLE)?;
+        Ok((file, contents))
+    }
+}
+
+/// The 8.3 name a program means by `path`, or `None` if it is in a
+/// directory or is not a valid DOS name
+///
+/// ```
+/// use disassembler::files::dos_name;
+///
+/// assert_eq!(dos_name("a:\\command.com").as_deref(), Some("COMMAND.COM"));
+/// assert_eq!(dos_name("dos\\command.com"), None);
+/// assert_eq!(dos_name("program.backup"), None);
+/// ```
+pub fn dos_name(path: &str) -> Option<String> {
+    name(path).ok()
+}
+
+/// The 8.3 name a program means by `path`, or the DOS error for it
+fn name(path: &str) -> Result<String, u16> {
+    let path = path.to_ascii_uppercase();
+    let path = match path.as_bytes() {
+        [drive, b':', ..] if drive.is_ascii_alphabetic() => &path[2..],
+        _ => &path,
+    };
+    let path = path.trim_start_matches(['\\', '/']);
+    if path.contains(['\\', '/']) {
+        return Err(PATH_NOT_FOUND);
+    }
+    let (stem, extension) = path.split_once('.').unwrap_or((path, ""));
+    let valid = |part: &str| {
+        part.bytes()
+            .all(|byte| byte > b' ' && !b"\"+,.:;<=>[]|".contains(&byte))
+    };
+    if stem.is_empty() || stem.len() > 8 || extension.len() > 3 || !valid(stem) || !valid(extension)
+    {
+        return Err(FILE_NOT_FOUND);
+    }
+    Ok(path.to_string())
+}
+
+/// Whether the 8.3 `name` matches `pattern`, where `?` stands for any
+/// character or none and `*` for the rest of the name or extension
+fn matches(pattern: &str, name: &str) -> bool {
+    let expand = |text: &str, width: usize| {
+        let mut field: Vec<u8> = Vec::new();
+        for byte in text.bytes() {
+            if byte == b'*' {
+                field.resize(width, b'?');
+                break;
+            }
+            field.push(byte);
+        }
+        field.resize(width, b' ');
+        field
+    };
+    let split = |text: &str| {
+        let (stem, extension) = text.split_once('.').unwrap_or((text, ""));
+        (expand(stem, 8), expand(extension, 3))
+    };
+    let (pattern_stem, pattern_extension) = split(pattern);
+    let (stem, extension) = split(name);
+    pattern_stem
+        .iter()
+        .chain(&pattern_extension)
+        .zip(stem.iter().chain(&extension))
+        .all(|(wanted, byte)| *wanted == b'?' || wanted == byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files() -> FileSystem {
+        let mut files = FileSystem::default();
+        files.files.insert("A.COM".to_string(), vec![0xC3]);
+        files.files.insert("B.COM".to_string(), vec![0x90, 0xC3]);
+        files.files.insert("NOTES.TXT".to_string(), b"abc".to_vec());
+        files
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Reading and writing
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn writes_extend_and_truncate_like_dos() {
+        let mut files = files();
+        let handle = files.open("notes.txt", 2).unwrap();
+        assert_eq!(handle, 5);
+        assert_eq!(files.seek(handle, 2, 2), Ok(5));
+        assert_eq!(files.write(handle, b"!"), Ok(1));
+        assert_eq!(files.files["NOTES.TXT"], b"abc\0\0!");
+        assert_eq!(files.seek(handle, 0, 1), Ok(1));
+        assert_eq!(files.write(handle, b""), Ok(0));
+        assert_eq!(files.files["NOTES.TXT"], b"a");
+        assert_eq!(files.close(handle), Ok(()));
+        assert_eq!(files.read(handle, 1), Err(INVALID_HANDLE));
+    }
+
+    #[test]
+    fn read_only_files_cannot_be_changed() {
+        let mut files = files();
+        files.read_only = true;
+        assert_eq!(files.open("A.COM", 1), Err(ACCESS_DENIED));
+        assert_eq!(files.delete("A.COM"), Err(ACCESS_DENIED));
+        assert_eq!(files.delete("C.COM"), Err(FILE_NOT_FOUND));
+        let handle = files.open("A.COM", 0).unwrap();
+        assert_eq!(files.write(handle, b"x"), Err(ACCESS_DENIED));
+        assert_eq!(files.open("\\DOS\\A.COM", 0), Err(PATH_NOT_FOUND));
+        assert!(files.changes(&self::files()).is_empty());
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Searching and changes
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn wildcards_match_like_dos() {
+        let mut files = files();
+        assert_eq!(files.find_first("*.COM").unwrap().name, "A.COM");
+        assert_eq!(
+            files.find_next(),
+            Ok(FoundFile {
+                name: "B.COM".to_string(),
+                size: 2
+            })
+        );
+        assert_eq!(files.find_next(), Err(NO_MORE_FILES));
+        assert_eq!(files.find_first("NOTE?.*").unwrap().name, "NOTES.TXT");
+        assert_eq!(files.find_first("?.???").unwrap().name, "A.COM");
+        assert_eq!(files.find_first("NOTE.TXT"), Err(NO_MORE_FILES));
+    }
+
+    #[test]
+    fn changes_compare_with_the_start() {
+        let mut files = files();
+        files.delete("A.COM").unwrap();
+        let handle = files.create("NEW.DAT").unwrap();
+        files.write(handle, b"x").unwrap();
+        let handle = files.open("B.COM", 1).unwrap();
+        files.write(handle, &[0xCC]).unwrap();
+        let changes: Vec<String> = files
+            .changes(&self::files())
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            ["changed B.COM", "created NEW.DAT", "deleted A.COM"]
+        );
+    }
+}
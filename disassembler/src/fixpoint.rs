@@ -0,0 +1,298 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Display};
+
+use crate::consts::{Address, AddressExt, COM_OFFSET};
+use crate::country::find_country_calls;
+use crate::disassemble::{Disassembler, is_data};
+use crate::interpreter::Interpreter;
+use crate::label::LabelType;
+use crate::limits::Deadline;
+use crate::managers::MemoryManagers;
+use crate::progress::Progress;
+use crate::table::Table;
+
+/// Something one run of the analysis found that changes how the program is
+/// decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discovery {
+    /// A string the code uses was decoded as instructions
+    String {
+        /// The first address of the string
+        start: Address,
+        /// The address just past the string
+        end: Address,
+    },
+    /// A table the code reads
+    Table(Table),
+    /// A jump or call target was inside another instruction
+    Code(Address),
+    /// A field of a buffer DOS fills in, or the variable holding the XMS
+    /// entry point, was decoded as instructions or started inside a row of
+    /// data, so its label could not be shown
+    Field {
+        /// The first address of the field
+        start: Address,
+        /// The address just past the field, or the end of the program
+        end: Address,
+    },
+}
+
+impl Display for Discovery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Discovery::String { start, end } => write!(
+                f,
+                "string at 0x{start:04x}-0x{end:04x}, decoded as instructions before"
+            ),
+            Discovery::Table(table) => write!(f, "table at 0x{:04x}: {table}", table.start),
+            Discovery::Code(address) => write!(
+                f,
+                "code at 0x{address:04x}, inside another instruction before"
+            ),
+            Discovery::Field { start, end } => write!(
+                f,
+                "buffer field at 0x{start:04x}-0x{end:04x}, not a row of data of its own before"
+            ),
+        }
+    }
+}
+
+/// How [`settle`] went
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Settled {
+    /// How many times the analysis ran, counting the one before [`settle`]
+    pub passes: usize,
+    /// What each run found, in the order it was found
+    pub discoveries: Vec<Discovery>,
+    /// Whether [`Limits::max_passes`](crate::limits::Limits::max_passes) or
+    /// [`Limits::max_time`](crate::limits::Limits::max_time) stopped the
+    /// analysis while it was still finding things
+    pub limited: bool,
+}
+
+/// Runs the analysis again until it stops finding anything that changes how
+/// the program is decoded
+///
+/// One run decodes the program in a single sweep, so what it finds late
+/// cannot change what it decoded early: a string the code prints may have
+/// been decoded as instructions, throwing the instructions after it out of
+/// line, and a table or jump target found in one run may hide code, and the
+/// strings and tables that code uses, from it. Each run's strings and tables
+/// are marked as data, and its jump and call targets that fall inside other
+/// instructions as code, before the next run. The fields of buffers DOS
+/// fills in are marked as data one by one, so each starts a row of its own
+/// and shows its label. Tables belonging to bytecode
+/// interpreters are left alone.
+///
+/// Every discovery is made once, so the runs always come to an end, but
+/// [`Limits::max_passes`](crate::limits::Limits::max_passes) and
+/// [`Limits::max_time`](crate::limits::Limits::max_time) can stop them
+/// sooner. Discoveries are kept as marks, so they show up in
+/// [`Disassembler::annotations`] like marks made by hand.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::fixpoint::{Discovery, settle};
+///
+/// let data = vec![
+///     0xEB, 0x03, // jmp 0x105
+///     b'H', b'i', b'$',
+///     0xBA, 0x02, 0x01, // mov dx, 0x102
+///     0xB4, 0x09, // mov ah, 9
+///     0xCD, 0x21, // int 21h
+///     0xC3, // ret
+/// ];
+/// let mut disassembler = Disassembler::new(data);
+/// // decoded in one sweep, "Hi$" swallows the start of `mov dx`
+/// assert!(disassembler.string_constant_list.0.is_empty());
+///
+/// let settled = settle(&mut disassembler);
+/// assert_eq!(settled.discoveries[0], Discovery::Code(0x105));
+/// assert_eq!(disassembler.string_constant_list.0[0].value, "Hi$");
+/// ```
+pub fn settle(disassembler: &mut Disassembler) -> Settled {
+    let limits = *disassembler.limits();
+    let deadline = Deadline::start(&limits);
+    let mut settled = Settled {
+        passes: 1,
+        ..Settled::default()
+    };
+    let mut seen = Vec::new();
+    loop {
+        let found: Vec<Discovery> = discover(disassembler)
+            .into_iter()
+            .filter(|discovery| !seen.iter().any(|old| same(old, discovery)))
+            .collect();
+        if found.is_empty() {
+            return settled;
+        }
+        if limits.max_passes.is_some_and(|max| settled.passes >= max) || deadline.passed() {
+            settled.limited = true;
+            return settled;
+        }
+        disassembler.progress().report(Progress::Rerun {
+            run: settled.passes,
+        });
+
+        let mut tables = Vec::new();
+        for discovery in &found {
+            match discovery {
+                Discovery::String { start, end } | Discovery::Field { start, end } => {
+                    disassembler.mark_data(*start, *end)
+                }
+                Discovery::Table(table) => tables.push(table.clone()),
+                Discovery::Code(address) => disassembler.mark_code(*address),
+            }
+        }
+        if tables.is_empty() {
+            disassembler.reanalyze();
+        } else {
+            disassembler.mark_tables(&tables);
+        }
+        settled.passes += 1;
+        seen.extend(found.iter().cloned());
+        settled.discoveries.extend(found);
+    }
+}
+
+/// Whether two discoveries are the same thing, tables being the same table
+/// whatever length each run gives them
+fn same(a: &Discovery, b: &Discovery) -> bool {
+    match (a, b) {
+        (Discovery::Table(a), Discovery::Table(b)) => a.start == b.start,
+        _ => a == b,
+    }
+}
+
+/// What the last run found that it did not decode accordingly
+fn discover(disassembler: &Disassembler) -> Vec<Discovery> {
+    let instructions = &disassembler.instructions;
+    let in_program = |address: Address| {
+        address
+            .file_offset()
+            .is_some_and(|offset| offset < disassembler.data.len())
+    };
+    // instructions are in address order without overlaps, so those ending
+    // after `start` begin at a partition point
+    let code_in = |start: Address, end: u32| {
+        let first = instructions
+            .0
+            .partition_point(|instruction| instruction.next_ip() <= start as u64);
+        instructions.0[first..]
+            .iter()
+            .take_while(|instruction| instruction.ip() < end as u64)
+            .any(|instruction| !is_data(instruction))
+    };
+
+    let mut found = Vec::new();
+    let targets: BTreeSet<Address> = disassembler
+        .labels
+        .0
+        .iter()
+        .filter(|label| label.label_type != LabelType::DATA)
+        .map(|label| label.address)
+        .filter(|&address| {
+            in_program(address)
+                && instructions.index_at(address).is_none()
+                && code_in(address, address as u32 + 1)
+        })
+        .collect();
+    found.extend(targets.into_iter().map(Discovery::Code));
+
+    for string in &disassembler.string_constant_list.0 {
+        if in_program(string.start) && code_in(string.start, string.end as u32) {
+            found.push(Discovery::String {
+                start: string.start,
+                end: string.end,
+            });
+        }
+    }
+
+    let program_end = COM_OFFSET as u32 + disassembler.data.len() as u32;
+    for call in find_country_calls(disassembler) {
+        for (start, field) in call.fields() {
+            let end = (start as u32 + field.size as u32).min(program_end);
+            let starts_row = instructions
+                .index_at(start)
+                .is_some_and(|index| is_data(&instructions.0[index]));
+            if in_program(start) && !starts_row {
+                found.push(Discovery::Field {
+                    start,
+                    end: end as Address,
+                });
+            }
+        }
+    }
+
+    if let Some(start) = MemoryManagers::analyze(disassembler).xms_entry {
+        let starts_row = instructions
+            .index_at(start)
+            .is_some_and(|index| is_data(&instructions.0[index]));
+        if in_program(start) && !starts_row {
+            let end = (start as u32 + 4).min(program_end);
+            found.push(Discovery::Field {
+                start,
+                end: end as Address,
+            });
+        }
+    }
+
+    let interpreters = Interpreter::detect(disassembler);
+    for table in Table::detect(disassembler) {
+        let interpreted = interpreters.iter().any(|interpreter| {
+            interpreter.table == table.start
+                || interpreter.bytecode.map(|(start, _)| start) == Some(table.start)
+        });
+        if !interpreted {
+            found.push(Discovery::Table(table));
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::Limits;
+    use crate::samples::SAMPLES;
+
+    // jmp 0x105 / "Hi$" / mov dx, 0x102 / mov ah, 9 / int 21h / ret
+    const HELLO: [u8; 13] = [
+        0xEB, 0x03, b'H', b'i', b'$', 0xBA, 0x02, 0x01, 0xB4, 0x09, 0xCD, 0x21, 0xC3,
+    ];
+
+    #[test]
+    fn the_samples_settle() {
+        for sample in SAMPLES {
+            let mut disassembler = Disassembler::new(sample.bytes.to_vec());
+            let settled = settle(&mut disassembler);
+            assert!(!settled.limited, "{}", sample.name);
+            assert!(
+                discover(&disassembler).iter().all(|discovery| {
+                    settled.discoveries.iter().any(|old| same(old, discovery))
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn settling_stops_at_the_pass_limit() {
+        let limits = Limits {
+            max_passes: Some(1),
+            ..Limits::default()
+        };
+        let mut disassembler = Disassembler::with_limits(HELLO.to_vec(), limits);
+        let settled = settle(&mut disassembler);
+        assert!(settled.limited);
+        assert_eq!(settled.passes, 1);
+        assert!(settled.discoveries.is_empty());
+
+        let mut disassembler = Disassembler::new(HELLO.to_vec());
+        let settled = settle(&mut disassembler);
+        assert!(!settled.limited);
+        assert!(settled.passes > 1);
+        assert!(disassembler.instructions.index_at(0x105).is_some());
+    }
+}
@@ -0,0 +1,422 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Display};
+
+use iced_x86::{FlowControl, OpKind};
+
+use crate::consts::{Address, COM_OFFSET};
+use crate::disassemble::{Disassembler, is_data};
+use crate::freespace::ends_flow;
+
+/// How control passes from one block to another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EdgeKind {
+    /// Running off the end of the block into the next one
+    Fallthrough,
+    /// An unconditional jump
+    Jump,
+    /// A conditional jump, when it is taken
+    Branch,
+    /// A call, which returns to the next block
+    Call,
+}
+
+/// An edge of the control flow graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Edge {
+    /// The block control passes to
+    pub target: Address,
+    /// How it gets there
+    pub kind: EdgeKind,
+}
+
+/// A run of instructions that is only entered at the top and left at the bottom
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BasicBlock {
+    /// The address of the first instruction
+    pub start: Address,
+    /// The address just past the last instruction
+    pub end: u32,
+    /// The addresses of the instructions
+    pub instructions: Vec<Address>,
+    /// Where control goes after the last instruction
+    pub successors: Vec<Edge>,
+}
+
+impl Display for BasicBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "0x{:04x}-0x{:04x}: {} instructions",
+            self.start,
+            self.end,
+            self.instructions.len()
+        )
+    }
+}
+
+/// A branch into a jump that only jumps somewhere else, perhaps through
+/// several more
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JumpChain {
+    /// The address of the branch into the chain
+    pub from: Address,
+    /// The jumps passed through, in order
+    pub hops: Vec<Address>,
+    /// Where control ends up
+    pub destination: Address,
+}
+
+/// The basic blocks of a program's code and the edges between them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ControlFlowGraph(pub Vec<BasicBlock>);
+
+impl ControlFlowGraph {
+    /// Splits the code into basic blocks
+    ///
+    /// Blocks start at the entry point, at branch and call targets and after
+    /// any instruction that transfers control; DOS calls only end a block
+    /// when they exit. Indirect jumps and calls have
+    /// no edges, and `db`/`dw` rows are not part of any block.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::flow::{ControlFlowGraph, EdgeKind};
+    ///
+    /// // cmp al, 1 / je +1 / nop / ret
+    /// let data = vec![0x3C, 0x01, 0x74, 0x01, 0x90, 0xC3];
+    /// let graph = ControlFlowGraph::build(&Disassembler::new(data));
+    /// assert_eq!(graph.0.len(), 3);
+    /// assert_eq!(graph.0[0].successors[1].kind, EdgeKind::Branch);
+    /// assert_eq!(graph.0[0].successors[1].target, 0x105);
+    /// ```
+    pub fn build(disassembler: &Disassembler) -> ControlFlowGraph {
+        let code: Vec<_> = disassembler
+            .instructions
+            .0
+            .iter()
+            .filter(|instruction| !is_data(instruction))
+            .collect();
+        let target = |instruction: &iced_x86::Instruction| {
+            (instruction.op0_kind() == OpKind::NearBranch16)
+                .then(|| instruction.near_branch_target() as Address)
+        };
+
+        let mut leaders = BTreeSet::from([COM_OFFSET]);
+        for instruction in &code {
+            let transfers = !matches!(
+                instruction.flow_control(),
+                FlowControl::Next | FlowControl::Interrupt
+            );
+            if transfers || ends_flow(disassembler, instruction) {
+                leaders.insert(instruction.next_ip() as Address);
+            }
+            leaders.extend(target(instruction));
+        }
+
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        for (index, instruction) in code.iter().enumerate() {
+            let address = instruction.ip() as Address;
+            let continues = blocks
+                .last()
+                .is_some_and(|block| block.end == address as u32 && !leaders.contains(&address));
+            if !continues {
+                blocks.push(BasicBlock {
+                    start: address,
+                    end: address as u32,
+                    instructions: Vec::new(),
+                    successors: Vec::new(),
+                });
+            }
+            let block = blocks.last_mut().expect("a block was just pushed");
+            block.instructions.push(address);
+            block.end = instruction.next_ip() as u32;
+
+            let next = code.get(index + 1).map(|next| next.ip() as Address);
+            let next_ip = instruction.next_ip() as Address;
+            let ends_block = leaders.contains(&next_ip) || next != Some(next_ip);
+            if !ends_block {
+                continue;
+            }
+            let kind = match instruction.flow_control() {
+                FlowControl::UnconditionalBranch => Some(EdgeKind::Jump),
+                FlowControl::ConditionalBranch => Some(EdgeKind::Branch),
+                FlowControl::Call => Some(EdgeKind::Call),
+                _ => None,
+            };
+            if !ends_flow(disassembler, instruction) && next == Some(next_ip) {
+                block.successors.push(Edge {
+                    target: next_ip,
+                    kind: EdgeKind::Fallthrough,
+                });
+            }
+            if let (Some(kind), Some(target)) = (kind, target(instruction)) {
+                block.successors.push(Edge { target, kind });
+            }
+        }
+        ControlFlowGraph(blocks)
+    }
+
+    /// The block containing `address`
+    pub fn block_containing(&self, address: Address) -> Option<&BasicBlock> {
+        self.0
+            .iter()
+            .find(|block| block.start <= address && (address as u32) < block.end)
+    }
+
+    /// The blocks with an edge to the block starting at `start`
+    pub fn predecessors(&self, start: Address) -> impl Iterator<Item = &BasicBlock> {
+        self.0
+            .iter()
+            .filter(move |block| block.successors.iter().any(|edge| edge.target == start))
+    }
+
+    /// The block starting at `start`, if it is a trampoline: a lone
+    /// unconditional jump
+    fn trampoline(&self, start: Address) -> Option<Address> {
+        let block = self.0.iter().find(|block| block.start == start)?;
+        match block.successors[..] {
+            [
+                Edge {
+                    target,
+                    kind: EdgeKind::Jump,
+                },
+            ] if block.instructions.len() == 1 => Some(target),
+            _ => None,
+        }
+    }
+
+    /// The trampolines passed through going to `address` and where control
+    /// ends up, or `None` if the jumps go round in a loop
+    fn resolve(&self, address: Address) -> Option<(Vec<Address>, Address)> {
+        let mut hops = Vec::new();
+        let mut destination = address;
+        while let Some(target) = self.trampoline(destination) {
+            if hops.contains(&destination) {
+                return None;
+            }
+            hops.push(destination);
+            destination = target;
+        }
+        Some((hops, destination))
+    }
+
+    /// Every branch, jump or call whose target only jumps somewhere else
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::flow::ControlFlowGraph;
+    ///
+    /// // call 0x104 / ret / jmp 0x106 / ret
+    /// let data = vec![0xE8, 0x01, 0x00, 0xC3, 0xEB, 0x00, 0xC3];
+    /// let graph = ControlFlowGraph::build(&Disassembler::new(data));
+    /// let chains = graph.jump_chains();
+    /// assert_eq!(chains[0].from, 0x100);
+    /// assert_eq!(chains[0].hops, vec![0x104]);
+    /// assert_eq!(chains[0].destination, 0x106);
+    /// ```
+    pub fn jump_chains(&self) -> Vec<JumpChain> {
+        let mut chains = Vec::new();
+        for block in &self.0 {
+            let Some(&from) = block.instructions.last() else {
+                continue;
+            };
+            for edge in &block.successors {
+                if edge.kind == EdgeKind::Fallthrough {
+                    continue;
+                }
+                if let Some((hops, destination)) = self.resolve(edge.target)
+                    && !hops.is_empty()
+                {
+                    chains.push(JumpChain {
+                        from,
+                        hops,
+                        destination,
+                    });
+                }
+            }
+        }
+        chains
+    }
+
+    /// The graph with every edge into a trampoline sent straight to where
+    /// the trampolines lead, and the trampolines nothing reaches any more
+    /// left out
+    ///
+    /// The entry block stays, as do jumps that loop back on themselves.
+    pub fn simplify(&self) -> ControlFlowGraph {
+        let mut blocks = self.0.clone();
+        for block in &mut blocks {
+            for edge in &mut block.successors {
+                if edge.kind == EdgeKind::Fallthrough {
+                    continue;
+                }
+                if let Some((_, destination)) = self.resolve(edge.target) {
+                    edge.target = destination;
+                }
+            }
+        }
+        let reached: BTreeSet<Address> = blocks
+            .iter()
+            .flat_map(|block| block.successors.iter().map(|edge| edge.target))
+            .collect();
+        blocks.retain(|block| {
+            block.start == COM_OFFSET
+                || reached.contains(&block.start)
+                || self.trampoline(block.start).is_none()
+                || self.resolve(block.start).is_none()
+        });
+        ControlFlowGraph(blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(data: Vec<u8>) -> ControlFlowGraph {
+        ControlFlowGraph::build(&Disassembler::new(data))
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Blocks and edges
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn straight_line_code_is_one_block() {
+        // mov ah, 2 / mov dl, 0x41 / int 21h / ret
+        let found = graph(vec![0xB4, 0x02, 0xB2, 0x41, 0xCD, 0x21, 0xC3]);
+        assert_eq!(found.0.len(), 1);
+        assert_eq!(found.0[0].instructions.len(), 4);
+        assert!(found.0[0].successors.is_empty(), "ret leaves the program");
+    }
+
+    #[test]
+    fn calls_return_to_the_next_block() {
+        let data = vec![
+            0xE8, 0x01, 0x00, // call 0x104
+            0xC3, // ret
+            0x90, // nop
+            0xC3, // ret
+        ];
+        let found = graph(data);
+        let starts: Vec<Address> = found.0.iter().map(|block| block.start).collect();
+        assert_eq!(starts, vec![0x100, 0x103, 0x104]);
+        assert_eq!(
+            found.0[0].successors,
+            vec![
+                Edge {
+                    target: 0x103,
+                    kind: EdgeKind::Fallthrough
+                },
+                Edge {
+                    target: 0x104,
+                    kind: EdgeKind::Call
+                },
+            ]
+        );
+        assert_eq!(
+            found.block_containing(0x105).map(|block| block.start),
+            Some(0x104)
+        );
+        assert_eq!(found.predecessors(0x104).count(), 1);
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Jump chains
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn chains_of_jumps_collapse_to_their_destination() {
+        let data = vec![
+            0x74, 0x03, // je 0x105
+            0xEB, 0x03, // jmp 0x107
+            0xC3, // ret
+            0xEB, 0x00, // 0x105: jmp 0x107
+            0xEB, 0x00, // 0x107: jmp 0x109
+            0xC3, // 0x109: ret
+        ];
+        let found = graph(data);
+        assert_eq!(
+            found.jump_chains(),
+            vec![
+                JumpChain {
+                    from: 0x100,
+                    hops: vec![0x105, 0x107],
+                    destination: 0x109,
+                },
+                JumpChain {
+                    from: 0x102,
+                    hops: vec![0x107],
+                    destination: 0x109,
+                },
+                JumpChain {
+                    from: 0x105,
+                    hops: vec![0x107],
+                    destination: 0x109,
+                },
+            ]
+        );
+
+        let simple = found.simplify();
+        let starts: Vec<Address> = simple.0.iter().map(|block| block.start).collect();
+        assert_eq!(starts, vec![0x100, 0x102, 0x104, 0x109]);
+        assert!(
+            simple
+                .0
+                .iter()
+                .flat_map(|block| &block.successors)
+                .all(|edge| edge.target != 0x105 && edge.target != 0x107)
+        );
+    }
+
+    #[test]
+    fn chains_are_noted_in_the_listing_when_asked() {
+        // call 0x104 / ret / jmp 0x106 / ret
+        let disassembler = Disassembler::new(vec![0xE8, 0x01, 0x00, 0xC3, 0xEB, 0x00, 0xC3]);
+        let opts = crate::disassemble::DisassemblerOptions {
+            jump_chains: true,
+            ..Default::default()
+        };
+        let mut listing = Vec::new();
+        disassembler.disassemble_stream(&mut listing, opts).unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+        assert!(
+            listing.contains("call FUNC_0x0104 ; function ; → ultimately 0x0106 (LABEL_0x0106)"),
+            "{listing}"
+        );
+    }
+
+    #[test]
+    fn jumps_in_a_loop_are_kept() {
+        // jmp 0x102 / jmp 0x100
+        let found = graph(vec![0xEB, 0x00, 0xEB, 0xFC]);
+        assert_eq!(found.jump_chains(), vec![]);
+        assert_eq!(found.simplify(), found);
+    }
+
+    #[test]
+    fn jumping_into_the_middle_splits_a_block() {
+        let data = vec![
+            0x90, // nop
+            0x90, // nop
+            0xEB, 0xFD, // jmp 0x101
+        ];
+        let found = graph(data);
+        let starts: Vec<Address> = found.0.iter().map(|block| block.start).collect();
+        assert_eq!(starts, vec![0x100, 0x101]);
+        assert_eq!(
+            found.0[1].successors,
+            vec![Edge {
+                target: 0x101,
+                kind: EdgeKind::Jump
+            }]
+        );
+    }
+}
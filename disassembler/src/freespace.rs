@@ -0,0 +1,291 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{FlowControl, Instruction, OpKind};
+
+use crate::consts::{Address, AddressExt, COM_OFFSET};
+use crate::disassemble::{Disassembler, is_data};
+use crate::syscall::SyscallType;
+
+/// The smallest unused region worth reporting
+const MIN_FREE: usize = 4;
+
+/// Bytes after the program that may be buffers it uses past its last referenced address
+const BSS_GUARD: u32 = 0x100;
+
+/// The top of the segment kept clear for the stack, which starts at 0xFFFE
+const STACK_RESERVE: u32 = 0x1000;
+
+/// Why a region is unused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FreeKind {
+    /// Filler bytes (0x00, 0x90 or 0xCC) that execution never reaches and nothing refers to
+    Padding,
+    /// Other bytes that execution never reaches and nothing refers to
+    DeadData,
+    /// Memory in the segment after the program and anything it refers to,
+    /// below the stack; using it makes the file longer
+    Slack,
+}
+
+/// A region the program does not use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FreeRegion {
+    /// The first unused address
+    pub start: Address,
+    /// The address just past the region
+    pub end: u32,
+    /// Why the region is unused
+    pub kind: FreeKind,
+}
+
+impl FreeRegion {
+    /// The number of bytes in the region
+    pub fn len(&self) -> usize {
+        (self.end - self.start as u32) as usize
+    }
+
+    /// Returns true if the region has no bytes left
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Display for FreeRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            FreeKind::Padding => "padding",
+            FreeKind::DeadData => "dead data",
+            FreeKind::Slack => "slack",
+        };
+        write!(
+            f,
+            "0x{:04x}-0x{:04x}: {} bytes of {kind}",
+            self.start,
+            self.end,
+            self.len()
+        )
+    }
+}
+
+/// The unused regions of a program, from which patch space is handed out
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FreeSpace(pub Vec<FreeRegion>);
+
+/// Returns true if execution never continues past `instruction`
+pub(crate) fn ends_flow(disassembler: &Disassembler, instruction: &Instruction) -> bool {
+    match instruction.flow_control() {
+        FlowControl::Return | FlowControl::UnconditionalBranch | FlowControl::IndirectBranch => {
+            true
+        }
+        FlowControl::Interrupt => {
+            let exits = disassembler
+                .syscall_list
+                .get_by_address(instruction.ip() as Address)
+                .is_some_and(|syscall| {
+                    matches!(
+                        syscall.number,
+                        SyscallType::ProgramTerminate | SyscallType::TerminateWithCode
+                    )
+                });
+            let int20 =
+                instruction.op0_kind() == OpKind::Immediate8 && instruction.immediate8() == 0x20;
+            exits || int20
+        }
+        _ => false,
+    }
+}
+
+impl FreeSpace {
+    /// Finds the regions of the program nothing uses
+    ///
+    /// Bytes after a `ret`, an unconditional jump or a call that exits are
+    /// unused up to the next address an instruction refers to, a label or a
+    /// string. Runs of a single filler byte are reported as padding. Past the
+    /// end of the program, memory is free from beyond the highest address the
+    /// program refers to, with room left for buffers there, up to the stack.
+    /// Data reached only through computed addresses is not seen, so patches
+    /// placed in dead data should be checked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::freespace::{FreeKind, FreeSpace};
+    ///
+    /// // ret / 8 bytes of nop padding / ret
+    /// let mut data = vec![0xC3];
+    /// data.extend([0x90; 8]);
+    /// let space = FreeSpace::analyze(&Disassembler::new(data));
+    /// assert_eq!(space.0[0].kind, FreeKind::Padding);
+    /// assert_eq!(space.0[0].start, 0x101);
+    /// ```
+    pub fn analyze(disassembler: &Disassembler) -> FreeSpace {
+        let program_end = COM_OFFSET as u32 + disassembler.data.len() as u32;
+        let mut live = disassembler.referenced_addresses();
+        live.extend(disassembler.labels.0.iter().map(|label| label.address));
+        live.extend(
+            disassembler
+                .string_constant_list
+                .0
+                .iter()
+                .map(|string| string.start),
+        );
+        let in_string = |address: Address| {
+            disassembler
+                .string_constant_list
+                .0
+                .iter()
+                .any(|string| string.start <= address && address < string.end)
+        };
+
+        let mut regions = Vec::new();
+        for instruction in &disassembler.instructions.0 {
+            if is_data(instruction) || !ends_flow(disassembler, instruction) {
+                continue;
+            }
+            let Ok(start) = Address::try_from(instruction.next_ip()) else {
+                continue;
+            };
+            if live.contains(&start) || in_string(start) || start as u32 >= program_end {
+                continue;
+            }
+            let end = live
+                .range(start..)
+                .next()
+                .map_or(program_end, |&next| (next as u32).min(program_end));
+            let Some(offset) = start.file_offset() else {
+                continue;
+            };
+            let bytes = &disassembler.data[offset..(end - COM_OFFSET as u32) as usize];
+            if bytes.len() < MIN_FREE {
+                continue;
+            }
+            let padding = matches!(bytes[0], 0x00 | 0x90 | 0xCC)
+                && bytes.iter().all(|&byte| byte == bytes[0]);
+            regions.push(FreeRegion {
+                start,
+                end,
+                kind: if padding {
+                    FreeKind::Padding
+                } else {
+                    FreeKind::DeadData
+                },
+            });
+        }
+
+        let last_referenced = live
+            .range(program_end.min(u16::MAX as u32) as Address..)
+            .next_back()
+            .map(|&address| address as u32 + BSS_GUARD);
+        let slack_start = last_referenced.unwrap_or(program_end).max(program_end);
+        // keep patches paragraph aligned
+        let slack_start = slack_start.next_multiple_of(16);
+        let slack_end = 0x10000 - STACK_RESERVE;
+        if slack_start < slack_end {
+            regions.push(FreeRegion {
+                start: slack_start as Address,
+                end: slack_end,
+                kind: FreeKind::Slack,
+            });
+        }
+        FreeSpace(regions)
+    }
+
+    /// Reserves `len` bytes for new code or data and returns their address
+    ///
+    /// Space inside the program is used first, so the file only grows when
+    /// nothing there is large enough; an address in slack is past the end of
+    /// the file, which must be extended up to it. Returns `None` if no region
+    /// is large enough.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::freespace::FreeSpace;
+    ///
+    /// let mut data = vec![0xC3];
+    /// data.extend([0x00; 8]);
+    /// let mut space = FreeSpace::analyze(&Disassembler::new(data));
+    /// assert_eq!(space.allocate_patch_space(6), Some(0x101));
+    /// // the two bytes left are too few, so the next patch goes past the program
+    /// assert_eq!(space.allocate_patch_space(6), Some(0x110));
+    /// ```
+    pub fn allocate_patch_space(&mut self, len: usize) -> Option<Address> {
+        let region = self
+            .0
+            .iter_mut()
+            .filter(|region| region.len() >= len)
+            .min_by_key(|region| region.kind == FreeKind::Slack)?;
+        let start = region.start;
+        region.start = (region.start as u32 + len as u32).min(region.end) as Address;
+        self.0.retain(|region| !region.is_empty());
+        Some(start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Finding unused regions
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn referenced_data_after_ret_is_live() {
+        // mov dx, 0x108 / mov ah, 9 / int 21h / ret / "ok$" then 5 more bytes
+        let mut data = vec![0xBA, 0x08, 0x01, 0xB4, 0x09, 0xCD, 0x21, 0xC3];
+        data.extend(b"ok$");
+        data.extend([0x00; 5]);
+        let space = FreeSpace::analyze(&Disassembler::new(data));
+        assert_eq!(
+            space.0,
+            vec![FreeRegion {
+                start: 0x110,
+                end: 0xF000,
+                kind: FreeKind::Slack
+            }]
+        );
+    }
+
+    #[test]
+    fn unreached_bytes_before_a_label_are_dead() {
+        // jmp short 0x107 / 5 junk bytes / ret
+        let data = vec![0xEB, 0x05, 0x12, 0x34, 0x56, 0x78, 0x9A, 0xC3];
+        let space = FreeSpace::analyze(&Disassembler::new(data));
+        assert_eq!(
+            space.0[0],
+            FreeRegion {
+                start: 0x102,
+                end: 0x107,
+                kind: FreeKind::DeadData
+            }
+        );
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Allocation
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn allocation_prefers_space_inside_the_program() {
+        let mut space = FreeSpace(vec![
+            FreeRegion {
+                start: 0x200,
+                end: 0xF000,
+                kind: FreeKind::Slack,
+            },
+            FreeRegion {
+                start: 0x120,
+                end: 0x130,
+                kind: FreeKind::DeadData,
+            },
+        ]);
+        assert_eq!(space.allocate_patch_space(0x10), Some(0x120));
+        assert_eq!(space.0.len(), 1);
+        assert_eq!(space.allocate_patch_space(0x10), Some(0x200));
+        assert_eq!(space.allocate_patch_space(0x10000), None);
+    }
+}
@@ -0,0 +1,363 @@
+use std::fmt::{self, Display};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+use crate::disassemble::{Disassembler, DisassemblerOptions};
+use crate::emulate::Emulator;
+use crate::limits::Limits;
+
+/// The largest program a mutation may grow to, the most a COM file can hold
+const MAX_PROGRAM: usize = 0xFF00;
+
+/// A change made to a program before analysing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    /// One bit of the byte at `offset` was flipped
+    Flip {
+        /// The offset of the byte in the file
+        offset: usize,
+        /// The bit flipped, 0 to 7
+        bit: u8,
+    },
+    /// The byte at `offset` was replaced with `byte`
+    Replace {
+        /// The offset of the byte in the file
+        offset: usize,
+        /// The new byte
+        byte: u8,
+    },
+    /// `byte` was inserted before `offset`
+    Insert {
+        /// The offset the byte was inserted at
+        offset: usize,
+        /// The inserted byte
+        byte: u8,
+    },
+    /// The byte at `offset` was removed
+    Delete {
+        /// The offset of the removed byte
+        offset: usize,
+    },
+    /// The program was cut short to `len` bytes
+    Truncate {
+        /// The new length
+        len: usize,
+    },
+}
+
+impl Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mutation::Flip { offset, bit } => write!(f, "flip bit {bit} at {offset:#06x}"),
+            Mutation::Replace { offset, byte } => {
+                write!(f, "replace the byte at {offset:#06x} with {byte:02x}")
+            }
+            Mutation::Insert { offset, byte } => write!(f, "insert {byte:02x} at {offset:#06x}"),
+            Mutation::Delete { offset } => write!(f, "delete the byte at {offset:#06x}"),
+            Mutation::Truncate { len } => write!(f, "truncate to {len} bytes"),
+        }
+    }
+}
+
+impl Mutation {
+    /// Makes this change to `program`
+    pub fn apply(&self, program: &mut Vec<u8>) {
+        match *self {
+            Mutation::Flip { offset, bit } => program[offset] ^= 1 << bit,
+            Mutation::Replace { offset, byte } => program[offset] = byte,
+            Mutation::Insert { offset, byte } => program.insert(offset, byte),
+            Mutation::Delete { offset } => {
+                program.remove(offset);
+            }
+            Mutation::Truncate { len } => program.truncate(len),
+        }
+    }
+}
+
+/// A small xorshift generator, so a seed always gives the same mutations
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // xorshift never leaves zero
+        Rng(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// A number below `bound`, which must not be zero
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+/// Picks one random change that can be made to `program`
+fn pick(rng: &mut Rng, program: &[u8]) -> Mutation {
+    let len = program.len();
+    let byte = rng.next() as u8;
+    match (rng.below(16), len) {
+        (_, 0) => Mutation::Insert { offset: 0, byte },
+        // short cuts are rare, or most programs would be a few bytes long
+        (0, _) => Mutation::Truncate {
+            len: rng.below(len),
+        },
+        (1..=3, _) if len > 1 => Mutation::Delete {
+            offset: rng.below(len),
+        },
+        (4..=6, _) if len < MAX_PROGRAM => Mutation::Insert {
+            offset: rng.below(len + 1),
+            byte,
+        },
+        (7..=10, _) => Mutation::Replace {
+            offset: rng.below(len),
+            byte,
+        },
+        _ => Mutation::Flip {
+            offset: rng.below(len),
+            bit: rng.below(8) as u8,
+        },
+    }
+}
+
+/// What to try on each mutated program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuzzOptions {
+    /// How many mutated programs to try
+    pub iterations: usize,
+    /// The most mutations made to each program; each gets between one and this many
+    pub mutations: usize,
+    /// The seed the mutations are drawn from
+    pub seed: u64,
+    /// The caps the analysis runs under
+    pub limits: Limits,
+    /// How many instructions each mutated program is also emulated for, if any
+    pub budget: Option<u64>,
+    /// How long analysing one program may take before it counts as hanging;
+    /// not checked on `wasm32-unknown-unknown`, which has no clock
+    pub timeout: Option<Duration>,
+}
+
+impl Default for FuzzOptions {
+    fn default() -> Self {
+        FuzzOptions {
+            iterations: 100,
+            mutations: 4,
+            seed: 0,
+            limits: Limits {
+                max_time: Some(Duration::from_secs(5)),
+                ..Limits::default()
+            },
+            budget: Some(10_000),
+            timeout: Some(Duration::from_secs(10)),
+        }
+    }
+}
+
+/// How the analysis failed on a mutated program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The analysis panicked with this message
+    Panic(String),
+    /// The analysis finished, but took this long
+    Timeout(Duration),
+}
+
+impl Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureKind::Panic(message) => write!(f, "panicked: {message}"),
+            FailureKind::Timeout(elapsed) => {
+                write!(f, "took {:.1} seconds", elapsed.as_secs_f64())
+            }
+        }
+    }
+}
+
+/// A mutated program the analysis failed on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzFailure {
+    /// Which of the mutated programs it was, counting from 0
+    pub iteration: usize,
+    /// The changes made to the original program, in order
+    pub mutations: Vec<Mutation>,
+    /// The mutated program
+    pub program: Vec<u8>,
+    /// What went wrong
+    pub kind: FailureKind,
+}
+
+impl Display for FuzzFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mutant {} {} after ", self.iteration, self.kind)?;
+        for (i, mutation) in self.mutations.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{mutation}")?;
+        }
+        Ok(())
+    }
+}
+
+/// What fuzzing one program found
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FuzzReport {
+    /// How many mutated programs were analysed
+    pub iterations: usize,
+    /// The mutated programs the analysis failed on
+    pub failures: Vec<FuzzFailure>,
+}
+
+/// Analyses randomly mutated copies of `program`, reporting the ones the
+/// analysis panics or hangs on
+///
+/// Each copy gets between one and [`FuzzOptions::mutations`] bit flips, byte
+/// changes, insertions, deletions or cuts, is analysed under
+/// [`FuzzOptions::limits`] and written out as a listing, and, with a
+/// [`FuzzOptions::budget`], emulated. The same seed always makes the same
+/// copies, so a failure can be reproduced from the seed alone.
+///
+/// Panics are caught and reported, but still reach the panic hook, which
+/// prints them unless replaced.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::fuzz::{FuzzOptions, fuzz};
+///
+/// // mov ah, 9 / mov dx, 0x108 / int 21h / ret / "Hi$"
+/// let program = [0xB4, 0x09, 0xBA, 0x08, 0x01, 0xCD, 0x21, 0xC3, b'H', b'i', b'$'];
+/// let options = FuzzOptions {
+///     iterations: 20,
+///     ..FuzzOptions::default()
+/// };
+/// let report = fuzz(&program, &options);
+/// assert_eq!(report.iterations, 20);
+/// assert!(report.failures.is_empty());
+/// ```
+pub fn fuzz(program: &[u8], options: &FuzzOptions) -> FuzzReport {
+    let mut rng = Rng::new(options.seed);
+    let mut report = FuzzReport::default();
+    for iteration in 0..options.iterations {
+        let mut mutant = program.to_vec();
+        let count = 1 + rng.below(options.mutations.max(1));
+        let mutations: Vec<Mutation> = (0..count)
+            .map(|_| {
+                let mutation = pick(&mut rng, &mutant);
+                mutation.apply(&mut mutant);
+                mutation
+            })
+            .collect();
+
+        let clock = Clock::start();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| exercise(&mutant, options)));
+        let kind = match result {
+            Err(payload) => Some(FailureKind::Panic(panic_message(payload.as_ref()))),
+            Ok(()) => clock
+                .elapsed()
+                .filter(|&elapsed| options.timeout.is_some_and(|timeout| elapsed > timeout))
+                .map(FailureKind::Timeout),
+        };
+        report.iterations += 1;
+        if let Some(kind) = kind {
+            report.failures.push(FuzzFailure {
+                iteration,
+                mutations,
+                program: mutant,
+                kind,
+            });
+        }
+    }
+    report
+}
+
+/// Analyses, lists and emulates `program` the way a user would
+fn exercise(program: &[u8], options: &FuzzOptions) {
+    let disassembler = Disassembler::with_limits(program.to_vec(), options.limits);
+    disassembler
+        .disassemble_stream(&mut io::sink(), DisassemblerOptions::default())
+        .expect("writing to a sink cannot fail");
+    if let Some(budget) = options.budget {
+        Emulator::new(program).run(budget);
+    }
+}
+
+/// The message a panic was raised with
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "a panic without a message".to_string())
+}
+
+/// Measures how long an analysis took, where there is a clock
+struct Clock(Option<std::time::Instant>);
+
+impl Clock {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn start() -> Clock {
+        Clock(Some(std::time::Instant::now()))
+    }
+
+    /// `Instant::now` panics in the browser, so nothing is timed there
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    fn start() -> Clock {
+        Clock(None)
+    }
+
+    fn elapsed(&self) -> Option<Duration> {
+        self.0.map(|start| start.elapsed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samples::SAMPLES;
+
+    #[test]
+    fn mutations_are_reproducible_and_stay_in_bounds() {
+        let program = [0x90; 4];
+        let mut first = Rng::new(7);
+        let mut second = Rng::new(7);
+        let mut mutant = program.to_vec();
+        for _ in 0..1000 {
+            let mutation = pick(&mut first, &mutant);
+            assert_eq!(mutation, pick(&mut second, &mutant));
+            mutation.apply(&mut mutant);
+            assert!(mutant.len() <= MAX_PROGRAM);
+        }
+        assert_eq!(
+            Mutation::Flip { offset: 3, bit: 7 }.to_string(),
+            "flip bit 7 at 0x0003"
+        );
+    }
+
+    #[test]
+    fn the_samples_survive_mutation() {
+        let options = FuzzOptions {
+            iterations: 25,
+            ..FuzzOptions::default()
+        };
+        for sample in SAMPLES {
+            let report = fuzz(sample.bytes, &options);
+            assert_eq!(report.iterations, options.iterations);
+            assert!(
+                report.failures.is_empty(),
+                "{}: {}",
+                sample.name,
+                report.failures[0]
+            );
+        }
+    }
+}
@@ -0,0 +1,82 @@
+/// Header fields that differ between runs on the same program: the tool
+/// version, where the input was read from and when it was disassembled
+///
+/// They are written as `; field: value` lines, which [`normalize`] leaves
+/// out.
+pub const VOLATILE_FIELDS: [&str; 3] = ["version", "input", "date"];
+
+/// The spaces a tab in an indent stands for
+const TAB: &str = "    ";
+
+/// Makes `listing` the same on every run and platform, so it can be
+/// committed as a golden file and diffed across versions of the tool
+///
+/// Lines with a [volatile field](VOLATILE_FIELDS) are left out, line
+/// endings become `\n`, tabs in indents become four spaces, trailing
+/// whitespace is trimmed, runs of blank lines become one, and the listing
+/// ends in exactly one newline. Nothing inside a line changes, so strings
+/// keep their spacing.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::golden::normalize;
+///
+/// let listing = "; version: 0.1.2\r\nstart:\r\n\tmov ah,9  \r\n\r\n\r\n    ret\r\n\r\n";
+/// assert_eq!(normalize(listing), "start:\n    mov ah,9\n\n    ret\n");
+/// ```
+pub fn normalize(listing: &str) -> String {
+    let mut normalized = String::with_capacity(listing.len());
+    let mut blank = false;
+    for line in listing.lines() {
+        let line = line.trim_end();
+        if is_volatile(line) {
+            continue;
+        }
+        if line.is_empty() {
+            blank = !normalized.is_empty();
+            continue;
+        }
+        if blank {
+            normalized.push('\n');
+            blank = false;
+        }
+        let text = line.trim_start_matches([' ', '\t']);
+        let indent = &line[..line.len() - text.len()];
+        normalized.push_str(&indent.replace('\t', TAB));
+        normalized.push_str(text);
+        normalized.push('\n');
+    }
+    normalized
+}
+
+/// Returns true if `line` is a `; field: value` line with a volatile field
+fn is_volatile(line: &str) -> bool {
+    line.strip_prefix("; ")
+        .and_then(|line| line.split_once(": "))
+        .is_some_and(|(field, _)| VOLATILE_FIELDS.contains(&field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassemble::Disassembler;
+
+    #[test]
+    fn normalizing_is_idempotent_and_keeps_listings() {
+        let listing =
+            Disassembler::new(crate::samples::find("hello").unwrap().bytes.to_vec()).to_string();
+        let normalized = normalize(&listing);
+        assert_eq!(normalize(&normalized), normalized);
+        assert_eq!(normalized.trim_end(), listing.trim_end());
+    }
+
+    #[test]
+    fn only_volatile_fields_are_left_out() {
+        let listing = "; date: 2026-10-17\n; sha256: 00ff\n    db \"a  b\"\n; note: x\n";
+        assert_eq!(
+            normalize(listing),
+            "; sha256: 00ff\n    db \"a  b\"\n; note: x\n"
+        );
+    }
+}
@@ -0,0 +1,457 @@
+use std::fmt::{self, Display};
+
+use crate::consts::{Address, COM_OFFSET};
+use crate::disassemble::{Disassembler, is_data};
+
+/// The bytes in a 320x200 picture with one byte per pixel
+const MODE_13H_SIZE: usize = 320 * 200;
+
+/// The bytes in a 320x200 picture with four pixels per byte
+const CGA_SIZE: usize = 320 * 200 / 4;
+
+/// The bytes in each bank of a CGA picture, holding every other line
+const CGA_BANK: usize = CGA_SIZE / 2;
+
+/// The glyph heights of the fonts recognised, for 8x8, 8x14 and 8x16 character cells
+const FONT_HEIGHTS: [usize; 3] = [8, 14, 16];
+
+/// The number of glyphs in a full font
+const GLYPHS: usize = 256;
+
+/// The tiles in each row of a tile or font preview
+const TILES_PER_ROW: usize = 16;
+
+/// The smallest data run previewed as tiles
+const MIN_TILES: usize = 32;
+
+/// How a block of bytes is laid out as pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GraphicsFormat {
+    /// A 256 character font, one byte per 8 pixel row
+    Font {
+        /// The height of each glyph in pixels
+        height: usize,
+    },
+    /// A 320x200 picture with one byte per pixel, as in VGA mode 13h
+    Mode13h,
+    /// A 320x200 picture with two bits per pixel, even lines followed by odd
+    /// lines, as in CGA mode 4
+    Cga,
+    /// Monochrome 8 pixel wide tiles, such as sprites or a partial font
+    Tiles {
+        /// The height of each tile in pixels
+        height: usize,
+    },
+}
+
+impl Display for GraphicsFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphicsFormat::Font { height } => write!(f, "8x{height} font"),
+            GraphicsFormat::Mode13h => write!(f, "320x200 256 colour picture"),
+            GraphicsFormat::Cga => write!(f, "320x200 4 colour CGA picture"),
+            GraphicsFormat::Tiles { height } => write!(f, "8x{height} monochrome tiles"),
+        }
+    }
+}
+
+/// A picture with one RGB triple per pixel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    /// The width in pixels
+    pub width: usize,
+    /// The height in pixels
+    pub height: usize,
+    /// The pixels, row by row, three bytes each
+    pub rgb: Vec<u8>,
+}
+
+impl Image {
+    pub(crate) fn new(width: usize, height: usize) -> Image {
+        Image {
+            width,
+            height,
+            rgb: vec![0; width * height * 3],
+        }
+    }
+
+    pub(crate) fn set(&mut self, x: usize, y: usize, [r, g, b]: [u8; 3]) {
+        let at = (y * self.width + x) * 3;
+        self.rgb[at..at + 3].copy_from_slice(&[r, g, b]);
+    }
+}
+
+/// The colours of CGA palette 1 in high intensity
+const CGA_PALETTE: [[u8; 3]; 4] = [
+    [0x00, 0x00, 0x00],
+    [0x55, 0xFF, 0xFF],
+    [0xFF, 0x55, 0xFF],
+    [0xFF, 0xFF, 0xFF],
+];
+
+/// The 16 colours of the EGA and VGA text palette
+pub(crate) const EGA_PALETTE: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00],
+    [0x00, 0x00, 0xAA],
+    [0x00, 0xAA, 0x00],
+    [0x00, 0xAA, 0xAA],
+    [0xAA, 0x00, 0x00],
+    [0xAA, 0x00, 0xAA],
+    [0xAA, 0x55, 0x00],
+    [0xAA, 0xAA, 0xAA],
+    [0x55, 0x55, 0x55],
+    [0x55, 0x55, 0xFF],
+    [0x55, 0xFF, 0x55],
+    [0x55, 0xFF, 0xFF],
+    [0xFF, 0x55, 0x55],
+    [0xFF, 0x55, 0xFF],
+    [0xFF, 0xFF, 0x55],
+    [0xFF, 0xFF, 0xFF],
+];
+
+/// The colour `index` has in the default VGA palette
+///
+/// Colours 16-31 are a grey ramp and 32-247 are nine rings of 24 hues at
+/// three intensities and three saturations; the rest are black. The rings
+/// are computed, so they come close to the BIOS values without matching
+/// them exactly.
+fn vga_colour(index: u8) -> [u8; 3] {
+    let index = index as usize;
+    match index {
+        0..16 => EGA_PALETTE[index],
+        16..32 => {
+            let level = ((index - 16) * 255 / 15) as u8;
+            [level; 3]
+        }
+        32..248 => {
+            let ring = (index - 32) / 24;
+            let hue = (index - 32) % 24;
+            // the brightest component and the lowest, by saturation, in 6 bit DAC units
+            let (high, lows) = [(63, [0, 31, 45]), (28, [0, 14, 20]), (16, [0, 8, 11])][ring / 3];
+            let low = lows[ring % 3];
+            // blue, magenta, red, yellow, green, cyan and back to blue
+            let corners = [
+                [0, 0, 1],
+                [1, 0, 1],
+                [1, 0, 0],
+                [1, 1, 0],
+                [0, 1, 0],
+                [0, 1, 1],
+            ];
+            let from = corners[hue / 4];
+            let to = corners[(hue / 4 + 1) % corners.len()];
+            let step = hue % 4;
+            let mut colour = [0; 3];
+            for channel in 0..3 {
+                let amount = from[channel] * (4 - step) + to[channel] * step;
+                let dac = low + (high - low) * amount / 4;
+                colour[channel] = (dac << 2 | dac >> 4) as u8;
+            }
+            colour
+        }
+        _ => [0; 3],
+    }
+}
+
+/// Draws 8 pixel wide monochrome tiles of `height` rows, 16 to a row
+fn tiles(bytes: &[u8], height: usize) -> Image {
+    let count = bytes.len().div_ceil(height);
+    let rows = count.div_ceil(TILES_PER_ROW);
+    let mut image = Image::new(TILES_PER_ROW * 8, rows * height);
+    for (offset, &byte) in bytes.iter().enumerate() {
+        let tile = offset / height;
+        let x = (tile % TILES_PER_ROW) * 8;
+        let y = (tile / TILES_PER_ROW) * height + offset % height;
+        for bit in 0..8 {
+            if byte & (0x80 >> bit) != 0 {
+                image.set(x + bit, y, [0xFF; 3]);
+            }
+        }
+    }
+    image
+}
+
+impl GraphicsFormat {
+    /// Draws `bytes` laid out in this format
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::graphics::GraphicsFormat;
+    ///
+    /// let image = GraphicsFormat::Tiles { height: 8 }.render(&[0x80; 8]);
+    /// assert_eq!((image.width, image.height), (128, 8));
+    /// assert_eq!(image.rgb[..6], [0xFF, 0xFF, 0xFF, 0, 0, 0]);
+    /// ```
+    pub fn render(&self, bytes: &[u8]) -> Image {
+        match *self {
+            GraphicsFormat::Font { height } | GraphicsFormat::Tiles { height } => {
+                tiles(bytes, height)
+            }
+            GraphicsFormat::Mode13h => {
+                let mut image = Image::new(320, 200);
+                for (offset, &byte) in bytes.iter().take(MODE_13H_SIZE).enumerate() {
+                    image.set(offset % 320, offset / 320, vga_colour(byte));
+                }
+                image
+            }
+            GraphicsFormat::Cga => {
+                let mut image = Image::new(320, 200);
+                for (offset, &byte) in bytes.iter().take(CGA_SIZE).enumerate() {
+                    let (bank, within) = (offset / CGA_BANK, offset % CGA_BANK);
+                    let y = within / 80 * 2 + bank;
+                    for pixel in 0..4 {
+                        let colour = byte >> (6 - pixel * 2) & 0b11;
+                        image.set(within % 80 * 4 + pixel, y, CGA_PALETTE[colour as usize]);
+                    }
+                }
+                image
+            }
+        }
+    }
+}
+
+/// A block of data that may be graphics
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Graphic {
+    /// Where the block was found: a region, companion file or `data`
+    pub name: String,
+    /// The address of the first byte
+    pub address: Address,
+    /// The number of bytes
+    pub len: usize,
+    /// How the bytes are drawn
+    pub format: GraphicsFormat,
+}
+
+impl Display for Graphic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at 0x{:04x}, {} bytes: {}",
+            self.name, self.address, self.len, self.format
+        )
+    }
+}
+
+impl Graphic {
+    /// The bytes of the block
+    pub fn bytes<'a>(&self, disassembler: &'a Disassembler) -> &'a [u8] {
+        disassembler
+            .bytes_at(self.address)
+            .map_or(&[], |bytes| &bytes[..self.len.min(bytes.len())])
+    }
+
+    /// A file name for a preview of the block, e.g. `0x0200_font.png`
+    pub fn file_name(&self) -> String {
+        let name: String = self
+            .name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        format!("0x{:04x}_{name}.png", self.address)
+    }
+}
+
+/// Returns true if `bytes` look like a 256 character font of `height` line glyphs
+///
+/// In code page 437 fonts the NUL and space glyphs are blank and every
+/// capital letter has pixels set.
+fn is_font(bytes: &[u8], height: usize) -> bool {
+    let glyph = |c: u8| &bytes[c as usize * height..(c as usize + 1) * height];
+    let blank = |c: u8| glyph(c).iter().all(|&row| row == 0);
+    bytes.len() == GLYPHS * height && blank(0) && blank(b' ') && (b'A'..=b'Z').all(|c| !blank(c))
+}
+
+/// The format a block of `bytes` is most likely in, judged by its size
+fn classify(bytes: &[u8]) -> Option<GraphicsFormat> {
+    if let Some(&height) = FONT_HEIGHTS.iter().find(|&&height| is_font(bytes, height)) {
+        return Some(GraphicsFormat::Font { height });
+    }
+    match bytes.len() {
+        MODE_13H_SIZE => Some(GraphicsFormat::Mode13h),
+        CGA_SIZE => Some(GraphicsFormat::Cga),
+        len if len >= MIN_TILES => Some(GraphicsFormat::Tiles { height: 8 }),
+        _ => None,
+    }
+}
+
+/// Finds blocks of data that may be fonts, pictures or sprites
+///
+/// Named regions, companion files and runs of data in the listing are each
+/// classified by size: 2048, 3584 and 4096 byte blocks with blank NUL and
+/// space glyphs and drawn letters are fonts, 64000 bytes is a VGA mode 13h
+/// picture and 16000 bytes a CGA one. Anything else of 32 bytes or more is
+/// shown as monochrome tiles. Fonts are also looked for at every address
+/// the code refers to.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::graphics::{find_graphics, GraphicsFormat};
+/// use disassembler::region::NamedRegion;
+///
+/// let mut disassembler = Disassembler::new(vec![0xAA; 64]);
+/// disassembler.regions.insert(NamedRegion {
+///     name: "sprites".to_string(),
+///     start: 0x100,
+///     end: 0x140,
+/// });
+/// let found = find_graphics(&disassembler);
+/// assert_eq!(found[0].format, GraphicsFormat::Tiles { height: 8 });
+/// ```
+pub fn find_graphics(disassembler: &Disassembler) -> Vec<Graphic> {
+    let mut blocks: Vec<(String, Address, usize)> = disassembler
+        .regions
+        .0
+        .iter()
+        .map(|region| {
+            (
+                region.name.clone(),
+                region.start,
+                (region.end - region.start) as usize,
+            )
+        })
+        .collect();
+    blocks.extend(
+        disassembler
+            .aux_files()
+            .iter()
+            .map(|file| (file.name.clone(), file.address, file.data.len())),
+    );
+
+    let mut run: Option<(Address, usize)> = None;
+    for instruction in &disassembler.instructions.0 {
+        if is_data(instruction) {
+            run.get_or_insert((instruction.ip() as Address, 0)).1 += instruction.len();
+        } else if let Some((start, len)) = run.take() {
+            blocks.push(("data".to_string(), start, len));
+        }
+    }
+    if let Some((start, len)) = run {
+        blocks.push(("data".to_string(), start, len));
+    }
+
+    let program_end = COM_OFFSET as usize + disassembler.data.len();
+    for address in disassembler.referenced_addresses() {
+        for height in FONT_HEIGHTS {
+            let len = GLYPHS * height;
+            if (address as usize) >= COM_OFFSET as usize && address as usize + len <= program_end {
+                blocks.push(("font".to_string(), address, len));
+            }
+        }
+    }
+
+    let mut found: Vec<Graphic> = Vec::new();
+    for (name, address, len) in blocks {
+        let Some(bytes) = disassembler.bytes_at(address) else {
+            continue;
+        };
+        let Some(format) = classify(&bytes[..len.min(bytes.len())]) else {
+            continue;
+        };
+        let referenced_font = name == "font";
+        if referenced_font && !matches!(format, GraphicsFormat::Font { .. }) {
+            continue;
+        }
+        if found
+            .iter()
+            .any(|other| other.address == address && other.len == len)
+        {
+            continue;
+        }
+        found.push(Graphic {
+            name,
+            address,
+            len,
+            format,
+        });
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::AuxFile;
+
+    /// A font whose glyphs are blank below `A` and solid from it
+    fn font(height: usize) -> Vec<u8> {
+        (0..GLYPHS)
+            .flat_map(|c| vec![if c >= b'A' as usize { 0xFF } else { 0 }; height])
+            .collect()
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Recognising formats
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn fonts_are_recognised_by_their_glyphs() {
+        assert_eq!(classify(&font(8)), Some(GraphicsFormat::Font { height: 8 }));
+        assert_eq!(
+            classify(&font(16)),
+            Some(GraphicsFormat::Font { height: 16 })
+        );
+        // the right size but with a drawn space is only tiles
+        let mut not_font = font(8);
+        not_font[b' ' as usize * 8] = 0x18;
+        assert_eq!(
+            classify(&not_font),
+            Some(GraphicsFormat::Tiles { height: 8 })
+        );
+    }
+
+    #[test]
+    fn fonts_referenced_by_the_code_are_found() {
+        // mov bp, 0x104 / ret / font
+        let mut data = vec![0xBD, 0x04, 0x01, 0xC3];
+        data.extend(font(8));
+        let found = find_graphics(&Disassembler::new(data));
+        assert_eq!(
+            found,
+            vec![Graphic {
+                name: "font".to_string(),
+                address: 0x104,
+                len: 2048,
+                format: GraphicsFormat::Font { height: 8 },
+            }]
+        );
+    }
+
+    #[test]
+    fn companion_files_are_classified_by_size() {
+        let mut disassembler = Disassembler::new(vec![0xC3]);
+        disassembler.map_file(AuxFile {
+            name: "TITLE.PIC".to_string(),
+            address: 0x1000,
+            data: vec![0; CGA_SIZE],
+        });
+        let found = find_graphics(&disassembler);
+        assert_eq!(found[0].format, GraphicsFormat::Cga);
+        assert_eq!(found[0].file_name(), "0x1000_TITLE_PIC.png");
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Rendering
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn cga_lines_are_interleaved() {
+        let mut bytes = vec![0; CGA_SIZE];
+        bytes[CGA_BANK] = 0b1100_0000; // first pixel of line 1 in white
+        let image = GraphicsFormat::Cga.render(&bytes);
+        assert_eq!(image.rgb[..3], [0; 3]);
+        let line_1 = 320 * 3;
+        assert_eq!(image.rgb[line_1..line_1 + 3], [0xFF; 3]);
+    }
+
+    #[test]
+    fn vga_palette_starts_with_the_ega_colours() {
+        assert_eq!(vga_colour(1), [0x00, 0x00, 0xAA]);
+        assert_eq!(vga_colour(31), [0xFF; 3]);
+        assert_eq!(vga_colour(32), [0x00, 0x00, 0xFF]);
+        assert_eq!(vga_colour(40), [0xFF, 0x00, 0x00]);
+    }
+}
@@ -0,0 +1,550 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Display};
+
+use iced_x86::{
+    FlowControl, Instruction, InstructionInfoFactory, Mnemonic, OpAccess, OpKind, Register,
+};
+
+use crate::consts::{Address, COM_OFFSET};
+use crate::disassemble::{Disassembler, is_data};
+use crate::syscall::SyscallType;
+
+/// How many instructions before a call or after a vector write the rest of
+/// the installation may be
+const WINDOW: usize = 6;
+
+/// The most instructions a walk through a handler visits
+const MAX_STEPS: usize = 4096;
+
+/// How a path through an interrupt handler leaves it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HandlerExit {
+    /// `iret`, as a handler should
+    Iret,
+    /// `retf` or `retf 2`, returning with the flags the handler set
+    FarReturn,
+    /// A far jump, usually to the handler that was installed before
+    Chain,
+    /// A near `ret`, which leaves the flags and CS on the stack
+    NearReturn,
+}
+
+/// Something an interrupt handler does that the interrupted code will not expect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HandlerProblem {
+    /// The handler returns with a near `ret` at the address
+    NearReturn(Address),
+    /// The handler changes the register without pushing it first
+    Clobbers(Register),
+    /// The handler calls DOS at the address without checking the InDOS flag
+    /// first, so it may enter DOS while DOS is already busy
+    UncheckedDosCall(Address),
+    /// The handler leaves at the address with this many more words pushed
+    /// than popped; negative if it popped more than it pushed
+    Unbalanced {
+        /// The instruction leaving the handler
+        address: Address,
+        /// Words pushed minus words popped
+        words: i32,
+    },
+}
+
+impl Display for HandlerProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandlerProblem::NearReturn(address) => write!(
+                f,
+                "returns with ret at 0x{address:04x} instead of iret, leaving CS and the flags on the stack"
+            ),
+            HandlerProblem::Clobbers(register) => {
+                write!(f, "changes {register:?} without saving it")
+            }
+            HandlerProblem::UncheckedDosCall(address) => write!(
+                f,
+                "calls DOS at 0x{address:04x} without checking the InDOS flag from int 21h/34h"
+            ),
+            HandlerProblem::Unbalanced { address, words } if *words > 0 => write!(
+                f,
+                "leaves {words} words on the stack when it exits at 0x{address:04x}"
+            ),
+            HandlerProblem::Unbalanced { address, words } => write!(
+                f,
+                "pops {} words more than it pushed before it exits at 0x{address:04x}",
+                -words
+            ),
+        }
+    }
+}
+
+/// An interrupt handler the program installs
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InterruptHandler {
+    /// The interrupt handled, if it could be told
+    pub vector: Option<u8>,
+    /// The first instruction of the handler
+    pub address: Address,
+    /// The int 21h/25h call or vector table write installing it
+    pub installed_at: Address,
+    /// Every way out of the handler, by address
+    pub exits: Vec<(Address, HandlerExit)>,
+    /// The int 21h calls the handler makes
+    pub dos_calls: Vec<Address>,
+    /// True if the handler reads the InDOS flag the program asked DOS for
+    pub checks_indos: bool,
+    /// What the handler does wrong
+    pub problems: Vec<HandlerProblem>,
+}
+
+impl InterruptHandler {
+    /// Returns true if the handler passes the interrupt on to the previous handler
+    pub fn chains(&self) -> bool {
+        self.exits
+            .iter()
+            .any(|&(_, exit)| exit == HandlerExit::Chain)
+    }
+}
+
+impl Display for InterruptHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.vector {
+            Some(vector) => write!(f, "interrupt {vector:02X}h handler")?,
+            None => write!(f, "interrupt handler")?,
+        }
+        write!(f, ", installed at 0x{:04x}", self.installed_at)?;
+        if self.chains() {
+            write!(f, ", chains to the previous handler")?;
+        }
+        if self.checks_indos && !self.dos_calls.is_empty() {
+            write!(f, ", checks InDOS before calling DOS")?;
+        }
+        Ok(())
+    }
+}
+
+/// The word register whose value `register` is part of, for the registers
+/// a handler must give back unchanged
+fn saved_register(register: Register) -> Option<Register> {
+    Some(match register {
+        Register::AL | Register::AH | Register::AX => Register::AX,
+        Register::BL | Register::BH | Register::BX => Register::BX,
+        Register::CL | Register::CH | Register::CX => Register::CX,
+        Register::DL | Register::DH | Register::DX => Register::DX,
+        Register::SI => Register::SI,
+        Register::DI => Register::DI,
+        Register::BP => Register::BP,
+        Register::DS => Register::DS,
+        Register::ES => Register::ES,
+        _ => return None,
+    })
+}
+
+/// The offset of the interrupt table word `instruction` moves a value to, if it does
+fn vector_table_write(instruction: &Instruction) -> Option<u16> {
+    if instruction.mnemonic() != Mnemonic::Mov
+        || instruction.op0_kind() != OpKind::Memory
+        || instruction.memory_base() != Register::None
+        || instruction.memory_index() != Register::None
+        || !matches!(instruction.memory_segment(), Register::ES | Register::DS)
+    {
+        return None;
+    }
+    let offset = instruction.memory_displacement32() as u16;
+    (offset < 0x400).then_some(offset)
+}
+
+/// Finds the handlers installed with int 21h/25h or by writing the
+/// interrupt table directly
+fn find_installations(
+    disassembler: &Disassembler,
+    code: &[&Instruction],
+) -> Vec<(Option<u8>, Address, Address)> {
+    let mut found = Vec::new();
+    for syscall in &disassembler.syscall_list.0 {
+        if syscall.number != SyscallType::SetInterruptVector {
+            continue;
+        }
+        let Some(handler) = syscall.dx else {
+            continue;
+        };
+        let index = code.partition_point(|instruction| instruction.ip() < syscall.address as u64);
+        let vector = code[index.saturating_sub(WINDOW)..index]
+            .iter()
+            .rev()
+            .find_map(|previous| {
+                if previous.mnemonic() != Mnemonic::Mov || previous.op0_kind() != OpKind::Register {
+                    return None;
+                }
+                match (previous.op0_register(), previous.op1_kind()) {
+                    (Register::AL, OpKind::Immediate8) => Some(previous.immediate8()),
+                    (Register::AX, OpKind::Immediate16) => Some(previous.immediate16() as u8),
+                    _ => None,
+                }
+            });
+        found.push((vector, handler, syscall.address));
+    }
+
+    // mov word [es:n*4], handler followed by a write of the segment to n*4+2
+    for (index, instruction) in code.iter().enumerate() {
+        let Some(offset) = vector_table_write(instruction) else {
+            continue;
+        };
+        if offset % 4 != 0 || instruction.op1_kind() != OpKind::Immediate16 {
+            continue;
+        }
+        let segment_written = code[index + 1..].iter().take(WINDOW).any(|next| {
+            vector_table_write(next) == Some(offset + 2)
+                && next.memory_segment() == instruction.memory_segment()
+        });
+        if segment_written {
+            found.push((
+                Some((offset / 4) as u8),
+                instruction.immediate16(),
+                instruction.ip() as Address,
+            ));
+        }
+    }
+    found.sort_by_key(|&(_, handler, installed_at)| (handler, installed_at));
+    found.dedup_by_key(|&mut (_, handler, _)| handler);
+    found
+}
+
+/// Follows every path through the handler starting at `start`, counting
+/// the words pushed and noting the registers changed
+///
+/// Returns the exits, the problems and the indices of the instructions reached.
+fn walk(
+    code: &[&Instruction],
+    start: usize,
+    factory: &mut InstructionInfoFactory,
+) -> (
+    Vec<(Address, HandlerExit)>,
+    Vec<HandlerProblem>,
+    BTreeSet<usize>,
+) {
+    let index_of = |address: u64| {
+        code.binary_search_by_key(&address, |instruction| instruction.ip())
+            .ok()
+    };
+    let mut exits = Vec::new();
+    let mut problems = Vec::new();
+    let mut written = BTreeSet::new();
+    let mut pushed = BTreeSet::new();
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![(start, 0i32)];
+    while let Some((index, depth)) = pending.pop() {
+        if visited.len() >= MAX_STEPS || !visited.insert((index, depth)) {
+            continue;
+        }
+        let Some(instruction) = code.get(index) else {
+            continue;
+        };
+        let address = instruction.ip() as Address;
+        let depth = depth
+            + match instruction.mnemonic() {
+                Mnemonic::Push | Mnemonic::Pushf => 1,
+                Mnemonic::Pop | Mnemonic::Popf => -1,
+                Mnemonic::Pusha => 8,
+                Mnemonic::Popa => -8,
+                _ => 0,
+            };
+        match instruction.mnemonic() {
+            Mnemonic::Push if instruction.op0_kind() == OpKind::Register => {
+                pushed.extend(saved_register(instruction.op0_register()));
+            }
+            Mnemonic::Pusha => pushed.extend([
+                Register::AX,
+                Register::BX,
+                Register::CX,
+                Register::DX,
+                Register::SI,
+                Register::DI,
+                Register::BP,
+            ]),
+            Mnemonic::Pop | Mnemonic::Popa | Mnemonic::Popf => {}
+            _ => {
+                let info = factory.info(instruction);
+                written.extend(
+                    info.used_registers()
+                        .iter()
+                        .filter(|used| {
+                            matches!(
+                                used.access(),
+                                OpAccess::Write
+                                    | OpAccess::CondWrite
+                                    | OpAccess::ReadWrite
+                                    | OpAccess::ReadCondWrite
+                            )
+                        })
+                        .filter_map(|used| saved_register(used.register())),
+                );
+            }
+        }
+
+        let exit = match instruction.mnemonic() {
+            Mnemonic::Iret => Some(HandlerExit::Iret),
+            Mnemonic::Retf => Some(HandlerExit::FarReturn),
+            Mnemonic::Ret => Some(HandlerExit::NearReturn),
+            Mnemonic::Jmp
+                if instruction.op0_kind() == OpKind::FarBranch16
+                    || instruction.is_jmp_far_indirect() =>
+            {
+                Some(HandlerExit::Chain)
+            }
+            _ => None,
+        };
+        if let Some(exit) = exit {
+            exits.push((address, exit));
+            if exit == HandlerExit::NearReturn {
+                problems.push(HandlerProblem::NearReturn(address));
+            } else if depth != 0 {
+                problems.push(HandlerProblem::Unbalanced {
+                    address,
+                    words: depth,
+                });
+            }
+            continue;
+        }
+
+        let target = (instruction.op0_kind() == OpKind::NearBranch16)
+            .then(|| index_of(instruction.near_branch_target()))
+            .flatten();
+        match instruction.flow_control() {
+            FlowControl::UnconditionalBranch => {
+                pending.extend(target.map(|target| (target, depth)))
+            }
+            FlowControl::ConditionalBranch => {
+                pending.extend(target.map(|target| (target, depth)));
+                pending.push((index + 1, depth));
+            }
+            FlowControl::Next
+            | FlowControl::Call
+            | FlowControl::IndirectCall
+            | FlowControl::Interrupt
+                if code
+                    .get(index + 1)
+                    .is_some_and(|next| next.ip() == instruction.next_ip()) =>
+            {
+                pending.push((index + 1, depth));
+            }
+            _ => {}
+        }
+    }
+    exits.sort_unstable_by_key(|&(address, _)| address);
+    exits.dedup();
+    problems.extend(
+        written
+            .difference(&pushed)
+            .map(|&register| HandlerProblem::Clobbers(register)),
+    );
+    problems.sort_unstable_by_key(|problem| match problem {
+        HandlerProblem::Clobbers(_) => 0,
+        HandlerProblem::NearReturn(address)
+        | HandlerProblem::UncheckedDosCall(address)
+        | HandlerProblem::Unbalanced { address, .. } => *address as u32 + 1,
+    });
+    problems.dedup();
+    let reached = visited.into_iter().map(|(index, _)| index).collect();
+    (exits, problems, reached)
+}
+
+/// Returns true if `instruction` compares or tests a byte through ES, as
+/// code reading the InDOS flag at the ES:BX int 21h/34h returned does
+fn reads_through_es(instruction: &Instruction) -> bool {
+    matches!(instruction.mnemonic(), Mnemonic::Cmp | Mnemonic::Test)
+        && instruction.op0_kind() == OpKind::Memory
+        && instruction.memory_segment() == Register::ES
+}
+
+/// Finds the interrupt handlers a program installs and checks each follows
+/// the rules for one
+///
+/// Handlers are found at the DX of int 21h/25h calls and in `mov` pairs
+/// writing an offset and segment to the interrupt table. Every path from the
+/// handler's first instruction is followed to its `iret`, `retf` or far jump
+/// to the previous handler; calls are assumed to return. A handler must
+/// leave the stack as it found it and push every register it changes, and
+/// may only call DOS if it checks the InDOS flag first: the program must
+/// ask for the flag's address with int 21h/34h and the handler compare the
+/// byte there.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::handlers::{HandlerProblem, find_handlers};
+/// use disassembler::samples;
+///
+/// let tsr = samples::find("tsr").unwrap();
+/// let handlers = find_handlers(&Disassembler::new(tsr.bytes.to_vec()));
+/// assert_eq!(handlers[0].vector, Some(0x1C));
+/// assert!(handlers[0].chains());
+/// assert_eq!(handlers[0].problems, vec![]);
+/// ```
+pub fn find_handlers(disassembler: &Disassembler) -> Vec<InterruptHandler> {
+    let code: Vec<&Instruction> = disassembler
+        .instructions
+        .0
+        .iter()
+        .filter(|instruction| !is_data(instruction))
+        .collect();
+    let mut factory = InstructionInfoFactory::new();
+    let end = COM_OFFSET as u32 + disassembler.data.len() as u32;
+    let gets_indos = disassembler
+        .syscall_list
+        .0
+        .iter()
+        .any(|syscall| syscall.number == SyscallType::GetInDOSFlag);
+    find_installations(disassembler, &code)
+        .into_iter()
+        .filter(|&(_, handler, _)| handler >= COM_OFFSET && (handler as u32) < end)
+        .filter_map(|(vector, address, installed_at)| {
+            let start = code
+                .binary_search_by_key(&(address as u64), |instruction| instruction.ip())
+                .ok()?;
+            let (exits, mut problems, reached) = walk(&code, start, &mut factory);
+            let dos_calls: Vec<Address> = reached
+                .iter()
+                .map(|&index| code[index])
+                .filter(|instruction| {
+                    instruction.mnemonic() == Mnemonic::Int && instruction.immediate8() == 0x21
+                })
+                .map(|instruction| instruction.ip() as Address)
+                .collect();
+            let checks_indos =
+                gets_indos && reached.iter().any(|&index| reads_through_es(code[index]));
+            if let (Some(&first), false) = (dos_calls.first(), checks_indos) {
+                problems.push(HandlerProblem::UncheckedDosCall(first));
+            }
+            Some(InterruptHandler {
+                vector,
+                address,
+                installed_at,
+                exits,
+                dos_calls,
+                checks_indos,
+                problems,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Finding handlers
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn vector_table_writes_install_handlers() {
+        let data = vec![
+            0x31, 0xC0, // xor ax, ax
+            0x8E, 0xC0, // mov es, ax
+            0x26, 0xC7, 0x06, 0x24, 0x00, 0x10, 0x01, // mov word [es:0x24], 0x110
+            0x26, 0x8C, 0x0E, 0x26, 0x00, // mov [es:0x26], cs
+            0x50, // 0x110: push ax
+            0xCF, // iret
+        ];
+        let handlers = find_handlers(&Disassembler::new(data));
+        assert_eq!(
+            handlers,
+            vec![InterruptHandler {
+                vector: Some(0x09),
+                address: 0x110,
+                installed_at: 0x104,
+                exits: vec![(0x111, HandlerExit::Iret)],
+                dos_calls: vec![],
+                checks_indos: false,
+                problems: vec![HandlerProblem::Unbalanced {
+                    address: 0x111,
+                    words: 1
+                }],
+            }]
+        );
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Checking handlers
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn near_returns_and_unsaved_registers_are_flagged() {
+        let data = vec![
+            0xBA, 0x0A, 0x01, // mov dx, 0x10a
+            0xB8, 0x09, 0x25, // mov ax, 0x2509
+            0xCD, 0x21, // int 21h
+            0xCD, 0x20, // int 20h
+            0x53, // 0x10a: push bx
+            0xE4, 0x60, // in al, 0x60
+            0xBB, 0x01, 0x00, // mov bx, 1
+            0x5B, // pop bx
+            0xC3, // ret
+        ];
+        let disassembler = Disassembler::new(data);
+        let handlers = find_handlers(&disassembler);
+        assert_eq!(handlers[0].vector, Some(0x09));
+        assert_eq!(
+            handlers[0].problems,
+            vec![
+                HandlerProblem::Clobbers(Register::AX),
+                HandlerProblem::NearReturn(0x111),
+            ]
+        );
+        let label = disassembler.labels.get_by_address(0x10a).unwrap();
+        assert_eq!(label.name, "int09_handler_0x010a");
+    }
+    /// Installs an int 1Ch handler that calls int 21h/0Bh, checking the
+    /// InDOS flag first if `checked`
+    fn indos_program(checked: bool) -> Vec<u8> {
+        let cmp: &[u8] = if checked {
+            &[0x26, 0x80, 0x3F, 0x00] // cmp byte [es:bx], 0
+        } else {
+            &[0x90, 0x90, 0x90, 0x90] // nop
+        };
+        [
+            &[
+                0xB4, 0x34, // mov ah, 0x34
+                0xCD, 0x21, // int 21h
+                0x89, 0x1E, 0x2C, 0x01, // mov [indos], bx
+                0x8C, 0x06, 0x2E, 0x01, // mov [indos+2], es
+                0xBA, 0x16, 0x01, // mov dx, handler
+                0xB8, 0x1C, 0x25, // mov ax, 0x251c
+                0xCD, 0x21, // int 21h
+                0xCD, 0x20, // int 20h
+                0x50, // 0x116: push ax
+                0x53, // push bx
+                0x06, // push es
+                0x2E, 0xC4, 0x1E, 0x2C, 0x01, // les bx, [cs:indos]
+            ][..],
+            cmp,
+            &[
+                0x75, 0x04, // jne skip
+                0xB4, 0x0B, // mov ah, 0x0b
+                0xCD, 0x21, // 0x126: int 21h
+                0x07, // skip: pop es
+                0x5B, // pop bx
+                0x58, // pop ax
+                0xCF, // iret
+                0x00, 0x00, 0x00, 0x00, // indos: dd 0
+            ],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn dos_calls_need_an_indos_check() {
+        let checked = find_handlers(&Disassembler::new(indos_program(true)));
+        assert_eq!(checked[0].dos_calls, vec![0x126]);
+        assert!(checked[0].checks_indos);
+        assert_eq!(checked[0].problems, vec![]);
+
+        let unchecked = find_handlers(&Disassembler::new(indos_program(false)));
+        assert!(!unchecked[0].checks_indos);
+        assert_eq!(
+            unchecked[0].problems,
+            vec![HandlerProblem::UncheckedDosCall(0x126)]
+        );
+    }
+}
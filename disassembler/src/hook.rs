@@ -0,0 +1,543 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{
+    BlockEncoder, BlockEncoderOptions, Instruction, InstructionBlock, OpKind, Register,
+};
+
+use crate::consts::{Address, COM_OFFSET, SIZE};
+use crate::disassemble::{Disassembler, is_data};
+use crate::freespace::FreeSpace;
+
+/// The length of a near `jmp rel16`
+const JMP_LEN: usize = 3;
+
+/// The most a COM file can hold, the 64K segment less the PSP
+const MAX_PROGRAM: usize = 0x10000 - COM_OFFSET as usize;
+
+/// Why a hook could not be installed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookError {
+    /// No instruction starts at the address
+    NotAnInstruction(Address),
+    /// The program ends before the jump to the trampoline would fit
+    TooShort(Address),
+    /// Something jumps into or refers to an instruction the jump would overwrite
+    Referenced(Address),
+    /// The bytes at the address were already changed by another patch
+    AlreadyPatched(Address),
+    /// The displaced instructions cannot be moved
+    Relocation(String),
+    /// No unused region can hold the trampoline
+    NoRoom(usize),
+    /// The program would grow past what a COM file can hold
+    TooLarge(usize),
+}
+
+impl Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookError::NotAnInstruction(address) => {
+                write!(f, "no instruction starts at 0x{address:04x}")
+            }
+            HookError::TooShort(address) => {
+                write!(
+                    f,
+                    "the program ends too soon after 0x{address:04x} for a jump"
+                )
+            }
+            HookError::Referenced(address) => write!(
+                f,
+                "the instruction at 0x{address:04x} would be overwritten but something refers to it"
+            ),
+            HookError::AlreadyPatched(address) => {
+                write!(f, "the code at 0x{address:04x} was already patched")
+            }
+            HookError::Relocation(message) => write!(f, "cannot relocate instructions: {message}"),
+            HookError::NoRoom(len) => write!(f, "no unused region holds {len} bytes"),
+            HookError::TooLarge(len) => write!(
+                f,
+                "the program would be {len} bytes, more than a COM file can hold"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HookError {}
+
+/// A hook installed by [`install_hook`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hook {
+    /// The hooked address, which now jumps to the trampoline
+    pub target: Address,
+    /// The address of the trampoline
+    pub trampoline: Address,
+    /// How many bytes of instructions were moved into the trampoline
+    pub displaced: usize,
+    /// How many bytes the trampoline takes
+    pub len: usize,
+}
+
+impl Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "hooked 0x{:04x}: trampoline at 0x{:04x}, {} bytes, {} bytes of instructions moved",
+            self.target, self.trampoline, self.len, self.displaced
+        )
+    }
+}
+
+/// Code inserted by [`insert_code`], and the program laid out around it
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Insertion {
+    /// The address the code was inserted at, where it now starts
+    pub at: Address,
+    /// How many bytes were inserted
+    pub len: usize,
+    /// How many more bytes short branches took once they no longer reached
+    pub grown: usize,
+    /// How many relative branches were rewritten
+    pub branches: usize,
+    /// How many immediates and memory operands pointing at a label or
+    /// string were rewritten
+    pub offsets: usize,
+    /// The old and new address of every instruction and data row
+    pub moved: Vec<(Address, Address)>,
+    /// The program with the code inserted
+    pub program: Vec<u8>,
+}
+
+impl Insertion {
+    /// Where `address` in the program before the insertion ended up
+    ///
+    /// The insertion point itself stays where it was, at the start of the
+    /// inserted code.
+    pub fn new_address(&self, address: Address) -> Address {
+        if address == self.at {
+            return self.moved_code();
+        }
+        match self.moved.partition_point(|&(old, _)| old <= address) {
+            0 => address,
+            after => {
+                let (old, new) = self.moved[after - 1];
+                new.wrapping_add(address - old)
+            }
+        }
+    }
+
+    /// Where the inserted code ended up, just before the instruction it
+    /// was inserted in front of
+    fn moved_code(&self) -> Address {
+        let before = self.moved.partition_point(|&(old, _)| old < self.at);
+        self.moved[before].1 - self.len as Address
+    }
+}
+
+impl Display for Insertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "inserted {} bytes at 0x{:04x}: {} branches and {} offsets rewritten",
+            self.len, self.at, self.branches, self.offsets
+        )?;
+        if self.grown > 0 {
+            write!(f, ", {} bytes of longer branches", self.grown)?;
+        }
+        Ok(())
+    }
+}
+
+/// Encodes `instructions` to run at `address`, fixing up relative branches
+fn relocate(instructions: &[Instruction], address: Address) -> Result<Vec<u8>, HookError> {
+    let block = InstructionBlock::new(instructions, address as u64);
+    BlockEncoder::encode(SIZE, block, BlockEncoderOptions::NONE)
+        .map(|result| result.code_buffer)
+        .map_err(|error| HookError::Relocation(error.to_string()))
+}
+
+/// A near `jmp` at `from` to `to`
+fn jump(from: Address, to: Address) -> [u8; JMP_LEN] {
+    let rel = to.wrapping_sub(from.wrapping_add(JMP_LEN as Address));
+    let [low, high] = rel.to_le_bytes();
+    [0xE9, low, high]
+}
+
+/// Hooks the instruction at `target` in `output` so `code` runs before it
+///
+/// The instructions the jump to the trampoline overwrites are moved into the
+/// trampoline after `code`, with relative branches fixed up, followed by a
+/// jump back to the instruction after them; leftover bytes at `target` are
+/// filled with `nop`. The trampoline is placed with
+/// [`FreeSpace::allocate_patch_space`], and `output` grows if it lands past
+/// the end of the file. `code` must not depend on where it is placed and
+/// should leave the registers and flags as it found them.
+///
+/// `disassembler` is the unpatched program and `output` a copy of it that
+/// earlier patches may have changed, so several hooks can be installed one
+/// after another. Moved conditional branches that no longer reach their
+/// target in a short jump are encoded in their near form, which needs a 386.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::freespace::FreeSpace;
+/// use disassembler::hook::install_hook;
+///
+/// // mov ah, 0x4c / int 21h
+/// let program = vec![0xB4, 0x4C, 0xCD, 0x21];
+/// let disassembler = Disassembler::new(program.clone());
+/// let mut space = FreeSpace::analyze(&disassembler);
+/// let mut output = program;
+/// let hook = install_hook(&disassembler, &mut space, &mut output, 0x100, &[0x90]).unwrap();
+/// assert_eq!(hook.trampoline, 0x110);
+/// assert_eq!(output[..3], [0xE9, 0x0D, 0x00]);
+/// ```
+pub fn install_hook(
+    disassembler: &Disassembler,
+    space: &mut FreeSpace,
+    output: &mut Vec<u8>,
+    target: Address,
+    code: &[u8],
+) -> Result<Hook, HookError> {
+    let instructions = &disassembler.instructions.0;
+    let index = disassembler
+        .instructions
+        .index_at(target)
+        .ok_or(HookError::NotAnInstruction(target))?;
+
+    let mut displaced = Vec::new();
+    let mut len = 0;
+    for instruction in &instructions[index..] {
+        if len >= JMP_LEN {
+            break;
+        }
+        if is_data(instruction) {
+            return Err(HookError::TooShort(target));
+        }
+        let address = instruction.ip() as Address;
+        if address != target && !disassembler.references(address).is_empty() {
+            return Err(HookError::Referenced(address));
+        }
+        len += instruction.len();
+        displaced.push(*instruction);
+    }
+    if len < JMP_LEN {
+        return Err(HookError::TooShort(target));
+    }
+    let offset = (target - COM_OFFSET) as usize;
+    if output.get(offset..offset + len) != Some(&disassembler.data[offset..offset + len]) {
+        return Err(HookError::AlreadyPatched(target));
+    }
+
+    // far from the original code every branch takes its longest form,
+    // which is the most room the moved instructions can need
+    let longest = relocate(&displaced, target.wrapping_add(0x8000))?.len();
+    let size = code.len() + longest + JMP_LEN;
+    let trampoline = space
+        .allocate_patch_space(size)
+        .ok_or(HookError::NoRoom(size))?;
+
+    let moved_at = trampoline + code.len() as Address;
+    let mut body = code.to_vec();
+    body.extend(relocate(&displaced, moved_at)?);
+    let back_at = trampoline + body.len() as Address;
+    body.extend(jump(back_at, target + len as Address));
+
+    let at = (trampoline - COM_OFFSET) as usize;
+    if output.len() < at + body.len() {
+        // slack lies past the end of the file
+        output.resize(at + body.len(), 0);
+    }
+    output[at..at + body.len()].copy_from_slice(&body);
+    output[offset..offset + JMP_LEN].copy_from_slice(&jump(target, trampoline));
+    output[offset + JMP_LEN..offset + len].fill(0x90);
+
+    Ok(Hook {
+        target,
+        trampoline,
+        displaced: len,
+        len: body.len(),
+    })
+}
+
+/// Inserts `code` before the instruction at `at`, moving the rest of the
+/// program up to make room
+///
+/// Every relative branch is rewritten to reach the same instruction as
+/// before, and branches to `at` reach the inserted code. Short branches
+/// that no longer reach are encoded in their near form, which for
+/// conditional branches needs a 386. Immediates and fixed memory operands
+/// are rewritten too when they point at a label or a string, as `mov dx,
+/// msg` does; other numbers are left alone. Jump tables, pointers stored in
+/// data and addresses the program computes are not found, so a program
+/// relying on them needs fixing by hand.
+///
+/// Everything else is copied byte for byte. Unlike [`install_hook`] the
+/// program is laid out again from scratch, so `disassembler` must be the
+/// program to change rather than an earlier patch of it.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::hook::insert_code;
+///
+/// // jmp short 0x104 / nop / nop / ret
+/// let disassembler = Disassembler::new(vec![0xEB, 0x02, 0x90, 0x90, 0xC3]);
+/// let insertion = insert_code(&disassembler, 0x102, &[0xF8]).unwrap();
+/// assert_eq!(insertion.program, [0xEB, 0x03, 0xF8, 0x90, 0x90, 0xC3]);
+/// assert_eq!(insertion.new_address(0x104), 0x105);
+/// ```
+pub fn insert_code(
+    disassembler: &Disassembler,
+    at: Address,
+    code: &[u8],
+) -> Result<Insertion, HookError> {
+    let instructions = &disassembler.instructions.0;
+    let index = disassembler
+        .instructions
+        .index_at(at)
+        .filter(|&index| !is_data(&instructions[index]))
+        .ok_or(HookError::NotAnInstruction(at))?;
+    let shift = code.len() as Address;
+    // where an address is with the code inserted, before any branch grows
+    let shifted = |address: Address| {
+        if address > at {
+            address.wrapping_add(shift)
+        } else {
+            address
+        }
+    };
+    let symbolized = |address: Address| {
+        disassembler.labels.get_by_address(address).is_some()
+            || disassembler
+                .string_constant_list
+                .0
+                .iter()
+                .any(|string| string.start == address)
+    };
+
+    // lays the program out with `place` telling where data ends up
+    let layout = |place: &dyn Fn(Address) -> Address| {
+        let mut block = Vec::new();
+        let (mut branches, mut offsets) = (0, 0);
+        for (position, instruction) in instructions.iter().enumerate() {
+            if position == index {
+                for (chunk_index, chunk) in code.chunks(16).enumerate() {
+                    let mut inserted = Instruction::with_declare_byte(chunk)
+                        .map_err(|error| HookError::Relocation(error.to_string()))?;
+                    inserted.set_ip((at + 16 * chunk_index as Address) as u64);
+                    block.push(inserted);
+                }
+            }
+            let ip = instruction.ip() as Address;
+            let mut moved = *instruction;
+            let mut changed = false;
+            if !is_data(instruction) && !instruction.is_invalid() {
+                for operand in 0..instruction.op_count() {
+                    match instruction.op_kind(operand) {
+                        OpKind::NearBranch16 => {
+                            let target = instruction.near_branch16();
+                            moved.set_near_branch16(shifted(target));
+                            changed = true;
+                            branches += usize::from(shifted(target) != target);
+                        }
+                        OpKind::Immediate16 if symbolized(instruction.immediate16()) => {
+                            let value = place(instruction.immediate16());
+                            offsets += usize::from(value != instruction.immediate16());
+                            moved.set_immediate16(value);
+                            changed = true;
+                        }
+                        OpKind::Memory
+                            if instruction.memory_base() == Register::None
+                                && instruction.memory_index() == Register::None
+                                && symbolized(instruction.memory_displacement32() as Address) =>
+                        {
+                            let displacement = instruction.memory_displacement32() as Address;
+                            let value = place(displacement);
+                            offsets += usize::from(value != displacement);
+                            moved.set_memory_displacement32(value as u32);
+                            changed = true;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            if !changed {
+                // copied as it was, whatever encoding it used
+                let bytes = disassembler.original_bytes(instruction).unwrap_or_default();
+                moved = Instruction::with_declare_byte(bytes)
+                    .map_err(|error| HookError::Relocation(error.to_string()))?;
+            }
+            moved.set_ip(if ip >= at { ip.wrapping_add(shift) } else { ip } as u64);
+            block.push(moved);
+        }
+        let result = BlockEncoder::encode(
+            SIZE,
+            InstructionBlock::new(&block, COM_OFFSET as u64),
+            BlockEncoderOptions::RETURN_NEW_INSTRUCTION_OFFSETS,
+        )
+        .map_err(|error| HookError::Relocation(error.to_string()))?;
+        Ok::<_, HookError>((block, result, branches, offsets))
+    };
+
+    let (block, mut result, branches, mut offsets) = layout(&shifted)?;
+    let inserted_rows = code.len().div_ceil(16);
+    let moved: Vec<(Address, Address)> = instructions
+        .iter()
+        .enumerate()
+        .map(|(position, instruction)| {
+            let row = position + if position >= index { inserted_rows } else { 0 };
+            (
+                instruction.ip() as Address,
+                COM_OFFSET + result.new_instruction_offsets[row] as Address,
+            )
+        })
+        .collect();
+    let expected = block.iter().map(Instruction::ip).eq(result
+        .new_instruction_offsets
+        .iter()
+        .map(|&offset| COM_OFFSET as u64 + offset as u64));
+    let mut insertion = Insertion {
+        at,
+        len: code.len(),
+        grown: 0,
+        branches,
+        offsets,
+        moved,
+        program: Vec::new(),
+    };
+    if !expected {
+        // branches grew, so the data moved further than the code inserted
+        (_, result, _, offsets) = layout(&|address| insertion.new_address(address))?;
+        insertion.offsets = offsets;
+    }
+    let length = result.code_buffer.len();
+    if length > MAX_PROGRAM {
+        return Err(HookError::TooLarge(length));
+    }
+    insertion.grown = length - disassembler.data.len() - code.len();
+    insertion.program = result.code_buffer;
+    Ok(insertion)
+}
+
+#[cfg(all(test, feature = "emulator"))]
+mod tests {
+    use super::*;
+    use crate::emulate::Emulator;
+
+    /// Prints `hi$` and exits
+    fn program() -> Vec<u8> {
+        let mut data = vec![
+            0xBA, 0x0C, 0x01, // mov dx, 0x10c
+            0xB4, 0x09, 0xCD, 0x21, // mov ah, 9 / int 21h
+            0xB8, 0x00, 0x4C, 0xCD, 0x21, // mov ax, 0x4c00 / int 21h
+        ];
+        data.extend(b"hi$");
+        data
+    }
+
+    fn run(program: &[u8]) -> Vec<u8> {
+        let mut emulator = Emulator::new(program);
+        emulator.run(1000);
+        emulator.output
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Installing hooks
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn hook_runs_before_the_original_code() {
+        let disassembler = Disassembler::new(program());
+        let mut space = FreeSpace::analyze(&disassembler);
+        let mut output = program();
+        // mov dl, '!' / mov ah, 2 / int 21h
+        let code = [0xB2, b'!', 0xB4, 0x02, 0xCD, 0x21];
+        let hook = install_hook(&disassembler, &mut space, &mut output, 0x100, &code).unwrap();
+        assert_eq!(hook.displaced, 3);
+        assert_eq!(run(&output), b"!hi");
+    }
+
+    #[test]
+    fn moved_branches_still_reach_their_target() {
+        // jmp short 0x104 / int 20h / mov dl, 'x' / mov ah, 2 / int 21h / int 20h
+        let program = vec![
+            0xEB, 0x02, 0xCD, 0x20, 0xB2, b'x', 0xB4, 0x02, 0xCD, 0x21, 0xCD, 0x20,
+        ];
+        let disassembler = Disassembler::new(program.clone());
+        let mut space = FreeSpace::analyze(&disassembler);
+        let mut output = program;
+        let hook = install_hook(&disassembler, &mut space, &mut output, 0x100, &[0x90]).unwrap();
+        assert_eq!(hook.displaced, 4);
+        assert_eq!(run(&output), b"x");
+    }
+
+    #[test]
+    fn overwriting_a_branch_target_is_refused() {
+        // nop / nop / jmp short 0x101 / ret
+        let program = vec![0x90, 0x90, 0xEB, 0xFD, 0xC3];
+        let disassembler = Disassembler::new(program.clone());
+        let mut space = FreeSpace::analyze(&disassembler);
+        let mut output = program;
+        assert_eq!(
+            install_hook(&disassembler, &mut space, &mut output, 0x100, &[]),
+            Err(HookError::Referenced(0x101))
+        );
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Inserting code
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn inserted_code_moves_strings_with_the_program() {
+        let disassembler = Disassembler::new(program());
+        // mov dl, '!' / mov ah, 2 / int 21h
+        let code = [0xB2, b'!', 0xB4, 0x02, 0xCD, 0x21];
+        let insertion = insert_code(&disassembler, 0x100, &code).unwrap();
+        assert_eq!(insertion.offsets, 1, "mov dx, msg");
+        assert_eq!(insertion.new_address(0x10C), 0x112);
+        assert_eq!(run(&insertion.program), b"!hi");
+    }
+
+    #[test]
+    fn short_branches_that_no_longer_reach_grow() {
+        let mut program = vec![0xEB, 0x7E]; // jmp short 0x180
+        program.extend([0x90; 0x7E]);
+        program.extend([
+            0xBA, 0x89, 0x01, // 0x180: mov dx, 0x189
+            0xB4, 0x09, 0xCD, 0x21, // mov ah, 9 / int 21h
+            0xCD, 0x20, // int 20h
+        ]);
+        program.extend(b"ok$");
+        let disassembler = Disassembler::new(program);
+        let insertion = insert_code(&disassembler, 0x102, &[0x90; 4]).unwrap();
+        assert_eq!(insertion.grown, 1, "jmp short becomes jmp near");
+        assert_eq!(insertion.new_address(0x180), 0x185);
+        assert_eq!(insertion.program[..3], [0xE9, 0x82, 0x00]);
+        assert_eq!(run(&insertion.program), b"ok");
+    }
+
+    #[test]
+    fn inserting_inside_an_instruction_is_refused() {
+        let disassembler = Disassembler::new(program());
+        assert_eq!(
+            insert_code(&disassembler, 0x101, &[0x90]),
+            Err(HookError::NotAnInstruction(0x101))
+        );
+    }
+
+    #[test]
+    fn hooking_twice_is_refused() {
+        let disassembler = Disassembler::new(program());
+        let mut space = FreeSpace::analyze(&disassembler);
+        let mut output = program();
+        install_hook(&disassembler, &mut space, &mut output, 0x100, &[]).unwrap();
+        assert_eq!(
+            install_hook(&disassembler, &mut space, &mut output, 0x100, &[]),
+            Err(HookError::AlreadyPatched(0x100))
+        );
+    }
+}
@@ -0,0 +1,371 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{FlowControl, Instruction, MemorySize, Mnemonic, OpKind, Register};
+
+use crate::comment::{Comment, CommentSource, CommentType};
+use crate::consts::{Address, COM_OFFSET};
+use crate::disassemble::Disassembler;
+
+/// How many instructions before the dispatch jump are searched for the fetch
+const FETCH_WINDOW: usize = 8;
+
+/// The most handlers read from a jump table
+const MAX_HANDLERS: usize = 256;
+
+/// An embedded bytecode interpreter
+///
+/// Recognised by a dispatch loop: a byte fetched through a pointer register
+/// (`lodsb` or `mov r8, [reg]`), an indirect near jump or call through a
+/// table of handler addresses indexed by a register, and a branch from
+/// outside the loop back to its head.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Interpreter {
+    /// The start of the dispatch loop, where the handlers return to
+    pub head: Address,
+    /// The instruction fetching the next opcode
+    pub fetch: Address,
+    /// The indirect jump or call through the table
+    pub dispatch: Address,
+    /// The address of the jump table
+    pub table: Address,
+    /// The handler for each opcode, indexed by opcode
+    pub handlers: Vec<Address>,
+    /// The bytecode the pointer register is loaded with before the loop, if found
+    pub bytecode: Option<(Address, Address)>,
+}
+
+impl Interpreter {
+    /// Looks for dispatch loops in the analysed program
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::interpreter::Interpreter;
+    ///
+    /// let data = vec![
+    ///     0xAC, // lodsb
+    ///     0x88, 0xC3, 0x30, 0xFF, 0xD1, 0xE3, // mov bl, al / xor bh, bh / shl bx, 1
+    ///     0xFF, 0xA7, 0x0E, 0x01, // jmp [bx+0x10e]
+    ///     0xC3, // ret
+    ///     0xEB, 0xF2, // jmp 0x100
+    ///     0x0B, 0x01, 0x0C, 0x01, // table
+    /// ];
+    /// let found = Interpreter::detect(&Disassembler::new(data));
+    /// assert_eq!(found[0].handlers, vec![0x10B, 0x10C]);
+    /// ```
+    pub fn detect(disassembler: &Disassembler) -> Vec<Interpreter> {
+        let instructions = &disassembler.instructions.0;
+        let end = COM_OFFSET as u64 + disassembler.data.len() as u64;
+        let mut found = Vec::new();
+        for (index, dispatch) in instructions.iter().enumerate() {
+            let Some((table, _)) = table_operand(dispatch, end) else {
+                continue;
+            };
+            let window = &instructions[index.saturating_sub(FETCH_WINDOW)..index];
+            let Some((fetch, pointer)) = window.iter().rev().find_map(|instruction| {
+                fetched_through(instruction).map(|pointer| (instruction, pointer))
+            }) else {
+                continue;
+            };
+            let Some(head) = loop_head(instructions, window[0].ip(), fetch.ip(), dispatch.ip())
+            else {
+                continue;
+            };
+
+            let bytecode_start = instructions
+                .iter()
+                .take_while(|instruction| instruction.ip() < head)
+                .filter(|instruction| {
+                    instruction.mnemonic() == Mnemonic::Mov
+                        && instruction.op0_kind() == OpKind::Register
+                        && instruction.op0_register() == pointer
+                        && instruction.op1_kind() == OpKind::Immediate16
+                        && (COM_OFFSET as u64..end).contains(&(instruction.immediate16() as u64))
+                })
+                .last()
+                .map(|instruction| instruction.immediate16());
+
+            let handlers = read_table(disassembler, table, bytecode_start, end);
+            if handlers.len() < 2 {
+                continue;
+            }
+
+            let mut interpreter = Interpreter {
+                head: head as Address,
+                fetch: fetch.ip() as Address,
+                dispatch: dispatch.ip() as Address,
+                table,
+                handlers,
+                bytecode: None,
+            };
+            interpreter.bytecode =
+                bytecode_start.map(|start| (start, interpreter.code_after(start, end)));
+            found.push(interpreter);
+        }
+        found
+    }
+
+    /// The address just past the jump table
+    pub fn table_end(&self) -> Address {
+        self.table + 2 * self.handlers.len() as Address
+    }
+
+    /// The first part of the interpreter after `start`, or the end of the program
+    fn code_after(&self, start: Address, end: u64) -> Address {
+        [self.head, self.fetch, self.dispatch, self.table]
+            .into_iter()
+            .chain(self.handlers.iter().copied())
+            .filter(|&address| address > start)
+            .min()
+            .unwrap_or(end as Address)
+    }
+
+    /// Labels the dispatch loop, table and handlers and shows the table and
+    /// bytecode as data
+    ///
+    /// Handlers are named `vm_handler_XX` after the first opcode they serve,
+    /// and every row of the bytecode is commented with its offset from the
+    /// start of the bytecode.
+    pub fn annotate(&self, disassembler: &mut Disassembler) {
+        disassembler.mark_words(self.table, self.table_end());
+        if let Some((start, end)) = self.bytecode {
+            disassembler.mark_data(start, end);
+        }
+        for &handler in &self.handlers {
+            disassembler.mark_code(handler);
+        }
+        disassembler.reanalyze();
+
+        disassembler.rename_label(self.head, "vm_dispatch");
+        disassembler.rename_label(self.table, "vm_table");
+        let mut comments = vec![(CommentType::PRE, self.head, self.to_string())];
+        for (opcode, &handler) in self.handlers.iter().enumerate() {
+            if self.handlers[..opcode].contains(&handler) {
+                continue;
+            }
+            disassembler.rename_label(handler, format!("vm_handler_{opcode:02x}"));
+            let opcodes: Vec<String> = self
+                .handlers
+                .iter()
+                .enumerate()
+                .filter(|&(_, &other)| other == handler)
+                .map(|(opcode, _)| format!("0x{opcode:02x}"))
+                .collect();
+            comments.push((
+                CommentType::PRE,
+                handler,
+                format!("handler for opcode {}", opcodes.join(", ")),
+            ));
+        }
+        if let Some((start, end)) = self.bytecode {
+            disassembler.rename_label(start, "vm_bytecode");
+            comments.push((
+                CommentType::PRE,
+                start,
+                format!(
+                    "bytecode run by the interpreter at 0x{:04x} ({} bytes)",
+                    self.head,
+                    end - start
+                ),
+            ));
+            for row in disassembler
+                .instructions
+                .0
+                .iter()
+                .filter(|instruction| (start as u64..end as u64).contains(&instruction.ip()))
+            {
+                let offset = row.ip() as Address - start;
+                comments.push((
+                    CommentType::INLINE,
+                    row.ip() as Address,
+                    format!("+0x{offset:04x}"),
+                ));
+            }
+        }
+
+        for (comment_type, address, text) in comments {
+            disassembler.comment_list.0.push(
+                Comment::new(comment_type, text, address)
+                    .with_source(CommentSource::analysis("interpreter")),
+            );
+        }
+    }
+}
+
+impl Display for Interpreter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bytecode interpreter at 0x{:04x}: fetches at 0x{:04x} and dispatches at 0x{:04x} through the table at 0x{:04x} to {} handlers",
+            self.head,
+            self.fetch,
+            self.dispatch,
+            self.table,
+            self.handlers.len()
+        )
+    }
+}
+
+/// The table address and index register of an indirect near jump or call
+/// through `[reg + table]`
+fn table_operand(instruction: &Instruction, end: u64) -> Option<(Address, Register)> {
+    if !matches!(
+        instruction.flow_control(),
+        FlowControl::IndirectBranch | FlowControl::IndirectCall
+    ) || instruction.op0_kind() != OpKind::Memory
+        || instruction.memory_size() != MemorySize::WordOffset
+    {
+        return None;
+    }
+    let index = match (instruction.memory_base(), instruction.memory_index()) {
+        (Register::None, Register::None) => return None,
+        (Register::None, index) | (index, _) => index,
+    };
+    let table = instruction.memory_displacement64();
+    (COM_OFFSET as u64..end)
+        .contains(&table)
+        .then_some((table as Address, index))
+}
+
+/// The pointer register `instruction` fetches a byte through, if it does
+fn fetched_through(instruction: &Instruction) -> Option<Register> {
+    match instruction.mnemonic() {
+        Mnemonic::Lodsb => Some(Register::SI),
+        Mnemonic::Mov
+            if instruction.op0_kind() == OpKind::Register
+                && instruction.op1_kind() == OpKind::Memory
+                && instruction.memory_size() == MemorySize::UInt8
+                && instruction.memory_base() != Register::None =>
+        {
+            Some(instruction.memory_base())
+        }
+        _ => None,
+    }
+}
+
+/// The head of the loop around `fetch`, found from a branch outside
+/// `first..=dispatch` back to an address in `first..=fetch`
+fn loop_head(instructions: &[Instruction], first: u64, fetch: u64, dispatch: u64) -> Option<u64> {
+    instructions
+        .iter()
+        .filter(|instruction| !(first..=dispatch).contains(&instruction.ip()))
+        .filter(|instruction| {
+            matches!(
+                instruction.flow_control(),
+                FlowControl::UnconditionalBranch | FlowControl::ConditionalBranch
+            ) && instruction.op0_kind() == OpKind::NearBranch16
+        })
+        .map(Instruction::near_branch_target)
+        .filter(|target| (first..=fetch).contains(target))
+        .min()
+}
+
+/// Reads handler addresses from the table until one points outside the
+/// program or the table runs into `stop`
+fn read_table(
+    disassembler: &Disassembler,
+    table: Address,
+    stop: Option<Address>,
+    end: u64,
+) -> Vec<Address> {
+    let mut handlers = Vec::new();
+    let mut entry = table;
+    while handlers.len() < MAX_HANDLERS && stop.is_none_or(|stop| entry < stop || stop < table) {
+        let Some(&[low, high]) = disassembler
+            .bytes_at(entry)
+            .and_then(|bytes| bytes.get(..2))
+        else {
+            break;
+        };
+        let handler = u16::from_le_bytes([low, high]);
+        if !(COM_OFFSET as u64..end).contains(&(handler as u64)) {
+            break;
+        }
+        handlers.push(handler);
+        entry += 2;
+    }
+    handlers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Prints each character of its bytecode with opcode 1 until opcode 0
+    fn interpreter_program() -> Vec<u8> {
+        vec![
+            0xBE, 0x1C, 0x01, // mov si, 0x11c
+            0xAC, // 0x103: lodsb
+            0x88, 0xC3, 0x30, 0xFF, 0xD1, 0xE3, // mov bl, al / xor bh, bh / shl bx, 1
+            0xFF, 0xA7, 0x18, 0x01, // jmp [bx+0x118]
+            0xC3, // 0x10e: opcode 0, ret
+            0xB4, 0x02, 0xAC, 0x88, 0xC2, // 0x10f: opcode 1, mov ah, 2 / lodsb / mov dl, al
+            0xCD, 0x21, 0xEB, 0xEB, // int 21h / jmp 0x103
+            0x0E, 0x01, 0x0F, 0x01, // 0x118: table
+            0x01, b'H', 0x01, b'i', 0x00, // 0x11c: bytecode
+        ]
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Detection
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn dispatch_loop_is_found() {
+        let found = Interpreter::detect(&Disassembler::new(interpreter_program()));
+        assert_eq!(
+            found,
+            vec![Interpreter {
+                head: 0x103,
+                fetch: 0x103,
+                dispatch: 0x10A,
+                table: 0x118,
+                handlers: vec![0x10E, 0x10F],
+                bytecode: Some((0x11C, 0x121)),
+            }]
+        );
+    }
+
+    #[test]
+    fn table_jump_without_a_loop_is_not_an_interpreter() {
+        let mut data = interpreter_program();
+        data[22] = 0xC3; // jmp 0x103 -> ret
+        assert!(Interpreter::detect(&Disassembler::new(data)).is_empty());
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Annotation
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn handlers_are_labelled_and_bytecode_dumped() {
+        let mut disassembler = Disassembler::new(interpreter_program());
+        let interpreter = Interpreter::detect(&disassembler).remove(0);
+        interpreter.annotate(&mut disassembler);
+
+        let name = |address| {
+            disassembler
+                .labels
+                .get_by_address(address)
+                .map(|label| label.name.clone())
+        };
+        assert_eq!(name(0x103).as_deref(), Some("vm_dispatch"));
+        assert_eq!(name(0x10F).as_deref(), Some("vm_handler_01"));
+        assert_eq!(name(0x11C).as_deref(), Some("vm_bytecode"));
+
+        let bytecode = disassembler
+            .instructions
+            .0
+            .iter()
+            .find(|instruction| instruction.ip() == 0x11C)
+            .expect("an instruction at the bytecode");
+        assert_eq!(bytecode.mnemonic(), Mnemonic::Db);
+        assert!(
+            disassembler
+                .comment_list
+                .get_comments(0x11C)
+                .iter()
+                .any(|comment| comment.comment_text == "+0x0000")
+        );
+    }
+}
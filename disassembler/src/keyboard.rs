@@ -0,0 +1,333 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{
+    FlowControl, Instruction, InstructionInfoFactory, MemorySize, Mnemonic, OpAccess, OpKind,
+    Register,
+};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+use crate::redundancy::writes_register;
+
+/// The keys of the main block of a US keyboard by row, unshifted and
+/// shifted, with the scan code of the first key in the row
+pub(crate) const KEYBOARD_ROWS: [(&str, &str, u8); 4] = [
+    ("1234567890-=", "!@#$%^&*()_+", 0x02),
+    ("qwertyuiop[]", "QWERTYUIOP{}", 0x10),
+    ("asdfghjkl;'`", "ASDFGHJKL:\"~", 0x1E),
+    ("\\zxcvbnm,./", "|ZXCVBNM<>?", 0x2B),
+];
+
+/// Keys outside the main block, with the name key scripts give them in
+/// braces, the name comments give them, their character and scan code
+pub(crate) const NAMED_KEYS: [(&str, &str, u8, u8); 24] = [
+    ("enter", "Enter", 0x0D, 0x1C),
+    ("esc", "Esc", 0x1B, 0x01),
+    ("tab", "Tab", 0x09, 0x0F),
+    ("backspace", "Backspace", 0x08, 0x0E),
+    ("space", "Space", b' ', 0x39),
+    ("f1", "F1", 0, 0x3B),
+    ("f2", "F2", 0, 0x3C),
+    ("f3", "F3", 0, 0x3D),
+    ("f4", "F4", 0, 0x3E),
+    ("f5", "F5", 0, 0x3F),
+    ("f6", "F6", 0, 0x40),
+    ("f7", "F7", 0, 0x41),
+    ("f8", "F8", 0, 0x42),
+    ("f9", "F9", 0, 0x43),
+    ("f10", "F10", 0, 0x44),
+    ("home", "Home", 0, 0x47),
+    ("up", "Up arrow", 0, 0x48),
+    ("pgup", "PgUp", 0, 0x49),
+    ("left", "Left arrow", 0, 0x4B),
+    ("right", "Right arrow", 0, 0x4D),
+    ("end", "End", 0, 0x4F),
+    ("down", "Down arrow", 0, 0x50),
+    ("pgdn", "PgDn", 0, 0x51),
+    ("del", "Del", 0, 0x53),
+];
+
+/// The int 16h functions that return a key's scancode in `ah`: read and
+/// peek, and their enhanced keyboard versions
+const READ_FUNCTIONS: [u8; 4] = [0x00, 0x01, 0x10, 0x11];
+
+/// Characters int 16h returns in `al` for keys that do not type anything
+/// printable, besides Ctrl with a letter
+const CONTROL_KEYS: [(u8, &str); 5] = [
+    (0x00, "extended key, scancode in ah"),
+    (0x08, "Backspace"),
+    (0x09, "Tab"),
+    (0x0D, "Enter"),
+    (0x1B, "Esc"),
+];
+
+/// What a byte compared against stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Key {
+    /// A printable character
+    Character(u8),
+    /// A key int 16h returns a control character for, such as Enter, or
+    /// Ctrl with a letter
+    Control(u8),
+    /// A key's scancode, which int 16h returns in `ah`
+    Scancode(u8),
+}
+
+impl Key {
+    /// The key with scancode `scan`, if it is one of the
+    /// [named keys](NAMED_KEYS) or in the main block of the keyboard
+    pub fn scancode(scan: u8) -> Option<Key> {
+        let named = NAMED_KEYS.iter().any(|&(_, _, _, key)| key == scan);
+        (named || main_block_key(scan).is_some()).then_some(Key::Scancode(scan))
+    }
+}
+
+/// The character on the main block key with scancode `scan`, unshifted
+/// except for letters
+fn main_block_key(scan: u8) -> Option<char> {
+    KEYBOARD_ROWS.iter().find_map(|&(plain, _, first)| {
+        let index = scan.checked_sub(first)? as usize;
+        plain.chars().nth(index).map(|key| key.to_ascii_uppercase())
+    })
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Key::Character(b'\'') => write!(f, "\"'\""),
+            Key::Character(byte) => write!(f, "'{}'", byte as char),
+            Key::Control(byte) => match CONTROL_KEYS.iter().find(|&&(key, _)| key == byte) {
+                Some((_, name)) => write!(f, "{name}"),
+                None => write!(f, "Ctrl+{}", (byte + b'A' - 1) as char),
+            },
+            Key::Scancode(scan) => match NAMED_KEYS.iter().find(|&&(_, _, _, key)| key == scan) {
+                Some((_, name, _, _)) => write!(f, "{name}"),
+                None => match main_block_key(scan) {
+                    Some(key) => write!(f, "{key} key"),
+                    None => write!(f, "scancode 0x{scan:02x}"),
+                },
+            },
+        }
+    }
+}
+
+/// A comparison against a character or key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeyCompare {
+    /// The address of the `cmp`
+    pub address: Address,
+    /// What the value compared against stands for
+    pub key: Key,
+}
+
+/// Finds byte comparisons against printable characters, against the
+/// control keys int 16h returns in `al`, such as Enter and Esc, and against
+/// the scancodes it returns in `ah`, such as F1 and the arrow keys
+///
+/// Comparisons the [style](crate::style::Style::immediate) already writes
+/// as a character are left out, as are other comparisons against `ah`,
+/// which holds function results rather than characters. Control keys are
+/// only named while `al` still holds what the last int 16h returned: until
+/// something else writes `ax`, the code calls, returns or jumps away.
+/// Scancodes likewise, while `ah` still holds what a call to read or peek
+/// at a key returned.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::keyboard::{Key, find_key_compares};
+///
+/// // xor ah, ah / int 16h / cmp al, 0x1b / ret
+/// let disassembler = Disassembler::new(vec![0x30, 0xE4, 0xCD, 0x16, 0x3C, 0x1B, 0xC3]);
+/// let found = find_key_compares(&disassembler);
+/// assert_eq!(found[0].key, Key::Control(0x1B));
+/// assert_eq!(found[0].key.to_string(), "Esc");
+///
+/// // xor ah, ah / int 16h / cmp ah, 0x48 / ret
+/// let disassembler = Disassembler::new(vec![0x30, 0xE4, 0xCD, 0x16, 0x80, 0xFC, 0x48, 0xC3]);
+/// let found = find_key_compares(&disassembler);
+/// assert_eq!(found[0].key.to_string(), "Up arrow");
+/// ```
+pub fn find_key_compares(disassembler: &Disassembler) -> Vec<KeyCompare> {
+    let mut factory = InstructionInfoFactory::new();
+    let mut found = Vec::new();
+    let mut keyboard = false;
+    let mut scancode = false;
+    let mut function = None;
+    for instruction in disassembler.code() {
+        if is_data(instruction) {
+            (keyboard, scancode, function) = (false, false, None);
+            continue;
+        }
+        if let Some((register, value)) = compared_byte(instruction) {
+            let key = match value {
+                _ if register == Register::AH => scancode.then(|| Key::scancode(value)).flatten(),
+                0x20..=0x7E => Some(Key::Character(value)),
+                0x00..=0x1B if keyboard => Some(Key::Control(value)),
+                _ => None,
+            }
+            // the listing already shows the character
+            .filter(|key| {
+                !matches!(key, Key::Character(_))
+                    || disassembler.style().immediate(instruction, 1).is_none()
+            });
+            if let Some(key) = key {
+                found.push(KeyCompare {
+                    address: instruction.ip() as Address,
+                    key,
+                });
+            }
+        }
+        if instruction.mnemonic() == Mnemonic::Int {
+            keyboard = instruction.immediate8() == 0x16;
+            scancode = keyboard && function.is_some_and(|ah| READ_FUNCTIONS.contains(&ah));
+            function = None;
+        } else if !matches!(
+            instruction.flow_control(),
+            FlowControl::Next | FlowControl::ConditionalBranch
+        ) {
+            (keyboard, scancode, function) = (false, false, None);
+        } else {
+            if writes_register(&mut factory, instruction, Register::AL) {
+                keyboard = false;
+            }
+            if writes_ah(&mut factory, instruction) {
+                scancode = false;
+                function = loaded_ah(instruction);
+            }
+        }
+    }
+    found
+}
+
+/// The register, or [`Register::None`] for memory, and value of a `cmp` of
+/// a byte with an immediate
+fn compared_byte(instruction: &Instruction) -> Option<(Register, u8)> {
+    if instruction.mnemonic() != Mnemonic::Cmp || instruction.op1_kind() != OpKind::Immediate8 {
+        return None;
+    }
+    let register = match instruction.op0_kind() {
+        OpKind::Register if instruction.op0_register().is_gpr8() => instruction.op0_register(),
+        OpKind::Memory if instruction.memory_size() == MemorySize::UInt8 => Register::None,
+        _ => return None,
+    };
+    Some((register, instruction.immediate8()))
+}
+
+/// Returns true if `instruction` writes `ah`, unlike
+/// [`writes_register`] leaving out writes to `al` alone, as `or al, al`
+/// tells extended keys apart without touching the scancode
+fn writes_ah(factory: &mut InstructionInfoFactory, instruction: &Instruction) -> bool {
+    factory
+        .info(instruction)
+        .used_registers()
+        .iter()
+        .any(|used| {
+            matches!(used.register(), Register::AH | Register::AX | Register::EAX)
+                && !matches!(
+                    used.access(),
+                    OpAccess::Read | OpAccess::CondRead | OpAccess::None
+                )
+        })
+}
+
+/// The value `instruction` puts in `ah`, for the ways programs pick an
+/// int 16h function: `mov ah, imm`, `mov ax, imm` and clearing `ah` or `ax`
+fn loaded_ah(instruction: &Instruction) -> Option<u8> {
+    let register = instruction.op0_register();
+    match instruction.mnemonic() {
+        Mnemonic::Mov
+            if instruction.op1_kind() == OpKind::Immediate8 && register == Register::AH =>
+        {
+            Some(instruction.immediate8())
+        }
+        Mnemonic::Mov
+            if instruction.op1_kind() == OpKind::Immediate16 && register == Register::AX =>
+        {
+            Some((instruction.immediate16() >> 8) as u8)
+        }
+        Mnemonic::Xor | Mnemonic::Sub
+            if matches!(register, Register::AH | Register::AX)
+                && instruction.op1_kind() == OpKind::Register
+                && instruction.op1_register() == register =>
+        {
+            Some(0)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::{Radix, Style};
+
+    #[test]
+    fn characters_are_commented_when_written_as_numbers() {
+        // cmp al, 'y' / cmp byte [si], 0x27 / ret
+        let data = vec![0x3C, 0x79, 0x80, 0x3C, 0x27, 0xC3];
+        let mut disassembler = Disassembler::new(data);
+        let found = find_key_compares(&disassembler);
+        assert_eq!(found.len(), 1, "'y' is already written as a character");
+        assert_eq!(found[0].key.to_string(), "\"'\"");
+
+        disassembler.set_style(Style {
+            radix: Radix::Hex,
+            ..Style::default()
+        });
+        let listing = disassembler.to_string();
+        assert!(listing.contains("cmp al,0x79 ; 'y'"), "{listing}");
+    }
+
+    #[test]
+    fn control_keys_are_only_named_after_int_16h() {
+        // cmp al, 0x0d / mov ah, 0 / int 16h / cmp al, 0x03 / cmp al, 0x0d /
+        // mov al, 1 / cmp al, 0x0d / ret
+        let data = vec![
+            0x3C, 0x0D, 0xB4, 0x00, 0xCD, 0x16, 0x3C, 0x03, 0x3C, 0x0D, 0xB0, 0x01, 0x3C, 0x0D,
+            0xC3,
+        ];
+        let found = find_key_compares(&Disassembler::new(data));
+        let names: Vec<(Address, String)> = found
+            .iter()
+            .map(|compare| (compare.address, compare.key.to_string()))
+            .collect();
+        assert_eq!(
+            names,
+            [(0x106, "Ctrl+C".to_string()), (0x108, "Enter".to_string())]
+        );
+    }
+
+    #[test]
+    fn scancodes_are_named_after_reading_a_key() {
+        let data = vec![
+            0xB4, 0x02, // mov ah, 2 (shift flags)
+            0xCD, 0x16, // int 16h
+            0x80, 0xFC, 0x3B, // cmp ah, 0x3b
+            0xB8, 0x00, 0x10, // mov ax, 0x1000
+            0xCD, 0x16, // int 16h
+            0x08, 0xC0, // or al, al
+            0x80, 0xFC, 0x3B, // cmp ah, 0x3b
+            0x80, 0xFC, 0x15, // cmp ah, 0x15
+            0x80, 0xFC, 0x7F, // cmp ah, 0x7f
+            0xB4, 0x00, // mov ah, 0
+            0x80, 0xFC, 0x48, // cmp ah, 0x48
+            0xC3, // ret
+        ];
+        let disassembler = Disassembler::new(data);
+        let names: Vec<(Address, String)> = find_key_compares(&disassembler)
+            .iter()
+            .map(|compare| (compare.address, compare.key.to_string()))
+            .collect();
+        assert_eq!(
+            names,
+            [(0x10E, "F1".to_string()), (0x111, "Y key".to_string())]
+        );
+
+        let listing = disassembler.to_string();
+        assert!(listing.contains("cmp ah,0x3b ; F1"), "{listing}");
+    }
+}
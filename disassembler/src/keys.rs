@@ -0,0 +1,176 @@
+use crate::keyboard::{KEYBOARD_ROWS, NAMED_KEYS};
+use crate::string::encode_cp437;
+
+/// A key pressed while a program runs in the emulator
+///
+/// Int 16h returns the scan code and character together; int 21h returns
+/// the character, and for keys without one, such as the arrows and function
+/// keys, a zero followed by the scan code on the next read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keystroke {
+    /// The character typed, or 0 for keys without one
+    pub ascii: u8,
+    /// The key's scan code
+    pub scan: u8,
+    /// How many instructions run after the previous key is read before this
+    /// one is pressed, for programs that poll the keyboard
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub delay: u64,
+}
+
+impl Keystroke {
+    /// The Enter key
+    pub const ENTER: Keystroke = Keystroke {
+        ascii: 0x0D,
+        scan: 0x1C,
+        delay: 0,
+    };
+
+    /// The key that types `byte` on a US keyboard, with Ctrl for control
+    /// characters
+    pub fn from_ascii(byte: u8) -> Keystroke {
+        let scan = NAMED_KEYS
+            .iter()
+            .find(|&&(_, _, ascii, _)| ascii != 0 && ascii == byte)
+            .map(|&(_, _, _, scan)| scan)
+            .or_else(|| {
+                // Ctrl+A to Ctrl+Z
+                let letter = match byte {
+                    0x01..=0x1A => byte + b'a' - 1,
+                    _ => byte,
+                };
+                KEYBOARD_ROWS.iter().find_map(|&(plain, shifted, first)| {
+                    plain
+                        .bytes()
+                        .position(|key| key == letter)
+                        .or_else(|| shifted.bytes().position(|key| key == letter))
+                        .map(|index| first + index as u8)
+                })
+            })
+            .unwrap_or(0);
+        Keystroke {
+            ascii: byte,
+            scan,
+            delay: 0,
+        }
+    }
+}
+
+/// Parses a key script, such as `Y\n` or `{down}{down}{enter}`
+///
+/// Characters are typed as they are, with line breaks pressing Enter.
+/// `\n` and `\r` press Enter, `\t` Tab,
+/// `\e` Esc, `\b` Backspace and `\xNN` the key typing byte NN; `\\` and `\{`
+/// type a backslash and a brace. Keys without a character are named in
+/// braces: `{f1}` to `{f10}`, `{up}`, `{down}`, `{left}`, `{right}`,
+/// `{home}`, `{end}`, `{pgup}`, `{pgdn}` and `{del}`, as are `{enter}`,
+/// `{esc}`, `{tab}`, `{backspace}` and `{space}`. `{wait N}` holds the next
+/// key back until N instructions after the previous one was read.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::keys::parse_keys;
+///
+/// let keys = parse_keys("y{wait 500}{f1}\n").unwrap();
+/// assert_eq!((keys[0].ascii, keys[0].scan), (b'y', 0x15));
+/// assert_eq!((keys[1].ascii, keys[1].scan, keys[1].delay), (0, 0x3B, 500));
+/// assert_eq!(keys[2].ascii, b'\r');
+/// assert!(parse_keys("{f13}").is_err());
+/// ```
+pub fn parse_keys(script: &str) -> Result<Vec<Keystroke>, String> {
+    let mut keys = Vec::new();
+    let mut delay = 0;
+    let mut chars = script.chars();
+    while let Some(c) = chars.next() {
+        let mut key = match c {
+            '\\' => {
+                let escaped = chars
+                    .next()
+                    .ok_or("the key script ends in the middle of an escape")?;
+                match escaped {
+                    'n' | 'r' => Keystroke::ENTER,
+                    't' => Keystroke::from_ascii(0x09),
+                    'e' => Keystroke::from_ascii(0x1B),
+                    'b' => Keystroke::from_ascii(0x08),
+                    'x' => {
+                        let hex: String = chars.by_ref().take(2).collect();
+                        let byte = u8::from_str_radix(&hex, 16)
+                            .map_err(|_| format!("invalid byte `\\x{hex}` in the key script"))?;
+                        Keystroke::from_ascii(byte)
+                    }
+                    '\\' | '{' => Keystroke::from_ascii(escaped as u8),
+                    other => return Err(format!("unknown escape `\\{other}` in the key script")),
+                }
+            }
+            '\n' | '\r' => Keystroke::ENTER,
+            '{' => {
+                let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let name = name.trim().to_ascii_lowercase();
+                if let Some(count) = name.strip_prefix("wait") {
+                    delay = count
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("invalid wait `{{{name}}}` in the key script"))?;
+                    continue;
+                }
+                NAMED_KEYS
+                    .iter()
+                    .find(|&&(key, _, _, _)| key == name)
+                    .map(|&(_, _, ascii, scan)| Keystroke {
+                        ascii,
+                        scan,
+                        delay: 0,
+                    })
+                    .ok_or_else(|| format!("unknown key `{{{name}}}` in the key script"))?
+            }
+            _ => Keystroke::from_ascii(
+                encode_cp437(c).ok_or_else(|| format!("{c:?} cannot be typed in code page 437"))?,
+            ),
+        };
+        key.delay = std::mem::take(&mut delay);
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn characters_get_the_scan_codes_of_their_keys() {
+        let scan = |byte: u8| Keystroke::from_ascii(byte).scan;
+        assert_eq!(scan(b'a'), 0x1E);
+        assert_eq!(scan(b'A'), 0x1E);
+        assert_eq!(scan(b'0'), 0x0B);
+        assert_eq!(scan(b'?'), 0x35);
+        assert_eq!(scan(b' '), 0x39);
+        assert_eq!(scan(0x03), 0x2E, "Ctrl+C");
+        assert_eq!(scan(0x1B), 0x01);
+        assert_eq!(scan(0xE1), 0);
+    }
+
+    #[test]
+    fn scripts_escape_name_keys_and_wait() {
+        let keys = parse_keys("a\\{\\x41{ Down }{wait 10}\\e").unwrap();
+        let pairs: Vec<(u8, u8)> = keys.iter().map(|key| (key.ascii, key.scan)).collect();
+        assert_eq!(
+            pairs,
+            [
+                (b'a', 0x1E),
+                (b'{', 0x1A),
+                (b'A', 0x1E),
+                (0, 0x50),
+                (0x1B, 0x01)
+            ]
+        );
+        assert_eq!(keys[4].delay, 10);
+        assert_eq!(keys[3].delay, 0);
+        assert_eq!(
+            parse_keys("\\q").unwrap_err(),
+            "unknown escape `\\q` in the key script"
+        );
+    }
+}
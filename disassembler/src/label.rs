@@ -2,7 +2,10 @@ use crate::consts::Address;
 use std::fmt::Display;
 
 /// An enum to represent the type of label
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// Where several labels share an address, the one listed first wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LabelType {
     /// A basic label detected via Jmp
     LABEL,
@@ -13,6 +16,7 @@ pub enum LabelType {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A struct to represent a label in the disassembled code
 pub struct Label {
     /// The address of the label
@@ -33,7 +37,8 @@ impl Display for Label {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A wrapper type around Vec<label> for implementing Display
 pub struct LabelList(pub Vec<Label>);
 
@@ -43,20 +48,20 @@ impl LabelList {
     /// # Returns
     ///
     /// A new instance of `LabelList` with an empty vector of instructions
-    /// 
+    ///
     /// # Examples
-    /// 
+    ///
     /// ```
     /// use disassembler::label::{LabelList, Label, LabelType};
     /// use disassembler::consts::Address;
-    /// 
+    ///
     /// let mut label_list = LabelList::new();
     /// label_list.0.push(Label {
     ///     address: 0x1234,
     ///     label_type: LabelType::LABEL,
     ///     name: String::from("my_label"),
     /// });
-    /// 
+    ///
     /// assert_eq!(label_list.0.len(), 1);
     /// assert_eq!(label_list.0[0].address, 0x1234);
     /// assert_eq!(label_list.0[0].label_type, LabelType::LABEL);
@@ -75,37 +80,63 @@ impl LabelList {
     /// # Returns
     ///
     /// An `Option` containing a reference to the label if found, or `None` if not found
-    /// 
+    ///
     /// # Examples
-    /// 
+    ///
     /// ```
     /// use disassembler::label::{LabelList, Label, LabelType};
     /// use disassembler::consts::Address;
-    /// 
+    ///
     /// let mut label_list = LabelList::new();
     /// label_list.0.push(Label {
     ///     address: 0x1234,
     ///     label_type: LabelType::LABEL,
     ///     name: String::from("my_label"),
     /// });
-    /// 
+    ///
     /// let label = label_list.get_by_address(0x1234);
-    /// 
+    ///
     /// assert!(label.is_some());
     /// assert_eq!(label.unwrap().address, 0x1234);
     /// assert_eq!(label.unwrap().label_type, LabelType::LABEL);
     /// assert_eq!(label.unwrap().name, "my_label");
-    /// 
+    ///
     /// ```
     pub fn get_by_address(&self, address: Address) -> Option<&Label> {
         self.0.iter().find(|label| label.address == address)
     }
+
+    /// Sorts the labels by address, then type, then name, and drops exact
+    /// duplicates
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use disassembler::label::{LabelList, Label, LabelType};
+    ///
+    /// let label = |address, label_type, name: &str| Label { address, label_type, name: name.into() };
+    /// let mut label_list = LabelList(vec![
+    ///     label(0x120, LabelType::LABEL, "LABEL_0x0120"),
+    ///     label(0x110, LabelType::FUNCTION, "FUNC_0x0110"),
+    ///     label(0x120, LabelType::LABEL, "LABEL_0x0120"),
+    /// ]);
+    /// label_list.sort();
+    ///
+    /// assert_eq!(label_list.0.len(), 2);
+    /// assert_eq!(label_list.0[0].address, 0x110);
+    /// ```
+    pub fn sort(&mut self) {
+        self.0.sort_by(|a, b| {
+            (a.address, a.label_type, &a.name).cmp(&(b.address, b.label_type, &b.name))
+        });
+        self.0.dedup();
+    }
 }
 
 impl Display for LabelList {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for label in self.0.iter() {
-            write!(f, "{}\n", label)?;
+            writeln!(f, "{}", label)?;
         }
         Ok(())
     }
@@ -195,4 +226,24 @@ mod tests {
         assert_eq!(a, b);
         assert_ne!(a, c);
     }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 6.  Sorting
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn sort_drops_duplicates_that_are_not_adjacent() {
+        let mut list = LabelList(vec![
+            lbl(0x100, LabelType::LABEL, "x"),
+            lbl(0x100, LabelType::LABEL, "y"),
+            lbl(0x100, LabelType::LABEL, "x"),
+        ]);
+        list.sort();
+        assert_eq!(
+            list.0,
+            vec![
+                lbl(0x100, LabelType::LABEL, "x"),
+                lbl(0x100, LabelType::LABEL, "y"),
+            ]
+        );
+    }
 }
@@ -1,15 +1,158 @@
 #![deny(missing_docs)]
 //! Disassembler Designed for COM files that outputs labeled assembly code in NASM syntax
 
+/// a Module for exchanging labels, comments and data ranges with other tools
+pub mod annotations;
+/// a Module declaring the memory past the end of the file a program uses
+pub mod bss;
+/// a Module building COM programs for tests
+#[cfg(feature = "builder")]
+pub mod builder;
+/// a Module for managing comments in the disassembly
+pub mod comment;
+/// a Module guessing which compiler or assembler built a program
+pub mod compiler;
+/// a Module explaining well-known values such as video segments and ports
+pub mod constants;
 /// a Module that contains Constants for the disassembler
 pub mod consts;
+/// a Module naming what country and code page calls do and the fields of
+/// the buffers they fill in
+pub mod country;
+/// a Module comparing static analysis with what the emulator executed
+#[cfg(feature = "emulator")]
+pub mod coverage;
+/// a Module describing data as typed `db`, `dw`, `dd` and `resb` items
+pub mod data;
+/// a Module recognising number conversions done with decimal and BCD arithmetic
+pub mod decimal;
+/// a Module recognising files that are empty, too short or have no code
+pub mod degenerate;
+/// a Module that undoes common obfuscation idioms
+pub mod deobfuscate;
 /// a Module that dissasmbles the binary code
 pub mod disassemble;
+/// a Module that runs COM programs in a small 8086 emulator
+#[cfg(feature = "emulator")]
+pub mod emulate;
+/// a Module naming the addresses and constants a listing uses without defining them
+pub mod equates;
+/// a Module recognising how programs handle errors from DOS calls
+pub mod errors;
+/// a Module summarising the files a program creates, opens, reads and deletes
+pub mod fileops;
+/// a Module for the files programs see in the emulator
+#[cfg(feature = "emulator")]
+pub mod files;
+/// a Module running the analysis again until it stops finding new code and data
+pub mod fixpoint;
+/// a Module splitting code into basic blocks joined by control flow edges
+pub mod flow;
+/// a Module mapping the unused space in a program
+pub mod freespace;
+/// a Module checking the analysis survives randomly mutated programs
+#[cfg(feature = "emulator")]
+pub mod fuzz;
+/// a Module making listings stable enough to keep as golden files
+pub mod golden;
+/// a Module finding fonts, pictures and sprites and drawing them
+#[cfg(feature = "graphics")]
+pub mod graphics;
+/// a Module checking the interrupt handlers a program installs
+pub mod handlers;
+/// a Module installing hooks that run new code before existing instructions
+pub mod hook;
+/// a Module detecting bytecode interpreters embedded in programs
+pub mod interpreter;
+/// a Module naming the characters and keys input code compares against
+pub mod keyboard;
+/// a Module for the keystrokes fed to programs in the emulator
+#[cfg(feature = "emulator")]
+pub mod keys;
 /// a Module that contains the label struct
 pub mod label;
+/// a Module capping how much work an analysis may do
+pub mod limits;
+/// a Module writing plain linear listings without any analysis
+pub mod linear;
+/// a Module recognising COM programs that load and jump into another file
+pub mod loader;
+/// a Module running two versions of a program side by side in the emulator
+#[cfg(feature = "emulator")]
+pub mod lockstep;
+/// a Module finding EMS and XMS calls and the memory they ask for
+pub mod managers;
+/// a Module mapping the memory a program allocates, frees and resizes
+pub mod memory;
+/// a Module naming int 33h mouse calls and the event handlers they install
+pub mod mouse;
+/// a Module finding PC speaker and AdLib music and the tables it plays
+pub mod music;
+/// a Module naming the int 2Fh network, IPX and NetBIOS calls
+pub mod network;
+/// a Module flagging instructions made 32-bit by size prefixes
+pub mod overrides;
+/// a Module finding the filler bytes that align code and data
+pub mod padding;
+/// a Module for actions the emulator takes on breakpoints, interrupts and writes
+#[cfg(feature = "emulator")]
+pub mod probe;
+/// a Module for the stable model of an analysed program that renderers use
+pub mod program;
+/// a Module reporting how far a long analysis has got
+pub mod progress;
+/// a Module flagging behaviour typical of copy protection
+pub mod protection;
+/// a Module describing what is at an address, for interactive frontends
+pub mod query;
+/// a Module recovering the original program from an infected one
+#[cfg(all(feature = "emulator", feature = "signatures"))]
+pub mod recover;
+/// a Module finding instructions whose effects are never observed
+pub mod redundancy;
+/// a Module for named regions of the program
+pub mod region;
+/// a Module for recording emulator runs and replaying them exactly
+#[cfg(feature = "emulator")]
+pub mod replay;
+/// a Module scoring how much a program behaves like malware
+pub mod risk;
+/// a Module recognising small helper routines by their shape
+pub mod routines;
+/// a Module for matching YARA-style byte pattern rules
+pub mod rules;
+/// a Module bundling small example programs
+pub mod samples;
+/// a Module for the text screen programs draw on in the emulator
+#[cfg(feature = "emulator")]
+pub mod screen;
+/// a Module finding code that writes over the program's own instructions
+pub mod selfmod;
+/// a Module for companion files mapped alongside the program
+pub mod session;
+/// a Module for matching known viruses from signature packs
+#[cfg(feature = "signatures")]
+pub mod signatures;
+/// a Module tracking where programs move the stack
+pub mod stack;
+/// a Module for defining string constants
+pub mod string;
+/// a Module for how numbers, addresses and mnemonics are written
+pub mod style;
+/// a Module checking that NASM assembles the listing to the same bytes
+pub mod syntax;
 /// a Module that contains int 21h syscalls
 pub mod syscall;
-/// a Module for managing comments in the disassembly
-pub mod comment;
-/// a Module for defining string constants
-pub mod string;
\ No newline at end of file
+/// a Module inferring the element size and length of data tables
+pub mod table;
+/// a Module finding where a program reprograms the timer interrupt's rate
+pub mod timer;
+/// a Module finding lives and health counters for trainers
+pub mod trainer;
+/// a Module recognising checks of the DOS version a program runs on
+pub mod version;
+/// a Module reporting which instructions used watched buffers at run time
+#[cfg(feature = "emulator")]
+pub mod watch;
+/// a Module flagging addresses that wrap around the end of the segment
+pub mod wraparound;
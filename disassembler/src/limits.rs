@@ -0,0 +1,108 @@
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+use crate::consts::Address;
+
+/// Caps on how much work an analysis may do, so degenerate or hostile input
+/// cannot hang a batch job or a browser tab
+///
+/// Every limit is off by default. When one is reached the analysis stops
+/// early and keeps what it has; see [`LimitHit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Limits {
+    /// The most instructions one sweep decodes, after which the rest of the
+    /// program is left as data
+    pub max_instructions: Option<usize>,
+    /// How long decoding and analysis may take; not enforced on
+    /// `wasm32-unknown-unknown`, which has no clock
+    pub max_time: Option<Duration>,
+    /// The most labels kept
+    pub max_labels: Option<usize>,
+    /// The most string constants kept
+    pub max_strings: Option<usize>,
+    /// The most times [`settle`](crate::fixpoint::settle) runs the analysis,
+    /// counting the first run
+    pub max_passes: Option<usize>,
+}
+
+/// Which limit was reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LimitKind {
+    /// [`Limits::max_instructions`]
+    Instructions,
+    /// [`Limits::max_time`]
+    Time,
+    /// [`Limits::max_labels`]
+    Labels,
+    /// [`Limits::max_strings`]
+    Strings,
+}
+
+/// A limit that cut an analysis short
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LimitHit {
+    /// The limit reached
+    pub kind: LimitKind,
+    /// Where decoding stopped, for limits hit while decoding
+    pub address: Option<Address>,
+}
+
+impl Display for LimitHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.kind {
+            LimitKind::Instructions => "instruction limit",
+            LimitKind::Time => "time limit",
+            LimitKind::Labels => "label limit",
+            LimitKind::Strings => "string limit",
+        };
+        match self.address {
+            Some(address) => write!(
+                f,
+                "analysis stopped at the {what}: bytes from 0x{address:04x} on are left as data"
+            ),
+            None => match self.kind {
+                LimitKind::Labels => {
+                    write!(f, "analysis stopped at the {what}: labels were dropped")
+                }
+                LimitKind::Strings => {
+                    write!(f, "analysis stopped at the {what}: strings were dropped")
+                }
+                _ => write!(
+                    f,
+                    "analysis stopped at the {what}: later passes were skipped"
+                ),
+            },
+        }
+    }
+}
+
+/// A point in time by which an analysis must finish
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Deadline(Option<std::time::Instant>);
+
+impl Deadline {
+    /// The deadline `limits` sets from now
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub(crate) fn start(limits: &Limits) -> Deadline {
+        Deadline(
+            limits
+                .max_time
+                .and_then(|time| std::time::Instant::now().checked_add(time)),
+        )
+    }
+
+    /// `Instant::now` panics in the browser, so there is no deadline there
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    pub(crate) fn start(_limits: &Limits) -> Deadline {
+        Deadline(None)
+    }
+
+    /// Returns true if the deadline has passed
+    pub(crate) fn passed(&self) -> bool {
+        self.0
+            .is_some_and(|deadline| std::time::Instant::now() >= deadline)
+    }
+}
@@ -0,0 +1,113 @@
+use std::io::{self, Write};
+
+use iced_x86::{Decoder, DecoderOptions, Formatter, IcedError, Instruction, NasmFormatter};
+
+use crate::consts::{COM_OFFSET, SIZE};
+use crate::disassemble::DisassemblerOptions;
+use crate::style::Style;
+
+/// Writes `data` as a plain linear listing, decoding every byte as code from
+/// the start of the program
+///
+/// Nothing is analysed: there are no labels, strings, syscall or other
+/// comments, so this is much faster than a full listing and suits grepping
+/// over many files. Bytes that do not decode are written as one byte `db`
+/// statements. Of `opts`, only the offset comments and byte dumps are used.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::DisassemblerOptions;
+/// use disassembler::linear::write_linear;
+/// use disassembler::style::Style;
+///
+/// let opts = DisassemblerOptions {
+///     offset_comments: false,
+///     ..Default::default()
+/// };
+/// let mut listing = Vec::new();
+/// // mov ah, 0x4c / int 0x21
+/// write_linear(&mut listing, &[0xB4, 0x4C, 0xCD, 0x21], &Style::default(), opts).unwrap();
+/// assert_eq!(String::from_utf8(listing).unwrap(), "mov ah,0x4c\nint 0x21\n");
+/// ```
+pub fn write_linear<W: Write>(
+    f: &mut W,
+    data: &[u8],
+    style: &Style,
+    opts: DisassemblerOptions,
+) -> io::Result<()> {
+    let mut formatter = NasmFormatter::new();
+    formatter.options_mut().set_digit_separator("'");
+    style.configure(formatter.options_mut());
+
+    let mut decoder = Decoder::with_ip(SIZE, data, COM_OFFSET as u64, DecoderOptions::NONE);
+    let mut text = String::new();
+    while decoder.can_decode() {
+        let offset = decoder.position();
+        let mut instruction = decoder.decode();
+        if instruction.is_invalid() {
+            instruction =
+                Instruction::with_declare_byte(&data[offset..offset + 1]).map_err(iced_error)?;
+            instruction.set_len(1);
+            decoder.set_position(offset + 1).map_err(iced_error)?;
+        }
+        let address = COM_OFFSET.wrapping_add(offset as u16);
+
+        text.clear();
+        formatter.format(&instruction, &mut text);
+        write!(f, "{text}")?;
+        if opts.offset_comments {
+            write!(f, " ; {}", style.address(address))?;
+        }
+        if opts.write_bytes {
+            write!(f, " ; bytes: ")?;
+            for &byte in &data[offset..offset + instruction.len()] {
+                write!(f, "{}", style.digits(byte as u64, 2))?;
+            }
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+/// An error from iced, which only a bug here could cause
+fn iced_error(err: IcedError) -> io::Error {
+    io::Error::other(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undecodable_bytes_are_listed_one_at_a_time() {
+        let opts = DisassemblerOptions {
+            offset_comments: true,
+            write_bytes: true,
+            ..Default::default()
+        };
+        let mut listing = Vec::new();
+        // ret / a lone prefix the program ends on
+        write_linear(&mut listing, &[0xC3, 0x0F], &Style::default(), opts).unwrap();
+        assert_eq!(
+            String::from_utf8(listing).unwrap(),
+            "ret ; 0x0100 ; bytes: c3\ndb 0xf ; 0x0101 ; bytes: 0f\n"
+        );
+    }
+
+    #[test]
+    fn strings_are_listed_as_code() {
+        let program = crate::samples::find("hello").unwrap().bytes;
+        let mut listing = Vec::new();
+        write_linear(
+            &mut listing,
+            program,
+            &Style::default(),
+            DisassemblerOptions::default(),
+        )
+        .unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+        assert!(!listing.contains("FUNC_"), "{listing}");
+        assert!(!listing.contains("db \""), "{listing}");
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{Instruction, Mnemonic};
+
+use crate::consts::Address;
+use crate::disassemble::Disassembler;
+use crate::syscall::SyscallType;
+
+/// A COM program that loads another file into memory and jumps into it
+///
+/// Recognised by an int 21h open (AH=3Dh) followed by a read (AH=3Fh) and
+/// then a far jump, far call or `retf` into the loaded code. Many loaders
+/// allocate the memory first with AH=48h.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Loader {
+    /// The name of the file opened, if DS:DX pointed into the program
+    pub file: Option<String>,
+    /// The address of the memory allocation call, if there is one
+    pub allocate: Option<Address>,
+    /// The address of the open call
+    pub open: Address,
+    /// The address of the read call
+    pub read: Address,
+    /// The address of the far transfer into the loaded code
+    pub jump: Address,
+}
+
+impl Loader {
+    /// Looks for the loader pattern in the analysed program
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::loader::Loader;
+    ///
+    /// let data = vec![
+    ///     0xBA, 0x0F, 0x01, // mov dx, 0x10f
+    ///     0xB4, 0x3D, 0xCD, 0x21, // mov ah, 0x3d / int 21h
+    ///     0xB4, 0x3F, 0xCD, 0x21, // mov ah, 0x3f / int 21h
+    ///     0xFF, 0x2E, 0x00, 0x00, // jmp far [0x0000]
+    ///     b'A', b'.', b'B', b'I', b'N', 0,
+    /// ];
+    /// let loader = Loader::detect(&Disassembler::new(data)).unwrap();
+    /// assert_eq!(loader.file.as_deref(), Some("A.BIN"));
+    /// ```
+    pub fn detect(disassembler: &Disassembler) -> Option<Loader> {
+        let syscalls = &disassembler.syscall_list.0;
+        let open = syscalls
+            .iter()
+            .find(|syscall| syscall.number == SyscallType::OpenFile2)?;
+        let read = syscalls.iter().find(|syscall| {
+            syscall.number == SyscallType::ReadFileOrDevice && syscall.address > open.address
+        })?;
+        let jump = disassembler
+            .instructions
+            .0
+            .iter()
+            .filter(|instruction| instruction.ip() > read.address as u64)
+            .find(|instruction| is_far_transfer(instruction))?;
+        let allocate = syscalls
+            .iter()
+            .find(|syscall| {
+                syscall.number == SyscallType::AllocateMemory && syscall.address < read.address
+            })
+            .map(|syscall| syscall.address);
+
+        Some(Loader {
+            file: open
+                .dx
+                .and_then(|address| disassembler.read_asciiz(address))
+                .filter(|name| !name.is_empty()),
+            allocate,
+            open: open.address,
+            read: read.address,
+            jump: jump.ip() as Address,
+        })
+    }
+}
+
+/// Returns true if `instruction` transfers control to another segment
+fn is_far_transfer(instruction: &Instruction) -> bool {
+    instruction.is_jmp_far()
+        || instruction.is_jmp_far_indirect()
+        || instruction.is_call_far()
+        || instruction.is_call_far_indirect()
+        || instruction.mnemonic() == Mnemonic::Retf
+}
+
+impl Display for Loader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "loader for {}",
+            self.file.as_deref().unwrap_or("an unknown file")
+        )?;
+        if let Some(allocate) = self.allocate {
+            write!(f, ": allocates memory at 0x{allocate:04x},")?;
+        } else {
+            write!(f, ":")?;
+        }
+        write!(
+            f,
+            " opens it at 0x{:04x}, reads it at 0x{:04x} and jumps into it at 0x{:04x}",
+            self.open, self.read, self.jump
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// mov bx, 0x10 / mov ah, 0x48 / int 21h, then opens and reads
+    /// `OVERLAY.BIN` and transfers with `push es / push 0 / retf`
+    fn loader_program() -> Vec<u8> {
+        let mut data = vec![
+            0xBB, 0x10, 0x00, // mov bx, 0x10
+            0xB4, 0x48, 0xCD, 0x21, // mov ah, 0x48 / int 21h
+            0xBA, 0x1A, 0x01, // mov dx, 0x11a
+            0xB4, 0x3D, 0xCD, 0x21, // mov ah, 0x3d / int 21h
+            0xB4, 0x3F, 0xCD, 0x21, // mov ah, 0x3f / int 21h
+            0x06, // push es
+            0x6A, 0x00, // push 0
+            0xCB, // retf
+            0x90, 0x90, 0x90, 0x90, // padding up to 0x11a
+        ];
+        data.extend(b"OVERLAY.BIN\0");
+        data
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Detection
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn loader_pattern_is_summarised() {
+        let loader = Loader::detect(&Disassembler::new(loader_program())).expect("a loader");
+        assert_eq!(
+            loader,
+            Loader {
+                file: Some("OVERLAY.BIN".to_string()),
+                allocate: Some(0x105),
+                open: 0x10C,
+                read: 0x110,
+                jump: 0x115,
+            }
+        );
+        assert_eq!(
+            loader.to_string(),
+            "loader for OVERLAY.BIN: allocates memory at 0x0105, opens it at 0x010c, reads it at 0x0110 and jumps into it at 0x0115"
+        );
+    }
+
+    #[test]
+    fn reading_without_a_far_jump_is_not_a_loader() {
+        let mut data = loader_program();
+        data[21] = 0xC3; // retf -> ret
+        assert_eq!(Loader::detect(&Disassembler::new(data)), None);
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Display};
+
+use crate::consts::Address;
+use crate::emulate::{BIOS_SEGMENT, Emulator, LOAD_SEGMENT, Registers, StopReason};
+
+/// How many agreeing program addresses are kept to show where the runs were
+/// before they diverged
+const HISTORY: usize = 8;
+
+/// How many bytes of differing output are shown from each run
+const OUTPUT_SHOWN: usize = 16;
+
+/// The registers compared between the runs, in the order they are reported
+const REGISTER_NAMES: [&str; 14] = [
+    "ax", "bx", "cx", "dx", "si", "di", "bp", "sp", "cs", "ds", "es", "ss", "ip", "flags",
+];
+
+/// How closely two runs are kept in step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Granularity {
+    /// Both programs must execute the same addresses with the same registers,
+    /// instruction by instruction
+    #[default]
+    Instruction,
+    /// Both programs must make the same DOS and BIOS calls with the same AX,
+    /// in the same order, however many instructions they take in between;
+    /// this lets patches that move or rewrite code still match
+    Service,
+}
+
+/// The first point at which two runs behaved differently
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// The runs were about to execute different addresses
+    Address {
+        /// The `cs:ip` of the old run
+        old: (u16, u16),
+        /// The `cs:ip` of the new run
+        new: (u16, u16),
+    },
+    /// The runs were at the same address with different registers
+    Registers {
+        /// The old run's registers
+        old: Registers,
+        /// The new run's registers
+        new: Registers,
+    },
+    /// The runs made different service calls
+    Service {
+        /// The interrupt vector and AX of the old run's call
+        old: (u8, u16),
+        /// The interrupt vector and AX of the new run's call
+        new: (u8, u16),
+    },
+    /// The runs wrote different output
+    Output {
+        /// The offset of the first differing byte in the output
+        offset: usize,
+        /// The old run's output from `offset`
+        old: Vec<u8>,
+        /// The new run's output from `offset`
+        new: Vec<u8>,
+    },
+    /// The runs stopped differently, or one stopped and the other did not
+    Stopped {
+        /// How the old run stopped, if it did
+        old: Option<StopReason>,
+        /// How the new run stopped, if it did
+        new: Option<StopReason>,
+    },
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stop = |reason: &Option<StopReason>| match reason {
+            Some(reason) => reason.to_string(),
+            None => "kept running".to_string(),
+        };
+        match self {
+            Divergence::Address { old, new } => write!(
+                f,
+                "the old program went to {:04x}:{:04x} and the new one to {:04x}:{:04x}",
+                old.0, old.1, new.0, new.1
+            ),
+            Divergence::Registers { old, new } => {
+                write!(f, "at {:04x}:{:04x} the registers differ:", old.cs, old.ip)?;
+                for name in REGISTER_NAMES {
+                    let (a, b) = (old.get(name).unwrap(), new.get(name).unwrap());
+                    if a != b {
+                        write!(f, " {} {a:04X} -> {b:04X}", name.to_ascii_uppercase())?;
+                    }
+                }
+                Ok(())
+            }
+            Divergence::Service { old, new } => write!(
+                f,
+                "the old program called int {:02X}h with AX={:04X} and the new one int {:02X}h with AX={:04X}",
+                old.0, old.1, new.0, new.1
+            ),
+            Divergence::Output { offset, old, new } => write!(
+                f,
+                "the output differs from byte {offset}: {:?} -> {:?}",
+                String::from_utf8_lossy(old),
+                String::from_utf8_lossy(new)
+            ),
+            Divergence::Stopped { old, new } => write!(
+                f,
+                "the old program {} and the new one {}",
+                stop(old),
+                stop(new)
+            ),
+        }
+    }
+}
+
+/// What running two versions of a program side by side showed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lockstep {
+    /// How many instructions the old program ran in agreement with the new one
+    pub instructions: u64,
+    /// How many service calls both programs made in agreement
+    pub service_calls: u64,
+    /// The first difference, or `None` if both runs behaved the same to the end
+    pub divergence: Option<Divergence>,
+    /// How both runs stopped, when they agreed to the end
+    pub stopped: Option<StopReason>,
+    /// The last program addresses of the old run executed in agreement,
+    /// oldest first
+    pub history: Vec<Address>,
+}
+
+impl Lockstep {
+    /// Whether both runs behaved the same to the end
+    pub fn agreed(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+impl Display for Lockstep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.divergence, &self.stopped) {
+            (Some(divergence), _) => write!(
+                f,
+                "the runs diverged after {} instructions and {} service calls: {divergence}",
+                self.instructions, self.service_calls
+            ),
+            (None, Some(stopped)) => write!(
+                f,
+                "both runs {stopped} after {} instructions and {} service calls, in agreement",
+                self.instructions, self.service_calls
+            ),
+            (None, None) => write!(
+                f,
+                "both runs agreed for {} instructions and {} service calls",
+                self.instructions, self.service_calls
+            ),
+        }
+    }
+}
+
+/// Runs `old` and `new` side by side and reports the first point at which
+/// they behave differently
+///
+/// Both emulators should be set up alike, with the same keys and files, so
+/// any difference comes from the programs. Each run may execute up to
+/// `budget` instructions. At [`Granularity::Instruction`] the runs must
+/// execute the same addresses with the same registers; at
+/// [`Granularity::Service`] they need only make the same service calls. In
+/// both, they must print the same output and stop the same way.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::emulate::Emulator;
+/// use disassembler::lockstep::{Divergence, Granularity, lockstep};
+///
+/// // mov dl, 'A' / mov ah, 2 / int 21h / ret
+/// let old = [0xB2, b'A', 0xB4, 0x02, 0xCD, 0x21, 0xC3];
+/// let mut new = old;
+/// new[1] = b'B';
+///
+/// let report = lockstep(
+///     &mut Emulator::new(&old),
+///     &mut Emulator::new(&new),
+///     1000,
+///     Granularity::Service,
+/// );
+/// assert!(matches!(report.divergence, Some(Divergence::Output { offset: 0, .. })));
+/// ```
+pub fn lockstep(
+    old: &mut Emulator,
+    new: &mut Emulator,
+    budget: u64,
+    granularity: Granularity,
+) -> Lockstep {
+    let mut report = Lockstep {
+        instructions: 0,
+        service_calls: 0,
+        divergence: None,
+        stopped: None,
+        history: Vec::new(),
+    };
+    let mut history = VecDeque::with_capacity(HISTORY);
+    let old_start = old.instructions_executed;
+    let old_limit = old_start.saturating_add(budget);
+    let new_limit = new.instructions_executed.saturating_add(budget);
+
+    let (divergence, stopped) = loop {
+        report.instructions = old.instructions_executed - old_start;
+        if granularity == Granularity::Instruction {
+            let old_at = (old.registers.cs, old.registers.ip);
+            let new_at = (new.registers.cs, new.registers.ip);
+            if old_at != new_at {
+                break (
+                    Some(Divergence::Address {
+                        old: old_at,
+                        new: new_at,
+                    }),
+                    None,
+                );
+            }
+            if old.registers != new.registers {
+                break (
+                    Some(Divergence::Registers {
+                        old: old.registers,
+                        new: new.registers,
+                    }),
+                    None,
+                );
+            }
+        }
+
+        let program_address = (old.registers.cs == LOAD_SEGMENT).then_some(old.registers.ip);
+        let (old_call, old_stop) = advance(old, old_limit, granularity);
+        let (new_call, new_stop) = advance(new, new_limit, granularity);
+        if let Some(divergence) = compare_output(&old.output, &new.output) {
+            break (Some(divergence), None);
+        }
+        match (old_call, new_call) {
+            (Some(a), Some(b)) if a != b => {
+                break (Some(Divergence::Service { old: a, new: b }), None);
+            }
+            (Some(_), Some(_)) => report.service_calls += 1,
+            _ => {}
+        }
+        match (old_stop, new_stop) {
+            (None, None) => {}
+            (Some(a), Some(b)) if a == b => break (None, Some(a)),
+            (old, new) => break (Some(Divergence::Stopped { old, new }), None),
+        }
+        if let Some(address) = program_address
+            && granularity == Granularity::Instruction
+        {
+            if history.len() == HISTORY {
+                history.pop_front();
+            }
+            history.push_back(address);
+        }
+    };
+
+    report.instructions = old.instructions_executed - old_start;
+    report.divergence = divergence;
+    report.stopped = stopped;
+    report.history = history.into();
+    report
+}
+
+/// Moves `emulator` on by one instruction, or at [`Granularity::Service`]
+/// through its next service call, returning the call's vector and AX and
+/// how the run stopped if it did
+fn advance(
+    emulator: &mut Emulator,
+    limit: u64,
+    granularity: Granularity,
+) -> (Option<(u8, u16)>, Option<StopReason>) {
+    loop {
+        let registers = emulator.registers;
+        let call = (registers.cs == BIOS_SEGMENT && registers.ip <= 0xFF)
+            .then_some((registers.ip as u8, registers.ax));
+        if call.is_none() && emulator.instructions_executed >= limit {
+            return (None, Some(StopReason::BudgetExhausted));
+        }
+        let stopped = emulator.step();
+        if stopped.is_some() || call.is_some() || granularity == Granularity::Instruction {
+            return (call, stopped);
+        }
+    }
+}
+
+/// The first difference between the output of two runs
+fn compare_output(old: &[u8], new: &[u8]) -> Option<Divergence> {
+    if old == new {
+        return None;
+    }
+    let offset = old
+        .iter()
+        .zip(new)
+        .position(|(a, b)| a != b)
+        .unwrap_or(old.len().min(new.len()));
+    let shown = |output: &[u8]| {
+        output[offset..]
+            .iter()
+            .take(OUTPUT_SHOWN)
+            .copied()
+            .collect()
+    };
+    Some(Divergence::Output {
+        offset,
+        old: shown(old),
+        new: shown(new),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // mov dl, 'A' / mov ah, 2 / int 21h / mov ax, 0x4c00 / int 21h
+    const PRINT_A: [u8; 11] = [
+        0xB2, b'A', 0xB4, 0x02, 0xCD, 0x21, 0xB8, 0x00, 0x4C, 0xCD, 0x21,
+    ];
+
+    fn run(old: &[u8], new: &[u8], granularity: Granularity) -> Lockstep {
+        lockstep(
+            &mut Emulator::new(old),
+            &mut Emulator::new(new),
+            1000,
+            granularity,
+        )
+    }
+
+    #[test]
+    fn identical_programs_agree_to_the_end() {
+        let report = run(&PRINT_A, &PRINT_A, Granularity::Instruction);
+        assert!(report.agreed());
+        assert_eq!(report.stopped, Some(StopReason::Exited(0)));
+        assert_eq!(report.instructions, 5);
+        assert_eq!(report.service_calls, 2);
+        assert_eq!(report.history, [0x100, 0x102, 0x104, 0x106, 0x109]);
+    }
+
+    #[test]
+    fn changed_registers_are_reported_where_they_first_differ() {
+        let mut new = PRINT_A;
+        new[1] = b'B';
+        let report = run(&PRINT_A, &new, Granularity::Instruction);
+        let Some(Divergence::Registers { old, new }) = report.divergence else {
+            panic!("expected registers to differ, got {report}");
+        };
+        assert_eq!(report.instructions, 1);
+        assert_eq!(old.ip, 0x102);
+        assert_eq!(
+            Divergence::Registers { old, new }.to_string(),
+            "at 1000:0102 the registers differ: DX 0041 -> 0042"
+        );
+    }
+
+    #[test]
+    fn moved_code_only_matches_by_service_call() {
+        // nop, then the same program one byte further on
+        let mut new = vec![0x90];
+        new.extend_from_slice(&PRINT_A);
+
+        let report = run(&PRINT_A, &new, Granularity::Instruction);
+        assert_eq!(
+            report.divergence,
+            Some(Divergence::Address {
+                old: (LOAD_SEGMENT, 0x102),
+                new: (LOAD_SEGMENT, 0x101)
+            })
+        );
+
+        let report = run(&PRINT_A, &new, Granularity::Service);
+        assert!(report.agreed(), "{report}");
+        assert_eq!(report.service_calls, 2);
+    }
+
+    #[test]
+    fn different_calls_and_stops_are_reported() {
+        let mut new = PRINT_A;
+        new[3] = 0x06; // mov ah, 6: direct console output of the same character
+        let report = run(&PRINT_A, &new, Granularity::Service);
+        assert_eq!(
+            report.divergence,
+            Some(Divergence::Service {
+                old: (0x21, 0x0200),
+                new: (0x21, 0x0600)
+            })
+        );
+
+        let mut new = PRINT_A;
+        new[7] = 0x01; // exit code 1
+        let report = run(&PRINT_A, &new, Granularity::Service);
+        assert_eq!(
+            report.divergence,
+            Some(Divergence::Service {
+                old: (0x21, 0x4C00),
+                new: (0x21, 0x4C01)
+            })
+        );
+
+        // jmp $
+        let report = run(&PRINT_A, &[0xEB, 0xFE], Granularity::Service);
+        assert_eq!(
+            report.divergence,
+            Some(Divergence::Output {
+                offset: 0,
+                old: b"A".to_vec(),
+                new: Vec::new()
+            })
+        );
+    }
+}
@@ -0,0 +1,443 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{
+    FlowControl, Instruction, InstructionInfoFactory, MemorySize, Mnemonic, OpKind, Register,
+};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+use crate::protection::value_before;
+use crate::redundancy::writes_register;
+
+/// The EMS interrupt, with the function in AH
+const EMS: u8 = 0x67;
+
+/// The multiplex interrupt, through which programs find the XMS driver
+const MULTIPLEX: u8 = 0x2F;
+
+/// Int 2Fh AX=4300h, returning AL=80h if an XMS driver is loaded
+const XMS_INSTALLED: u16 = 0x4300;
+
+/// Int 2Fh AX=4310h, returning the XMS driver's entry point in ES:BX
+const XMS_ENTRY: u16 = 0x4310;
+
+/// The size of an EMS page in kilobytes
+pub const EMS_PAGE_KB: u32 = 16;
+
+/// A call to the expanded or extended memory manager
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ManagerCall {
+    /// Int 67h, with the EMS function in AH
+    Expanded {
+        /// AH
+        function: u8,
+    },
+    /// Int 2Fh AX=4300h, checking for an XMS driver
+    XmsInstalled,
+    /// Int 2Fh AX=4310h, asking for the XMS driver's entry point
+    XmsEntry,
+    /// A far call through the XMS driver's entry point, with the function
+    /// in AH
+    Extended {
+        /// AH
+        function: u8,
+    },
+}
+
+impl ManagerCall {
+    /// Returns true if the call allocates memory of the size passed
+    fn allocates(&self) -> bool {
+        matches!(
+            self,
+            ManagerCall::Expanded {
+                function: 0x43 | 0x5A
+            } | ManagerCall::Extended { function: 0x09 }
+        )
+    }
+
+    /// Returns true if the call changes the size of memory allocated before
+    fn reallocates(&self) -> bool {
+        matches!(
+            self,
+            ManagerCall::Expanded { function: 0x51 } | ManagerCall::Extended { function: 0x0F }
+        )
+    }
+
+    /// The register holding the size the call passes, if it passes one
+    fn size_register(&self) -> Option<Register> {
+        match self {
+            ManagerCall::Expanded {
+                function: 0x43 | 0x51 | 0x5A,
+            }
+            | ManagerCall::Extended { function: 0x0F } => Some(Register::BX),
+            ManagerCall::Extended {
+                function: 0x09 | 0x10,
+            } => Some(Register::DX),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ManagerCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ManagerCall::Expanded { function } => match ems_name(function) {
+                Some(name) => write!(f, "EMS: {name}"),
+                None => write!(f, "EMS: function 0x{function:02x}"),
+            },
+            ManagerCall::XmsInstalled => write!(f, "XMS: installation check"),
+            ManagerCall::XmsEntry => write!(f, "XMS: get the driver entry point into es:bx"),
+            ManagerCall::Extended { function } => match xms_name(function) {
+                Some(name) => write!(f, "XMS: {name}"),
+                None => write!(f, "XMS: function 0x{function:02x}"),
+            },
+        }
+    }
+}
+
+/// A call to the expanded or extended memory manager in the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryManagerCall {
+    /// The address of the `int` or far call
+    pub address: Address,
+    /// What it asks for
+    pub call: ManagerCall,
+    /// The size it passes, in EMS pages for EMS, in kilobytes for
+    /// extended memory blocks and in paragraphs for upper memory blocks,
+    /// if it passes one and it was tracked
+    pub size: Option<u16>,
+}
+
+impl Display for MemoryManagerCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.call)?;
+        match (self.call, self.size) {
+            (ManagerCall::Expanded { .. }, Some(pages)) => {
+                write!(f, " ({pages} pages, {} KB)", pages as u32 * EMS_PAGE_KB)
+            }
+            (ManagerCall::Extended { function: 0x10 }, Some(paragraphs)) => {
+                write!(f, " ({paragraphs} paragraphs)")
+            }
+            (_, Some(kilobytes)) => write!(f, " ({kilobytes} KB)"),
+            (_, None) => Ok(()),
+        }
+    }
+}
+
+/// How much memory of one kind a program asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Requirement {
+    /// The memory asked for, in kilobytes
+    pub kilobytes: u32,
+    /// False if some allocation's size was not tracked, so the program may
+    /// ask for more
+    pub exact: bool,
+}
+
+impl Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.exact {
+            write!(f, "at least ")?;
+        }
+        write!(f, "{} KB", self.kilobytes)
+    }
+}
+
+/// The EMS and XMS calls a program makes and the memory they ask for
+///
+/// EMS is called through int 67h. XMS is called through a far pointer the
+/// program gets from int 2Fh AX=4310h and stores, so only far calls through
+/// the variable it stores BX in straight after are found.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::managers::MemoryManagers;
+///
+/// let data = vec![
+///     0xB4, 0x43, // mov ah, 0x43
+///     0xBB, 0x04, 0x00, // mov bx, 4
+///     0xCD, 0x67, // int 67h
+///     0xC3, // ret
+/// ];
+/// let managers = MemoryManagers::analyze(&Disassembler::new(data));
+/// assert_eq!(managers.calls[0].to_string(), "EMS: allocate pages (4 pages, 64 KB)");
+/// assert_eq!(managers.expanded().unwrap().to_string(), "64 KB");
+/// assert_eq!(managers.extended(), None);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryManagers {
+    /// The calls, in address order
+    pub calls: Vec<MemoryManagerCall>,
+    /// The variable the program keeps the XMS driver's entry point in
+    pub xms_entry: Option<Address>,
+}
+
+impl MemoryManagers {
+    /// Finds the EMS and XMS calls in the program
+    pub fn analyze(disassembler: &Disassembler) -> MemoryManagers {
+        let mut factory = InstructionInfoFactory::new();
+        let instructions = &disassembler.instructions.0;
+        let mut calls = Vec::new();
+        let mut xms_entry = None;
+        for (index, instruction) in instructions.iter().enumerate() {
+            if is_data(instruction)
+                || instruction.mnemonic() != Mnemonic::Int
+                || instruction.op0_kind() != OpKind::Immediate8
+            {
+                continue;
+            }
+            let call = match instruction.immediate8() {
+                EMS => match value_before(&mut factory, instructions, index, Register::AH) {
+                    Some(function) => ManagerCall::Expanded {
+                        function: function as u8,
+                    },
+                    None => continue,
+                },
+                MULTIPLEX => match value_before(&mut factory, instructions, index, Register::AX) {
+                    Some(XMS_INSTALLED) => ManagerCall::XmsInstalled,
+                    Some(XMS_ENTRY) => {
+                        xms_entry = xms_entry.or(entry_variable(&mut factory, instructions, index));
+                        ManagerCall::XmsEntry
+                    }
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            calls.push(with_size(&mut factory, instructions, index, call));
+        }
+        if let Some(entry) = xms_entry {
+            for (index, instruction) in instructions.iter().enumerate() {
+                if is_data(instruction) || !calls_through(instruction, entry) {
+                    continue;
+                }
+                let Some(function) = value_before(&mut factory, instructions, index, Register::AH)
+                else {
+                    continue;
+                };
+                let call = ManagerCall::Extended {
+                    function: function as u8,
+                };
+                calls.push(with_size(&mut factory, instructions, index, call));
+            }
+        }
+        calls.sort_by_key(|call| call.address);
+        MemoryManagers { calls, xms_entry }
+    }
+
+    /// The expanded memory the program allocates through EMS, if it
+    /// allocates any
+    pub fn expanded(&self) -> Option<Requirement> {
+        self.requirement(EMS_PAGE_KB, |call| {
+            matches!(call, ManagerCall::Expanded { .. })
+        })
+    }
+
+    /// The extended memory the program allocates through XMS, if it
+    /// allocates any
+    pub fn extended(&self) -> Option<Requirement> {
+        self.requirement(1, |call| matches!(call, ManagerCall::Extended { .. }))
+    }
+
+    /// The sum of the allocations of the calls `of` picks, or the largest
+    /// reallocation if that is more, in units of `unit` kilobytes
+    fn requirement(&self, unit: u32, of: impl Fn(&ManagerCall) -> bool) -> Option<Requirement> {
+        let mut allocated = 0;
+        let mut reallocated = 0;
+        let mut exact = true;
+        let mut any = false;
+        for call in self.calls.iter().filter(|call| of(&call.call)) {
+            if !call.call.allocates() && !call.call.reallocates() {
+                continue;
+            }
+            any = true;
+            match call.size {
+                Some(size) if call.call.allocates() => allocated += size as u32,
+                Some(size) => reallocated = reallocated.max(size as u32),
+                None => exact = false,
+            }
+        }
+        any.then(|| Requirement {
+            kilobytes: allocated.max(reallocated) * unit,
+            exact,
+        })
+    }
+}
+
+/// `call` at `index`, with the size it passes if it passes one
+fn with_size(
+    factory: &mut InstructionInfoFactory,
+    instructions: &[Instruction],
+    index: usize,
+    call: ManagerCall,
+) -> MemoryManagerCall {
+    MemoryManagerCall {
+        address: instructions[index].ip() as Address,
+        call,
+        size: call
+            .size_register()
+            .and_then(|register| value_before(factory, instructions, index, register)),
+    }
+}
+
+/// The variable BX is stored in straight after the int 2Fh AX=4310h call
+/// at `index`, which is where the program keeps the XMS entry point
+fn entry_variable(
+    factory: &mut InstructionInfoFactory,
+    instructions: &[Instruction],
+    index: usize,
+) -> Option<Address> {
+    for instruction in &instructions[index + 1..] {
+        if is_data(instruction) || instruction.flow_control() != FlowControl::Next {
+            break;
+        }
+        if instruction.mnemonic() == Mnemonic::Mov
+            && instruction.op0_kind() == OpKind::Memory
+            && instruction.op1_kind() == OpKind::Register
+            && instruction.op1_register() == Register::BX
+            && instruction.memory_base() == Register::None
+            && instruction.memory_index() == Register::None
+        {
+            return Some(instruction.memory_displacement64() as Address);
+        }
+        if writes_register(factory, instruction, Register::BX) {
+            break;
+        }
+    }
+    None
+}
+
+/// Returns true for a far call through the pointer at `entry`
+fn calls_through(instruction: &Instruction, entry: Address) -> bool {
+    instruction.mnemonic() == Mnemonic::Call
+        && instruction.op0_kind() == OpKind::Memory
+        && instruction.memory_size() == MemorySize::SegPtr16
+        && instruction.memory_base() == Register::None
+        && instruction.memory_index() == Register::None
+        && instruction.memory_displacement64() == entry as u64
+}
+
+/// The EMS functions of int 67h by AH
+fn ems_name(function: u8) -> Option<&'static str> {
+    Some(match function {
+        0x40 => "get the status",
+        0x41 => "get the page frame segment into bx",
+        0x42 => "get the free and total page counts into bx and dx",
+        0x43 => "allocate pages",
+        0x44 => "map a page",
+        0x45 => "release a handle and its pages",
+        0x46 => "get the version",
+        0x47 => "save the page map",
+        0x48 => "restore the page map",
+        0x4B => "get the handle count",
+        0x4C => "get the pages of a handle",
+        0x4D => "get the pages of every handle",
+        0x4E => "get or set the page map",
+        0x4F => "get or set part of the page map",
+        0x50 => "map several pages",
+        0x51 => "reallocate pages",
+        0x52 => "get or set handle attributes",
+        0x53 => "get or set a handle name",
+        0x54 => "get the handle directory",
+        0x55 => "alter the page map and jump",
+        0x56 => "alter the page map and call",
+        0x57 => "move or exchange a memory region",
+        0x58 => "get the mappable physical addresses",
+        0x59 => "get the hardware info",
+        0x5A => "allocate standard or raw pages",
+        0x5B => "get or set an alternate map register set",
+        0x5D => "enable or disable OS functions",
+        _ => return None,
+    })
+}
+
+/// The XMS driver functions by AH
+fn xms_name(function: u8) -> Option<&'static str> {
+    Some(match function {
+        0x00 => "get the version",
+        0x01 => "request the HMA",
+        0x02 => "release the HMA",
+        0x03 => "enable A20 globally",
+        0x04 => "disable A20 globally",
+        0x05 => "enable A20 locally",
+        0x06 => "disable A20 locally",
+        0x07 => "query A20",
+        0x08 => "query free extended memory",
+        0x09 => "allocate an extended memory block",
+        0x0A => "free an extended memory block",
+        0x0B => "move an extended memory block",
+        0x0C => "lock an extended memory block",
+        0x0D => "unlock an extended memory block",
+        0x0E => "get the info of a handle",
+        0x0F => "reallocate an extended memory block",
+        0x10 => "request an upper memory block",
+        0x11 => "release an upper memory block",
+        0x12 => "reallocate an upper memory block",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xms_calls_go_through_the_stored_entry_point() {
+        let data = vec![
+            0xB8, 0x10, 0x43, // mov ax, 0x4310
+            0xCD, 0x2F, // int 2Fh
+            0x89, 0x1E, 0x00, 0x02, // mov [0x200], bx
+            0x8C, 0x06, 0x02, 0x02, // mov [0x202], es
+            0xB4, 0x09, // mov ah, 9
+            0xBA, 0x00, 0x01, // mov dx, 0x100
+            0xFF, 0x1E, 0x00, 0x02, // call far [0x200]
+            0xB4, 0x0F, // mov ah, 0x0f
+            0xFF, 0x1E, 0x00, 0x02, // call far [0x200] (bx unknown)
+            0xC3, // ret
+        ];
+        let disassembler = Disassembler::new(data);
+        let managers = MemoryManagers::analyze(&disassembler);
+        assert_eq!(managers.xms_entry, Some(0x200));
+        let calls: Vec<String> = managers.calls.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            calls,
+            [
+                "XMS: get the driver entry point into es:bx",
+                "XMS: allocate an extended memory block (256 KB)",
+                "XMS: reallocate an extended memory block"
+            ]
+        );
+        assert_eq!(managers.extended().unwrap().to_string(), "at least 256 KB");
+        let listing = disassembler.to_string();
+        assert!(listing.contains("xms_entry:"), "{listing}");
+        assert!(
+            listing.contains("; XMS: allocate an extended memory block (256 KB)"),
+            "{listing}"
+        );
+    }
+
+    #[test]
+    fn ems_allocations_add_up() {
+        let data = vec![
+            0xB4, 0x43, 0xBB, 0x02, 0x00, 0xCD, 0x67, // allocate 2 pages
+            0xB4, 0x43, 0xBB, 0x03, 0x00, 0xCD, 0x67, // allocate 3 pages
+            0xB4, 0x51, 0xBB, 0x04, 0x00, 0xCD, 0x67, // reallocate to 4
+            0xB4, 0x41, 0xCD, 0x67, // get the page frame
+            0xC3, // ret
+        ];
+        let managers = MemoryManagers::analyze(&Disassembler::new(data));
+        assert_eq!(
+            managers.expanded(),
+            Some(Requirement {
+                kilobytes: 80,
+                exact: true
+            })
+        );
+        assert_eq!(managers.calls.len(), 4);
+    }
+}
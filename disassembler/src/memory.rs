@@ -0,0 +1,388 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{FlowControl, Instruction, InstructionInfoFactory, Mnemonic, OpKind, Register};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+use crate::redundancy::writes_register;
+use crate::syscall::SyscallType;
+
+/// The bytes in a paragraph, the unit DOS allocates memory in
+pub const PARAGRAPH: u32 = 16;
+
+/// What a memory call asks DOS to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MemoryOperation {
+    /// Int 21h AH=48h, allocating BX paragraphs
+    Allocate,
+    /// Int 21h AH=49h, freeing the block at ES
+    Free,
+    /// Int 21h AH=4Ah, resizing the block at ES to BX paragraphs
+    Resize,
+}
+
+/// An int 21h call allocating, freeing or resizing memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryCall {
+    /// The address of the int 21h
+    pub address: Address,
+    /// What it asks for
+    pub operation: MemoryOperation,
+    /// The paragraphs in BX, if they could be worked out
+    pub paragraphs: Option<u16>,
+    /// Whether ES is still the program's own segment, as nothing loads it
+    /// in the code leading up to the call
+    pub own_block: bool,
+}
+
+impl MemoryCall {
+    /// The bytes asked for, if the paragraphs are known
+    pub fn bytes(&self) -> Option<u32> {
+        self.paragraphs
+            .map(|paragraphs| paragraphs as u32 * PARAGRAPH)
+    }
+}
+
+impl Display for MemoryCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let block = if self.own_block {
+            "the program's own memory block"
+        } else {
+            "the memory block at es"
+        };
+        match self.operation {
+            MemoryOperation::Allocate => write!(f, "allocates ")?,
+            MemoryOperation::Free => return write!(f, "frees {block}"),
+            MemoryOperation::Resize => write!(f, "resizes {block} to ")?,
+        }
+        match (self.paragraphs, self.bytes()) {
+            (Some(paragraphs), Some(bytes)) => {
+                write!(f, "0x{paragraphs:04x} paragraphs ({bytes} bytes)")
+            }
+            _ => write!(f, "bx paragraphs"),
+        }
+    }
+}
+
+/// A shift by 4 turning a size in bytes into paragraphs for a memory call,
+/// or the paragraphs it returns back into bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParagraphConversion {
+    /// The address of the shift
+    pub address: Address,
+    /// Whether it turns bytes into paragraphs rather than back
+    pub to_paragraphs: bool,
+    /// Whether 15 is added first, or 1 after, so a partial paragraph counts
+    pub rounded_up: bool,
+}
+
+impl Display for ParagraphConversion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.to_paragraphs, self.rounded_up) {
+            (true, true) => write!(f, "bytes to paragraphs, rounded up"),
+            (true, false) => write!(f, "bytes to paragraphs"),
+            (false, _) => write!(f, "paragraphs to bytes"),
+        }
+    }
+}
+
+/// How a program uses memory from DOS
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryMap {
+    /// The calls allocating, freeing and resizing memory, in address order
+    pub calls: Vec<MemoryCall>,
+    /// The sizes converted between bytes and paragraphs around the calls
+    pub conversions: Vec<ParagraphConversion>,
+    /// The first int 21h AH=4Bh call running another program, if there is
+    /// one
+    pub exec: Option<Address>,
+}
+
+impl MemoryMap {
+    /// Finds the memory calls, what they ask for and the conversions
+    /// between bytes and paragraphs around them
+    ///
+    /// BX is worked out from a `mov bx, imm` followed by additions and
+    /// shifts in the code leading up to the call; a shift right by 4, by an
+    /// immediate or by a CL of 4, turns bytes into paragraphs. A shift left
+    /// of the BX a call returns, such as the largest block free after an
+    /// allocation fails, turns paragraphs back into bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::memory::MemoryMap;
+    ///
+    /// let data = vec![
+    ///     0xBB, 0x0F, 0x10, // mov bx, 0x100f
+    ///     0xB1, 0x04, // mov cl, 4
+    ///     0xD3, 0xEB, // shr bx, cl
+    ///     0xB4, 0x4A, // mov ah, 0x4a
+    ///     0xCD, 0x21, // int 21h
+    ///     0xC3, // ret
+    /// ];
+    /// let map = MemoryMap::analyze(&Disassembler::new(data));
+    /// assert_eq!(
+    ///     map.calls[0].to_string(),
+    ///     "resizes the program's own memory block to 0x0100 paragraphs (4096 bytes)"
+    /// );
+    /// assert_eq!(map.conversions[0].address, 0x105);
+    /// ```
+    pub fn analyze(disassembler: &Disassembler) -> MemoryMap {
+        let mut factory = InstructionInfoFactory::new();
+        let instructions = &disassembler.instructions.0;
+        let mut map = MemoryMap::default();
+        for syscall in &disassembler.syscall_list.0 {
+            let operation = match syscall.number {
+                SyscallType::AllocateMemory => MemoryOperation::Allocate,
+                SyscallType::ReleaseMemory => MemoryOperation::Free,
+                SyscallType::ReallocateMemory => MemoryOperation::Resize,
+                SyscallType::ExecuteProgram => {
+                    map.exec = map.exec.or(Some(syscall.address));
+                    continue;
+                }
+                _ => continue,
+            };
+            let Some(index) = disassembler.instructions.index_at(syscall.address) else {
+                continue;
+            };
+            let leading = straight_line_before(instructions, index);
+            let own_block = !leading
+                .iter()
+                .any(|instruction| writes_register(&mut factory, instruction, Register::ES));
+            let paragraphs = if operation == MemoryOperation::Free {
+                None
+            } else {
+                let (paragraphs, conversions) = paragraphs_in_bx(&mut factory, &leading);
+                map.conversions.extend(conversions);
+                map.conversions
+                    .extend(conversions_after(&mut factory, instructions, index));
+                paragraphs
+            };
+            map.calls.push(MemoryCall {
+                address: syscall.address,
+                operation,
+                paragraphs,
+                own_block,
+            });
+        }
+        map.calls.sort_by_key(|call| call.address);
+        map.conversions.sort_by_key(|conversion| conversion.address);
+        map.conversions.dedup();
+        map
+    }
+
+    /// Whether the program shrinks its own memory block before it first
+    /// runs another program, which DOS otherwise has no memory left for, or
+    /// `None` if it runs none
+    pub fn shrinks_before_exec(&self) -> Option<bool> {
+        let exec = self.exec?;
+        Some(self.calls.iter().any(|call| {
+            call.operation == MemoryOperation::Resize && call.own_block && call.address < exec
+        }))
+    }
+}
+
+/// The instructions running straight into the one at `index`, latest
+/// first, up to a jump, call, return or data
+fn straight_line_before(instructions: &[Instruction], index: usize) -> Vec<Instruction> {
+    instructions[..index]
+        .iter()
+        .rev()
+        .take_while(|instruction| {
+            !is_data(instruction) && instruction.flow_control() == FlowControl::Next
+        })
+        .copied()
+        .collect()
+}
+
+/// Returns true if `instruction` shifts `register` by 4 in `direction`,
+/// with `earlier` the instructions before it, latest first, for the CL
+fn shifts_by_four(
+    factory: &mut InstructionInfoFactory,
+    instruction: &Instruction,
+    register: Register,
+    direction: Mnemonic,
+    earlier: &[Instruction],
+) -> bool {
+    if instruction.mnemonic() != direction
+        || instruction.op0_kind() != OpKind::Register
+        || instruction.op0_register() != register
+    {
+        return false;
+    }
+    match instruction.op1_kind() {
+        OpKind::Immediate8 => instruction.immediate8() == 4,
+        OpKind::Register if instruction.op1_register() == Register::CL => earlier
+            .iter()
+            .find(|earlier| writes_register(factory, earlier, Register::CX))
+            .is_some_and(|load| {
+                load.mnemonic() == Mnemonic::Mov
+                    && match (load.op0_register(), load.op1_kind()) {
+                        (Register::CL, OpKind::Immediate8) => load.immediate8() == 4,
+                        (Register::CX, OpKind::Immediate16) => load.immediate16() & 0xFF == 4,
+                        _ => false,
+                    }
+            }),
+        _ => false,
+    }
+}
+
+/// What BX holds at the end of `leading`, latest first, if it is loaded
+/// with a constant and then only added to and shifted, and the shifts
+/// turning bytes into paragraphs on the way
+fn paragraphs_in_bx(
+    factory: &mut InstructionInfoFactory,
+    leading: &[Instruction],
+) -> (Option<u16>, Vec<ParagraphConversion>) {
+    let mut conversions = Vec::new();
+    let mut rounded_up = false;
+    let mut start = None;
+    let mut steps = Vec::new();
+    for (position, instruction) in leading.iter().enumerate() {
+        if !writes_register(factory, instruction, Register::BX) {
+            continue;
+        }
+        let earlier = &leading[position + 1..];
+        let (mnemonic, kind) = (instruction.mnemonic(), instruction.op1_kind());
+        if instruction.op0_register() != Register::BX {
+            break;
+        }
+        if shifts_by_four(factory, instruction, Register::BX, Mnemonic::Shr, earlier) {
+            conversions.push(instruction.ip() as Address);
+            steps.push(instruction);
+        } else if mnemonic == Mnemonic::Mov && kind == OpKind::Immediate16 {
+            start = Some(instruction.immediate16());
+            break;
+        } else if mnemonic == Mnemonic::Inc
+            || mnemonic == Mnemonic::Add
+                && matches!(kind, OpKind::Immediate16 | OpKind::Immediate8to16)
+        {
+            rounded_up = true;
+            steps.push(instruction);
+        } else {
+            break;
+        }
+    }
+    let conversions = conversions
+        .into_iter()
+        .map(|address| ParagraphConversion {
+            address,
+            to_paragraphs: true,
+            rounded_up,
+        })
+        .collect();
+    let value = start.map(|start| {
+        steps
+            .iter()
+            .rev()
+            .fold(start, |value, step| match step.mnemonic() {
+                Mnemonic::Shr => value >> 4,
+                Mnemonic::Inc => value.wrapping_add(1),
+                _ if step.op1_kind() == OpKind::Immediate16 => {
+                    value.wrapping_add(step.immediate16())
+                }
+                _ => value.wrapping_add(step.immediate8to16() as u16),
+            })
+    });
+    (value, conversions)
+}
+
+/// The shifts left turning the paragraphs a call at `index` returns in BX
+/// into bytes, before BX changes or the code goes elsewhere
+fn conversions_after(
+    factory: &mut InstructionInfoFactory,
+    instructions: &[Instruction],
+    index: usize,
+) -> Vec<ParagraphConversion> {
+    let mut found = Vec::new();
+    for (position, instruction) in instructions.iter().enumerate().skip(index + 1) {
+        if is_data(instruction)
+            || !matches!(
+                instruction.flow_control(),
+                FlowControl::Next | FlowControl::ConditionalBranch
+            )
+        {
+            break;
+        }
+        let earlier = straight_line_before(instructions, position);
+        if shifts_by_four(factory, instruction, Register::BX, Mnemonic::Shl, &earlier) {
+            found.push(ParagraphConversion {
+                address: instruction.ip() as Address,
+                to_paragraphs: false,
+                rounded_up: false,
+            });
+            break;
+        }
+        if writes_register(factory, instruction, Register::BX) {
+            break;
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn programs_shrinking_before_exec_are_told_apart() {
+        let data = vec![
+            0xBB, 0x00, 0x10, // mov bx, 0x1000
+            0x83, 0xC3, 0x0F, // add bx, 15
+            0xD1, 0xEB, 0xD1, 0xEB, 0xD1, 0xEB, 0xD1, 0xEB, // shr bx, 1 (4 times)
+            0xB4, 0x4A, // mov ah, 0x4a
+            0xCD, 0x21, // int 21h
+            0xB8, 0x00, 0x4B, // mov ax, 0x4b00
+            0xCD, 0x21, // int 21h
+            0xC3, // ret
+        ];
+        let map = MemoryMap::analyze(&Disassembler::new(data));
+        assert_eq!(map.exec, Some(0x115));
+        assert_eq!(map.shrinks_before_exec(), Some(true));
+        // shifts by one at a time are not worked out
+        assert_eq!(map.calls[0].paragraphs, None);
+        assert!(map.conversions.is_empty());
+
+        // mov ax, 0x4b00 / int 21h / ret
+        let map = MemoryMap::analyze(&Disassembler::new(vec![0xB8, 0x00, 0x4B, 0xCD, 0x21, 0xC3]));
+        assert_eq!(map.shrinks_before_exec(), Some(false));
+    }
+
+    #[test]
+    fn failed_allocations_convert_the_largest_block_back() {
+        let data = vec![
+            0xBB, 0xFF, 0x0F, // mov bx, 0x0fff
+            0x83, 0xC3, 0x0F, // add bx, 15
+            0xC1, 0xEB, 0x04, // shr bx, 4
+            0xB4, 0x48, // mov ah, 0x48
+            0xCD, 0x21, // int 21h
+            0x73, 0x03, // jnc 0x112
+            0xC1, 0xE3, 0x04, // shl bx, 4
+            0xC3, // ret
+        ];
+        let disassembler = Disassembler::new(data);
+        let map = MemoryMap::analyze(&disassembler);
+        assert_eq!(map.calls[0].paragraphs, Some(0x100));
+        let conversions: Vec<String> = map.conversions.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            conversions,
+            ["bytes to paragraphs, rounded up", "paragraphs to bytes"]
+        );
+
+        let listing = disassembler.to_string();
+        assert!(
+            listing.contains("int 0x21 ; allocates 0x0100 paragraphs (4096 bytes)"),
+            "{listing}"
+        );
+        assert!(
+            listing.contains("shl bx,4 ; paragraphs to bytes"),
+            "{listing}"
+        );
+    }
+}
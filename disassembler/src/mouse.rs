@@ -0,0 +1,198 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{InstructionInfoFactory, Mnemonic, OpKind, Register};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+use crate::protection::value_before;
+
+/// The mouse driver's interrupt, with the function in AX
+const MOUSE: u8 = 0x33;
+
+/// The events a mouse event handler can be called for, by bit of the mask
+/// in CX
+const EVENTS: [&str; 7] = [
+    "movement",
+    "left press",
+    "left release",
+    "right press",
+    "right release",
+    "middle press",
+    "middle release",
+];
+
+/// A call to the mouse driver through int 33h
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MouseCall {
+    /// The address of the int 33h
+    pub address: Address,
+    /// AX
+    pub function: u16,
+    /// The event handler at ES:DX the call installs, if it installs one
+    /// and DX was tracked
+    pub handler: Option<Address>,
+    /// The events the handler is called for, from CX, if it was tracked
+    pub events: Option<u16>,
+}
+
+impl MouseCall {
+    /// Returns true if the call installs an event handler: AX=000Ch,
+    /// AX=0014h exchanging it for the old one, or AX=0018h installing an
+    /// alternate one
+    pub fn installs_handler(&self) -> bool {
+        matches!(self.function, 0x0C | 0x14 | 0x18)
+    }
+}
+
+impl Display for MouseCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match mouse_name(self.function) {
+            Some(name) => write!(f, "mouse: {name}")?,
+            None => write!(f, "mouse: function 0x{:02x}", self.function)?,
+        }
+        if let Some(handler) = self.handler {
+            let preposition = if self.function == 0x14 { "for" } else { "to" };
+            write!(f, " {preposition} 0x{handler:04x}")?;
+        }
+        if let Some(events) = self.events.filter(|_| self.installs_handler()) {
+            let names: Vec<&str> = EVENTS
+                .iter()
+                .enumerate()
+                .filter(|&(bit, _)| events & (1 << bit) != 0)
+                .map(|(_, name)| *name)
+                .collect();
+            if names.is_empty() {
+                write!(f, " for no events")?;
+            } else {
+                write!(f, " for {}", names.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The int 33h functions by AX
+fn mouse_name(function: u16) -> Option<&'static str> {
+    Some(match function {
+        0x00 => "reset and check for a driver",
+        0x01 => "show the cursor",
+        0x02 => "hide the cursor",
+        0x03 => "get the position into cx, dx and the buttons into bx",
+        0x04 => "set the position to cx, dx",
+        0x05 => "get the presses of button bx",
+        0x06 => "get the releases of button bx",
+        0x07 => "set the horizontal range to cx to dx",
+        0x08 => "set the vertical range to cx to dx",
+        0x09 => "set the graphics cursor to the masks at es:dx",
+        0x0A => "set the text cursor",
+        0x0B => "get the motion counters into cx, dx",
+        0x0C => "set the event handler",
+        0x0D => "turn light pen emulation on",
+        0x0E => "turn light pen emulation off",
+        0x0F => "set the mickeys per 8 pixels to cx, dx",
+        0x10 => "hide the cursor in an area",
+        0x13 => "set the double speed threshold to dx",
+        0x14 => "exchange the event handler",
+        0x15 => "get the size of the driver state into bx",
+        0x16 => "save the driver state to es:dx",
+        0x17 => "restore the driver state from es:dx",
+        0x18 => "set an alternate event handler",
+        0x19 => "get an alternate event handler",
+        0x1A => "set the sensitivity",
+        0x1B => "get the sensitivity",
+        0x1C => "set the interrupt rate to bx",
+        0x1D => "set the display page to bx",
+        0x1E => "get the display page into bx",
+        0x1F => "disable the driver",
+        0x20 => "enable the driver",
+        0x21 => "software reset",
+        0x22 => "set the language to bx",
+        0x23 => "get the language into bx",
+        0x24 => "get the driver version and type",
+        _ => return None,
+    })
+}
+
+/// Finds the calls to the mouse driver through int 33h and the event
+/// handlers they install
+///
+/// The function is taken from AX and, for calls installing an event
+/// handler, the handler from DX and the events it wants from CX, each set
+/// just before the call. The [disassembler](Disassembler) labels the
+/// handlers `mouse_handler`, so they are decoded as code like any other
+/// function, as the driver calls them far with the event in AX.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::mouse::find_mouse_calls;
+///
+/// // mov ax, 1 / int 33h / ret
+/// let disassembler = Disassembler::new(vec![0xB8, 0x01, 0x00, 0xCD, 0x33, 0xC3]);
+/// let calls = find_mouse_calls(&disassembler);
+/// assert_eq!(calls[0].to_string(), "mouse: show the cursor");
+/// ```
+pub fn find_mouse_calls(disassembler: &Disassembler) -> Vec<MouseCall> {
+    let mut factory = InstructionInfoFactory::new();
+    let instructions = &disassembler.instructions.0;
+    let mut found = Vec::new();
+    for (index, instruction) in instructions.iter().enumerate() {
+        if is_data(instruction)
+            || instruction.mnemonic() != Mnemonic::Int
+            || instruction.op0_kind() != OpKind::Immediate8
+            || instruction.immediate8() != MOUSE
+        {
+            continue;
+        }
+        let Some(function) = value_before(&mut factory, instructions, index, Register::AX) else {
+            continue;
+        };
+        let mut call = MouseCall {
+            address: instruction.ip() as Address,
+            function,
+            handler: None,
+            events: None,
+        };
+        if call.installs_handler() {
+            call.handler = value_before(&mut factory, instructions, index, Register::DX);
+            call.events = value_before(&mut factory, instructions, index, Register::CX);
+        }
+        found.push(call);
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixpoint::settle;
+
+    #[test]
+    fn event_handlers_are_labelled_and_decoded() {
+        let data = vec![
+            0xB8, 0x0C, 0x00, // mov ax, 0x0c
+            0xB9, 0x03, 0x00, // mov cx, 3
+            0xBA, 0x0E, 0x01, // mov dx, 0x10e
+            0xCD, 0x33, // int 33h
+            0xC3, // ret
+            0xB8, // a stray byte swallowing the handler's first instruction
+            0x90, // nop
+            0xB4, 0x01, // mov ah, 1 (the handler, at 0x10e)
+            0xCB, // retf
+        ];
+        let mut disassembler = Disassembler::new(data);
+        let calls = find_mouse_calls(&disassembler);
+        assert_eq!(
+            calls[0].to_string(),
+            "mouse: set the event handler to 0x010e for movement, left press"
+        );
+        settle(&mut disassembler);
+        let listing = disassembler.to_string();
+        assert!(
+            listing.contains("mouse_handler_0x010e: ; function\n    mov ah,1\n    retf"),
+            "{listing}"
+        );
+    }
+}
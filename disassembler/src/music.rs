@@ -0,0 +1,363 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{FlowControl, Instruction, Mnemonic, OpKind, Register};
+
+use crate::consts::{Address, AddressExt, COM_OFFSET};
+use crate::disassemble::{Disassembler, is_data};
+use crate::table::access;
+
+/// The PIT clock, divided by the value written to port 42h to give the speaker's pitch
+/// and to port 40h to give the rate of the timer interrupt
+pub(crate) const PIT_HZ: u32 = 1_193_182;
+
+/// The timer channel 2 data port, which sets the speaker's pitch
+const SPEAKER_PITCH: u16 = 0x42;
+
+/// The system control port, whose low bits gate the speaker
+const SPEAKER_GATE: u16 = 0x61;
+
+/// The AdLib register select and data ports
+const ADLIB_PORTS: [u16; 2] = [0x388, 0x389];
+
+/// How many instructions before an `out` the value it writes is traced back
+const SOURCE_WINDOW: usize = 12;
+
+/// The most events read from a music table
+const MAX_EVENTS: usize = 4096;
+
+/// A sound device a routine writes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Device {
+    /// The PC speaker, driven by timer channel 2
+    PcSpeaker,
+    /// The AdLib OPL2 FM synthesiser
+    AdLib,
+}
+
+impl Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Device::PcSpeaker => write!(f, "PC speaker"),
+            Device::AdLib => write!(f, "AdLib"),
+        }
+    }
+}
+
+/// An entry of a music table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MusicEvent {
+    /// A timer divisor for the PC speaker and the pitch it plays
+    Tone {
+        /// The value written to port 42h
+        divisor: u16,
+        /// The pitch in Hz
+        frequency: u32,
+    },
+    /// A zero divisor, usually a rest
+    Silence,
+    /// A value written to an AdLib register
+    Write {
+        /// The register number
+        register: u8,
+        /// The value written
+        value: u8,
+    },
+}
+
+/// A routine playing music and the table it plays from
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Music {
+    /// The device played
+    pub device: Device,
+    /// The `out` instruction writing the notes or registers
+    pub routine: Address,
+    /// The table the written values are read from, if it was found
+    pub data: Option<(Address, Address)>,
+    /// The entries of the table
+    pub events: Vec<MusicEvent>,
+}
+
+impl Display for Music {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} music routine at 0x{:04x}", self.device, self.routine)?;
+        match self.data {
+            Some((start, end)) => {
+                let what = match self.device {
+                    Device::PcSpeaker => "notes",
+                    Device::AdLib => "register writes",
+                };
+                write!(
+                    f,
+                    " playing 0x{start:04x}-0x{end:04x} ({} {what})",
+                    self.events.len()
+                )
+            }
+            None => write!(f, ", data not found"),
+        }
+    }
+}
+
+/// The port an `out` writes to, if it is known
+///
+/// A port in DX is followed back to the `mov dx, imm` setting it, counting
+/// any `inc dx` and `dec dx` in between.
+pub(crate) fn port(instructions: &[Instruction], index: usize) -> Option<u16> {
+    let instruction = &instructions[index];
+    match instruction.op0_kind() {
+        OpKind::Immediate8 => return Some(instruction.immediate8() as u16),
+        OpKind::Register if instruction.op0_register() == Register::DX => {}
+        _ => return None,
+    }
+    let mut offset: u16 = 0;
+    for before in instructions[index.saturating_sub(SOURCE_WINDOW)..index]
+        .iter()
+        .rev()
+    {
+        if before.op0_kind() != OpKind::Register
+            || before.op0_register().full_register() != Register::DX.full_register()
+        {
+            continue;
+        }
+        match before.mnemonic() {
+            Mnemonic::Inc if before.op0_register() == Register::DX => {
+                offset = offset.wrapping_add(1)
+            }
+            Mnemonic::Dec if before.op0_register() == Register::DX => {
+                offset = offset.wrapping_sub(1)
+            }
+            Mnemonic::Mov
+                if before.op0_register() == Register::DX
+                    && before.op1_kind() == OpKind::Immediate16 =>
+            {
+                return Some(before.immediate16().wrapping_add(offset));
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// The table the value written by the `out` at `index` is read from
+fn source(instructions: &[Instruction], index: usize) -> Option<Address> {
+    for at in (index.saturating_sub(SOURCE_WINDOW)..index).rev() {
+        let instruction = &instructions[at];
+        if matches!(
+            instruction.flow_control(),
+            FlowControl::Return | FlowControl::Call
+        ) {
+            return None;
+        }
+        let into_ax = matches!(instruction.mnemonic(), Mnemonic::Lodsb | Mnemonic::Lodsw)
+            || (instruction.op0_kind() == OpKind::Register
+                && instruction.op0_register().full_register() == Register::AX.full_register());
+        if !into_ax {
+            continue;
+        }
+        if let Some(read) = access(instructions, at) {
+            return Some(read.start);
+        }
+        let direct = instruction.mnemonic() == Mnemonic::Mov
+            && instruction.op1_kind() == OpKind::Memory
+            && instruction.memory_base() == Register::None
+            && instruction.memory_index() == Register::None;
+        if direct {
+            return Some(instruction.memory_displacement64() as Address);
+        }
+    }
+    None
+}
+
+/// Reads the events of a `device` table from `bytes`, which end at the next
+/// address something else refers to
+fn read_events(device: Device, bytes: &[u8]) -> (usize, Vec<MusicEvent>) {
+    let mut events = Vec::new();
+    let mut len = 0;
+    for pair in bytes.chunks_exact(2).take(MAX_EVENTS) {
+        let event = match device {
+            Device::PcSpeaker => match u16::from_le_bytes([pair[0], pair[1]]) {
+                0xFFFF => break,
+                0 => MusicEvent::Silence,
+                divisor => MusicEvent::Tone {
+                    divisor,
+                    frequency: PIT_HZ / divisor as u32,
+                },
+            },
+            Device::AdLib if pair[0] == 0xFF => break,
+            Device::AdLib => MusicEvent::Write {
+                register: pair[0],
+                value: pair[1],
+            },
+        };
+        events.push(event);
+        len += 2;
+    }
+    (len, events)
+}
+
+/// Finds routines playing music on the PC speaker or an AdLib, and the
+/// tables they play
+///
+/// A routine is an `out` to port 42h or 61h, or to 388h/389h through DX,
+/// and its table is where the value written was loaded from, by `lodsb`,
+/// `lodsw` or an indexed `mov`. PC speaker tables are read as words of
+/// timer divisors, ending at 0FFFFh, and AdLib tables as register and value
+/// byte pairs, ending at register 0FFh; either ends early at the next
+/// address the code refers to. Each table is reported once, with the first
+/// routine found playing it; a device played without a table being found
+/// is reported once, with no data.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::music::{find_music, Device, MusicEvent};
+///
+/// // mov si, 0x10a / lodsw / out 0x42, al / mov al, ah / out 0x42, al / dw 1193, 0xffff
+/// let data = vec![
+///     0xBE, 0x0A, 0x01, 0xAD, 0xE6, 0x42, 0x88, 0xE0, 0xE6, 0x42, 0xA9, 0x04, 0xFF, 0xFF,
+/// ];
+/// let music = find_music(&Disassembler::new(data));
+/// assert_eq!(music[0].device, Device::PcSpeaker);
+/// assert_eq!(music[0].events, vec![MusicEvent::Tone { divisor: 1193, frequency: 1000 }]);
+/// ```
+pub fn find_music(disassembler: &Disassembler) -> Vec<Music> {
+    let instructions = &disassembler.instructions.0;
+    let program_end = COM_OFFSET as u32 + disassembler.data.len() as u32;
+    let mut live = disassembler.referenced_addresses();
+    live.extend(disassembler.labels.0.iter().map(|label| label.address));
+
+    let mut found: Vec<Music> = Vec::new();
+    for (index, instruction) in instructions.iter().enumerate() {
+        if instruction.mnemonic() != Mnemonic::Out || is_data(instruction) {
+            continue;
+        }
+        let device = match port(instructions, index) {
+            Some(SPEAKER_PITCH | SPEAKER_GATE) => Device::PcSpeaker,
+            Some(port) if ADLIB_PORTS.contains(&port) => Device::AdLib,
+            _ => continue,
+        };
+        let routine = instruction.ip() as Address;
+        let start = source(instructions, index).filter(|&start| (start as u32) < program_end);
+        let Some(start) = start else {
+            if !found.iter().any(|music| music.device == device) {
+                found.push(Music {
+                    device,
+                    routine,
+                    data: None,
+                    events: Vec::new(),
+                });
+            }
+            continue;
+        };
+        if found
+            .iter()
+            .any(|music| music.data.is_some_and(|(other, _)| other == start))
+        {
+            continue;
+        }
+        let next_live = live
+            .range(start.saturating_add(1)..)
+            .next()
+            .map_or(program_end, |&next| next as u32)
+            .min(program_end);
+        let Some(offset) = start.file_offset() else {
+            continue;
+        };
+        let bytes = &disassembler.data[offset..(next_live - COM_OFFSET as u32) as usize];
+        let (len, events) = read_events(device, bytes);
+        found.retain(|music| music.device != device || music.data.is_some());
+        found.push(Music {
+            device,
+            routine,
+            data: Some((start, start + len as Address)),
+            events,
+        });
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Routines and tables
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn adlib_register_pairs_are_read() {
+        let data = vec![
+            0xBE, 0x13, 0x01, // mov si, 0x113
+            0xBA, 0x88, 0x03, // 0x103: mov dx, 0x388
+            0xAC, // lodsb
+            0x3C, 0xFF, // cmp al, 0xff
+            0x74, 0x06, // je 0x111
+            0xEE, // out dx, al
+            0x42, // inc dx
+            0xAC, // lodsb
+            0xEE, // out dx, al
+            0xEB, 0xF2, // jmp 0x103
+            0xC3, // 0x111: ret
+            0x90, // nop
+            0x20, 0x01, 0xB0, 0x32, 0xFF, 0x00, // registers 20h = 1, B0h = 32h, end
+        ];
+        let music = find_music(&Disassembler::new(data));
+        assert_eq!(
+            music,
+            vec![Music {
+                device: Device::AdLib,
+                routine: 0x10B,
+                data: Some((0x113, 0x117)),
+                events: vec![
+                    MusicEvent::Write {
+                        register: 0x20,
+                        value: 0x01
+                    },
+                    MusicEvent::Write {
+                        register: 0xB0,
+                        value: 0x32
+                    },
+                ],
+            }]
+        );
+        assert_eq!(
+            music[0].to_string(),
+            "AdLib music routine at 0x010b playing 0x0113-0x0117 (2 register writes)"
+        );
+    }
+
+    #[test]
+    fn speaker_gate_without_a_table_is_still_reported() {
+        // in al, 0x61 / or al, 3 / out 0x61, al / ret
+        let data = vec![0xE4, 0x61, 0x0C, 0x03, 0xE6, 0x61, 0xC3];
+        let music = find_music(&Disassembler::new(data));
+        assert_eq!(music.len(), 1);
+        assert_eq!(music[0].data, None);
+        assert_eq!(
+            music[0].to_string(),
+            "PC speaker music routine at 0x0104, data not found"
+        );
+    }
+
+    #[test]
+    fn zero_divisors_are_rests() {
+        assert_eq!(
+            read_events(
+                Device::PcSpeaker,
+                &[0x00, 0x00, 0xA9, 0x04, 0xFF, 0xFF, 1, 0]
+            ),
+            (
+                4,
+                vec![
+                    MusicEvent::Silence,
+                    MusicEvent::Tone {
+                        divisor: 1193,
+                        frequency: 1000
+                    }
+                ]
+            )
+        );
+    }
+}
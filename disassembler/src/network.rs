@@ -0,0 +1,291 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{
+    FlowControl, Instruction, InstructionInfoFactory, MemorySize, Mnemonic, OpKind, Register,
+};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+use crate::protection::value_before;
+
+/// The multiplex interrupt, whose network functions are picked by AX
+const MULTIPLEX: u8 = 0x2F;
+
+/// The IPX and SPX interrupt, with the function in BX
+const IPX: u8 = 0x7A;
+
+/// The NetBIOS interrupt, with ES:BX pointing at a network control block
+const NETBIOS: u8 = 0x5C;
+
+/// The bit of a NetBIOS command asking it to return at once and post the
+/// result later
+const NO_WAIT: u8 = 0x80;
+
+/// A call to one of the network interrupts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NetworkCall {
+    /// A network function of int 2Fh, with AX
+    Multiplex {
+        /// AX
+        function: u16,
+    },
+    /// Int 7Ah, with the IPX or SPX function in BX if it was tracked
+    Ipx {
+        /// BX
+        function: Option<u16>,
+    },
+    /// Int 5Ch, with the command in the network control block if it could
+    /// be found
+    NetBios {
+        /// The first byte of the control block
+        command: Option<u8>,
+    },
+}
+
+impl Display for NetworkCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            NetworkCall::Multiplex { function } => match multiplex_name(function) {
+                Some(name) => write!(f, "{name}"),
+                None => write!(f, "network redirector function 0x{:02x}", function & 0xFF),
+            },
+            NetworkCall::Ipx { function } => match function.and_then(ipx_name) {
+                Some(name) => write!(f, "IPX: {name}"),
+                None => match function {
+                    Some(function) => write!(f, "IPX: function 0x{function:02x}"),
+                    None => write!(f, "IPX: function in bx"),
+                },
+            },
+            NetworkCall::NetBios { command } => match command {
+                Some(command) => {
+                    match netbios_name(command & !NO_WAIT) {
+                        Some(name) => write!(f, "NetBIOS: {name}")?,
+                        None => write!(f, "NetBIOS: command 0x{command:02x}")?,
+                    }
+                    if command & NO_WAIT != 0 && command != 0xFF {
+                        write!(f, ", no wait")?;
+                    }
+                    Ok(())
+                }
+                None => write!(f, "NetBIOS: command in the control block at es:bx"),
+            },
+        }
+    }
+}
+
+/// A network interrupt call in the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NetworkInterrupt {
+    /// The address of the `int`
+    pub address: Address,
+    /// What it asks for
+    pub call: NetworkCall,
+}
+
+/// The network functions of int 2Fh by AX: the redirector, the network
+/// installation checks and the IPX one
+fn multiplex_name(function: u16) -> Option<&'static str> {
+    Some(match function {
+        0x1100 => "network redirector: installation check",
+        0x7A00 => "IPX: installation check, entry point in es:di",
+        0xB800 => "network: installation check",
+        0xB803 => "network: get the event post handler",
+        0xB804 => "network: set the event post handler",
+        0xB809 => "network: get the version",
+        _ => return None,
+    })
+}
+
+/// Returns true if int 2Fh with `function` in AX is a network call
+fn is_network_multiplex(function: u16) -> bool {
+    function >> 8 == 0x11 || multiplex_name(function).is_some()
+}
+
+/// The IPX and SPX functions of int 7Ah by BX
+fn ipx_name(function: u16) -> Option<&'static str> {
+    Some(match function {
+        0x00 => "open socket",
+        0x01 => "close socket",
+        0x02 => "get local target",
+        0x03 => "send packet",
+        0x04 => "listen for packet",
+        0x05 => "schedule event",
+        0x06 => "cancel event",
+        0x07 => "schedule special event",
+        0x08 => "get interval marker",
+        0x09 => "get internetwork address",
+        0x0A => "relinquish control",
+        0x0B => "disconnect from target",
+        0x10 => "SPX installation check",
+        0x11 => "establish SPX connection",
+        0x12 => "listen for SPX connection",
+        0x13 => "terminate SPX connection",
+        0x14 => "abort SPX connection",
+        0x15 => "get SPX connection status",
+        0x16 => "send SPX packet",
+        0x17 => "listen for SPX packet",
+        _ => return None,
+    })
+}
+
+/// The NetBIOS commands, without the no-wait bit
+fn netbios_name(command: u8) -> Option<&'static str> {
+    Some(match command {
+        0x10 => "call",
+        0x11 => "listen",
+        0x12 => "hang up",
+        0x14 => "send",
+        0x15 => "receive",
+        0x16 => "receive any",
+        0x17 => "chain send",
+        0x20 => "send datagram",
+        0x21 => "receive datagram",
+        0x22 => "send broadcast datagram",
+        0x23 => "receive broadcast datagram",
+        0x30 => "add name",
+        0x31 => "delete name",
+        0x32 => "reset",
+        0x33 => "adapter status",
+        0x34 => "session status",
+        0x35 => "cancel",
+        0x36 => "add group name",
+        0x70 => "unlink",
+        // an invalid command, which a missing NetBIOS leaves alone
+        0x7F => "installation check",
+        _ => return None,
+    })
+}
+
+/// The command a NetBIOS call at `index` issues: a byte stored into the
+/// control block just before the call, or the one already in the program
+fn netbios_command(
+    disassembler: &Disassembler,
+    instructions: &[Instruction],
+    index: usize,
+    control_block: Option<u16>,
+) -> Option<u8> {
+    for instruction in instructions[..index].iter().rev() {
+        if is_data(instruction) || instruction.flow_control() != FlowControl::Next {
+            break;
+        }
+        let stores_byte = instruction.mnemonic() == Mnemonic::Mov
+            && instruction.op0_kind() == OpKind::Memory
+            && instruction.memory_size() == MemorySize::UInt8
+            && instruction.op1_kind() == OpKind::Immediate8
+            && instruction.memory_index() == Register::None;
+        let into_block = match instruction.memory_base() {
+            Register::BX => instruction.memory_displacement64() == 0,
+            Register::None => control_block
+                .is_some_and(|block| instruction.memory_displacement64() == block as u64),
+            _ => false,
+        };
+        if stores_byte && into_block {
+            return Some(instruction.immediate8());
+        }
+    }
+    control_block
+        .and_then(|block| disassembler.bytes_at(block))
+        .and_then(|bytes| bytes.first().copied())
+}
+
+/// Finds the calls to int 2Fh network functions, IPX and SPX through int
+/// 7Ah and NetBIOS through int 5Ch
+///
+/// Int 2Fh calls are only taken when AX is set just before them to one of
+/// the network functions, as the interrupt serves print spoolers, XMS and
+/// much more. Calls to IPX through the far entry point its installation
+/// check returns are not found.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::network::find_network_calls;
+///
+/// // mov bx, 3 / int 7Ah / ret
+/// let disassembler = Disassembler::new(vec![0xBB, 0x03, 0x00, 0xCD, 0x7A, 0xC3]);
+/// let calls = find_network_calls(&disassembler);
+/// assert_eq!(calls[0].call.to_string(), "IPX: send packet");
+/// ```
+pub fn find_network_calls(disassembler: &Disassembler) -> Vec<NetworkInterrupt> {
+    let mut factory = InstructionInfoFactory::new();
+    let instructions = &disassembler.instructions.0;
+    let mut found = Vec::new();
+    for (index, instruction) in instructions.iter().enumerate() {
+        if is_data(instruction)
+            || instruction.mnemonic() != Mnemonic::Int
+            || instruction.op0_kind() != OpKind::Immediate8
+        {
+            continue;
+        }
+        let mut before = |register| value_before(&mut factory, instructions, index, register);
+        let call = match instruction.immediate8() {
+            MULTIPLEX => {
+                let function = before(Register::AX)
+                    .or_else(|| Some(before(Register::AH)? << 8 | before(Register::AL)?));
+                match function.filter(|&function| is_network_multiplex(function)) {
+                    Some(function) => NetworkCall::Multiplex { function },
+                    None => continue,
+                }
+            }
+            IPX => NetworkCall::Ipx {
+                function: before(Register::BX),
+            },
+            NETBIOS => {
+                let control_block = before(Register::BX);
+                NetworkCall::NetBios {
+                    command: netbios_command(disassembler, instructions, index, control_block),
+                }
+            }
+            _ => continue,
+        };
+        found.push(NetworkInterrupt {
+            address: instruction.ip() as Address,
+            call,
+        });
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn netbios_commands_are_read_from_the_control_block() {
+        let data = vec![
+            0xBB, 0x10, 0x01, // mov bx, 0x110
+            0xCD, 0x5C, // int 5Ch (reset, in the block)
+            0xC6, 0x07, 0x94, // mov byte [bx], 0x94
+            0xCD, 0x5C, // int 5Ch
+            0xB8, 0x00, 0x7A, // mov ax, 0x7a00
+            0xCD, 0x2F, // int 2Fh
+            0xC3, // ret
+            0x32, 0x00, // the control block at 0x110
+        ];
+        let disassembler = Disassembler::new(data);
+        let calls: Vec<String> = find_network_calls(&disassembler)
+            .iter()
+            .map(|found| found.call.to_string())
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                "NetBIOS: reset",
+                "NetBIOS: send, no wait",
+                "IPX: installation check, entry point in es:di"
+            ]
+        );
+        let listing = disassembler.to_string();
+        assert!(listing.contains("int 0x5c ; NetBIOS: reset"), "{listing}");
+    }
+
+    #[test]
+    fn other_multiplex_calls_are_left_alone() {
+        // mov ax, 0x4300 / int 2Fh (XMS) / int 2Fh (AX unknown) / ret
+        let data = vec![0xB8, 0x00, 0x43, 0xCD, 0x2F, 0xCD, 0x2F, 0xC3];
+        assert!(find_network_calls(&Disassembler::new(data)).is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt::{self, Display};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+
+/// The prefixes an instruction can start with before its opcode
+const PREFIXES: [u8; 11] = [
+    0x26, 0x2E, 0x36, 0x3E, 0x64, 0x65, 0x66, 0x67, 0xF0, 0xF2, 0xF3,
+];
+
+/// A prefix switching a 16-bit instruction to 32 bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SizeOverride {
+    /// `66h`, making the operands 32-bit, as in `inc eax`
+    Operand,
+    /// `67h`, making the addressing 32-bit, as in `mov ax,[ebx]`
+    Address,
+}
+
+impl SizeOverride {
+    /// The prefix byte
+    pub fn byte(self) -> u8 {
+        match self {
+            SizeOverride::Operand => 0x66,
+            SizeOverride::Address => 0x67,
+        }
+    }
+}
+
+impl Display for SizeOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SizeOverride::Operand => write!(f, "32-bit operands"),
+            SizeOverride::Address => write!(f, "32-bit addressing"),
+        }
+    }
+}
+
+/// An instruction with operand- or address-size prefixes, which only a 386
+/// or later runs as listed
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WideInstruction {
+    /// The address of the instruction
+    pub address: Address,
+    /// The size prefixes it has, in the order they come
+    pub overrides: Vec<SizeOverride>,
+}
+
+impl Display for WideInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what: Vec<String> = self
+            .overrides
+            .iter()
+            .map(|size| format!("{size} (0x{:02x} prefix)", size.byte()))
+            .collect();
+        // 60h-6Fh are the conditional jumps again on an 8086
+        write!(
+            f,
+            "{}: needs a 386, an 8086 reads the prefix as a jump",
+            what.join(" and ")
+        )
+    }
+}
+
+/// Finds the instructions with operand- or address-size prefixes
+///
+/// A COM program runs in 16-bit real mode, where `66h` and `67h` make an
+/// instruction use 32-bit registers or addressing. The listing writes
+/// these with 32-bit register names, which is easy to miss, and on an 8086
+/// or 286 the prefixes are not prefixes at all.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::overrides::{SizeOverride, find_size_overrides};
+///
+/// // inc eax / ret
+/// let found = find_size_overrides(&Disassembler::new(vec![0x66, 0x40, 0xC3]));
+/// assert_eq!(found[0].address, 0x100);
+/// assert_eq!(found[0].overrides, [SizeOverride::Operand]);
+/// ```
+pub fn find_size_overrides(disassembler: &Disassembler) -> Vec<WideInstruction> {
+    let mut found = Vec::new();
+    for instruction in disassembler.code() {
+        if is_data(instruction) {
+            continue;
+        }
+        let Some(bytes) = disassembler.original_bytes(instruction) else {
+            continue;
+        };
+        let mut overrides = Vec::new();
+        for &byte in bytes.iter().take_while(|byte| PREFIXES.contains(byte)) {
+            let size = match byte {
+                0x66 => SizeOverride::Operand,
+                0x67 => SizeOverride::Address,
+                _ => continue,
+            };
+            if !overrides.contains(&size) {
+                overrides.push(size);
+            }
+        }
+        if !overrides.is_empty() {
+            found.push(WideInstruction {
+                address: instruction.ip() as Address,
+                overrides,
+            });
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_prefixes_are_found_and_commented() {
+        // mov ax, [ebx] / mov eax, [ebx] / mov ax, bx / ret
+        let data = vec![0x67, 0x8B, 0x03, 0x66, 0x67, 0x8B, 0x03, 0x89, 0xD8, 0xC3];
+        let disassembler = Disassembler::new(data);
+        let found = find_size_overrides(&disassembler);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].overrides, [SizeOverride::Address]);
+        assert_eq!(
+            found[1].overrides,
+            [SizeOverride::Operand, SizeOverride::Address]
+        );
+
+        let listing = disassembler.to_string();
+        assert!(
+            listing.contains("mov eax,[ebx] ; 32-bit operands (0x66 prefix) and 32-bit addressing"),
+            "{listing}"
+        );
+    }
+
+    #[test]
+    fn strings_are_not_instructions() {
+        // mov dx, 0x108 / mov ah, 9 / int 21h / ret / "fg$"
+        let data = vec![
+            0xBA, 0x08, 0x01, 0xB4, 0x09, 0xCD, 0x21, 0xC3, b'f', b'g', b'$',
+        ];
+        assert!(find_size_overrides(&Disassembler::new(data)).is_empty());
+    }
+}
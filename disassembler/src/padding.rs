@@ -0,0 +1,204 @@
+use iced_x86::{FlowControl, Mnemonic};
+
+use crate::consts::{Address, AddressExt};
+use crate::disassemble::{Disassembler, is_data};
+use crate::style::Style;
+
+/// The bytes compilers and assemblers pad with: `nop`, zero and `int3`
+const FILLERS: [u8; 3] = [0x90, 0x00, 0xCC];
+
+/// The alignments tried, largest first
+const ALIGNMENTS: [u32; 4] = [16, 8, 4, 2];
+
+/// Filler bytes between the end of one piece of code or data and the start
+/// of the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Padding {
+    /// The first filler byte
+    pub start: Address,
+    /// The labelled address the padding runs up to
+    pub end: Address,
+    /// The byte repeated
+    pub fill: u8,
+}
+
+impl Padding {
+    /// Finds the padding in front of labels
+    ///
+    /// Padding is a run of one filler byte, listed as `nop` or `int3`
+    /// instructions or as data, that follows a return, an unconditional
+    /// jump or data and runs up to a label, with nothing named or commented
+    /// inside it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::padding::Padding;
+    ///
+    /// // call 0x110 / ret / nop × 12 / 0x110: ret
+    /// let mut data = vec![0xE8, 0x0D, 0x00, 0xC3];
+    /// data.extend([0x90; 12]);
+    /// data.push(0xC3);
+    ///
+    /// let padding = Padding::detect(&Disassembler::new(data));
+    /// assert_eq!((padding[0].start, padding[0].end), (0x104, 0x110));
+    /// assert_eq!(padding[0].alignment(), Some(16));
+    /// ```
+    pub fn detect(disassembler: &Disassembler) -> Vec<Padding> {
+        let instructions = &disassembler.instructions.0;
+        let mut found = Vec::new();
+        for label in &disassembler.labels.0 {
+            let Some(last) = disassembler.instructions.index_at(label.address) else {
+                continue;
+            };
+            let Some(&fill) = label
+                .address
+                .checked_sub(1)
+                .and_then(|address| disassembler.bytes_at(address)?.first())
+                .filter(|fill| FILLERS.contains(fill))
+            else {
+                continue;
+            };
+            let is_fill = |index: usize| {
+                let row = &instructions[index];
+                let filled = disassembler
+                    .original_bytes(row)
+                    .is_some_and(|bytes| bytes.iter().all(|&byte| byte == fill));
+                let listed = is_data(row)
+                    || (row.len() == 1 && matches!(row.mnemonic(), Mnemonic::Nop | Mnemonic::Int3));
+                filled && listed
+            };
+            let first = (0..last)
+                .rev()
+                .take_while(|&index| is_fill(index))
+                .last()
+                .unwrap_or(last);
+            if first == 0 || first == last {
+                continue;
+            }
+            let before = &instructions[first - 1];
+            let ends_flow = is_data(before)
+                || matches!(
+                    before.flow_control(),
+                    FlowControl::Return | FlowControl::UnconditionalBranch
+                );
+            let start = instructions[first].ip() as Address;
+            let quiet = (start..label.address).all(|address| {
+                disassembler.labels.get_by_address(address).is_none()
+                    && disassembler.comment_list.get_comments(address).is_empty()
+            });
+            let padding = Padding {
+                start,
+                end: label.address,
+                fill,
+            };
+            if ends_flow && quiet && (padding.alignment().is_some() || padding.len() > 1) {
+                found.push(padding);
+            }
+        }
+        found.sort_by_key(|padding| padding.start);
+        found.dedup();
+        found
+    }
+
+    /// The number of filler bytes
+    pub fn len(&self) -> usize {
+        (self.end - self.start) as usize
+    }
+
+    /// Returns true if there are no filler bytes
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The largest alignment, up to 16, that the padding is exactly what
+    /// NASM's `align` adds to reach
+    ///
+    /// The listing starts at a multiple of 16, so the alignment holds for
+    /// `$` whether or not it is assembled with `org 0x100`.
+    pub fn alignment(&self) -> Option<u32> {
+        ALIGNMENTS.into_iter().find(|&alignment| {
+            (self.end as u32).is_multiple_of(alignment) && (self.len() as u32) < alignment
+        })
+    }
+
+    /// The directive making the same bytes, written in `style`: `align` if
+    /// the padding aligns what follows it and `times` otherwise
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::padding::Padding;
+    /// use disassembler::style::Style;
+    ///
+    /// let style = Style::default();
+    /// let nops = Padding { start: 0x10D, end: 0x110, fill: 0x90 };
+    /// assert_eq!(nops.directive(&style), "align 16");
+    /// let zeros = Padding { start: 0x103, end: 0x104, fill: 0x00 };
+    /// assert_eq!(zeros.directive(&style), "align 4, db 0x00");
+    /// let odd = Padding { start: 0x104, end: 0x109, fill: 0xCC };
+    /// assert_eq!(odd.directive(&style), "times 5 db 0xcc");
+    /// ```
+    pub fn directive(&self, style: &Style) -> String {
+        let fill = format!(
+            "{} {}",
+            style.mnemonic("db"),
+            style.hex(self.fill as u64, 2)
+        );
+        match self.alignment() {
+            // nop is what align fills with unless told otherwise
+            Some(alignment) if self.fill == 0x90 => {
+                format!("{} {alignment}", style.mnemonic("align"))
+            }
+            Some(alignment) => format!("{} {alignment}, {fill}", style.mnemonic("align")),
+            None => format!("{} {} {fill}", style.mnemonic("times"), self.len()),
+        }
+    }
+
+    /// The padding's bytes in the file
+    pub fn bytes<'a>(&self, disassembler: &'a Disassembler) -> &'a [u8] {
+        self.start
+            .file_offset()
+            .and_then(|start| disassembler.data.get(start..start + self.len()))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassemble::DisassemblerOptions;
+
+    #[test]
+    fn padding_is_listed_as_directives_unless_literal() {
+        // call 0x108 / ret / nop × 4 / 0x108: ret
+        let data = vec![0xE8, 0x05, 0x00, 0xC3, 0x90, 0x90, 0x90, 0x90, 0xC3];
+        let disassembler = Disassembler::new(data);
+        let padding = Padding::detect(&disassembler);
+        assert_eq!(padding.len(), 1);
+        assert_eq!(padding[0].directive(&Style::default()), "align 8");
+        assert_eq!(padding[0].bytes(&disassembler), [0x90; 4]);
+
+        let listing = disassembler.to_string();
+        assert!(listing.contains("align 8"), "{listing}");
+        assert!(!listing.contains("nop"), "{listing}");
+
+        let mut literal = Vec::new();
+        let opts = DisassemblerOptions {
+            padding_directives: false,
+            ..DisassemblerOptions::default()
+        };
+        disassembler.disassemble_stream(&mut literal, opts).unwrap();
+        let literal = String::from_utf8(literal).unwrap();
+        assert_eq!(literal.matches("nop").count(), 4, "{literal}");
+    }
+
+    #[test]
+    fn code_running_into_a_label_is_not_padding() {
+        // call 0x104 / nop / 0x104: ret, where the nop runs on into the function
+        let data = vec![0xE8, 0x01, 0x00, 0x90, 0xC3];
+        assert!(Padding::detect(&Disassembler::new(data)).is_empty());
+    }
+}
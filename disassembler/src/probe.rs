@@ -0,0 +1,457 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use iced_x86::{Instruction, Mnemonic, OpKind};
+
+use crate::comment::{Comment, CommentSource, CommentType};
+use crate::consts::Address;
+use crate::disassemble::Disassembler;
+use crate::emulate::{Emulator, Registers};
+use crate::rules::{RuleError, parse_blocks, parse_number, unquote};
+
+/// The emulator event a probe waits for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Trigger {
+    /// The instruction at this address is about to run
+    Break(Address),
+    /// The program is about to call this interrupt, with this AH if given
+    Interrupt {
+        /// The interrupt number
+        vector: u8,
+        /// The function number in AH, or `None` for any
+        ah: Option<u8>,
+    },
+    /// An instruction of the program wrote memory from `start` up to `end`
+    Write {
+        /// The first address watched
+        start: Address,
+        /// The address just past the watched range
+        end: Address,
+    },
+}
+
+impl Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::Break(address) => write!(f, "break at 0x{address:04x}"),
+            Trigger::Interrupt { vector, ah: None } => write!(f, "int {vector:02X}h"),
+            Trigger::Interrupt {
+                vector,
+                ah: Some(ah),
+            } => write!(f, "int {vector:02X}h/{ah:02X}h"),
+            Trigger::Write { start, end } => {
+                write!(f, "write to 0x{start:04x}-0x{end:04x}")
+            }
+        }
+    }
+}
+
+/// An action to take in the emulator whenever an event happens: log a
+/// line, change registers or comment the instruction
+///
+/// `log` and `comment` may name registers in braces, such as `{ax}` or
+/// `{dl}`, replaced by their values at the time, and `{address}`, the
+/// address written for write probes or the instruction's otherwise. They
+/// are filled in after `set` is applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Probe {
+    /// The probe's name, shown as the source of its comments
+    pub name: String,
+    /// When the probe fires
+    pub trigger: Trigger,
+    /// A line to log each time
+    pub log: Option<String>,
+    /// Registers to set each time, by name; unknown names are skipped
+    pub set: Vec<(String, u16)>,
+    /// A comment for the instruction
+    pub comment: Option<String>,
+}
+
+impl Probe {
+    /// Whether the probe fires before `instruction` runs
+    pub(crate) fn fires_before(&self, instruction: &Instruction, registers: &Registers) -> bool {
+        match self.trigger {
+            Trigger::Break(address) => registers.ip == address,
+            Trigger::Interrupt { vector, ah } => {
+                instruction.mnemonic() == Mnemonic::Int
+                    && instruction.op0_kind() == OpKind::Immediate8
+                    && instruction.immediate8() == vector
+                    && ah.is_none_or(|ah| (registers.ax >> 8) as u8 == ah)
+            }
+            Trigger::Write { .. } => false,
+        }
+    }
+
+    /// Whether the probe fires after an instruction wrote `size` bytes at
+    /// `address`
+    pub(crate) fn fires_on_write(&self, address: Address, size: usize) -> bool {
+        match self.trigger {
+            Trigger::Write { start, end } => {
+                (address as usize) < end as usize && (start as usize) < address as usize + size
+            }
+            _ => false,
+        }
+    }
+
+    /// Takes the probe's actions for the instruction at `instruction`,
+    /// where `address` is the memory written, if any
+    pub(crate) fn fire(&self, emulator: &mut Emulator, instruction: Address, address: Address) {
+        for (register, value) in &self.set {
+            let _ = emulator.registers.set(register, *value);
+        }
+        let text = |template: &Option<String>| {
+            template.as_ref().map(|template| {
+                expand(template, &emulator.registers, address).unwrap_or(template.clone())
+            })
+        };
+        let hit = ProbeHit {
+            probe: self.name.clone(),
+            address: instruction,
+            instructions: emulator.instructions_executed,
+            log: text(&self.log),
+            comment: text(&self.comment),
+        };
+        emulator.trace.probe_hits.push(hit);
+    }
+}
+
+/// One time a probe fired
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProbeHit {
+    /// The probe's name
+    pub probe: String,
+    /// The instruction the probe fired at
+    pub address: Address,
+    /// How many instructions had run
+    pub instructions: u64,
+    /// The line logged, with the values filled in
+    pub log: Option<String>,
+    /// The comment, with the values filled in
+    pub comment: Option<String>,
+}
+
+impl Display for ProbeHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at 0x{:04x}", self.probe, self.address)?;
+        if let Some(log) = &self.log {
+            write!(f, ": {log}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Probes read from a probe file
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProbeSet(pub Vec<Probe>);
+
+impl ProbeSet {
+    /// Parses probe files, in the block syntax of rule files
+    ///
+    /// Each probe has one trigger: `break = ADDRESS`, `interrupt = 21h` with
+    /// an optional ` ah=3Dh`, or `write = START-END` (or a single address);
+    /// and any of `log`, `comment` and `set = ax=1, cx=0x10`.
+    ///
+    /// ```text
+    /// probe password_check {
+    ///     break = 0x0142
+    ///     log = "comparing {al} with {dl}"
+    ///     set = ax=0
+    /// }
+    /// ```
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::emulate::Emulator;
+    /// use disassembler::probe::ProbeSet;
+    ///
+    /// let probes = ProbeSet::parse("probe exit {\n interrupt = 21h ah=4Ch\n log = \"code {al}\"\n}");
+    /// // mov ax, 0x4c03 / int 21h
+    /// let mut emulator = Emulator::new(&[0xB8, 0x03, 0x4C, 0xCD, 0x21]);
+    /// for probe in probes.unwrap().0 {
+    ///     emulator.probe(probe);
+    /// }
+    /// emulator.run(100);
+    /// assert_eq!(emulator.trace.probe_hits[0].to_string(), "exit at 0x0103: code 0x03");
+    /// ```
+    pub fn parse(text: &str) -> Result<ProbeSet, RuleError> {
+        parse_blocks(text, "probe")?
+            .into_iter()
+            .map(|block| {
+                let line = block.line;
+                let name = block.name;
+                let error = |message: String| RuleError { line, message };
+                let mut trigger = None;
+                let mut log = None;
+                let mut set = Vec::new();
+                let mut comment = None;
+                for (key, value) in block.keys {
+                    let address = |value: &str| {
+                        parse_number(value)
+                            .and_then(|number| Address::try_from(number).ok())
+                            .ok_or_else(|| error(format!("probe {name}: invalid {key} `{value}`")))
+                    };
+                    let parsed = match key.as_str() {
+                        "break" => Trigger::Break(address(&value)?),
+                        "interrupt" => {
+                            let (vector, ah) = match value.split_once(' ') {
+                                Some((vector, ah)) => (vector, Some(ah.trim())),
+                                None => (value.as_str(), None),
+                            };
+                            let byte = |value: &str| {
+                                address(value).and_then(|number| {
+                                    u8::try_from(number).map_err(|_| {
+                                        error(format!("probe {name}: invalid {key} `{value}`"))
+                                    })
+                                })
+                            };
+                            let ah = match ah {
+                                Some(ah) => {
+                                    Some(byte(ah.strip_prefix("ah=").ok_or_else(|| {
+                                        error(format!(
+                                            "probe {name}: expected `ah=` after the interrupt"
+                                        ))
+                                    })?)?)
+                                }
+                                None => None,
+                            };
+                            Trigger::Interrupt {
+                                vector: byte(vector)?,
+                                ah,
+                            }
+                        }
+                        "write" => {
+                            let (start, end) = match value.split_once('-') {
+                                Some((start, end)) => {
+                                    (address(start.trim())?, address(end.trim())?)
+                                }
+                                None => {
+                                    let start = address(&value)?;
+                                    (start, start.saturating_add(1))
+                                }
+                            };
+                            if end <= start {
+                                return Err(error(format!(
+                                    "probe {name}: the write range ends before it starts"
+                                )));
+                            }
+                            Trigger::Write { start, end }
+                        }
+                        "log" | "comment" => {
+                            let template = unquote(&value);
+                            expand(&template, &Registers::default(), 0)
+                                .map_err(|err| error(format!("probe {name}: {err}")))?;
+                            if key == "log" {
+                                log = Some(template);
+                            } else {
+                                comment = Some(template);
+                            }
+                            continue;
+                        }
+                        "set" => {
+                            for assignment in value.split(',') {
+                                let (register, number) =
+                                    assignment.trim().split_once('=').ok_or_else(|| {
+                                        error(format!(
+                                            "probe {name}: expected `register=value` in set"
+                                        ))
+                                    })?;
+                                let number = parse_number(number.trim())
+                                    .and_then(|number| u16::try_from(number).ok())
+                                    .ok_or_else(|| {
+                                        error(format!("probe {name}: invalid value `{number}`"))
+                                    })?;
+                                Registers::default()
+                                    .set(register.trim(), number)
+                                    .map_err(|err| error(format!("probe {name}: {err}")))?;
+                                set.push((register.trim().to_ascii_lowercase(), number));
+                            }
+                            continue;
+                        }
+                        other => {
+                            return Err(error(format!("probe {name}: unknown key `{other}`")));
+                        }
+                    };
+                    if trigger.replace(parsed).is_some() {
+                        return Err(error(format!("probe {name} has more than one trigger")));
+                    }
+                }
+                Ok(Probe {
+                    trigger: trigger.ok_or_else(|| {
+                        error(format!(
+                            "probe {name} needs one of `break`, `interrupt` or `write`"
+                        ))
+                    })?,
+                    name,
+                    log,
+                    set,
+                    comment,
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(ProbeSet)
+    }
+}
+
+/// Comments each instruction a probe with a comment fired at, once per probe
+/// and instruction with the first hit's values, as coming from a script
+/// named after the probe
+///
+/// Returns how many comments were added.
+pub fn annotate(disassembler: &mut Disassembler, hits: &[ProbeHit]) -> usize {
+    let mut first: BTreeMap<(Address, &str), (&str, usize)> = BTreeMap::new();
+    for hit in hits {
+        if let Some(comment) = &hit.comment {
+            first
+                .entry((hit.address, hit.probe.as_str()))
+                .or_insert((comment, 0))
+                .1 += 1;
+        }
+    }
+    let added = first.len();
+    for ((address, probe), (comment, count)) in first {
+        let text = if count > 1 {
+            format!("{comment} (hit {count} times)")
+        } else {
+            comment.to_string()
+        };
+        disassembler.comment_list.0.push(
+            Comment::new(CommentType::PRE, text, address)
+                .with_source(CommentSource::Script(probe.to_string())),
+        );
+    }
+    added
+}
+
+/// Fills in the `{register}` and `{address}` placeholders in `template`
+fn expand(template: &str, registers: &Registers, address: Address) -> Result<String, String> {
+    let mut text = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        text.push_str(&rest[..open]);
+        let close = rest[open..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed `{{` in `{template}`"))?;
+        let name = rest[open + 1..open + close].to_ascii_lowercase();
+        let value = match name.as_str() {
+            "address" => format!("0x{address:04x}"),
+            _ => register_value(registers, &name)
+                .ok_or_else(|| format!("unknown value `{{{name}}}`"))?,
+        };
+        text.push_str(&value);
+        rest = &rest[open + close + 1..];
+    }
+    text.push_str(rest);
+    Ok(text)
+}
+
+/// The value of the 8- or 16-bit register called `name`, in hex
+fn register_value(registers: &Registers, name: &str) -> Option<String> {
+    let half = |word: u16, high: bool| {
+        let byte = if high { word >> 8 } else { word & 0xFF };
+        Some(format!("0x{byte:02x}"))
+    };
+    match name {
+        "al" => half(registers.ax, false),
+        "ah" => half(registers.ax, true),
+        "bl" => half(registers.bx, false),
+        "bh" => half(registers.bx, true),
+        "cl" => half(registers.cx, false),
+        "ch" => half(registers.cx, true),
+        "dl" => half(registers.dx, false),
+        "dh" => half(registers.dx, true),
+        _ => registers
+            .get(name)
+            .ok()
+            .map(|value| format!("0x{value:04x}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Parsing
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn probe_files_parse_every_trigger() {
+        let text = "probe a {\n break = 0x120\n set = ax=1, CX=0x10\n}\n\
+                    probe b {\n interrupt = 16h\n}\n\
+                    probe c {\n write = 0x200-0x210\n comment = \"at {address}\"\n}\n";
+        let probes = ProbeSet::parse(text).unwrap().0;
+        assert_eq!(probes[0].trigger, Trigger::Break(0x120));
+        assert_eq!(
+            probes[0].set,
+            [("ax".to_string(), 1), ("cx".to_string(), 0x10)]
+        );
+        assert_eq!(
+            probes[1].trigger,
+            Trigger::Interrupt {
+                vector: 0x16,
+                ah: None
+            }
+        );
+        assert_eq!(
+            probes[2].trigger,
+            Trigger::Write {
+                start: 0x200,
+                end: 0x210
+            }
+        );
+    }
+
+    #[test]
+    fn mistakes_are_reported_with_their_line() {
+        let error = |text: &str| ProbeSet::parse(text).unwrap_err().to_string();
+        assert_eq!(
+            error("probe a {\n log = \"x\"\n}"),
+            "line 1: probe a needs one of `break`, `interrupt` or `write`"
+        );
+        assert_eq!(
+            error("probe a {\n break = 0x100\n log = \"{zz}\"\n}"),
+            "line 1: probe a: unknown value `{zz}`"
+        );
+        assert_eq!(
+            error("probe a {\n break = 0x100\n set = ip\n}"),
+            "line 1: probe a: expected `register=value` in set"
+        );
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Firing and annotating
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn writes_fire_after_the_instruction_and_comment_it_once() {
+        let data = vec![
+            0xB9, 0x03, 0x00, // mov cx, 3
+            0xBF, 0x00, 0x02, // mov di, 0x200
+            0xAA, // 0x106: stosb
+            0xE2, 0xFD, // loop 0x106
+            0xC3, // ret
+        ];
+        let text = "probe fill {\n write = 0x201\n log = \"{address} from {di}\"\n \
+                    comment = \"fills {address}\"\n}";
+        let mut emulator = Emulator::new(&data);
+        for probe in ProbeSet::parse(text).unwrap().0 {
+            emulator.probe(probe);
+        }
+        emulator.run(100);
+        let hits = &emulator.trace.probe_hits;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].to_string(), "fill at 0x0106: 0x0201 from 0x0202");
+
+        let mut disassembler = Disassembler::new(data);
+        let twice = [hits[0].clone(), hits[0].clone()];
+        assert_eq!(annotate(&mut disassembler, &twice), 1);
+        let comment = disassembler.comment_list.0.last().unwrap();
+        assert_eq!(
+            comment.with_provenance(),
+            "; [script: fill] fills 0x0201 (hit 2 times)"
+        );
+    }
+}
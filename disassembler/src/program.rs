@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+
+use iced_x86::{Instruction, Mnemonic};
+
+use crate::bss::Reservation;
+use crate::comment::Comment;
+use crate::consts::{Address, COM_OFFSET};
+use crate::disassemble::{Disassembler, is_data, operand_addresses};
+use crate::label::Label;
+use crate::region::NamedRegion;
+use crate::session::AuxFile;
+use crate::string::StringConstant;
+use crate::table::ElementSize;
+
+/// What one item of a program is
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ItemKind {
+    /// An instruction, as decoded
+    Instruction(Instruction),
+    /// Bytes listed as `db`, `dw` or `dd` rows
+    Data {
+        /// The size of the elements the bytes are listed in
+        element: ElementSize,
+    },
+    /// A string constant
+    String(StringConstant),
+}
+
+/// A range of the program's address space and what it holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Item {
+    /// The first address of the item
+    pub start: Address,
+    /// The address just past the item, which may be 0x10000
+    pub end: u32,
+    /// What the item is
+    pub kind: ItemKind,
+}
+
+/// A read-only view of what analysis found in a program, for exporters
+///
+/// The [`Disassembler`] keeps its findings in separate lists shaped by how
+/// the passes work: instructions and data rows in one list, strings laid
+/// over them in another, references recomputed on each query. A `Program`
+/// is the same result in one stable shape:
+///
+/// - [`Program::items`] covers the program's bytes in address order, each
+///   byte in exactly one item. A string takes the place of the data rows it
+///   covers, so a listing need not check both.
+/// - [`Program::symbols`], [`Program::comments`] and [`Program::regions`]
+///   are sorted by address, with the same tie-breaking as the listing.
+/// - [`Program::xrefs`] maps each address code refers to, as a jump or call
+///   target, an immediate or a memory operand, to the instructions referring
+///   to it, in address order.
+///
+/// Building a `Program` does no analysis, and changing one does not change
+/// the disassembler it came from. New fields may be added, but what the
+/// existing ones mean will not change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Program {
+    /// The program's bytes, loaded at [`COM_OFFSET`]
+    pub bytes: Vec<u8>,
+    /// Companion files mapped into the address space
+    pub aux_files: Vec<AuxFile>,
+    /// Memory past the end of the file the program uses
+    pub reservations: Vec<Reservation>,
+    /// What each range of the program is, in address order
+    pub items: Vec<Item>,
+    /// The names of addresses
+    pub symbols: Vec<Label>,
+    /// Comments, from the analysis and the user
+    pub comments: Vec<Comment>,
+    /// Named regions
+    pub regions: Vec<NamedRegion>,
+    /// The instructions referring to each address
+    pub xrefs: BTreeMap<Address, Vec<Address>>,
+}
+
+impl Program {
+    /// Collects what `disassembler` found into a program model
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::program::{ItemKind, Program};
+    ///
+    /// // mov dx, 0x109 / mov ah, 9 / int 21h / ret / "Hi$"
+    /// let data = vec![0xBA, 0x09, 0x01, 0xB4, 0x09, 0xCD, 0x21, 0xC3, 0x90, b'H', b'i', b'$'];
+    /// let program = Program::build(&Disassembler::new(data));
+    ///
+    /// let last = program.items.last().unwrap();
+    /// assert!(matches!(&last.kind, ItemKind::String(string) if string.value == "Hi$"));
+    /// assert_eq!((last.start, last.end), (0x109, 0x10C));
+    /// assert_eq!(program.xrefs[&0x109], [0x100]);
+    /// ```
+    pub fn build(disassembler: &Disassembler) -> Program {
+        let strings = &disassembler.string_constant_list.0;
+        let mut items: Vec<Item> = Vec::new();
+        for instruction in &disassembler.instructions.0 {
+            let start = instruction.ip() as Address;
+            let end = instruction.next_ip() as u32;
+            let overlapping = |from: u32| {
+                strings
+                    .iter()
+                    .filter(move |string| {
+                        string.start < string.end
+                            && string.end as u32 > from
+                            && (string.start as u32) < end
+                    })
+                    .min_by_key(|string| string.start)
+            };
+            // an item may already cover the start, when a string ran into it
+            let mut cursor = (start as u32).max(items.last().map_or(0, |item| item.end));
+            if cursor == start as u32 && overlapping(cursor).is_none() {
+                let kind = if is_data(instruction) {
+                    ItemKind::Data {
+                        element: element_of(instruction),
+                    }
+                } else {
+                    ItemKind::Instruction(*instruction)
+                };
+                items.push(Item { start, end, kind });
+                continue;
+            }
+            // strings are cut out of the instructions and rows they lie across,
+            // as code inside a string is left out of references; what is left
+            // is listed as bytes
+            let element = element_of(instruction);
+            while cursor < end {
+                let Some(string) = overlapping(cursor) else {
+                    push_data(&mut items, cursor, end, element);
+                    break;
+                };
+                let string_start = (string.start as u32).max(cursor);
+                push_data(&mut items, cursor, string_start, element);
+                items.push(Item {
+                    start: string_start as Address,
+                    end: string.end as u32,
+                    kind: ItemKind::String(string.clone()),
+                });
+                cursor = string.end as u32;
+            }
+        }
+
+        let mut xrefs: BTreeMap<Address, Vec<Address>> = BTreeMap::new();
+        for item in &items {
+            if let ItemKind::Instruction(instruction) = &item.kind {
+                for target in operand_addresses(instruction) {
+                    if let Ok(target) = Address::try_from(target) {
+                        xrefs.entry(target).or_default().push(item.start);
+                    }
+                }
+            }
+        }
+        for references in xrefs.values_mut() {
+            references.dedup();
+        }
+
+        Program {
+            bytes: disassembler.data.clone(),
+            aux_files: disassembler.aux_files().to_vec(),
+            reservations: disassembler.reservations().to_vec(),
+            items,
+            symbols: disassembler.labels.0.clone(),
+            comments: disassembler.comment_list.0.clone(),
+            regions: disassembler.regions.0.clone(),
+            xrefs,
+        }
+    }
+
+    /// The item containing `address`
+    pub fn item_at(&self, address: Address) -> Option<&Item> {
+        let index = self.items.partition_point(|item| item.start <= address);
+        index
+            .checked_sub(1)
+            .map(|index| &self.items[index])
+            .filter(|item| (address as u32) < item.end)
+    }
+
+    /// The symbol naming `address`
+    pub fn symbol(&self, address: Address) -> Option<&Label> {
+        self.symbols.iter().find(|label| label.address == address)
+    }
+
+    /// The bytes of `item`, empty for items in companion files
+    pub fn bytes_of(&self, item: &Item) -> &[u8] {
+        let start = (item.start as usize).saturating_sub(COM_OFFSET as usize);
+        let end = (item.end as usize).saturating_sub(COM_OFFSET as usize);
+        self.bytes.get(start..end).unwrap_or_default()
+    }
+}
+
+/// The size of the elements a data row lists, bytes for anything else
+fn element_of(instruction: &Instruction) -> ElementSize {
+    match instruction.mnemonic() {
+        Mnemonic::Dw => ElementSize::Word,
+        Mnemonic::Dd => ElementSize::Dword,
+        _ => ElementSize::Byte,
+    }
+}
+
+/// Adds the data bytes `start..end` to `items`, if there are any
+fn push_data(items: &mut Vec<Item>, start: u32, end: u32, element: ElementSize) {
+    if start < end {
+        items.push(Item {
+            start: start as Address,
+            end,
+            kind: ItemKind::Data { element },
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samples::SAMPLES;
+
+    #[test]
+    fn items_cover_every_byte_once() {
+        for sample in SAMPLES {
+            let disassembler = Disassembler::new(sample.bytes.to_vec());
+            let program = Program::build(&disassembler);
+            let mut cursor = COM_OFFSET as u32;
+            for item in &program.items {
+                assert_eq!(item.start as u32, cursor, "{}: gap or overlap", sample.name);
+                assert!(item.start as u32 <= item.end);
+                cursor = item.end;
+            }
+            assert_eq!(
+                cursor,
+                COM_OFFSET as u32 + sample.bytes.len() as u32,
+                "{}",
+                sample.name
+            );
+        }
+    }
+
+    #[test]
+    fn strings_take_the_place_of_what_they_lie_across() {
+        // mov dx, 0x108 / mov ah, 9 / int 21h / ret / "Hi$" / add [bx+si], al
+        let data = vec![
+            0xBA, 0x08, 0x01, 0xB4, 0x09, 0xCD, 0x21, 0xC3, b'H', b'i', b'$', 0x00, 0x00,
+        ];
+        let disassembler = Disassembler::new(data);
+        let program = Program::build(&disassembler);
+
+        let string = program.item_at(0x109).unwrap();
+        assert_eq!((string.start, string.end), (0x108, 0x10B));
+        assert!(matches!(&string.kind, ItemKind::String(string) if string.value == "Hi$"));
+        assert_eq!(program.bytes_of(string), b"Hi$");
+        let rest = program.item_at(0x10B).unwrap();
+        assert_eq!(rest.start, 0x10B);
+        assert_eq!(program.xrefs[&0x108], [0x100]);
+    }
+}
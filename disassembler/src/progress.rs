@@ -0,0 +1,132 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// How far an analysis has got
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// The sweep has decoded the program up to `bytes` of its `total` bytes
+    Decoded {
+        /// The bytes decoded so far
+        bytes: usize,
+        /// The size of the program
+        total: usize,
+    },
+    /// `done` of the `total` passes over the decoded program have finished
+    Pass {
+        /// The passes finished so far
+        done: usize,
+        /// The passes in one analysis
+        total: usize,
+    },
+    /// [`settle`](crate::fixpoint::settle) found something in run `run` and
+    /// is running the analysis again
+    Rerun {
+        /// The run that found something, counting from 1
+        run: usize,
+    },
+}
+
+/// A callback told how far an analysis has got
+///
+/// The default reports nothing. Reporting never changes the analysis, so
+/// disassemblers compare equal whatever they report to, and the callback is
+/// not serialized.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+///
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::limits::Limits;
+/// use disassembler::progress::{Progress, Reporter};
+///
+/// let seen = Arc::new(Mutex::new(Vec::new()));
+/// let log = seen.clone();
+/// let reporter = Reporter::new(move |progress| log.lock().unwrap().push(progress));
+/// // nop / ret
+/// Disassembler::with_progress(vec![0x90, 0xC3], Limits::default(), reporter);
+///
+/// let seen = seen.lock().unwrap();
+/// assert_eq!(seen[0], Progress::Decoded { bytes: 1, total: 2 });
+/// assert!(matches!(seen.last(), Some(Progress::Pass { done, total }) if done == total));
+/// ```
+#[derive(Clone, Default)]
+pub struct Reporter(Option<Arc<dyn Fn(Progress) + Send + Sync>>);
+
+impl Reporter {
+    /// Reports to `callback`
+    pub fn new(callback: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        Reporter(Some(Arc::new(callback)))
+    }
+
+    /// Tells the callback about `progress`, if there is one
+    pub fn report(&self, progress: Progress) {
+        if let Some(callback) = &self.0 {
+            callback(progress);
+        }
+    }
+}
+
+impl fmt::Debug for Reporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "Reporter(callback)"),
+            None => write!(f, "Reporter(none)"),
+        }
+    }
+}
+
+impl PartialEq for Reporter {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Reporter {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::disassemble::Disassembler;
+    use crate::fixpoint::settle;
+    use crate::limits::Limits;
+
+    #[test]
+    fn settling_reports_each_rerun() {
+        let data = vec![
+            0xEB, 0x03, // jmp 0x105
+            b'H', b'i', b'$', // "Hi$"
+            0xBA, 0x02, 0x01, // mov dx, 0x102
+            0xB4, 0x09, // mov ah, 9
+            0xCD, 0x21, // int 21h
+            0xC3, // ret
+        ];
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let mut disassembler = Disassembler::with_progress(
+            data.clone(),
+            Limits::default(),
+            Reporter::new(move |progress| log.lock().unwrap().push(progress)),
+        );
+        let settled = settle(&mut disassembler);
+
+        let seen = seen.lock().unwrap();
+        let reruns: Vec<_> = seen
+            .iter()
+            .filter(|progress| matches!(progress, Progress::Rerun { .. }))
+            .collect();
+        assert_eq!(reruns.len(), settled.passes - 1);
+        assert!(seen.contains(&Progress::Decoded {
+            bytes: data.len(),
+            total: data.len()
+        }));
+
+        // reporting changes nothing about the result
+        let mut quiet = Disassembler::new(data);
+        settle(&mut quiet);
+        assert_eq!(quiet, disassembler);
+    }
+}
@@ -0,0 +1,351 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{
+    FlowControl, Instruction, InstructionInfoFactory, Mnemonic, OpAccess, OpKind, Register,
+};
+
+use crate::consts::{Address, AddressExt};
+use crate::disassemble::Disassembler;
+
+/// The I/O ports of the programmable interval timer
+const TIMER_PORTS: std::ops::RangeInclusive<u16> = 0x40..=0x43;
+
+/// How far back register values are looked for, in instructions
+const LOOKBACK: usize = 8;
+
+/// How many bytes a loop around a timer query may span
+const TIGHT_LOOP: u64 = 32;
+
+/// The highest sector number on a standard floppy track
+const MAX_STANDARD_SECTOR: u16 = 18;
+
+/// Phrases that appear in prompts for a key disk
+const KEY_DISK_PHRASES: [&str; 6] = [
+    "key disk",
+    "keydisk",
+    "original disk",
+    "master disk",
+    "program disk",
+    "copy protect",
+];
+
+/// The shortest run of printable bytes searched for key disk phrases
+const MIN_STRING: usize = 4;
+
+/// Behaviour typical of copy protection
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ProtectionKind {
+    /// Reads or programs the interval timer directly through `port`
+    TimerPort {
+        /// The timer port accessed
+        port: u16,
+    },
+    /// Queries the BIOS clock with int 1Ah inside a tight loop, as timing checks do
+    TimerLoop,
+    /// Reads a raw floppy sector with int 13h using a sector number outside 1-18
+    RawSectorRead {
+        /// The sector number in CL
+        sector: u16,
+    },
+    /// A string asking for the original or key disk
+    KeyDiskString {
+        /// The text of the string
+        text: String,
+    },
+}
+
+/// Something at `address` that suggests the program is copy protected
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Protection {
+    /// The address of the instruction or string
+    pub address: Address,
+    /// What was found
+    pub kind: ProtectionKind,
+}
+
+impl Display for Protection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "possible copy protection at 0x{:04x}: ", self.address)?;
+        match &self.kind {
+            ProtectionKind::TimerPort { port } => {
+                write!(f, "accesses timer port 0x{port:02x} directly")
+            }
+            ProtectionKind::TimerLoop => write!(f, "queries the clock in a tight loop"),
+            ProtectionKind::RawSectorRead { sector } => {
+                write!(f, "reads nonstandard sector {sector} with int 13h")
+            }
+            ProtectionKind::KeyDiskString { text } => write!(f, "key disk string {text:?}"),
+        }
+    }
+}
+
+/// The value `register` holds before `instructions[index]`, if a `mov` or
+/// `xor` just before it in the same straight-line code sets it
+pub(crate) fn value_before(
+    factory: &mut InstructionInfoFactory,
+    instructions: &[Instruction],
+    index: usize,
+    register: Register,
+) -> Option<u16> {
+    let full = register.full_register();
+    for instruction in instructions[index.saturating_sub(LOOKBACK)..index]
+        .iter()
+        .rev()
+    {
+        if instruction.flow_control() != FlowControl::Next {
+            return None;
+        }
+        let destination = instruction.op0_register();
+        let sets =
+            instruction.op0_kind() == OpKind::Register && destination.full_register() == full;
+        let immediate = matches!(
+            instruction.op1_kind(),
+            OpKind::Immediate8 | OpKind::Immediate16
+        );
+        let zeroes = instruction.mnemonic() == Mnemonic::Xor
+            && instruction.op1_kind() == OpKind::Register
+            && instruction.op1_register() == destination;
+        if sets && ((instruction.mnemonic() == Mnemonic::Mov && immediate) || zeroes) {
+            let value = if zeroes {
+                0
+            } else {
+                instruction.immediate(1) as u16
+            };
+            if destination == register {
+                return Some(value);
+            }
+            if destination.size() == 2 && register.size() == 1 {
+                return Some(if is_high(register) {
+                    value >> 8
+                } else {
+                    value & 0xFF
+                });
+            }
+            if destination.size() == 1 && register.size() == 1 {
+                // the other half of the register was set
+                continue;
+            }
+            return None;
+        }
+        let written = factory
+            .info(instruction)
+            .used_registers()
+            .iter()
+            .any(|used| {
+                used.register().full_register() == full
+                    && !matches!(
+                        used.access(),
+                        OpAccess::Read | OpAccess::CondRead | OpAccess::None
+                    )
+            });
+        if written {
+            return None;
+        }
+    }
+    None
+}
+
+/// Returns true for the high byte registers
+fn is_high(register: Register) -> bool {
+    matches!(
+        register,
+        Register::AH | Register::BH | Register::CH | Register::DH
+    )
+}
+
+/// The interrupt number of an `int imm8` instruction
+fn interrupt(instruction: &Instruction) -> Option<u8> {
+    (instruction.mnemonic() == Mnemonic::Int && instruction.op0_kind() == OpKind::Immediate8)
+        .then(|| instruction.immediate8())
+}
+
+/// The port an `in` or `out` accesses, if it is known
+fn port(
+    factory: &mut InstructionInfoFactory,
+    instructions: &[Instruction],
+    index: usize,
+) -> Option<u16> {
+    let instruction = &instructions[index];
+    let operand = match instruction.mnemonic() {
+        Mnemonic::In => 1,
+        Mnemonic::Out => 0,
+        _ => return None,
+    };
+    match instruction.op_kind(operand) {
+        OpKind::Immediate8 => Some(instruction.immediate8() as u16),
+        OpKind::Register => value_before(factory, instructions, index, Register::DX),
+        _ => None,
+    }
+}
+
+/// Returns true if a backward branch within a few bytes after `instructions[index]`
+/// jumps to just before or at it
+fn in_tight_loop(instructions: &[Instruction], index: usize) -> bool {
+    let address = instructions[index].ip();
+    instructions[index..]
+        .iter()
+        .take_while(|instruction| instruction.ip() <= address + TIGHT_LOOP)
+        .filter(|instruction| {
+            matches!(
+                instruction.flow_control(),
+                FlowControl::UnconditionalBranch | FlowControl::ConditionalBranch
+            ) && instruction.op0_kind() == OpKind::NearBranch16
+        })
+        .any(|instruction| {
+            let target = instruction.near_branch_target();
+            target <= address && address - target <= TIGHT_LOOP
+        })
+}
+
+/// Finds runs of printable bytes containing a key disk phrase
+fn key_disk_strings(data: &[u8]) -> Vec<Protection> {
+    let mut found = Vec::new();
+    let mut start = 0;
+    for (offset, &byte) in data.iter().chain(std::iter::once(&0)).enumerate() {
+        if (0x20..0x7F).contains(&byte) {
+            continue;
+        }
+        let run = &data[start..offset];
+        if run.len() >= MIN_STRING {
+            let text = String::from_utf8_lossy(run).into_owned();
+            let lower = text.to_ascii_lowercase();
+            if KEY_DISK_PHRASES.iter().any(|phrase| lower.contains(phrase))
+                && let Some(address) = Address::from_file_offset(start)
+            {
+                found.push(Protection {
+                    address,
+                    kind: ProtectionKind::KeyDiskString { text },
+                });
+            }
+        }
+        start = offset + 1;
+    }
+    found
+}
+
+/// Finds behaviour typical of copy protection
+///
+/// Flags direct access to the interval timer ports 40h-43h, int 1Ah clock
+/// queries in tight loops, int 13h reads (AH=02h) of sectors outside the
+/// standard 1-18, and strings asking for a key or original disk. Any of
+/// these also has innocent uses, so each is only a hint for a closer look.
+/// Findings are ordered by address.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::protection::{find_protection, ProtectionKind};
+///
+/// // in al, 0x40 / ret
+/// let found = find_protection(&Disassembler::new(vec![0xE4, 0x40, 0xC3]));
+/// assert_eq!(found[0].kind, ProtectionKind::TimerPort { port: 0x40 });
+/// ```
+pub fn find_protection(disassembler: &Disassembler) -> Vec<Protection> {
+    let instructions = &disassembler.instructions.0;
+    let mut factory = InstructionInfoFactory::new();
+    let mut found = Vec::new();
+    for (index, instruction) in instructions.iter().enumerate() {
+        let address = instruction.ip() as Address;
+        if let Some(port) = port(&mut factory, instructions, index)
+            && TIMER_PORTS.contains(&port)
+        {
+            found.push(Protection {
+                address,
+                kind: ProtectionKind::TimerPort { port },
+            });
+        }
+        match interrupt(instruction) {
+            Some(0x1A) if in_tight_loop(instructions, index) => found.push(Protection {
+                address,
+                kind: ProtectionKind::TimerLoop,
+            }),
+            Some(0x13)
+                if value_before(&mut factory, instructions, index, Register::AH) == Some(0x02) =>
+            {
+                if let Some(sector) = value_before(&mut factory, instructions, index, Register::CL)
+                    .map(|cl| cl & 0x3F)
+                    .filter(|&sector| sector == 0 || sector > MAX_STANDARD_SECTOR)
+                {
+                    found.push(Protection {
+                        address,
+                        kind: ProtectionKind::RawSectorRead { sector },
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    found.extend(key_disk_strings(&disassembler.data));
+    found.sort_by_key(|protection| protection.address);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(data: Vec<u8>) -> Vec<(Address, ProtectionKind)> {
+        find_protection(&Disassembler::new(data))
+            .into_iter()
+            .map(|protection| (protection.address, protection.kind))
+            .collect()
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Timing
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn timer_port_through_dx_is_found() {
+        // mov dx, 0x43 / out dx, al / mov dx, 0x3f8 / in al, dx / ret
+        let found = kinds(vec![0xBA, 0x43, 0x00, 0xEE, 0xBA, 0xF8, 0x03, 0xEC, 0xC3]);
+        assert_eq!(
+            found,
+            vec![(0x103, ProtectionKind::TimerPort { port: 0x43 })]
+        );
+    }
+
+    #[test]
+    fn clock_query_in_a_loop_is_found() {
+        // xor ah, ah / int 1Ah / cmp dx, bx / jb 0x100 / int 1Ah / ret
+        let found = kinds(vec![
+            0x30, 0xE4, 0xCD, 0x1A, 0x39, 0xDA, 0x72, 0xF8, 0xCD, 0x1A, 0xC3,
+        ]);
+        assert_eq!(found, vec![(0x102, ProtectionKind::TimerLoop)]);
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Disks
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn nonstandard_sector_reads_are_found() {
+        // mov ax, 0x0201 / mov cx, 0x2701 (track 39, sector 1) / int 13h
+        // mov ah, 2 / mov cl, 0x25 (sector 37) / int 13h / ret
+        let found = kinds(vec![
+            0xB8, 0x01, 0x02, 0xB9, 0x01, 0x27, 0xCD, 0x13, 0xB4, 0x02, 0xB1, 0x25, 0xCD, 0x13,
+            0xC3,
+        ]);
+        assert_eq!(
+            found,
+            vec![(0x10C, ProtectionKind::RawSectorRead { sector: 0x25 })]
+        );
+    }
+
+    #[test]
+    fn key_disk_prompts_are_found() {
+        let mut data = vec![0xC3];
+        data.extend(b"Insert KEY DISK in drive A$");
+        data.extend(b"\0ok$");
+        assert_eq!(
+            kinds(data),
+            vec![(
+                0x101,
+                ProtectionKind::KeyDiskString {
+                    text: "Insert KEY DISK in drive A$".to_string()
+                }
+            )]
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt::{self, Display};
+
+use iced_x86::Instruction;
+
+use crate::bss::Reservation;
+use crate::comment::Comment;
+use crate::consts::Address;
+use crate::label::Label;
+use crate::string::StringConstant;
+
+/// What the byte at an address is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AddressKind {
+    /// Part of an instruction
+    Code,
+    /// Part of a `db` or `dw` row
+    Data,
+    /// Part of a string constant
+    String,
+    /// Filler bytes that nothing runs or refers to
+    Padding,
+    /// Memory past the end of the file the program uses
+    Uninitialized,
+    /// Outside the program and anything it uses
+    Unmapped,
+}
+
+impl Display for AddressKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressKind::Code => write!(f, "code"),
+            AddressKind::Data => write!(f, "data"),
+            AddressKind::String => write!(f, "string"),
+            AddressKind::Padding => write!(f, "padding"),
+            AddressKind::Uninitialized => write!(f, "uninitialized"),
+            AddressKind::Unmapped => write!(f, "unmapped"),
+        }
+    }
+}
+
+/// Everything known about an address, returned by
+/// [`Disassembler::query`](crate::disassemble::Disassembler::query)
+///
+/// The label, comments and references belong to the item containing the
+/// address, so querying any byte of an instruction describes the whole
+/// instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AddressInfo {
+    /// The address asked about
+    pub address: Address,
+    /// What is at the address
+    pub kind: AddressKind,
+    /// The address the containing item starts at
+    pub start: Address,
+    /// The instruction or data row containing the address
+    pub instruction: Option<Instruction>,
+    /// The string constant containing the address
+    pub string: Option<StringConstant>,
+    /// The reserved memory containing the address
+    pub reservation: Option<Reservation>,
+    /// The label at the start of the item
+    pub label: Option<Label>,
+    /// The comments at the start of the item
+    pub comments: Vec<Comment>,
+    /// The instructions referring to the start of the item
+    pub references: Vec<Address>,
+    /// The instructions an emulator run saw using a watched buffer holding
+    /// the address
+    pub dynamic_references: Vec<Address>,
+}
+
+impl Display for AddressInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:04x}: {}", self.address, self.kind)?;
+        if self.start != self.address {
+            write!(f, " starting at 0x{:04x}", self.start)?;
+        }
+        if let Some(label) = &self.label {
+            write!(f, " ({})", label.name)?;
+        }
+        if !self.references.is_empty() {
+            let references: Vec<String> = self
+                .references
+                .iter()
+                .map(|address| format!("0x{address:04x}"))
+                .collect();
+            write!(f, ", referenced at {}", references.join(", "))?;
+        }
+        if !self.dynamic_references.is_empty() {
+            let references: Vec<String> = self
+                .dynamic_references
+                .iter()
+                .map(|address| format!("0x{address:04x}"))
+                .collect();
+            write!(f, ", used at run time at {}", references.join(", "))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{Decoder, DecoderOptions, Mnemonic, OpKind};
+
+use crate::consts::{Address, AddressExt, COM_OFFSET, SIZE};
+use crate::emulate::{Emulator, LOAD_SEGMENT, StopReason};
+use crate::signatures::Infection;
+
+/// How many bytes of a moved host must line up before a shift is believed
+const MIN_MATCH: usize = 16;
+
+/// Why the original program could not be recovered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecoveryError {
+    /// The program stopped before control came back to 0x0100
+    NoHandOff(StopReason),
+    /// Control came back to 0x0100 but the original program could not be
+    /// told apart from the virus
+    HostNotFound,
+}
+
+impl Display for RecoveryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecoveryError::NoHandOff(reason) => write!(
+                f,
+                "the program never handed control back to 0x0100: {reason}"
+            ),
+            RecoveryError::HostNotFound => write!(
+                f,
+                "control came back to 0x0100 but the original program was not found in memory"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RecoveryError {}
+
+/// An original program taken back out of an infected one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recovery {
+    /// How the virus was attached
+    pub infection: Infection,
+    /// The first address of the virus in the infected file
+    pub start: Address,
+    /// The address just past the virus in the infected file
+    pub end: Address,
+    /// How many instructions the virus ran before handing control back
+    pub hand_off: u64,
+    /// The original program
+    pub host: Vec<u8>,
+    /// How the infected program stopped after the hand-off
+    pub infected_stop: StopReason,
+    /// How the recovered program stopped when run on its own
+    pub host_stop: StopReason,
+    /// Whether the recovered program printed the same and stopped the same
+    /// way as the infected one did after the hand-off
+    pub verified: bool,
+}
+
+impl Display for Recovery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "virus {} at 0x{:04x}-0x{:04x} handed control back after {} instructions, \
+             leaving a {}-byte original program",
+            self.infection,
+            self.start,
+            self.end,
+            self.hand_off,
+            self.host.len()
+        )
+    }
+}
+
+/// Rebuilds the clean program from a COM file with a simple appending or
+/// prepending virus, by letting the virus repair the host itself
+///
+/// Such a virus has to put the host back before running it: an appender
+/// restores the first bytes it replaced with a jump, a prepender moves the
+/// host down to 0x0100. The program is emulated until control comes back
+/// to 0x0100, and the host is read out of memory there. DOS file calls fail
+/// in the emulator, so the virus cannot spread while it runs.
+///
+/// The recovery is then checked by running the recovered program and the
+/// rest of the infected one: both must print the same and stop the same
+/// way within `budget` instructions.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::recover::recover;
+/// use disassembler::signatures::Infection;
+///
+/// let infected = [
+///     0xE9, 0x02, 0x00, // jmp 0x105, replacing mov ax, 0x4c00
+///     0xCD, 0x21, // the rest of the host: int 21h
+///     0xC7, 0x06, 0x00, 0x01, 0xB8, 0x00, // mov word [0x100], 0x00b8
+///     0xC6, 0x06, 0x02, 0x01, 0x4C, // mov byte [0x102], 0x4c
+///     0xB8, 0x00, 0x01, 0xFF, 0xE0, // mov ax, 0x100 / jmp ax
+/// ];
+/// let recovery = recover(&infected, 1000).unwrap();
+/// assert_eq!(recovery.infection, Infection::Append);
+/// assert_eq!(recovery.host, [0xB8, 0x00, 0x4C, 0xCD, 0x21]);
+/// assert!(recovery.verified);
+/// ```
+pub fn recover(program: &[u8], budget: u64) -> Result<Recovery, RecoveryError> {
+    let mut emulator = Emulator::new(program);
+    while emulator.registers.cs != LOAD_SEGMENT
+        || emulator.registers.ip != COM_OFFSET
+        || emulator.instructions_executed == 0
+    {
+        if emulator.instructions_executed >= budget {
+            return Err(RecoveryError::NoHandOff(StopReason::BudgetExhausted));
+        }
+        if let Some(reason) = emulator.step() {
+            return Err(RecoveryError::NoHandOff(reason));
+        }
+    }
+    let hand_off = emulator.instructions_executed;
+    let image: Vec<u8> = (0..program.len())
+        .map_while(Address::from_file_offset)
+        .map(|address| emulator.read_u8(LOAD_SEGMENT, address))
+        .collect();
+
+    let entry = Decoder::with_ip(SIZE, program, COM_OFFSET as u64, DecoderOptions::NONE).decode();
+    let appended = (entry.mnemonic() == Mnemonic::Jmp && entry.op0_kind() == OpKind::NearBranch16)
+        .then(|| entry.near_branch16() as usize)
+        .filter(|&target| target >= entry.next_ip() as usize)
+        .and_then(|target| target.checked_sub(COM_OFFSET as usize))
+        .filter(|&offset| offset < program.len());
+    let (infection, start, end, host) = match appended {
+        Some(offset) => {
+            if image[..entry.len()] == program[..entry.len()] {
+                return Err(RecoveryError::HostNotFound);
+            }
+            (
+                Infection::Append,
+                offset,
+                program.len(),
+                image[..offset].to_vec(),
+            )
+        }
+        None => {
+            let shift = (1..program.len())
+                .find(|&shift| {
+                    let compared = MIN_MATCH.min(program.len() - shift);
+                    image[..compared] == program[shift..shift + compared]
+                })
+                .ok_or(RecoveryError::HostNotFound)?;
+            let length = image
+                .iter()
+                .zip(&program[shift..])
+                .take_while(|(moved, original)| moved == original)
+                .count();
+            (Infection::Prepend, 0, shift, image[..length].to_vec())
+        }
+    };
+
+    let printed = emulator.output.len();
+    let infected_stop = emulator.run(budget);
+    let mut clean = Emulator::new(&host);
+    let host_stop = clean.run(budget);
+    let verified = infected_stop == host_stop && emulator.output[printed..] == clean.output[..];
+
+    let address =
+        |offset: usize| (COM_OFFSET as usize + offset).min(Address::MAX as usize) as Address;
+    Ok(Recovery {
+        infection,
+        start: address(start),
+        end: address(end),
+        hand_off,
+        host,
+        infected_stop,
+        host_stop,
+        verified,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// mov dl, 'A' / mov ah, 2 / int 21h / ret
+    const HOST: [u8; 7] = [0xB2, 0x41, 0xB4, 0x02, 0xCD, 0x21, 0xC3];
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Recovery
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn appenders_restore_the_first_bytes() {
+        let mut infected = vec![0xE9, 0x04, 0x00]; // jmp 0x107
+        infected.extend(&HOST[3..]);
+        infected.extend([
+            0xBE, 0x18, 0x01, // 0x107: mov si, 0x118
+            0xBF, 0x00, 0x01, // mov di, 0x100
+            0xB9, 0x03, 0x00, // mov cx, 3
+            0xFC, // cld
+            0xF3, 0xA4, // rep movsb
+            0xB8, 0x00, 0x01, // mov ax, 0x100
+            0x50, // push ax
+            0xC3, // ret
+        ]);
+        infected.extend(&HOST[..3]);
+        let recovery = recover(&infected, 1000).unwrap();
+        assert_eq!(recovery.infection, Infection::Append);
+        assert_eq!((recovery.start, recovery.end), (0x107, 0x11B));
+        assert_eq!(recovery.host, HOST);
+        assert_eq!(recovery.hand_off, 9);
+        assert!(recovery.verified);
+    }
+
+    #[test]
+    fn prependers_move_the_host_down() {
+        let mut infected = vec![
+            0xC7, 0x06, 0x00, 0xFF, 0xF3, 0xA4, // mov word [0xff00], rep movsb
+            0xC6, 0x06, 0x02, 0xFF, 0xC3, // mov byte [0xff02], ret
+            0xBE, 0x19, 0x01, // mov si, 0x119
+            0xBF, 0x00, 0x01, // mov di, 0x100
+            0xB9, 0x07, 0x00, // mov cx, 7
+            0xFC, // cld
+            0x57, // push di
+            0xE9, 0xE7, 0xFD, // jmp 0xff00
+        ];
+        infected.extend(HOST);
+        let recovery = recover(&infected, 1000).unwrap();
+        assert_eq!(recovery.infection, Infection::Prepend);
+        assert_eq!((recovery.start, recovery.end), (0x100, 0x119));
+        assert_eq!(recovery.host, HOST);
+        assert_eq!(recovery.host_stop, StopReason::Exited(0));
+        assert!(recovery.verified);
+    }
+
+    #[test]
+    fn programs_that_never_come_back_are_not_recovered() {
+        // mov ah, 4ch / int 21h
+        assert_eq!(
+            recover(&[0xB4, 0x4C, 0xCD, 0x21], 100),
+            Err(RecoveryError::NoHandOff(StopReason::Exited(0)))
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{
+    FlowControl, Instruction, InstructionInfoFactory, Mnemonic, OpAccess, OpKind, Register,
+    RflagsBits,
+};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+
+/// Flags whose changes matter even if no instruction reads them
+const SYSTEM_FLAGS: u32 = RflagsBits::IF | RflagsBits::AC | RflagsBits::UIF;
+
+/// Why an instruction is redundant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RedundancyKind {
+    /// The instruction only sets flags, and every flag it sets is overwritten before it is read
+    DeadFlags,
+    /// A `push` undone by a `pop` of the same register at `pop`, with nothing using the
+    /// stack or changing the register in between
+    CancellingPushPop {
+        /// The address of the matching `pop`
+        pop: Address,
+    },
+}
+
+/// An instruction that can be removed without changing what the program does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Redundancy {
+    /// The address of the redundant instruction
+    pub address: Address,
+    /// Why it is redundant
+    pub kind: RedundancyKind,
+}
+
+impl Display for Redundancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            RedundancyKind::DeadFlags => write!(f, "redundant: flags set here are never read"),
+            RedundancyKind::CancellingPushPop { pop } => {
+                write!(f, "redundant: undone by the pop at 0x{pop:04x}")
+            }
+        }
+    }
+}
+
+/// Returns true if `instruction` writes a register or memory
+fn writes_anything(factory: &mut InstructionInfoFactory, instruction: &Instruction) -> bool {
+    let info = factory.info(instruction);
+    let written = |access: OpAccess| {
+        matches!(
+            access,
+            OpAccess::Write | OpAccess::CondWrite | OpAccess::ReadWrite | OpAccess::ReadCondWrite
+        )
+    };
+    info.used_registers()
+        .iter()
+        .any(|register| written(register.access()))
+        || info
+            .used_memory()
+            .iter()
+            .any(|memory| written(memory.access()))
+}
+
+/// Returns true if `instruction` writes `register` or any register overlapping it
+pub(crate) fn writes_register(
+    factory: &mut InstructionInfoFactory,
+    instruction: &Instruction,
+    register: Register,
+) -> bool {
+    factory
+        .info(instruction)
+        .used_registers()
+        .iter()
+        .any(|used| {
+            used.register().full_register() == register.full_register()
+                && !matches!(
+                    used.access(),
+                    OpAccess::Read | OpAccess::CondRead | OpAccess::None
+                )
+        })
+}
+
+/// Returns true if execution simply continues with the next instruction
+fn falls_through(instruction: &Instruction) -> bool {
+    instruction.flow_control() == FlowControl::Next && !is_data(instruction)
+}
+
+/// Returns true if the flags `instruction` sets are all overwritten before any is read
+///
+/// Only the straight-line code after the instruction is followed; reaching a
+/// branch, call, interrupt or data counts as the flags being read.
+fn flags_are_dead(instructions: &[Instruction], index: usize) -> bool {
+    let mut pending = instructions[index].rflags_modified();
+    for next in &instructions[index + 1..] {
+        if next.rflags_read() & pending != 0 || !falls_through(next) {
+            return false;
+        }
+        pending &= !next.rflags_modified();
+        if pending == 0 {
+            return true;
+        }
+    }
+    false
+}
+
+/// Finds the `pop` cancelling the `push` at `index`, if there is one
+fn cancelling_pop(
+    disassembler: &Disassembler,
+    factory: &mut InstructionInfoFactory,
+    index: usize,
+) -> Option<Address> {
+    let instructions = &disassembler.instructions.0;
+    let push = &instructions[index];
+    if push.mnemonic() != Mnemonic::Push || push.op0_kind() != OpKind::Register {
+        return None;
+    }
+    let register = push.op0_register();
+    for next in &instructions[index + 1..] {
+        // another path into the code between could rely on the pushed value
+        if disassembler
+            .labels
+            .get_by_address(next.ip() as Address)
+            .is_some()
+        {
+            return None;
+        }
+        if next.mnemonic() == Mnemonic::Pop
+            && next.op0_kind() == OpKind::Register
+            && next.op0_register() == register
+        {
+            return Some(next.ip() as Address);
+        }
+        if !falls_through(next)
+            || next.stack_pointer_increment() != 0
+            || writes_register(factory, next, register)
+            || writes_register(factory, next, Register::SP)
+        {
+            return None;
+        }
+    }
+    None
+}
+
+/// Finds instructions whose only effect is never observed
+///
+/// Reports flag-only instructions such as `cmp` or `clc` whose flags are all
+/// overwritten before being read, and `push`/`pop` pairs of a register with
+/// nothing in between that uses the stack, changes the register or is a
+/// jump target. The pop of a cancelling pair is not reported separately.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::redundancy::{find_redundancies, RedundancyKind};
+///
+/// // cmp ax, 1 / xor ax, ax / ret
+/// let disassembler = Disassembler::new(vec![0x3D, 0x01, 0x00, 0x31, 0xC0, 0xC3]);
+/// let found = find_redundancies(&disassembler);
+/// assert_eq!(found[0].kind, RedundancyKind::DeadFlags);
+/// ```
+pub fn find_redundancies(disassembler: &Disassembler) -> Vec<Redundancy> {
+    let instructions = &disassembler.instructions.0;
+    let mut factory = InstructionInfoFactory::new();
+    let mut found = Vec::new();
+    for (index, instruction) in instructions.iter().enumerate() {
+        let address = instruction.ip() as Address;
+        let modified = instruction.rflags_modified();
+        if modified != 0
+            && modified & SYSTEM_FLAGS == 0
+            && falls_through(instruction)
+            && !writes_anything(&mut factory, instruction)
+            && flags_are_dead(instructions, index)
+        {
+            found.push(Redundancy {
+                address,
+                kind: RedundancyKind::DeadFlags,
+            });
+        } else if let Some(pop) = cancelling_pop(disassembler, &mut factory, index) {
+            found.push(Redundancy {
+                address,
+                kind: RedundancyKind::CancellingPushPop { pop },
+            });
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(data: Vec<u8>) -> Vec<(Address, RedundancyKind)> {
+        find_redundancies(&Disassembler::new(data))
+            .into_iter()
+            .map(|redundancy| (redundancy.address, redundancy.kind))
+            .collect()
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Flag liveness
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn overwritten_flags_are_dead() {
+        // test ax, ax / clc / cmp ax, bx / jz $ / ret
+        let found = kinds(vec![0x85, 0xC0, 0xF8, 0x39, 0xD8, 0x74, 0xFE, 0xC3]);
+        assert_eq!(
+            found,
+            vec![
+                (0x100, RedundancyKind::DeadFlags),
+                (0x102, RedundancyKind::DeadFlags)
+            ]
+        );
+    }
+
+    #[test]
+    fn flags_read_later_are_live() {
+        // cmp ax, bx / mov cx, 1 / jz $ / ret
+        assert!(kinds(vec![0x39, 0xD8, 0xB9, 0x01, 0x00, 0x74, 0xFE, 0xC3]).is_empty());
+        // clc / ret: the caller may read the carry flag
+        assert!(kinds(vec![0xF8, 0xC3]).is_empty());
+        // cli / sti change how the CPU behaves even though nothing reads IF
+        assert!(kinds(vec![0xFA, 0xFB, 0xC3]).is_empty());
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Push / pop pairs
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn push_pop_of_an_untouched_register_cancels() {
+        // push ax / mov bx, 1 / pop ax / ret
+        let found = kinds(vec![0x50, 0xBB, 0x01, 0x00, 0x58, 0xC3]);
+        assert_eq!(
+            found,
+            vec![(0x100, RedundancyKind::CancellingPushPop { pop: 0x104 })]
+        );
+    }
+
+    #[test]
+    fn push_pop_preserving_a_register_is_needed() {
+        // push ax / mov al, 1 / pop ax / ret
+        assert!(kinds(vec![0x50, 0xB0, 0x01, 0x58, 0xC3]).is_empty());
+        // push ax / push bx / pop bx / pop ax / ret: the inner pair cancels, the outer uses the stack
+        assert_eq!(
+            kinds(vec![0x50, 0x53, 0x5B, 0x58, 0xC3]),
+            vec![(0x101, RedundancyKind::CancellingPushPop { pop: 0x102 })]
+        );
+    }
+}
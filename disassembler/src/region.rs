@@ -0,0 +1,130 @@
+use std::fmt::Display;
+
+use crate::consts::Address;
+
+/// A named part of the program, such as "init" or "font data"
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NamedRegion {
+    /// The name shown in the banner above the region
+    pub name: String,
+    /// The first address in the region
+    pub start: Address,
+    /// The address just past the region
+    pub end: Address,
+}
+
+impl NamedRegion {
+    /// Returns true if `address` falls inside the region
+    pub fn contains(&self, address: Address) -> bool {
+        (self.start..self.end).contains(&address)
+    }
+}
+
+impl Display for NamedRegion {
+    /// displays the region as a banner comment
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rule = format!("; {}", "=".repeat(60));
+        writeln!(f, "{rule}")?;
+        writeln!(
+            f,
+            "; {} (0x{:04x}-0x{:04x})",
+            self.name, self.start, self.end
+        )?;
+        write!(f, "{rule}")
+    }
+}
+
+/// A list of named regions, kept ordered by start address
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegionList(pub Vec<NamedRegion>);
+
+impl RegionList {
+    /// Creates an empty region list
+    pub fn new() -> Self {
+        RegionList(Vec::new())
+    }
+
+    /// Adds a region, replacing any region with the same name
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::region::{NamedRegion, RegionList};
+    ///
+    /// let mut regions = RegionList::new();
+    /// regions.insert(NamedRegion { name: "data".into(), start: 0x180, end: 0x200 });
+    /// regions.insert(NamedRegion { name: "init".into(), start: 0x100, end: 0x120 });
+    ///
+    /// assert_eq!(regions.0[0].name, "init");
+    /// assert_eq!(regions.get_by_address(0x190).unwrap().name, "data");
+    /// ```
+    pub fn insert(&mut self, region: NamedRegion) {
+        self.0.retain(|existing| existing.name != region.name);
+        let index = self
+            .0
+            .partition_point(|existing| existing.start <= region.start);
+        self.0.insert(index, region);
+    }
+
+    /// Removes the region called `name`, returning it if it existed
+    pub fn remove(&mut self, name: &str) -> Option<NamedRegion> {
+        let index = self.0.iter().position(|region| region.name == name)?;
+        Some(self.0.remove(index))
+    }
+
+    /// get the innermost region containing an address
+    pub fn get_by_address(&self, address: Address) -> Option<&NamedRegion> {
+        self.0
+            .iter()
+            .filter(|region| region.contains(address))
+            .min_by_key(|region| region.end - region.start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(name: &str, start: Address, end: Address) -> NamedRegion {
+        NamedRegion {
+            name: name.to_string(),
+            start,
+            end,
+        }
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  RegionList bookkeeping
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn inserting_a_name_again_replaces_it() {
+        let mut regions = RegionList::new();
+        regions.insert(region("music player", 0x200, 0x300));
+        regions.insert(region("music player", 0x220, 0x300));
+        assert_eq!(regions.0, vec![region("music player", 0x220, 0x300)]);
+        assert!(regions.remove("music player").is_some());
+        assert!(regions.0.is_empty());
+    }
+
+    #[test]
+    fn nested_regions_resolve_to_the_innermost() {
+        let mut regions = RegionList::new();
+        regions.insert(region("code", 0x100, 0x400));
+        regions.insert(region("music player", 0x200, 0x300));
+        assert_eq!(regions.get_by_address(0x250).unwrap().name, "music player");
+        assert_eq!(regions.get_by_address(0x350).unwrap().name, "code");
+        assert!(regions.get_by_address(0x400).is_none());
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Banner formatting
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn banner_names_the_range() {
+        let banner = region("init", 0x100, 0x120).to_string();
+        assert_eq!(banner.lines().count(), 3);
+        assert_eq!(banner.lines().nth(1), Some("; init (0x0100-0x0120)"));
+    }
+}
@@ -0,0 +1,268 @@
+use std::fmt::{self, Display};
+
+use crate::consts::Address;
+use crate::emulate::{CallResult, Emulator, LOAD_SEGMENT, Registers, ServiceCall, StopReason};
+use crate::files::FileSystem;
+use crate::keys::Keystroke;
+
+/// Why a recorded run could not be replayed exactly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The program is not the one the run was recorded with
+    WrongProgram,
+    /// The program made a service call the recording does not have
+    Diverged {
+        /// How many instructions had run before the call
+        instruction: u64,
+    },
+    /// The run ended differently than when it was recorded
+    DifferentResult {
+        /// How the recorded run ended
+        recorded: String,
+        /// How the replayed run ended
+        replayed: String,
+    },
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::WrongProgram => {
+                write!(f, "the recording was made with a different program")
+            }
+            ReplayError::Diverged { instruction } => write!(
+                f,
+                "the run left the recording at a service call after {instruction} instructions"
+            ),
+            ReplayError::DifferentResult { recorded, replayed } => write!(
+                f,
+                "the recorded run {recorded}, but the replayed run {replayed}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Everything needed to run a program in the emulator again exactly: how it
+/// was set up, the keys it was given, what every DOS and BIOS service call
+/// returned and how the run ended
+///
+/// Recordings are meant to be saved and shared, so an analysis that relies
+/// on a particular run, such as one reaching a code path only after certain
+/// keys, can be reproduced by someone else or by a later version of the
+/// emulator.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::keys::Keystroke;
+/// use disassembler::replay::Recording;
+///
+/// // mov ah, 1 / int 21h / mov dl, al / mov ah, 2 / int 21h / ret
+/// let program = [0xB4, 0x01, 0xCD, 0x21, 0x88, 0xC2, 0xB4, 0x02, 0xCD, 0x21, 0xC3];
+/// let mut recording = Recording::new(&program, &[Keystroke::from_ascii(b'y')], 100);
+/// let mut emulator = recording.emulator(&program).unwrap();
+/// recording.record(&mut emulator);
+/// assert_eq!(emulator.output, b"yy");
+///
+/// let mut again = recording.emulator(&program).unwrap();
+/// assert!(recording.replay(&mut again).is_ok());
+/// assert_eq!(again.output, b"yy");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Recording {
+    /// The length of the program
+    pub program_length: usize,
+    /// A hash of the program, to catch replaying it against another
+    pub program_hash: u64,
+    /// The keys fed to keyboard input calls
+    pub input: Vec<Keystroke>,
+    /// The registers at the start
+    pub registers: Registers,
+    /// Bytes written to the program's segment before starting, by address
+    pub memory: Vec<(Address, Vec<u8>)>,
+    /// The function called instead of running from the entry point, see
+    /// [`Emulator::call`]
+    pub call: Option<Address>,
+    /// The files the program was given, as they were at the start
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub files: Option<FileSystem>,
+    /// The instruction budget
+    pub budget: u64,
+    /// Every service call made, in order
+    pub services: Vec<ServiceCall>,
+    /// How the run ended, once recorded
+    pub result: Option<CallResult>,
+}
+
+impl Recording {
+    /// A run of `program` from its entry point, given `input` as keys and
+    /// stopped after `budget` instructions, that has not been recorded yet
+    ///
+    /// Change the starting registers, memory, files and called function
+    /// through the fields before calling [`Recording::emulator`].
+    pub fn new(program: &[u8], input: &[Keystroke], budget: u64) -> Recording {
+        Recording {
+            program_length: program.len(),
+            program_hash: hash(program),
+            input: input.to_vec(),
+            registers: Emulator::new(&[]).registers,
+            memory: Vec::new(),
+            call: None,
+            files: None,
+            budget,
+            services: Vec::new(),
+            result: None,
+        }
+    }
+
+    /// An emulator loaded with `program` and set up the way the run starts,
+    /// ready to be watched and then passed to [`Recording::record`] or
+    /// [`Recording::replay`]
+    pub fn emulator(&self, program: &[u8]) -> Result<Emulator, ReplayError> {
+        if program.len() != self.program_length || hash(program) != self.program_hash {
+            return Err(ReplayError::WrongProgram);
+        }
+        let mut emulator = Emulator::new(program).with_keys(&self.input);
+        emulator.registers = self.registers;
+        emulator.files = self.files.clone();
+        for (address, bytes) in &self.memory {
+            emulator.write_bytes(LOAD_SEGMENT, *address, bytes);
+        }
+        Ok(emulator)
+    }
+
+    /// Runs `emulator` and records the service calls it makes and how it
+    /// ends, replacing any earlier recording
+    pub fn record(&mut self, emulator: &mut Emulator) -> CallResult {
+        emulator.record();
+        let result = self.run(emulator);
+        self.services = emulator.take_recording();
+        self.result = Some(result.clone());
+        result
+    }
+
+    /// Runs `emulator` with every service call answered from the recording,
+    /// checking that it makes the same calls and ends the same way
+    pub fn replay(&self, emulator: &mut Emulator) -> Result<CallResult, ReplayError> {
+        emulator.replay(self.services.clone());
+        let result = self.run(emulator);
+        if let Some(StopReason::Diverged { instruction }) = result.stopped {
+            return Err(ReplayError::Diverged { instruction });
+        }
+        match &self.result {
+            Some(recorded) if *recorded != result => Err(ReplayError::DifferentResult {
+                recorded: ending(recorded),
+                replayed: ending(&result),
+            }),
+            _ => Ok(result),
+        }
+    }
+
+    fn run(&self, emulator: &mut Emulator) -> CallResult {
+        match self.call {
+            Some(address) => emulator.call(address, self.budget),
+            None => {
+                let reason = emulator.run(self.budget);
+                CallResult {
+                    stopped: Some(reason),
+                    registers: emulator.registers,
+                    output: emulator.output.clone(),
+                    instructions: emulator.instructions_executed,
+                }
+            }
+        }
+    }
+}
+
+/// How a run ended, for error messages
+fn ending(result: &CallResult) -> String {
+    let reason = result
+        .stopped
+        .as_ref()
+        .map_or("returned".to_string(), |reason| reason.to_string());
+    let output = String::from_utf8_lossy(&result.output);
+    let mut printed: String = output.chars().take(40).collect();
+    if printed.len() < output.len() {
+        printed.push_str("...");
+    }
+    format!(
+        "{reason} after {} instructions, printing {printed:?}",
+        result.instructions
+    )
+}
+
+/// The 64-bit FNV-1a hash of `bytes`, stable across platforms and releases
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// mov ah, 2ch / int 21h / mov dl, dh / add dl, '0' / mov ah, 2 / int 21h / ret
+    const CLOCK: [u8; 13] = [
+        0xB4, 0x2C, 0xCD, 0x21, 0x88, 0xF2, 0x80, 0xC2, 0x30, 0xB4, 0x02, 0xCD, 0x21,
+    ];
+
+    fn program() -> Vec<u8> {
+        let mut program = CLOCK.to_vec();
+        program.push(0xC3);
+        program
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Replaying
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn replay_answers_services_from_the_recording() {
+        let program = program();
+        let mut recording = Recording::new(&program, &[], 100);
+        let mut emulator = recording.emulator(&program).unwrap();
+        recording.record(&mut emulator);
+        assert_eq!(emulator.output, b"0");
+
+        // the seconds the clock returned when recorded
+        recording.services[0].after.dx = 0x0700;
+        recording.result = None;
+        let mut again = recording.emulator(&program).unwrap();
+        let result = recording.replay(&mut again).unwrap();
+        assert_eq!(result.output, b"7");
+        assert_eq!(result.stopped, Some(StopReason::Exited(0)));
+    }
+
+    #[test]
+    fn replays_that_leave_the_recording_fail() {
+        let program = program();
+        let mut recording = Recording::new(&program, &[], 100);
+        let mut emulator = recording.emulator(&program).unwrap();
+        recording.record(&mut emulator);
+
+        // int 21h is called with a different AX than recorded
+        recording.registers.ax = 0x0041;
+        let mut changed = recording.emulator(&program).unwrap();
+        assert_eq!(
+            recording.replay(&mut changed),
+            Err(ReplayError::Diverged { instruction: 2 })
+        );
+
+        recording.registers.ax = 0;
+        recording.result.as_mut().unwrap().output = b"9".to_vec();
+        let mut again = recording.emulator(&program).unwrap();
+        let err = recording.replay(&mut again).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the recorded run exited with code 0 after 8 instructions, printing \"9\", \
+             but the replayed run exited with code 0 after 8 instructions, printing \"0\""
+        );
+        assert_eq!(
+            recording.emulator(&CLOCK).err(),
+            Some(ReplayError::WrongProgram)
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{InstructionInfoFactory, Mnemonic, OpKind, Register};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+use crate::handlers::find_handlers;
+use crate::protection::value_before;
+use crate::selfmod::find_code_writes;
+use crate::syscall::SyscallType;
+
+/// The lowest score rated [`RiskLevel::Medium`]
+const MEDIUM: u32 = 30;
+
+/// The lowest score rated [`RiskLevel::High`]
+const HIGH: u32 = 60;
+
+/// How worrying a program's behaviour is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RiskLevel {
+    /// Nothing more than an ordinary program does, or one thing at most
+    Low,
+    /// Worth a look
+    Medium,
+    /// Behaves like a virus or trojan
+    High,
+}
+
+impl Display for RiskLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskLevel::Low => write!(f, "low"),
+            RiskLevel::Medium => write!(f, "medium"),
+            RiskLevel::High => write!(f, "high"),
+        }
+    }
+}
+
+/// Something that adds to a program's risk score
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RiskReason {
+    /// How much it adds
+    pub weight: u32,
+    /// Where it happens, if in one place
+    pub address: Option<Address>,
+    /// What the program does
+    pub text: String,
+}
+
+/// A heuristic score of how much a program behaves like malware, for
+/// sorting a large collection so the worrying files are looked at first
+///
+/// The score adds up behaviour viruses and trojans need and ordinary
+/// programs rarely show: hooking the DOS or disk interrupts, writing disk
+/// sectors directly, searching for `*.COM` or `*.EXE` files, opening,
+/// seeking and writing files, restoring file attributes and timestamps,
+/// staying resident and changing its own code. It is capped at 100. A high
+/// score is a reason to look, not proof; disk utilities and TSRs score too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RiskReport {
+    /// The score, from 0 to 100
+    pub score: u32,
+    /// The score as a level
+    pub level: RiskLevel,
+    /// Everything that added to the score
+    pub reasons: Vec<RiskReason>,
+}
+
+impl RiskReport {
+    /// Scores the analysed program
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::risk::{RiskLevel, RiskReport};
+    ///
+    /// let tsr = disassembler::samples::find("tsr").unwrap();
+    /// let report = RiskReport::assess(&Disassembler::new(tsr.bytes.to_vec()));
+    /// // hooks int 1Ch, patches the far jump chaining to the old handler
+    /// // and stays resident
+    /// assert_eq!(report.score, 30);
+    /// assert_eq!(report.level, RiskLevel::Medium);
+    /// ```
+    pub fn assess(disassembler: &Disassembler) -> RiskReport {
+        let mut reasons = Vec::new();
+        let mut add = |weight: u32, address: Option<Address>, text: String| {
+            reasons.push(RiskReason {
+                weight,
+                address,
+                text,
+            });
+        };
+
+        for handler in find_handlers(disassembler) {
+            let (weight, what) = match handler.vector {
+                Some(0x21) => (25, ", the DOS services"),
+                Some(0x13) => (25, ", the BIOS disk services"),
+                Some(0x26) => (25, ", absolute disk writes"),
+                _ => (10, ""),
+            };
+            let vector = handler
+                .vector
+                .map_or("an unknown interrupt".to_string(), |vector| {
+                    format!("int {vector:02X}h")
+                });
+            add(
+                weight,
+                Some(handler.installed_at),
+                format!("hooks {vector}{what}"),
+            );
+        }
+
+        let instructions = &disassembler.instructions.0;
+        let mut factory = InstructionInfoFactory::new();
+        for (index, instruction) in instructions.iter().enumerate() {
+            if is_data(instruction)
+                || instruction.mnemonic() != Mnemonic::Int
+                || instruction.op0_kind() != OpKind::Immediate8
+            {
+                continue;
+            }
+            let address = Some(instruction.ip() as Address);
+            match instruction.immediate8() {
+                0x13 => {
+                    let service = value_before(&mut factory, instructions, index, Register::AH);
+                    let what = match service {
+                        Some(0x03) => "writes disk sectors",
+                        Some(0x05) => "formats a disk track",
+                        Some(0x0B) => "writes long disk sectors",
+                        _ => continue,
+                    };
+                    add(30, address, format!("{what} with int 13h"));
+                }
+                0x26 => add(30, address, "writes disk sectors with int 26h".to_string()),
+                0x27 => add(10, address, "stays resident with int 27h".to_string()),
+                _ => {}
+            }
+        }
+
+        let syscalls = &disassembler.syscall_list.0;
+        let find = |number: SyscallType| syscalls.iter().find(|syscall| syscall.number == number);
+        for syscall in syscalls
+            .iter()
+            .filter(|syscall| syscall.number == SyscallType::FindFirstFile2)
+        {
+            let Some(pattern) = syscall
+                .dx
+                .and_then(|address| disassembler.read_asciiz(address))
+            else {
+                continue;
+            };
+            let upper = pattern.to_ascii_uppercase();
+            let wildcard = upper.contains('*') || upper.contains('?');
+            if wildcard && (upper.ends_with(".COM") || upper.ends_with(".EXE")) {
+                add(
+                    20,
+                    Some(syscall.address),
+                    format!("searches for {pattern} files"),
+                );
+            }
+        }
+        let opens = find(SyscallType::OpenFile2).or(find(SyscallType::CreateFile));
+        if let (Some(open), Some(_), Some(_)) = (
+            opens,
+            find(SyscallType::MoveFilePointer),
+            find(SyscallType::WriteFileOrDevice),
+        ) {
+            add(
+                20,
+                Some(open.address),
+                "opens, seeks in and writes files".to_string(),
+            );
+        }
+        if let (Some(attributes), Some(_)) = (
+            find(SyscallType::GetOrSetFileAttr),
+            find(SyscallType::GetOrSetFileDateTime),
+        ) {
+            add(
+                10,
+                Some(attributes.address),
+                "handles file attributes and timestamps".to_string(),
+            );
+        }
+        if let Some(resident) = find(SyscallType::TerminateAndStayResident) {
+            add(
+                10,
+                Some(resident.address),
+                "stays resident with int 21h/31h".to_string(),
+            );
+        }
+        if let Some(write) = find_code_writes(disassembler).first() {
+            add(10, Some(write.address), "changes its own code".to_string());
+        }
+
+        let score = reasons
+            .iter()
+            .map(|reason| reason.weight)
+            .sum::<u32>()
+            .min(100);
+        let level = match score {
+            HIGH.. => RiskLevel::High,
+            MEDIUM.. => RiskLevel::Medium,
+            _ => RiskLevel::Low,
+        };
+        RiskReport {
+            score,
+            level,
+            reasons,
+        }
+    }
+}
+
+impl Display for RiskReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "risk {}/100 ({})", self.score, self.level)?;
+        let reasons: Vec<&str> = self
+            .reasons
+            .iter()
+            .map(|reason| reason.text.as_str())
+            .collect();
+        if !reasons.is_empty() {
+            write!(f, ": {}", reasons.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Scoring
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn infection_patterns_score_high() {
+        let mut data = vec![
+            0xBA, 0x23, 0x01, // mov dx, 0x123
+            0xB4, 0x4E, 0xCD, 0x21, // mov ah, 0x4e / int 21h
+            0xB8, 0x02, 0x3D, 0xCD, 0x21, // mov ax, 0x3d02 / int 21h
+            0xB8, 0x02, 0x42, 0xCD, 0x21, // mov ax, 0x4202 / int 21h
+            0xB4, 0x40, 0xCD, 0x21, // mov ah, 0x40 / int 21h
+            0xB4, 0x03, 0xCD, 0x13, // mov ah, 3 / int 13h
+            0xC3, // ret
+            0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, // padding up to 0x123
+        ];
+        data.extend(b"*.COM\0");
+        let report = RiskReport::assess(&Disassembler::new(data));
+        assert_eq!(report.score, 70);
+        assert_eq!(report.level, RiskLevel::High);
+        assert_eq!(
+            report.to_string(),
+            "risk 70/100 (high): writes disk sectors with int 13h, searches for *.COM files, \
+             opens, seeks in and writes files"
+        );
+    }
+
+    #[test]
+    fn ordinary_programs_score_nothing() {
+        let hello = crate::samples::find("hello").unwrap();
+        let report = RiskReport::assess(&Disassembler::new(hello.bytes.to_vec()));
+        assert_eq!(report.score, 0);
+        assert_eq!(report.to_string(), "risk 0/100 (low)");
+    }
+}
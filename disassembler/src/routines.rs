@@ -0,0 +1,400 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{FlowControl, Instruction, Mnemonic, OpKind, Register};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+use crate::label::LabelType;
+use crate::syscall::SyscallType;
+
+/// The most instructions a function may have, up to its first return, to be
+/// taken for a small helper
+const MAX_BODY: usize = 32;
+
+/// The timer ports a delay loop may poll
+const TIMER_PORTS: [u16; 3] = [0x40, 0x43, 0x61];
+
+/// A small helper recognised by its shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RoutineKind {
+    /// Prints a string one character at a time in a loop
+    PrintString,
+    /// Prints a number in hex, shifting out a nibble at a time
+    PrintHex,
+    /// Copies memory with `rep movsb` or `rep movsw`
+    MemCopy,
+    /// Fills memory with `rep stosb` or `rep stosw`
+    MemSet,
+    /// Measures a string with `repne scasb`
+    StringLength,
+    /// Waits, polling the timer or counting down in an empty loop
+    Delay,
+}
+
+impl RoutineKind {
+    /// The name the function is given, before its address is added
+    pub fn name(self) -> &'static str {
+        match self {
+            RoutineKind::PrintString => "print_string",
+            RoutineKind::PrintHex => "print_hex",
+            RoutineKind::MemCopy => "memcpy",
+            RoutineKind::MemSet => "memset",
+            RoutineKind::StringLength => "strlen",
+            RoutineKind::Delay => "delay",
+        }
+    }
+}
+
+impl Display for RoutineKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoutineKind::PrintString => write!(f, "prints a string one character at a time"),
+            RoutineKind::PrintHex => write!(f, "prints a number in hex, a nibble at a time"),
+            RoutineKind::MemCopy => write!(f, "copies memory with rep movs"),
+            RoutineKind::MemSet => write!(f, "fills memory with rep stos"),
+            RoutineKind::StringLength => write!(f, "measures a string with repne scasb"),
+            RoutineKind::Delay => write!(f, "waits in a loop"),
+        }
+    }
+}
+
+/// A function recognised as a small helper
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Routine {
+    /// The start of the function
+    pub address: Address,
+    /// What the function does
+    pub kind: RoutineKind,
+}
+
+/// Returns true if `instruction` is `mov reg, value` for a register `value`
+/// fits in
+fn loads(instruction: &Instruction, register: Register, value: u16) -> bool {
+    instruction.mnemonic() == Mnemonic::Mov
+        && instruction.op0_kind() == OpKind::Register
+        && instruction.op0_register() == register
+        && match instruction.op1_kind() {
+            OpKind::Immediate8 => instruction.immediate8() as u16 == value,
+            OpKind::Immediate16 => instruction.immediate16() == value,
+            _ => false,
+        }
+}
+
+/// Returns true if `instruction` writes a character to the screen: int 21h
+/// function 2 or 6, int 29h, or int 10h after `mov ah, 0Eh` in `body`
+fn prints_character(
+    disassembler: &Disassembler,
+    body: &[&Instruction],
+    instruction: &Instruction,
+) -> bool {
+    if instruction.mnemonic() != Mnemonic::Int {
+        return false;
+    }
+    match instruction.immediate8() {
+        0x21 => disassembler
+            .syscall_list
+            .get_by_address(instruction.ip() as Address)
+            .is_some_and(|syscall| {
+                matches!(
+                    syscall.number,
+                    SyscallType::CharacterOutput | SyscallType::DirectConsoleIO
+                )
+            }),
+        0x29 => true,
+        0x10 => body.iter().any(|previous| {
+            loads(previous, Register::AH, 0x0E) || loads(previous, Register::AX, 0x0E00)
+        }),
+        _ => false,
+    }
+}
+
+/// Returns true if `instruction` jumps back to an address in `start..=ip`
+fn loops_back(instruction: &Instruction, start: Address) -> bool {
+    matches!(
+        instruction.flow_control(),
+        FlowControl::ConditionalBranch | FlowControl::UnconditionalBranch
+    ) && (start as u64..=instruction.ip()).contains(&instruction.near_branch_target())
+}
+
+/// Returns true if `instruction` reads a byte through SI, DI or BX, as a
+/// string loop reads its next character
+fn reads_next_byte(instruction: &Instruction) -> bool {
+    match instruction.mnemonic() {
+        Mnemonic::Lodsb => true,
+        Mnemonic::Mov | Mnemonic::Cmp => (0..instruction.op_count()).any(|operand| {
+            instruction.op_kind(operand) == OpKind::Memory
+                && instruction.memory_size().size() == 1
+                && matches!(
+                    instruction.memory_base(),
+                    Register::SI | Register::DI | Register::BX
+                )
+        }),
+        _ => false,
+    }
+}
+
+/// Returns true if `instruction` shifts or rotates by four bits, the size
+/// of a hex digit
+fn shifts_nibble(instruction: &Instruction) -> bool {
+    matches!(
+        instruction.mnemonic(),
+        Mnemonic::Shr | Mnemonic::Shl | Mnemonic::Rol | Mnemonic::Ror
+    ) && match instruction.op1_kind() {
+        OpKind::Immediate8 => instruction.immediate8() == 4,
+        OpKind::Register => instruction.op1_register() == Register::CL,
+        _ => false,
+    }
+}
+
+/// Returns true if `instruction` reads the timer: int 1Ah, the BIOS tick
+/// count at 0040:006C or a timer port
+fn reads_timer(instruction: &Instruction) -> bool {
+    match instruction.mnemonic() {
+        Mnemonic::Int => instruction.immediate8() == 0x1A,
+        Mnemonic::In => {
+            instruction.op1_kind() == OpKind::Immediate8
+                && TIMER_PORTS.contains(&(instruction.immediate8() as u16))
+        }
+        _ => (0..instruction.op_count()).any(|operand| {
+            instruction.op_kind(operand) == OpKind::Memory
+                && instruction.memory_base() == Register::None
+                && matches!(instruction.memory_displacement64(), 0x6C | 0x46C)
+        }),
+    }
+}
+
+/// Recognises what the function whose instructions up to its first return
+/// are `body` does, if it is one of the helpers looked for
+fn classify(
+    disassembler: &Disassembler,
+    body: &[&Instruction],
+    start: Address,
+) -> Option<RoutineKind> {
+    let any =
+        |test: &dyn Fn(&Instruction) -> bool| body.iter().any(|instruction| test(instruction));
+    let calls_out =
+        any(&|instruction| matches!(instruction.mnemonic(), Mnemonic::Int | Mnemonic::Call));
+    let loops = any(&|instruction| loops_back(instruction, start));
+    let prints = body
+        .iter()
+        .any(|instruction| prints_character(disassembler, body, instruction));
+
+    if any(&|instruction| {
+        instruction.mnemonic() == Mnemonic::Scasb && instruction.has_repne_prefix()
+    }) {
+        return Some(RoutineKind::StringLength);
+    }
+    if !calls_out
+        && any(&|instruction| {
+            matches!(instruction.mnemonic(), Mnemonic::Movsb | Mnemonic::Movsw)
+                && instruction.has_rep_prefix()
+        })
+    {
+        return Some(RoutineKind::MemCopy);
+    }
+    if !calls_out
+        && any(&|instruction| {
+            matches!(instruction.mnemonic(), Mnemonic::Stosb | Mnemonic::Stosw)
+                && instruction.has_rep_prefix()
+        })
+    {
+        return Some(RoutineKind::MemSet);
+    }
+    if prints && any(&shifts_nibble) {
+        return Some(RoutineKind::PrintHex);
+    }
+    if prints && loops && any(&reads_next_byte) {
+        return Some(RoutineKind::PrintString);
+    }
+    // a loop doing nothing but counting is a busy wait
+    let only_counts = body.iter().all(|instruction| {
+        matches!(
+            instruction.mnemonic(),
+            Mnemonic::Mov
+                | Mnemonic::Push
+                | Mnemonic::Pop
+                | Mnemonic::Dec
+                | Mnemonic::Jne
+                | Mnemonic::Loop
+                | Mnemonic::Nop
+                | Mnemonic::Ret
+        ) && (instruction.mnemonic() != Mnemonic::Mov
+            || instruction.op1_kind() != OpKind::Memory && instruction.op0_kind() != OpKind::Memory)
+    });
+    if loops && (any(&reads_timer) || only_counts) {
+        return Some(RoutineKind::Delay);
+    }
+    None
+}
+
+/// Finds small helper functions by their shape: printing a string a
+/// character at a time, printing hex, copying or filling memory, measuring
+/// a string and waiting
+///
+/// Only functions of up to 32 instructions before their first return are
+/// looked at, so larger functions doing one of these things among others
+/// are left alone.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::routines::{RoutineKind, find_routines};
+///
+/// let data = vec![
+///     0xE8, 0x01, 0x00, // call 0x104
+///     0xC3, // ret
+///     0xF3, 0xA4, // 0x104: rep movsb
+///     0xC3, // ret
+/// ];
+/// let routines = find_routines(&Disassembler::new(data));
+/// assert_eq!(routines[0].address, 0x104);
+/// assert_eq!(routines[0].kind, RoutineKind::MemCopy);
+/// ```
+pub fn find_routines(disassembler: &Disassembler) -> Vec<Routine> {
+    let code: Vec<&Instruction> = disassembler
+        .instructions
+        .0
+        .iter()
+        .filter(|instruction| !is_data(instruction))
+        .collect();
+    let mut functions: Vec<Address> = disassembler
+        .labels
+        .0
+        .iter()
+        .filter(|label| label.label_type == LabelType::FUNCTION)
+        .map(|label| label.address)
+        .collect();
+    functions.sort_unstable();
+    functions.dedup();
+
+    let mut found = Vec::new();
+    for start in functions {
+        let first = code.partition_point(|instruction| instruction.ip() < start as u64);
+        if code
+            .get(first)
+            .is_none_or(|instruction| instruction.ip() != start as u64)
+        {
+            continue;
+        }
+        let Some(length) = code[first..]
+            .iter()
+            .take(MAX_BODY)
+            .position(|instruction| instruction.flow_control() == FlowControl::Return)
+        else {
+            continue;
+        };
+        let body = &code[first..=first + length];
+        if let Some(kind) = classify(disassembler, body, start) {
+            found.push(Routine {
+                address: start,
+                kind,
+            });
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::ComBuilder;
+
+    /// The kinds found in a program calling a function at 0x104
+    fn kinds(function: &[u8]) -> Vec<RoutineKind> {
+        let mut data = vec![
+            0xE8, 0x01, 0x00, // call 0x104
+            0xC3, // ret
+        ];
+        data.extend_from_slice(function);
+        find_routines(&Disassembler::new(data))
+            .into_iter()
+            .map(|routine| routine.kind)
+            .collect()
+    }
+
+    #[test]
+    fn string_loops_are_print_string() {
+        assert_eq!(
+            kinds(&[
+                0xAC, // 0x104: lodsb
+                0x3C, 0x00, // cmp al, 0
+                0x74, 0x08, // je 0x112
+                0x88, 0xC2, // mov dl, al
+                0xB4, 0x02, // mov ah, 2
+                0xCD, 0x21, // int 21h
+                0xEB, 0xF5, // jmp 0x104
+                0xC3, // 0x112: ret
+            ]),
+            [RoutineKind::PrintString]
+        );
+    }
+
+    #[test]
+    fn nibble_shifts_with_teletype_output_are_print_hex() {
+        assert_eq!(
+            kinds(&[
+                0xB1, 0x04, // 0x104: mov cl, 4
+                0xD2, 0xC0, // rol al, cl
+                0x24, 0x0F, // and al, 0x0f
+                0x04, 0x30, // add al, '0'
+                0xB4, 0x0E, // mov ah, 0x0e
+                0xCD, 0x10, // int 10h
+                0xC3, // ret
+            ]),
+            [RoutineKind::PrintHex]
+        );
+    }
+
+    #[test]
+    fn string_instructions_name_their_routines() {
+        // xor al, al / mov cx, -1 / repne scasb / not cx / dec cx / ret
+        assert_eq!(
+            kinds(&[
+                0x30, 0xC0, 0xB9, 0xFF, 0xFF, 0xF2, 0xAE, 0xF7, 0xD1, 0x49, 0xC3
+            ]),
+            [RoutineKind::StringLength]
+        );
+        // mov al, 0 / rep stosb / ret
+        assert_eq!(
+            kinds(&[0xB0, 0x00, 0xF3, 0xAA, 0xC3]),
+            [RoutineKind::MemSet]
+        );
+    }
+
+    #[test]
+    fn counting_and_timer_loops_are_delays() {
+        // mov cx, 0xffff / loop $ / ret
+        assert_eq!(
+            kinds(&[0xB9, 0xFF, 0xFF, 0xE2, 0xFE, 0xC3]),
+            [RoutineKind::Delay]
+        );
+        // 0x104: in al, 0x61 / test al, 0x10 / jz 0x104 / ret
+        assert_eq!(
+            kinds(&[0xE4, 0x61, 0xA8, 0x10, 0x74, 0xFA, 0xC3]),
+            [RoutineKind::Delay]
+        );
+        // a loop that writes memory is not a delay
+        // 0x104: mov [bx], al / inc bx / loop 0x104 / ret
+        assert!(kinds(&[0x88, 0x07, 0x43, 0xE2, 0xFB, 0xC3]).is_empty());
+    }
+
+    #[test]
+    fn routines_are_named_and_commented() {
+        let program = ComBuilder::new()
+            .call("copy")
+            .ret()
+            .label("copy")
+            .bytes(&[0xF3, 0xA4]) // rep movsb
+            .ret()
+            .build();
+        let listing = Disassembler::new(program).to_string();
+        assert!(listing.contains("call memcpy_0x0104"), "{listing}");
+        assert!(
+            listing.contains("; copies memory with rep movs"),
+            "{listing}"
+        );
+    }
+}
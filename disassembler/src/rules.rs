@@ -0,0 +1,455 @@
+use std::fmt::{self, Display};
+
+use crate::consts::{Address, COM_OFFSET};
+
+/// The longest gap a single `[n-m]` jump may span
+const MAX_JUMP: usize = 0x1000;
+
+/// An error in a rule file, with the line it was found on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleError {
+    /// The 1-based line number of the error
+    pub line: usize,
+    /// What is wrong with the line
+    pub message: String,
+}
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+/// A single element of a byte pattern
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    /// A byte that must match `value` in the bits set in `mask`
+    ///
+    /// `??` has a mask of zero, `4?` and `?4` mask one nibble.
+    Byte {
+        /// The expected bits
+        value: u8,
+        /// The bits that are compared
+        mask: u8,
+    },
+    /// Between `min` and `max` bytes of anything
+    Jump {
+        /// The fewest bytes skipped
+        min: usize,
+        /// The most bytes skipped
+        max: usize,
+    },
+}
+
+/// A YARA-style hex pattern such as `B4 09 ?? CD 21 [2-4] C3`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern(pub Vec<Token>);
+
+impl Pattern {
+    /// Parses a pattern of hex bytes, `??` and nibble wildcards, and `[n]` or `[n-m]` jumps
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::rules::Pattern;
+    ///
+    /// let pattern = Pattern::parse("B4 ?? CD 21 [0-2] C3").unwrap();
+    /// assert_eq!(pattern.match_at(&[0xB4, 0x4C, 0xCD, 0x21, 0x90, 0xC3], 0), Some(6));
+    /// ```
+    pub fn parse(text: &str) -> Result<Pattern, String> {
+        let mut tokens = Vec::new();
+        let mut rest = text.trim();
+        while !rest.is_empty() {
+            if let Some(jump) = rest.strip_prefix('[') {
+                let end = jump.find(']').ok_or("unterminated jump")?;
+                let (min, max) = match jump[..end].split_once('-') {
+                    Some((min, max)) => (parse_count(min)?, parse_count(max)?),
+                    None => {
+                        let count = parse_count(&jump[..end])?;
+                        (count, count)
+                    }
+                };
+                if min > max || max > MAX_JUMP {
+                    return Err(format!("invalid jump [{}]", &jump[..end]));
+                }
+                tokens.push(Token::Jump { min, max });
+                rest = jump[end + 1..].trim_start();
+                continue;
+            }
+
+            let byte: String = rest.chars().take(2).collect();
+            let nibble = |c: char| match c {
+                '?' => Ok((0, 0)),
+                c => c
+                    .to_digit(16)
+                    .map(|digit| (digit as u8, 0xF))
+                    .ok_or(format!("invalid byte `{byte}`")),
+            };
+            let mut chars = byte.chars();
+            let (Some(high), Some(low)) = (chars.next(), chars.next()) else {
+                return Err(format!("invalid byte `{byte}`"));
+            };
+            let ((high, high_mask), (low, low_mask)) = (nibble(high)?, nibble(low)?);
+            tokens.push(Token::Byte {
+                value: high << 4 | low,
+                mask: high_mask << 4 | low_mask,
+            });
+            rest = rest[byte.len()..].trim_start();
+        }
+
+        if !matches!(tokens.first(), Some(Token::Byte { .. }))
+            || !matches!(tokens.last(), Some(Token::Byte { .. }))
+        {
+            return Err("a pattern must start and end with a byte".to_string());
+        }
+        Ok(Pattern(tokens))
+    }
+
+    /// Returns the length of the match starting at `offset` in `data`, if there is one
+    ///
+    /// Jumps match as few bytes as they can.
+    pub fn match_at(&self, data: &[u8], offset: usize) -> Option<usize> {
+        match_tokens(&self.0, data, offset).map(|end| end - offset)
+    }
+}
+
+/// Parses the decimal byte count of a jump
+fn parse_count(text: &str) -> Result<usize, String> {
+    text.trim()
+        .parse()
+        .map_err(|_| format!("invalid jump length `{}`", text.trim()))
+}
+
+/// Matches `tokens` against `data` from `offset`, returning the offset just past the match
+fn match_tokens(tokens: &[Token], data: &[u8], offset: usize) -> Option<usize> {
+    let Some((token, rest)) = tokens.split_first() else {
+        return Some(offset);
+    };
+    match *token {
+        Token::Byte { value, mask } => {
+            let byte = *data.get(offset)?;
+            if byte & mask != value & mask {
+                return None;
+            }
+            match_tokens(rest, data, offset + 1)
+        }
+        Token::Jump { min, max } => (min..=max)
+            .take_while(|skip| offset + skip <= data.len())
+            .find_map(|skip| match_tokens(rest, data, offset + skip)),
+    }
+}
+
+/// A named pattern and what to add to the listing where it matches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    /// The name of the rule, reported with every match
+    pub name: String,
+    /// The bytes the rule matches
+    pub pattern: Pattern,
+    /// A label to place at the start of every match
+    pub label: Option<String>,
+    /// A comment to place above every match
+    pub comment: Option<String>,
+}
+
+/// A place in the program where a rule matched
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RuleMatch {
+    /// The name of the rule that matched
+    pub rule: String,
+    /// The address of the first matched byte
+    pub start: Address,
+    /// The address just past the last matched byte
+    pub end: Address,
+    /// The rule's label, if it has one
+    pub label: Option<String>,
+    /// The rule's comment, if it has one
+    pub comment: Option<String>,
+}
+
+impl Display for RuleMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rule {} matched 0x{:04x}-0x{:04x}",
+            self.rule, self.start, self.end
+        )?;
+        if let Some(comment) = &self.comment {
+            write!(f, ": {comment}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A set of rules, usually loaded from a rule file
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RuleSet(pub Vec<Rule>);
+
+impl RuleSet {
+    /// Parses a rule file
+    ///
+    /// Every rule is a block of `key = value` lines; `bytes` is required,
+    /// `label` and `comment` are optional. Lines starting with `#` or `//`
+    /// are ignored.
+    ///
+    /// ```text
+    /// rule print_and_exit {
+    ///     bytes = { B4 09 CD 21 [0-4] B4 4C CD 21 }
+    ///     label = print_and_exit
+    ///     comment = "prints a string and exits"
+    /// }
+    /// ```
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::rules::RuleSet;
+    ///
+    /// let rules = RuleSet::parse("rule exit {\n bytes = B4 4C CD 21\n}").unwrap();
+    /// let matches = rules.scan(&[0x90, 0xB4, 0x4C, 0xCD, 0x21]);
+    /// assert_eq!(matches[0].start, 0x101);
+    /// ```
+    pub fn parse(text: &str) -> Result<RuleSet, RuleError> {
+        parse_blocks(text, "rule")?
+            .into_iter()
+            .map(|block| {
+                let line = block.line;
+                block.build().map_err(|message| RuleError { line, message })
+            })
+            .collect::<Result<_, _>>()
+            .map(RuleSet)
+    }
+
+    /// Finds every match of every rule in `data`, a program loaded at [`COM_OFFSET`]
+    ///
+    /// Matches are ordered by address, then by rule order.
+    pub fn scan(&self, data: &[u8]) -> Vec<RuleMatch> {
+        let mut matches = Vec::new();
+        for offset in 0..data.len() {
+            for rule in &self.0 {
+                let Some(length) = rule.pattern.match_at(data, offset) else {
+                    continue;
+                };
+                let start = COM_OFFSET as usize + offset;
+                matches.push(RuleMatch {
+                    rule: rule.name.clone(),
+                    start: start as Address,
+                    end: (start + length).min(Address::MAX as usize) as Address,
+                    label: rule.label.clone(),
+                    comment: rule.comment.clone(),
+                });
+            }
+        }
+        matches
+    }
+}
+
+/// A block as read from a rule file
+pub(crate) struct Block {
+    /// The name after the keyword
+    pub(crate) name: String,
+    /// The line the block starts on
+    pub(crate) line: usize,
+    /// The `key = value` pairs in the block, in order
+    pub(crate) keys: Vec<(String, String)>,
+}
+
+/// Splits a file of `keyword <name> { key = value ... }` blocks, the syntax
+/// shared by rule files and signature packs, skipping blank lines and `#`
+/// or `//` comments
+pub(crate) fn parse_blocks(text: &str, keyword: &str) -> Result<Vec<Block>, RuleError> {
+    let mut blocks = Vec::new();
+    let mut current: Option<Block> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let error = |message: String| RuleError {
+            line: number,
+            message,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+
+        if let Some(header) = line
+            .strip_prefix(keyword)
+            .and_then(|rest| rest.strip_prefix(' '))
+        {
+            if current.is_some() {
+                return Err(error(format!(
+                    "{keyword} started before the previous one ended"
+                )));
+            }
+            let name = header.trim().trim_end_matches('{').trim();
+            if name.is_empty() || !header.trim_end().ends_with('{') {
+                return Err(error(format!("expected `{keyword} <name> {{`")));
+            }
+            current = Some(Block {
+                name: name.to_string(),
+                line: number,
+                keys: Vec::new(),
+            });
+        } else if line == "}" {
+            let block = current
+                .take()
+                .ok_or_else(|| error(format!("`}}` outside a {keyword}")))?;
+            blocks.push(block);
+        } else if let Some((key, value)) = line.split_once('=') {
+            let block = current
+                .as_mut()
+                .ok_or_else(|| error(format!("key outside a {keyword}")))?;
+            block
+                .keys
+                .push((key.trim().to_string(), value.trim().to_string()));
+        } else {
+            return Err(error(format!("unexpected `{line}`")));
+        }
+    }
+
+    if let Some(block) = current {
+        return Err(RuleError {
+            line: block.line,
+            message: format!("{keyword} {} is never closed", block.name),
+        });
+    }
+    Ok(blocks)
+}
+
+/// Parses the pattern of a `bytes` key, with or without braces
+pub(crate) fn parse_bytes(name: &str, value: &str) -> Result<Pattern, String> {
+    let bytes = value.trim_start_matches('{').trim_end_matches('}');
+    Pattern::parse(bytes).map_err(|err| format!("{name}: {err}"))
+}
+
+impl Block {
+    /// Builds the rule the block describes
+    fn build(self) -> Result<Rule, String> {
+        let name = self.name;
+        let mut pattern = None;
+        let mut label = None;
+        let mut comment = None;
+        for (key, value) in self.keys {
+            match key.as_str() {
+                "bytes" => pattern = Some(parse_bytes(&format!("rule {name}"), &value)?),
+                "label" => label = Some(unquote(&value)),
+                "comment" => comment = Some(unquote(&value)),
+                other => return Err(format!("rule {name}: unknown key `{other}`")),
+            }
+        }
+        Ok(Rule {
+            pattern: pattern.ok_or_else(|| format!("rule {name} has no bytes"))?,
+            name,
+            label,
+            comment,
+        })
+    }
+}
+
+/// Strips one pair of surrounding double quotes
+pub(crate) fn unquote(value: &str) -> String {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+        .to_string()
+}
+
+/// Parses a number in hex with a `0x` prefix or an `h` suffix, or in decimal
+#[cfg(any(feature = "emulator", feature = "signatures"))]
+pub(crate) fn parse_number(value: &str) -> Option<usize> {
+    let hex = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_suffix(['h', 'H']));
+    match hex {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Pattern parsing and matching
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn wildcards_match_any_bits_they_cover() {
+        let pattern = Pattern::parse("B? ?9 ??").unwrap();
+        assert_eq!(pattern.match_at(&[0xB4, 0x09, 0x00], 0), Some(3));
+        assert_eq!(pattern.match_at(&[0xB4, 0x19, 0xFF], 0), Some(3));
+        assert_eq!(pattern.match_at(&[0xC4, 0x09, 0x00], 0), None);
+        assert_eq!(pattern.match_at(&[0xB4, 0x09], 0), None, "runs off the end");
+    }
+
+    #[test]
+    fn jumps_take_the_shortest_match() {
+        let pattern = Pattern::parse("90 [1-3] C3").unwrap();
+        assert_eq!(pattern.match_at(&[0x90, 0xC3, 0xC3, 0xC3], 0), Some(3));
+        assert_eq!(pattern.match_at(&[0x90, 0xC3], 0), None);
+        assert_eq!(
+            Pattern::parse("90 [2] C3").unwrap().0[1],
+            Token::Jump { min: 2, max: 2 }
+        );
+    }
+
+    #[test]
+    fn malformed_patterns_are_rejected() {
+        assert!(Pattern::parse("B4 0").is_err());
+        assert!(Pattern::parse("B4 G9").is_err());
+        assert!(Pattern::parse("[2] C3").is_err());
+        assert!(Pattern::parse("90 [3-1] C3").is_err());
+        assert!(Pattern::parse("90 [1-2 C3").is_err());
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Rule files
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn rule_files_parse_and_scan() {
+        let rules = RuleSet::parse(
+            r#"
+            # DOS exit
+            rule exit {
+                bytes = { B4 4C CD 21 }
+                label = quit
+                comment = "terminates the program"
+            }
+            rule int21 {
+                bytes = CD 21
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(rules.0.len(), 2);
+        assert_eq!(rules.0[0].label.as_deref(), Some("quit"));
+
+        let matches = rules.scan(&[0xB4, 0x4C, 0xCD, 0x21]);
+        let found: Vec<_> = matches
+            .iter()
+            .map(|m| (m.rule.as_str(), m.start, m.end))
+            .collect();
+        assert_eq!(found, vec![("exit", 0x100, 0x104), ("int21", 0x102, 0x104)]);
+        assert_eq!(
+            matches[0].to_string(),
+            "rule exit matched 0x0100-0x0104: terminates the program"
+        );
+    }
+
+    #[test]
+    fn rule_errors_report_the_line() {
+        let err = RuleSet::parse("rule a {\n  bytes = 90\n  colour = red\n}").unwrap_err();
+        assert_eq!(err.line, 1);
+        assert!(err.message.contains("colour"));
+
+        let err = RuleSet::parse("rule a {\n  bytes = 90\n").unwrap_err();
+        assert!(err.message.contains("never closed"));
+
+        let err = RuleSet::parse("\nbytes = 90").unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+}
@@ -0,0 +1,167 @@
+/// A small COM program bundled with the library
+///
+/// The samples let a frontend offer "load example" without shipping
+/// separate files, and give tests whole programs to work on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    /// A short name to look the sample up by
+    pub name: &'static str,
+    /// A one-line title
+    pub title: &'static str,
+    /// What the program does and what to look for in its disassembly
+    pub description: &'static str,
+    /// The program
+    pub bytes: &'static [u8],
+}
+
+/// Prints "Hello, World" with int 21h/09h and exits
+const HELLO: &[u8] = &[
+    0xEB, 0x0F, // jmp start
+    // msg: db "Hello, World", 0x0d, 0x0a, "$"
+    b'H', b'e', b'l', b'l', b'o', b',', b' ', b'W', b'o', b'r', b'l', b'd', 0x0D, 0x0A, b'$',
+    // start:
+    0xB4, 0x09, // mov ah, 9
+    0xBA, 0x02, 0x01, // mov dx, msg
+    0xCD, 0x21, // int 21h
+    0xB4, 0x4C, // mov ah, 0x4c
+    0xCD, 0x21, // int 21h
+];
+
+/// Counts timer ticks from an int 1Ch handler left resident
+const TSR: &[u8] = &[
+    0xEB, 0x10, // jmp install
+    0x00, 0x00, // counter: dw 0
+    0x2E, 0xFF, 0x06, 0x02, 0x01, // handler: inc word [cs:counter]
+    0x2E, 0xFF, 0x2E, 0x0E, 0x01, // jmp far [cs:old]
+    0x00, 0x00, 0x00, 0x00, // old: dd 0
+    0xB8, 0x1C, 0x35, // install: mov ax, 0x351c
+    0xCD, 0x21, // int 21h
+    0x89, 0x1E, 0x0E, 0x01, // mov [old], bx
+    0x8C, 0x06, 0x10, 0x01, // mov [old+2], es
+    0xB8, 0x1C, 0x25, // mov ax, 0x251c
+    0xBA, 0x04, 0x01, // mov dx, handler
+    0xCD, 0x21, // int 21h
+    0xB8, 0x00, 0x31, // mov ax, 0x3100
+    0xBA, 0x12, 0x00, // mov dx, 0x12 paragraphs, up to install
+    0xCD, 0x21, // int 21h
+];
+
+/// Decodes a program XORed with 0x55 in place and jumps into it
+const PACKED: &[u8] = &[
+    0xBE, 0x0F, 0x01, // mov si, payload
+    0xB9, 0x18, 0x00, // mov cx, 24
+    0x80, 0x34, 0x55, // decode: xor byte [si], 0x55
+    0x46, // inc si
+    0xE2, 0xFA, // loop decode
+    0xE9, 0x00, 0x00, // jmp payload
+    // payload, XORed with 0x55:
+    // mov ah, 9 / mov dx, msg / int 21h / mov ax, 0x4c00 / int 21h
+    0xE1, 0x5C, 0xEF, 0x4E, 0x54, 0x98, 0x74, 0xED, 0x55, 0x19, 0x98, 0x74,
+    // msg: db "Unpacked!", 0x0d, 0x0a, "$"
+    0x00, 0x3B, 0x25, 0x34, 0x36, 0x3E, 0x30, 0x31, 0x74, 0x58, 0x5F, 0x71,
+];
+
+/// Every bundled sample
+pub const SAMPLES: &[Sample] = &[
+    Sample {
+        name: "hello",
+        title: "Hello, World",
+        description: "Jumps over its message, prints it with int 21h/09h and exits with \
+                      int 21h/4Ch. The smallest complete program: one string, one label.",
+        bytes: HELLO,
+    },
+    Sample {
+        name: "tsr",
+        title: "Timer tick counter TSR",
+        description: "Hooks the int 1Ch timer tick with int 21h/35h and 25h, chains to the \
+                      old handler with a far jump, and stays resident with int 21h/31h. \
+                      Shows interrupt vectors and code that runs after the program exits.",
+        bytes: TSR,
+    },
+    Sample {
+        name: "packed",
+        title: "XOR-packed program",
+        description: "Decodes the rest of itself with a xor loop and jumps into it, so the \
+                      static disassembly of the payload is garbage. Run it in the emulator \
+                      to see the message it hides.",
+        bytes: PACKED,
+    },
+];
+
+/// Looks up a sample by name
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::samples;
+///
+/// let hello = samples::find("hello").unwrap();
+/// let disassembler = Disassembler::new(hello.bytes.to_vec());
+/// assert_eq!(disassembler.string_constant_list.0[0].value, "Hello, World\r\n$");
+/// ```
+pub fn find(name: &str) -> Option<&'static Sample> {
+    SAMPLES.iter().find(|sample| sample.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassemble::Disassembler;
+    #[cfg(feature = "emulator")]
+    use crate::emulate::{Emulator, StopReason};
+    use crate::syscall::SyscallType;
+
+    fn syscalls(sample: &str) -> Vec<SyscallType> {
+        let disassembler = Disassembler::new(find(sample).unwrap().bytes.to_vec());
+        disassembler
+            .syscall_list
+            .0
+            .iter()
+            .map(|syscall| syscall.number)
+            .collect()
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  The samples do what they say
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn names_are_unique() {
+        for (index, sample) in SAMPLES.iter().enumerate() {
+            assert_eq!(find(sample.name), Some(&SAMPLES[index]));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "emulator")]
+    fn hello_prints_its_message() {
+        let mut emulator = Emulator::new(HELLO);
+        assert_eq!(emulator.run(1000), StopReason::Exited(0));
+        assert_eq!(emulator.output, b"Hello, World\r\n");
+        assert_eq!(
+            syscalls("hello"),
+            vec![SyscallType::DisplayString, SyscallType::TerminateWithCode]
+        );
+    }
+
+    #[test]
+    fn tsr_hooks_the_timer_and_stays_resident() {
+        assert_eq!(
+            syscalls("tsr"),
+            vec![
+                SyscallType::GetInterruptVector,
+                SyscallType::SetInterruptVector,
+                SyscallType::TerminateAndStayResident,
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "emulator")]
+    fn packed_payload_only_appears_when_run() {
+        assert_eq!(syscalls("packed"), vec![], "the payload is encoded");
+        let mut emulator = Emulator::new(PACKED);
+        assert_eq!(emulator.run(1000), StopReason::Exited(0));
+        assert_eq!(emulator.output, b"Unpacked!\r\n");
+    }
+}
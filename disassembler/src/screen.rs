@@ -0,0 +1,372 @@
+use std::fmt::{self, Display};
+
+use crate::graphics::{EGA_PALETTE, Image};
+use crate::string::decode_cp437;
+
+/// The columns of the text screen
+pub const COLUMNS: usize = 80;
+/// The rows of the text screen
+pub const ROWS: usize = 25;
+
+/// The width and height of a character cell in the rendered screen, with
+/// every row of the 8x8 font drawn twice like a VGA in a CGA text mode
+const CELL: (usize, usize) = (8, 16);
+
+/// The characters of code page 437 from 0x01 to 0x1F, which are drawn as
+/// symbols rather than acted on when written straight to the screen
+const CP437_LOW: [char; 31] = [
+    '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼', '►', //
+    '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+];
+
+/// Glyphs for the printable ASCII characters, one byte per row with the
+/// leftmost pixel in the high bit
+const FONT: [[u8; 8]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x18, 0x3C, 0x3C, 0x18, 0x18, 0x00, 0x18, 0x00], // !
+    [0x6C, 0x6C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // "
+    [0x6C, 0x6C, 0xFE, 0x6C, 0xFE, 0x6C, 0x6C, 0x00], // #
+    [0x30, 0x7C, 0xC0, 0x78, 0x0C, 0xF8, 0x30, 0x00], // $
+    [0x00, 0xC6, 0xCC, 0x18, 0x30, 0x66, 0xC6, 0x00], // %
+    [0x38, 0x6C, 0x38, 0x76, 0xDC, 0xCC, 0x76, 0x00], // &
+    [0x60, 0x60, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00], // '
+    [0x18, 0x30, 0x60, 0x60, 0x60, 0x30, 0x18, 0x00], // (
+    [0x60, 0x30, 0x18, 0x18, 0x18, 0x30, 0x60, 0x00], // )
+    [0x00, 0x66, 0x3C, 0xFF, 0x3C, 0x66, 0x00, 0x00], // *
+    [0x00, 0x30, 0x30, 0xFC, 0x30, 0x30, 0x00, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x30, 0x30, 0x60], // ,
+    [0x00, 0x00, 0x00, 0xFC, 0x00, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x30, 0x30, 0x00], // .
+    [0x06, 0x0C, 0x18, 0x30, 0x60, 0xC0, 0x80, 0x00], // /
+    [0x7C, 0xC6, 0xCE, 0xDE, 0xF6, 0xE6, 0x7C, 0x00], // 0
+    [0x30, 0x70, 0x30, 0x30, 0x30, 0x30, 0xFC, 0x00], // 1
+    [0x78, 0xCC, 0x0C, 0x38, 0x60, 0xCC, 0xFC, 0x00], // 2
+    [0x78, 0xCC, 0x0C, 0x38, 0x0C, 0xCC, 0x78, 0x00], // 3
+    [0x1C, 0x3C, 0x6C, 0xCC, 0xFE, 0x0C, 0x1E, 0x00], // 4
+    [0xFC, 0xC0, 0xF8, 0x0C, 0x0C, 0xCC, 0x78, 0x00], // 5
+    [0x38, 0x60, 0xC0, 0xF8, 0xCC, 0xCC, 0x78, 0x00], // 6
+    [0xFC, 0xCC, 0x0C, 0x18, 0x30, 0x30, 0x30, 0x00], // 7
+    [0x78, 0xCC, 0xCC, 0x78, 0xCC, 0xCC, 0x78, 0x00], // 8
+    [0x78, 0xCC, 0xCC, 0x7C, 0x0C, 0x18, 0x70, 0x00], // 9
+    [0x00, 0x30, 0x30, 0x00, 0x00, 0x30, 0x30, 0x00], // :
+    [0x00, 0x30, 0x30, 0x00, 0x00, 0x30, 0x30, 0x60], // ;
+    [0x18, 0x30, 0x60, 0xC0, 0x60, 0x30, 0x18, 0x00], // <
+    [0x00, 0x00, 0xFC, 0x00, 0x00, 0xFC, 0x00, 0x00], // =
+    [0x60, 0x30, 0x18, 0x0C, 0x18, 0x30, 0x60, 0x00], // >
+    [0x78, 0xCC, 0x0C, 0x18, 0x30, 0x00, 0x30, 0x00], // ?
+    [0x7C, 0xC6, 0xDE, 0xDE, 0xDE, 0xC0, 0x78, 0x00], // @
+    [0x30, 0x78, 0xCC, 0xCC, 0xFC, 0xCC, 0xCC, 0x00], // A
+    [0xFC, 0x66, 0x66, 0x7C, 0x66, 0x66, 0xFC, 0x00], // B
+    [0x3C, 0x66, 0xC0, 0xC0, 0xC0, 0x66, 0x3C, 0x00], // C
+    [0xF8, 0x6C, 0x66, 0x66, 0x66, 0x6C, 0xF8, 0x00], // D
+    [0xFE, 0x62, 0x68, 0x78, 0x68, 0x62, 0xFE, 0x00], // E
+    [0xFE, 0x62, 0x68, 0x78, 0x68, 0x60, 0xF0, 0x00], // F
+    [0x3C, 0x66, 0xC0, 0xC0, 0xCE, 0x66, 0x3E, 0x00], // G
+    [0xCC, 0xCC, 0xCC, 0xFC, 0xCC, 0xCC, 0xCC, 0x00], // H
+    [0x78, 0x30, 0x30, 0x30, 0x30, 0x30, 0x78, 0x00], // I
+    [0x1E, 0x0C, 0x0C, 0x0C, 0xCC, 0xCC, 0x78, 0x00], // J
+    [0xE6, 0x66, 0x6C, 0x78, 0x6C, 0x66, 0xE6, 0x00], // K
+    [0xF0, 0x60, 0x60, 0x60, 0x62, 0x66, 0xFE, 0x00], // L
+    [0xC6, 0xEE, 0xFE, 0xFE, 0xD6, 0xC6, 0xC6, 0x00], // M
+    [0xC6, 0xE6, 0xF6, 0xDE, 0xCE, 0xC6, 0xC6, 0x00], // N
+    [0x38, 0x6C, 0xC6, 0xC6, 0xC6, 0x6C, 0x38, 0x00], // O
+    [0xFC, 0x66, 0x66, 0x7C, 0x60, 0x60, 0xF0, 0x00], // P
+    [0x78, 0xCC, 0xCC, 0xCC, 0xDC, 0x78, 0x1C, 0x00], // Q
+    [0xFC, 0x66, 0x66, 0x7C, 0x6C, 0x66, 0xE6, 0x00], // R
+    [0x78, 0xCC, 0xE0, 0x70, 0x1C, 0xCC, 0x78, 0x00], // S
+    [0xFC, 0xB4, 0x30, 0x30, 0x30, 0x30, 0x78, 0x00], // T
+    [0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xFC, 0x00], // U
+    [0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0x78, 0x30, 0x00], // V
+    [0xC6, 0xC6, 0xC6, 0xD6, 0xFE, 0xEE, 0xC6, 0x00], // W
+    [0xC6, 0xC6, 0x6C, 0x38, 0x38, 0x6C, 0xC6, 0x00], // X
+    [0xCC, 0xCC, 0xCC, 0x78, 0x30, 0x30, 0x78, 0x00], // Y
+    [0xFE, 0xC6, 0x8C, 0x18, 0x32, 0x66, 0xFE, 0x00], // Z
+    [0x78, 0x60, 0x60, 0x60, 0x60, 0x60, 0x78, 0x00], // [
+    [0xC0, 0x60, 0x30, 0x18, 0x0C, 0x06, 0x02, 0x00], // \
+    [0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0x78, 0x00], // ]
+    [0x10, 0x38, 0x6C, 0xC6, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF], // _
+    [0x30, 0x30, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // `
+    [0x00, 0x00, 0x78, 0x0C, 0x7C, 0xCC, 0x76, 0x00], // a
+    [0xE0, 0x60, 0x60, 0x7C, 0x66, 0x66, 0xDC, 0x00], // b
+    [0x00, 0x00, 0x78, 0xCC, 0xC0, 0xCC, 0x78, 0x00], // c
+    [0x1C, 0x0C, 0x0C, 0x7C, 0xCC, 0xCC, 0x76, 0x00], // d
+    [0x00, 0x00, 0x78, 0xCC, 0xFC, 0xC0, 0x78, 0x00], // e
+    [0x38, 0x6C, 0x60, 0xF0, 0x60, 0x60, 0xF0, 0x00], // f
+    [0x00, 0x00, 0x76, 0xCC, 0xCC, 0x7C, 0x0C, 0xF8], // g
+    [0xE0, 0x60, 0x6C, 0x76, 0x66, 0x66, 0xE6, 0x00], // h
+    [0x30, 0x00, 0x70, 0x30, 0x30, 0x30, 0x78, 0x00], // i
+    [0x0C, 0x00, 0x0C, 0x0C, 0x0C, 0xCC, 0xCC, 0x78], // j
+    [0xE0, 0x60, 0x66, 0x6C, 0x78, 0x6C, 0xE6, 0x00], // k
+    [0x70, 0x30, 0x30, 0x30, 0x30, 0x30, 0x78, 0x00], // l
+    [0x00, 0x00, 0xCC, 0xFE, 0xFE, 0xD6, 0xC6, 0x00], // m
+    [0x00, 0x00, 0xF8, 0xCC, 0xCC, 0xCC, 0xCC, 0x00], // n
+    [0x00, 0x00, 0x78, 0xCC, 0xCC, 0xCC, 0x78, 0x00], // o
+    [0x00, 0x00, 0xDC, 0x66, 0x66, 0x7C, 0x60, 0xF0], // p
+    [0x00, 0x00, 0x76, 0xCC, 0xCC, 0x7C, 0x0C, 0x1E], // q
+    [0x00, 0x00, 0xDC, 0x76, 0x66, 0x60, 0xF0, 0x00], // r
+    [0x00, 0x00, 0x7C, 0xC0, 0x78, 0x0C, 0xF8, 0x00], // s
+    [0x10, 0x30, 0x7C, 0x30, 0x30, 0x34, 0x18, 0x00], // t
+    [0x00, 0x00, 0xCC, 0xCC, 0xCC, 0xCC, 0x76, 0x00], // u
+    [0x00, 0x00, 0xCC, 0xCC, 0xCC, 0x78, 0x30, 0x00], // v
+    [0x00, 0x00, 0xC6, 0xD6, 0xFE, 0xFE, 0x6C, 0x00], // w
+    [0x00, 0x00, 0xC6, 0x6C, 0x38, 0x6C, 0xC6, 0x00], // x
+    [0x00, 0x00, 0xCC, 0xCC, 0xCC, 0x7C, 0x0C, 0xF8], // y
+    [0x00, 0x00, 0xFC, 0x98, 0x30, 0x64, 0xFC, 0x00], // z
+    [0x1C, 0x30, 0x30, 0xE0, 0x30, 0x30, 0x1C, 0x00], // {
+    [0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00], // |
+    [0xE0, 0x30, 0x30, 0x1C, 0x30, 0x30, 0xE0, 0x00], // }
+    [0x76, 0xDC, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ~
+];
+
+/// The lines leaving the middle of each box drawing character from 0xB3 to
+/// 0xDA, up, down, left and right, as 0 for none, 1 for single and 2 for double
+const BOX_LINES: [[u8; 4]; 40] = [
+    [1, 1, 0, 0], // │
+    [1, 1, 1, 0], // ┤
+    [1, 1, 2, 0], // ╡
+    [2, 2, 1, 0], // ╢
+    [0, 2, 1, 0], // ╖
+    [0, 1, 2, 0], // ╕
+    [2, 2, 2, 0], // ╣
+    [2, 2, 0, 0], // ║
+    [0, 2, 2, 0], // ╗
+    [2, 0, 2, 0], // ╝
+    [2, 0, 1, 0], // ╜
+    [1, 0, 2, 0], // ╛
+    [0, 1, 1, 0], // ┐
+    [1, 0, 0, 1], // └
+    [1, 0, 1, 1], // ┴
+    [0, 1, 1, 1], // ┬
+    [1, 1, 0, 1], // ├
+    [0, 0, 1, 1], // ─
+    [1, 1, 1, 1], // ┼
+    [1, 1, 0, 2], // ╞
+    [2, 2, 0, 1], // ╟
+    [2, 0, 0, 2], // ╚
+    [0, 2, 0, 2], // ╔
+    [2, 0, 2, 2], // ╩
+    [0, 2, 2, 2], // ╦
+    [2, 2, 0, 2], // ╠
+    [0, 0, 2, 2], // ═
+    [2, 2, 2, 2], // ╬
+    [1, 0, 2, 2], // ╧
+    [2, 0, 1, 1], // ╨
+    [0, 1, 2, 2], // ╤
+    [0, 2, 1, 1], // ╥
+    [2, 0, 0, 1], // ╙
+    [1, 0, 0, 2], // ╘
+    [0, 1, 0, 2], // ╒
+    [0, 2, 0, 1], // ╓
+    [2, 2, 1, 1], // ╫
+    [1, 1, 2, 2], // ╪
+    [1, 0, 1, 0], // ┘
+    [0, 1, 0, 1], // ┌
+];
+
+/// What an 80x25 text screen shows: a character and an attribute for
+/// every cell, and where the cursor is
+///
+/// The attribute's low nibble is the foreground colour and the next three
+/// bits the background, as in the default palette of a colour adapter.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::screen::Screen;
+///
+/// let mut screen = Screen::default();
+/// screen.cells[0] = (b'H', 0x1F);
+/// screen.cells[1] = (b'i', 0x1F);
+/// screen.cells[80] = (0xC4, 0x07);
+/// assert_eq!(screen.text(), "Hi\n─\n");
+///
+/// let image = screen.render();
+/// assert_eq!((image.width, image.height), (640, 400));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Screen {
+    /// The character and attribute of each cell, row by row
+    pub cells: Vec<(u8, u8)>,
+    /// The row and column of the cursor
+    pub cursor: (u8, u8),
+}
+
+impl Default for Screen {
+    /// A blank screen in light grey on black, with the cursor at the top left
+    fn default() -> Screen {
+        Screen {
+            cells: vec![(b' ', 0x07); COLUMNS * ROWS],
+            cursor: (0, 0),
+        }
+    }
+}
+
+impl Screen {
+    /// The screen shown by `memory`, the character and attribute bytes of
+    /// each cell as stored in video memory
+    pub fn from_memory(memory: &[u8], cursor: (u8, u8)) -> Screen {
+        let mut screen = Screen {
+            cursor,
+            ..Default::default()
+        };
+        for (cell, pair) in screen.cells.iter_mut().zip(memory.chunks_exact(2)) {
+            *cell = (pair[0], pair[1]);
+        }
+        screen
+    }
+
+    /// Each row of the screen as text, without trailing spaces
+    pub fn lines(&self) -> Vec<String> {
+        self.cells
+            .chunks(COLUMNS)
+            .map(|row| {
+                let line: String = row.iter().map(|&(byte, _)| character(byte)).collect();
+                line.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    /// The screen as text, one line per row, leaving out the blank rows at
+    /// the bottom
+    pub fn text(&self) -> String {
+        let lines = self.lines();
+        let used = lines
+            .iter()
+            .rposition(|line| !line.is_empty())
+            .map_or(0, |last| last + 1);
+        lines[..used]
+            .iter()
+            .map(|line| format!("{line}\n"))
+            .collect()
+    }
+
+    /// Whether nothing but spaces is on the screen
+    pub fn is_blank(&self) -> bool {
+        self.lines().iter().all(String::is_empty)
+    }
+
+    /// Draws the screen as a 640x400 picture in the colours of a VGA
+    ///
+    /// Letters, digits and punctuation, the box drawing characters, shades
+    /// and blocks are drawn; other characters are shown as a small box.
+    pub fn render(&self) -> Image {
+        let (width, height) = CELL;
+        let mut image = Image::new(COLUMNS * width, ROWS * height);
+        for (index, &(byte, attribute)) in self.cells.iter().enumerate() {
+            let (top, left) = (index / COLUMNS * height, index % COLUMNS * width);
+            let foreground = EGA_PALETTE[(attribute & 0x0F) as usize];
+            let background = EGA_PALETTE[(attribute >> 4 & 0x07) as usize];
+            for (row, bits) in glyph(byte).into_iter().enumerate() {
+                for x in 0..width {
+                    let colour = if bits & 0x80 >> x != 0 {
+                        foreground
+                    } else {
+                        background
+                    };
+                    image.set(left + x, top + row * 2, colour);
+                    image.set(left + x, top + row * 2 + 1, colour);
+                }
+            }
+        }
+        image
+    }
+}
+
+impl Display for Screen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+/// The character `byte` shows on screen
+fn character(byte: u8) -> char {
+    match byte {
+        0x00 => ' ',
+        0x01..0x20 => CP437_LOW[byte as usize - 1],
+        0x7F => '⌂',
+        _ => decode_cp437(byte),
+    }
+}
+
+/// The 8x8 glyph drawn for `byte`
+fn glyph(byte: u8) -> [u8; 8] {
+    match byte {
+        0x20..0x7F => FONT[byte as usize - 0x20],
+        0xB0 => [0x22, 0x88, 0x22, 0x88, 0x22, 0x88, 0x22, 0x88],
+        0xB1 => [0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55],
+        0xB2 => [0xDD, 0x77, 0xDD, 0x77, 0xDD, 0x77, 0xDD, 0x77],
+        0xB3..=0xDA => box_glyph(BOX_LINES[byte as usize - 0xB3]),
+        0xDB => [0xFF; 8],
+        0xDC => [0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF],
+        0xDD => [0xF0; 8],
+        0xDE => [0x0F; 8],
+        0xDF => [0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00],
+        0xFE => [0x00, 0x00, 0x3C, 0x3C, 0x3C, 0x3C, 0x00, 0x00],
+        0x00 | 0xFF => [0x00; 8],
+        _ => [0x00, 0x7E, 0x42, 0x42, 0x42, 0x42, 0x7E, 0x00],
+    }
+}
+
+/// Draws a box drawing character from the lines leaving its middle
+fn box_glyph([up, down, left, right]: [u8; 4]) -> [u8; 8] {
+    let offsets = |lines: u8| match lines {
+        1 => &[3][..],
+        2 => &[2, 4][..],
+        _ => &[][..],
+    };
+    let mut glyph = [0u8; 8];
+    for &x in offsets(up) {
+        for row in &mut glyph[..=4] {
+            *row |= 0x80 >> x;
+        }
+    }
+    for &x in offsets(down) {
+        for row in &mut glyph[3..] {
+            *row |= 0x80 >> x;
+        }
+    }
+    for &y in offsets(left) {
+        glyph[y] |= 0xF8;
+    }
+    for &y in offsets(right) {
+        glyph[y] |= 0x1F;
+    }
+    glyph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_keeps_symbols_and_drops_the_blank_bottom() {
+        let mut screen = Screen::default();
+        for (cell, &byte) in screen.cells.iter_mut().zip(b"\x01 A\xDB\0") {
+            cell.0 = byte;
+        }
+        screen.cells[COLUMNS * 2].0 = b'z';
+        assert_eq!(screen.text(), "☺ A█\n\nz\n");
+        assert!(!screen.is_blank());
+        assert!(Screen::default().is_blank());
+        assert_eq!(Screen::default().text(), "");
+    }
+
+    #[test]
+    fn render_uses_the_attribute_colours() {
+        let memory = [0xDB, 0x1E, b' ', 0x4F];
+        let screen = Screen::from_memory(&memory, (0, 0));
+        let image = screen.render();
+        let pixel = |x: usize, y: usize| {
+            let at = (y * image.width + x) * 3;
+            image.rgb[at..at + 3].to_vec()
+        };
+        // yellow on blue, but the block covers the whole cell
+        assert_eq!(pixel(0, 0), [0xFF, 0xFF, 0x55]);
+        assert_eq!(pixel(7, 15), [0xFF, 0xFF, 0x55]);
+        // red background behind a space
+        assert_eq!(pixel(8, 0), [0xAA, 0x00, 0x00]);
+        assert_eq!(pixel(16, 0), [0x00, 0x00, 0x00]);
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{InstructionInfoFactory, OpAccess, Register};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+use crate::protection::value_before;
+
+/// An instruction that writes over the program's own code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CodeWrite {
+    /// The address of the writing instruction
+    pub address: Address,
+    /// The first byte written, inside a decoded instruction
+    pub target: Address,
+    /// The register pointing at the code, if the write goes through one
+    pub pointer: Option<Register>,
+}
+
+impl Display for CodeWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "self-modifying code: writes to the code at 0x{:04x}",
+            self.target
+        )?;
+        if let Some(pointer) = self.pointer {
+            write!(f, " through {pointer:?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds the instructions that write to addresses decoded as code
+///
+/// The address written must be fixed, or held in BX, SI, DI or BP after a
+/// `mov` just before the write sets it, as a decoding loop walking over a
+/// packed payload does. The bytes written may be garbage decoded as code,
+/// which is what an encrypted payload looks like before it runs.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::samples;
+/// use disassembler::selfmod::find_code_writes;
+///
+/// let packed = samples::find("packed").unwrap();
+/// let writes = find_code_writes(&Disassembler::new(packed.bytes.to_vec()));
+/// assert_eq!(writes[0].target, 0x10F);
+/// ```
+pub fn find_code_writes(disassembler: &Disassembler) -> Vec<CodeWrite> {
+    let instructions = &disassembler.instructions.0;
+    let is_code = |address: Address| {
+        let after = instructions.partition_point(|instruction| instruction.ip() <= address as u64);
+        after > 0
+            && !is_data(&instructions[after - 1])
+            && (address as u64) < instructions[after - 1].next_ip()
+    };
+    let mut factory = InstructionInfoFactory::new();
+    let mut found = Vec::new();
+    for (index, instruction) in instructions.iter().enumerate() {
+        if is_data(instruction) {
+            continue;
+        }
+        let writes: Vec<_> = factory
+            .info(instruction)
+            .used_memory()
+            .iter()
+            .filter(|memory| {
+                !matches!(
+                    memory.access(),
+                    OpAccess::Read | OpAccess::CondRead | OpAccess::NoMemAccess
+                ) && memory.index() == Register::None
+                    && matches!(memory.segment(), Register::DS | Register::ES | Register::CS)
+            })
+            .map(|memory| (memory.base(), memory.displacement() as u16))
+            .collect();
+        for (base, displacement) in writes {
+            let pointer = (base != Register::None).then_some(base);
+            let start = match pointer {
+                None => Some(0),
+                Some(register) => value_before(&mut factory, instructions, index, register),
+            };
+            let Some(target) = start.map(|start| start.wrapping_add(displacement)) else {
+                continue;
+            };
+            if is_code(target) {
+                found.push(CodeWrite {
+                    address: instruction.ip() as Address,
+                    target,
+                    pointer,
+                });
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Writes to code
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn fixed_and_pointer_writes_into_code_are_found() {
+        let data = vec![
+            0xC6, 0x06, 0x0E, 0x01, 0xC3, // mov byte [0x10e], 0xc3
+            0xBF, 0x0F, 0x01, // mov di, 0x10f
+            0xB0, 0x90, // mov al, 0x90
+            0xAA, // stosb
+            0xC7, 0x06, 0x20, 0x01, 0x00, 0x00, // mov word [0x120], 0
+            0x90, // 0x10e: nop
+            0x90, // 0x10f: nop
+            0xC3, // ret
+        ];
+        let writes = find_code_writes(&Disassembler::new(data));
+        assert_eq!(
+            writes,
+            vec![
+                CodeWrite {
+                    address: 0x100,
+                    target: 0x10E,
+                    pointer: None,
+                },
+                CodeWrite {
+                    address: 0x10A,
+                    target: 0x10F,
+                    pointer: Some(Register::DI),
+                },
+            ]
+        );
+        assert_eq!(
+            writes[1].to_string(),
+            "self-modifying code: writes to the code at 0x010f through DI"
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use crate::consts::Address;
+
+/// A companion file, such as data or an overlay, mapped into the program's address space
+///
+/// Reads and jumps into the mapped range resolve to the file's bytes, so
+/// strings and tables a program expects to find there can be recovered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuxFile {
+    /// A name for the file, shown in the listing
+    pub name: String,
+    /// The address the file's first byte is mapped at
+    pub address: Address,
+    /// The contents of the file
+    pub data: Vec<u8>,
+}
+
+impl AuxFile {
+    /// The address just past the mapped bytes, clamped to the end of the segment
+    pub fn end(&self) -> u32 {
+        (self.address as u32 + self.data.len() as u32).min(0x10000)
+    }
+
+    /// Returns true if `address` falls inside the mapped bytes
+    pub fn contains(&self, address: Address) -> bool {
+        address >= self.address && (address as u32) < self.end()
+    }
+
+    /// The mapped bytes from `address` to the end of the file
+    pub fn bytes_at(&self, address: Address) -> Option<&[u8]> {
+        if !self.contains(address) {
+            return None;
+        }
+        self.data.get((address - self.address) as usize..)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Address ranges
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn mapping_covers_exactly_the_file() {
+        let aux = AuxFile {
+            name: "DATA.BIN".to_string(),
+            address: 0x2000,
+            data: vec![1, 2, 3],
+        };
+        assert!(!aux.contains(0x1FFF));
+        assert_eq!(aux.bytes_at(0x2001), Some(&[2, 3][..]));
+        assert!(!aux.contains(0x2003));
+    }
+
+    #[test]
+    fn mapping_stops_at_the_end_of_the_segment() {
+        let aux = AuxFile {
+            name: "BIG.BIN".to_string(),
+            address: 0xFFFE,
+            data: vec![0; 16],
+        };
+        assert_eq!(aux.end(), 0x10000);
+        assert!(aux.contains(0xFFFF));
+    }
+}
@@ -0,0 +1,373 @@
+use std::fmt::{self, Display};
+
+use crate::consts::{Address, COM_OFFSET};
+use crate::rules::{Pattern, RuleError, parse_blocks, parse_bytes, parse_number, unquote};
+
+/// How a virus attaches itself to the program it infects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Infection {
+    /// The virus is placed before the host, which is moved up intact
+    Prepend,
+    /// The virus is placed after the host, whose first bytes are replaced
+    /// with a jump to it
+    Append,
+    /// The virus overwrites the start of the host, which is lost
+    Overwrite,
+}
+
+impl Display for Infection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Infection::Prepend => write!(f, "prepended"),
+            Infection::Append => write!(f, "appended"),
+            Infection::Overwrite => write!(f, "overwriting"),
+        }
+    }
+}
+
+/// Where an appending virus keeps the host's original first bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Saved {
+    /// At a fixed offset into the virus body
+    Offset(usize),
+    /// At the address in the word at an offset into the match, plus a
+    /// displacement, for viruses that point a register at their data
+    Pointer {
+        /// The offset of the word into the match
+        word: usize,
+        /// What is added to the word
+        plus: usize,
+    },
+}
+
+/// A known virus: the bytes that identify it and how it infects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// The name of the virus
+    pub name: String,
+    /// Bytes found in every copy of the virus
+    pub pattern: Pattern,
+    /// How the virus attaches itself
+    pub infection: Infection,
+    /// How far into the virus body the pattern is
+    pub offset: usize,
+    /// How long the virus body is, if it is always the same
+    pub length: Option<usize>,
+    /// Where an appending virus keeps the host's original first bytes
+    pub saved: Option<Saved>,
+    /// How many of the host's first bytes are saved
+    pub saved_length: usize,
+    /// How many bytes the virus adds after the host, such as a marker
+    pub trailer: usize,
+    /// A description of the virus
+    pub comment: Option<String>,
+}
+
+/// An infection found in a program
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Detection {
+    /// The name of the virus
+    pub virus: String,
+    /// How the virus attached itself
+    pub infection: Infection,
+    /// The first address of the virus body
+    pub start: Address,
+    /// The address just past the virus body
+    pub end: Address,
+    /// The original program, if it can be put back together
+    pub host: Option<Vec<u8>>,
+    /// The signature's description of the virus
+    pub comment: Option<String>,
+}
+
+impl Display for Detection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} virus {} at 0x{:04x}-0x{:04x}",
+            self.virus, self.infection, self.start, self.end
+        )?;
+        if let Some(host) = &self.host {
+            write!(
+                f,
+                ", the {}-byte original program can be extracted",
+                host.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A set of virus signatures, loaded from a signature pack
+///
+/// No signatures are built in: a pack is a separate file, so it can be
+/// kept up to date, or left out, without rebuilding. The repository ships
+/// one for a few well-documented viruses in `signatures/dos-viruses.sig`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SignaturePack(pub Vec<Signature>);
+
+impl SignaturePack {
+    /// Parses a signature pack
+    ///
+    /// Packs use the rule file syntax with `virus` blocks. `bytes` and
+    /// `infection` (`prepend`, `append` or `overwrite`) are required.
+    /// `length` is the size of the virus body and `offset` how far into it
+    /// the bytes are. An appending virus's saved host bytes are found with
+    /// `saved`, either an offset into the body or `word N + K`, the word N
+    /// bytes into the match plus K; `saved_length` says how many there are,
+    /// 3 by default. `trailer` counts bytes the virus adds after the host.
+    ///
+    /// ```text
+    /// virus Example {
+    ///     bytes = { BF 00 01 B9 03 00 F3 A4 }
+    ///     infection = append
+    ///     length = 512
+    ///     saved = 0x1F0
+    ///     comment = "restores three bytes at 0x100"
+    /// }
+    /// ```
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::signatures::{Infection, SignaturePack};
+    ///
+    /// let pack = "virus Demo {\n bytes = EB FE\n infection = prepend\n length = 2\n}";
+    /// let detections = SignaturePack::parse(pack).unwrap().scan(&[0xEB, 0xFE, 0xC3]);
+    /// assert_eq!(detections[0].infection, Infection::Prepend);
+    /// assert_eq!(detections[0].host, Some(vec![0xC3]));
+    /// ```
+    pub fn parse(text: &str) -> Result<SignaturePack, RuleError> {
+        parse_blocks(text, "virus")?
+            .into_iter()
+            .map(|block| {
+                let line = block.line;
+                let name = block.name;
+                let error = |message: String| RuleError { line, message };
+                let mut pattern = None;
+                let mut infection = None;
+                let mut offset = 0;
+                let mut length = None;
+                let mut saved = None;
+                let mut saved_length = 3;
+                let mut trailer = 0;
+                let mut comment = None;
+                for (key, value) in block.keys {
+                    let number = |value: &str| {
+                        parse_number(value)
+                            .ok_or_else(|| error(format!("virus {name}: invalid {key} `{value}`")))
+                    };
+                    match key.as_str() {
+                        "bytes" => {
+                            pattern =
+                                Some(parse_bytes(&format!("virus {name}"), &value).map_err(error)?)
+                        }
+                        "infection" => {
+                            infection = Some(match value.as_str() {
+                                "prepend" => Infection::Prepend,
+                                "append" => Infection::Append,
+                                "overwrite" => Infection::Overwrite,
+                                other => {
+                                    return Err(error(format!(
+                                        "virus {name}: unknown infection `{other}`"
+                                    )));
+                                }
+                            })
+                        }
+                        "offset" => offset = number(&value)?,
+                        "length" => length = Some(number(&value)?),
+                        "saved" => {
+                            saved = Some(match value.strip_prefix("word ") {
+                                Some(pointer) => {
+                                    let (word, plus) =
+                                        pointer.split_once('+').unwrap_or((pointer, "0"));
+                                    Saved::Pointer {
+                                        word: number(word.trim())?,
+                                        plus: number(plus.trim())?,
+                                    }
+                                }
+                                None => Saved::Offset(number(&value)?),
+                            })
+                        }
+                        "saved_length" => saved_length = number(&value)?,
+                        "trailer" => trailer = number(&value)?,
+                        "comment" => comment = Some(unquote(&value)),
+                        other => {
+                            return Err(error(format!("virus {name}: unknown key `{other}`")));
+                        }
+                    }
+                }
+                Ok(Signature {
+                    pattern: pattern.ok_or_else(|| error(format!("virus {name} has no bytes")))?,
+                    infection: infection
+                        .ok_or_else(|| error(format!("virus {name} has no infection")))?,
+                    name,
+                    offset,
+                    length,
+                    saved,
+                    saved_length,
+                    trailer,
+                    comment,
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(SignaturePack)
+    }
+
+    /// Finds every virus in the pack in `data`, a program loaded at
+    /// [`COM_OFFSET`], once per virus
+    ///
+    /// The region each virus takes up is worked out from how it infects and
+    /// its length: a prepended or overwriting virus starts the file, an
+    /// appended one starts `offset` bytes before its signature and runs to
+    /// its length or the end of the file.
+    pub fn scan(&self, data: &[u8]) -> Vec<Detection> {
+        let mut detections = Vec::new();
+        for signature in &self.0 {
+            let Some(found) =
+                (0..data.len()).find(|&offset| signature.pattern.match_at(data, offset).is_some())
+            else {
+                continue;
+            };
+            let start = match signature.infection {
+                Infection::Append => found.saturating_sub(signature.offset),
+                Infection::Prepend | Infection::Overwrite => 0,
+            };
+            let end = signature
+                .length
+                .map_or(data.len(), |length| (start + length).min(data.len()));
+            detections.push(Detection {
+                virus: signature.name.clone(),
+                infection: signature.infection,
+                start: address(start),
+                end: address(end),
+                host: host(signature, data, found, start, end),
+                comment: signature.comment.clone(),
+            });
+        }
+        detections.sort_by_key(|detection| detection.start);
+        detections
+    }
+}
+
+/// The address of an offset into the program
+fn address(offset: usize) -> Address {
+    (COM_OFFSET as usize + offset).min(Address::MAX as usize) as Address
+}
+
+/// Puts the original program back together, for a virus found at `found`
+/// whose body is at `start..end`
+fn host(
+    signature: &Signature,
+    data: &[u8],
+    found: usize,
+    start: usize,
+    end: usize,
+) -> Option<Vec<u8>> {
+    match signature.infection {
+        Infection::Overwrite => None,
+        Infection::Prepend => {
+            signature.length?;
+            let host = data.get(end..data.len().checked_sub(signature.trailer)?)?;
+            (!host.is_empty()).then(|| host.to_vec())
+        }
+        Infection::Append => {
+            let saved = match signature.saved? {
+                Saved::Offset(offset) => start + offset,
+                Saved::Pointer { word, plus } => {
+                    let at = found + word;
+                    let pointer = u16::from_le_bytes([*data.get(at)?, *data.get(at + 1)?]);
+                    (pointer as usize + plus).checked_sub(COM_OFFSET as usize)?
+                }
+            };
+            let original = data.get(saved..saved + signature.saved_length)?;
+            if start < original.len() || end < saved + original.len() {
+                return None;
+            }
+            let mut host = data[..start].to_vec();
+            host[..original.len()].copy_from_slice(original);
+            Some(host)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Signature packs
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn appended_viruses_give_back_the_saved_bytes() {
+        let pack = SignaturePack::parse(
+            r#"
+            virus Restorer {
+                bytes = { BA ?? ?? 8B F2 83 C6 04 BF 00 01 B9 03 00 F3 A4 }
+                infection = append
+                offset = 1
+                length = 0x19
+                saved = word 1 + 4
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(pack.0[0].saved, Some(Saved::Pointer { word: 1, plus: 4 }));
+
+        let mut data = vec![
+            0xE9, 0x02, 0x00, // jmp 0x105, over the first host bytes
+            0xCD, 0x21, // the rest of mov ax, 0x4c00 / int 21h
+        ];
+        data.extend([
+            0x51, // 0x105: push cx
+            0xBA, 0x17, 0x01, // mov dx, 0x117
+            0x8B, 0xF2, // mov si, dx
+            0x83, 0xC6, 0x04, // add si, 4
+            0xBF, 0x00, 0x01, // mov di, 0x100
+            0xB9, 0x03, 0x00, // mov cx, 3
+            0xF3, 0xA4, // rep movsb
+            0x59, // pop cx
+            0x00, 0x00, 0x00, 0x00, // 0x117: data
+            0xB8, 0x00, 0x4C, // the saved first bytes
+        ]);
+        let detections = pack.scan(&data);
+        assert_eq!(detections.len(), 1);
+        assert_eq!((detections[0].start, detections[0].end), (0x105, 0x11E));
+        assert_eq!(detections[0].host, Some(vec![0xB8, 0x00, 0x4C, 0xCD, 0x21]));
+        assert_eq!(
+            detections[0].to_string(),
+            "Restorer virus appended at 0x0105-0x011e, the 5-byte original program can be extracted"
+        );
+    }
+
+    #[test]
+    fn prepended_viruses_leave_the_host_after_them() {
+        let pack = SignaturePack::parse(
+            "virus Front {\n bytes = B4 E0 CD 21\n infection = prepend\n length = 4\n trailer = 2\n}",
+        )
+        .unwrap();
+        let data = vec![0xB4, 0xE0, 0xCD, 0x21, 0xCD, 0x20, b'M', b'z'];
+        let detections = pack.scan(&data);
+        assert_eq!((detections[0].start, detections[0].end), (0x100, 0x104));
+        assert_eq!(detections[0].host, Some(vec![0xCD, 0x20]));
+    }
+
+    #[test]
+    fn malformed_signatures_are_rejected() {
+        let err = SignaturePack::parse("virus A {\n bytes = 90\n}").unwrap_err();
+        assert!(err.message.contains("no infection"));
+        let err =
+            SignaturePack::parse("virus A {\n bytes = 90\n infection = sideways\n}").unwrap_err();
+        assert!(err.message.contains("sideways"));
+        let err = SignaturePack::parse("virus A {\n bytes = 90\n length = many\n}").unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+
+    #[test]
+    fn the_shipped_pack_parses() {
+        let pack = include_str!("../../signatures/dos-viruses.sig");
+        assert!(!SignaturePack::parse(pack).unwrap().0.is_empty());
+    }
+}
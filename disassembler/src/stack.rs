@@ -0,0 +1,179 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{Instruction, Mnemonic, OpKind, Register};
+
+use crate::consts::Address;
+use crate::disassemble::is_data;
+use crate::syscall::{SyscallList, SyscallType};
+
+/// Where DOS puts the stack pointer when it starts a COM program
+pub const INITIAL_SP: u16 = 0xFFFE;
+
+/// A way an instruction moves the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StackChange {
+    /// Loads SP, with the value if it is a constant
+    Pointer {
+        /// The new stack pointer, if it is known
+        value: Option<u16>,
+    },
+    /// Loads SS
+    Segment,
+    /// Loads SS and SP together with `lss`
+    Both,
+}
+
+/// An instruction that moves the stack somewhere else
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StackMove {
+    /// The address of the instruction
+    pub address: Address,
+    /// What it changes
+    pub change: StackChange,
+}
+
+impl Display for StackMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.change {
+            StackChange::Pointer { value: Some(value) } => {
+                write!(f, "moves the stack: SP = 0x{value:04x}")
+            }
+            StackChange::Pointer { value: None } => write!(f, "moves the stack: loads SP"),
+            StackChange::Segment => write!(f, "moves the stack: loads SS"),
+            StackChange::Both => write!(f, "moves the stack: loads SS:SP"),
+        }
+    }
+}
+
+/// How a program treats the stack DOS gives it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StackModel {
+    /// The int 21h/4Ah calls resizing the program's memory block
+    pub resizes: Vec<Address>,
+    /// The instructions moving the stack
+    pub moves: Vec<StackMove>,
+}
+
+impl StackModel {
+    /// Finds the instructions that load SP or SS and the calls resizing memory
+    ///
+    /// Adjusting SP with `add`, `sub`, `push` or `pop` is ordinary stack use
+    /// and not counted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::stack::StackModel;
+    ///
+    /// // mov bx, 0x100 / mov ah, 0x4a / int 21h / mov sp, 0x1000 / ret
+    /// let data = vec![0xBB, 0x00, 0x01, 0xB4, 0x4A, 0xCD, 0x21, 0xBC, 0x00, 0x10, 0xC3];
+    /// let disassembler = Disassembler::new(data);
+    /// let model = StackModel::analyze(&disassembler.instructions.0, &disassembler.syscall_list);
+    /// assert!(model.has_own_stack());
+    /// assert_eq!(model.moves[0].to_string(), "moves the stack: SP = 0x1000");
+    /// ```
+    pub fn analyze(instructions: &[Instruction], syscalls: &SyscallList) -> StackModel {
+        let resizes = syscalls
+            .0
+            .iter()
+            .filter(|syscall| syscall.number == SyscallType::ReallocateMemory)
+            .map(|syscall| syscall.address)
+            .collect();
+        let moves = instructions
+            .iter()
+            .filter(|instruction| !is_data(instruction))
+            .filter_map(|instruction| {
+                Some(StackMove {
+                    address: instruction.ip() as Address,
+                    change: stack_change(instruction)?,
+                })
+            })
+            .collect();
+        StackModel { resizes, moves }
+    }
+
+    /// Returns true if the program shrinks its memory block and moves the
+    /// stack into what it kept
+    pub fn has_own_stack(&self) -> bool {
+        !self.resizes.is_empty()
+            && self.moves.iter().any(|stack_move| {
+                matches!(
+                    stack_move.change,
+                    StackChange::Pointer { .. } | StackChange::Both
+                )
+            })
+    }
+}
+
+/// How `instruction` moves the stack, if it loads SP or SS
+fn stack_change(instruction: &Instruction) -> Option<StackChange> {
+    if instruction.mnemonic() == Mnemonic::Lss {
+        return Some(StackChange::Both);
+    }
+    if instruction.op0_kind() != OpKind::Register {
+        return None;
+    }
+    let loads = matches!(
+        instruction.mnemonic(),
+        Mnemonic::Mov | Mnemonic::Pop | Mnemonic::Xchg
+    );
+    match instruction.op0_register() {
+        Register::SS if loads => Some(StackChange::Segment),
+        Register::SP if loads && instruction.mnemonic() != Mnemonic::Pop => {
+            let value =
+                (instruction.op1_kind() == OpKind::Immediate16).then(|| instruction.immediate16());
+            Some(StackChange::Pointer { value })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassemble::Disassembler;
+
+    fn model(data: Vec<u8>) -> StackModel {
+        let disassembler = Disassembler::new(data);
+        StackModel::analyze(&disassembler.instructions.0, &disassembler.syscall_list)
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Moving the stack
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn local_variables_do_not_move_the_stack() {
+        // sub sp, 4 / push ax / pop ax / add sp, 4 / ret
+        let data = vec![0x83, 0xEC, 0x04, 0x50, 0x58, 0x83, 0xC4, 0x04, 0xC3];
+        assert_eq!(model(data), StackModel::default());
+    }
+
+    #[test]
+    fn stack_segment_and_pointer_loads_are_found() {
+        let data = vec![
+            0x8C, 0xC8, // mov ax, cs
+            0x8E, 0xD0, // mov ss, ax
+            0x89, 0xDC, // mov sp, bx
+            0xC3, // ret
+        ];
+        let found = model(data);
+        assert_eq!(
+            found.moves,
+            vec![
+                StackMove {
+                    address: 0x102,
+                    change: StackChange::Segment
+                },
+                StackMove {
+                    address: 0x104,
+                    change: StackChange::Pointer { value: None }
+                },
+            ]
+        );
+        assert!(!found.has_own_stack(), "memory was not resized");
+    }
+}
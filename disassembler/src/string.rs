@@ -1,7 +1,11 @@
+use std::fmt::Display;
+
 use crate::consts::Address;
+use crate::style::Style;
 
 /// A struct representing a string constant in the disassembly
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StringConstant {
     /// the raw value of the string
     pub value: String,
@@ -38,7 +42,7 @@ impl StringConstant {
     pub fn new(value: &str, start: Address, end: Address) -> Self {
         assert_eq!(
             end - start,
-            value.bytes().len() as Address,
+            value.len() as Address,
             "The length of the string does not match the length of the address range"
         );
 
@@ -67,6 +71,25 @@ impl StringConstant {
         self.value.len()
     }
 
+    /// Returns true if the string has no characters
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use disassembler::string::StringConstant;
+    ///
+    /// assert!(StringConstant::new("", 0x1000, 0x1000).is_empty());
+    /// assert!(!StringConstant::new("A", 0x1000, 0x1001).is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty()
+    }
+
+    /// Guesses the language the string is written in, see [`detect_language`]
+    pub fn language(&self) -> Option<Language> {
+        detect_language(&self.value)
+    }
+
     /// Returns the string constant as a assembly `db` statement
     ///
     /// # Returns
@@ -81,50 +104,267 @@ impl StringConstant {
     ///
     /// let string_constant = StringConstant::new("Hello, World!\r\n$", 0x1000, 0x1010);
     ///
-    /// assert_eq!(string_constant.as_db_statement(), "db \"Hello, World!\", 0x0D, 0x0A, \"$\"");
+    /// assert_eq!(string_constant.as_db_statement(), "db \"Hello, World!\", 0x0d, 0x0a, \"$\"");
     /// ```
     pub fn as_db_statement(&self) -> String {
-        let mut db_statement = String::from("db ");
-        let mut in_quotes = false;
-
-        for byte in self.value.bytes() {
-            let is_printable = byte.is_ascii_graphic() || byte == b' ';
-
-            if is_printable {
-                if !in_quotes {
-                    if !db_statement.ends_with("db ") {
-                        db_statement.push_str(", ");
-                    }
-                    db_statement.push('"');
-                    in_quotes = true;
-                }
-                if byte == b'"' {
-                    db_statement.push_str("\\\"");
-                } else {
-                    db_statement.push(byte as char);
-                }
-            } else {
-                if in_quotes {
-                    db_statement.push('"');
-                    in_quotes = false;
-                }
-                if !db_statement.ends_with("db ") && !db_statement.ends_with(", ") {
+        self.db_statement(&Style::default())
+    }
+
+    /// Returns the string constant as a `db` statement written in `style`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use disassembler::string::StringConstant;
+    /// use disassembler::style::{Case, Style};
+    ///
+    /// let string_constant = StringConstant::new("Hi\r\n$", 0x1000, 0x1005);
+    /// let style = Style { hex_case: Case::Upper, mnemonic_case: Case::Upper, ..Style::default() };
+    ///
+    /// assert_eq!(string_constant.db_statement(&style), "DB \"Hi\", 0x0D, 0x0A, \"$\"");
+    /// ```
+    pub fn db_statement(&self, style: &Style) -> String {
+        // each character holds one byte of the program
+        text_directive(
+            self.value.chars().map(|c| u8::try_from(c).unwrap_or(b'?')),
+            style,
+        )
+    }
+}
+
+/// Writes `bytes` as a `db` directive, printable runs in quotes and other
+/// bytes as numbers
+pub(crate) fn text_directive(bytes: impl IntoIterator<Item = u8>, style: &Style) -> String {
+    let directive = format!("{} ", style.mnemonic("db"));
+    let mut db_statement = directive.clone();
+    let mut in_quotes = false;
+
+    for byte in bytes {
+        let is_printable = byte.is_ascii_graphic() || byte == b' ';
+
+        if is_printable {
+            if !in_quotes {
+                if db_statement != directive {
                     db_statement.push_str(", ");
                 }
-                db_statement.push_str(&format!("0x{:02X}", byte));
+                db_statement.push('"');
+                in_quotes = true;
+            }
+            if byte == b'"' {
+                db_statement.push_str("\\\"");
+            } else {
+                db_statement.push(byte as char);
             }
+        } else {
+            if in_quotes {
+                db_statement.push('"');
+                in_quotes = false;
+            }
+            if db_statement != directive && !db_statement.ends_with(", ") {
+                db_statement.push_str(", ");
+            }
+            db_statement.push_str(&style.hex(byte as u64, 2));
         }
+    }
 
-        if in_quotes {
-            db_statement.push('"');
-        }
+    if in_quotes {
+        db_statement.push('"');
+    }
+
+    db_statement
+}
+
+/// A language a string may be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Language {
+    /// English
+    English,
+    /// German
+    German,
+    /// French
+    French,
+    /// Spanish
+    Spanish,
+}
+
+impl Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// Common short words of each language, as they appear in program messages
+const COMMON_WORDS: [(Language, &[&str]); 4] = [
+    (
+        Language::English,
+        &[
+            "the", "and", "is", "to", "of", "you", "not", "file", "for", "press", "any", "key",
+            "with", "are", "this", "your", "disk", "insert", "cannot", "found",
+        ],
+    ),
+    (
+        Language::German,
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "ein", "eine", "sie", "mit", "für",
+            "datei", "taste", "bitte", "fehler", "drücken", "auf", "den", "von", "zu",
+        ],
+    ),
+    (
+        Language::French,
+        &[
+            "le",
+            "les",
+            "et",
+            "est",
+            "pas",
+            "une",
+            "vous",
+            "pour",
+            "fichier",
+            "touche",
+            "appuyez",
+            "erreur",
+            "du",
+            "des",
+            "sur",
+            "avec",
+            "ne",
+            "introuvable",
+        ],
+    ),
+    (
+        Language::Spanish,
+        &[
+            "el",
+            "los",
+            "las",
+            "y",
+            "es",
+            "una",
+            "para",
+            "archivo",
+            "tecla",
+            "pulse",
+            "del",
+            "con",
+            "por",
+            "que",
+            "encontrado",
+            "presione",
+            "disco",
+        ],
+    ),
+];
+
+/// Letters that only some of the languages use
+const DISTINCT_LETTERS: [(Language, &str); 3] = [
+    (Language::German, "äöüß"),
+    (Language::French, "èêàçâôûë"),
+    (Language::Spanish, "ñ¿¡áíóú"),
+];
+
+/// The characters of code page 437 from 0x80 to 0xFF
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}',
+];
+
+/// Decodes a byte of code page 437 text
+///
+/// Bytes below 0x80 are ASCII, control characters included.
+///
+/// # Examples
+///
+/// ```
+/// use disassembler::string::decode_cp437;
+///
+/// assert_eq!(decode_cp437(b'A'), 'A');
+/// assert_eq!(decode_cp437(0x81), 'ü');
+/// ```
+pub fn decode_cp437(byte: u8) -> char {
+    match byte {
+        0x00..0x80 => byte as char,
+        _ => CP437_HIGH[byte as usize - 0x80],
+    }
+}
+
+/// Encodes a character as a byte of code page 437, if it has one
+///
+/// # Examples
+///
+/// ```
+/// use disassembler::string::encode_cp437;
+///
+/// assert_eq!(encode_cp437('ß'), Some(0xE1));
+/// assert_eq!(encode_cp437('€'), None);
+/// ```
+pub fn encode_cp437(c: char) -> Option<u8> {
+    if c.is_ascii() {
+        return Some(c as u8);
+    }
+    CP437_HIGH
+        .iter()
+        .position(|&high| high == c)
+        .map(|index| 0x80 + index as u8)
+}
+
+/// Guesses the language of CP437 `text`
+///
+/// Each language scores a point for every one of its common words in the
+/// text and two for every letter only it uses. The best score wins if it is
+/// at least two and no other language has the same score, so short or
+/// ambiguous text gives `None`.
+///
+/// # Examples
+///
+/// ```
+/// use disassembler::string::{detect_language, Language};
+///
+/// assert_eq!(detect_language("Datei nicht gefunden"), Some(Language::German));
+/// assert_eq!(detect_language("Press any key$"), Some(Language::English));
+/// assert_eq!(detect_language("OK"), None);
+/// ```
+pub fn detect_language(text: &str) -> Option<Language> {
+    // strings are read one byte per character
+    let text: String = text
+        .chars()
+        .map(|c| u8::try_from(c).map_or(c, decode_cp437))
+        .flat_map(char::to_lowercase)
+        .collect();
+    let words: Vec<&str> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .collect();
 
-        db_statement
+    let mut scores: Vec<(Language, usize)> = COMMON_WORDS
+        .iter()
+        .map(|&(language, common)| {
+            let matching = words.iter().filter(|word| common.contains(word)).count();
+            let letters = DISTINCT_LETTERS
+                .iter()
+                .filter(|&&(other, _)| other == language)
+                .map(|&(_, letters)| text.chars().filter(|&c| letters.contains(c)).count())
+                .sum::<usize>();
+            (language, matching + 2 * letters)
+        })
+        .collect();
+    scores.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    match scores.as_slice() {
+        [(language, best), (_, second), ..] if *best >= 2 && best > second => Some(*language),
+        _ => None,
     }
 }
 
 /// A struct representing a list of string constants
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StringConstantList(pub Vec<StringConstant>);
 
 impl StringConstantList {
@@ -179,6 +419,27 @@ impl StringConstantList {
             .iter()
             .find(|s| s.start <= address && s.end >= address)
     }
+
+    /// Sorts the string constants by address and drops exact duplicates
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use disassembler::string::{StringConstantList, StringConstant};
+    ///
+    /// let mut string_constant_list = StringConstantList::new();
+    /// string_constant_list.0.push(StringConstant::new("Bye$", 0x120, 0x124));
+    /// string_constant_list.0.push(StringConstant::new("Hi$", 0x110, 0x113));
+    /// string_constant_list.0.push(StringConstant::new("Bye$", 0x120, 0x124));
+    /// string_constant_list.sort();
+    ///
+    /// assert_eq!(string_constant_list.0.len(), 2);
+    /// assert_eq!(string_constant_list.0[0].value, "Hi$");
+    /// ```
+    pub fn sort(&mut self) {
+        self.0.sort_by_key(|string| (string.start, string.end));
+        self.0.dedup();
+    }
 }
 
 #[cfg(test)]
@@ -236,7 +497,7 @@ mod tests {
     #[test]
     fn db_statement_mixed_content() {
         let s = str_const("hi\x0D\x0A$", 0x0000);
-        assert_eq!(s.as_db_statement(), r#"db "hi", 0x0D, 0x0A, "$""#);
+        assert_eq!(s.as_db_statement(), r#"db "hi", 0x0d, 0x0a, "$""#);
     }
 
     #[test]
@@ -294,4 +555,44 @@ mod tests {
         assert_eq!(list1, list2);
         assert_ne!(list1, list3);
     }
+
+    // ─────────────────────────────────────────────────────────────────────────────
+    // 5. Language detection
+    // ─────────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn languages_are_told_apart() {
+        assert_eq!(
+            detect_language("Insert the disk and press any key"),
+            Some(Language::English)
+        );
+        assert_eq!(
+            detect_language("Fichier introuvable, appuyez sur une touche"),
+            Some(Language::French)
+        );
+        assert_eq!(
+            detect_language("Pulse una tecla para continuar"),
+            Some(Language::Spanish)
+        );
+    }
+
+    #[test]
+    fn cp437_letters_count_towards_their_language() {
+        // "Bitte Taste dr\x81cken" as read byte by byte from a CP437 program
+        let text: String = b"Bitte Taste dr\x81cken"
+            .iter()
+            .map(|&b| b as char)
+            .collect();
+        assert_eq!(detect_language(&text), Some(Language::German));
+        assert_eq!(detect_language("la"), None);
+    }
+
+    // ─────────────────────────────────────────────────────────────────────────────
+    // 6. Code page 437
+    // ─────────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn cp437_round_trips_every_byte() {
+        for byte in 0..=u8::MAX {
+            assert_eq!(encode_cp437(decode_cp437(byte)), Some(byte));
+        }
+    }
 }
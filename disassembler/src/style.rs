@@ -0,0 +1,248 @@
+use iced_x86::{FormatterOptions, Instruction, MemorySize, Mnemonic, NumberBase, OpKind, Register};
+
+use crate::consts::Address;
+
+/// Upper or lower case
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Case {
+    /// `0x01ff`, `mov`
+    #[default]
+    Lower,
+    /// `0x01FF`, `MOV`
+    Upper,
+}
+
+impl Case {
+    fn apply(self, text: &str) -> String {
+        match self {
+            Case::Lower => text.to_ascii_lowercase(),
+            Case::Upper => text.to_ascii_uppercase(),
+        }
+    }
+}
+
+/// The base the numbers in instructions are written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Radix {
+    /// Hex, except characters compared against, written as `'A'`, and
+    /// counts, written in decimal; see [`Style::immediate`]
+    #[default]
+    Contextual,
+    /// Every number in hex
+    Hex,
+    /// Every number in decimal
+    Decimal,
+}
+
+/// The largest value loaded into `cx` or `cl` that is taken to be a count
+const MAX_COUNT: u64 = 0xFF;
+
+/// How numbers, addresses and mnemonics are written in the listing
+///
+/// One policy covers the instructions, generated label names, `; 0x0100`
+/// offsets, byte dumps and `db` statements, so they all agree. The `0x`
+/// prefix is always lower case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Style {
+    /// The case of hex digits
+    pub hex_case: Case,
+    /// The case of mnemonics and `db`/`dw`
+    pub mnemonic_case: Case,
+    /// How many hex digits addresses are padded to
+    pub address_width: usize,
+    /// The base the numbers in instructions are written in; offsets, byte
+    /// dumps and `db` statements are always hex
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub radix: Radix,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            hex_case: Case::Lower,
+            mnemonic_case: Case::Lower,
+            address_width: 4,
+            radix: Radix::Contextual,
+        }
+    }
+}
+
+impl Style {
+    /// Writes `value` as `0x` and at least `width` hex digits
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::style::{Case, Style};
+    ///
+    /// let style = Style { hex_case: Case::Upper, ..Style::default() };
+    /// assert_eq!(style.hex(0x0d, 2), "0x0D");
+    /// ```
+    pub fn hex(&self, value: u64, width: usize) -> String {
+        format!("0x{}", self.digits(value, width))
+    }
+
+    /// Writes `value` as at least `width` hex digits with no prefix, as in byte dumps
+    pub fn digits(&self, value: u64, width: usize) -> String {
+        match self.hex_case {
+            Case::Lower => format!("{value:0width$x}"),
+            Case::Upper => format!("{value:0width$X}"),
+        }
+    }
+
+    /// Writes an address padded to the address width
+    pub fn address(&self, address: Address) -> String {
+        self.hex(address as u64, self.address_width)
+    }
+
+    /// Names a generated label after its address, e.g. `FUNC_0x0120`
+    pub fn label_name(&self, prefix: &str, address: Address) -> String {
+        format!("{prefix}_{}", self.address(address))
+    }
+
+    /// Writes a mnemonic or directive such as `db` in the mnemonic case
+    pub fn mnemonic(&self, mnemonic: &str) -> String {
+        self.mnemonic_case.apply(mnemonic)
+    }
+
+    /// Sets up an iced formatter to write instructions the same way
+    pub fn configure(&self, options: &mut FormatterOptions) {
+        options.set_hex_prefix("0x");
+        options.set_hex_suffix("");
+        options.set_uppercase_hex(self.hex_case == Case::Upper);
+        options.set_uppercase_mnemonics(self.mnemonic_case == Case::Upper);
+        options.set_number_base(match self.radix {
+            Radix::Decimal => NumberBase::Decimal,
+            Radix::Contextual | Radix::Hex => NumberBase::Hexadecimal,
+        });
+    }
+
+    /// Writes immediate `operand` of `instruction` the way it reads best, if
+    /// that is not hex and the radix is [`Radix::Contextual`]
+    ///
+    /// A printable character a byte is compared against is written as
+    /// `'A'`, except against `ah`, where DOS and the BIOS return function
+    /// results and scancodes. Counts loaded into `cx` or `cl` up to 255 and
+    /// shift counts are written in decimal.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::style::Style;
+    /// use iced_x86::{Decoder, DecoderOptions};
+    ///
+    /// let decode = |bytes: &[u8]| Decoder::new(16, bytes, DecoderOptions::NONE).decode();
+    /// let style = Style::default();
+    /// // cmp al, 0x59
+    /// assert_eq!(style.immediate(&decode(&[0x3C, 0x59]), 1).as_deref(), Some("'Y'"));
+    /// // mov cx, 0x10
+    /// assert_eq!(style.immediate(&decode(&[0xB9, 0x10, 0x00]), 1).as_deref(), Some("16"));
+    /// // mov ah, 0x4c
+    /// assert_eq!(style.immediate(&decode(&[0xB4, 0x4C]), 1), None);
+    /// ```
+    pub fn immediate(&self, instruction: &Instruction, operand: u32) -> Option<String> {
+        if self.radix != Radix::Contextual {
+            return None;
+        }
+        let value = match instruction.op_kind(operand) {
+            OpKind::Immediate8 => instruction.immediate8() as u64,
+            OpKind::Immediate16 => instruction.immediate16() as u64,
+            _ => return None,
+        };
+        let compared_byte = instruction.mnemonic() == Mnemonic::Cmp
+            && instruction.op_kind(operand) == OpKind::Immediate8
+            && match instruction.op0_kind() {
+                OpKind::Register => {
+                    instruction.op0_register().is_gpr8()
+                        && instruction.op0_register() != Register::AH
+                }
+                OpKind::Memory => instruction.memory_size() == MemorySize::UInt8,
+                _ => false,
+            };
+        // a quote has no plain way to be written between quotes
+        if compared_byte && (0x20..0x7F).contains(&value) && value != b'\'' as u64 {
+            return Some(format!("'{}'", value as u8 as char));
+        }
+        let count = match instruction.mnemonic() {
+            Mnemonic::Mov => {
+                matches!(instruction.op0_register(), Register::CX | Register::CL)
+                    && value <= MAX_COUNT
+            }
+            Mnemonic::Shl
+            | Mnemonic::Sal
+            | Mnemonic::Shr
+            | Mnemonic::Sar
+            | Mnemonic::Rol
+            | Mnemonic::Ror
+            | Mnemonic::Rcl
+            | Mnemonic::Rcr => true,
+            _ => false,
+        };
+        count.then(|| value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Numbers and names
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn addresses_are_padded_to_the_width() {
+        let style = Style::default();
+        assert_eq!(style.address(0x10b), "0x010b");
+        assert_eq!(style.label_name("FUNC", 0x10b), "FUNC_0x010b");
+
+        let wide = Style {
+            address_width: 8,
+            hex_case: Case::Upper,
+            ..Style::default()
+        };
+        assert_eq!(wide.address(0x10b), "0x0000010B");
+        assert_eq!(wide.digits(0xab, 2), "AB");
+    }
+
+    #[test]
+    fn mnemonic_case_applies_to_directives() {
+        let style = Style {
+            mnemonic_case: Case::Upper,
+            ..Style::default()
+        };
+        assert_eq!(style.mnemonic("db"), "DB");
+        assert_eq!(Style::default().mnemonic("DW"), "dw");
+    }
+
+    #[test]
+    fn immediates_follow_the_radix() {
+        use crate::disassemble::Disassembler;
+
+        // cmp al, 'Y' / mov cx, 16 / shl ax, 12 / mov ah, 0x4c / ret
+        let data = vec![
+            0x3C, 0x59, 0xB9, 0x10, 0x00, 0xC1, 0xE0, 0x0C, 0xB4, 0x4C, 0xC3,
+        ];
+        let mut disassembler = Disassembler::new(data);
+        let listing = disassembler.to_string();
+        for line in ["cmp al,'Y'", "mov cx,16", "shl ax,12", "mov ah,0x4c"] {
+            assert!(listing.contains(line), "{line} in {listing}");
+        }
+
+        disassembler.set_style(Style {
+            radix: Radix::Hex,
+            ..Style::default()
+        });
+        let listing = disassembler.to_string();
+        assert!(listing.contains("cmp al,0x59"), "{listing}");
+        assert!(listing.contains("mov cx,0x10"), "{listing}");
+
+        disassembler.set_style(Style {
+            radix: Radix::Decimal,
+            ..Style::default()
+        });
+        assert!(disassembler.to_string().contains("mov ah,76"));
+    }
+}
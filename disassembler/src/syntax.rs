@@ -0,0 +1,450 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{
+    Code, Encoder, EncodingKind, Formatter, Instruction, MandatoryPrefix, MasmFormatter,
+    NasmFormatter, OpCodeTableKind, OpKind, Register,
+};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+
+/// Something in how an instruction is encoded that NASM would do another way
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Quirk {
+    /// Two registers encoded with the direction bit set, where NASM puts the
+    /// first register in the r/m field
+    OperandOrder,
+    /// A longer or less usual opcode than the one NASM picks for the same
+    /// operands, such as `81 /0` for a small immediate or `FF /0` for
+    /// `inc ax`
+    LongerForm,
+    /// An undocumented alias, such as opcode `82`, `test` as `F6 /1` or `sal`
+    /// as `D1 /6`, which NASM never emits
+    Alias,
+    /// A displacement wider than its value needs
+    Displacement,
+    /// A prefix the listing does not show, such as a segment override naming
+    /// the segment the operand uses anyway or a repeated prefix
+    HiddenPrefix,
+    /// A near jump to a target a short jump reaches, which NASM shortens
+    NearBranch,
+}
+
+impl Display for Quirk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Quirk::OperandOrder => write!(f, "register operands encoded the other way round"),
+            Quirk::LongerForm => write!(f, "a longer form than NASM picks"),
+            Quirk::Alias => write!(f, "an undocumented alias opcode"),
+            Quirk::Displacement => write!(f, "a wider displacement than needed"),
+            Quirk::HiddenPrefix => write!(f, "a prefix the listing does not show"),
+            Quirk::NearBranch => write!(f, "a near jump NASM makes short"),
+        }
+    }
+}
+
+/// An instruction whose listed form NASM would assemble to other bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SyntaxIssue {
+    /// The address of the instruction
+    pub address: Address,
+    /// The instruction as the listing writes it
+    pub nasm: String,
+    /// The instruction as MASM would write it, to compare with
+    pub masm: String,
+    /// The bytes in the file
+    pub bytes: Vec<u8>,
+    /// The bytes NASM would assemble the listed instruction to
+    pub reassembled: Vec<u8>,
+    /// What NASM would do differently
+    pub quirks: Vec<Quirk>,
+}
+
+impl Display for SyntaxIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let quirks: Vec<String> = self.quirks.iter().map(Quirk::to_string).collect();
+        write!(
+            f,
+            "NASM assembles this to {}, not {}: {}",
+            hex(&self.reassembled),
+            hex(&self.bytes),
+            quirks.join(", ")
+        )
+    }
+}
+
+/// The legacy opcode valid in 16-bit code with the given opcode byte, `/r`
+/// group and operand size
+fn form(table: OpCodeTableKind, op_code: u32, group: i32, operand_size: u32) -> Option<Code> {
+    Code::values().find(|code| {
+        let info = code.op_code();
+        info.encoding() == EncodingKind::Legacy
+            && info.is_instruction()
+            && info.mode16()
+            && info.mandatory_prefix() == MandatoryPrefix::None
+            && info.table() == table
+            && info.op_code() == op_code
+            && info.group_index() == group
+            && info.operand_size() == operand_size
+    })
+}
+
+/// Returns true if `value`, sign-extended from a byte, is itself
+fn fits_byte(value: u16) -> bool {
+    (value as i16) >= i8::MIN as i16 && (value as i16) <= i8::MAX as i16
+}
+
+/// Returns true if `register` is AL or AX
+fn is_accumulator(register: Register) -> bool {
+    matches!(register, Register::AL | Register::AX)
+}
+
+/// Rewrites `instruction` into the form NASM assembles its listed text to,
+/// recording what changed in `quirks`
+fn as_nasm(instruction: &mut Instruction, nasm: &str, quirks: &mut Vec<Quirk>) {
+    let info = instruction.op_code();
+    let (op_code, group, size) = (info.op_code(), info.group_index(), info.operand_size());
+    let normal = info.table() == OpCodeTableKind::Normal;
+    let registers = instruction.op_count() == 2
+        && instruction.op0_kind() == OpKind::Register
+        && instruction.op1_kind() == OpKind::Register;
+    let mut set = |instruction: &mut Instruction, code: Option<Code>, quirk: Quirk| {
+        if let Some(code) = code {
+            instruction.set_code(code);
+            quirks.push(quirk);
+        }
+    };
+
+    if normal {
+        // add/or/adc/sbb/and/sub/xor/cmp and mov between two registers
+        let arithmetic = op_code < 0x40 && matches!(op_code & 7, 2 | 3);
+        if registers && (arithmetic || matches!(op_code, 0x8A | 0x8B)) {
+            let code = form(OpCodeTableKind::Normal, op_code - 2, -1, size);
+            set(instruction, code, Quirk::OperandOrder);
+        }
+        match op_code {
+            0x82 => {
+                let code = form(OpCodeTableKind::Normal, 0x80, group, size);
+                set(instruction, code, Quirk::Alias);
+            }
+            0xF6 | 0xF7 if group == 1 => {
+                let code = form(OpCodeTableKind::Normal, op_code, 0, size);
+                set(instruction, code, Quirk::Alias);
+            }
+            0xC0 | 0xC1 | 0xD0..=0xD3 if group == 6 => {
+                let code = form(OpCodeTableKind::Normal, op_code, 4, size);
+                set(instruction, code, Quirk::Alias);
+            }
+            _ => {}
+        }
+    }
+
+    let info = instruction.op_code();
+    let (op_code, group, size) = (info.op_code(), info.group_index(), info.operand_size());
+    let op0_register =
+        (instruction.op0_kind() == OpKind::Register).then(|| instruction.op0_register());
+    let direct: Vec<bool> = (0..2)
+        .map(|operand| {
+            instruction.op_kind(operand) == OpKind::Memory
+                && instruction.memory_base() == Register::None
+                && instruction.memory_index() == Register::None
+        })
+        .collect();
+    let shorter = if !normal {
+        None
+    } else {
+        match op_code {
+            // a small immediate is sign-extended from a byte
+            0x81 if fits_byte(instruction.immediate16()) => {
+                let value = instruction.immediate16();
+                form(OpCodeTableKind::Normal, 0x83, group, size).inspect(|&code| {
+                    instruction.set_code(code);
+                    instruction.set_op1_kind(OpKind::Immediate8to16);
+                    instruction.set_immediate8to16(value as i16);
+                })
+            }
+            0x81 if op0_register == Some(Register::AX) => {
+                form(OpCodeTableKind::Normal, group as u32 * 8 + 5, -1, size)
+            }
+            0x80 if op0_register == Some(Register::AL) => {
+                form(OpCodeTableKind::Normal, group as u32 * 8 + 4, -1, size)
+            }
+            0x05 | 0x0D | 0x15 | 0x1D | 0x25 | 0x2D | 0x35 | 0x3D
+                if fits_byte(instruction.immediate16()) =>
+            {
+                let value = instruction.immediate16();
+                form(OpCodeTableKind::Normal, 0x83, (op_code / 8) as i32, size).inspect(|_| {
+                    instruction.set_op1_kind(OpKind::Immediate8to16);
+                    instruction.set_immediate8to16(value as i16);
+                })
+            }
+            0xF6 | 0xF7 if group == 0 && op0_register.is_some_and(is_accumulator) => {
+                form(OpCodeTableKind::Normal, op_code - 0x4E, -1, size)
+            }
+            0xC6 | 0xC7 if op0_register.is_some() => {
+                let base = if op_code == 0xC6 { 0xB0 } else { 0xB8 };
+                form(OpCodeTableKind::Normal, base, -1, size)
+            }
+            0x8A | 0x8B if op0_register.is_some_and(is_accumulator) && direct[1] => {
+                form(OpCodeTableKind::Normal, op_code + 0x16, -1, size)
+            }
+            0x88 | 0x89
+                if direct[0]
+                    && instruction.op1_kind() == OpKind::Register
+                    && is_accumulator(instruction.op1_register()) =>
+            {
+                form(OpCodeTableKind::Normal, op_code + 0x1A, -1, size)
+            }
+            0xFF if op0_register.is_some() && matches!(group, 0 | 1 | 6) => form(
+                OpCodeTableKind::Normal,
+                [0x40, 0x48, 0, 0, 0, 0, 0x50][group as usize],
+                -1,
+                size,
+            ),
+            0x8F if op0_register.is_some() => form(OpCodeTableKind::Normal, 0x58, -1, size),
+            0x68 if fits_byte(instruction.immediate16()) => {
+                let value = instruction.immediate16();
+                Some(Code::Pushw_imm8).inspect(|_| {
+                    instruction.set_op0_kind(OpKind::Immediate8to16);
+                    instruction.set_immediate8to16(value as i16);
+                })
+            }
+            0xC0 | 0xC1 if instruction.immediate8() == 1 => {
+                form(OpCodeTableKind::Normal, op_code + 0x10, group, size)
+            }
+            // xchg with ax has a one-byte form either way round
+            0x87 if registers
+                && instruction.op0_register() != instruction.op1_register()
+                && (instruction.op0_register() == Register::AX
+                    || instruction.op1_register() == Register::AX) =>
+            {
+                let other = if instruction.op0_register() == Register::AX {
+                    instruction.op1_register()
+                } else {
+                    instruction.op0_register()
+                };
+                instruction.set_op0_register(other);
+                instruction.set_op1_register(Register::AX);
+                Some(Code::Xchg_r16_AX)
+            }
+            _ => None,
+        }
+    };
+    set(instruction, shorter, Quirk::LongerForm);
+
+    if let Some(short) = near_to_short(instruction) {
+        let mut attempt = *instruction;
+        attempt.set_code(short);
+        if Encoder::new(16).encode(&attempt, attempt.ip()).is_ok() {
+            *instruction = attempt;
+            quirks.push(Quirk::NearBranch);
+        }
+    }
+
+    if let Some(operand) =
+        (0..instruction.op_count()).find(|&operand| instruction.op_kind(operand) == OpKind::Memory)
+    {
+        let displacement = instruction.memory_displacement32() as u16;
+        let based = instruction.memory_base() != Register::None
+            || instruction.memory_index() != Register::None;
+        // [bp] has no form without a displacement
+        let bp_only = instruction.memory_base() == Register::BP
+            && instruction.memory_index() == Register::None;
+        let needed = match displacement {
+            _ if !based => 2,
+            0 if !bp_only => 0,
+            value if fits_byte(value) => 1,
+            _ => 2,
+        };
+        if based && instruction.memory_displ_size() > needed && operand < 2 {
+            instruction.set_memory_displ_size(needed);
+            quirks.push(Quirk::Displacement);
+        }
+    }
+
+    let segment = instruction.segment_prefix();
+    if segment != Register::None
+        && !nasm.contains(&format!("{}:", format!("{segment:?}").to_lowercase()))
+    {
+        instruction.set_segment_prefix(Register::None);
+        quirks.push(Quirk::HiddenPrefix);
+    }
+}
+
+/// The short form of a near `jmp` or conditional jump
+fn near_to_short(instruction: &Instruction) -> Option<Code> {
+    let info = instruction.op_code();
+    match (info.table(), info.op_code()) {
+        (OpCodeTableKind::Normal, 0xE9) if instruction.code() == Code::Jmp_rel16 => {
+            Some(Code::Jmp_rel8_16)
+        }
+        (OpCodeTableKind::T0F, op_code @ 0x80..=0x8F) => form(
+            OpCodeTableKind::Normal,
+            op_code - 0x10,
+            -1,
+            info.operand_size(),
+        ),
+        _ => None,
+    }
+}
+
+/// Finds the instructions that NASM would not assemble back to the same
+/// bytes from how the listing writes them
+///
+/// x86 often has more than one encoding for the same instruction, and an
+/// assembler picks one: NASM puts the first of two registers in the r/m
+/// field, uses the short accumulator and register forms, sign-extends small
+/// immediates from a byte, gives displacements the fewest bytes and
+/// shortens jumps. Code written by another assembler, by hand or by a
+/// packer can pick differently, and then a reassembled listing has other
+/// bytes, and possibly other addresses, than the original. Each
+/// instruction is also written as MASM would, to compare.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::syntax::{Quirk, check_syntax};
+///
+/// // mov ax, bx with the operands the other way round / ret
+/// let issues = check_syntax(&Disassembler::new(vec![0x8B, 0xC3, 0xC3]));
+/// assert_eq!(issues[0].nasm, "mov ax,bx");
+/// assert_eq!(issues[0].reassembled, [0x89, 0xD8]);
+/// assert_eq!(issues[0].quirks, [Quirk::OperandOrder]);
+/// ```
+pub fn check_syntax(disassembler: &Disassembler) -> Vec<SyntaxIssue> {
+    let mut nasm_formatter = NasmFormatter::new();
+    disassembler.style().configure(nasm_formatter.options_mut());
+    let mut masm_formatter = MasmFormatter::new();
+    let mut encoder = Encoder::new(16);
+
+    let mut issues = Vec::new();
+    for instruction in &disassembler.instructions.0 {
+        if is_data(instruction) {
+            continue;
+        }
+        let Some(bytes) = disassembler.original_bytes(instruction) else {
+            continue;
+        };
+        let mut nasm = String::new();
+        nasm_formatter.format(instruction, &mut nasm);
+
+        let mut quirks = Vec::new();
+        let mut reassembled = *instruction;
+        as_nasm(&mut reassembled, &nasm, &mut quirks);
+        if encoder.encode(&reassembled, reassembled.ip()).is_err() {
+            continue;
+        }
+        let reassembled = encoder.take_buffer();
+        if reassembled == bytes {
+            continue;
+        }
+        if quirks.is_empty() {
+            // a repeated prefix, which the encoder writes once
+            quirks.push(Quirk::HiddenPrefix);
+        }
+        let mut masm = String::new();
+        masm_formatter.format(instruction, &mut masm);
+        issues.push(SyntaxIssue {
+            address: instruction.ip() as Address,
+            nasm,
+            masm,
+            bytes: bytes.to_vec(),
+            reassembled,
+            quirks,
+        });
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassemble::DisassemblerOptions;
+
+    /// The quirks found in `code`, which runs to a `ret`
+    fn quirks(code: &[u8]) -> Vec<Quirk> {
+        let mut data = code.to_vec();
+        data.push(0xC3);
+        check_syntax(&Disassembler::new(data))
+            .into_iter()
+            .flat_map(|issue| issue.quirks)
+            .collect()
+    }
+
+    #[test]
+    fn the_samples_written_for_nasm_reassemble_to_themselves() {
+        for name in ["hello", "tsr"] {
+            let sample = crate::samples::find(name).unwrap();
+            let mut disassembler = Disassembler::new(sample.bytes.to_vec());
+            crate::fixpoint::settle(&mut disassembler);
+            assert_eq!(check_syntax(&disassembler), [], "{name}");
+        }
+        // the packer's stub jumps near to the next instruction
+        let packed = crate::samples::find("packed").unwrap();
+        let mut disassembler = Disassembler::new(packed.bytes.to_vec());
+        crate::fixpoint::settle(&mut disassembler);
+        assert_eq!(check_syntax(&disassembler)[0].quirks, [Quirk::NearBranch]);
+    }
+
+    #[test]
+    fn canonical_code_has_no_issues() {
+        // mov ax, bx / add ax, 1 / add ax, 0x1234 / inc ax / mov ax, [bx+1] / mov ax, [0x200]
+        let code = [
+            0x89, 0xD8, 0x83, 0xC0, 0x01, 0x05, 0x34, 0x12, 0x40, 0x8B, 0x47, 0x01, 0xA1, 0x00,
+            0x02,
+        ];
+        assert!(quirks(&code).is_empty());
+    }
+
+    #[test]
+    fn longer_forms_and_aliases_are_flagged() {
+        assert_eq!(quirks(&[0x81, 0xC0, 0x01, 0x00]), [Quirk::LongerForm]);
+        assert_eq!(quirks(&[0x05, 0x01, 0x00]), [Quirk::LongerForm]);
+        assert_eq!(quirks(&[0xFF, 0xC0]), [Quirk::LongerForm]);
+        assert_eq!(quirks(&[0xC7, 0xC3, 0x34, 0x12]), [Quirk::LongerForm]);
+        assert_eq!(quirks(&[0x8B, 0x06, 0x00, 0x02]), [Quirk::LongerForm]);
+        assert_eq!(quirks(&[0x87, 0xC3]), [Quirk::LongerForm]);
+        assert_eq!(quirks(&[0x82, 0xC3, 0x01]), [Quirk::Alias]);
+        assert_eq!(quirks(&[0xD1, 0xF0]), [Quirk::Alias]);
+    }
+
+    #[test]
+    fn displacements_prefixes_and_jumps_are_flagged() {
+        // mov ax, [bx+0] / mov ax, [bp+0], which needs its displacement
+        assert_eq!(
+            quirks(&[0x8B, 0x47, 0x00, 0x8B, 0x46, 0x00]),
+            [Quirk::Displacement]
+        );
+        // ds: mov ax, [bx] / es: mov ax, [bx], which is listed with its prefix
+        assert_eq!(
+            quirks(&[0x3E, 0x8B, 0x07, 0x26, 0x8B, 0x07]),
+            [Quirk::HiddenPrefix]
+        );
+        // jmp near 0x103
+        assert_eq!(quirks(&[0xE9, 0x00, 0x00]), [Quirk::NearBranch]);
+    }
+
+    #[test]
+    fn exact_encodings_are_listed_as_bytes() {
+        // mov ax, bx with the operands the other way round / ret
+        let disassembler = Disassembler::new(vec![0x8B, 0xC3, 0xC3]);
+        let opts = DisassemblerOptions {
+            exact_encodings: true,
+            ..DisassemblerOptions::default()
+        };
+        let mut listing = Vec::new();
+        disassembler.disassemble_stream(&mut listing, opts).unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+        assert!(listing.contains("db 0x8b,0xc3 ; mov ax,bx"), "{listing}");
+    }
+}
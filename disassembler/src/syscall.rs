@@ -3,6 +3,7 @@ use std::fmt::Display;
 use crate::consts::Address;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u16)]
 /// An enum to represent the syscall numbers
 pub enum SyscallType {
@@ -229,19 +230,78 @@ pub enum SyscallType {
 impl SyscallType {
     /// Returns the syscall number as a u16
     pub fn as_u16(&self) -> u16 {
-        self.clone() as u16
+        *self as u16
     }
 
     /// parses a u16 into a syscall number
     pub fn from_u16(n: u16) -> Option<Self> {
         if n > 0x6C {
-            return None;
+            None
         } else {
-            return Some(unsafe { std::mem::transmute(n) });
+            Some(unsafe { std::mem::transmute::<u16, SyscallType>(n) })
+        }
+    }
+
+    /// The DOS error codes the call can return in AX when it sets the carry flag
+    ///
+    /// Calls that do not report errors through the carry flag return an empty slice.
+    pub fn error_codes(&self) -> &'static [u16] {
+        match self {
+            SyscallType::CreateSubdirectory => &[0x03, 0x05],
+            SyscallType::RemoveSubdirectory => &[0x03, 0x05, 0x10],
+            SyscallType::ChangeCurrentDirectory => &[0x03],
+            SyscallType::CreateFile => &[0x03, 0x04, 0x05],
+            SyscallType::OpenFile2 => &[0x02, 0x03, 0x04, 0x05, 0x0C],
+            SyscallType::CloseFile2 => &[0x06],
+            SyscallType::ReadFileOrDevice | SyscallType::WriteFileOrDevice => &[0x05, 0x06],
+            SyscallType::DeleteFile2 => &[0x02, 0x03, 0x05],
+            SyscallType::MoveFilePointer => &[0x01, 0x06],
+            SyscallType::GetOrSetFileAttr => &[0x01, 0x02, 0x03, 0x05],
+            SyscallType::DuplicateHandle | SyscallType::RedirectHandle => &[0x04, 0x06],
+            SyscallType::AllocateMemory => &[0x07, 0x08],
+            SyscallType::ReleaseMemory => &[0x07, 0x09],
+            SyscallType::ReallocateMemory => &[0x07, 0x08, 0x09],
+            SyscallType::ExecuteProgram => &[0x01, 0x02, 0x03, 0x04, 0x05, 0x08, 0x0A, 0x0B],
+            SyscallType::FindFirstFile2 => &[0x02, 0x03, 0x12],
+            SyscallType::FindNextFile2 => &[0x12],
+            SyscallType::RenameFile2 => &[0x02, 0x03, 0x05, 0x11],
+            _ => &[],
         }
     }
 }
 
+/// The meaning of a DOS error code returned in AX
+///
+/// # Example
+///
+/// ```
+/// use disassembler::syscall::error_name;
+///
+/// assert_eq!(error_name(0x02), Some("file not found"));
+/// ```
+pub fn error_name(code: u16) -> Option<&'static str> {
+    Some(match code {
+        0x01 => "invalid function",
+        0x02 => "file not found",
+        0x03 => "path not found",
+        0x04 => "too many open files",
+        0x05 => "access denied",
+        0x06 => "invalid handle",
+        0x07 => "memory control blocks destroyed",
+        0x08 => "insufficient memory",
+        0x09 => "invalid memory block address",
+        0x0A => "invalid environment",
+        0x0B => "invalid format",
+        0x0C => "invalid access mode",
+        0x0D => "invalid data",
+        0x0F => "invalid drive",
+        0x10 => "cannot remove the current directory",
+        0x11 => "not the same device",
+        0x12 => "no more files",
+        _ => return None,
+    })
+}
+
 impl Display for SyscallType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let _ = write!(f, "{:?} 0x{:02x}", self, self.as_u16());
@@ -250,15 +310,25 @@ impl Display for SyscallType {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A struct to represent a syscall
 pub struct Syscall {
     /// The syscall number
     pub number: SyscallType,
     /// The address of the syscall
     pub address: Address,
+    /// The value DX was last set to before the call, if it was tracked
+    pub dx: Option<u16>,
+    /// The value AL was last set to before the call, if it was tracked
+    pub al: Option<u8>,
+    /// The value DI was last set to before the call, if it was tracked
+    pub di: Option<u16>,
+    /// The value BX was last set to before the call, if it was tracked
+    pub bx: Option<u16>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A wrapper type around Vec<Syscall> for implementing Display
 pub struct SyscallList(pub Vec<Syscall>);
 
@@ -278,7 +348,6 @@ impl SyscallList {
     }
 }
 
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -329,6 +398,10 @@ mod tests {
         Syscall {
             number: SyscallType::DisplayString,
             address: addr,
+            dx: Some(0x0200),
+            al: None,
+            di: None,
+            bx: None,
         }
     }
 
@@ -363,4 +436,19 @@ mod tests {
         assert_eq!(a, b);
         assert_ne!(a, c);
     }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 5.  Error codes
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn every_error_code_has_a_meaning() {
+        for n in 0..=0x6C {
+            let number = SyscallType::from_u16(n).unwrap();
+            for &code in number.error_codes() {
+                assert!(error_name(code).is_some(), "{number}: {code:#x}");
+            }
+        }
+        assert!(SyscallType::DisplayString.error_codes().is_empty());
+        assert_eq!(error_name(0x0E), None);
+    }
 }
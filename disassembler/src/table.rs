@@ -0,0 +1,368 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use iced_x86::{FlowControl, Instruction, Mnemonic, OpKind, Register};
+
+use crate::consts::{Address, COM_OFFSET};
+use crate::disassemble::{Disassembler, is_data};
+use crate::style::Style;
+use crate::syscall::SyscallType;
+
+/// How many instructions before a `lods` or `xlat` its pointer and count are looked for
+const SETUP_WINDOW: usize = 8;
+
+/// The fewest elements a table is reported with
+const MIN_ELEMENTS: usize = 2;
+
+/// The most elements a table is given when nothing else bounds it
+const MAX_ELEMENTS: usize = 256;
+
+/// The size of the elements of a table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ElementSize {
+    /// Bytes, listed with `db`
+    Byte,
+    /// Words, listed with `dw`
+    Word,
+    /// Doublewords, such as far pointers, listed with `dd`
+    Dword,
+}
+
+impl ElementSize {
+    /// The number of bytes in an element
+    pub fn bytes(self) -> usize {
+        match self {
+            ElementSize::Byte => 1,
+            ElementSize::Word => 2,
+            ElementSize::Dword => 4,
+        }
+    }
+
+    /// The NASM directive listing elements of this size
+    pub fn directive(self) -> &'static str {
+        match self {
+            ElementSize::Byte => "db",
+            ElementSize::Word => "dw",
+            ElementSize::Dword => "dd",
+        }
+    }
+
+    fn from_bytes(bytes: usize) -> Option<ElementSize> {
+        match bytes {
+            1 => Some(ElementSize::Byte),
+            2 => Some(ElementSize::Word),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ElementSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElementSize::Byte => write!(f, "byte"),
+            ElementSize::Word => write!(f, "word"),
+            ElementSize::Dword => write!(f, "dword"),
+        }
+    }
+}
+
+/// An array the program reads element by element
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Table {
+    /// The address of the first element
+    pub start: Address,
+    /// The size of each element
+    pub element: ElementSize,
+    /// The number of elements
+    pub count: usize,
+    /// The instructions reading the table
+    pub accesses: Vec<Address>,
+}
+
+impl Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let accesses: Vec<String> = self
+            .accesses
+            .iter()
+            .map(|address| format!("0x{address:04x}"))
+            .collect();
+        write!(
+            f,
+            "table of {} {}s, indexed at {}",
+            self.count,
+            self.element,
+            accesses.join(", ")
+        )
+    }
+}
+
+/// An element size seen at a table address, and how many elements the code reads, if known
+pub(crate) struct Access {
+    pub(crate) start: Address,
+    pub(crate) element: ElementSize,
+    pub(crate) count: Option<usize>,
+    pub(crate) at: Address,
+}
+
+/// The immediate `register` is loaded with by the nearest `mov` before
+/// `instructions[index]`, if nothing else changes it in between
+fn loaded_before(instructions: &[Instruction], index: usize, register: Register) -> Option<u16> {
+    for instruction in instructions[index.saturating_sub(SETUP_WINDOW)..index]
+        .iter()
+        .rev()
+    {
+        if instruction.op0_kind() != OpKind::Register
+            || instruction.op0_register().full_register() != register.full_register()
+        {
+            continue;
+        }
+        return (instruction.mnemonic() == Mnemonic::Mov
+            && instruction.op0_register() == register
+            && instruction.op1_kind() == OpKind::Immediate16)
+            .then(|| instruction.immediate16());
+    }
+    None
+}
+
+/// The table read by `instructions[index]`, if it reads one
+pub(crate) fn access(instructions: &[Instruction], index: usize) -> Option<Access> {
+    let instruction = &instructions[index];
+    let at = instruction.ip() as Address;
+    match instruction.mnemonic() {
+        Mnemonic::Lodsb | Mnemonic::Lodsw => Some(Access {
+            start: loaded_before(instructions, index, Register::SI)?,
+            element: if instruction.mnemonic() == Mnemonic::Lodsb {
+                ElementSize::Byte
+            } else {
+                ElementSize::Word
+            },
+            count: loaded_before(instructions, index, Register::CX).map(usize::from),
+            at,
+        }),
+        Mnemonic::Xlatb => Some(Access {
+            start: loaded_before(instructions, index, Register::BX)?,
+            element: ElementSize::Byte,
+            count: None,
+            at,
+        }),
+        _ => {
+            let indexed = (0..instruction.op_count())
+                .any(|operand| instruction.op_kind(operand) == OpKind::Memory)
+                && (instruction.memory_base() != Register::None
+                    || instruction.memory_index() != Register::None)
+                && instruction.memory_base() != Register::BP;
+            if !indexed || instruction.memory_displacement64() < COM_OFFSET as u64 {
+                return None;
+            }
+            Some(Access {
+                start: instruction.memory_displacement64() as Address,
+                element: ElementSize::from_bytes(instruction.memory_size().size())?,
+                count: None,
+                at,
+            })
+        }
+    }
+}
+
+/// Returns true if execution runs straight on from code into `address`
+fn falls_into(disassembler: &Disassembler, address: Address) -> bool {
+    let Some(before) = disassembler
+        .instructions
+        .0
+        .iter()
+        .find(|instruction| instruction.next_ip() == address as u64)
+    else {
+        return false;
+    };
+    let in_string =
+        disassembler.string_constant_list.0.iter().any(|string| {
+            string.start as u64 <= before.ip() && (before.ip() as Address) < string.end
+        });
+    let exits = before.mnemonic() == Mnemonic::Int
+        && (before.immediate8() == 0x20
+            || disassembler
+                .syscall_list
+                .get_by_address(before.ip() as Address)
+                .is_some_and(|syscall| syscall.number == SyscallType::TerminateWithCode));
+    !is_data(before)
+        && !in_string
+        && !exits
+        && !matches!(
+            before.flow_control(),
+            FlowControl::Return | FlowControl::UnconditionalBranch | FlowControl::IndirectBranch
+        )
+}
+
+impl Table {
+    /// Looks for tables read with a consistent element size
+    ///
+    /// Tables are found from indexed reads like `mov ax, [bx+0x200]`, and
+    /// from `lodsb`/`lodsw` and `xlat` with SI or BX loaded with the table's
+    /// address just before. Addresses read with both bytes and words are
+    /// left alone, as are strings and data already marked. A table ends at
+    /// the count loaded into CX for a `lods` loop, or else at the next
+    /// address something refers to, and must not be reached by running on
+    /// from code. [`Disassembler::mark_tables`] lists them as arrays.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use disassembler::disassemble::Disassembler;
+    /// use disassembler::table::{ElementSize, Table};
+    ///
+    /// // mov ax, [bx+0x105] / ret / dw 1, 2, 3
+    /// let data = vec![0x8B, 0x87, 0x05, 0x01, 0xC3, 1, 0, 2, 0, 3, 0];
+    /// let tables = Table::detect(&Disassembler::new(data));
+    /// assert_eq!((tables[0].element, tables[0].count), (ElementSize::Word, 3));
+    /// ```
+    pub fn detect(disassembler: &Disassembler) -> Vec<Table> {
+        let instructions = &disassembler.instructions.0;
+        let program_end = COM_OFFSET as u32 + disassembler.data.len() as u32;
+
+        let mut accesses: BTreeMap<Address, Vec<Access>> = BTreeMap::new();
+        for index in 0..instructions.len() {
+            if is_data(&instructions[index]) {
+                continue;
+            }
+            if let Some(access) = access(instructions, index)
+                && (access.start as u32) < program_end
+            {
+                accesses.entry(access.start).or_default().push(access);
+            }
+        }
+
+        let mut live = disassembler.referenced_addresses();
+        live.extend(disassembler.labels.0.iter().map(|label| label.address));
+        live.extend(
+            disassembler
+                .string_constant_list
+                .0
+                .iter()
+                .map(|string| string.start),
+        );
+
+        let mut tables = Vec::new();
+        for (start, found) in accesses {
+            let element = found[0].element;
+            if found.iter().any(|access| access.element != element) {
+                continue;
+            }
+            let marked = disassembler
+                .instructions
+                .0
+                .iter()
+                .find(|instruction| {
+                    instruction.ip() <= start as u64 && (start as u64) < instruction.next_ip()
+                })
+                .is_some_and(is_data);
+            let in_string = disassembler
+                .string_constant_list
+                .0
+                .iter()
+                .any(|string| string.start <= start && start < string.end);
+            if marked || in_string || falls_into(disassembler, start) {
+                continue;
+            }
+
+            let next_live = live
+                .range(start.saturating_add(1)..)
+                .next()
+                .map_or(program_end, |&next| next as u32)
+                .min(program_end);
+            let room = (next_live - start as u32) as usize / element.bytes();
+            let count = found
+                .iter()
+                .filter_map(|access| access.count)
+                .max()
+                .unwrap_or(MAX_ELEMENTS)
+                .min(room);
+            if count < MIN_ELEMENTS {
+                continue;
+            }
+            let mut accesses: Vec<Address> = found.iter().map(|access| access.at).collect();
+            accesses.dedup();
+            tables.push(Table {
+                start,
+                element,
+                count,
+                accesses,
+            });
+        }
+        tables
+    }
+
+    /// The address just past the last element
+    pub fn end(&self) -> Address {
+        self.start + (self.count * self.element.bytes()) as Address
+    }
+
+    /// The label name for the table in `style`, e.g. `word_table_0x0200`
+    pub fn label_name(&self, style: &Style) -> String {
+        style.label_name(&format!("{}_table", self.element), self.start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Detection
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn lodsw_loop_uses_the_count_in_cx() {
+        let mut data = vec![
+            0xBE, 0x0B, 0x01, // mov si, 0x10b
+            0xB9, 0x02, 0x00, // mov cx, 2
+            0xAD, // lodsw
+            0xE2, 0xFD, // loop 0x106
+            0xCD, 0x20, // int 20h
+        ];
+        data.extend([0x34, 0x12, 0x78, 0x56, 0xFF, 0xFF]);
+        let tables = Table::detect(&Disassembler::new(data));
+        assert_eq!(
+            tables,
+            vec![Table {
+                start: 0x10B,
+                element: ElementSize::Word,
+                count: 2,
+                accesses: vec![0x106],
+            }]
+        );
+    }
+
+    #[test]
+    fn mixed_element_sizes_are_not_a_table() {
+        let data = vec![
+            0x8A, 0x87, 0x09, 0x01, // mov al, [bx+0x109]
+            0x8B, 0x87, 0x09, 0x01, // mov ax, [bx+0x109]
+            0xC3, // ret
+            1, 2, 3, 4,
+        ];
+        assert!(Table::detect(&Disassembler::new(data)).is_empty());
+    }
+
+    #[test]
+    fn code_running_on_into_the_address_is_not_a_table() {
+        // mov al, [bx+0x104] / nop / nop / ret
+        let data = vec![0x8A, 0x87, 0x04, 0x01, 0x90, 0x90, 0xC3];
+        assert!(Table::detect(&Disassembler::new(data)).is_empty());
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 2.  Listing
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn word_tables_are_listed_with_dw() {
+        let data = vec![0x8B, 0x87, 0x05, 0x01, 0xC3, 0x34, 0x12, 0x78, 0x56];
+        let mut disassembler = Disassembler::new(data);
+        let tables = Table::detect(&disassembler);
+        disassembler.mark_tables(&tables);
+        let listing = disassembler.to_string();
+        assert!(listing.contains("word_table_0x0105:"), "{listing}");
+        assert!(listing.contains("dw 0x1234,0x5678"), "{listing}");
+        assert!(listing.contains("; table of 2 words, indexed at 0x0100"));
+    }
+}
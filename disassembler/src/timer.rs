@@ -0,0 +1,306 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{FlowControl, Instruction, InstructionInfoFactory, Mnemonic, OpKind, Register};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+use crate::handlers::{InterruptHandler, find_handlers};
+use crate::music::{PIT_HZ, port};
+use crate::protection::value_before;
+
+/// The PIT mode/command port
+const PIT_CONTROL: u16 = 0x43;
+
+/// The timer channel 0 data port, whose divisor sets the rate of int 8
+const PIT_CHANNEL_0: u16 = 0x40;
+
+/// The timer interrupt, raised by channel 0
+const TIMER_VECTOR: u8 = 0x08;
+
+/// The divisor the BIOS programs, 0 standing for 65536, giving ~18.2 Hz
+const BIOS_DIVISOR: u32 = 0x10000;
+
+/// How many instructions after the command byte the divisor may be written
+const DIVISOR_WINDOW: usize = 12;
+
+/// What happens to the BIOS int 8 handler while the timer runs at the new
+/// rate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Chaining {
+    /// The program's int 8 handler at the address passes ticks on to the
+    /// old one, by a far jump or a `pushf` and far call
+    Chains(Address),
+    /// The program's int 8 handler at the address never passes ticks on,
+    /// so the BIOS clock stops
+    Replaces(Address),
+    /// The program installs no int 8 handler, so the BIOS one counts every
+    /// tick and the time of day runs at the new rate
+    Bios,
+}
+
+/// A write of a new divisor to timer channel 0, changing how often int 8
+/// fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimerRate {
+    /// The `out 0x43` selecting channel 0
+    pub control: Address,
+    /// The last `out 0x40`, completing the divisor
+    pub address: Address,
+    /// The divisor, from 1 to 65536, which the PIT takes as 0
+    pub divisor: u32,
+    /// What happens to the old int 8 handler
+    pub chaining: Chaining,
+}
+
+impl TimerRate {
+    /// How many times a second int 8 fires at this rate
+    pub fn frequency(&self) -> f64 {
+        PIT_HZ as f64 / self.divisor as f64
+    }
+}
+
+impl Display for TimerRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // tenths for slow rates such as the BIOS one, whole hertz above
+        let frequency = (self.frequency() * 10.0).round() / 10.0;
+        let frequency = if frequency < 100.0 {
+            format!("{frequency:.1}")
+        } else {
+            format!("{frequency:.0}")
+        };
+        if self.divisor == BIOS_DIVISOR {
+            return write!(f, "PIT set back to the BIOS rate of ~{frequency} Hz");
+        }
+        write!(f, "PIT reprogrammed to ~{frequency} Hz")?;
+        match self.chaining {
+            Chaining::Chains(handler) => write!(
+                f,
+                ", the int 8 handler at 0x{handler:04x} chains to the old one"
+            ),
+            Chaining::Replaces(handler) => write!(
+                f,
+                ", the int 8 handler at 0x{handler:04x} does not chain to the old one"
+            ),
+            Chaining::Bios => write!(f, ", no int 8 handler, so the BIOS clock runs fast"),
+        }
+    }
+}
+
+/// Finds the writes of a new divisor to timer channel 0 and whether the
+/// program's int 8 handler passes the faster ticks on to the old one
+///
+/// The command byte written to port 43h must select channel 0 and be loaded
+/// just before, and the divisor bytes written to port 40h within the next
+/// few instructions, each loaded into AL or taken from AH with `mov al, ah`.
+/// A handler that chains now and then, counting ticks to keep the BIOS
+/// clock right, counts as chaining.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::timer::find_timer_rates;
+///
+/// let data = vec![
+///     0xB0, 0x36, 0xE6, 0x43, // mov al, 0x36 / out 0x43, al
+///     0xB8, 0xA9, 0x04, // mov ax, 0x04a9
+///     0xE6, 0x40, 0x88, 0xE0, 0xE6, 0x40, // out 0x40, al / mov al, ah / out 0x40, al
+///     0xC3, // ret
+/// ];
+/// let rates = find_timer_rates(&Disassembler::new(data));
+/// assert_eq!(rates[0].divisor, 0x04A9);
+/// assert_eq!(
+///     rates[0].to_string(),
+///     "PIT reprogrammed to ~1000 Hz, no int 8 handler, so the BIOS clock runs fast"
+/// );
+/// ```
+pub fn find_timer_rates(disassembler: &Disassembler) -> Vec<TimerRate> {
+    let mut factory = InstructionInfoFactory::new();
+    let instructions = &disassembler.instructions.0;
+    let mut found = Vec::new();
+    let mut chaining = None;
+    for (index, instruction) in instructions.iter().enumerate() {
+        if !writes_port(instructions, index, PIT_CONTROL) {
+            continue;
+        }
+        let Some(command) = value_before(&mut factory, instructions, index, Register::AL) else {
+            continue;
+        };
+        // channel 0 in bits 7-6, and which divisor bytes follow in bits 5-4
+        if command >> 6 != 0 {
+            continue;
+        }
+        let bytes = match (command >> 4) & 0b11 {
+            0b01 | 0b10 => 1,
+            0b11 => 2,
+            _ => continue,
+        };
+        let mut divisor_bytes = Vec::new();
+        let mut last = index;
+        for at in index + 1..(index + 1 + DIVISOR_WINDOW).min(instructions.len()) {
+            let next = &instructions[at];
+            if is_data(next) || next.flow_control() != FlowControl::Next {
+                break;
+            }
+            if writes_port(instructions, at, PIT_CONTROL) {
+                break;
+            }
+            if writes_port(instructions, at, PIT_CHANNEL_0) {
+                divisor_bytes.push(written_byte(&mut factory, instructions, at));
+                last = at;
+                if divisor_bytes.len() == bytes {
+                    break;
+                }
+            }
+        }
+        if divisor_bytes.len() != bytes {
+            continue;
+        }
+        let divisor = match (command >> 4) & 0b11 {
+            0b01 => divisor_bytes[0].map(|low| low as u32),
+            0b10 => divisor_bytes[0].map(|high| (high as u32) << 8),
+            _ => divisor_bytes[0]
+                .zip(divisor_bytes[1])
+                .map(|(low, high)| (high as u32) << 8 | low as u32),
+        };
+        let Some(divisor) = divisor else {
+            continue;
+        };
+        let chaining = *chaining.get_or_insert_with(|| timer_chaining(disassembler));
+        found.push(TimerRate {
+            control: instruction.ip() as Address,
+            address: instructions[last].ip() as Address,
+            divisor: if divisor == 0 { BIOS_DIVISOR } else { divisor },
+            chaining,
+        });
+    }
+    found
+}
+
+/// Returns true if the instruction at `index` is an `out` of AL to port `to`
+fn writes_port(instructions: &[Instruction], index: usize, to: u16) -> bool {
+    let instruction = &instructions[index];
+    !is_data(instruction)
+        && instruction.mnemonic() == Mnemonic::Out
+        && instruction.op1_register() == Register::AL
+        && port(instructions, index) == Some(to)
+}
+
+/// The byte the `out` at `index` writes from AL, if it was loaded just
+/// before or copied from AH with `mov al, ah`
+fn written_byte(
+    factory: &mut InstructionInfoFactory,
+    instructions: &[Instruction],
+    index: usize,
+) -> Option<u8> {
+    if let Some(value) = value_before(factory, instructions, index, Register::AL) {
+        return Some(value as u8);
+    }
+    let copy = index.checked_sub(1)?;
+    let before = &instructions[copy];
+    let from_ah = before.mnemonic() == Mnemonic::Mov
+        && before.op0_kind() == OpKind::Register
+        && before.op0_register() == Register::AL
+        && before.op1_kind() == OpKind::Register
+        && before.op1_register() == Register::AH;
+    if !from_ah {
+        return None;
+    }
+    value_before(factory, instructions, copy, Register::AH).map(|value| value as u8)
+}
+
+/// Whether the program's int 8 handler, if it installs one, passes ticks on
+/// to the old one
+fn timer_chaining(disassembler: &Disassembler) -> Chaining {
+    let handlers = find_handlers(disassembler);
+    let Some(handler) = handlers
+        .iter()
+        .find(|handler| handler.vector == Some(TIMER_VECTOR))
+    else {
+        return Chaining::Bios;
+    };
+    if handler.chains() || calls_previous(disassembler, handler) {
+        Chaining::Chains(handler.address)
+    } else {
+        Chaining::Replaces(handler.address)
+    }
+}
+
+/// Returns true if `handler` calls the previous handler as an interrupt,
+/// with `pushf` then a far call, between its first instruction and its
+/// last exit
+fn calls_previous(disassembler: &Disassembler, handler: &InterruptHandler) -> bool {
+    let Some(start) = disassembler.instructions.index_at(handler.address) else {
+        return false;
+    };
+    let end = handler
+        .exits
+        .iter()
+        .map(|&(address, _)| address)
+        .max()
+        .unwrap_or(handler.address);
+    let instructions = &disassembler.instructions.0[start..];
+    instructions
+        .windows(2)
+        .take_while(|pair| pair[1].ip() <= end as u64)
+        .any(|pair| {
+            pair[0].mnemonic() == Mnemonic::Pushf
+                && pair[1].mnemonic() == Mnemonic::Call
+                && (pair[1].op0_kind() == OpKind::FarBranch16 || pair[1].is_call_far_indirect())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handlers_calling_the_old_one_chain() {
+        let data = vec![
+            0xB8, 0x08, 0x25, // mov ax, 0x2508
+            0xBA, 0x17, 0x01, // mov dx, 0x117
+            0xCD, 0x21, // int 21h
+            0xB0, 0x34, // mov al, 0x34
+            0xE6, 0x43, // out 0x43, al
+            0xB0, 0x9C, // mov al, 0x9c
+            0xE6, 0x40, // out 0x40, al
+            0xB0, 0x2E, // mov al, 0x2e
+            0xE6, 0x40, // out 0x40, al
+            0xC3, // ret
+            0x00, 0x00, // the old handler
+            0x9C, // pushf (the handler, at 0x117)
+            0x2E, 0xFF, 0x1E, 0x15, 0x01, // call far [cs:0x115]
+            0xCF, // iret
+        ];
+        let disassembler = Disassembler::new(data);
+        let rates = find_timer_rates(&disassembler);
+        assert_eq!(rates[0].chaining, Chaining::Chains(0x117));
+        assert_eq!(rates[0].address, 0x112);
+        let listing = disassembler.to_string();
+        assert!(
+            listing.contains(
+                "; PIT reprogrammed to ~100 Hz, the int 8 handler at 0x0117 chains to the old one"
+            ),
+            "{listing}"
+        );
+    }
+
+    #[test]
+    fn a_zero_divisor_restores_the_bios_rate() {
+        let data = vec![
+            0xB0, 0x36, 0xE6, 0x43, // mov al, 0x36 / out 0x43, al
+            0x31, 0xC0, // xor ax, ax
+            0xE6, 0x40, 0xE6, 0x40, // out 0x40, al / out 0x40, al
+            0xB0, 0xB6, 0xE6, 0x43, // mov al, 0xb6 / out 0x43, al (channel 2)
+            0xC3, // ret
+        ];
+        let rates = find_timer_rates(&Disassembler::new(data));
+        assert_eq!(rates.len(), 1);
+        assert_eq!(
+            rates[0].to_string(),
+            "PIT set back to the BIOS rate of ~18.2 Hz"
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use iced_x86::{
+    FlowControl, Instruction, InstructionInfoFactory, Mnemonic, OpAccess, OpKind, Register,
+};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+
+/// The largest constant a counter is expected to be compared with
+const SMALL_CONSTANT: u16 = 100;
+
+/// How many instructions after a load the loaded value is followed
+const RELAY_WINDOW: usize = 4;
+
+/// What an instruction does with a counter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AccessKind {
+    /// Compares the counter with a small constant
+    Compare {
+        /// The constant compared with
+        value: u16,
+    },
+    /// Subtracts from the counter
+    Decrement,
+    /// Reads the counter
+    Read,
+    /// Writes the counter, such as when it is reset
+    Write,
+}
+
+/// An instruction that touches a counter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CounterAccess {
+    /// The address of the instruction
+    pub address: Address,
+    /// What it does with the counter
+    pub kind: AccessKind,
+}
+
+/// A memory location used like a lives or health counter
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Counter {
+    /// The address of the counter
+    pub address: Address,
+    /// The size of the counter in bytes
+    pub size: usize,
+    /// Every instruction touching it, ordered by address
+    pub accesses: Vec<CounterAccess>,
+}
+
+impl Counter {
+    /// How many times the counter is compared with a small constant
+    pub fn compares(&self) -> usize {
+        self.accesses
+            .iter()
+            .filter(|access| matches!(access.kind, AccessKind::Compare { .. }))
+            .count()
+    }
+
+    /// How many times the counter is decremented
+    pub fn decrements(&self) -> usize {
+        self.accesses
+            .iter()
+            .filter(|access| access.kind == AccessKind::Decrement)
+            .count()
+    }
+}
+
+impl Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = if self.size == 1 { "byte" } else { "word" };
+        write!(f, "possible counter at 0x{:04x} ({size}):", self.address)?;
+        for (index, access) in self.accesses.iter().enumerate() {
+            let separator = if index == 0 { "" } else { "," };
+            let what = match access.kind {
+                AccessKind::Compare { value } => format!("compared with {value}"),
+                AccessKind::Decrement => "decremented".to_string(),
+                AccessKind::Read => "read".to_string(),
+                AccessKind::Write => "written".to_string(),
+            };
+            write!(f, "{separator} {what} at 0x{:04x}", access.address)?;
+        }
+        Ok(())
+    }
+}
+
+/// The fixed address and size of the memory operand of `instruction`, if it
+/// has no base or index register
+fn direct_memory(instruction: &Instruction) -> Option<(Address, usize)> {
+    let has_memory =
+        (0..instruction.op_count()).any(|operand| instruction.op_kind(operand) == OpKind::Memory);
+    let size = instruction.memory_size().size();
+    (has_memory
+        && instruction.memory_base() == Register::None
+        && instruction.memory_index() == Register::None
+        && matches!(size, 1 | 2))
+    .then(|| (instruction.memory_displacement64() as Address, size))
+}
+
+/// The immediate operand of `instruction` truncated to `size` bytes
+fn immediate(instruction: &Instruction, size: usize) -> Option<u16> {
+    let value = match instruction.op1_kind() {
+        OpKind::Immediate8 | OpKind::Immediate16 | OpKind::Immediate8to16 => {
+            instruction.immediate(1) as u16
+        }
+        _ => return None,
+    };
+    Some(if size == 1 { value & 0xFF } else { value })
+}
+
+/// Returns true if adding `value` to a `size` byte counter makes it smaller
+fn is_negative(value: u16, size: usize) -> bool {
+    if size == 1 {
+        value & 0x80 != 0
+    } else {
+        value & 0x8000 != 0
+    }
+}
+
+/// What `instruction` does with the memory it addresses directly
+fn classify(
+    factory: &mut InstructionInfoFactory,
+    instruction: &Instruction,
+    size: usize,
+) -> AccessKind {
+    let on_memory = instruction.op0_kind() == OpKind::Memory;
+    match instruction.mnemonic() {
+        Mnemonic::Dec if on_memory => return AccessKind::Decrement,
+        Mnemonic::Sub if on_memory && immediate(instruction, size).is_some() => {
+            return AccessKind::Decrement;
+        }
+        Mnemonic::Add
+            if on_memory
+                && immediate(instruction, size).is_some_and(|value| is_negative(value, size)) =>
+        {
+            return AccessKind::Decrement;
+        }
+        Mnemonic::Cmp if on_memory => {
+            if let Some(value) = immediate(instruction, size).filter(|&v| v <= SMALL_CONSTANT) {
+                return AccessKind::Compare { value };
+            }
+        }
+        _ => {}
+    }
+    let writes = factory
+        .info(instruction)
+        .used_memory()
+        .iter()
+        .any(|memory| {
+            !matches!(
+                memory.access(),
+                OpAccess::Read | OpAccess::CondRead | OpAccess::NoMemAccess
+            )
+        });
+    if writes {
+        AccessKind::Write
+    } else {
+        AccessKind::Read
+    }
+}
+
+/// Follows a counter loaded into a register, finding a comparison of the
+/// register with a small constant, or a decrement stored back to `address`
+fn relay(instructions: &[Instruction], index: usize, address: Address) -> Option<CounterAccess> {
+    let load = &instructions[index];
+    if load.mnemonic() != Mnemonic::Mov || load.op0_kind() != OpKind::Register {
+        return None;
+    }
+    let register = load.op0_register();
+    let size = register.size();
+    let mut decrement = None;
+    for instruction in instructions[index + 1..].iter().take(RELAY_WINDOW) {
+        let on_register =
+            instruction.op0_kind() == OpKind::Register && instruction.op0_register() == register;
+        match instruction.mnemonic() {
+            Mnemonic::Cmp if on_register && decrement.is_none() => {
+                return immediate(instruction, size)
+                    .filter(|&value| value <= SMALL_CONSTANT)
+                    .map(|value| CounterAccess {
+                        address: instruction.ip() as Address,
+                        kind: AccessKind::Compare { value },
+                    });
+            }
+            Mnemonic::Dec | Mnemonic::Sub if on_register => {
+                decrement = Some(instruction.ip() as Address);
+            }
+            Mnemonic::Mov
+                if decrement.is_some()
+                    && instruction.op1_kind() == OpKind::Register
+                    && instruction.op1_register() == register
+                    && direct_memory(instruction).map(|(to, _)| to) == Some(address) =>
+            {
+                return decrement.map(|at| CounterAccess {
+                    address: at,
+                    kind: AccessKind::Decrement,
+                });
+            }
+            _ if on_register || instruction.flow_control() != FlowControl::Next => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Finds memory locations used like lives or health counters
+///
+/// A counter is a byte or word at a fixed address that is both decremented
+/// and compared with a constant of at most 100, either in place or through
+/// a register it was loaded into. Candidates with the most such uses come
+/// first; each lists every instruction touching it, which are the places a
+/// trainer would patch.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::trainer::find_counters;
+///
+/// // dec byte [0x200] / cmp byte [0x200], 0 / ret
+/// let data = vec![0xFE, 0x0E, 0x00, 0x02, 0x80, 0x3E, 0x00, 0x02, 0x00, 0xC3];
+/// let counters = find_counters(&Disassembler::new(data));
+/// assert_eq!(counters[0].address, 0x200);
+/// ```
+pub fn find_counters(disassembler: &Disassembler) -> Vec<Counter> {
+    let instructions = &disassembler.instructions.0;
+    let mut factory = InstructionInfoFactory::new();
+    let mut counters: BTreeMap<Address, Counter> = BTreeMap::new();
+    for (index, instruction) in instructions.iter().enumerate() {
+        if is_data(instruction) {
+            continue;
+        }
+        let Some((address, size)) = direct_memory(instruction) else {
+            continue;
+        };
+        let counter = counters.entry(address).or_insert_with(|| Counter {
+            address,
+            size,
+            accesses: Vec::new(),
+        });
+        counter.size = counter.size.max(size);
+        counter.accesses.push(CounterAccess {
+            address: instruction.ip() as Address,
+            kind: classify(&mut factory, instruction, size),
+        });
+        if let Some(access) = relay(instructions, index, address) {
+            counter.accesses.push(access);
+        }
+    }
+
+    let mut found: Vec<Counter> = counters
+        .into_values()
+        .filter(|counter| counter.compares() > 0 && counter.decrements() > 0)
+        .map(|mut counter| {
+            counter.accesses.sort_by_key(|access| access.address);
+            counter.accesses.dedup();
+            counter
+        })
+        .collect();
+    found.sort_by_key(|counter| {
+        (
+            std::cmp::Reverse(counter.compares() + counter.decrements()),
+            counter.address,
+        )
+    });
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Counters in memory
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn decremented_and_compared_in_place() {
+        let data = vec![
+            0xC6, 0x06, 0x00, 0x02, 0x03, // mov byte [0x200], 3
+            0xFE, 0x0E, 0x00, 0x02, // dec byte [0x200]
+            0x80, 0x3E, 0x00, 0x02, 0x00, // cmp byte [0x200], 0
+            0x75, 0xF5, // jnz 0x105
+            0xC3, // ret
+        ];
+        let counters = find_counters(&Disassembler::new(data));
+        assert_eq!(
+            counters,
+            vec![Counter {
+                address: 0x200,
+                size: 1,
+                accesses: vec![
+                    CounterAccess {
+                        address: 0x100,
+                        kind: AccessKind::Write
+                    },
+                    CounterAccess {
+                        address: 0x105,
+                        kind: AccessKind::Decrement
+                    },
+                    CounterAccess {
+                        address: 0x109,
+                        kind: AccessKind::Compare { value: 0 }
+                    },
+                ],
+            }]
+        );
+        assert_eq!(
+            counters[0].to_string(),
+            "possible counter at 0x0200 (byte): written at 0x0100, decremented at 0x0105, compared with 0 at 0x0109"
+        );
+    }
+
+    #[test]
+    fn counters_through_a_register_are_found() {
+        let data = vec![
+            0xA1, 0x00, 0x03, // mov ax, [0x300]
+            0x48, // dec ax
+            0xA3, 0x00, 0x03, // mov [0x300], ax
+            0xA1, 0x00, 0x03, // mov ax, [0x300]
+            0x3D, 0x05, 0x00, // cmp ax, 5
+            0xC3, // ret
+        ];
+        let counters = find_counters(&Disassembler::new(data));
+        assert_eq!(counters.len(), 1);
+        assert_eq!(counters[0].size, 2);
+        assert_eq!(counters[0].decrements(), 1);
+        assert_eq!(counters[0].compares(), 1);
+    }
+
+    #[test]
+    fn large_comparisons_are_not_counters() {
+        let data = vec![
+            0xFF, 0x0E, 0x00, 0x02, // dec word [0x200]
+            0x81, 0x3E, 0x00, 0x02, 0x00, 0x10, // cmp word [0x200], 0x1000
+            0xC3, // ret
+        ];
+        assert!(find_counters(&Disassembler::new(data)).is_empty());
+    }
+}
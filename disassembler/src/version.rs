@@ -0,0 +1,222 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{FlowControl, Instruction, InstructionInfoFactory, Mnemonic, OpKind, Register};
+
+use crate::consts::Address;
+use crate::disassemble::{Disassembler, is_data};
+use crate::redundancy::writes_register;
+use crate::syscall::SyscallType;
+
+/// A DOS version, as int 21h AH=30h returns it: the major version in `al`
+/// and the minor in `ah`, in hundredths
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DosVersion {
+    /// The major version, such as 3 for DOS 3.30
+    pub major: u8,
+    /// The minor version, such as 30 for DOS 3.30
+    pub minor: u8,
+}
+
+impl Display for DosVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02}", self.major, self.minor)
+    }
+}
+
+/// A check that the program runs on a recent enough DOS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VersionCheck {
+    /// The address of the int 21h call asking for the version
+    pub call: Address,
+    /// The address of the `cmp` against the version
+    pub address: Address,
+    /// The oldest version the check lets through
+    pub version: DosVersion,
+}
+
+impl Display for VersionCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "requires DOS {} or later", self.version)
+    }
+}
+
+/// Finds the checks of the DOS version after int 21h AH=30h calls
+///
+/// Recognised by a `cmp al, N` against the major version, or a `cmp ax`
+/// against both once `xchg al, ah` has put the major version on top,
+/// followed by a conditional jump on which is lower, such as `jb` to an
+/// error message. The comparison must come before anything else writes
+/// `ax` or the code calls, returns or jumps away.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::version::{DosVersion, find_version_checks};
+///
+/// let data = vec![
+///     0xB4, 0x30, 0xCD, 0x21, // mov ah, 0x30 / int 21h
+///     0x3C, 0x02, 0x76, 0x01, // cmp al, 2 / jbe 0x109
+///     0xC3, // ret
+///     0xCD, 0x20, // int 20h
+/// ];
+/// let checks = find_version_checks(&Disassembler::new(data));
+/// assert_eq!(checks[0].version, DosVersion { major: 3, minor: 0 });
+/// assert_eq!(checks[0].to_string(), "requires DOS 3.00 or later");
+/// ```
+pub fn find_version_checks(disassembler: &Disassembler) -> Vec<VersionCheck> {
+    let mut factory = InstructionInfoFactory::new();
+    let instructions = &disassembler.instructions.0;
+    let mut found = Vec::new();
+    for syscall in &disassembler.syscall_list.0 {
+        if syscall.number != SyscallType::GetDosVersion {
+            continue;
+        }
+        let Some(index) = disassembler.instructions.index_at(syscall.address) else {
+            continue;
+        };
+        let mut swapped = false;
+        for (position, instruction) in instructions.iter().enumerate().skip(index + 1) {
+            if is_data(instruction) {
+                break;
+            }
+            if let Some((compared, step)) = compared_version(instruction, swapped) {
+                let version = instructions
+                    .get(position + 1)
+                    .and_then(|branch| oldest_passing(branch, compared, step));
+                if let Some(version) = version {
+                    found.push(VersionCheck {
+                        call: syscall.address,
+                        address: instruction.ip() as Address,
+                        version,
+                    });
+                }
+                break;
+            }
+            if is_swap(instruction) {
+                swapped = !swapped;
+            } else if writes_register(&mut factory, instruction, Register::AX)
+                || !matches!(
+                    instruction.flow_control(),
+                    FlowControl::Next | FlowControl::ConditionalBranch
+                )
+            {
+                break;
+            }
+        }
+    }
+    found
+}
+
+/// The oldest DOS version any of the checks in the program lets through
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::version::minimum_dos_version;
+///
+/// // mov ah, 0x30 / int 21h / ret
+/// let disassembler = Disassembler::new(vec![0xB4, 0x30, 0xCD, 0x21, 0xC3]);
+/// assert_eq!(minimum_dos_version(&disassembler), None);
+/// ```
+pub fn minimum_dos_version(disassembler: &Disassembler) -> Option<DosVersion> {
+    find_version_checks(disassembler)
+        .iter()
+        .map(|check| check.version)
+        .max()
+}
+
+/// Returns true for `xchg al, ah`, which puts the major version on top so
+/// `cmp ax` orders versions
+fn is_swap(instruction: &Instruction) -> bool {
+    instruction.mnemonic() == Mnemonic::Xchg
+        && instruction.op0_kind() == OpKind::Register
+        && instruction.op1_kind() == OpKind::Register
+        && matches!(
+            (instruction.op0_register(), instruction.op1_register()),
+            (Register::AL, Register::AH) | (Register::AH, Register::AL)
+        )
+}
+
+/// The version a `cmp` compares the one DOS returned against, as the major
+/// version in the high byte and minor in the low, and the step to the next
+/// version it tells apart: the next major version, or the next minor one
+fn compared_version(instruction: &Instruction, swapped: bool) -> Option<(u16, u16)> {
+    if instruction.mnemonic() != Mnemonic::Cmp || instruction.op0_kind() != OpKind::Register {
+        return None;
+    }
+    match (instruction.op0_register(), instruction.op1_kind(), swapped) {
+        (Register::AL, OpKind::Immediate8, false) | (Register::AH, OpKind::Immediate8, true) => {
+            Some(((instruction.immediate8() as u16) << 8, 0x100))
+        }
+        (Register::AX, OpKind::Immediate16, true) => Some((instruction.immediate16(), 1)),
+        (Register::AX, OpKind::Immediate8to16, true) => {
+            Some((instruction.immediate8to16() as u16, 1))
+        }
+        _ => None,
+    }
+}
+
+/// The oldest version `branch` lets through after comparing against
+/// `compared`, if it branches on which version is lower
+fn oldest_passing(branch: &Instruction, compared: u16, step: u16) -> Option<DosVersion> {
+    let oldest = match branch.mnemonic() {
+        Mnemonic::Jb | Mnemonic::Jae | Mnemonic::Jl | Mnemonic::Jge => compared,
+        Mnemonic::Jbe | Mnemonic::Ja | Mnemonic::Jle | Mnemonic::Jg => {
+            compared.checked_add(step)?
+        }
+        _ => return None,
+    };
+    Some(DosVersion {
+        major: (oldest >> 8) as u8,
+        minor: oldest as u8,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swapped_versions_compare_minor_versions_too() {
+        let data = vec![
+            0xB8, 0x00, 0x30, // mov ax, 0x3000
+            0xCD, 0x21, // int 21h
+            0x86, 0xC4, // xchg al, ah
+            0x3D, 0x0A, 0x03, // cmp ax, 0x030a
+            0x72, 0x01, // jb 0x10e
+            0xC3, // ret
+            0xCD, 0x20, // int 20h
+        ];
+        let disassembler = Disassembler::new(data);
+        assert_eq!(
+            minimum_dos_version(&disassembler),
+            Some(DosVersion {
+                major: 3,
+                minor: 10
+            })
+        );
+        let listing = disassembler.to_string();
+        assert!(
+            listing.contains("; requires DOS 3.10 or later"),
+            "{listing}"
+        );
+    }
+
+    #[test]
+    fn comparisons_after_ax_changes_are_not_checks() {
+        let data = vec![
+            0xB4, 0x30, // mov ah, 0x30
+            0xCD, 0x21, // int 21h
+            0xB0, 0x05, // mov al, 5
+            0x3C, 0x03, // cmp al, 3
+            0x72, 0x01, // jb 0x10b
+            0xC3, // ret
+            0xCD, 0x20, // int 20h
+        ];
+        assert!(find_version_checks(&Disassembler::new(data)).is_empty());
+    }
+}
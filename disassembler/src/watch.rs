@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use crate::consts::{Address, COM_OFFSET};
+use crate::disassemble::Disassembler;
+use crate::emulate::Trace;
+
+/// A buffer watched during emulation and the instructions that used it
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WatchedBuffer {
+    /// The buffer's label, or its address if it has none
+    pub name: String,
+    /// The first address of the buffer
+    pub start: Address,
+    /// The address just past the buffer
+    pub end: Address,
+    /// The instructions that read the buffer, with how many times each did
+    pub reads: BTreeMap<Address, usize>,
+    /// The instructions that wrote the buffer, with how many times each did
+    pub writes: BTreeMap<Address, usize>,
+}
+
+impl Display for WatchedBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (0x{:04x}-0x{:04x})", self.name, self.start, self.end)?;
+        let list = |accesses: &BTreeMap<Address, usize>| {
+            accesses
+                .iter()
+                .map(|(address, count)| format!("0x{address:04x} ({count}x)"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match (self.reads.is_empty(), self.writes.is_empty()) {
+            (true, true) => write!(f, ": never used"),
+            (false, true) => write!(f, ": read by {}", list(&self.reads)),
+            (true, false) => write!(f, ": written by {}", list(&self.writes)),
+            (false, false) => write!(
+                f,
+                ": read by {}; written by {}",
+                list(&self.reads),
+                list(&self.writes)
+            ),
+        }
+    }
+}
+
+/// The extent of the labelled item starting at `start`: up to the next
+/// label, or the end of the program or its reserved memory
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::watch::extent;
+///
+/// // mov [0x106], al / ret / nop / db 0, 0, 0
+/// let data = vec![0xA2, 0x06, 0x01, 0xC3, 0x90, 0x90, 0, 0, 0];
+/// let disassembler = Disassembler::new(data);
+/// assert_eq!(extent(&disassembler, 0x106), (0x106, 0x109));
+/// ```
+pub fn extent(disassembler: &Disassembler, start: Address) -> (Address, Address) {
+    let next = disassembler
+        .labels
+        .0
+        .iter()
+        .map(|label| label.address)
+        .filter(|&address| address > start)
+        .min();
+    let file_end = COM_OFFSET as usize + disassembler.data.len();
+    let end = next.map(|address| address as usize).unwrap_or_else(|| {
+        disassembler
+            .query(start)
+            .reservation
+            .map_or(file_end, |reservation| reservation.end() as usize)
+            .max(start as usize + 1)
+    });
+    (start, end.min(Address::MAX as usize) as Address)
+}
+
+/// Which instructions read and wrote each watched buffer during an
+/// emulator run
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WatchReport(pub Vec<WatchedBuffer>);
+
+impl WatchReport {
+    /// Sorts the accesses in `trace` into the `buffers` they touched, each
+    /// given as a start and end address
+    ///
+    /// Run the emulator with [`Emulator::watch`](crate::emulate::Emulator::watch)
+    /// on every buffer first, or no accesses will have been recorded.
+    pub fn new(
+        disassembler: &Disassembler,
+        buffers: &[(Address, Address)],
+        trace: &Trace,
+    ) -> WatchReport {
+        WatchReport(
+            buffers
+                .iter()
+                .map(|&(start, end)| {
+                    let mut buffer = WatchedBuffer {
+                        name: disassembler
+                            .labels
+                            .get_by_address(start)
+                            .map_or(format!("0x{start:04x}"), |label| label.name.clone()),
+                        start,
+                        end,
+                        reads: BTreeMap::new(),
+                        writes: BTreeMap::new(),
+                    };
+                    let touched = trace.accesses.iter().filter(|access| {
+                        (access.address as usize) < end as usize
+                            && (start as usize) < access.address as usize + access.size
+                    });
+                    for access in touched {
+                        let accesses = if access.write {
+                            &mut buffer.writes
+                        } else {
+                            &mut buffer.reads
+                        };
+                        *accesses.entry(access.instruction).or_default() += 1;
+                    }
+                    buffer
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulate::Emulator;
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Watch reports
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn accesses_are_sorted_into_buffers() {
+        let data = vec![
+            0xBE, 0x14, 0x01, // mov si, 0x114
+            0xBF, 0x17, 0x01, // mov di, 0x117
+            0xB9, 0x03, 0x00, // mov cx, 3
+            0xFC, // cld
+            0xF3, 0xA4, // rep movsb
+            0xA0, 0x14, 0x01, // mov al, [0x114]
+            0xA2, 0x18, 0x01, // mov [0x118], al
+            0xC3, // ret
+            0x90, // nop
+            1, 2, 3, // 0x114: source
+            0, 0, 0, // 0x117: target
+        ];
+        let mut disassembler = Disassembler::new(data.clone());
+        let buffers = [(0x114, 0x117), (0x117, 0x11A)];
+        let mut emulator = Emulator::new(&data);
+        for &(start, end) in &buffers {
+            emulator.watch(start, end);
+        }
+        emulator.run(100);
+
+        let report = WatchReport::new(&disassembler, &buffers, &emulator.trace);
+        assert_eq!(report.0[0].reads, BTreeMap::from([(0x10A, 1), (0x10C, 1)]));
+        assert!(report.0[0].writes.is_empty());
+        assert_eq!(report.0[1].writes, BTreeMap::from([(0x10A, 1), (0x10F, 1)]));
+        assert_eq!(
+            report.0[1].to_string(),
+            "0x0117 (0x0117-0x011a): written by 0x010a (1x), 0x010f (1x)"
+        );
+
+        disassembler.add_dynamic_references(&report);
+        assert_eq!(disassembler.query(0x117).dynamic_references, [0x10A, 0x10F]);
+        assert!(disassembler.query(0x117).references.is_empty());
+    }
+}
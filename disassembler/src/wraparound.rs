@@ -0,0 +1,216 @@
+use std::fmt::{self, Display};
+
+use iced_x86::{Decoder, DecoderOptions, Instruction, OpCodeOperandKind, OpKind, Register};
+
+use crate::consts::{Address, COM_OFFSET, SIZE};
+use crate::disassemble::{Disassembler, is_data};
+
+/// The longest an x86 instruction can be
+const MAX_INSTRUCTION: usize = 15;
+
+/// An address that runs off one end of the 64K segment and comes back in
+/// at the other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Wraparound {
+    /// A jump or call whose displacement goes past 0xFFFF or below 0, so it
+    /// lands at the other end of the segment
+    Branch {
+        /// Where the branch lands once wrapped
+        target: Address,
+    },
+    /// A read or write at a fixed address whose last bytes are past 0xFFFF,
+    /// and so at the start of the segment
+    Memory {
+        /// The first byte accessed
+        address: Address,
+        /// How many bytes are accessed
+        size: usize,
+    },
+    /// An instruction that needs more bytes than the file has left
+    Truncated {
+        /// How long the instruction is
+        length: usize,
+        /// How many of its bytes are in the file
+        available: usize,
+    },
+}
+
+/// A wraparound and the instruction it happens in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WrapFinding {
+    /// The address of the instruction
+    pub address: Address,
+    /// What wraps
+    pub kind: Wraparound,
+}
+
+impl Display for WrapFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            Wraparound::Branch { target } => {
+                write!(f, "branch wraps around the segment to 0x{target:04x}")
+            }
+            Wraparound::Memory { address, size } => write!(
+                f,
+                "{size}-byte access at 0x{address:04x} wraps around to 0x0000"
+            ),
+            Wraparound::Truncated { length, available } => write!(
+                f,
+                "{length}-byte instruction cut off by the end of the file after {available} bytes"
+            ),
+        }
+    }
+}
+
+/// The displacement of a relative branch, read from the end of its bytes
+fn branch_displacement(disassembler: &Disassembler, instruction: &Instruction) -> Option<i32> {
+    let bytes = disassembler.bytes_at(instruction.ip() as Address)?;
+    let bytes = bytes.get(..instruction.len())?;
+    match instruction.op_code().op_kind(0) {
+        OpCodeOperandKind::br16_1 => Some(*bytes.last()? as i8 as i32),
+        OpCodeOperandKind::br16_2 => {
+            let [low, high] = bytes.get(bytes.len().checked_sub(2)?..)? else {
+                return None;
+            };
+            Some(i16::from_le_bytes([*low, *high]) as i32)
+        }
+        _ => None,
+    }
+}
+
+/// Finds the wraparound, if any, in a single instruction
+fn wraparound(disassembler: &Disassembler, instruction: &Instruction) -> Option<Wraparound> {
+    if instruction.is_invalid() {
+        let offset = (instruction.ip() as usize).checked_sub(COM_OFFSET as usize)?;
+        let available = disassembler.data.len().checked_sub(offset)?;
+        if available >= MAX_INSTRUCTION {
+            return None;
+        }
+        // decode again as if the file went on, to see how long it would be
+        let mut padded = disassembler.data[offset..].to_vec();
+        padded.resize(MAX_INSTRUCTION, 0);
+        let whole =
+            Decoder::with_ip(SIZE, &padded, instruction.ip(), DecoderOptions::NONE).decode();
+        return (!whole.is_invalid() && whole.len() > available).then_some(Wraparound::Truncated {
+            length: whole.len(),
+            available,
+        });
+    }
+    if instruction.op_kind(0) == OpKind::NearBranch16 {
+        let displacement = branch_displacement(disassembler, instruction)?;
+        let unwrapped = instruction.next_ip() as i32 + displacement;
+        return (!(0..=0xFFFF).contains(&unwrapped)).then_some(Wraparound::Branch {
+            target: instruction.near_branch16(),
+        });
+    }
+    let fixed = (0..instruction.op_count())
+        .any(|operand| instruction.op_kind(operand) == OpKind::Memory)
+        && instruction.memory_base() == Register::None
+        && instruction.memory_index() == Register::None;
+    let address = instruction.memory_displacement32() as usize;
+    let size = instruction.memory_size().size();
+    (fixed && address + size > 0x10000).then_some(Wraparound::Memory {
+        address: address as Address,
+        size,
+    })
+}
+
+/// Finds the branches, memory accesses and instructions that run off the
+/// end of the segment or the file
+///
+/// A COM program has one 64K segment, and offsets past 0xFFFF wrap around
+/// to its start. Code rarely means to, so each wraparound is reported rather
+/// than quietly followed. Only accesses at fixed addresses are checked;
+/// where `[bx+si]` points depends on the registers at run time.
+///
+/// # Example
+///
+/// ```
+/// use disassembler::disassemble::Disassembler;
+/// use disassembler::wraparound::{find_wraparounds, Wraparound};
+///
+/// // mov ax, [0xffff] / ret
+/// let found = find_wraparounds(&Disassembler::new(vec![0xA1, 0xFF, 0xFF, 0xC3]));
+/// assert_eq!(found[0].kind, Wraparound::Memory { address: 0xFFFF, size: 2 });
+/// ```
+pub fn find_wraparounds(disassembler: &Disassembler) -> Vec<WrapFinding> {
+    disassembler
+        .instructions
+        .0
+        .iter()
+        .filter(|instruction| !is_data(instruction))
+        .filter_map(|instruction| {
+            Some(WrapFinding {
+                address: instruction.ip() as Address,
+                kind: wraparound(disassembler, instruction)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(data: Vec<u8>) -> Vec<(Address, Wraparound)> {
+        find_wraparounds(&Disassembler::new(data))
+            .into_iter()
+            .map(|finding| (finding.address, finding.kind))
+            .collect()
+    }
+
+    // ──────────────────────────────────────────────────────────────────────────
+    // 1.  Wraparounds
+    // ──────────────────────────────────────────────────────────────────────────
+    #[test]
+    fn branches_past_either_end_wrap() {
+        let data = vec![
+            0xE9, 0xFD, 0xFE, // jmp 0, backwards to the PSP
+            0xEB, 0xFB, // jmp 0x100
+            0xE9, 0x00, 0xFE, // jmp -0x200, below 0
+        ];
+        assert_eq!(
+            kinds(data),
+            vec![(0x105, Wraparound::Branch { target: 0xFF08 })]
+        );
+
+        // jmp +0x7fff from 0x8103 goes past 0xffff
+        let mut data = vec![0x90; 0x8000];
+        data.extend([0xE9, 0xFF, 0x7F]);
+        assert_eq!(
+            kinds(data),
+            vec![(0x8100, Wraparound::Branch { target: 0x0102 })]
+        );
+    }
+
+    #[test]
+    fn truncated_instructions_are_reported() {
+        let disassembler = Disassembler::new(vec![0x90, 0xB8, 0x34]);
+        let found = find_wraparounds(&disassembler);
+        assert_eq!(
+            found,
+            vec![WrapFinding {
+                address: 0x101,
+                kind: Wraparound::Truncated {
+                    length: 3,
+                    available: 2
+                },
+            }]
+        );
+        assert_eq!(
+            found[0].to_string(),
+            "3-byte instruction cut off by the end of the file after 2 bytes"
+        );
+    }
+
+    #[test]
+    fn only_fixed_accesses_past_the_end_wrap() {
+        // mov al, [0xffff] / mov ax, [bx+0xffff] / mov ax, [0xfffe] / ret
+        let data = vec![
+            0xA0, 0xFF, 0xFF, 0x8B, 0x87, 0xFF, 0xFF, 0xA1, 0xFE, 0xFF, 0xC3,
+        ];
+        assert_eq!(kinds(data), vec![]);
+    }
+}
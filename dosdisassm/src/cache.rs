@@ -0,0 +1,98 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use disassembler::disassemble::Disassembler;
+use sha2::{Digest, Sha256};
+
+/// The directory analysis results are cached in
+///
+/// `$XDG_CACHE_HOME/dosdisassm` or `~/.cache/dosdisassm` on Unix,
+/// `%LOCALAPPDATA%\dosdisassm\cache` on Windows.
+pub fn directory() -> io::Result<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA")
+            .map(|dir| PathBuf::from(dir).join("dosdisassm").join("cache"))
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .map(|dir| dir.join("dosdisassm"))
+    };
+    base.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "could not determine the cache directory",
+        )
+    })
+}
+
+/// The cache key for `program` analysed with `options`
+///
+/// `options` describes every setting that changes the analysis result. The
+/// dosdisassm version is part of the key, so upgrading never reads results
+/// written by an older analysis.
+pub fn key(program: &[u8], options: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+    hasher.update([0]);
+    hasher.update(options.as_bytes());
+    hasher.update([0]);
+    hasher.update(program);
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Loads the analysis cached under `key`, if there is a readable one
+pub fn load(key: &str) -> Option<Disassembler> {
+    let contents = fs::read(directory().ok()?.join(format!("{key}.json"))).ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+/// Caches `disassembler` under `key`
+pub fn store(key: &str, disassembler: &Disassembler) -> io::Result<()> {
+    let directory = directory()?;
+    fs::create_dir_all(&directory)?;
+    let contents = serde_json::to_vec(disassembler).map_err(io::Error::other)?;
+    // write to a temporary file first so a concurrent run never reads half an entry
+    let temporary = directory.join(format!("{key}.{}.tmp", process_id()));
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, directory.join(format!("{key}.json")))
+}
+
+/// This process's id, to keep temporary files of concurrent runs apart
+#[cfg(not(target_os = "wasi"))]
+fn process_id() -> u32 {
+    std::process::id()
+}
+
+/// WASI has no process ids, and a sandbox runs one analysis at a time
+#[cfg(target_os = "wasi")]
+fn process_id() -> u32 {
+    0
+}
+
+/// Deletes every cached analysis, returning how many entries were removed
+pub fn clear() -> io::Result<usize> {
+    let directory = directory()?;
+    let entries = match fs::read_dir(&directory) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err),
+    };
+    let mut removed = 0;
+    for entry in entries {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|ext| ext == "json" || ext == "tmp")
+        {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
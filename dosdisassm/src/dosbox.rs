@@ -0,0 +1,41 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use disassembler::disassemble::Disassembler;
+
+use crate::status::with_path;
+
+/// Writes the labels as a linker map a DOSBox-X debugger can load, so
+/// breakpoints and disassembly in the emulator show the same names
+///
+/// The map has the layout of a Microsoft LINK `.map` file: a segment table
+/// with the one segment of a COM program, then the labels under "Publics by
+/// Value". As in any linker map, segments are counted from where the program
+/// is loaded, so every label is in segment `0000` at its offset from the
+/// start of the PSP.
+pub fn export(disassembler: &Disassembler, path: &Path) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path).map_err(with_path(path))?);
+    write_map(disassembler, &mut out)?;
+    out.flush()
+}
+
+fn write_map(disassembler: &Disassembler, out: &mut impl Write) -> io::Result<()> {
+    let mut labels: Vec<_> = disassembler.labels.0.iter().collect();
+    labels.sort_by_key(|label| label.address);
+    labels.dedup_by_key(|label| label.address);
+
+    writeln!(out)?;
+    writeln!(out, " Start  Stop   Length Name               Class")?;
+    writeln!(out, " 00000H 0FFFFH 10000H _TEXT              CODE")?;
+    writeln!(out)?;
+    writeln!(out, "  Address         Publics by Value")?;
+    writeln!(out)?;
+    for label in labels {
+        // names in a map file cannot contain spaces
+        let name = label.name.replace(char::is_whitespace, "_");
+        writeln!(out, " 0000:{:04X}       {name}", label.address)?;
+    }
+    writeln!(out)?;
+    writeln!(out, "Program entry point at 0000:0100")
+}
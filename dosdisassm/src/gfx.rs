@@ -0,0 +1,39 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use disassembler::disassemble::Disassembler;
+use disassembler::graphics::{Graphic, Image, find_graphics};
+
+use crate::status::with_path;
+
+/// Writes a PNG preview of every block that may be graphics into `directory`
+///
+/// The directory is created if needed. Returns the blocks written, each
+/// to the file named by [`Graphic::file_name`].
+pub fn extract(disassembler: &Disassembler, directory: &Path) -> io::Result<Vec<Graphic>> {
+    fs::create_dir_all(directory).map_err(with_path(directory))?;
+    let graphics = find_graphics(disassembler);
+    for graphic in &graphics {
+        let image = graphic.format.render(graphic.bytes(disassembler));
+        write_png(&image, &directory.join(graphic.file_name()))?;
+    }
+    Ok(graphics)
+}
+
+/// Writes `image` to `path` as an RGB PNG
+pub fn write_png(image: &Image, path: &Path) -> io::Result<()> {
+    let file = File::create(path).map_err(with_path(path))?;
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        image.width as u32,
+        image.height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer
+        .write_image_data(&image.rgb)
+        .map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
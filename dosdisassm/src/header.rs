@@ -0,0 +1,86 @@
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::info;
+
+/// The options that name files rather than change the listing, followed by
+/// their value
+const FILE_OPTIONS: [&str; 4] = ["-i", "--input", "-o", "--output"];
+
+/// The comment block put at the top of a listing to record how it was made
+///
+/// Each line is a `; field: value` line. The version, input path and date
+/// are among [`VOLATILE_FIELDS`](disassembler::golden::VOLATILE_FIELDS), so
+/// `--normalize` leaves them out; `date` is false to leave out the date on
+/// its own.
+pub fn header(input: &Path, program: &[u8], date: bool) -> String {
+    let mut lines = vec![
+        format!("; version: DosDisassm {}", env!("CARGO_PKG_VERSION")),
+        format!("; input: {}", input.display()),
+        format!("; size: {} bytes", program.len()),
+        format!("; sha256: {}", info::sha256(program)),
+        format!("; options: {}", options(std::env::args().skip(1))),
+    ];
+    if date {
+        lines.push(format!("; date: {}", today()));
+    }
+    // a blank line between the header and the listing
+    lines.extend([String::new(), String::new()]);
+    lines.join("\n")
+}
+
+/// The command-line arguments without the input and output files
+fn options(args: impl Iterator<Item = String>) -> String {
+    let mut kept = Vec::new();
+    let mut skip_value = false;
+    for arg in args {
+        if std::mem::take(&mut skip_value) {
+            continue;
+        }
+        if FILE_OPTIONS.contains(&arg.as_str()) {
+            skip_value = true;
+        } else if !FILE_OPTIONS
+            .iter()
+            .any(|option| arg.starts_with(&format!("{option}=")))
+        {
+            kept.push(arg);
+        }
+    }
+    if kept.is_empty() {
+        "(defaults)".to_string()
+    } else {
+        kept.join(" ")
+    }
+}
+
+/// Today's date in UTC as `YYYY-MM-DD`
+fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let (year, month, day) = civil_date(seconds / 86_400);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// The year, month and day `days` days after 1970-01-01
+///
+/// This is Howard Hinnant's `civil_from_days`, counting in 400-year eras
+/// that start on the 1st of March so leap days fall at the end.
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,121 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use disassembler::compiler::Identification;
+use disassembler::disassemble::{Disassembler, is_data};
+use disassembler::fileops::FileActivity;
+use disassembler::label::LabelType;
+use disassembler::limits::Limits;
+use disassembler::loader::Loader;
+use disassembler::managers::MemoryManagers;
+use disassembler::risk::RiskReport;
+use disassembler::version::minimum_dos_version;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::status::with_path;
+
+/// How the summary of a program is written
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `key: value` lines for reading
+    Text,
+    /// A single JSON object for scripts
+    Json,
+}
+
+/// The SHA-256 hash of `bytes` in lowercase hex
+pub fn sha256(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// A summary of the program at `path`: its size and hash, what it was
+/// built with, whether it loads another program, the oldest DOS it checks
+/// for, the files it touches, the expanded and extended memory it
+/// allocates and how risky it looks
+pub fn write(path: &Path, limits: Limits, out: &mut impl Write, format: Format) -> io::Result<()> {
+    let program = std::fs::read(path).map_err(with_path(path))?;
+    let sha256 = sha256(&program);
+    let bytes = program.len();
+    let disassembler = Disassembler::with_limits(program, limits);
+    let instructions = disassembler
+        .instructions
+        .0
+        .iter()
+        .filter(|instruction| !is_data(instruction))
+        .count();
+    let functions = disassembler
+        .labels
+        .0
+        .iter()
+        .filter(|label| label.label_type == LabelType::FUNCTION)
+        .count();
+    let compiler = Identification::detect(&disassembler);
+    let loader = Loader::detect(&disassembler);
+    let risk = RiskReport::assess(&disassembler);
+    let dos = minimum_dos_version(&disassembler);
+    let files = FileActivity::analyze(&disassembler);
+    let managers = MemoryManagers::analyze(&disassembler);
+    let ems = managers.expanded();
+    let xms = managers.extended();
+
+    match format {
+        Format::Text => {
+            writeln!(out, "file: {}", path.display())?;
+            writeln!(out, "bytes: {bytes}")?;
+            writeln!(out, "sha256: {sha256}")?;
+            writeln!(out, "instructions: {instructions}")?;
+            writeln!(out, "functions: {functions}")?;
+            writeln!(out, "compiler: {compiler}")?;
+            if let Some(loader) = &loader {
+                writeln!(out, "loader: {loader}")?;
+            }
+            if let Some(dos) = dos {
+                writeln!(out, "dos: {dos} or later")?;
+            }
+            if !files.files.is_empty() {
+                writeln!(out, "files:")?;
+                for file in &files.files {
+                    writeln!(out, "  {file}")?;
+                }
+            }
+            if let Some(ems) = ems {
+                writeln!(out, "ems: {ems}")?;
+            }
+            if let Some(xms) = xms {
+                writeln!(out, "xms: {xms}")?;
+            }
+            writeln!(out, "risk: {}/100 ({})", risk.score, risk.level)?;
+            for reason in &risk.reasons {
+                match reason.address {
+                    Some(address) => {
+                        writeln!(out, "  +{} 0x{address:04x}: {}", reason.weight, reason.text)?
+                    }
+                    None => writeln!(out, "  +{} {}", reason.weight, reason.text)?,
+                }
+            }
+        }
+        Format::Json => {
+            let summary = json!({
+                "file": path.display().to_string(),
+                "bytes": bytes,
+                "sha256": sha256,
+                "instructions": instructions,
+                "functions": functions,
+                "compiler": compiler,
+                "loader": loader,
+                "dos": dos,
+                "files": files.files,
+                "ems": ems,
+                "xms": xms,
+                "risk": risk,
+            });
+            serde_json::to_writer_pretty(&mut *out, &summary).map_err(io::Error::other)?;
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use disassembler::consts::Address;
+use disassembler::disassemble::{Disassembler, DisassemblerOptions};
+use disassembler::syscall::error_name;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait,
+};
+use lsp_types::request::{
+    GotoDefinition, HoverRequest, References, Rename, Request as RequestTrait,
+};
+use lsp_types::{
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf, Position, Range,
+    ReferenceParams, RenameParams, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::vscode::split_offset;
+
+/// An open listing and the program it was generated from
+struct Document {
+    text: String,
+    program: Option<Program>,
+}
+
+/// A program next to an open listing, analysed the same way as on the command line
+struct Program {
+    disassembler: Disassembler,
+    /// Each line of the listing written with the default options, without
+    /// its offset comment, and the address it is at
+    lines: Vec<(String, Option<Address>)>,
+}
+
+impl Program {
+    /// Loads the program a listing at `path` was made from, which has the
+    /// same name and a .com extension
+    fn load(path: &Path) -> Option<Program> {
+        let bytes = ["com", "COM"]
+            .iter()
+            .find_map(|extension| std::fs::read(path.with_extension(extension)).ok())?;
+        let disassembler = Disassembler::new(bytes);
+        let lines = rendered_lines(&disassembler);
+        Some(Program {
+            disassembler,
+            lines,
+        })
+    }
+
+    /// The address of line `line` of `text`, from its `; 0x0100` offset
+    /// comment if it has one and otherwise from the matching line of the
+    /// listing
+    ///
+    /// Lines are matched in order on their code, leaving out comments, so
+    /// listings written with other options or edited by hand still line up
+    /// where their code is unchanged.
+    fn address(&self, text: &str, line: usize) -> Option<Address> {
+        let target = text.lines().nth(line)?;
+        if let (_, Some(address)) = split_offset(target) {
+            return Some(address);
+        }
+        let mut next = 0;
+        let mut found = None;
+        for document_line in text.lines().take(line + 1) {
+            let wanted = code(document_line);
+            found = None;
+            if wanted.is_empty() {
+                continue;
+            }
+            if let Some(at) = self.lines[next..]
+                .iter()
+                .position(|(rendered, _)| code(rendered) == wanted)
+            {
+                found = self.lines[next + at].1;
+                next += at + 1;
+            }
+        }
+        found
+    }
+
+    fn label(&self, name: &str) -> Option<Address> {
+        self.disassembler
+            .labels
+            .0
+            .iter()
+            .find(|label| label.name == name)
+            .map(|label| label.address)
+    }
+}
+
+/// The part of `line` before its comment, trimmed
+fn code(line: &str) -> &str {
+    let mut quote = None;
+    for (at, c) in line.char_indices() {
+        match (quote, c) {
+            (None, ';') => return line[..at].trim(),
+            (None, '"' | '\'' | '`') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            _ => {}
+        }
+    }
+    line.trim()
+}
+
+/// Each line of the listing written with the default options, without its
+/// offset comment, and the address it is at
+fn rendered_lines(disassembler: &Disassembler) -> Vec<(String, Option<Address>)> {
+    let opts = DisassemblerOptions {
+        offset_comments: true,
+        ..DisassemblerOptions::default()
+    };
+    let mut listing = Vec::new();
+    if disassembler.disassemble_stream(&mut listing, opts).is_err() {
+        return Vec::new();
+    }
+    String::from_utf8_lossy(&listing)
+        .lines()
+        .map(|line| {
+            let (text, address) = split_offset(line);
+            (text.to_string(), address)
+        })
+        .collect()
+}
+
+/// Returns true if `c` can be part of a NASM identifier
+fn is_identifier(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | '@' | '?' | '#' | '~')
+}
+
+/// The identifier at `position` in `text` and the range it covers
+fn word_at(text: &str, position: Position) -> Option<(String, Range)> {
+    let line = text.lines().nth(position.line as usize)?;
+    let column = (position.character as usize).min(line.len());
+    let start = line[..column]
+        .rfind(|c: char| !is_identifier(c))
+        .map_or(0, |at| at + 1);
+    let end = line[column..]
+        .find(|c: char| !is_identifier(c))
+        .map_or(line.len(), |at| column + at);
+    (start < end).then(|| {
+        let range = Range::new(
+            Position::new(position.line, start as u32),
+            Position::new(position.line, end as u32),
+        );
+        (line[start..end].to_string(), range)
+    })
+}
+
+/// Every place `word` appears in `text` as a whole identifier
+fn occurrences(text: &str, word: &str) -> Vec<Range> {
+    let mut found = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        for (at, _) in line.match_indices(word) {
+            let before = line[..at].chars().next_back();
+            let after = line[at + word.len()..].chars().next();
+            if !before.is_some_and(is_identifier) && !after.is_some_and(is_identifier) {
+                found.push(Range::new(
+                    Position::new(number as u32, at as u32),
+                    Position::new(number as u32, (at + word.len()) as u32),
+                ));
+            }
+        }
+    }
+    found
+}
+
+/// Describes `address` for a hover: what it is, its bytes, its references
+/// and the DOS call made there
+fn describe(disassembler: &Disassembler, address: Address) -> String {
+    let info = disassembler.query(address);
+    let mut text = format!("**{info}**");
+    if let Some(instruction) = info.instruction {
+        let bytes: Vec<String> = disassembler
+            .original_bytes(&instruction)
+            .unwrap_or_default()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        text.push_str(&format!("\n\nbytes: `{}`", bytes.join(" ")));
+    }
+    if let Some(string) = &info.string {
+        text.push_str(&format!("\n\n`{}`", string.as_db_statement()));
+    }
+    if let Some(syscall) = disassembler.syscall_list.get_by_address(info.start) {
+        text.push_str(&format!("\n\nint 21h: {}", syscall.number));
+        let errors: Vec<String> = syscall
+            .number
+            .error_codes()
+            .iter()
+            .map(|&code| format!("{code:02x}h {}", error_name(code).unwrap_or("unknown")))
+            .collect();
+        if !errors.is_empty() {
+            text.push_str(&format!(
+                "\n\nsets the carry flag on error: {}",
+                errors.join(", ")
+            ));
+        }
+    }
+    for comment in &info.comments {
+        text.push_str(&format!("\n\n{}", comment.with_provenance()));
+    }
+    text
+}
+
+/// Answers requests about open listings
+struct Server {
+    documents: HashMap<Url, Document>,
+}
+
+impl Server {
+    fn open(&mut self, uri: Url, text: String) {
+        let program = uri.to_file_path().ok().as_deref().and_then(Program::load);
+        self.documents.insert(uri, Document { text, program });
+    }
+
+    /// The document, its program, and the identifier at `position`
+    fn lookup(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> Option<(&Document, &Program, String, Range)> {
+        let document = self.documents.get(uri)?;
+        let program = document.program.as_ref()?;
+        let (word, range) = word_at(&document.text, position)?;
+        Some((document, program, word, range))
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let at = params.text_document_position_params;
+        let uri = at.text_document.uri;
+        let (document, program, word, _) = self.lookup(&uri, at.position)?;
+        program.label(&word)?;
+        let definition = format!("{word}:");
+        let line = document
+            .text
+            .lines()
+            .position(|line| line.trim_start().starts_with(&definition))?;
+        let column = document.text.lines().nth(line)?.find(&definition)?;
+        let range = Range::new(
+            Position::new(line as u32, column as u32),
+            Position::new(line as u32, (column + word.len()) as u32),
+        );
+        Some(GotoDefinitionResponse::Scalar(Location::new(uri, range)))
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let at = params.text_document_position_params;
+        let document = self.documents.get(&at.text_document.uri)?;
+        let program = document.program.as_ref()?;
+        let word = word_at(&document.text, at.position);
+        let (address, range) = match word
+            .as_ref()
+            .and_then(|(word, range)| Some((program.label(word)?, *range)))
+        {
+            Some((address, range)) => (address, Some(range)),
+            None => (
+                program.address(&document.text, at.position.line as usize)?,
+                None,
+            ),
+        };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: describe(&program.disassembler, address),
+            }),
+            range,
+        })
+    }
+
+    fn references(&self, params: ReferenceParams) -> Option<Vec<Location>> {
+        let at = params.text_document_position;
+        let uri = at.text_document.uri;
+        let (document, program, word, _) = self.lookup(&uri, at.position)?;
+        program.label(&word)?;
+        let definition = format!("{word}:");
+        Some(
+            occurrences(&document.text, &word)
+                .into_iter()
+                .filter(|range| {
+                    params.context.include_declaration
+                        || !document
+                            .text
+                            .lines()
+                            .nth(range.start.line as usize)
+                            .is_some_and(|line| line.trim_start().starts_with(&definition))
+                })
+                .map(|range| Location::new(uri.clone(), range))
+                .collect(),
+        )
+    }
+
+    fn rename(&mut self, params: RenameParams) -> Result<Option<WorkspaceEdit>, String> {
+        let at = params.text_document_position;
+        let uri = at.text_document.uri;
+        let Some((document, program, word, _)) = self.lookup(&uri, at.position) else {
+            return Ok(None);
+        };
+        let Some(address) = program.label(&word) else {
+            return Err(format!("{word} is not a label"));
+        };
+        let name = params.new_name;
+        let valid = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || matches!(c, '_' | '.' | '?'))
+            && name.chars().all(is_identifier);
+        if !valid {
+            return Err(format!("{name} is not a valid label name"));
+        }
+        if program.label(&name).is_some() {
+            return Err(format!("a label named {name} already exists"));
+        }
+        let edits: Vec<TextEdit> = occurrences(&document.text, &word)
+            .into_iter()
+            .map(|range| TextEdit::new(range, name.clone()))
+            .collect();
+        if let Some(program) = self
+            .documents
+            .get_mut(&uri)
+            .and_then(|document| document.program.as_mut())
+        {
+            program.disassembler.rename_label(address, name);
+        }
+        Ok(Some(WorkspaceEdit::new(HashMap::from([(uri, edits)]))))
+    }
+
+    fn handle(&mut self, request: Request) -> Response {
+        /// Answers a request whose parameters parse with `answer`
+        fn respond<R: RequestTrait>(
+            id: RequestId,
+            params: serde_json::Value,
+            answer: impl FnOnce(R::Params) -> Result<R::Result, String>,
+        ) -> Response {
+            match serde_json::from_value(params) {
+                Ok(params) => match answer(params) {
+                    Ok(result) => Response::new_ok(id, result),
+                    Err(message) => {
+                        Response::new_err(id, ErrorCode::InvalidRequest as i32, message)
+                    }
+                },
+                Err(err) => Response::new_err(id, ErrorCode::InvalidParams as i32, err.to_string()),
+            }
+        }
+
+        let Request { id, method, params } = request;
+        match method.as_str() {
+            GotoDefinition::METHOD => {
+                respond::<GotoDefinition>(id, params, |p| Ok(self.definition(p)))
+            }
+            HoverRequest::METHOD => respond::<HoverRequest>(id, params, |p| Ok(self.hover(p))),
+            References::METHOD => respond::<References>(id, params, |p| Ok(self.references(p))),
+            Rename::METHOD => respond::<Rename>(id, params, |p| self.rename(p)),
+            _ => Response::new_err(
+                id,
+                ErrorCode::MethodNotFound as i32,
+                format!("{method} is not supported"),
+            ),
+        }
+    }
+
+    fn notify(&mut self, notification: Notification) {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                if let Ok(params) =
+                    serde_json::from_value::<DidOpenTextDocumentParams>(notification.params)
+                {
+                    self.open(params.text_document.uri, params.text_document.text);
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                if let Ok(params) =
+                    serde_json::from_value::<DidChangeTextDocumentParams>(notification.params)
+                    && let Some(change) = params.content_changes.into_iter().next_back()
+                    && let Some(document) = self.documents.get_mut(&params.text_document.uri)
+                {
+                    // full sync: the last change holds the whole text
+                    document.text = change.text;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                if let Ok(params) =
+                    serde_json::from_value::<DidCloseTextDocumentParams>(notification.params)
+                {
+                    self.documents.remove(&params.text_document.uri);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Serves listings over the Language Server Protocol on stdin and stdout
+///
+/// A listing `NAME.asm` is backed by the program `NAME.com` next to it.
+/// Lines are matched to addresses by their `; 0x0100` offset comments, or
+/// else by their position in the listing written with the default options.
+/// Renamed labels last until the server stops.
+pub fn serve() -> io::Result<()> {
+    let (connection, threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        references_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    let capabilities = serde_json::to_value(capabilities).map_err(io::Error::other)?;
+    connection
+        .initialize(capabilities)
+        .map_err(io::Error::other)?;
+
+    let mut server = Server {
+        documents: HashMap::new(),
+    };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection
+                    .handle_shutdown(&request)
+                    .map_err(io::Error::other)?
+                {
+                    break;
+                }
+                let response = server.handle(request);
+                connection
+                    .sender
+                    .send(Message::Response(response))
+                    .map_err(io::Error::other)?;
+            }
+            Message::Notification(notification) => server.notify(notification),
+            Message::Response(_) => {}
+        }
+    }
+    drop(connection);
+    threads.join()
+}
@@ -1,18 +1,475 @@
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
 use std::fs::File;
-use std::io::{self, Read};
-use std::path::PathBuf;
+use std::io::{self, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
 
-use disassembler::comment::{Comment, CommentType};
+use disassembler::annotations::Annotations;
+use disassembler::comment::{Comment, CommentSource, CommentType};
+use disassembler::compiler::Identification;
+use disassembler::constants::ConstantTable;
+use disassembler::coverage::CoverageReport;
+use disassembler::degenerate::find_degenerate;
+use disassembler::deobfuscate::normalize;
 use disassembler::disassemble::{Disassembler, DisassemblerOptions};
+use disassembler::emulate::{Emulator, LOAD_SEGMENT, StopReason};
+use disassembler::files::{FileChange, FileSystem, dos_name};
+use disassembler::fixpoint::{Discovery, settle};
+use disassembler::freespace::FreeSpace;
+use disassembler::fuzz::{FuzzOptions, fuzz};
+use disassembler::golden;
+use disassembler::handlers::find_handlers;
+use disassembler::hook::{HookError, Insertion, insert_code, install_hook};
+use disassembler::interpreter::Interpreter;
+use disassembler::keys::{Keystroke, parse_keys};
+use disassembler::label::LabelType;
+use disassembler::limits::{LimitKind, Limits};
+use disassembler::linear::write_linear;
+use disassembler::loader::Loader;
+use disassembler::lockstep::{Granularity, lockstep};
+use disassembler::memory::MemoryMap;
+use disassembler::music::find_music;
+use disassembler::overrides::find_size_overrides;
+use disassembler::probe::{ProbeSet, annotate};
+use disassembler::protection::find_protection;
+use disassembler::recover::recover;
+use disassembler::redundancy::find_redundancies;
+use disassembler::region::NamedRegion;
+use disassembler::replay::Recording;
+use disassembler::risk::RiskReport;
+use disassembler::rules::RuleSet;
+use disassembler::selfmod::find_code_writes;
+use disassembler::session::AuxFile;
+use disassembler::signatures::SignaturePack;
+use disassembler::style::{Case, Radix, Style};
+use disassembler::syntax::check_syntax;
+use disassembler::table::Table;
+use disassembler::trainer::find_counters;
+use disassembler::watch::{WatchReport, extent};
+use disassembler::wraparound::find_wraparounds;
+use indicatif::ProgressBar;
+
+mod cache;
+mod dosbox;
+mod gfx;
+mod header;
+mod info;
+#[cfg(feature = "lsp")]
+mod lsp;
+mod music;
+mod nasm;
+mod progress;
+mod sarif;
+mod stats;
+mod status;
+mod strings;
+mod translate;
+mod vscode;
+
+use status::{ErrorFormat, Status};
 
 /// Simple CLI for disassembling DOS .COM binaries
 #[derive(Parser, Debug)]
-#[command(author, version, about)]
+#[command(
+    name = "dosdisassm",
+    author,
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
+    #[command(flatten)]
+    disasm: DisasmArgs,
+
+    /// How to write errors: text, or JSON objects with the exit status for scripts
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Utilities that do not disassemble anything
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage the cache of analysis results
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Run a .COM program in the built-in emulator and print what it writes to the console
+    Emulate {
+        /// Path to the .COM binary file
+        input: PathBuf,
+
+        /// Stop after this many instructions
+        #[arg(long, default_value_t = DEFAULT_BUDGET)]
+        budget: u64,
+
+        /// Keys to press for the program's keyboard input calls, such as
+        /// `Y\n` or `{down}{down}{enter}`, with `{wait N}` to hold the next
+        /// key back for N instructions
+        #[arg(long, default_value = "")]
+        keys: String,
+
+        /// Call only the function at this address and stop when it returns,
+        /// instead of running the program from its entry point
+        #[arg(long, value_name = "ADDRESS", value_parser = parse_address)]
+        call: Option<u16>,
+
+        /// Set a register before running, e.g. `si=0x200`, may be repeated
+        #[arg(long, value_name = "REGISTER=VALUE", value_parser = parse_register)]
+        set: Vec<(String, u16)>,
+
+        /// Write bytes given in hex to memory before running, e.g.
+        /// `0x200=01 02 03`, may be repeated
+        #[arg(long, value_name = "ADDRESS=BYTES", value_parser = parse_hook)]
+        poke: Vec<(u16, Vec<u8>)>,
+
+        /// Print memory in hex after running, e.g. `0x200:16`, may be repeated
+        #[arg(long, value_name = "ADDRESS:LENGTH", value_parser = parse_dump)]
+        dump: Vec<(u16, usize)>,
+
+        /// Report which instructions read and write the buffer at a label or
+        /// address, up to the next label, may be repeated
+        #[arg(long, value_name = "LABEL")]
+        watch: Vec<String>,
+
+        /// Save the run, with its setup, keys and every service call's
+        /// result, so it can be replayed exactly with --replay
+        #[arg(long, value_name = "FILE")]
+        record: Option<PathBuf>,
+
+        /// Run exactly as recorded with --record, checking the program makes
+        /// the same service calls and ends the same way
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = ["budget", "keys", "call", "set", "poke", "record", "mount"]
+        )]
+        replay: Option<PathBuf>,
+
+        /// Probe file whose actions are taken on breakpoints, interrupt
+        /// calls and memory writes, may be repeated
+        #[arg(long = "probes", value_name = "FILE")]
+        probes: Vec<PathBuf>,
+
+        /// Let the program create, open, read, write, delete and search for
+        /// copies of the files in this directory; the directory itself is
+        /// left alone unless --write-back is given
+        #[arg(long, value_name = "DIR")]
+        mount: Option<PathBuf>,
+
+        /// Make the mounted files read-only to the program
+        #[arg(long, requires = "mount", default_value_t = false)]
+        read_only: bool,
+
+        /// Save the files the program created, changed or deleted back to
+        /// the mounted directory
+        #[arg(
+            long,
+            requires = "mount",
+            conflicts_with = "read_only",
+            default_value_t = false
+        )]
+        write_back: bool,
+
+        /// Save the text screen as the program left it, as a PNG picture if
+        /// FILE ends in `.png` and as text otherwise
+        #[arg(long, value_name = "FILE")]
+        screenshot: Option<PathBuf>,
+    },
+    /// Run two versions of a .COM program side by side in the emulator with
+    /// the same keys and report where they first behave differently
+    ///
+    /// By default both must execute the same addresses with the same
+    /// registers; with --by-service they need only make the same DOS and BIOS
+    /// calls, so patches that move code can be checked too. Either way they
+    /// must print the same and stop the same way.
+    Lockstep {
+        /// Path to the original .COM file
+        old: PathBuf,
+
+        /// Path to the changed .COM file
+        new: PathBuf,
+
+        /// Stop each program after this many instructions
+        #[arg(long, default_value_t = DEFAULT_BUDGET)]
+        budget: u64,
+
+        /// Keys to press for both programs' keyboard input calls, as for
+        /// `emulate --keys`
+        #[arg(long, default_value = "")]
+        keys: String,
+
+        /// Give both programs their own copy of the files in this directory
+        #[arg(long, value_name = "DIR")]
+        mount: Option<PathBuf>,
+
+        /// Compare only the service calls the programs make, not every
+        /// instruction
+        #[arg(long, default_value_t = false)]
+        by_service: bool,
+    },
+    /// Print a completion script for the given shell to stdout
+    Completions {
+        /// The shell to generate completions for
+        shell: clap_complete::Shell,
+    },
+    /// Compare what static analysis decodes as code with what actually runs in the emulator
+    Coverage {
+        /// Paths to the .COM binary files
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Stop each program after this many instructions
+        #[arg(long, default_value_t = DEFAULT_BUDGET)]
+        budget: u64,
+
+        /// Compare against trace-assisted disassembly instead of the plain linear sweep
+        #[arg(long, default_value_t = false)]
+        trace_assist: bool,
+    },
+    /// Print the man page in roff format to stdout
+    Manpage,
+    /// Apply translated strings from a .po file written by --export-strings
+    Translate {
+        /// Path to the .COM binary file
+        input: PathBuf,
+
+        /// The translated .po file
+        #[arg(long, value_name = "FILE")]
+        strings: PathBuf,
+
+        /// Where to write the translated program
+        #[arg(long, value_name = "FILE")]
+        out: PathBuf,
+    },
+    /// Change a .COM program without overwriting anything it uses
+    Patch {
+        /// Path to the .COM binary file
+        input: PathBuf,
+
+        /// Run machine code given in hex before the instruction at an address,
+        /// e.g. `0x105=9c 50 58 9d`, may be repeated
+        #[arg(long, value_name = "ADDRESS=BYTES", value_parser = parse_hook)]
+        hook: Vec<(u16, Vec<u8>)>,
+
+        /// Insert machine code given in hex before the instruction at an
+        /// address, moving the rest of the program up and fixing its
+        /// branches, e.g. `0x105=90`, may be repeated
+        #[arg(long, value_name = "ADDRESS=BYTES", value_parser = parse_hook)]
+        insert: Vec<(u16, Vec<u8>)>,
+
+        /// Where to write the patched program
+        #[arg(long, value_name = "FILE")]
+        out: PathBuf,
+    },
+    /// Take the original program back out of a file infected by a simple
+    /// appending or prepending virus
+    ///
+    /// The infected program is emulated, with file access failing, until
+    /// the virus hands control back to the repaired program at 0x0100. The
+    /// result is only written if it runs the same as the infected program
+    /// did after the hand-off.
+    Recover {
+        /// Path to the infected .COM file
+        input: PathBuf,
+
+        /// Where to write the recovered program
+        #[arg(long, value_name = "FILE")]
+        out: PathBuf,
+
+        /// Stop after this many instructions
+        #[arg(long, default_value_t = DEFAULT_BUDGET)]
+        budget: u64,
+
+        /// The SHA-256 hash the clean program is known to have, checked
+        /// before anything is written
+        #[arg(long, value_name = "SHA256")]
+        expect: Option<String>,
+
+        /// Write the recovered program even if it does not run the same
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// Summarise a single .COM file: its hash, toolchain, the DOS version it
+    /// checks for and a risk score for triage
+    ///
+    /// The risk score adds up behaviour viruses and trojans show, such as
+    /// hooking int 21h, writing disk sectors and searching for *.COM files.
+    Info {
+        /// Path to the .COM binary file
+        input: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = info::Format::Text)]
+        format: info::Format,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Analyse every .COM file in a directory tree and print aggregate statistics
+    Stats {
+        /// Directory to search for .COM files
+        directory: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = stats::Format::Csv)]
+        format: stats::Format,
+
+        /// Number of files to analyse at once, defaults to the number of CPUs
+        #[arg(short, long)]
+        jobs: Option<usize>,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+
+        /// Also write what was found in every file to this SARIF log
+        #[arg(long, value_name = "FILE")]
+        sarif: Option<PathBuf>,
+    },
+    /// Analyse randomly mutated copies of every .COM file in a directory tree
+    /// and report any the analysis panics or hangs on
+    ///
+    /// The same seed always makes the same copies. Exits with an error if the
+    /// analysis failed on any of them.
+    FuzzCorpus {
+        /// Directory to search for .COM files
+        directory: PathBuf,
+
+        /// How many mutated copies of each file to analyse
+        #[arg(long, default_value_t = 100)]
+        iterations: usize,
+
+        /// The most mutations made to each copy
+        #[arg(long, default_value_t = 4)]
+        mutations: usize,
+
+        /// The seed the mutations are drawn from
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+
+        /// Also emulate each copy for this many instructions, 0 to skip it
+        #[arg(long, default_value_t = 10_000)]
+        budget: u64,
+
+        /// How many seconds analysing one copy may take before it counts as
+        /// hanging
+        #[arg(long, value_name = "SECONDS", value_parser = parse_seconds, default_value = "10")]
+        timeout: Duration,
+
+        /// Save the copies the analysis failed on to this directory
+        #[arg(long, value_name = "DIR")]
+        out: Option<PathBuf>,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Serve listings over the Language Server Protocol on stdin and stdout
+    ///
+    /// A listing NAME.asm is backed by the program NAME.com next to it.
+    #[cfg(feature = "lsp")]
+    Lsp,
+}
+
+/// Caps on the analysis of a single program
+#[derive(clap::Args, Debug)]
+struct LimitArgs {
+    /// Stop decoding after this many instructions and leave the rest of the program as data
+    #[arg(long, value_name = "N")]
+    max_instructions: Option<usize>,
+
+    /// Stop analysing after this many seconds and keep what was found so far
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    max_time: Option<Duration>,
+
+    /// Keep at most this many labels
+    #[arg(long, value_name = "N")]
+    max_labels: Option<usize>,
+
+    /// Keep at most this many strings
+    #[arg(long, value_name = "N")]
+    max_strings: Option<usize>,
+
+    /// Run the analysis at most this many times while it keeps finding new code, strings and tables
+    #[arg(long, value_name = "N")]
+    max_passes: Option<usize>,
+}
+
+impl LimitArgs {
+    fn limits(&self) -> Limits {
+        Limits {
+            max_instructions: self.max_instructions,
+            max_time: self.max_time,
+            max_labels: self.max_labels,
+            max_strings: self.max_strings,
+            max_passes: self.max_passes,
+        }
+    }
+}
+
+/// What to do with the analysis cache
+#[derive(Subcommand, Debug)]
+enum CacheAction {
+    /// Delete every cached analysis
+    Clear,
+    /// Print the directory the cache is kept in
+    Dir,
+}
+
+/// The oldest processor the program is meant to run on
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Cpu {
+    /// The 8086 and 8088 of the original PC
+    #[value(name = "8086")]
+    I8086,
+    /// The 80186, which added `push imm`, `enter` and shifts by an immediate
+    #[value(name = "186")]
+    I186,
+    /// The 80286 of the PC/AT
+    #[value(name = "286")]
+    I286,
+    /// The 80386, the first with 32-bit registers
+    #[value(name = "386")]
+    I386,
+}
+
+/// The base of numbers in instructions, see [`Radix`]
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum RadixArg {
+    /// Hex, with compared characters as 'A' and counts in decimal
+    Contextual,
+    /// Every number in hex
+    Hex,
+    /// Every number in decimal
+    Decimal,
+}
+
+/// What `--export` writes to the path given with `--export-to`
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportFormat {
+    /// The listing with a source map and symbol index for an editor, into a directory
+    Vscode,
+    /// A symbol map for the DOSBox-X debugger, into a file
+    DosboxSyms,
+    /// The listing as main.asm with the names it uses in an equates.inc it includes, into a
+    /// directory
+    Nasm,
+    /// The findings as a SARIF log for security and code review tools, into a file
+    Sarif,
+}
+
+/// Options for disassembling a binary, used when no subcommand is given
+#[derive(clap::Args, Debug)]
+struct DisasmArgs {
     /// Path to the .COM binary file
-    #[arg(short, long)]
-    input: PathBuf,
+    // optional only so subcommands can be parsed without it; clap still requires it otherwise
+    #[arg(short, long, required = true)]
+    input: Option<PathBuf>,
 
     /// Optional output file
     #[arg(short, long)]
@@ -41,49 +498,1654 @@ struct Args {
     #[arg(long, default_value_t = true)]
     /// Include misc comments in the output
     comments: bool,
+
+    /// Prefix comments with the analysis pass, user or script that added them
+    #[arg(long, default_value_t = false)]
+    comment_sources: bool,
+
+    /// Note where jumps into chains of jumps finally end up
+    #[arg(long, default_value_t = false)]
+    jump_chains: bool,
+
+    /// Write alignment padding byte for byte instead of as `align` and `times` directives
+    #[arg(long, default_value_t = false)]
+    literal_padding: bool,
+
+    /// Comment instructions NASM would assemble to other bytes than the file has, comparing with MASM
+    #[arg(long, default_value_t = false)]
+    check_syntax: bool,
+
+    /// Write instructions NASM would assemble to other bytes as db with their bytes
+    #[arg(long, default_value_t = false)]
+    exact_encodings: bool,
+
+    /// Leave out what changes between runs and tidy whitespace, for listings kept as golden files
+    #[arg(long, default_value_t = false)]
+    normalize: bool,
+
+    /// Start the listing with the tool version, input file, size, SHA-256, options and date
+    #[arg(long, default_value_t = false)]
+    header: bool,
+
+    /// Leave the date out of the header, so the same input gives the same listing
+    #[arg(long, default_value_t = false)]
+    no_date: bool,
+
+    /// Skip the analysis and list every byte as code, for quick greps over many files
+    #[arg(long, default_value_t = false)]
+    fast: bool,
+
+    /// The oldest processor the program should run on; before the 386, 32-bit size prefixes are errors
+    #[arg(long, value_enum, default_value_t = Cpu::I386)]
+    cpu: Cpu,
+
+    /// Write hex digits in upper case, in instructions, labels, offsets, bytes and strings
+    #[arg(long, default_value_t = false)]
+    uppercase_hex: bool,
+
+    /// Write mnemonics and db/dw in upper case
+    #[arg(long, default_value_t = false)]
+    uppercase_mnemonics: bool,
+
+    /// Pad addresses in labels and offsets to this many hex digits
+    #[arg(long, value_name = "DIGITS", default_value_t = 4)]
+    address_width: usize,
+
+    /// The base of numbers in instructions: contextual writes compared characters as 'A' and counts in decimal
+    #[arg(long, value_enum, default_value_t = RadixArg::Contextual)]
+    radix: RadixArg,
+
+    /// Run the program in the emulator first and use what it executed to separate code from data
+    #[arg(long, default_value_t = false)]
+    trace_assist: bool,
+
+    /// Instruction budget for --trace-assist, --watch and --probes
+    #[arg(long, default_value_t = DEFAULT_BUDGET)]
+    trace_budget: u64,
+
+    /// Run --trace-assist, --watch and --probes exactly as a run recorded with
+    /// `emulate --record`, with its keys and setup
+    #[arg(long, value_name = "FILE", conflicts_with = "trace_budget")]
+    replay: Option<PathBuf>,
+
+    /// Keys to press when the program reads the keyboard during
+    /// --trace-assist, --watch and --probes, as for `emulate --keys`
+    #[arg(long, default_value = "", conflicts_with = "replay")]
+    keys: String,
+
+    /// Run the program in the emulator with the probes in this file, adding
+    /// their comments where they fire, may be repeated
+    #[arg(long = "probes", value_name = "FILE")]
+    probes: Vec<PathBuf>,
+
+    /// Run the program in the emulator and add the instructions that read
+    /// or write the buffer at a label or address, up to the next label, as
+    /// run-time references, may be repeated
+    #[arg(long, value_name = "LABEL")]
+    watch: Vec<String>,
+
+    #[command(flatten)]
+    limits: LimitArgs,
+
+    /// Byte pattern rule file whose matches are labelled and commented, may be repeated
+    #[arg(long = "rules", value_name = "FILE")]
+    rules: Vec<PathBuf>,
+
+    /// Virus signature pack, such as signatures/dos-viruses.sig, whose
+    /// matches are named as regions, may be repeated
+    #[arg(long = "signatures", value_name = "FILE")]
+    signatures: Vec<PathBuf>,
+
+    /// Write the original program, taken back out of the first virus found
+    /// with --signatures, to this file
+    #[arg(long, value_name = "FILE", requires = "signatures")]
+    extract_host: Option<PathBuf>,
+
+    /// Table of `VALUE = meaning` lines explaining immediates, on top of the built-in one, may be repeated
+    #[arg(long = "constants", value_name = "FILE")]
+    constants: Vec<PathBuf>,
+
+    /// Map a companion file into the address space, e.g. `data.bin@0x2000`, may be repeated
+    #[arg(long, value_name = "FILE@ADDRESS", value_parser = parse_aux)]
+    aux: Vec<(PathBuf, u16)>,
+
+    /// Name a region of the program, e.g. `"font data@0x180-0x200"`, may be repeated
+    #[arg(long, value_name = "NAME@START-END", value_parser = parse_region)]
+    region: Vec<NamedRegion>,
+
+    /// Import labels, comments and data ranges from this annotations JSON file, may be repeated
+    #[arg(long = "annotations", value_name = "FILE")]
+    annotations: Vec<PathBuf>,
+
+    /// Write the labels, comments and data ranges to this file as annotations JSON
+    #[arg(long, value_name = "FILE")]
+    export_annotations: Option<PathBuf>,
+
+    /// Comment instructions whose flags are never read and push/pop pairs that cancel out
+    #[arg(long, default_value_t = false)]
+    redundant: bool,
+
+    /// Report memory locations used like lives or health counters and comment the code touching them
+    #[arg(long, default_value_t = false)]
+    counters: bool,
+
+    /// Report the memory the program allocates, frees and resizes, and whether it shrinks its own block before running another program
+    #[arg(long, default_value_t = false)]
+    memory: bool,
+
+    /// Write rule matches and other findings to this file as JSON
+    #[arg(long, value_name = "FILE")]
+    findings: Option<PathBuf>,
+
+    /// Write the strings with their bytes and references to this .csv, .json or .po file
+    #[arg(long, value_name = "FILE")]
+    export_strings: Option<PathBuf>,
+
+    /// Write PNG previews of fonts, pictures and other data blocks into this directory
+    #[arg(long, value_name = "DIR")]
+    extract_gfx: Option<PathBuf>,
+
+    /// Write the PC speaker and AdLib music tables found to this .json or .vgm file
+    #[arg(long, value_name = "FILE")]
+    export_music: Option<PathBuf>,
+
+    /// Export for another tool, to the path given with --export-to
+    #[arg(long, value_enum, value_name = "FORMAT", requires = "export_to")]
+    export: Option<ExportFormat>,
+
+    /// The file or directory --export writes to
+    #[arg(long, value_name = "PATH", requires = "export")]
+    export_to: Option<PathBuf>,
+
+    /// Undo obfuscation idioms and write the cleaned listing to this file
+    #[arg(long, value_name = "FILE")]
+    deobfuscate: Option<PathBuf>,
+
+    /// If the input is a loader, also disassemble the file it loads from this path
+    #[arg(long, value_name = "FILE")]
+    follow: Option<PathBuf>,
+
+    /// Analyse the input from scratch instead of reusing or storing a cached analysis
+    #[arg(long, default_value_t = false)]
+    no_cache: bool,
+}
+
+impl DisasmArgs {
+    /// How the listing writes numbers and mnemonics
+    fn style(&self) -> Style {
+        let case = |upper| if upper { Case::Upper } else { Case::Lower };
+        Style {
+            hex_case: case(self.uppercase_hex),
+            mnemonic_case: case(self.uppercase_mnemonics),
+            address_width: self.address_width,
+            radix: match self.radix {
+                RadixArg::Contextual => Radix::Contextual,
+                RadixArg::Hex => Radix::Hex,
+                RadixArg::Decimal => Radix::Decimal,
+            },
+        }
+    }
+
+    /// What the listing includes
+    fn options(&self) -> DisassemblerOptions {
+        DisassemblerOptions {
+            write_labels: self.labels,
+            write_indent: self.indent,
+            offset_comments: self.offsets,
+            syscall_comments: self.syscalls,
+            write_bytes: self.bytes,
+            misc_comments: self.comments,
+            comment_sources: self.comment_sources,
+            jump_chains: self.jump_chains,
+            padding_directives: !self.literal_padding,
+            equates: false,
+            exact_encodings: self.exact_encodings,
+        }
+    }
 }
 
-fn main() -> io::Result<()> {
-    let args = Args::parse();
+/// How many instructions the emulator runs unless told otherwise
+const DEFAULT_BUDGET: u64 = 1_000_000;
+
+fn main() -> ExitCode {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(err) if !err.use_stderr() => err.exit(),
+        Err(err) => {
+            // clap could not read --error-format either, so look for it
+            let json = std::env::args()
+                .collect::<Vec<_>>()
+                .windows(2)
+                .any(|pair| pair[0] == "--error-format" && pair[1] == "json")
+                || std::env::args().any(|arg| arg == "--error-format=json");
+            if json {
+                let message = err.render().to_string();
+                status::report(Status::InvalidInput, message.trim_end(), ErrorFormat::Json);
+            } else {
+                let _ = err.print();
+            }
+            return Status::InvalidInput.into();
+        }
+    };
+    let format = args.error_format;
+    if format == ErrorFormat::Json {
+        // the panic is reported as JSON below instead
+        panic::set_hook(Box::new(|_| {}));
+    }
+    let result = panic::catch_unwind(AssertUnwindSafe(|| match args.command {
+        Some(command) => run(command).map(|()| Status::Success),
+        None => disassemble(args.disasm),
+    }));
+    match result {
+        Ok(Ok(status)) => status,
+        Ok(Err(error)) => {
+            let status = Status::of(&error);
+            status::report(status, &error.to_string(), format);
+            status
+        }
+        Err(payload) => {
+            if format == ErrorFormat::Json {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "panicked".to_string());
+                status::report(Status::Internal, &message, format);
+            }
+            Status::Internal
+        }
+    }
+    .into()
+}
 
-    if args.input.extension().map_or(true, |ext| ext != "com") {
+/// Runs a subcommand
+fn run(command: Command) -> io::Result<()> {
+    match command {
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Args::command(), "dosdisassm", &mut io::stdout());
+            Ok(())
+        }
+        Command::Emulate {
+            input,
+            budget,
+            keys,
+            call,
+            set,
+            poke,
+            dump,
+            watch,
+            record,
+            replay,
+            probes,
+            mount,
+            read_only,
+            write_back,
+            screenshot,
+        } => emulate(
+            &input,
+            budget,
+            &keys,
+            call,
+            &Setup {
+                set,
+                poke,
+                dump,
+                watch,
+                record,
+                replay,
+                probes,
+                mount,
+                read_only,
+                write_back,
+                screenshot,
+            },
+        ),
+        Command::Lockstep {
+            old,
+            new,
+            budget,
+            keys,
+            mount,
+            by_service,
+        } => run_lockstep(&old, &new, budget, &keys, mount.as_deref(), by_service),
+        Command::Coverage {
+            inputs,
+            budget,
+            trace_assist,
+        } => coverage(&inputs, budget, trace_assist),
+        Command::Cache {
+            action: CacheAction::Clear,
+        } => {
+            let removed = cache::clear()?;
+            println!("Removed {removed} cached analyses");
+            Ok(())
+        }
+        Command::Cache {
+            action: CacheAction::Dir,
+        } => {
+            println!("{}", cache::directory()?.display());
+            Ok(())
+        }
+        Command::Manpage => clap_mangen::Man::new(Args::command()).render(&mut io::stdout()),
+        Command::Translate {
+            input,
+            strings,
+            out,
+        } => translate_program(&input, &strings, &out),
+        Command::Patch {
+            input,
+            hook,
+            insert,
+            out,
+        } => patch_program(&input, &insert, &hook, &out),
+        Command::Recover {
+            input,
+            out,
+            budget,
+            expect,
+            force,
+        } => recover_host(&input, &out, budget, expect.as_deref(), force),
+        Command::Info {
+            input,
+            format,
+            limits,
+        } => info::write(&input, limits.limits(), &mut io::stdout().lock(), format),
+        Command::Stats {
+            directory,
+            format,
+            jobs,
+            limits,
+            sarif,
+        } => corpus_stats(&directory, format, jobs, limits.limits(), sarif.as_deref()),
+        Command::FuzzCorpus {
+            directory,
+            iterations,
+            mutations,
+            seed,
+            budget,
+            timeout,
+            out,
+            limits,
+        } => {
+            let mut limits = limits.limits();
+            // a slow analysis should give up well before it counts as hanging
+            limits.max_time = limits.max_time.or(Some(timeout / 2));
+            let options = FuzzOptions {
+                iterations,
+                mutations,
+                seed,
+                limits,
+                budget: (budget > 0).then_some(budget),
+                timeout: Some(timeout),
+            };
+            fuzz_corpus(&directory, &options, out.as_deref())
+        }
+        #[cfg(feature = "lsp")]
+        Command::Lsp => lsp::serve(),
+    }
+}
+
+/// Writes `input` with the translations from the `.po` file `strings` applied to `out`
+fn translate_program(input: &Path, strings: &Path, out: &Path) -> io::Result<()> {
+    let program = std::fs::read(input).map_err(status::with_path(input))?;
+    let po = std::fs::read_to_string(strings).map_err(status::with_path(strings))?;
+    let (translated, report) = translate::translate(&program, &po)?;
+    for line in &report {
+        eprintln!("{line}");
+    }
+    std::fs::write(out, translated).map_err(status::with_path(out))?;
+    eprintln!("Translated {} strings into {}", report.len(), out.display());
+    Ok(())
+}
+
+/// Writes `input` with each of `inserts` made and then each of `hooks`
+/// installed to `out`
+///
+/// Every address is in the program as it was read, before anything moved.
+fn patch_program(
+    input: &Path,
+    inserts: &[(u16, Vec<u8>)],
+    hooks: &[(u16, Vec<u8>)],
+    out: &Path,
+) -> io::Result<()> {
+    let invalid = |err: HookError| io::Error::new(io::ErrorKind::InvalidInput, err.to_string());
+    let mut program = std::fs::read(input).map_err(status::with_path(input))?;
+    let mut insertions: Vec<Insertion> = Vec::new();
+    let moved = |insertions: &[Insertion], address: u16| {
+        insertions
+            .iter()
+            .fold(address, |address, insertion| insertion.new_address(address))
+    };
+    for (at, code) in inserts {
+        let at = moved(&insertions, *at);
+        let insertion = insert_code(&Disassembler::new(program), at, code).map_err(invalid)?;
+        eprintln!("{insertion}");
+        program = insertion.program.clone();
+        insertions.push(insertion);
+    }
+
+    let disassembler = Disassembler::new(program.clone());
+    let mut space = FreeSpace::analyze(&disassembler);
+    let mut output = program;
+    for (target, code) in hooks {
+        let target = moved(&insertions, *target);
+        let hook =
+            install_hook(&disassembler, &mut space, &mut output, target, code).map_err(invalid)?;
+        eprintln!("{hook}");
+    }
+    std::fs::write(out, output).map_err(status::with_path(out))?;
+    eprintln!(
+        "Made {} insertions and installed {} hooks into {}",
+        inserts.len(),
+        hooks.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+/// Runs the input file in the emulator, printing its output to stdout and a report to stderr
+fn emulate(
+    input: &Path,
+    budget: u64,
+    keys: &str,
+    call: Option<u16>,
+    setup: &Setup,
+) -> io::Result<()> {
+    let program = std::fs::read(input).map_err(status::with_path(input))?;
+    let disassembler = Disassembler::new(program.clone());
+    let mut recording = match &setup.replay {
+        Some(path) => load_recording(path)?,
+        None => {
+            let keys =
+                parse_keys(keys).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            let mut recording = Recording::new(&program, &keys, budget);
+            for (register, value) in &setup.set {
+                recording
+                    .registers
+                    .set(register, *value)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            }
+            recording.memory = setup.poke.clone();
+            recording.call = call;
+            if let Some(dir) = &setup.mount {
+                let mut files = mount(dir)?;
+                files.read_only = setup.read_only;
+                recording.files = Some(files);
+            }
+            recording
+        }
+    };
+    let mut emulator = recording
+        .emulator(&program)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+    let buffers = watch_buffers(&disassembler, &setup.watch, &mut emulator)?;
+    for probe in load_probes(&setup.probes)?.0 {
+        emulator.probe(probe);
+    }
+
+    if let Some(address) = recording.call {
+        let known = disassembler
+            .labels
+            .0
+            .iter()
+            .any(|label| label.address == address && label.label_type == LabelType::FUNCTION);
+        if !known {
+            eprintln!("Warn: no function was found at 0x{address:04x}, calling it anyway");
+        }
+    }
+    let result = if setup.replay.is_some() {
+        recording
+            .replay(&mut emulator)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?
+    } else {
+        recording.record(&mut emulator)
+    };
+    if let Some(path) = &setup.record {
+        let json = serde_json::to_vec_pretty(&recording).map_err(io::Error::other)?;
+        std::fs::write(path, json).map_err(status::with_path(path))?;
+    }
+
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(&result.output)?;
+    stdout.flush()?;
+
+    let ending = result
+        .stopped
+        .map_or("returned".to_string(), |reason| reason.to_string());
+    eprintln!();
+    eprintln!("--- {ending} after {} instructions", result.instructions);
+    if setup.replay.is_some() {
+        eprintln!(
+            "--- replayed {} service calls as recorded",
+            recording.services.len()
+        );
+    }
+    eprintln!("{}", emulator.registers);
+    for buffer in WatchReport::new(&disassembler, &buffers, &emulator.trace).0 {
+        eprintln!("Watch: {buffer}");
+    }
+    for hit in &emulator.trace.probe_hits {
+        eprintln!("Probe: {hit}");
+    }
+    if let (Some(before), Some(after)) = (&recording.files, &emulator.files) {
+        for change in after.changes(before) {
+            eprintln!("File: {change}");
+            if let (true, Some(dir)) = (setup.write_back, &setup.mount) {
+                save_change(dir, after, &change)?;
+            }
+        }
+    }
+    if let Some(path) = &setup.screenshot {
+        let screen = emulator.screen();
+        let png = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        if png {
+            gfx::write_png(&screen.render(), path)?;
+        } else {
+            std::fs::write(path, screen.text()).map_err(status::with_path(path))?;
+        }
+    }
+    for &(address, length) in &setup.dump {
+        let bytes = emulator.read_bytes(LOAD_SEGMENT, address, length);
+        for (row, chunk) in bytes.chunks(16).enumerate() {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{byte:02x}")).collect();
+            eprintln!(
+                "{:04x}: {}",
+                address.wrapping_add(row as u16 * 16),
+                hex.join(" ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Registers and memory to set before emulating, and memory to print after
+struct Setup {
+    /// Registers to set, by name
+    set: Vec<(String, u16)>,
+    /// Bytes to write to memory in the program's segment
+    poke: Vec<(u16, Vec<u8>)>,
+    /// Ranges of memory to print afterwards
+    dump: Vec<(u16, usize)>,
+    /// Labels or addresses of buffers to watch
+    watch: Vec<String>,
+    /// Where to save the run
+    record: Option<PathBuf>,
+    /// A saved run to replay instead of setting one up
+    replay: Option<PathBuf>,
+    /// Probe files to run with
+    probes: Vec<PathBuf>,
+    /// The directory whose files the program sees
+    mount: Option<PathBuf>,
+    /// Whether the program may only read the files
+    read_only: bool,
+    /// Whether to save changes to the files back to the directory
+    write_back: bool,
+    /// Where to save the screen afterwards
+    screenshot: Option<PathBuf>,
+}
+
+/// Runs `old` and `new` side by side and reports where they first differ,
+/// failing if they do
+fn run_lockstep(
+    old: &Path,
+    new: &Path,
+    budget: u64,
+    keys: &str,
+    mount_dir: Option<&Path>,
+    by_service: bool,
+) -> io::Result<()> {
+    let keys = parse_keys(keys).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let files = mount_dir.map(mount).transpose()?;
+    let start = |path: &Path| -> io::Result<Emulator> {
+        let mut emulator =
+            Emulator::new(&std::fs::read(path).map_err(status::with_path(path))?).with_keys(&keys);
+        emulator.files = files.clone();
+        Ok(emulator)
+    };
+    let (mut old_run, mut new_run) = (start(old)?, start(new)?);
+    let granularity = if by_service {
+        Granularity::Service
+    } else {
+        Granularity::Instruction
+    };
+    let report = lockstep(&mut old_run, &mut new_run, budget, granularity);
+    if !report.history.is_empty() {
+        let history: Vec<String> = report
+            .history
+            .iter()
+            .map(|address| format!("0x{address:04x}"))
+            .collect();
+        eprintln!("Last agreed: {}", history.join(" "));
+    }
+    if report.agreed() {
+        eprintln!("Agreed: {report}");
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            report.to_string(),
+        ))
+    }
+}
+
+/// Copies the files directly in `dir` with valid DOS names into a file
+/// system for the emulator
+fn mount(dir: &Path) -> io::Result<FileSystem> {
+    let mut files = FileSystem::default();
+    for entry in std::fs::read_dir(dir).map_err(status::with_path(dir))? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let host_name = entry.file_name();
+        match host_name.to_str().and_then(dos_name) {
+            Some(name) => {
+                let path = entry.path();
+                let contents = std::fs::read(&path).map_err(status::with_path(&path))?;
+                files.files.insert(name, contents);
+            }
+            None => eprintln!(
+                "Warn: {} is not a DOS file name, so the program cannot see it",
+                host_name.to_string_lossy()
+            ),
+        }
+    }
+    Ok(files)
+}
+
+/// Makes the same `change` to the mounted `dir` that the program made to
+/// `files`
+fn save_change(dir: &Path, files: &FileSystem, change: &FileChange) -> io::Result<()> {
+    // an existing file keeps its name even if it differs in case
+    let host_path = |name: &str| -> io::Result<PathBuf> {
+        for entry in std::fs::read_dir(dir).map_err(status::with_path(dir))? {
+            let entry = entry?;
+            if entry.file_name().to_str().and_then(dos_name).as_deref() == Some(name) {
+                return Ok(entry.path());
+            }
+        }
+        Ok(dir.join(name))
+    };
+    match change {
+        FileChange::Created(name) | FileChange::Changed(name) => {
+            let path = host_path(name)?;
+            std::fs::write(&path, &files.files[name]).map_err(status::with_path(&path))
+        }
+        FileChange::Deleted(name) => {
+            let path = host_path(name)?;
+            std::fs::remove_file(&path).map_err(status::with_path(&path))
+        }
+    }
+}
+
+/// Reads the probes in every file of `paths`
+fn load_probes(paths: &[PathBuf]) -> io::Result<ProbeSet> {
+    let mut probes = ProbeSet::default();
+    for path in paths {
+        let text = std::fs::read_to_string(path).map_err(status::with_path(path))?;
+        let parsed = ProbeSet::parse(&text).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {err}", path.display()),
+            )
+        })?;
+        probes.0.extend(parsed.0);
+    }
+    Ok(probes)
+}
+
+/// Reads a run saved by `emulate --record`
+fn load_recording(path: &Path) -> io::Result<Recording> {
+    serde_json::from_slice(&std::fs::read(path).map_err(status::with_path(path))?).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {err}", path.display()),
+        )
+    })
+}
+
+/// An emulator loaded with `program`, set up like the `recording` if there
+/// is one or given `keys` to press otherwise
+fn prepare_emulator(
+    program: &[u8],
+    recording: Option<&Recording>,
+    keys: &[Keystroke],
+) -> io::Result<Emulator> {
+    match recording {
+        Some(recording) => recording
+            .emulator(program)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string())),
+        None => Ok(Emulator::new(program).with_keys(keys)),
+    }
+}
+
+/// Runs `emulator` within `budget`, or exactly like the `recording` if
+/// there is one
+fn run_emulator(
+    emulator: &mut Emulator,
+    budget: u64,
+    recording: Option<&Recording>,
+    bar: &ProgressBar,
+) -> io::Result<StopReason> {
+    match recording {
+        Some(recording) => {
+            let result = recording
+                .replay(emulator)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+            // a called function that returned ends like a finished program
+            Ok(result.stopped.unwrap_or(StopReason::Exited(0)))
+        }
+        None => Ok(progress::emulate(emulator, budget, bar)),
+    }
+}
+
+/// Watches the buffer at each of `targets`, a label name or an address, in
+/// `emulator`, returning the ranges watched
+fn watch_buffers(
+    disassembler: &Disassembler,
+    targets: &[String],
+    emulator: &mut Emulator,
+) -> io::Result<Vec<(u16, u16)>> {
+    let mut buffers = Vec::new();
+    for target in targets {
+        let start = parse_address(target)
+            .ok()
+            .or_else(|| {
+                disassembler
+                    .labels
+                    .0
+                    .iter()
+                    .find(|label| label.name == *target)
+                    .map(|label| label.address)
+            })
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("no label or address `{target}` to watch"),
+                )
+            })?;
+        let (start, end) = extent(disassembler, start);
+        emulator.watch(start, end);
+        buffers.push((start, end));
+    }
+    Ok(buffers)
+}
+
+/// Recovers the original program from the infected `input` and writes it
+/// to `out`, explaining each step and checking the result first
+fn recover_host(
+    input: &Path,
+    out: &Path,
+    budget: u64,
+    expect: Option<&str>,
+    force: bool,
+) -> io::Result<()> {
+    let program = std::fs::read(input).map_err(status::with_path(input))?;
+    eprintln!(
+        "Infected: {} bytes, sha256 {}",
+        program.len(),
+        info::sha256(&program)
+    );
+    let recovery = recover(&program, budget)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+    eprintln!("Recovery: {recovery}");
+    eprintln!(
+        "Recovered: {} bytes, sha256 {}",
+        recovery.host.len(),
+        info::sha256(&recovery.host)
+    );
+    if recovery.verified {
+        eprintln!(
+            "Verified: the recovered program {} like the infected one after the hand-off",
+            recovery.host_stop
+        );
+    } else {
+        eprintln!(
+            "Warn: the recovered program {} but the infected one {} after the hand-off",
+            recovery.host_stop, recovery.infected_stop
+        );
+        if !force {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not writing a recovery that runs differently, use --force to write it anyway",
+            ));
+        }
+    }
+    if let Some(expected) = expect {
+        let actual = info::sha256(&recovery.host);
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the recovered program's sha256 is {actual}, not {expected}"),
+            ));
+        }
+        eprintln!("Verified: the sha256 is the expected one");
+    }
+    std::fs::write(out, &recovery.host).map_err(status::with_path(out))?;
+    eprintln!("Wrote the recovered program to {}", out.display());
+    Ok(())
+}
+
+/// Prints a coverage report for every input and a summary line for the whole set
+fn coverage(inputs: &[PathBuf], budget: u64, trace_assist: bool) -> io::Result<()> {
+    let mut conflicts = 0;
+    for input in inputs {
+        let program = std::fs::read(input).map_err(status::with_path(input))?;
+        let mut emulator = Emulator::new(&program);
+        let reason = emulator.run(budget);
+        let disassembler = if trace_assist {
+            Disassembler::with_trace(program, &emulator.trace)
+        } else {
+            Disassembler::new(program)
+        };
+
+        let report = CoverageReport::new(&disassembler, &emulator.trace);
+        if report.has_conflicts() {
+            conflicts += 1;
+        }
+        println!("{} ({reason}):", input.display());
+        print!("{report}");
+    }
+    println!(
+        "{conflicts} of {} files have code that static analysis got wrong",
+        inputs.len()
+    );
+    Ok(())
+}
+
+/// Prints aggregate statistics for every .COM file below `directory`
+fn corpus_stats(
+    directory: &Path,
+    format: stats::Format,
+    jobs: Option<usize>,
+    limits: Limits,
+    sarif: Option<&Path>,
+) -> io::Result<()> {
+    let files = stats::collect(directory)?;
+    let jobs = jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+    });
+    let stats = stats::run(&files, jobs, limits, sarif.is_some());
+    for (path, error) in stats.failed() {
+        eprintln!("Warn: could not analyse {}: {error}", path.display());
+    }
+    if let Some(path) = sarif {
+        sarif::export(&stats.sarif(), path)?;
+    }
+    stats.write(&mut io::stdout().lock(), format)
+}
+
+/// Fuzzes every .COM file in `directory`, saving the copies the analysis
+/// failed on to `out`
+fn fuzz_corpus(directory: &Path, options: &FuzzOptions, out: Option<&Path>) -> io::Result<()> {
+    let files = stats::collect(directory)?;
+    if let Some(out) = out {
+        std::fs::create_dir_all(out).map_err(status::with_path(out))?;
+    }
+    // panics are reported below, not as they happen
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let mut failures = 0;
+    let mut iterations = 0;
+    for path in &files {
+        let program = std::fs::read(path).map_err(status::with_path(path))?;
+        let report = fuzz(&program, options);
+        iterations += report.iterations;
+        failures += report.failures.len();
+        for failure in &report.failures {
+            eprintln!("Fail: {}: {failure}", path.display());
+            if let Some(out) = out {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                let saved = out.join(format!("{stem}.{}.com", failure.iteration));
+                std::fs::write(&saved, &failure.program).map_err(status::with_path(&saved))?;
+                eprintln!("Saved the failing copy to {}", saved.display());
+            }
+        }
+    }
+    std::panic::set_hook(hook);
+    eprintln!(
+        "Fuzzed {} files with {iterations} mutated copies, seed {}: {failures} failures",
+        files.len(),
+        options.seed
+    );
+    if failures > 0 {
+        return Err(io::Error::other(format!(
+            "the analysis failed on {failures} mutated copies"
+        )));
+    }
+    Ok(())
+}
+
+/// Parses an address in hex with a `0x` prefix, or in decimal
+fn parse_address(address: &str) -> Result<u16, String> {
+    match address.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => address.parse(),
+    }
+    .map_err(|err| format!("invalid address `{address}`: {err}"))
+}
+
+/// Parses a `FILE@ADDRESS` companion file mapping
+fn parse_aux(value: &str) -> Result<(PathBuf, u16), String> {
+    let (path, address) = value
+        .rsplit_once('@')
+        .ok_or("expected FILE@ADDRESS, e.g. data.bin@0x2000")?;
+    Ok((PathBuf::from(path), parse_address(address)?))
+}
+
+/// Parses an `ADDRESS=BYTES` hook, the bytes in hex with optional spaces
+fn parse_hook(value: &str) -> Result<(u16, Vec<u8>), String> {
+    let (address, hex) = value
+        .split_once('=')
+        .ok_or("expected ADDRESS=BYTES, e.g. 0x105=9c50589d")?;
+    let digits: Vec<char> = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("odd number of hex digits in `{hex}`"));
+    }
+    let code = digits
+        .chunks(2)
+        .map(|pair| {
+            let pair: String = pair.iter().collect();
+            u8::from_str_radix(&pair, 16).map_err(|err| format!("invalid byte `{pair}`: {err}"))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((parse_address(address)?, code))
+}
+
+/// Parses a `REGISTER=VALUE` register setting
+fn parse_register(value: &str) -> Result<(String, u16), String> {
+    let (register, number) = value
+        .split_once('=')
+        .ok_or("expected REGISTER=VALUE, e.g. si=0x200")?;
+    Ok((register.trim().to_string(), parse_address(number.trim())?))
+}
+
+/// Parses an `ADDRESS:LENGTH` memory range
+fn parse_dump(value: &str) -> Result<(u16, usize), String> {
+    let (address, length) = value
+        .split_once(':')
+        .ok_or("expected ADDRESS:LENGTH, e.g. 0x200:16")?;
+    let length = length
+        .parse()
+        .map_err(|err| format!("invalid length `{length}`: {err}"))?;
+    Ok((parse_address(address)?, length))
+}
+
+/// Parses a non-negative number of seconds, fractions allowed
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("invalid number of seconds `{value}`"))
+}
+
+/// Parses a `NAME@START-END` named region
+fn parse_region(value: &str) -> Result<NamedRegion, String> {
+    let (name, range) = value
+        .rsplit_once('@')
+        .ok_or("expected NAME@START-END, e.g. init@0x100-0x120")?;
+    let (start, end) = range
+        .split_once('-')
+        .ok_or("expected NAME@START-END, e.g. init@0x100-0x120")?;
+    let (start, end) = (parse_address(start)?, parse_address(end)?);
+    if name.is_empty() || start >= end {
+        return Err(format!("invalid region `{value}`"));
+    }
+    Ok(NamedRegion {
+        name: name.to_string(),
+        start,
+        end,
+    })
+}
+
+/// Writes `header`, the listing and the code found in mapped files, made the
+/// same on every run if `normalize` is set
+fn write_listing(
+    disassembler: &Disassembler,
+    opts: DisassemblerOptions,
+    header: Option<&str>,
+    normalize: bool,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    if !normalize {
+        out.write_all(header.unwrap_or_default().as_bytes())?;
+        disassembler.disassemble_stream(out, opts)?;
+        return write_aux_code(disassembler, out);
+    }
+    let mut listing = header.unwrap_or_default().as_bytes().to_vec();
+    disassembler.disassemble_stream(&mut listing, opts)?;
+    write_aux_code(disassembler, &mut listing)?;
+    out.write_all(golden::normalize(&String::from_utf8_lossy(&listing)).as_bytes())
+}
+
+/// Lists the strings found in every companion file and the code at every label pointing into one
+///
+/// Code is decoded up to the first `ret` or unconditional jump.
+fn write_aux_code(disassembler: &Disassembler, out: &mut impl Write) -> io::Result<()> {
+    for file in disassembler.aux_files() {
+        let mut labels: Vec<_> = disassembler
+            .labels
+            .0
+            .iter()
+            .filter(|label| file.contains(label.address))
+            .collect();
+        let strings: Vec<_> = disassembler
+            .string_constant_list
+            .0
+            .iter()
+            .filter(|string| file.contains(string.start))
+            .collect();
+        if labels.is_empty() && strings.is_empty() {
+            continue;
+        }
+        labels.sort_by_key(|label| label.address);
+        labels.dedup_by_key(|label| label.address);
+
+        writeln!(out)?;
+        writeln!(
+            out,
+            "; {} mapped at 0x{:04x}-0x{:04x}",
+            file.name,
+            file.address,
+            file.end()
+        )?;
+        for string in strings {
+            writeln!(
+                out,
+                "; 0x{:04x}: {}",
+                string.start,
+                string.as_db_statement()
+            )?;
+        }
+        for label in labels {
+            writeln!(out, "{label}")?;
+            let end = file.end().min(u16::MAX as u32) as u16;
+            for instruction in disassembler
+                .decode_range(label.address, end)
+                .into_iter()
+                .take(64)
+            {
+                writeln!(out, "    {instruction}")?;
+                if matches!(
+                    instruction.flow_control(),
+                    iced_x86::FlowControl::Return | iced_x86::FlowControl::UnconditionalBranch
+                ) {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Analyses `program` within `limits`, running it in the emulator first with
+/// `trace_assist` and pressing `keys`, or replaying `recording` if there is one
+fn analyze(
+    program: Vec<u8>,
+    trace_assist: bool,
+    budget: u64,
+    recording: Option<&Recording>,
+    keys: &[Keystroke],
+    limits: Limits,
+    bar: &ProgressBar,
+) -> io::Result<Disassembler> {
+    if !trace_assist {
+        return Ok(Disassembler::with_progress(
+            program,
+            limits,
+            progress::reporter(bar),
+        ));
+    }
+    let mut emulator = prepare_emulator(&program, recording, keys)?;
+    let reason = run_emulator(&mut emulator, budget, recording, bar)?;
+    bar.suspend(|| {
+        eprintln!(
+            "Trace: {reason} after {} instructions, {} distinct addresses executed",
+            emulator.instructions_executed,
+            emulator.trace.executed.len()
+        )
+    });
+    let mut disassembler = Disassembler::with_trace_and_limits(program, &emulator.trace, limits);
+    disassembler.set_progress(progress::reporter(bar));
+    Ok(disassembler)
+}
+
+/// Disassembles the input file to the output file or stdout, partly if the
+/// analysis was cut short
+fn disassemble(args: DisasmArgs) -> io::Result<Status> {
+    let style = args.style();
+    let opts = args.options();
+    let input = args
+        .input
+        .expect("clap requires --input without a subcommand");
+    if input.extension().is_none_or(|ext| ext != "com") {
         eprintln!(
             "Warn: Input file should have a .COM extension. this program will treat **ANY** file as a .COM file due to the nature of the DOS .COM file format not existing and being raw bytecode"
         );
     }
 
-    let mut file = File::open(&args.input)?;
+    let mut file = File::open(&input).map_err(status::with_path(&input))?;
     let mut buffer = Vec::new();
     file.read_to_end(&mut buffer)?;
+    let header = args
+        .header
+        .then(|| header::header(&input, &buffer, !args.no_date));
+    if args.fast {
+        let mut listing = header.unwrap_or_default().into_bytes();
+        write_linear(&mut listing, &buffer, &style, opts)?;
+        if args.normalize {
+            listing = golden::normalize(&String::from_utf8_lossy(&listing)).into_bytes();
+        }
+        match &args.output {
+            Some(path) => File::create(path)
+                .map_err(status::with_path(path))?
+                .write_all(&listing)?,
+            None => io::stdout().lock().write_all(&listing)?,
+        }
+        return Ok(Status::Success);
+    }
+
+    let limits = args.limits.limits();
+    let recording = args.replay.as_deref().map(load_recording).transpose()?;
+    let keys =
+        parse_keys(&args.keys).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let mut options = match (&recording, args.trace_assist) {
+        (Some(recording), true) => format!(
+            "trace-assist replay={}",
+            info::sha256(&serde_json::to_vec(recording).map_err(io::Error::other)?)
+        ),
+        (None, true) if keys.is_empty() => format!("trace-assist budget={}", args.trace_budget),
+        (None, true) => format!(
+            "trace-assist budget={} keys={:?}",
+            args.trace_budget, args.keys
+        ),
+        (_, false) => "linear".to_string(),
+    };
+    if limits != Limits::default() {
+        options.push_str(&format!(" {limits:?}"));
+    }
+    let key = cache::key(&buffer, &options);
+    let cached = if args.no_cache {
+        None
+    } else {
+        cache::load(&key)
+    };
+    // a bar on stderr would be mixed into a listing written there too
+    let shown = args.output.is_some();
+    let bar = progress::bar(shown);
+    let mut disassembler = match cached {
+        Some(disassembler) => disassembler,
+        None => {
+            let disassembler = analyze(
+                buffer,
+                args.trace_assist,
+                args.trace_budget,
+                recording.as_ref(),
+                &keys,
+                limits,
+                &bar,
+            )?;
+            // a run cut short by the clock depends on the machine, so it is not worth keeping
+            let timed_out = disassembler
+                .limits_hit()
+                .iter()
+                .any(|hit| hit.kind == LimitKind::Time);
+            if !args.no_cache
+                && !timed_out
+                && let Err(err) = cache::store(&key, &disassembler)
+            {
+                eprintln!("Warn: could not cache the analysis: {err}");
+            }
+            disassembler
+        }
+    };
+    bar.finish_and_clear();
+    for hit in disassembler.limits_hit() {
+        eprintln!("Warn: {hit}");
+    }
+
+    for (path, address) in &args.aux {
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        disassembler.map_file(AuxFile {
+            name,
+            address: *address,
+            data: std::fs::read(path).map_err(status::with_path(path))?,
+        });
+    }
+
+    for region in args.region {
+        disassembler.regions.insert(region);
+    }
+
+    let mut watched = WatchReport::default();
+    let mut probe_hits = Vec::new();
+    if !args.watch.is_empty() || !args.probes.is_empty() {
+        let mut emulator = prepare_emulator(&disassembler.data, recording.as_ref(), &keys)?;
+        let buffers = watch_buffers(&disassembler, &args.watch, &mut emulator)?;
+        for probe in load_probes(&args.probes)?.0 {
+            emulator.probe(probe);
+        }
+        let bar = progress::bar(shown);
+        run_emulator(&mut emulator, args.trace_budget, recording.as_ref(), &bar)?;
+        bar.finish_and_clear();
+        watched = WatchReport::new(&disassembler, &buffers, &emulator.trace);
+        disassembler.add_dynamic_references(&watched);
+        probe_hits = std::mem::take(&mut emulator.trace.probe_hits);
+        for hit in &probe_hits {
+            eprintln!("Probe: {hit}");
+        }
+        annotate(&mut disassembler, &probe_hits);
+    }
+
+    for path in &args.annotations {
+        let annotations: Annotations =
+            serde_json::from_slice(&std::fs::read(path).map_err(status::with_path(path))?)
+                .map_err(|err| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}: {err}", path.display()),
+                    )
+                })?;
+        disassembler
+            .import_annotations(&annotations)
+            .map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {err}", path.display()),
+                )
+            })?;
+    }
+
+    if style != *disassembler.style() {
+        disassembler.set_style(style);
+    }
+
+    if !args.constants.is_empty() {
+        let mut table = ConstantTable(Vec::new());
+        for path in &args.constants {
+            let text = std::fs::read_to_string(path).map_err(status::with_path(path))?;
+            let parsed = ConstantTable::parse(&text).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {err}", path.display()),
+                )
+            })?;
+            table.extend(parsed);
+        }
+        disassembler.add_constants(table);
+    }
+
+    let mut matches = Vec::new();
+    if !args.rules.is_empty() {
+        let mut rules = RuleSet::default();
+        for path in &args.rules {
+            let text = std::fs::read_to_string(path).map_err(status::with_path(path))?;
+            let parsed = RuleSet::parse(&text).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {err}", path.display()),
+                )
+            })?;
+            rules.0.extend(parsed.0);
+        }
+        matches = disassembler.apply_rules(&rules);
+        eprintln!("Rules: {} matches", matches.len());
+    }
 
-    let mut disassembler = Disassembler::new(buffer);
+    let mut viruses = Vec::new();
+    if !args.signatures.is_empty() {
+        let mut pack = SignaturePack::default();
+        for path in &args.signatures {
+            let text = std::fs::read_to_string(path).map_err(status::with_path(path))?;
+            let parsed = SignaturePack::parse(&text).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {err}", path.display()),
+                )
+            })?;
+            pack.0.extend(parsed.0);
+        }
+        viruses = disassembler.apply_signatures(&pack);
+        for detection in &viruses {
+            eprintln!("Virus: {detection}");
+            let text = detection.comment.clone().unwrap_or(detection.to_string());
+            disassembler.comment_list.0.push(
+                Comment::new(CommentType::PRE, text, detection.start)
+                    .with_source(CommentSource::analysis("signatures")),
+            );
+        }
+        if let Some(path) = &args.extract_host {
+            let host = viruses
+                .iter()
+                .find_map(|detection| detection.host.as_ref())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "no virus found whose host can be extracted",
+                    )
+                })?;
+            std::fs::write(path, host).map_err(status::with_path(path))?;
+            eprintln!("Extracted the original program to {}", path.display());
+        }
+    }
+
+    let bar = progress::bar(shown);
+    disassembler.set_progress(progress::reporter(&bar));
+    let settled = settle(&mut disassembler);
+    bar.finish_and_clear();
+    for discovery in &settled.discoveries {
+        match discovery {
+            Discovery::Table(table) => eprintln!("Table: 0x{:04x}: {table}", table.start),
+            discovery => eprintln!("Found: {discovery}"),
+        }
+    }
+    let tables: Vec<&Table> = settled
+        .discoveries
+        .iter()
+        .filter_map(|discovery| match discovery {
+            Discovery::Table(table) => Some(table),
+            _ => None,
+        })
+        .collect();
+    let partial = settled.limited || !disassembler.limits_hit().is_empty();
+    if settled.limited {
+        eprintln!(
+            "Warn: the analysis was still finding things when it stopped (runs: {})",
+            settled.passes
+        );
+    }
+
+    let interpreters = Interpreter::detect(&disassembler);
+
+    for interpreter in &interpreters {
+        eprintln!("Interpreter: {interpreter}");
+        interpreter.annotate(&mut disassembler);
+    }
+
+    let mut normalized = args.deobfuscate.as_ref().map(|_| normalize(&disassembler));
+    if let Some(normalized) = &normalized {
+        eprintln!(
+            "Deobfuscate: {} transformations",
+            normalized.transformations.len()
+        );
+    }
+
+    let loader = Loader::detect(&disassembler);
+    if let Some(loader) = &loader {
+        eprintln!("Loader: {loader}");
+        disassembler.comment_list.0.push(
+            Comment::new(CommentType::PRE, loader.to_string(), loader.jump)
+                .with_source(CommentSource::analysis("loader")),
+        );
+    }
+
+    let compiler = Identification::detect(&disassembler);
+    eprintln!("Compiler: {compiler}");
 
-    disassembler.comment_list.0.push(Comment::new(
+    let degenerate = find_degenerate(&disassembler);
+    if let Some(degenerate) = degenerate {
+        eprintln!("Warn: {degenerate}");
+    }
+
+    let wraparounds = find_wraparounds(&disassembler);
+    for finding in &wraparounds {
+        eprintln!("Wraparound: 0x{:04x}: {finding}", finding.address);
+    }
+
+    let protection = find_protection(&disassembler);
+    for finding in &protection {
+        eprintln!("Protection: {finding}");
+        disassembler.comment_list.0.push(
+            Comment::new(CommentType::PRE, finding.to_string(), finding.address)
+                .with_source(CommentSource::analysis("protection")),
+        );
+    }
+
+    for buffer in &watched.0 {
+        eprintln!("Watch: {buffer}");
+        disassembler.comment_list.0.push(
+            Comment::new(CommentType::PRE, buffer.to_string(), buffer.start)
+                .with_source(CommentSource::analysis("watch")),
+        );
+    }
+
+    let code_writes = find_code_writes(&disassembler);
+    for write in &code_writes {
+        eprintln!("Self-modifying: 0x{:04x}: {write}", write.address);
+        disassembler.comment_list.0.push(
+            Comment::new(CommentType::PRE, write.to_string(), write.address)
+                .with_source(CommentSource::analysis("selfmod")),
+        );
+    }
+
+    let music = find_music(&disassembler);
+    for found in &music {
+        eprintln!("Music: {found}");
+        disassembler.comment_list.0.push(
+            Comment::new(CommentType::PRE, found.to_string(), found.routine)
+                .with_source(CommentSource::analysis("music")),
+        );
+    }
+
+    let redundancies = find_redundancies(&disassembler);
+    if args.redundant {
+        eprintln!("Redundant: {} instructions", redundancies.len());
+        for redundancy in &redundancies {
+            disassembler.comment_list.0.push(
+                Comment::new(
+                    CommentType::INLINE,
+                    redundancy.to_string(),
+                    redundancy.address,
+                )
+                .with_source(CommentSource::analysis("redundancy")),
+            );
+        }
+    }
+
+    let size_overrides = find_size_overrides(&disassembler);
+    for wide in &size_overrides {
+        eprintln!("Warn: 0x{:04x}: {wide}", wide.address);
+    }
+    if args.cpu < Cpu::I386
+        && let Some(first) = size_overrides.first()
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} instructions need a 386, the first at 0x{:04x}, but --cpu is older",
+                size_overrides.len(),
+                first.address
+            ),
+        ));
+    }
+
+    let syntax = check_syntax(&disassembler);
+    if args.check_syntax {
+        for issue in &syntax {
+            eprintln!(
+                "Syntax: 0x{:04x}: {} (MASM: {}): {issue}",
+                issue.address, issue.nasm, issue.masm
+            );
+            disassembler.comment_list.0.push(
+                Comment::new(CommentType::INLINE, issue.to_string(), issue.address)
+                    .with_source(CommentSource::analysis("syntax")),
+            );
+        }
+    }
+
+    let counters = find_counters(&disassembler);
+    if args.counters {
+        for counter in &counters {
+            eprintln!("Counter: {counter}");
+            for access in &counter.accesses {
+                disassembler.comment_list.0.push(
+                    Comment::new(
+                        CommentType::INLINE,
+                        format!("counter 0x{:04x}", counter.address),
+                        access.address,
+                    )
+                    .with_source(CommentSource::analysis("trainer")),
+                );
+            }
+        }
+    }
+
+    let memory = MemoryMap::analyze(&disassembler);
+    if args.memory {
+        for call in &memory.calls {
+            eprintln!("Memory: 0x{:04x}: {call}", call.address);
+        }
+        match (memory.exec, memory.shrinks_before_exec()) {
+            (Some(exec), Some(true)) => eprintln!(
+                "Memory: 0x{exec:04x}: runs another program after shrinking its own block"
+            ),
+            (Some(exec), _) => eprintln!(
+                "Memory: 0x{exec:04x}: runs another program without shrinking its own block first"
+            ),
+            (None, _) => {}
+        }
+    }
+
+    if let Some(path) = &args.findings {
+        let findings = serde_json::json!({
+            "rule_matches": matches,
+            "loader": loader,
+            "compiler": compiler,
+            "redundant": redundancies,
+            "interpreters": interpreters,
+            "tables": tables,
+            "protection": protection,
+            "code_writes": code_writes,
+            "counters": counters,
+            "memory": memory,
+            "syntax": syntax,
+            "size_overrides": size_overrides,
+            "music": music,
+            "strings": disassembler
+                .string_constant_list
+                .0
+                .iter()
+                .map(|string| serde_json::json!({
+                    "start": string.start,
+                    "end": string.end,
+                    "value": string.value,
+                    "language": string.language(),
+                }))
+                .collect::<Vec<_>>(),
+            "deobfuscation": normalized.as_ref().map(|normalized| &normalized.transformations),
+            "free_space": FreeSpace::analyze(&disassembler).0,
+            "limits": disassembler.limits_hit(),
+            "handlers": find_handlers(&disassembler),
+            "wraparounds": wraparounds,
+            "degenerate": degenerate,
+            "watched": watched.0,
+            "probe_hits": probe_hits,
+            "risk": RiskReport::assess(&disassembler),
+            "viruses": viruses,
+        });
+        let json = serde_json::to_vec_pretty(&findings).map_err(io::Error::other)?;
+        std::fs::write(path, json).map_err(status::with_path(path))?;
+    }
+
+    if let Some(path) = &args.export_annotations {
+        let json =
+            serde_json::to_vec_pretty(&disassembler.annotations()).map_err(io::Error::other)?;
+        std::fs::write(path, json).map_err(status::with_path(path))?;
+    }
+
+    if let Some(path) = &args.export_strings {
+        strings::export(&disassembler, path)?;
+    }
+
+    if let Some(path) = &args.export_music {
+        music::export(&music, path)?;
+    }
+
+    if let Some(directory) = &args.extract_gfx {
+        for graphic in gfx::extract(&disassembler, directory)? {
+            eprintln!("Graphics: {graphic} -> {}", graphic.file_name());
+        }
+    }
+
+    let credit = Comment::new(
         CommentType::PRE,
         "Disassembled by DosDisassm".to_string(),
         0x100,
-    ));
-
-    let opts = DisassemblerOptions {
-        write_labels: args.labels,
-        write_indent: args.indent,
-        offset_comments: args.offsets,
-        syscall_comments: args.syscalls,
-        write_bytes: args.bytes,
-        misc_comments: args.comments,
+    )
+    .with_source(CommentSource::analysis("dosdisassm"));
+    disassembler.comment_list.0.push(credit.clone());
+
+    let mut out: Box<dyn Write> = match args.output {
+        Some(path) => Box::new(File::create(&path).map_err(status::with_path(&path))?),
+        None => Box::new(io::stdout().lock()),
     };
+    write_listing(
+        &disassembler,
+        opts,
+        header.as_deref(),
+        args.normalize,
+        &mut out,
+    )?;
 
-    match args.output {
-        Some(path) => {
-            let mut out_file = File::create(path)?;
-            disassembler.disassemble_stream(&mut out_file, opts)?;
+    if let (Some(path), Some(normalized)) = (&args.deobfuscate, &mut normalized) {
+        let cleaned = &mut normalized.disassembler;
+        cleaned.comment_list.0.push(credit);
+        let mut file = File::create(path).map_err(status::with_path(path))?;
+        write_listing(cleaned, opts, header.as_deref(), args.normalize, &mut file)?;
+    }
+
+    if let (Some(format), Some(path)) = (args.export, &args.export_to) {
+        match format {
+            ExportFormat::Vscode => vscode::export(&disassembler, opts, path)?,
+            ExportFormat::DosboxSyms => dosbox::export(&disassembler, path)?,
+            ExportFormat::Nasm => nasm::export(&disassembler, opts, path)?,
+            ExportFormat::Sarif => {
+                let uri = input.display().to_string();
+                let results = sarif::results(&disassembler, &uri, &matches);
+                sarif::export(&sarif::log(&[uri], results), path)?
+            }
         }
-        None => {
-            let stdout = io::stdout();
-            let mut handle = stdout.lock();
-            disassembler.disassemble_stream(&mut handle, opts)?;
+    }
+
+    if let Some(path) = &args.follow {
+        match &loader {
+            Some(loader) => {
+                let mut loaded =
+                    Disassembler::new(std::fs::read(path).map_err(status::with_path(path))?);
+                loaded.comment_list.0.push(
+                    Comment::new(
+                        CommentType::PRE,
+                        format!(
+                            "{} loaded by {}, addresses assume it is loaded at 0x100",
+                            path.display(),
+                            input.display()
+                        ),
+                        0x100,
+                    )
+                    .with_source(CommentSource::analysis("loader")),
+                );
+                writeln!(out)?;
+                writeln!(out, "; {loader}")?;
+                loaded.disassemble_stream(&mut out, opts)?;
+            }
+            None => eprintln!(
+                "Warn: {} is not a loader, not following {}",
+                input.display(),
+                path.display()
+            ),
         }
     }
 
-    Ok(())
+    out.flush()?;
+    Ok(if partial {
+        Status::Partial
+    } else {
+        Status::Success
+    })
 }
@@ -0,0 +1,71 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use disassembler::music::{Device, Music, MusicEvent};
+
+use crate::status::with_path;
+
+/// The clock of the YM3812 on an AdLib card, in Hz
+const YM3812_CLOCK: u32 = 3_579_545;
+
+/// The size of a VGM 1.51 header
+const VGM_HEADER: usize = 0x80;
+
+/// The number of 44.1 kHz samples a VGM `wait 1/60 s` command lasts
+const SAMPLES_PER_FRAME: u32 = 735;
+
+/// Writes the music tables in `music` to `path`
+///
+/// A `.json` file lists every routine and its events. A `.vgm` file holds
+/// the AdLib register writes for a VGM player, one table after another with
+/// a frame's pause after each write, since the tempo is not known; PC
+/// speaker tables have no VGM form and are left out.
+pub fn export(music: &[Music], path: &Path) -> io::Result<()> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    if !matches!(extension.as_deref(), Some("json" | "vgm")) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: music is written as .json or .vgm", path.display()),
+        ));
+    }
+    let mut out = BufWriter::new(File::create(path).map_err(with_path(path))?);
+    if extension.as_deref() == Some("vgm") {
+        out.write_all(&vgm(music))?;
+    } else {
+        serde_json::to_writer_pretty(&mut out, music).map_err(io::Error::other)?;
+        writeln!(out)?;
+    }
+    out.flush()
+}
+
+/// Builds a VGM file playing the AdLib register writes in `music`
+fn vgm(music: &[Music]) -> Vec<u8> {
+    let mut commands = Vec::new();
+    let mut samples = 0;
+    for event in music
+        .iter()
+        .filter(|music| music.device == Device::AdLib)
+        .flat_map(|music| &music.events)
+    {
+        if let MusicEvent::Write { register, value } = *event {
+            // YM3812 write, then wait 1/60 s
+            commands.extend([0x5A, register, value, 0x62]);
+            samples += SAMPLES_PER_FRAME;
+        }
+    }
+    commands.push(0x66);
+
+    let mut file = vec![0; VGM_HEADER];
+    let mut put = |at: usize, value: u32| file[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    put(0x04, (VGM_HEADER + commands.len() - 4) as u32);
+    put(0x08, 0x151);
+    put(0x18, samples);
+    put(0x34, (VGM_HEADER - 0x34) as u32);
+    put(0x50, YM3812_CLOCK);
+    file[..4].copy_from_slice(b"Vgm ");
+    file.extend(commands);
+    file
+}
@@ -0,0 +1,53 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use disassembler::consts::COM_OFFSET;
+use disassembler::disassemble::{Disassembler, DisassemblerOptions};
+use disassembler::equates::{equates, write_equates};
+
+use crate::status::with_path;
+
+/// The include file the generated names are written to
+const EQUATES: &str = "equates.inc";
+
+/// Writes the listing as a NASM project in `directory`
+///
+/// `equates.inc` defines the names the listing uses without defining them:
+/// memory past the end of the file, other addresses the listing has no line
+/// for and the constants the code uses. `main.asm` is the listing written
+/// with `opts`, including `equates.inc` and referring to those names, so the
+/// code can be edited and the names generated again without touching it.
+pub fn export(
+    disassembler: &Disassembler,
+    opts: DisassemblerOptions,
+    directory: &Path,
+) -> io::Result<()> {
+    std::fs::create_dir_all(directory).map_err(with_path(directory))?;
+    let style = disassembler.style();
+
+    let path = directory.join(EQUATES);
+    let mut include = BufWriter::new(File::create(&path).map_err(with_path(&path))?);
+    write_equates(&mut include, &equates(disassembler), style)?;
+    include.flush()?;
+
+    let path = directory.join("main.asm");
+    let mut main = BufWriter::new(File::create(&path).map_err(with_path(&path))?);
+    writeln!(main, "{} 16", style.mnemonic("bits"))?;
+    writeln!(
+        main,
+        "{} {}",
+        style.mnemonic("org"),
+        style.address(COM_OFFSET)
+    )?;
+    writeln!(main, "%include \"{EQUATES}\"")?;
+    writeln!(main)?;
+    disassembler.disassemble_stream(
+        &mut main,
+        DisassemblerOptions {
+            equates: true,
+            ..opts
+        },
+    )?;
+    main.flush()
+}
@@ -0,0 +1,64 @@
+use disassembler::emulate::{Emulator, StopReason};
+use disassembler::progress::{Progress, Reporter};
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// How many updates an emulator run is shown in
+const EMULATION_STEPS: u64 = 100;
+
+/// A progress bar on stderr, or one that draws nothing unless `shown`
+///
+/// indicatif hides it too when stderr is not a terminal. It is only redrawn
+/// as the work goes on, as a steady tick would need a thread, which WASI
+/// does not have.
+pub fn bar(shown: bool) -> ProgressBar {
+    if !shown {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(0);
+    bar.set_style(
+        ProgressStyle::with_template("{msg:>10} [{bar:40}] {pos}/{len} {elapsed}")
+            .expect("the template is valid")
+            .progress_chars("=> "),
+    );
+    bar
+}
+
+/// Shows how far an analysis has got on `bar`, until it is finished
+pub fn reporter(bar: &ProgressBar) -> Reporter {
+    let bar = bar.clone();
+    Reporter::new(move |progress| {
+        if bar.is_finished() {
+            return;
+        }
+        match progress {
+            Progress::Decoded { bytes, total } => {
+                bar.set_message("decoding");
+                bar.set_length(total as u64);
+                bar.set_position(bytes as u64);
+            }
+            Progress::Pass { done, total } => {
+                bar.set_message("analysing");
+                bar.set_length(total as u64);
+                bar.set_position(done as u64);
+            }
+            Progress::Rerun { run } => bar.set_message(format!("run {}", run + 1)),
+        }
+    })
+}
+
+/// Runs `emulator` until the program stops or `budget` instructions have
+/// been executed, showing how many have on `bar`
+pub fn emulate(emulator: &mut Emulator, budget: u64, bar: &ProgressBar) -> StopReason {
+    bar.set_message("emulating");
+    bar.set_length(budget);
+    let step = (budget / EMULATION_STEPS).max(1);
+    let mut ran = 0;
+    loop {
+        let reason = emulator.run(step.min(budget - ran));
+        ran = (ran + step).min(budget);
+        bar.set_position(ran);
+        if reason != StopReason::BudgetExhausted || ran == budget {
+            return reason;
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::io;
+use std::path::Path;
+
+use disassembler::consts::{Address, COM_OFFSET};
+use disassembler::deobfuscate::{Transformation, normalize};
+use disassembler::disassemble::Disassembler;
+use disassembler::handlers::{HandlerProblem, find_handlers};
+use disassembler::loader::Loader;
+use disassembler::protection::{ProtectionKind, find_protection};
+use disassembler::rules::RuleMatch;
+use disassembler::selfmod::find_code_writes;
+use disassembler::wraparound::find_wraparounds;
+use serde_json::{Value, json};
+
+use crate::status::with_path;
+
+/// Every rule a result can be reported under: its id, SARIF level and what
+/// it means
+const RULES: &[(&str, &str, &str)] = &[
+    (
+        "copy-protection",
+        "warning",
+        "Behaviour typical of copy protection",
+    ),
+    (
+        "loader",
+        "warning",
+        "Loads another file into memory and jumps into it, as packers and droppers do",
+    ),
+    (
+        "self-modifying-code",
+        "warning",
+        "Writes over its own instructions, as packed and encrypted programs do",
+    ),
+    (
+        "obfuscation",
+        "note",
+        "An obfuscation idiom hiding the real instructions",
+    ),
+    (
+        "interrupt-handler",
+        "warning",
+        "An interrupt handler that can corrupt the program it interrupts",
+    ),
+    (
+        "wraparound",
+        "warning",
+        "An address that wraps around the end of the 64K segment",
+    ),
+    ("rule-match", "note", "A byte pattern rule matched"),
+    (
+        "analysis-limit",
+        "note",
+        "A resource limit cut the analysis short",
+    ),
+];
+
+/// A SARIF result for `rule` about the `length` bytes at `address` of the
+/// program at `uri`
+fn result(
+    rule: &str,
+    uri: &str,
+    address: Option<Address>,
+    length: usize,
+    message: String,
+) -> Value {
+    let index = RULES
+        .iter()
+        .position(|&(id, _, _)| id == rule)
+        .expect("every rule reported is listed");
+    let mut location = json!({ "artifactLocation": { "uri": uri } });
+    if let Some(offset) = address.and_then(|address| address.checked_sub(COM_OFFSET)) {
+        location["region"] = json!({
+            "byteOffset": offset,
+            "byteLength": length.max(1),
+        });
+    }
+    json!({
+        "ruleId": rule,
+        "ruleIndex": index,
+        "level": RULES[index].1,
+        "message": { "text": message },
+        "locations": [{ "physicalLocation": location }],
+    })
+}
+
+/// The length of the instruction at `address`, or 1 if none starts there
+fn length_at(disassembler: &Disassembler, address: Address) -> usize {
+    disassembler
+        .instructions
+        .0
+        .binary_search_by_key(&(address as u64), |instruction| instruction.ip())
+        .map_or(1, |index| disassembler.instructions.0[index].len())
+}
+
+/// Collects what the analysis of the program at `uri` found as SARIF
+/// results, located by byte ranges of the file
+pub fn results(disassembler: &Disassembler, uri: &str, matches: &[RuleMatch]) -> Vec<Value> {
+    let mut results = Vec::new();
+    let length = |address| length_at(disassembler, address);
+
+    for finding in find_protection(disassembler) {
+        let bytes = match &finding.kind {
+            ProtectionKind::KeyDiskString { text } => text.len(),
+            _ => length(finding.address),
+        };
+        results.push(result(
+            "copy-protection",
+            uri,
+            Some(finding.address),
+            bytes,
+            finding.to_string(),
+        ));
+    }
+    if let Some(loader) = Loader::detect(disassembler) {
+        results.push(result(
+            "loader",
+            uri,
+            Some(loader.jump),
+            length(loader.jump),
+            loader.to_string(),
+        ));
+    }
+    for write in find_code_writes(disassembler) {
+        results.push(result(
+            "self-modifying-code",
+            uri,
+            Some(write.address),
+            length(write.address),
+            write.to_string(),
+        ));
+    }
+    for transformation in normalize(disassembler).transformations {
+        let (address, bytes) = match transformation {
+            Transformation::OverlappingJump { branch, .. } => (branch, length(branch)),
+            Transformation::OpaquePredicate { address, .. } => (address, length(address)),
+            Transformation::JunkBytes { start, end } => (start, (end - start) as usize),
+        };
+        results.push(result(
+            "obfuscation",
+            uri,
+            Some(address),
+            bytes,
+            transformation.to_string(),
+        ));
+    }
+    for handler in find_handlers(disassembler) {
+        for problem in &handler.problems {
+            let address = match *problem {
+                HandlerProblem::Clobbers(_) => handler.address,
+                HandlerProblem::NearReturn(address)
+                | HandlerProblem::UncheckedDosCall(address)
+                | HandlerProblem::Unbalanced { address, .. } => address,
+            };
+            results.push(result(
+                "interrupt-handler",
+                uri,
+                Some(address),
+                length(address),
+                format!("{handler}: {problem}"),
+            ));
+        }
+    }
+    for finding in find_wraparounds(disassembler) {
+        results.push(result(
+            "wraparound",
+            uri,
+            Some(finding.address),
+            length(finding.address),
+            finding.to_string(),
+        ));
+    }
+    for found in matches {
+        results.push(result(
+            "rule-match",
+            uri,
+            Some(found.start),
+            (found.end - found.start) as usize,
+            found.to_string(),
+        ));
+    }
+    for hit in disassembler.limits_hit() {
+        results.push(result(
+            "analysis-limit",
+            uri,
+            hit.address,
+            1,
+            hit.to_string(),
+        ));
+    }
+    results
+}
+
+/// A SARIF log of `results` about the programs at `uris`
+pub fn log(uris: &[String], results: Vec<Value>) -> Value {
+    let rules: Vec<Value> = RULES
+        .iter()
+        .map(|&(id, level, description)| {
+            json!({
+                "id": id,
+                "shortDescription": { "text": description },
+                "defaultConfiguration": { "level": level },
+            })
+        })
+        .collect();
+    let artifacts: Vec<Value> = uris
+        .iter()
+        .map(|uri| json!({ "location": { "uri": uri } }))
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "dosdisassm",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "artifacts": artifacts,
+            "results": results,
+        }],
+    })
+}
+
+/// Writes `log` to `path`
+pub fn export(log: &Value, path: &Path) -> io::Result<()> {
+    let json = serde_json::to_vec_pretty(log).map_err(io::Error::other)?;
+    std::fs::write(path, json).map_err(with_path(path))
+}
@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use disassembler::disassemble::{Disassembler, is_data};
+use disassembler::label::LabelType;
+use disassembler::limits::Limits;
+
+use crate::sarif;
+use crate::status::with_path;
+
+/// How the aggregate statistics are written
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `metric,value` rows
+    Csv,
+    /// A single JSON object
+    Json,
+}
+
+/// What the analysis of a single file found
+struct FileStats {
+    bytes: usize,
+    instructions: usize,
+    functions: usize,
+    labels: usize,
+    strings: usize,
+    /// Names of the int 21h services called, once per call site
+    syscalls: Vec<String>,
+    /// Whether a limit cut the analysis short
+    limited: bool,
+    /// What was found, as SARIF results, if asked for
+    results: Vec<serde_json::Value>,
+}
+
+/// Statistics aggregated over a corpus of programs
+#[derive(Default)]
+pub struct CorpusStats {
+    files: usize,
+    bytes: usize,
+    instructions: usize,
+    functions: usize,
+    labels: usize,
+    strings: usize,
+    /// Call sites of every int 21h service
+    syscall_calls: BTreeMap<String, usize>,
+    /// Number of files calling every int 21h service
+    syscall_files: BTreeMap<String, usize>,
+    /// Files whose analysis a limit cut short
+    limited: usize,
+    /// Files that could not be read, and why
+    failed: Vec<(PathBuf, String)>,
+    /// The SARIF results of every file, by file
+    results: BTreeMap<PathBuf, Vec<serde_json::Value>>,
+}
+
+impl CorpusStats {
+    fn add(&mut self, path: &Path, file: FileStats) {
+        self.files += 1;
+        self.bytes += file.bytes;
+        self.instructions += file.instructions;
+        self.functions += file.functions;
+        self.labels += file.labels;
+        self.strings += file.strings;
+        self.limited += usize::from(file.limited);
+        for syscall in &file.syscalls {
+            *self.syscall_calls.entry(syscall.clone()).or_default() += 1;
+        }
+        let mut distinct = file.syscalls;
+        distinct.sort();
+        distinct.dedup();
+        for syscall in distinct {
+            *self.syscall_files.entry(syscall).or_default() += 1;
+        }
+        self.results.insert(path.to_path_buf(), file.results);
+    }
+
+    fn average(&self, total: usize) -> f64 {
+        if self.files == 0 {
+            0.0
+        } else {
+            total as f64 / self.files as f64
+        }
+    }
+
+    /// Services ordered from most to least called
+    fn syscalls_by_frequency(&self) -> Vec<(&str, usize, usize)> {
+        let mut syscalls: Vec<_> = self
+            .syscall_calls
+            .iter()
+            .map(|(name, &calls)| {
+                let files = self.syscall_files.get(name).copied().unwrap_or(0);
+                (name.as_str(), calls, files)
+            })
+            .collect();
+        syscalls.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        syscalls
+    }
+
+    /// Writes the statistics to `out` in `format`
+    pub fn write(&self, out: &mut impl Write, format: Format) -> io::Result<()> {
+        match format {
+            Format::Csv => {
+                writeln!(out, "metric,value")?;
+                writeln!(out, "files,{}", self.files)?;
+                writeln!(out, "failed,{}", self.failed.len())?;
+                writeln!(out, "limited,{}", self.limited)?;
+                writeln!(out, "total_bytes,{}", self.bytes)?;
+                writeln!(out, "average_bytes,{:.2}", self.average(self.bytes))?;
+                writeln!(
+                    out,
+                    "average_instructions,{:.2}",
+                    self.average(self.instructions)
+                )?;
+                writeln!(out, "average_functions,{:.2}", self.average(self.functions))?;
+                writeln!(out, "average_labels,{:.2}", self.average(self.labels))?;
+                writeln!(out, "average_strings,{:.2}", self.average(self.strings))?;
+                for (name, calls, files) in self.syscalls_by_frequency() {
+                    writeln!(out, "syscall_calls:{name},{calls}")?;
+                    writeln!(out, "syscall_files:{name},{files}")?;
+                }
+            }
+            Format::Json => {
+                let syscalls: serde_json::Map<String, serde_json::Value> = self
+                    .syscalls_by_frequency()
+                    .into_iter()
+                    .map(|(name, calls, files)| {
+                        (
+                            name.to_string(),
+                            serde_json::json!({ "calls": calls, "files": files }),
+                        )
+                    })
+                    .collect();
+                let failed: Vec<_> = self
+                    .failed
+                    .iter()
+                    .map(|(path, error)| {
+                        serde_json::json!({ "path": path.display().to_string(), "error": error })
+                    })
+                    .collect();
+                let value = serde_json::json!({
+                    "files": self.files,
+                    "failed": failed,
+                    "limited": self.limited,
+                    "total_bytes": self.bytes,
+                    "average_bytes": self.average(self.bytes),
+                    "average_instructions": self.average(self.instructions),
+                    "average_functions": self.average(self.functions),
+                    "average_labels": self.average(self.labels),
+                    "average_strings": self.average(self.strings),
+                    "syscalls": syscalls,
+                });
+                serde_json::to_writer_pretty(&mut *out, &value).map_err(io::Error::other)?;
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Files that could not be read, and why
+    pub fn failed(&self) -> &[(PathBuf, String)] {
+        &self.failed
+    }
+
+    /// A SARIF log of what was found in every file analysed
+    pub fn sarif(&self) -> serde_json::Value {
+        let uris: Vec<String> = self
+            .results
+            .keys()
+            .map(|path| path.display().to_string())
+            .collect();
+        sarif::log(&uris, self.results.values().flatten().cloned().collect())
+    }
+}
+
+/// Finds every `.com` file below `directory`, in a stable order
+pub fn collect(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![directory.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        for entry in fs::read_dir(&directory).map_err(with_path(&directory))? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                pending.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("com"))
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Analyses a single program within `limits`, collecting SARIF results if
+/// `findings` is set
+fn analyze(path: &Path, limits: Limits, findings: bool) -> io::Result<FileStats> {
+    let program = fs::read(path).map_err(with_path(path))?;
+    let bytes = program.len();
+    let disassembler = Disassembler::with_limits(program, limits);
+    let labels = &disassembler.labels.0;
+    Ok(FileStats {
+        bytes,
+        instructions: disassembler
+            .instructions
+            .0
+            .iter()
+            .filter(|instruction| !is_data(instruction))
+            .count(),
+        functions: labels
+            .iter()
+            .filter(|label| label.label_type == LabelType::FUNCTION)
+            .count(),
+        labels: labels.len(),
+        strings: disassembler.string_constant_list.0.len(),
+        syscalls: disassembler
+            .syscall_list
+            .0
+            .iter()
+            .map(|syscall| format!("{:?}", syscall.number))
+            .collect(),
+        limited: !disassembler.limits_hit().is_empty(),
+        results: if findings {
+            sarif::results(&disassembler, &path.display().to_string(), &[])
+        } else {
+            Vec::new()
+        },
+    })
+}
+
+/// Analyses every file in `files` on `jobs` threads, each within `limits`,
+/// collecting SARIF results if `findings` is set
+///
+/// WASI has no threads, so there the files are analysed one at a time.
+pub fn run(files: &[PathBuf], jobs: usize, limits: Limits, findings: bool) -> CorpusStats {
+    let jobs = if cfg!(target_os = "wasi") { 1 } else { jobs };
+    let next = AtomicUsize::new(0);
+    let stats = Mutex::new(CorpusStats::default());
+    let work = || {
+        while let Some(path) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+            let result = analyze(path, limits, findings);
+            let mut stats = stats.lock().unwrap();
+            match result {
+                Ok(file) => stats.add(path, file),
+                Err(err) => stats.failed.push((path.clone(), err.to_string())),
+            }
+        }
+    };
+    if jobs <= 1 {
+        work();
+    } else {
+        std::thread::scope(|scope| {
+            for _ in 0..jobs {
+                scope.spawn(work);
+            }
+        });
+    }
+    let mut stats = stats.into_inner().unwrap();
+    stats.failed.sort();
+    stats
+}
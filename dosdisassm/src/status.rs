@@ -0,0 +1,94 @@
+use std::io;
+use std::path::Path;
+use std::process::ExitCode;
+
+use serde_json::json;
+
+/// How errors are written to stderr
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    /// `Error: message` lines for reading
+    #[default]
+    Text,
+    /// One JSON object per error for scripts
+    Json,
+}
+
+/// How a run ended, which is also its exit status
+///
+/// The values are a contract with scripts wrapping dosdisassm and do not
+/// change between versions. Argument errors are invalid input, which is
+/// also the status clap exits with for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Everything was done
+    Success = 0,
+    /// The output was written, but the analysis stopped at a limit or was
+    /// still finding things, so it may be incomplete
+    Partial = 1,
+    /// The arguments or the program were not what was expected
+    InvalidInput = 2,
+    /// A file could not be read or written
+    Io = 3,
+    /// dosdisassm itself failed
+    Internal = 4,
+}
+
+impl Status {
+    /// The status for a run that failed with `error`
+    ///
+    /// Errors about the arguments or the program are made with
+    /// [`io::ErrorKind::InvalidInput`] or [`io::ErrorKind::InvalidData`].
+    /// [`io::Error::other`] wraps failures of dosdisassm itself, such as a
+    /// result that cannot be serialised.
+    pub fn of(error: &io::Error) -> Status {
+        match error.kind() {
+            io::ErrorKind::InvalidInput
+            | io::ErrorKind::InvalidData
+            | io::ErrorKind::Unsupported => Status::InvalidInput,
+            io::ErrorKind::Other => Status::Internal,
+            _ => Status::Io,
+        }
+    }
+
+    /// The name of the status in JSON errors
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Success => "success",
+            Status::Partial => "partial",
+            Status::InvalidInput => "invalid_input",
+            Status::Io => "io",
+            Status::Internal => "internal",
+        }
+    }
+}
+
+impl From<Status> for ExitCode {
+    fn from(status: Status) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+/// Prefixes an error on the file at `path` with the path, keeping its kind
+///
+/// The operating system's messages, such as `No such file or directory`,
+/// do not say which file they are about.
+pub fn with_path(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> io::Error {
+    let path = path.as_ref().display().to_string();
+    move |err| io::Error::new(err.kind(), format!("{path}: {err}"))
+}
+
+/// Writes the error a run with `status` failed with to stderr in `format`
+pub fn report(status: Status, message: &str, format: ErrorFormat) {
+    match format {
+        ErrorFormat::Text => eprintln!("Error: {message}"),
+        ErrorFormat::Json => eprintln!(
+            "{}",
+            json!({
+                "status": status.name(),
+                "code": status as u8,
+                "message": message,
+            })
+        ),
+    }
+}
@@ -0,0 +1,181 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use disassembler::consts::Address;
+use disassembler::disassemble::Disassembler;
+use disassembler::string::{decode_cp437, encode_cp437};
+
+use crate::status::with_path;
+
+/// How a string table is written, chosen from the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Csv,
+    Json,
+    Po,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> io::Result<Format> {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("csv") => Ok(Format::Csv),
+            Some("json") => Ok(Format::Json),
+            Some("po") => Ok(Format::Po),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{}: string tables are written as .csv, .json or .po",
+                    path.display()
+                ),
+            )),
+        }
+    }
+}
+
+/// A string and what it takes to translate it
+struct Entry {
+    address: Address,
+    bytes: Vec<u8>,
+    references: Vec<Address>,
+}
+
+impl Entry {
+    /// The bytes as hex pairs separated by spaces
+    fn hex(&self) -> String {
+        let pairs: Vec<String> = self
+            .bytes
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        pairs.join(" ")
+    }
+
+    fn references(&self) -> Vec<String> {
+        self.references
+            .iter()
+            .map(|address| format!("0x{address:04x}"))
+            .collect()
+    }
+}
+
+/// Decodes `bytes` from code page 437, with C-style escapes for quotes,
+/// backslashes and control bytes
+pub fn escape(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for &byte in bytes {
+        match byte {
+            b'\\' => text.push_str("\\\\"),
+            b'"' => text.push_str("\\\""),
+            b'\n' => text.push_str("\\n"),
+            b'\r' => text.push_str("\\r"),
+            b'\t' => text.push_str("\\t"),
+            0x00..0x20 | 0x7F => text.push_str(&format!("\\x{byte:02x}")),
+            _ => text.push(decode_cp437(byte)),
+        }
+    }
+    text
+}
+
+/// Encodes `text` written by [`escape`] back into code page 437 bytes
+pub fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            bytes.push(encode_cp437(c).ok_or_else(|| format!("{c:?} is not in code page 437"))?);
+            continue;
+        }
+        bytes.push(match chars.next() {
+            Some('\\') => b'\\',
+            Some('"') => b'"',
+            Some('n') => b'\n',
+            Some('r') => b'\r',
+            Some('t') => b'\t',
+            Some('x') => {
+                let digits: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&digits, 16)
+                    .map_err(|_| format!("invalid escape \\x{digits}"))?
+            }
+            other => return Err(format!("invalid escape \\{}", other.unwrap_or(' '))),
+        });
+    }
+    Ok(bytes)
+}
+
+/// Writes every string constant to `path` in the format its extension names
+///
+/// Each entry has the string's address, length, raw bytes in hex, the text
+/// as written by [`escape`] and the addresses of the instructions referring
+/// to it. In a `.po` file the address is the message context, so translations
+/// can be matched back to where they go.
+pub fn export(disassembler: &Disassembler, path: &Path) -> io::Result<()> {
+    let format = Format::from_path(path)?;
+    let entries: Vec<Entry> = disassembler
+        .string_constant_list
+        .0
+        .iter()
+        .map(|string| Entry {
+            address: string.start,
+            bytes: string
+                .value
+                .chars()
+                .map(|c| u8::try_from(c).unwrap_or(b'?'))
+                .collect(),
+            references: disassembler.references(string.start),
+        })
+        .collect();
+
+    let mut out = BufWriter::new(File::create(path).map_err(with_path(path))?);
+    match format {
+        Format::Csv => {
+            writeln!(out, "address,length,bytes,text,references")?;
+            for entry in &entries {
+                writeln!(
+                    out,
+                    "0x{:04x},{},{},\"{}\",{}",
+                    entry.address,
+                    entry.bytes.len(),
+                    entry.hex(),
+                    escape(&entry.bytes).replace('"', "\"\""),
+                    entry.references().join(" ")
+                )?;
+            }
+        }
+        Format::Json => {
+            let value: Vec<_> = entries
+                .iter()
+                .map(|entry| {
+                    serde_json::json!({
+                        "address": entry.address,
+                        "length": entry.bytes.len(),
+                        "bytes": entry.hex(),
+                        "text": escape(&entry.bytes),
+                        "references": entry.references,
+                    })
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut out, &value).map_err(io::Error::other)?;
+            writeln!(out)?;
+        }
+        Format::Po => {
+            writeln!(out, "msgid \"\"")?;
+            writeln!(out, "msgstr \"\"")?;
+            writeln!(out, "\"Content-Type: text/plain; charset=UTF-8\\n\"")?;
+            for entry in &entries {
+                writeln!(out)?;
+                writeln!(out, "#. {} bytes: {}", entry.bytes.len(), entry.hex())?;
+                if !entry.references.is_empty() {
+                    writeln!(out, "#: {}", entry.references().join(" "))?;
+                }
+                writeln!(out, "msgctxt \"0x{:04x}\"", entry.address)?;
+                writeln!(out, "msgid \"{}\"", escape(&entry.bytes))?;
+                writeln!(out, "msgstr \"\"")?;
+            }
+        }
+    }
+    out.flush()
+}
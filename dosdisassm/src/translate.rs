@@ -0,0 +1,197 @@
+use std::io;
+
+use disassembler::consts::{Address, COM_OFFSET, SIZE};
+use disassembler::disassemble::Disassembler;
+use disassembler::freespace::FreeSpace;
+use iced_x86::{Decoder, DecoderOptions, OpKind};
+
+use crate::strings::unescape;
+
+/// The largest a COM program can be
+const MAX_COM_SIZE: usize = 0xFF00;
+
+/// A message from a `.po` file, with its fields still escaped
+#[derive(Default)]
+struct Message {
+    context: Option<String>,
+    id: String,
+    translation: String,
+}
+
+/// Which field of a message continuation lines belong to
+#[derive(Clone, Copy)]
+enum Field {
+    Context,
+    Id,
+    Translation,
+}
+
+/// Reads the messages of a `.po` file
+///
+/// Only the fields [`crate::strings::export`] writes are understood; plural
+/// forms are not.
+fn parse_po(text: &str) -> Result<Vec<Message>, String> {
+    let mut messages = Vec::new();
+    let mut message = Message::default();
+    let mut field = None;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let quoted = |value: &str| {
+            value
+                .trim()
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .map(str::to_string)
+                .ok_or_else(|| format!("line {}: expected a quoted string", number + 1))
+        };
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(value) = line.strip_prefix("msgctxt ") {
+            if field.is_some() {
+                messages.push(std::mem::take(&mut message));
+            }
+            message.context = Some(quoted(value)?);
+            field = Some(Field::Context);
+        } else if let Some(value) = line.strip_prefix("msgid ") {
+            if matches!(field, Some(Field::Id | Field::Translation)) {
+                messages.push(std::mem::take(&mut message));
+            }
+            message.id = quoted(value)?;
+            field = Some(Field::Id);
+        } else if let Some(value) = line.strip_prefix("msgstr ") {
+            message.translation = quoted(value)?;
+            field = Some(Field::Translation);
+        } else if line.starts_with('"') {
+            let value = quoted(line)?;
+            match field {
+                Some(Field::Context) => message.context.get_or_insert_default().push_str(&value),
+                Some(Field::Id) => message.id.push_str(&value),
+                Some(Field::Translation) => message.translation.push_str(&value),
+                None => return Err(format!("line {}: string outside a message", number + 1)),
+            }
+        } else {
+            return Err(format!("line {}: unexpected {line:?}", number + 1));
+        }
+    }
+    messages.push(message);
+    Ok(messages)
+}
+
+/// Points the reference at `reference` to `to` instead of `from`
+fn fix_reference(
+    program: &[u8],
+    output: &mut [u8],
+    reference: Address,
+    from: Address,
+    to: Address,
+) -> Result<(), String> {
+    let offset = (reference - COM_OFFSET) as usize;
+    let mut decoder = Decoder::with_ip(
+        SIZE,
+        &program[offset..],
+        reference as u64,
+        DecoderOptions::NONE,
+    );
+    let instruction = decoder.decode();
+    let constants = decoder.get_constant_offsets(&instruction);
+    let operands: Vec<OpKind> = (0..instruction.op_count())
+        .map(|operand| instruction.op_kind(operand))
+        .collect();
+    let at = if operands.contains(&OpKind::Immediate16)
+        && instruction.immediate16() == from
+        && constants.immediate_size() == 2
+    {
+        constants.immediate_offset()
+    } else if operands.contains(&OpKind::Memory)
+        && instruction.memory_displacement64() == from as u64
+        && constants.displacement_size() == 2
+    {
+        constants.displacement_offset()
+    } else {
+        return Err(format!(
+            "cannot update the reference to 0x{from:04x} at 0x{reference:04x}"
+        ));
+    };
+    output[offset + at..offset + at + 2].copy_from_slice(&to.to_le_bytes());
+    Ok(())
+}
+
+/// Applies the translations in the `.po` file `po` to `program`
+///
+/// Every translated message must have the string's address as its context
+/// and the original text as its id, as written by `--export-strings`. A
+/// `$`-terminated string stays terminated. Translations that fit replace the
+/// original in place; longer ones are moved to space the program does not
+/// use, found by [`FreeSpace`], and every instruction loading the old address
+/// is changed to load the new one.
+/// Returns the patched program and a line describing each change.
+pub fn translate(program: &[u8], po: &str) -> io::Result<(Vec<u8>, Vec<String>)> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let disassembler = Disassembler::new(program.to_vec());
+    let mut space = FreeSpace::analyze(&disassembler);
+    let mut output = program.to_vec();
+    let mut report = Vec::new();
+
+    for message in parse_po(po).map_err(invalid)? {
+        let Some(context) = &message.context else {
+            continue;
+        };
+        if message.translation.is_empty() {
+            continue;
+        }
+        let address = context
+            .strip_prefix("0x")
+            .and_then(|hex| Address::from_str_radix(hex, 16).ok())
+            .filter(|&address| address >= COM_OFFSET)
+            .ok_or_else(|| invalid(format!("{context:?} is not a string address")))?;
+        let original = unescape(&message.id).map_err(invalid)?;
+        let mut translated = unescape(&message.translation).map_err(invalid)?;
+
+        let offset = (address - COM_OFFSET) as usize;
+        if program.get(offset..offset + original.len()) != Some(&original[..]) {
+            return Err(invalid(format!(
+                "the string at 0x{address:04x} is not {:?}",
+                message.id
+            )));
+        }
+        if original.last() == Some(&b'$') && translated.last() != Some(&b'$') {
+            translated.push(b'$');
+        }
+
+        if translated.len() <= original.len() {
+            output[offset..offset + translated.len()].copy_from_slice(&translated);
+            report.push(format!("0x{address:04x}: translated in place"));
+            continue;
+        }
+
+        let references = disassembler.references(address);
+        if references.is_empty() {
+            return Err(invalid(format!(
+                "the translation of 0x{address:04x} does not fit and nothing refers to the string, so it cannot be moved"
+            )));
+        }
+        let moved = space
+            .allocate_patch_space(translated.len())
+            .filter(|&moved| (moved - COM_OFFSET) as usize + translated.len() <= MAX_COM_SIZE)
+            .ok_or_else(|| {
+                invalid(format!(
+                    "no room to move the string at 0x{address:04x}, the program would exceed {MAX_COM_SIZE} bytes"
+                ))
+            })?;
+        for &reference in &references {
+            fix_reference(program, &mut output, reference, address, moved).map_err(invalid)?;
+        }
+        let at = (moved - COM_OFFSET) as usize;
+        if output.len() < at + translated.len() {
+            // slack lies past the end of the file
+            output.resize(at + translated.len(), 0);
+        }
+        output[at..at + translated.len()].copy_from_slice(&translated);
+        report.push(format!(
+            "0x{address:04x}: moved to 0x{moved:04x}, {} references updated",
+            references.len()
+        ));
+    }
+    Ok((output, report))
+}
@@ -0,0 +1,174 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use disassembler::consts::Address;
+use disassembler::disassemble::{Disassembler, DisassemblerOptions};
+use disassembler::label::LabelType;
+use disassembler::program::{ItemKind, Program};
+
+use crate::status::with_path;
+
+/// Writes the listing, its source map and a symbol index into `directory`
+/// for an editor extension
+///
+/// `listing.asm` is the listing written with `opts`. `sourcemap.json` gives
+/// the address of each of its lines, or null, and `symbols.json` lists the
+/// labels, strings and functions with the lines they span. Lines are counted
+/// from 0, as editors do.
+pub fn export(
+    disassembler: &Disassembler,
+    opts: DisassemblerOptions,
+    directory: &Path,
+) -> io::Result<()> {
+    std::fs::create_dir_all(directory).map_err(with_path(directory))?;
+    let mut listing = Vec::new();
+    disassembler.disassemble_stream(&mut listing, opts)?;
+    let path = directory.join("listing.asm");
+    std::fs::write(&path, &listing).map_err(with_path(&path))?;
+
+    // offset comments only add to the end of lines, so the lines match up
+    let mut offsets = Vec::new();
+    disassembler.disassemble_stream(
+        &mut offsets,
+        DisassemblerOptions {
+            offset_comments: true,
+            ..opts
+        },
+    )?;
+    let offsets = String::from_utf8_lossy(&offsets);
+    let lines: Vec<Option<Address>> = offsets.lines().map(|line| split_offset(line).1).collect();
+    let listing = String::from_utf8_lossy(&listing);
+    let text: Vec<&str> = listing.lines().collect();
+
+    write_json(
+        &directory.join("sourcemap.json"),
+        &serde_json::json!({
+            "file": "listing.asm",
+            "lines": lines,
+        }),
+    )?;
+
+    let first_line = |start: Address, end: u32| {
+        lines
+            .iter()
+            .position(|&address| address.is_some_and(|a| a >= start && (a as u32) < end))
+    };
+    let program = Program::build(disassembler);
+    let labels: Vec<_> = program
+        .symbols
+        .iter()
+        .map(|label| {
+            let definition = format!("{}:", label.name);
+            let line = text
+                .iter()
+                .position(|line| line.trim_start().starts_with(&definition))
+                .or_else(|| first_line(label.address, label.address as u32 + 1));
+            serde_json::json!({
+                "name": label.name,
+                "address": label.address,
+                "type": label.label_type,
+                "line": line,
+            })
+        })
+        .collect();
+    let strings: Vec<_> = program
+        .items
+        .iter()
+        .filter_map(|item| match &item.kind {
+            ItemKind::String(string) => Some(string),
+            _ => None,
+        })
+        .map(|string| {
+            serde_json::json!({
+                "start": string.start,
+                "end": string.end,
+                "value": string.value,
+                "line": first_line(string.start, string.end as u32),
+            })
+        })
+        .collect();
+    write_json(
+        &directory.join("symbols.json"),
+        &serde_json::json!({
+            "labels": labels,
+            "strings": strings,
+            "functions": functions(&program, &lines, &text),
+        }),
+    )
+}
+
+/// The line range of each function, from its label to its last instruction
+/// before the next function
+fn functions(
+    program: &Program,
+    lines: &[Option<Address>],
+    text: &[&str],
+) -> Vec<serde_json::Value> {
+    let mut starts: Vec<_> = program
+        .symbols
+        .iter()
+        .filter(|label| label.label_type == LabelType::FUNCTION)
+        .collect();
+    starts.sort_by_key(|label| label.address);
+    starts
+        .iter()
+        .enumerate()
+        .filter_map(|(index, label)| {
+            let end = starts
+                .get(index + 1)
+                .map_or(u32::MAX, |next| next.address as u32);
+            let inside = |address: Address| label.address <= address && (address as u32) < end;
+            let definition = format!("{}:", label.name);
+            let start_line = text
+                .iter()
+                .position(|line| line.trim_start().starts_with(&definition))?;
+            let end_line = lines
+                .iter()
+                .enumerate()
+                .skip(start_line)
+                .take_while(|&(_, address)| address.is_none_or(inside))
+                .filter(|&(_, address)| {
+                    address.is_some_and(|address| {
+                        program
+                            .item_at(address)
+                            .is_some_and(|item| matches!(item.kind, ItemKind::Instruction(_)))
+                    })
+                })
+                .map(|(line, _)| line)
+                .last()?;
+            Some(serde_json::json!({
+                "name": label.name,
+                "start": label.address,
+                "start_line": start_line,
+                "end_line": end_line,
+            }))
+        })
+        .collect()
+}
+
+/// Splits the first `; 0x0100` offset comment off `line`, returning the
+/// trimmed text before it and the address
+pub fn split_offset(line: &str) -> (&str, Option<Address>) {
+    for (at, _) in line.match_indices("; 0x") {
+        let digits = &line[at + 4..];
+        let end = digits
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(digits.len());
+        let rest = digits[end..].trim_start();
+        if end >= 4
+            && (rest.is_empty() || rest.starts_with(';'))
+            && let Ok(address) = Address::from_str_radix(&digits[..end], 16)
+        {
+            return (line[..at].trim(), Some(address));
+        }
+    }
+    (line.trim(), None)
+}
+
+fn write_json(path: &Path, value: &serde_json::Value) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path).map_err(with_path(path))?);
+    serde_json::to_writer_pretty(&mut out, value).map_err(io::Error::other)?;
+    writeln!(out)?;
+    out.flush()
+}
@@ -0,0 +1,161 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use disassembler::consts::Address;
+use disassembler::flow::{BasicBlock, ControlFlowGraph, EdgeKind};
+use eframe::egui::{self, Align2, Color32, FontId, Pos2, Rect, Sense, Stroke, StrokeKind, Vec2};
+
+/// The width of a block in the graph
+const BLOCK_WIDTH: f32 = 240.0;
+
+/// The space between rows and columns of blocks
+const GAP: f32 = 36.0;
+
+/// How many instructions of a block are shown before it is cut short
+const MAX_LINES: usize = 10;
+
+/// Places the blocks reachable from `root` without following calls in rows
+/// by their distance from it
+fn layout(graph: &ControlFlowGraph, root: Address) -> Vec<(&BasicBlock, usize, usize)> {
+    let mut depth: BTreeMap<Address, usize> = BTreeMap::from([(root, 0)]);
+    let mut queue = VecDeque::from([root]);
+    while let Some(start) = queue.pop_front() {
+        let Some(block) = graph.0.iter().find(|block| block.start == start) else {
+            continue;
+        };
+        for edge in &block.successors {
+            if edge.kind != EdgeKind::Call && !depth.contains_key(&edge.target) {
+                depth.insert(edge.target, depth[&start] + 1);
+                queue.push_back(edge.target);
+            }
+        }
+    }
+
+    let mut columns: BTreeMap<usize, usize> = BTreeMap::new();
+    graph
+        .0
+        .iter()
+        .filter_map(|block| {
+            let row = *depth.get(&block.start)?;
+            let column = columns.entry(row).or_default();
+            *column += 1;
+            Some((block, row, *column - 1))
+        })
+        .collect()
+}
+
+/// Draws the part of `graph` reachable from the block containing `selected`
+///
+/// `code` gives the text of the instruction at an address. Returns the
+/// address of a block that was clicked.
+pub fn show(
+    ui: &mut egui::Ui,
+    graph: &ControlFlowGraph,
+    selected: Address,
+    code: impl Fn(Address) -> String,
+) -> Option<Address> {
+    let Some(root) = graph.block_containing(selected) else {
+        ui.label("The selected address is not code.");
+        return None;
+    };
+    let placed = layout(graph, root.start);
+    let font = FontId::monospace(12.0);
+    let line_height = ui.fonts_mut(|fonts| fonts.row_height(&font));
+    let height = |block: &BasicBlock| {
+        (block.instructions.len().min(MAX_LINES) + 1) as f32 * line_height + 8.0
+    };
+
+    let mut row_heights: BTreeMap<usize, f32> = BTreeMap::new();
+    for (block, row, _) in &placed {
+        let tallest = row_heights.entry(*row).or_default();
+        *tallest = tallest.max(height(block));
+    }
+    let mut row_tops = Vec::new();
+    let mut top = 0.0;
+    for tallest in row_heights.values() {
+        row_tops.push(top);
+        top += tallest + GAP;
+    }
+    let columns = placed
+        .iter()
+        .map(|(_, _, column)| column + 1)
+        .max()
+        .unwrap_or(1);
+    let size = Vec2::new(columns as f32 * (BLOCK_WIDTH + GAP), top);
+
+    let mut clicked = None;
+    egui::ScrollArea::both().id_salt("graph").show(ui, |ui| {
+        let (response, painter) = ui.allocate_painter(size, Sense::click());
+        let origin = response.rect.min;
+        let rects: BTreeMap<Address, Rect> = placed
+            .iter()
+            .map(|(block, row, column)| {
+                let min = origin + Vec2::new(*column as f32 * (BLOCK_WIDTH + GAP), row_tops[*row]);
+                (
+                    block.start,
+                    Rect::from_min_size(min, Vec2::new(BLOCK_WIDTH, height(block))),
+                )
+            })
+            .collect();
+
+        for (block, _, _) in &placed {
+            let from = rects[&block.start];
+            for edge in &block.successors {
+                let Some(to) = rects.get(&edge.target) else {
+                    continue;
+                };
+                let color = match edge.kind {
+                    EdgeKind::Fallthrough => Color32::GRAY,
+                    EdgeKind::Jump => Color32::LIGHT_BLUE,
+                    EdgeKind::Branch => Color32::LIGHT_GREEN,
+                    EdgeKind::Call => continue,
+                };
+                let start = from.center_bottom();
+                let end: Pos2 = to.center_top();
+                painter.arrow(start, end - start, Stroke::new(1.5, color));
+            }
+        }
+
+        let visuals = ui.visuals();
+        for (block, _, _) in &placed {
+            let rect = rects[&block.start];
+            let fill = if block.start == root.start {
+                visuals.selection.bg_fill
+            } else {
+                visuals.extreme_bg_color
+            };
+            painter.rect_filled(rect, 4.0, fill);
+            painter.rect_stroke(
+                rect,
+                4.0,
+                visuals.widgets.noninteractive.bg_stroke,
+                StrokeKind::Inside,
+            );
+            let mut lines: Vec<String> = block
+                .instructions
+                .iter()
+                .take(MAX_LINES)
+                .map(|&address| format!("{address:04x}  {}", code(address)))
+                .collect();
+            if block.instructions.len() > MAX_LINES {
+                lines.push("...".to_string());
+            }
+            painter.text(
+                rect.min + Vec2::new(6.0, 4.0),
+                Align2::LEFT_TOP,
+                lines.join("\n"),
+                font.clone(),
+                visuals.text_color(),
+            );
+        }
+
+        if response.clicked()
+            && let Some(pointer) = response.interact_pointer_pos()
+        {
+            clicked = rects
+                .iter()
+                .find(|(_, rect)| rect.contains(pointer))
+                .map(|(&start, _)| start);
+        }
+    });
+    clicked
+}
@@ -0,0 +1,449 @@
+//! A window for browsing the disassembly of a COM program
+//!
+//! The listing, hex dump and control flow graph follow one selected
+//! address, so clicking in any of them shows the same place in the others.
+
+mod graph;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use disassembler::consts::{Address, AddressExt, COM_OFFSET};
+use disassembler::disassemble::{Disassembler, DisassemblerOptions};
+use disassembler::flow::ControlFlowGraph;
+use eframe::egui::{self, Color32, RichText};
+
+/// How many bytes each row of the hex view shows
+const HEX_ROW: usize = 16;
+
+/// A line of the listing and the address it is at
+struct Line {
+    text: String,
+    address: Option<Address>,
+}
+
+/// The address in the `; 0x0100` offset comment at the end of `line`
+fn offset(line: &str) -> Option<Address> {
+    let (_, digits) = line.rsplit_once("; 0x")?;
+    Address::from_str_radix(digits.trim_end(), 16).ok()
+}
+
+/// An open program
+struct Program {
+    path: PathBuf,
+    disassembler: Disassembler,
+    lines: Vec<Line>,
+    graph: ControlFlowGraph,
+    /// The instruction text at each address, for the graph
+    code: BTreeMap<Address, String>,
+}
+
+impl Program {
+    fn open(path: &Path) -> std::io::Result<Program> {
+        let disassembler = Disassembler::new(std::fs::read(path)?);
+        let graph = ControlFlowGraph::build(&disassembler);
+        let mut program = Program {
+            path: path.to_path_buf(),
+            disassembler,
+            lines: Vec::new(),
+            graph,
+            code: BTreeMap::new(),
+        };
+        program.render();
+        Ok(program)
+    }
+
+    /// Writes the listing again, after labels change
+    fn render(&mut self) {
+        let opts = DisassemblerOptions {
+            offset_comments: true,
+            syscall_comments: true,
+            ..DisassemblerOptions::default()
+        };
+        let mut listing = Vec::new();
+        if let Err(err) = self.disassembler.disassemble_stream(&mut listing, opts) {
+            listing = format!("; {err}").into_bytes();
+        }
+        self.lines = String::from_utf8_lossy(&listing)
+            .lines()
+            .map(|text| Line {
+                text: text.to_string(),
+                address: offset(text),
+            })
+            .collect();
+        self.code = self
+            .lines
+            .iter()
+            .filter_map(|line| {
+                let code = line.text.split(';').next()?.trim();
+                Some((line.address?, code.to_string()))
+            })
+            .filter(|(_, code)| !code.is_empty() && !code.ends_with(':'))
+            .collect();
+    }
+
+    /// The first line of the listing at or after `address`
+    fn line_of(&self, address: Address) -> Option<usize> {
+        self.lines
+            .iter()
+            .position(|line| line.address.is_some_and(|at| at >= address))
+    }
+}
+
+/// Which view fills the middle of the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    Listing,
+    Graph,
+}
+
+/// Which list the side panel shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum List {
+    Strings,
+    Labels,
+}
+
+/// A label being renamed
+struct Rename {
+    address: Address,
+    name: String,
+    error: Option<String>,
+}
+
+/// The browser window
+struct BrowserApp {
+    path: String,
+    program: Option<Program>,
+    error: Option<String>,
+    selected: Address,
+    /// Set when the selection changes, so the views scroll to it next frame
+    scroll: bool,
+    /// Whether the views scroll to the selection this frame
+    scrolling: bool,
+    view: View,
+    list: List,
+    rename: Option<Rename>,
+}
+
+impl BrowserApp {
+    fn open(&mut self, path: &Path) {
+        match Program::open(path) {
+            Ok(program) => {
+                self.path = path.display().to_string();
+                self.program = Some(program);
+                self.error = None;
+                self.select(COM_OFFSET);
+            }
+            Err(err) => self.error = Some(format!("{}: {err}", path.display())),
+        }
+    }
+
+    fn select(&mut self, address: Address) {
+        self.selected = address;
+        self.scroll = true;
+    }
+
+    /// Scrolls every view to the selection this frame if it changed last
+    /// frame, so views drawn before a click still follow it
+    fn start_frame(&mut self, ctx: &egui::Context) {
+        self.scrolling = std::mem::take(&mut self.scroll);
+        if self.scrolling {
+            ctx.request_repaint();
+        }
+    }
+
+    fn file_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("File");
+            let field = ui.text_edit_singleline(&mut self.path);
+            let entered =
+                field.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+            if ui.button("Open").clicked() || entered {
+                let path = PathBuf::from(self.path.trim());
+                self.open(&path);
+            }
+            if let Some(error) = &self.error {
+                ui.colored_label(Color32::LIGHT_RED, error);
+            }
+        });
+    }
+
+    fn side_list(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.list, List::Strings, "Strings");
+            ui.selectable_value(&mut self.list, List::Labels, "Labels");
+        });
+        let Some(program) = &self.program else {
+            return;
+        };
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .id_salt("list")
+            .show(ui, |ui| match self.list {
+                List::Strings => {
+                    for string in &program.disassembler.string_constant_list.0 {
+                        let text = format!("{:04x}  {:?}", string.start, string.value);
+                        let selected = string.start <= self.selected && self.selected < string.end;
+                        if ui
+                            .selectable_label(selected, RichText::new(text).monospace())
+                            .clicked()
+                        {
+                            clicked = Some(string.start);
+                        }
+                    }
+                }
+                List::Labels => {
+                    let mut labels: Vec<_> = program.disassembler.labels.0.iter().collect();
+                    labels.sort_by_key(|label| label.address);
+                    for label in labels {
+                        let text = format!("{:04x}  {}", label.address, label.name);
+                        let selected = label.address == self.selected;
+                        if ui
+                            .selectable_label(selected, RichText::new(text).monospace())
+                            .clicked()
+                        {
+                            clicked = Some(label.address);
+                        }
+                    }
+                }
+            });
+        if let Some(address) = clicked {
+            self.select(address);
+        }
+    }
+
+    fn listing(&mut self, ui: &mut egui::Ui) {
+        let Some(program) = &self.program else {
+            return;
+        };
+        let item = program.disassembler.query(self.selected).start;
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let spacing = ui.spacing().item_spacing.y;
+        let mut area = egui::ScrollArea::vertical()
+            .id_salt("listing")
+            .auto_shrink(false);
+        if self.scrolling
+            && let Some(line) = program.line_of(item)
+        {
+            // keep a few lines of context above the selection
+            let top = line.saturating_sub(5) as f32 * (row_height + spacing);
+            area = area.vertical_scroll_offset(top);
+        }
+        let mut clicked = None;
+        area.show_rows(ui, row_height, program.lines.len(), |ui, rows| {
+            for line in &program.lines[rows] {
+                let selected = line.address == Some(item);
+                let text = RichText::new(&line.text).monospace();
+                if ui.selectable_label(selected, text).clicked() {
+                    clicked = line.address;
+                }
+            }
+        });
+        if let Some(address) = clicked {
+            self.select(address);
+        }
+    }
+
+    fn hex(&mut self, ui: &mut egui::Ui) {
+        let Some(program) = &self.program else {
+            return;
+        };
+        let info = program.disassembler.query(self.selected);
+        let length = info.instruction.map_or(1, |instruction| instruction.len());
+        let item = info.start as usize..info.start as usize + length;
+        let data = &program.disassembler.data;
+        let rows = data.len().div_ceil(HEX_ROW);
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let spacing = ui.spacing().item_spacing.y;
+        let mut area = egui::ScrollArea::vertical()
+            .id_salt("hex")
+            .auto_shrink(false);
+        if self.scrolling
+            && let Some(offset) = info.start.file_offset()
+        {
+            let row = offset / HEX_ROW;
+            area =
+                area.vertical_scroll_offset(row.saturating_sub(5) as f32 * (row_height + spacing));
+        }
+        let mut clicked = None;
+        area.show_rows(ui, row_height, rows, |ui, rows| {
+            for row in rows {
+                ui.horizontal(|ui| {
+                    ui.spacing_mut().item_spacing.x = 4.0;
+                    let start = row * HEX_ROW;
+                    ui.monospace(format!("{:04x}", start + COM_OFFSET as usize));
+                    for (index, byte) in data[start..(start + HEX_ROW).min(data.len())]
+                        .iter()
+                        .enumerate()
+                    {
+                        let address = start + index + COM_OFFSET as usize;
+                        let text = RichText::new(format!("{byte:02x}")).monospace();
+                        if ui.selectable_label(item.contains(&address), text).clicked() {
+                            clicked = Some(address as Address);
+                        }
+                    }
+                });
+            }
+        });
+        if let Some(address) = clicked {
+            self.select(address);
+        }
+    }
+
+    fn graph(&mut self, ui: &mut egui::Ui) {
+        let Some(program) = &self.program else {
+            return;
+        };
+        let code = |address| program.code.get(&address).cloned().unwrap_or_default();
+        if let Some(address) = graph::show(ui, &program.graph, self.selected, code) {
+            self.select(address);
+        }
+    }
+
+    fn status(&mut self, ui: &mut egui::Ui) {
+        let Some(program) = &self.program else {
+            ui.label("Open a .COM file, or drop one on the window.");
+            return;
+        };
+        let info = program.disassembler.query(self.selected);
+        ui.horizontal(|ui| {
+            ui.label(format!("{}: {info}", program.path.display()));
+            if let Some(label) = &info.label
+                && ui.button("Rename label").clicked()
+            {
+                self.rename = Some(Rename {
+                    address: label.address,
+                    name: label.name.clone(),
+                    error: None,
+                });
+            }
+        });
+    }
+
+    fn rename_dialog(&mut self, ctx: &egui::Context) {
+        let Some(rename) = &mut self.rename else {
+            return;
+        };
+        let mut done = false;
+        let mut apply = false;
+        egui::Window::new("Rename label")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "New name for the label at 0x{:04x}",
+                    rename.address
+                ));
+                let field = ui.text_edit_singleline(&mut rename.name);
+                let entered =
+                    field.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+                if let Some(error) = &rename.error {
+                    ui.colored_label(Color32::LIGHT_RED, error);
+                }
+                ui.horizontal(|ui| {
+                    apply = ui.button("Rename").clicked() || entered;
+                    done = ui.button("Cancel").clicked();
+                });
+            });
+        if apply && let Some(program) = &mut self.program {
+            let name = rename.name.trim().to_string();
+            rename.error = name_error(&program.disassembler, rename.address, &name);
+            if rename.error.is_none() {
+                program.disassembler.rename_label(rename.address, name);
+                program.render();
+                done = true;
+            }
+        }
+        if done {
+            self.rename = None;
+        }
+    }
+}
+
+/// Why `name` cannot be given to the label at `address`, if it cannot
+fn name_error(disassembler: &Disassembler, address: Address, name: &str) -> Option<String> {
+    let identifier =
+        |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | '@' | '?' | '#' | '~');
+    let starts_well = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || matches!(c, '_' | '.' | '?'));
+    if !starts_well || !name.chars().all(identifier) {
+        return Some(format!("{name:?} is not a valid NASM label"));
+    }
+    let taken = disassembler
+        .labels
+        .0
+        .iter()
+        .any(|label| label.name == name && label.address != address);
+    taken.then(|| format!("a label named {name} already exists"))
+}
+
+impl eframe::App for BrowserApp {
+    fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+        self.start_frame(ui.ctx());
+        let dropped = ui.input(|input| {
+            input
+                .raw
+                .dropped_files
+                .iter()
+                .map(|file| file.path().to_path_buf())
+                .find(|path| !path.as_os_str().is_empty())
+        });
+        if let Some(path) = dropped {
+            self.open(&path);
+        }
+
+        egui::Panel::top("file").show(ui, |ui| self.file_bar(ui));
+        egui::Panel::bottom("status").show(ui, |ui| self.status(ui));
+        egui::Panel::left("list")
+            .default_size(260.0)
+            .show(ui, |ui| self.side_list(ui));
+        egui::Panel::right("hex")
+            .default_size(420.0)
+            .show(ui, |ui| self.hex(ui));
+        egui::CentralPanel::default().show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.view, View::Listing, "Listing");
+                ui.selectable_value(&mut self.view, View::Graph, "Graph");
+            });
+            ui.separator();
+            match self.view {
+                View::Listing => self.listing(ui),
+                View::Graph => self.graph(ui),
+            }
+        });
+        self.rename_dialog(ui.ctx());
+    }
+}
+
+fn main() -> eframe::Result {
+    let native_options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("DosDisassm")
+            .with_inner_size([1280.0, 800.0])
+            .with_drag_and_drop(true),
+        ..Default::default()
+    };
+
+    let mut app = BrowserApp {
+        path: String::new(),
+        program: None,
+        error: None,
+        selected: COM_OFFSET,
+        scroll: false,
+        scrolling: false,
+        view: View::Listing,
+        list: List::Strings,
+        rename: None,
+    };
+    if let Some(path) = std::env::args_os().nth(1) {
+        app.open(Path::new(&path));
+    }
+    eframe::run_native(
+        "DosDisassm",
+        native_options,
+        Box::new(move |_cc| Ok(Box::new(app))),
+    )
+}
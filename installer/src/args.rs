@@ -16,11 +16,58 @@ pub struct Args {
     #[arg(long)]
     pub uninstall: bool,
 
-    /// add to the system path
-    #[arg(short, long, default_value_t = true)]
+    /// Do not add the install directory to the system path
+    #[arg(long = "no-path", action = clap::ArgAction::SetFalse)]
     pub add_to_path: bool,
 
-    /// Create Shortcuts desktop and start menu shortcuts
-    #[arg(short, long, default_value_t = true)]
+    /// Do not create desktop and start menu shortcuts to the GUI
+    #[arg(long = "no-shortcuts", action = clap::ArgAction::SetFalse)]
     pub create_shortcuts: bool,
+
+    /// Do not install shell completions and the man page
+    #[arg(long = "no-completions", action = clap::ArgAction::SetFalse)]
+    pub completions: bool,
+
+    /// Install release artifacts even if their signature is missing or cannot be verified
+    #[arg(long)]
+    pub insecure: bool,
+
+    /// Answer every question with the recommended choice instead of prompting
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Print nothing but errors. Implies --yes
+    #[arg(long, conflicts_with = "json")]
+    pub silent: bool,
+
+    /// Report progress as one JSON object per line on stdout. Implies --yes
+    #[arg(long)]
+    pub json: bool,
+
+    /// Show the graphical installer. This is the default when the installer is double-clicked on Windows
+    #[cfg(feature = "gui")]
+    #[arg(long)]
+    pub gui: bool,
+
+    /// GitHub token used to query releases. Only needed when the anonymous API rate limit is exhausted.
+    #[arg(long, env = "GITHUB_TOKEN", hide_env_values = true)]
+    pub github_token: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn everything_is_installed_unless_turned_off() {
+        let args = Args::parse_from(["installer"]);
+        assert!(args.add_to_path && args.create_shortcuts && args.completions);
+        let args = Args::parse_from([
+            "installer",
+            "--no-path",
+            "--no-shortcuts",
+            "--no-completions",
+        ]);
+        assert!(!args.add_to_path && !args.create_shortcuts && !args.completions);
+    }
 }
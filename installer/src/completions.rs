@@ -0,0 +1,116 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::manifest::Manifest;
+use crate::output;
+
+/// A file generated by running the installed binary, and where it goes
+struct Generated {
+    /// Arguments that make dosdisassm print the file
+    args: &'static [&'static str],
+    /// Where the file is written
+    destination: PathBuf,
+}
+
+/// Locations of the completion scripts and man page on Unix
+///
+/// Elevated installs use the system-wide directories, user installs the
+/// XDG data directories under the home directory.
+#[cfg(unix)]
+fn targets(_install_path: &Path) -> io::Result<Vec<Generated>> {
+    let (share, fish) = if crate::utils::is_elevated() {
+        (
+            PathBuf::from("/usr/share"),
+            crate::path!("/usr", "share", "fish", "vendor_completions.d"),
+        )
+    } else {
+        let home = std::env::var("HOME").map_err(io::Error::other)?;
+        let config = std::env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| format!("{home}/.config"));
+        (
+            crate::path!(home, ".local", "share"),
+            crate::path!(config, "fish", "completions"),
+        )
+    };
+
+    Ok(vec![
+        Generated {
+            args: &["completions", "bash"],
+            destination: crate::path!(&share, "bash-completion", "completions", "dosdisassm"),
+        },
+        Generated {
+            args: &["completions", "zsh"],
+            destination: crate::path!(&share, "zsh", "site-functions", "_dosdisassm"),
+        },
+        Generated {
+            args: &["completions", "fish"],
+            destination: fish.join("dosdisassm.fish"),
+        },
+        Generated {
+            args: &["manpage"],
+            destination: crate::path!(&share, "man", "man1", "dosdisassm.1"),
+        },
+    ])
+}
+
+/// Location of the PowerShell completion script on Windows
+///
+/// PowerShell has no directory it loads completions from, so the script is
+/// kept in the install directory and the user is told how to load it.
+#[cfg(windows)]
+fn targets(install_path: &Path) -> io::Result<Vec<Generated>> {
+    Ok(vec![Generated {
+        args: &["completions", "powershell"],
+        destination: install_path.join("dosdisassm.ps1"),
+    }])
+}
+
+/// Runs `binary` with `args` and returns what it printed
+fn generate(binary: &Path, args: &[&str]) -> io::Result<Vec<u8>> {
+    let output = Command::new(binary).args(args).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "`dosdisassm {}` failed",
+            args.join(" ")
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// Writes the shell completions and man page of the installed `binary`
+///
+/// Every file written is recorded in `manifest` so uninstalling removes it.
+/// Releases that cannot generate them yet are skipped with a warning.
+pub fn install(binary: &Path, install_path: &Path, manifest: &mut Manifest) -> io::Result<()> {
+    for target in targets(install_path)? {
+        let contents = match generate(binary, target.args) {
+            Ok(contents) => contents,
+            Err(err) => {
+                output::warn(format!(
+                    "could not generate {}: {err}",
+                    target.destination.display()
+                ));
+                continue;
+            }
+        };
+        if let Some(parent) = target.destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&target.destination, &contents)?;
+        manifest.record_file(&target.destination, &contents);
+
+        if target
+            .destination
+            .extension()
+            .is_some_and(|ext| ext == "ps1")
+        {
+            output::message(format!(
+                "To enable tab completion in PowerShell, add the following line to your $PROFILE:
+. \"{}\"",
+                target.destination.display()
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::io::{self, Cursor};
+use std::path::Path;
+
+use octocrab::Octocrab;
+use octocrab::models::repos::Release;
+use qbsdiff::Bspatch;
+
+use crate::error::Error;
+use crate::release::{self, platform_suffix};
+
+/// The name of the bsdiff patch turning the `from` release binary into the one in `release`
+///
+/// The release workflow publishes one patch per platform from the previous
+/// release, named `dosdisassm-v<from>-to-<tag>-<suffix>.bsdiff`.
+pub fn patch_asset_name(from: &semver::Version, release: &Release) -> Option<String> {
+    let suffix = platform_suffix()?;
+    Some(format!(
+        "dosdisassm-v{from}-to-{}-{suffix}.bsdiff",
+        release.tag_name
+    ))
+}
+
+/// Applies a bsdiff 4.x patch to `old`
+///
+/// The target size in the patch header is not trusted to reserve memory, as
+/// a corrupt patch could claim any size; the output grows as it is written.
+pub fn apply_patch(old: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    let patcher = Bspatch::new(patch)?;
+    let mut new = Vec::new();
+    patcher.apply(old, Cursor::new(&mut new))?;
+    Ok(new)
+}
+
+/// Rebuilds the new binary by patching the installed one
+///
+/// The result must hash to `expected` (the digest of the full asset from the
+/// signed manifest), otherwise an error is returned so the caller can fall
+/// back to downloading the full binary.
+pub async fn download_patched(
+    octocrab: &Octocrab,
+    release: &Release,
+    installed: &semver::Version,
+    binary: &Path,
+    expected: &str,
+) -> Result<Vec<u8>, Error> {
+    let name = patch_asset_name(installed, release).ok_or(Error::UnsupportedPlatform)?;
+    let asset = release::find_asset(release, &name)?;
+    let patch = release::download_asset(octocrab, asset).await?;
+    patch_installed(binary, &patch, &name, expected)
+}
+
+/// Patches the installed `binary` with the patch asset `name` without
+/// touching the file, checking the result hashes to `expected`
+fn patch_installed(
+    binary: &Path,
+    patch: &[u8],
+    name: &str,
+    expected: &str,
+) -> Result<Vec<u8>, Error> {
+    let old = std::fs::read(binary)?;
+    let new = apply_patch(&old, patch)?;
+    let actual = release::sha256_hex(&new);
+    if actual != expected {
+        return Err(Error::ChecksumMismatch {
+            name: name.to_string(),
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+
+    Ok(new)
+}
+
+#[cfg(test)]
+mod tests {
+    use qbsdiff::Bsdiff;
+
+    use super::*;
+    use crate::utils::create_temp_dir;
+
+    const OLD: &[u8] = b"dosdisassm 0.1.1\0\x01\x02\x03 the old binary's code and data";
+    const NEW: &[u8] = b"dosdisassm 0.1.2\0\x01\x02\x04 the new binary's code, data and more";
+
+    fn diff(old: &[u8], new: &[u8]) -> Vec<u8> {
+        let mut patch = Vec::new();
+        Bsdiff::new(old, new)
+            .compare(Cursor::new(&mut patch))
+            .expect("diffing in memory cannot fail");
+        patch
+    }
+
+    #[test]
+    fn patches_round_trip() {
+        assert_eq!(apply_patch(OLD, &diff(OLD, NEW)).unwrap(), NEW);
+    }
+
+    #[test]
+    fn corrupt_patches_fail_and_leave_the_binary_alone() {
+        let binary = create_temp_dir().join("dosdisassm");
+        std::fs::write(&binary, OLD).unwrap();
+        let expected = release::sha256_hex(NEW);
+
+        let patch = diff(OLD, NEW);
+        let mut corrupt = patch.clone();
+        corrupt[..8].copy_from_slice(b"NOTBSDIF");
+        assert!(patch_installed(&binary, &corrupt, "patch", &expected).is_err());
+        let truncated = &patch[..patch.len() / 2];
+        assert!(patch_installed(&binary, truncated, "patch", &expected).is_err());
+        assert_eq!(std::fs::read(&binary).unwrap(), OLD);
+
+        assert_eq!(
+            patch_installed(&binary, &patch, "patch", &expected).unwrap(),
+            NEW
+        );
+        std::fs::remove_dir_all(binary.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn huge_target_sizes_in_the_header_are_not_reserved() {
+        let mut patch = diff(OLD, NEW);
+        // the target size, after the magic and the control and diff block lengths
+        patch[24..32].copy_from_slice(&(1u64 << 62).to_le_bytes());
+        assert_eq!(apply_patch(OLD, &patch).unwrap(), NEW);
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// Exit code for a successful run
+pub const EXIT_SUCCESS: i32 = 0;
+/// Exit code for failures without a more specific code
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit code when the newest version is already installed and nothing was done
+pub const EXIT_ALREADY_INSTALLED: i32 = 10;
+/// Exit code when the install directory cannot be written without elevated privileges
+pub const EXIT_NEEDS_ELEVATION: i32 = 11;
+/// Exit code when GitHub could not be reached or refused the request
+pub const EXIT_NETWORK: i32 = 12;
+/// Exit code when the user chose to leave an existing installation untouched
+pub const EXIT_ABORTED: i32 = 13;
+
+/// Errors that can occur while installing or updating
+#[derive(Debug)]
+pub enum Error {
+    /// An error talking to the GitHub API
+    GitHub(octocrab::Error),
+    /// An error reading or writing files
+    Io(std::io::Error),
+    /// The GitHub API rate limit was exceeded
+    RateLimited {
+        /// Unix timestamp at which the limit resets, if known
+        reset: Option<u64>,
+        /// Whether the requests were made with a token
+        authenticated: bool,
+    },
+    /// No release with a valid version tag was found
+    NoRelease,
+    /// There are no prebuilt binaries for this OS/architecture
+    UnsupportedPlatform,
+    /// The release does not contain an expected asset
+    MissingAsset(String),
+    /// The checksum manifest does not list an asset
+    MissingChecksum(String),
+    /// A downloaded asset does not match its published checksum
+    ChecksumMismatch {
+        /// The name of the asset
+        name: String,
+        /// The digest listed in the checksum manifest
+        expected: String,
+        /// The digest of the downloaded bytes
+        actual: String,
+    },
+    /// A release artifact has no signature and `--insecure` was not given
+    Unsigned(String),
+    /// A signature did not verify against the compiled-in public key
+    BadSignature(String),
+    /// The installer was built without a release signing key and `--insecure` was not given
+    NoReleaseKey,
+    /// The install directory is not writable by the current user
+    NeedsElevation(PathBuf),
+    /// The freshly installed binary failed the `--version` smoke test and was rolled back
+    SmokeTestFailed(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::GitHub(octocrab::Error::GitHub { source, .. }) => {
+                write!(f, "GitHub request failed: {}", source.message)
+            }
+            // octocrab appends a backtrace to the message of transport errors
+            Error::GitHub(err) => write!(
+                f,
+                "GitHub request failed: {}",
+                err.to_string().lines().next().unwrap_or_default()
+            ),
+            Error::Io(err) => write!(f, "IO error: {err}"),
+            Error::RateLimited {
+                reset,
+                authenticated,
+            } => {
+                write!(f, "GitHub API rate limit exceeded")?;
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|now| now.as_secs())
+                    .unwrap_or_default();
+                if let Some(reset) = reset {
+                    let minutes = reset.saturating_sub(now).div_ceil(60);
+                    write!(f, ", try again in {minutes} minute(s)")?;
+                }
+                if !authenticated {
+                    write!(
+                        f,
+                        " or set GITHUB_TOKEN (or pass --github-token) to raise the limit"
+                    )?;
+                }
+                Ok(())
+            }
+            Error::NoRelease => write!(f, "no published release was found"),
+            Error::UnsupportedPlatform => write!(
+                f,
+                "no prebuilt binary is published for {}-{}",
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            ),
+            Error::MissingAsset(name) => write!(f, "release is missing asset {name}"),
+            Error::MissingChecksum(name) => write!(f, "no checksum is published for {name}"),
+            Error::ChecksumMismatch {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "checksum mismatch for {name}: expected {expected}, got {actual}"
+            ),
+            Error::Unsigned(name) => write!(
+                f,
+                "{name} is not signed; refusing to install it without --insecure"
+            ),
+            Error::BadSignature(reason) => write!(f, "signature verification failed: {reason}"),
+            Error::NoReleaseKey => write!(
+                f,
+                "this installer has no release signing key to verify releases with; refusing to install without --insecure"
+            ),
+            Error::NeedsElevation(path) => write!(
+                f,
+                "{} is not writable, re-run as administrator/root or pick another --install-path",
+                path.display()
+            ),
+            Error::SmokeTestFailed(reason) => {
+                write!(
+                    f,
+                    "new binary failed its smoke test ({reason}), rolled back"
+                )
+            }
+        }
+    }
+}
+
+impl Error {
+    /// Wraps an IO error on `path`, recognising permission problems as [`Error::NeedsElevation`]
+    pub fn io_at(err: std::io::Error, path: &Path) -> Self {
+        if err.kind() == std::io::ErrorKind::PermissionDenied {
+            Error::NeedsElevation(path.to_path_buf())
+        } else {
+            Error::Io(err)
+        }
+    }
+
+    /// The process exit code this error is reported with
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::GitHub(_) | Error::RateLimited { .. } => EXIT_NETWORK,
+            Error::NeedsElevation(_) => EXIT_NEEDS_ELEVATION,
+            _ => EXIT_FAILURE,
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<octocrab::Error> for Error {
+    fn from(err: octocrab::Error) -> Self {
+        Error::GitHub(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
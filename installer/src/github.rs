@@ -0,0 +1,53 @@
+use octocrab::Octocrab;
+
+use crate::error::Error;
+
+/// Builds the GitHub client used to query releases
+///
+/// Public releases need no credentials, so the client is anonymous unless a
+/// token is supplied (via `--github-token` or the `GITHUB_TOKEN` environment
+/// variable) to lift the unauthenticated rate limit.
+pub fn client(token: Option<&str>) -> Result<Octocrab, Error> {
+    let builder = Octocrab::builder();
+    let builder = match token {
+        Some(token) => builder.personal_token(token.trim().to_string()),
+        None => builder,
+    };
+    Ok(builder.build()?)
+}
+
+/// Returns true if GitHub rejected a request because the rate limit was hit
+fn is_rate_limited(err: &octocrab::Error) -> bool {
+    match err {
+        octocrab::Error::GitHub { source, .. } => {
+            let status = source.status_code.as_u16();
+            (status == 403 || status == 429) && source.message.to_lowercase().contains("rate limit")
+        }
+        _ => false,
+    }
+}
+
+/// Turns a rate-limit failure into [`Error::RateLimited`] with the time until the limit resets
+///
+/// Any other error is returned unchanged.
+pub async fn explain_error(octocrab: &Octocrab, authenticated: bool, err: Error) -> Error {
+    let Error::GitHub(source) = &err else {
+        return err;
+    };
+    if !is_rate_limited(source) {
+        return err;
+    }
+
+    // querying the rate limit itself does not count against it
+    let reset = octocrab
+        .ratelimit()
+        .get()
+        .await
+        .ok()
+        .map(|limit| limit.resources.core.reset);
+
+    Error::RateLimited {
+        reset,
+        authenticated,
+    }
+}
@@ -0,0 +1,230 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use eframe::egui;
+
+use crate::error::Error;
+use crate::github;
+use crate::output::{self, Event};
+use crate::update::{self, InstallOptions, Outcome};
+
+/// Returns true if the installer owns its console window, i.e. it was
+/// double-clicked in Explorer rather than started from a terminal
+#[cfg(windows)]
+pub fn launched_from_explorer() -> bool {
+    let mut processes = [0u32; 2];
+    unsafe { winapi::um::wincon::GetConsoleProcessList(processes.as_mut_ptr(), 2) == 1 }
+}
+
+#[cfg(not(windows))]
+pub fn launched_from_explorer() -> bool {
+    false
+}
+
+/// Progress shared between the install task and the window
+#[derive(Default)]
+struct Progress {
+    /// Every event reported so far, formatted for people
+    log: Vec<String>,
+    /// How far along the install is, from 0 to 1
+    fraction: f32,
+    /// The final message once the install has finished, and whether it succeeded
+    result: Option<(bool, String)>,
+}
+
+/// The page the installer window is showing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Page {
+    /// Install path and component selection
+    Options,
+    /// Progress bar and log while installing
+    Installing,
+    /// The outcome of the install
+    Finished,
+}
+
+/// The installer window
+struct InstallerApp {
+    page: Page,
+    install_path: String,
+    options: InstallOptions,
+    token: Option<String>,
+    progress: Arc<Mutex<Progress>>,
+    runtime: tokio::runtime::Handle,
+}
+
+impl InstallerApp {
+    /// Starts the install on the tokio runtime and switches to the progress page
+    fn start(&mut self, ctx: &egui::Context) {
+        self.page = Page::Installing;
+
+        let install_path = PathBuf::from(self.install_path.trim());
+        let options = self.options;
+        let token = self.token.clone();
+        let progress = Arc::clone(&self.progress);
+        let ctx = ctx.clone();
+        self.runtime.spawn(async move {
+            let result = install(&install_path, &options, token.as_deref()).await;
+            let result = match result {
+                Ok(Outcome::Installed) => (true, "DosDisassm has been installed.".to_string()),
+                Ok(Outcome::UpToDate) => (true, "DosDisassm is already up to date.".to_string()),
+                Ok(Outcome::Aborted) => (true, "The installation was cancelled.".to_string()),
+                Err(err) => (false, err.to_string()),
+            };
+            let mut progress = progress.lock().unwrap();
+            progress.fraction = 1.0;
+            progress.result = Some(result);
+            ctx.request_repaint();
+        });
+    }
+
+    fn options_page(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Install DosDisassm");
+        ui.add_space(8.0);
+
+        ui.label("Install location");
+        ui.text_edit_singleline(&mut self.install_path);
+        ui.add_space(8.0);
+
+        ui.label("Components");
+        ui.add_enabled(
+            false,
+            egui::Checkbox::new(&mut true, "dosdisassm command line tool"),
+        );
+        ui.checkbox(
+            &mut self.options.install_completions,
+            "Shell completions and man page",
+        );
+        ui.checkbox(
+            &mut self.options.create_shortcuts,
+            "Start menu and desktop shortcuts to the GUI",
+        );
+        ui.checkbox(&mut self.options.add_to_path, "Add to PATH");
+        ui.add_space(16.0);
+
+        let ready = !self.install_path.trim().is_empty();
+        if ui
+            .add_enabled(ready, egui::Button::new("Install"))
+            .clicked()
+        {
+            self.start(ui.ctx());
+        }
+    }
+
+    fn installing_page(&mut self, ui: &mut egui::Ui) {
+        let progress = self.progress.lock().unwrap();
+        ui.heading("Installing DosDisassm");
+        ui.add_space(8.0);
+        ui.add(egui::ProgressBar::new(progress.fraction).animate(true));
+        ui.add_space(8.0);
+        egui::ScrollArea::vertical()
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for line in &progress.log {
+                    ui.label(line);
+                }
+            });
+        if progress.result.is_some() {
+            drop(progress);
+            self.page = Page::Finished;
+        }
+    }
+
+    fn finished_page(&mut self, ui: &mut egui::Ui) {
+        let progress = self.progress.lock().unwrap();
+        let (success, message) = progress.result.clone().unwrap_or_default();
+        ui.heading(if success {
+            "Installation complete"
+        } else {
+            "Installation failed"
+        });
+        ui.add_space(8.0);
+        ui.label(message);
+        ui.add_space(16.0);
+        if ui.button("Close").clicked() {
+            ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+    }
+}
+
+impl eframe::App for InstallerApp {
+    fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ui, |ui| match self.page {
+            Page::Options => self.options_page(ui),
+            Page::Installing => self.installing_page(ui),
+            Page::Finished => self.finished_page(ui),
+        });
+    }
+}
+
+/// Runs the install as the CLI would, explaining GitHub errors
+async fn install(
+    install_path: &std::path::Path,
+    options: &InstallOptions,
+    token: Option<&str>,
+) -> Result<Outcome, Error> {
+    let octocrab = github::client(token)?;
+    match update::run(&octocrab, install_path, options).await {
+        Err(err) => Err(github::explain_error(&octocrab, token.is_some(), err).await),
+        result => result,
+    }
+}
+
+/// How far along the install is once `event` has been reported
+fn stage(event: &Event) -> Option<f32> {
+    match event {
+        Event::LatestRelease { .. } => Some(0.1),
+        Event::ExistingInstall { .. } => Some(0.2),
+        Event::Download { .. } => Some(0.4),
+        Event::Installed { .. } | Event::UpToDate { .. } => Some(1.0),
+        _ => None,
+    }
+}
+
+/// Shows the installer window until it is closed
+///
+/// Must be called from the main thread inside the tokio runtime; the install
+/// itself runs as a task on that runtime. Questions are answered with the
+/// recommended choice, as with `--yes`.
+pub fn run(
+    install_path: PathBuf,
+    options: InstallOptions,
+    token: Option<String>,
+) -> eframe::Result {
+    let progress = Arc::new(Mutex::new(Progress::default()));
+    let listener_progress = Arc::clone(&progress);
+    let native_options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("DosDisassm Installer")
+            .with_inner_size([480.0, 360.0]),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "DosDisassm Installer",
+        native_options,
+        Box::new(move |cc| {
+            let ctx = cc.egui_ctx.clone();
+            output::set_listener(move |event| {
+                let mut progress = listener_progress.lock().unwrap();
+                progress.log.push(event.to_string());
+                if let Some(fraction) = stage(event) {
+                    progress.fraction = progress.fraction.max(fraction);
+                }
+                ctx.request_repaint();
+            });
+
+            Ok(Box::new(InstallerApp {
+                page: Page::Options,
+                install_path: install_path.display().to_string(),
+                options: InstallOptions {
+                    assume_yes: true,
+                    ..options
+                },
+                token,
+                progress,
+                runtime: tokio::runtime::Handle::current(),
+            }))
+        }),
+    )
+}
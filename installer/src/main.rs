@@ -1,51 +1,87 @@
 use clap::Parser;
 
+use error::{EXIT_ABORTED, EXIT_ALREADY_INSTALLED, EXIT_SUCCESS, Error};
+use output::{Event, Mode};
+
 mod args;
+mod completions;
+mod delta;
+mod error;
+mod github;
+#[cfg(feature = "gui")]
+mod gui;
+mod manifest;
+mod output;
+mod release;
+#[cfg(unix)]
+mod shell;
+mod shortcuts;
+mod uninstall;
+mod update;
 mod utils;
+mod verify;
+
+/// Reports `err` and terminates with its exit code
+fn fail(err: &Error) -> ! {
+    let exit_code = err.exit_code();
+    output::emit(Event::Error {
+        message: &err.to_string(),
+        exit_code,
+    });
+    std::process::exit(exit_code);
+}
 
 #[tokio::main]
 /// The main function for the installer
 async fn main() {
     let args = args::Args::parse();
+    output::set_mode(if args.json {
+        Mode::Json
+    } else if args.silent {
+        Mode::Silent
+    } else {
+        Mode::Human
+    });
     let install_path = args
         .install_path
-        .unwrap_or_else(|| utils::get_default_installation_path());
-
-    println!("Installing to: {:?}", install_path);
-
-    let octocrab = octocrab::Octocrab::builder()
-        .build()
-        .expect("Failed to create Octocrab client");
-    let repo = octocrab.repos("sk337", "COM");
-    let releases = repo.releases().list().send().await.unwrap();
-    let tag_names = releases
-        .into_iter()
-        .filter_map(|release| Some(release.tag_name.clone()))
-        .collect::<Vec<_>>();
-    let latest_release = tag_names
-        .iter()
-        .filter_map(|tag| {
-            if tag.starts_with('v') {
-                // Remove the 'v' and parse the version
-                semver::Version::parse(&tag[1..])
-                    .ok()
-                    .map(|version| (version, tag))
-            } else {
-                None
-            }
-        })
-        .max_by(|(version_a, _), (version_b, _)| version_a.cmp(version_b))
-        .map(|(_, tag)| tag.clone())
-        .unwrap_or_else(|| "v0.0.0".to_string());
-    println!("Latest release: {}", latest_release);
-
-    let release = repo
-        .releases()
-        .get_by_tag(&latest_release)
-        .await
-        .expect("Failed to get release");
-    let assets = release.assets;
-    for asset in &assets {
-        println!("Asset: {}", asset.name);
+        .unwrap_or_else(utils::get_default_installation_path);
+
+    if args.uninstall {
+        if let Err(err) = uninstall::run(&install_path) {
+            fail(&err);
+        }
+        return;
+    }
+
+    output::message(format!("Installing to: {:?}", install_path));
+
+    let token = args
+        .github_token
+        .as_deref()
+        .filter(|token| !token.trim().is_empty());
+    let options = update::InstallOptions {
+        insecure: args.insecure,
+        add_to_path: args.add_to_path,
+        create_shortcuts: args.create_shortcuts,
+        install_completions: args.completions,
+        assume_yes: args.yes || args.silent || args.json,
+    };
+
+    #[cfg(feature = "gui")]
+    if args.gui || (std::env::args_os().len() == 1 && gui::launched_from_explorer()) {
+        match gui::run(install_path.clone(), options, token.map(str::to_string)) {
+            Ok(()) => return,
+            Err(err) => output::warn(format!(
+                "could not open the installer window ({err}), continuing in the terminal"
+            )),
+        }
     }
+    let octocrab = github::client(token).unwrap_or_else(|err| fail(&err));
+    let exit_code = match update::run(&octocrab, &install_path, &options).await {
+        Ok(update::Outcome::Installed) => EXIT_SUCCESS,
+        Ok(update::Outcome::UpToDate) => EXIT_ALREADY_INSTALLED,
+        Ok(update::Outcome::Aborted) => EXIT_ABORTED,
+        Err(err) => fail(&github::explain_error(&octocrab, token.is_some(), err).await),
+    };
+    std::process::exit(exit_code);
 }
@@ -0,0 +1,191 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::release::sha256_hex;
+
+/// The file name of the manifest written into the install directory
+pub const MANIFEST_NAME: &str = "install-manifest.json";
+
+/// A file copied into place by the installer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledFile {
+    /// Absolute path of the file
+    pub path: PathBuf,
+    /// Lowercase hex SHA-256 of the contents that were installed
+    pub sha256: String,
+}
+
+/// A directory the installer added to the user's PATH
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathEntry {
+    /// The directory that was added
+    pub directory: PathBuf,
+    /// The shell profile that was edited, on Unix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<PathBuf>,
+    /// The line appended to [`PathEntry::profile`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<String>,
+}
+
+/// A registry key created by the installer, relative to `HKEY_CURRENT_USER`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegistryKey {
+    /// The path of the key, e.g. `Software\Microsoft\Windows\CurrentVersion\Uninstall\DOSDisassm`
+    pub path: String,
+}
+
+/// A record of what the installer put on this machine
+///
+/// Everything listed here is undone by `--uninstall`, and files that an
+/// upgrade no longer installs are removed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The version of dosdisassm that was installed
+    pub version: semver::Version,
+    /// Files written by the installer
+    pub files: Vec<InstalledFile>,
+    /// Desktop and start menu shortcuts
+    #[serde(default)]
+    pub shortcuts: Vec<PathBuf>,
+    /// Modifications made to PATH
+    #[serde(default)]
+    pub path_entries: Vec<PathEntry>,
+    /// Registry keys created (Windows only)
+    #[serde(default)]
+    pub registry_keys: Vec<RegistryKey>,
+}
+
+impl Manifest {
+    /// Creates an empty manifest for `version`
+    pub fn new(version: semver::Version) -> Self {
+        Manifest {
+            version,
+            files: Vec::new(),
+            shortcuts: Vec::new(),
+            path_entries: Vec::new(),
+            registry_keys: Vec::new(),
+        }
+    }
+
+    /// The location of the manifest for an install directory
+    pub fn path(install_path: &Path) -> PathBuf {
+        install_path.join(MANIFEST_NAME)
+    }
+
+    /// Loads the manifest of an existing install, if there is one
+    pub fn load(install_path: &Path) -> io::Result<Option<Self>> {
+        match fs::read(Self::path(install_path)) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Writes the manifest into the install directory
+    pub fn save(&self, install_path: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(Self::path(install_path), json)
+    }
+
+    /// Records a file with the given contents, replacing any earlier record of the same path
+    pub fn record_file(&mut self, path: &Path, contents: &[u8]) {
+        self.files.retain(|file| file.path != path);
+        self.files.push(InstalledFile {
+            path: path.to_path_buf(),
+            sha256: sha256_hex(contents),
+        });
+    }
+
+    /// Records a shortcut unless it is already listed
+    pub fn record_shortcut(&mut self, path: &Path) {
+        if !self.shortcuts.iter().any(|shortcut| shortcut == path) {
+            self.shortcuts.push(path.to_path_buf());
+        }
+    }
+
+    /// Records a PATH modification unless it is already listed
+    pub fn record_path_entry(&mut self, entry: PathEntry) {
+        if !self.path_entries.contains(&entry) {
+            self.path_entries.push(entry);
+        }
+    }
+
+    /// Records a registry key unless it is already listed
+    pub fn record_registry_key(&mut self, path: &str) {
+        if !self.registry_keys.iter().any(|key| key.path == path) {
+            self.registry_keys.push(RegistryKey {
+                path: path.to_string(),
+            });
+        }
+    }
+
+    /// Files recorded in `self` that `newer` no longer installs
+    pub fn stale_files<'a>(&'a self, newer: &Manifest) -> Vec<&'a InstalledFile> {
+        self.files
+            .iter()
+            .filter(|file| !newer.files.iter().any(|other| other.path == file.path))
+            .collect()
+    }
+
+    /// Files that are missing or no longer match what was installed
+    pub fn damaged_files(&self) -> Vec<&InstalledFile> {
+        self.files
+            .iter()
+            .filter(|file| {
+                fs::read(&file.path).map_or(true, |contents| sha256_hex(&contents) != file.sha256)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::create_temp_dir;
+
+    #[test]
+    fn missing_and_modified_files_are_damaged() {
+        let dir = create_temp_dir();
+        let intact = dir.join("dosdisassm");
+        let modified = dir.join("dosdisassm.1");
+        let missing = dir.join("dosdisassm.bash");
+        fs::write(&intact, b"binary").unwrap();
+        fs::write(&modified, b"edited by hand").unwrap();
+
+        let mut manifest = Manifest::new(semver::Version::new(0, 1, 2));
+        manifest.record_file(&intact, b"binary");
+        manifest.record_file(&modified, b"man page");
+        manifest.record_file(&missing, b"completions");
+        let damaged: Vec<&Path> = manifest
+            .damaged_files()
+            .iter()
+            .map(|file| file.path.as_path())
+            .collect();
+        assert_eq!(damaged, [modified.as_path(), missing.as_path()]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn files_the_new_version_drops_are_stale() {
+        let mut old = Manifest::new(semver::Version::new(0, 1, 1));
+        old.record_file(Path::new("/opt/dosdisassm/dosdisassm"), b"old binary");
+        old.record_file(Path::new("/opt/dosdisassm/README.txt"), b"readme");
+        let mut new = Manifest::new(semver::Version::new(0, 1, 2));
+        new.record_file(Path::new("/opt/dosdisassm/dosdisassm"), b"new binary");
+
+        let stale: Vec<&Path> = old
+            .stale_files(&new)
+            .iter()
+            .map(|file| file.path.as_path())
+            .collect();
+        assert_eq!(stale, [Path::new("/opt/dosdisassm/README.txt")]);
+        assert!(new.stale_files(&new).is_empty());
+    }
+}
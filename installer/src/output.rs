@@ -0,0 +1,160 @@
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use serde::Serialize;
+
+/// How progress is reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Plain text for a person at a terminal
+    Human,
+    /// One JSON object per line on stdout, for provisioning scripts and wrappers
+    Json,
+    /// Nothing but errors on stderr
+    Silent,
+}
+
+static MODE: OnceLock<Mode> = OnceLock::new();
+
+/// Selects the output mode; only the first call has an effect
+pub fn set_mode(mode: Mode) {
+    let _ = MODE.set(mode);
+}
+
+/// The selected output mode
+pub fn mode() -> Mode {
+    MODE.get().copied().unwrap_or(Mode::Human)
+}
+
+/// A callback that sees every event, whatever the output mode
+type Listener = Box<dyn Fn(&Event) + Send + Sync>;
+
+static LISTENER: OnceLock<Listener> = OnceLock::new();
+
+/// Forwards every event to `listener` as well; only the first call has an effect
+#[cfg(feature = "gui")]
+pub fn set_listener(listener: impl Fn(&Event) + Send + Sync + 'static) {
+    let _ = LISTENER.set(Box::new(listener));
+}
+
+/// Something that happened during an install, update or uninstall
+///
+/// In JSON mode each event is printed as an object with an `event` field
+/// naming the variant, e.g. `{"event":"installed","version":"0.1.2",...}`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// General progress information
+    Message {
+        /// The text of the message
+        message: &'a str,
+    },
+    /// Something went wrong but the installer carries on
+    Warning {
+        /// The text of the warning
+        message: &'a str,
+    },
+    /// The newest published release
+    LatestRelease {
+        /// The version of the release
+        version: &'a semver::Version,
+    },
+    /// An installation was found in the install directory
+    ExistingInstall {
+        /// The installed version
+        version: &'a semver::Version,
+        /// Files that are missing or were modified since they were installed
+        damaged: Vec<&'a Path>,
+    },
+    /// A release asset is being downloaded
+    Download {
+        /// The name of the asset
+        asset: &'a str,
+        /// Its size in bytes
+        size: i64,
+    },
+    /// A file, shortcut, PATH entry or registry key was removed
+    Removed {
+        /// What was removed
+        item: &'a str,
+    },
+    /// The installed version is already the newest
+    UpToDate {
+        /// The installed version
+        version: &'a semver::Version,
+    },
+    /// The installation finished
+    Installed {
+        /// The version that is now installed
+        version: &'a semver::Version,
+        /// The path of the installed binary
+        path: &'a Path,
+    },
+    /// The uninstall finished
+    Uninstalled,
+    /// The run failed
+    Error {
+        /// What went wrong
+        message: &'a str,
+        /// The exit code the installer terminates with
+        exit_code: i32,
+    },
+}
+
+impl Display for Event<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::Message { message } => write!(f, "{message}"),
+            Event::Warning { message } => write!(f, "Warn: {message}"),
+            Event::LatestRelease { version } => write!(f, "Latest release: v{version}"),
+            Event::ExistingInstall { version, damaged } => {
+                write!(f, "Found dosdisassm {version} installed")?;
+                for path in damaged {
+                    write!(f, "\n  missing or modified: {}", path.display())?;
+                }
+                Ok(())
+            }
+            Event::Download { asset, size } => write!(f, "Downloading {asset} ({size} bytes)"),
+            Event::Removed { item } => write!(f, "Removing {item}"),
+            Event::UpToDate { version } => write!(f, "dosdisassm {version} is already up to date"),
+            Event::Installed { version, path } => {
+                write!(f, "Installed dosdisassm {version} to {}", path.display())
+            }
+            Event::Uninstalled => write!(f, "dosdisassm has been uninstalled"),
+            Event::Error { message, .. } => write!(f, "Error: {message}"),
+        }
+    }
+}
+
+/// Reports an event in the selected output mode
+pub fn emit(event: Event) {
+    if let Some(listener) = LISTENER.get() {
+        listener(&event);
+    }
+    let is_problem = matches!(event, Event::Warning { .. } | Event::Error { .. });
+    match mode() {
+        Mode::Json => match serde_json::to_string(&event) {
+            Ok(json) => println!("{json}"),
+            Err(_) => eprintln!("{event}"),
+        },
+        Mode::Human if is_problem => eprintln!("{event}"),
+        Mode::Human => println!("{event}"),
+        Mode::Silent if matches!(event, Event::Error { .. }) => eprintln!("{event}"),
+        Mode::Silent => {}
+    }
+}
+
+/// Reports general progress
+pub fn message(message: impl AsRef<str>) {
+    emit(Event::Message {
+        message: message.as_ref(),
+    });
+}
+
+/// Reports a recoverable problem
+pub fn warn(message: impl AsRef<str>) {
+    emit(Event::Warning {
+        message: message.as_ref(),
+    });
+}
@@ -0,0 +1,258 @@
+use std::path::Path;
+
+use octocrab::Octocrab;
+use octocrab::models::repos::{Asset, Release};
+use sha2::{Digest, Sha256};
+
+use crate::delta;
+use crate::error::Error;
+use crate::output::{self, Event};
+use crate::verify;
+
+/// The owner of the GitHub repository releases are published to
+pub const REPO_OWNER: &str = "sk337";
+/// The name of the GitHub repository releases are published to
+pub const REPO_NAME: &str = "COM";
+/// The name of the checksum manifest uploaded alongside every release
+pub const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+/// The asset name suffix for the platform this installer was built for
+///
+/// The release workflow uploads one raw binary per platform named
+/// `dosdisassm-<tag>-<suffix>`, and one of the GUI named
+/// `dosdisassm-gui-<tag>-<suffix>`.
+pub fn platform_suffix() -> Option<&'static str> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => Some("linux-x86_64"),
+        ("windows", "x86_64") => Some("windows-x64.exe"),
+        ("macos", "x86_64") => Some("macos-x86_64"),
+        ("macos", "aarch64") => Some("macos-aarch64"),
+        _ => None,
+    }
+}
+
+/// Parses a release tag such as `v0.1.2` into a version
+pub fn parse_tag(tag: &str) -> Option<semver::Version> {
+    tag.strip_prefix('v')
+        .and_then(|version| semver::Version::parse(version).ok())
+}
+
+/// Fetches the release with the highest semver tag
+pub async fn latest_release(octocrab: &Octocrab) -> Result<Release, Error> {
+    let repo = octocrab.repos(REPO_OWNER, REPO_NAME);
+    let releases = repo.releases().list().send().await?;
+    let latest_tag = releases
+        .into_iter()
+        .filter_map(|release| {
+            parse_tag(&release.tag_name).map(|version| (version, release.tag_name))
+        })
+        .max_by(|(version_a, _), (version_b, _)| version_a.cmp(version_b))
+        .map(|(_, tag)| tag)
+        .ok_or(Error::NoRelease)?;
+
+    Ok(repo.releases().get_by_tag(&latest_tag).await?)
+}
+
+/// Finds the binary asset matching the current platform in a release
+pub fn platform_asset(release: &Release) -> Result<&Asset, Error> {
+    let suffix = platform_suffix().ok_or(Error::UnsupportedPlatform)?;
+    let name = format!("dosdisassm-{}-{suffix}", release.tag_name);
+    find_asset(release, &name)
+}
+
+/// Finds the GUI binary asset matching the current platform in a release
+pub fn gui_asset(release: &Release) -> Result<&Asset, Error> {
+    let suffix = platform_suffix().ok_or(Error::UnsupportedPlatform)?;
+    let name = format!("dosdisassm-gui-{}-{suffix}", release.tag_name);
+    find_asset(release, &name)
+}
+
+/// Finds an asset in a release by its exact name
+pub fn find_asset<'a>(release: &'a Release, name: &str) -> Result<&'a Asset, Error> {
+    release
+        .assets
+        .iter()
+        .find(|asset| asset.name == name)
+        .ok_or_else(|| Error::MissingAsset(name.to_string()))
+}
+
+/// Downloads the contents of a release asset
+pub async fn download_asset(octocrab: &Octocrab, asset: &Asset) -> Result<Vec<u8>, Error> {
+    output::emit(Event::Download {
+        asset: &asset.name,
+        size: asset.size,
+    });
+    Ok(octocrab
+        .download(
+            asset.browser_download_url.as_str(),
+            "application/octet-stream",
+        )
+        .await?)
+}
+
+/// Returns the lowercase hex SHA-256 digest of `bytes`
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Looks up the expected digest of `name` in a `sha256sum` style manifest
+///
+/// Lines whose digest is not 64 hex digits are skipped.
+pub fn expected_checksum(manifest: &str, name: &str) -> Option<String> {
+    manifest.lines().find_map(|line| {
+        let (digest, file) = line.split_once(char::is_whitespace)?;
+        if digest.len() != 64 || !digest.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return None;
+        }
+        // `sha256sum` marks binary mode files with a leading `*`
+        let file = file.trim_start().trim_start_matches('*');
+        (file == name).then(|| digest.to_ascii_lowercase())
+    })
+}
+
+/// Downloads the checksum manifest of `release` and checks its minisign signature
+///
+/// Unless `insecure` is set, a manifest without a valid signature from the
+/// compiled-in release key is refused, as is every manifest when the
+/// installer was built without a release key.
+pub async fn download_manifest(
+    octocrab: &Octocrab,
+    release: &Release,
+    insecure: bool,
+) -> Result<String, Error> {
+    let manifest = download_asset(octocrab, find_asset(release, CHECKSUMS_ASSET)?).await?;
+
+    if insecure {
+        output::warn(format!(
+            "--insecure given, skipping signature verification of {CHECKSUMS_ASSET}"
+        ));
+    } else {
+        let signature = find_asset(release, verify::SIGNATURE_ASSET)
+            .map_err(|_| Error::Unsigned(CHECKSUMS_ASSET.to_string()))?;
+        let signature = download_asset(octocrab, signature).await?;
+        verify::verify_signature(
+            &manifest,
+            &String::from_utf8_lossy(&signature),
+            verify::PUBLIC_KEY,
+        )?;
+    }
+
+    Ok(String::from_utf8_lossy(&manifest).into_owned())
+}
+
+/// Downloads the platform binary from `release` and verifies it against
+/// `checksums`, the signed checksum manifest from [`download_manifest`]
+///
+/// When `installed` names the version and path of an existing binary, a
+/// delta patch from that version is tried first; the full binary is only
+/// downloaded if no patch is published or the patched result does not match.
+pub async fn download_verified(
+    octocrab: &Octocrab,
+    release: &Release,
+    checksums: &str,
+    installed: Option<(&semver::Version, &Path)>,
+) -> Result<Vec<u8>, Error> {
+    let asset = platform_asset(release)?;
+    let expected = expected_checksum(checksums, &asset.name)
+        .ok_or_else(|| Error::MissingChecksum(asset.name.clone()))?;
+
+    if let Some((version, binary)) = installed {
+        match delta::download_patched(octocrab, release, version, binary, &expected).await {
+            Ok(bytes) => return Ok(bytes),
+            Err(err) => output::message(format!(
+                "Delta update unavailable ({err}), downloading the full binary"
+            )),
+        }
+    }
+
+    download_checked(octocrab, asset, expected).await
+}
+
+/// Downloads the GUI binary from `release` and verifies it against
+/// `checksums`, the signed checksum manifest from [`download_manifest`]
+pub async fn download_gui(
+    octocrab: &Octocrab,
+    release: &Release,
+    checksums: &str,
+) -> Result<Vec<u8>, Error> {
+    let asset = gui_asset(release)?;
+    let expected = expected_checksum(checksums, &asset.name)
+        .ok_or_else(|| Error::MissingChecksum(asset.name.clone()))?;
+    download_checked(octocrab, asset, expected).await
+}
+
+/// Downloads `asset` and checks that it hashes to `expected`
+async fn download_checked(
+    octocrab: &Octocrab,
+    asset: &Asset,
+    expected: String,
+) -> Result<Vec<u8>, Error> {
+    let bytes = download_asset(octocrab, asset).await?;
+    let actual = sha256_hex(&bytes);
+    if actual != expected {
+        return Err(Error::ChecksumMismatch {
+            name: asset.name.clone(),
+            expected,
+            actual,
+        });
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08";
+
+    #[test]
+    fn checksums_are_found_by_file_name() {
+        let manifest = format!(
+            "{} dosdisassm-v0.1.2-linux-x86_64\n{DIGEST} *dosdisassm-v0.1.2-windows-x64.exe\n",
+            "0".repeat(64)
+        );
+        assert_eq!(
+            expected_checksum(&manifest, "dosdisassm-v0.1.2-windows-x64.exe"),
+            Some(DIGEST.to_ascii_lowercase())
+        );
+        assert_eq!(
+            expected_checksum(&manifest, "dosdisassm-v0.1.2-linux-x86_64"),
+            Some("0".repeat(64))
+        );
+    }
+
+    #[test]
+    fn missing_entries_have_no_checksum() {
+        let manifest = format!("{DIGEST}  dosdisassm-v0.1.2-linux-x86_64\n");
+        assert_eq!(
+            expected_checksum(&manifest, "dosdisassm-v0.1.2-macos-aarch64"),
+            None
+        );
+        assert_eq!(
+            expected_checksum("", "dosdisassm-v0.1.2-linux-x86_64"),
+            None
+        );
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        let name = "dosdisassm-v0.1.2-linux-x86_64";
+        for manifest in [
+            name.to_string(),
+            format!("not-a-digest  {name}"),
+            format!("{}  {name}", &DIGEST[1..]),
+            format!("{}  {name}", DIGEST.replace('F', "g")),
+        ] {
+            assert_eq!(expected_checksum(&manifest, name), None, "{manifest}");
+        }
+        let manifest = format!("garbage\n{DIGEST}  {name}\n");
+        assert_eq!(
+            expected_checksum(&manifest, name),
+            Some(DIGEST.to_ascii_lowercase())
+        );
+    }
+}
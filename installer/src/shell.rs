@@ -0,0 +1,189 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The comment placed above every line the installer adds to a shell profile
+const MARKER: &str = "# added by the dosdisassm installer";
+
+/// A login shell whose startup file the installer knows how to edit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    /// GNU bash, configured through `~/.bashrc`
+    Bash,
+    /// zsh, configured through `$ZDOTDIR/.zshrc`
+    Zsh,
+    /// fish, configured through `~/.config/fish/config.fish`
+    Fish,
+    /// Any other POSIX shell, configured through `~/.profile`
+    Posix,
+}
+
+impl Shell {
+    /// Detects the user's shell from `$SHELL`
+    pub fn detect() -> Self {
+        let shell = std::env::var_os("SHELL").map(PathBuf::from);
+        match shell
+            .as_deref()
+            .and_then(Path::file_name)
+            .and_then(|name| name.to_str())
+        {
+            Some("bash") => Shell::Bash,
+            Some("zsh") => Shell::Zsh,
+            Some("fish") => Shell::Fish,
+            _ => Shell::Posix,
+        }
+    }
+
+    /// The startup file this shell reads for interactive sessions
+    pub fn profile(self) -> io::Result<PathBuf> {
+        let home = std::env::var("HOME").map_err(io::Error::other)?;
+        Ok(match self {
+            Shell::Bash => crate::path!(home, ".bashrc"),
+            Shell::Zsh => {
+                let dir = std::env::var("ZDOTDIR").unwrap_or(home);
+                crate::path!(dir, ".zshrc")
+            }
+            Shell::Fish => {
+                let config =
+                    std::env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| format!("{home}/.config"));
+                crate::path!(config, "fish", "config.fish")
+            }
+            Shell::Posix => crate::path!(home, ".profile"),
+        })
+    }
+
+    /// The line that appends `directory` to PATH in this shell's syntax
+    pub fn path_line(self, directory: &Path) -> String {
+        match self {
+            Shell::Fish => format!("set -gx PATH $PATH \"{}\"", directory.display()),
+            _ => format!("export PATH=\"$PATH:{}\"", directory.display()),
+        }
+    }
+}
+
+/// Appends `line` to `profile`, below a marker comment, unless it is already there
+///
+/// Returns whether the profile was modified.
+pub fn add_path_line(profile: &Path, line: &str) -> io::Result<bool> {
+    let mut contents = match fs::read_to_string(profile) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    if contents.lines().any(|existing| existing.trim() == line) {
+        return Ok(false);
+    }
+
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&format!("\n{MARKER}\n{line}\n"));
+    if let Some(parent) = profile.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(profile, contents)?;
+    Ok(true)
+}
+
+/// Removes `line`, and the marker comment above it, from `profile`
+///
+/// Only the block the installer added is removed: the same line written by
+/// the user, without the marker above it, is left alone.
+pub fn remove_path_line(profile: &Path, line: &str) -> io::Result<()> {
+    let contents = match fs::read_to_string(profile) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    let mut kept: Vec<&str> = Vec::new();
+    for existing in contents.lines() {
+        if existing.trim() == line && kept.last() == Some(&MARKER) {
+            kept.pop();
+            // drop the blank line separating our block as well
+            if kept.last().is_some_and(|previous| previous.is_empty()) {
+                kept.pop();
+            }
+            continue;
+        }
+        kept.push(existing);
+    }
+
+    let mut remaining = kept.join("\n");
+    if !remaining.is_empty() {
+        remaining.push('\n');
+    }
+    if remaining != contents {
+        fs::write(profile, remaining)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::create_temp_dir;
+
+    const LINE: &str = "export PATH=\"$PATH:/opt/dosdisassm\"";
+    const PROFILE: &str = "# my aliases\nalias ll='ls -l'\n\nexport EDITOR=vi";
+
+    #[test]
+    fn adding_twice_changes_nothing_the_second_time() {
+        let dir = create_temp_dir();
+        let profile = dir.join(".bashrc");
+        fs::write(&profile, PROFILE).unwrap();
+
+        assert!(add_path_line(&profile, LINE).unwrap());
+        let added = fs::read_to_string(&profile).unwrap();
+        assert_eq!(added, format!("{PROFILE}\n\n{MARKER}\n{LINE}\n"));
+        assert!(!add_path_line(&profile, LINE).unwrap());
+        assert_eq!(fs::read_to_string(&profile).unwrap(), added);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn removing_drops_the_marker_line_and_blank_line() {
+        let dir = create_temp_dir();
+        let profile = dir.join(".bashrc");
+        fs::write(&profile, format!("{PROFILE}\n")).unwrap();
+
+        add_path_line(&profile, LINE).unwrap();
+        remove_path_line(&profile, LINE).unwrap();
+        assert_eq!(
+            fs::read_to_string(&profile).unwrap(),
+            format!("{PROFILE}\n")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn removing_keeps_the_rest_of_the_profile() {
+        let dir = create_temp_dir();
+        let profile = dir.join(".bashrc");
+        let after = "alias gs='git status'\n";
+        fs::write(&profile, format!("{PROFILE}\n")).unwrap();
+
+        add_path_line(&profile, LINE).unwrap();
+        let mut contents = fs::read_to_string(&profile).unwrap();
+        contents.push_str(after);
+        fs::write(&profile, contents).unwrap();
+        remove_path_line(&profile, LINE).unwrap();
+        assert_eq!(
+            fs::read_to_string(&profile).unwrap(),
+            format!("{PROFILE}\n{after}")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn removing_leaves_the_line_the_user_wrote() {
+        let dir = create_temp_dir();
+        let profile = dir.join(".bashrc");
+        let own = format!("{PROFILE}\n{LINE}\n");
+        fs::write(&profile, &own).unwrap();
+
+        remove_path_line(&profile, LINE).unwrap();
+        assert_eq!(fs::read_to_string(&profile).unwrap(), own);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
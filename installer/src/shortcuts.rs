@@ -0,0 +1,104 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Creates a desktop entry for the GUI at `gui` in the user's application
+/// menu
+///
+/// Returns the paths of the shortcuts that were written.
+#[cfg(target_os = "linux")]
+pub fn create_shortcuts(gui: &Path) -> io::Result<Vec<PathBuf>> {
+    let home = std::env::var("HOME").map_err(io::Error::other)?;
+    let applications = crate::path!(home, ".local", "share", "applications");
+    Ok(vec![write_desktop_entry(&applications, gui)?])
+}
+
+/// Writes `dosdisassm.desktop`, opening files with the GUI at `gui`, into
+/// the `applications` directory and returns its path
+#[cfg(target_os = "linux")]
+fn write_desktop_entry(applications: &Path, gui: &Path) -> io::Result<PathBuf> {
+    crate::utils::mkdir_all(&applications.to_path_buf())?;
+    let entry = applications.join("dosdisassm.desktop");
+    std::fs::write(
+        &entry,
+        format!(
+            "[Desktop Entry]
+Type=Application
+Name=DosDisassm
+Comment=DOS .COM disassembler
+Exec=\"{}\" %f
+Terminal=false
+Categories=Development;Utility;
+",
+            gui.display()
+        ),
+    )?;
+    Ok(entry)
+}
+
+/// Creates start menu and desktop shortcuts to the GUI at `gui` through the
+/// `WScript.Shell` COM object
+///
+/// Returns the paths of the shortcuts that were written.
+#[cfg(windows)]
+pub fn create_shortcuts(gui: &Path) -> io::Result<Vec<PathBuf>> {
+    let app_data = std::env::var("APPDATA").map_err(io::Error::other)?;
+    let profile = std::env::var("USERPROFILE").map_err(io::Error::other)?;
+    let shortcuts = [
+        crate::path!(
+            app_data,
+            "Microsoft",
+            "Windows",
+            "Start Menu",
+            "Programs",
+            "DosDisassm.lnk"
+        ),
+        crate::path!(profile, "Desktop", "DosDisassm.lnk"),
+    ];
+
+    let mut created = Vec::new();
+    for shortcut in shortcuts {
+        let script = format!(
+            "$s = (New-Object -ComObject WScript.Shell).CreateShortcut('{}'); $s.TargetPath = '{}'; $s.Save()",
+            shortcut.display(),
+            gui.display()
+        );
+        let status = std::process::Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+            .status()?;
+        if status.success() {
+            created.push(shortcut);
+        } else {
+            crate::output::warn(format!("failed to create shortcut {}", shortcut.display()));
+        }
+    }
+    Ok(created)
+}
+
+/// Shortcuts are not created on other platforms
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn create_shortcuts(_gui: &Path) -> io::Result<Vec<PathBuf>> {
+    Ok(Vec::new())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::utils::create_temp_dir;
+
+    #[test]
+    fn the_desktop_entry_opens_files_with_the_gui() {
+        let dir = create_temp_dir();
+        let gui = dir.join("install").join("dosdisassm-gui");
+        let applications = dir.join("applications");
+
+        let entry = write_desktop_entry(&applications, &gui).unwrap();
+        assert_eq!(entry, applications.join("dosdisassm.desktop"));
+        let contents = std::fs::read_to_string(&entry).unwrap();
+        assert!(
+            contents.contains(&format!("\nExec=\"{}\" %f\n", gui.display())),
+            "{contents}"
+        );
+        assert!(contents.contains("\nTerminal=false\n"), "{contents}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::manifest::Manifest;
+use crate::output::{self, Event};
+use crate::{update, utils};
+
+/// Removes a file, treating an already missing file as success
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Deletes the running executable once this process has exited
+///
+/// Windows does not allow deleting a running executable, which is exactly
+/// what happens when uninstalling through "Apps & features".
+#[cfg(windows)]
+fn remove_running_exe(path: &Path) -> io::Result<()> {
+    std::process::Command::new("cmd")
+        .args(["/C", "ping -n 3 127.0.0.1 >NUL & del /F /Q"])
+        .arg(path)
+        .spawn()
+        .map(|_| ())
+}
+
+#[cfg(not(windows))]
+fn remove_running_exe(path: &Path) -> io::Result<()> {
+    remove_file(path)
+}
+
+/// Reports that something recorded in the manifest was removed
+fn removed(item: &dyn std::fmt::Display) {
+    output::emit(Event::Removed {
+        item: &item.to_string(),
+    });
+}
+
+/// Undoes everything recorded in the install manifest of `install_path`
+pub fn run(install_path: &Path) -> Result<(), Error> {
+    let Some(manifest) = Manifest::load(install_path)? else {
+        output::message(format!(
+            "No installation found in {}",
+            install_path.display()
+        ));
+        return Ok(());
+    };
+    output::message(format!(
+        "Uninstalling dosdisassm {} from {}",
+        manifest.version,
+        install_path.display()
+    ));
+
+    for file in &manifest.files {
+        removed(&file.path.display());
+        if update::is_current_exe(&file.path) {
+            remove_running_exe(&file.path).map_err(|err| Error::io_at(err, install_path))?;
+        } else {
+            remove_file(&file.path).map_err(|err| Error::io_at(err, install_path))?;
+        }
+    }
+
+    for shortcut in &manifest.shortcuts {
+        removed(&format_args!("shortcut {}", shortcut.display()));
+        remove_file(shortcut).map_err(|err| Error::io_at(err, shortcut))?;
+    }
+
+    for entry in &manifest.path_entries {
+        removed(&format_args!("{} from PATH", entry.directory.display()));
+        utils::remove_from_path(entry)?;
+    }
+
+    for key in &manifest.registry_keys {
+        removed(&format_args!("registry key HKCU\\{}", key.path));
+        utils::delete_registry_key(&key.path)?;
+    }
+
+    remove_file(&Manifest::path(install_path))?;
+    // only succeeds if nothing but our own files were in there
+    let _ = fs::remove_dir(install_path);
+
+    output::emit(Event::Uninstalled);
+    Ok(())
+}
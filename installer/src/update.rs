@@ -0,0 +1,452 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use octocrab::Octocrab;
+use octocrab::models::repos::Release;
+
+use crate::error::Error;
+use crate::manifest::Manifest;
+use crate::output::{self, Event};
+use crate::{completions, release, shortcuts, utils};
+
+/// The file name of the disassembler binary inside the install directory
+pub fn binary_name() -> &'static str {
+    if cfg!(windows) {
+        "dosdisassm.exe"
+    } else {
+        "dosdisassm"
+    }
+}
+
+/// The file name of the GUI binary inside the install directory
+pub fn gui_name() -> &'static str {
+    if cfg!(windows) {
+        "dosdisassm-gui.exe"
+    } else {
+        "dosdisassm-gui"
+    }
+}
+
+/// Returns `path` with `suffix` appended to its file name
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Pulls the first semver-looking word out of `--version` output such as `dosdisassm 0.1.2`
+fn parse_version_output(output: &str) -> Option<semver::Version> {
+    output
+        .split_whitespace()
+        .find_map(|word| semver::Version::parse(word.trim_start_matches('v')).ok())
+}
+
+/// Asks an installed binary for its version by running it with `--version`
+///
+/// Returns `None` if the binary is missing or does not answer sensibly.
+pub fn installed_version(binary: &Path) -> Option<semver::Version> {
+    let output = Command::new(binary).arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_version_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Checks that a freshly installed binary runs and reports the version we installed
+pub fn smoke_test(binary: &Path, expected: &semver::Version) -> Result<(), String> {
+    match installed_version(binary) {
+        Some(version) if &version == expected => Ok(()),
+        Some(version) => Err(format!(
+            "it reported version {version}, expected {expected}"
+        )),
+        None => Err("`--version` did not run successfully".to_string()),
+    }
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Keeps a copy of the old binary while leaving the target in place, so the
+/// following rename replaces it atomically
+#[cfg(not(windows))]
+fn move_aside(target: &Path, backup: &Path) -> io::Result<()> {
+    fs::copy(target, backup).map(|_| ())
+}
+
+/// Windows refuses to overwrite a running executable but allows renaming it,
+/// so the old binary is moved out of the way before the new one moves in
+#[cfg(windows)]
+fn move_aside(target: &Path, backup: &Path) -> io::Result<()> {
+    let _ = fs::remove_file(backup);
+    fs::rename(target, backup)
+}
+
+/// A binary that has been swapped in but not yet confirmed working
+pub struct PendingSwap {
+    target: PathBuf,
+    backup: Option<PathBuf>,
+}
+
+impl PendingSwap {
+    /// Restores the previous binary, or removes the new one if there was none
+    pub fn rollback(self) -> io::Result<()> {
+        match self.backup {
+            Some(backup) => {
+                if cfg!(windows) {
+                    fs::remove_file(&self.target)?;
+                }
+                fs::rename(backup, &self.target)
+            }
+            None => fs::remove_file(&self.target),
+        }
+    }
+
+    /// Keeps the new binary and deletes the backup
+    ///
+    /// On Windows the backup of a running executable stays locked until the
+    /// process exits; it is removed by the next [`swap_binary`] of the same
+    /// file.
+    pub fn commit(self) {
+        if let Some(backup) = self.backup {
+            let _ = fs::remove_file(backup);
+        }
+    }
+}
+
+/// Atomically replaces `target` with `bytes`, keeping a backup for rollback
+pub fn swap_binary(target: &Path, bytes: &[u8]) -> io::Result<PendingSwap> {
+    let staged = sibling(target, ".new");
+    fs::write(&staged, bytes)?;
+    make_executable(&staged)?;
+
+    let backup = if target.exists() {
+        let backup = sibling(target, ".old");
+        move_aside(target, &backup)?;
+        Some(backup)
+    } else {
+        None
+    };
+
+    if let Err(err) = fs::rename(&staged, target) {
+        let _ = fs::remove_file(&staged);
+        return Err(err);
+    }
+
+    Ok(PendingSwap {
+        target: target.to_path_buf(),
+        backup,
+    })
+}
+
+/// Returns true if `path` is the executable of the running process
+pub fn is_current_exe(path: &Path) -> bool {
+    match (std::env::current_exe(), fs::canonicalize(path)) {
+        (Ok(exe), Ok(path)) => fs::canonicalize(exe).is_ok_and(|exe| exe == path),
+        _ => false,
+    }
+}
+
+/// What to do when an existing installation is found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Install the latest release over the existing one
+    Upgrade,
+    /// Re-install the recorded version to restore missing or modified files
+    Repair,
+    /// Leave the installation untouched
+    Abort,
+}
+
+/// Asks the user what to do with an existing installation
+///
+/// With `assume_yes` the first choice is taken without asking.
+fn choose_action(choices: &[Action], assume_yes: bool) -> io::Result<Action> {
+    if assume_yes {
+        return Ok(choices.first().copied().unwrap_or(Action::Abort));
+    }
+    let options = choices
+        .iter()
+        .map(|action| match action {
+            Action::Upgrade => ('u', "upgrade"),
+            Action::Repair => ('r', "repair"),
+            Action::Abort => ('a', "abort"),
+        })
+        .collect::<Vec<_>>();
+    let choice = utils::prompt_choice("What would you like to do?", &options)?;
+    Ok(options
+        .iter()
+        .position(|(key, _)| *key == choice)
+        .map_or(Action::Abort, |index| choices[index]))
+}
+
+/// Choices from the command line that shape an install
+#[derive(Debug, Clone, Copy)]
+pub struct InstallOptions {
+    /// Skip signature verification
+    pub insecure: bool,
+    /// Add the install directory to PATH
+    pub add_to_path: bool,
+    /// Create desktop and start menu shortcuts
+    pub create_shortcuts: bool,
+    /// Install shell completions and the man page
+    pub install_completions: bool,
+    /// Never prompt, taking the first (recommended) answer instead
+    pub assume_yes: bool,
+}
+
+/// How a run of the installer ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// A release was installed, upgraded or repaired
+    Installed,
+    /// The newest release was already installed and intact
+    UpToDate,
+    /// The user chose to leave the existing installation untouched
+    Aborted,
+}
+
+/// Installs the latest release into `install_path`, or upgrades/repairs the installation already there
+///
+/// An existing installation is detected through its [`Manifest`]; installs
+/// made before the manifest existed are recognised by asking the binary for
+/// its version.
+pub async fn run(
+    octocrab: &Octocrab,
+    install_path: &Path,
+    options: &InstallOptions,
+) -> Result<Outcome, Error> {
+    let target = install_path.join(binary_name());
+    let release = release::latest_release(octocrab).await?;
+    let latest = release::parse_tag(&release.tag_name).ok_or(Error::NoRelease)?;
+    output::emit(Event::LatestRelease { version: &latest });
+
+    let Some(manifest) = Manifest::load(install_path)? else {
+        let installed = installed_version(&target);
+        match &installed {
+            Some(current) if current >= &latest => {
+                output::emit(Event::UpToDate { version: current });
+                return Ok(Outcome::UpToDate);
+            }
+            Some(current) => output::message(format!("Updating dosdisassm {current} -> {latest}")),
+            None => output::message(format!("Installing dosdisassm {latest}")),
+        }
+        return install_release(
+            octocrab,
+            &release,
+            install_path,
+            options,
+            installed.as_ref(),
+        )
+        .await;
+    };
+
+    let damaged = manifest.damaged_files();
+    output::emit(Event::ExistingInstall {
+        version: &manifest.version,
+        damaged: damaged.iter().map(|file| file.path.as_path()).collect(),
+    });
+
+    let choices: &[Action] = match (latest > manifest.version, damaged.is_empty()) {
+        (true, _) => &[Action::Upgrade, Action::Repair, Action::Abort],
+        (false, false) => &[Action::Repair, Action::Abort],
+        (false, true) => {
+            output::emit(Event::UpToDate {
+                version: &manifest.version,
+            });
+            return Ok(Outcome::UpToDate);
+        }
+    };
+
+    match choose_action(choices, options.assume_yes)? {
+        Action::Upgrade => {
+            output::message(format!(
+                "Updating dosdisassm {} -> {latest}",
+                manifest.version
+            ));
+            // a damaged binary cannot be patched, so only use delta updates on a healthy install
+            let installed = damaged.is_empty().then_some(&manifest.version);
+            install_release(octocrab, &release, install_path, options, installed).await
+        }
+        Action::Repair => {
+            let tag = format!("v{}", manifest.version);
+            output::message(format!("Repairing dosdisassm {}", manifest.version));
+            let release = octocrab
+                .repos(release::REPO_OWNER, release::REPO_NAME)
+                .releases()
+                .get_by_tag(&tag)
+                .await?;
+            install_release(octocrab, &release, install_path, options, None).await
+        }
+        Action::Abort => {
+            output::message("Leaving the existing installation untouched");
+            Ok(Outcome::Aborted)
+        }
+    }
+}
+
+/// The file name the installer copies itself to, so the install can later be updated or removed
+pub fn installer_name() -> &'static str {
+    if cfg!(windows) {
+        "dosdisassm-installer.exe"
+    } else {
+        "dosdisassm-installer"
+    }
+}
+
+/// Copies the running installer into the install directory and returns its path and contents
+fn copy_installer(install_path: &Path) -> io::Result<(PathBuf, Vec<u8>)> {
+    let destination = install_path.join(installer_name());
+    let bytes = fs::read(std::env::current_exe()?)?;
+    if !is_current_exe(&destination) {
+        fs::write(&destination, &bytes)?;
+        make_executable(&destination)?;
+    }
+    Ok((destination, bytes))
+}
+
+/// Downloads `release` and swaps its binaries into `install_path`
+///
+/// The new binary is downloaded and checked against the signed checksum manifest,
+/// swapped in, and then run with `--version`; if that fails the previous
+/// binary is restored. On success the GUI is swapped in next to it, PATH,
+/// shortcuts to the GUI and the uninstall entry are set up, and everything
+/// that was changed is recorded in the install manifest.
+async fn install_release(
+    octocrab: &Octocrab,
+    release: &Release,
+    install_path: &Path,
+    options: &InstallOptions,
+    installed: Option<&semver::Version>,
+) -> Result<Outcome, Error> {
+    let target = install_path.join(binary_name());
+    let version = release::parse_tag(&release.tag_name).ok_or(Error::NoRelease)?;
+
+    let checksums = release::download_manifest(octocrab, release, options.insecure).await?;
+    let bytes = release::download_verified(
+        octocrab,
+        release,
+        &checksums,
+        installed.map(|version| (version, target.as_path())),
+    )
+    .await?;
+    let gui_bytes = match release::download_gui(octocrab, release, &checksums).await {
+        Ok(bytes) => Some(bytes),
+        // older releases publish no GUI
+        Err(Error::MissingAsset(name)) => {
+            output::warn(format!(
+                "release is missing {name}, installing without the GUI and its shortcuts"
+            ));
+            None
+        }
+        Err(err) => return Err(err),
+    };
+
+    let elevation = |err| Error::io_at(err, install_path);
+    utils::mkdir_all(&install_path.to_path_buf()).map_err(elevation)?;
+    let swap = swap_binary(&target, &bytes).map_err(elevation)?;
+    if let Err(reason) = smoke_test(&target, &version) {
+        swap.rollback()?;
+        return Err(Error::SmokeTestFailed(reason));
+    }
+    swap.commit();
+
+    let previous = Manifest::load(install_path)?;
+    let mut manifest = Manifest::new(version.clone());
+    if let Some(previous) = &previous {
+        // changes made by earlier installs still have to be undone on uninstall
+        manifest.shortcuts = previous.shortcuts.clone();
+        manifest.path_entries = previous.path_entries.clone();
+        manifest.registry_keys = previous.registry_keys.clone();
+    }
+    manifest.record_file(&target, &bytes);
+
+    let (installer, installer_bytes) = copy_installer(install_path).map_err(elevation)?;
+    manifest.record_file(&installer, &installer_bytes);
+
+    let gui = install_path.join(gui_name());
+    if let Some(gui_bytes) = &gui_bytes {
+        swap_binary(&gui, gui_bytes).map_err(elevation)?.commit();
+        manifest.record_file(&gui, gui_bytes);
+    }
+
+    if options.add_to_path {
+        match utils::add_to_path(install_path) {
+            Ok(Some(entry)) => manifest.record_path_entry(entry),
+            Ok(None) => {}
+            Err(err) => output::warn(format!("failed to add {install_path:?} to PATH: {err}")),
+        }
+    }
+
+    if options.create_shortcuts && gui_bytes.is_some() {
+        match shortcuts::create_shortcuts(&gui) {
+            Ok(created) => created
+                .iter()
+                .for_each(|shortcut| manifest.record_shortcut(shortcut)),
+            Err(err) => output::warn(format!("failed to create shortcuts: {err}")),
+        }
+    }
+
+    if options.install_completions
+        && let Err(err) = completions::install(&target, install_path, &mut manifest)
+    {
+        output::warn(format!("failed to install shell completions: {err}"));
+    }
+
+    if let Some(key) = utils::register_uninstaller(&version, install_path, &installer)? {
+        manifest.record_registry_key(&key);
+    }
+
+    if let Some(previous) = &previous {
+        for stale in previous.stale_files(&manifest) {
+            output::emit(Event::Removed {
+                item: &stale.path.display().to_string(),
+            });
+            let _ = fs::remove_file(&stale.path);
+        }
+    }
+    manifest.save(install_path).map_err(elevation)?;
+    output::emit(Event::Installed {
+        version: &version,
+        path: &target,
+    });
+    Ok(Outcome::Installed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_read_from_version_output() {
+        let expected = semver::Version::new(0, 1, 2);
+        assert_eq!(
+            parse_version_output("dosdisassm 0.1.2\n"),
+            Some(expected.clone())
+        );
+        assert_eq!(parse_version_output("dosdisassm v0.1.2"), Some(expected));
+    }
+
+    #[test]
+    fn unexpected_version_output_has_no_version() {
+        for output in [
+            "",
+            "dosdisassm",
+            "dosdisassm 0.1",
+            "error: unexpected argument '--version' found",
+            "\u{fffd}\u{fffd} 1.x.3",
+        ] {
+            assert_eq!(parse_version_output(output), None, "{output}");
+        }
+    }
+}
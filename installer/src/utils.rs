@@ -1,4 +1,6 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::manifest::PathEntry;
 
 #[macro_export]
 macro_rules! path {
@@ -79,6 +81,7 @@ pub fn get_default_installation_path() -> PathBuf {
     }
 }
 
+#[allow(dead_code)]
 pub fn create_temp_dir() -> PathBuf {
     let temp_dir = std::env::temp_dir();
     let random_string = rand::random::<u64>().to_string();
@@ -89,6 +92,35 @@ pub fn create_temp_dir() -> PathBuf {
     temp_dir
 }
 
+/// Prints `question` with the given `(key, description)` options and reads the user's choice
+///
+/// Keeps asking until one of the keys is entered; end of input picks the last option.
+pub fn prompt_choice(question: &str, options: &[(char, &str)]) -> std::io::Result<char> {
+    use std::io::{BufRead, Write};
+
+    let menu = options
+        .iter()
+        .map(|(key, description)| format!("[{key}] {description}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let stdin = std::io::stdin();
+    loop {
+        print!("{question} {menu}: ");
+        std::io::stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(options.last().map_or('\0', |(key, _)| *key));
+        }
+        let answer = line.trim().to_lowercase();
+        if let Some((key, _)) = options.iter().find(|(key, description)| {
+            answer == key.to_string() || answer == description.to_lowercase()
+        }) {
+            return Ok(*key);
+        }
+    }
+}
+
 pub fn mkdir_all(path: &PathBuf) -> std::io::Result<()> {
     if !path.exists() {
         std::fs::create_dir_all(path)?;
@@ -96,29 +128,181 @@ pub fn mkdir_all(path: &PathBuf) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Adds `path` to PATH through the profile of the user's shell
+///
+/// Returns the modification to record, or `None` if the profile already
+/// contained the entry.
 #[cfg(unix)]
-pub fn add_to_path(path: &PathBuf) -> std::io::Result<()> {
-    println!(
-        "To add to path, add the following line to your ~/.bashrc or ~/.zshrc file:
-export PATH=\"$PATH:{path:?}\""
-    );
-    Ok(())
+pub fn add_to_path(path: &Path) -> std::io::Result<Option<PathEntry>> {
+    use crate::shell::{self, Shell};
+
+    let shell = Shell::detect();
+    let profile = shell.profile()?;
+    let line = shell.path_line(path);
+    if !shell::add_path_line(&profile, &line)? {
+        return Ok(None);
+    }
+    crate::output::message(format!(
+        "Added {} to PATH in {}, open a new terminal to use it",
+        path.display(),
+        profile.display()
+    ));
+    Ok(Some(PathEntry {
+        directory: path.to_path_buf(),
+        profile: Some(profile),
+        line: Some(line),
+    }))
 }
 
+/// Tells running programs, Explorer in particular, that the user environment changed
+///
+/// Without this, terminals opened before the next logon keep the old PATH.
 #[cfg(windows)]
-pub fn add_to_path(path: &PathBuf) -> std::io::Result<()> {
+fn broadcast_environment_change() {
+    use winapi::um::winuser::{
+        HWND_BROADCAST, SMTO_ABORTIFHUNG, SendMessageTimeoutW, WM_SETTINGCHANGE,
+    };
+
+    let environment = "Environment\0".encode_utf16().collect::<Vec<u16>>();
+    unsafe {
+        SendMessageTimeoutW(
+            HWND_BROADCAST,
+            WM_SETTINGCHANGE,
+            0,
+            environment.as_ptr() as isize,
+            SMTO_ABORTIFHUNG,
+            5000,
+            std::ptr::null_mut(),
+        );
+    }
+}
+
+/// Adds `path` to the user's PATH in the registry
+///
+/// Returns the modification to record, or `None` if PATH already contained it.
+#[cfg(windows)]
+pub fn add_to_path(path: &Path) -> std::io::Result<Option<PathEntry>> {
     use winreg::RegKey;
     use winreg::enums::*;
 
     let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-    let path_key = hkcu.open_subkey_with_flags("Environment", KEY_SET_VALUE)?;
+    let path_key = hkcu.open_subkey_with_flags("Environment", KEY_READ | KEY_SET_VALUE)?;
 
-    let mut current_path: String = path_key.get_value("Path")?;
-    if !current_path.contains(path.to_str().unwrap()) {
+    let mut current_path: String = path_key.get_value("Path").unwrap_or_default();
+    let entry = path.to_string_lossy();
+    if current_path.split(';').any(|existing| existing == entry) {
+        return Ok(None);
+    }
+    if !current_path.is_empty() && !current_path.ends_with(';') {
         current_path.push(';');
-        current_path.push_str(path.to_str().unwrap());
-        path_key.set_value("Path", &current_path)?;
     }
+    current_path.push_str(&entry);
+    path_key.set_value("Path", &current_path)?;
+    broadcast_environment_change();
+
+    Ok(Some(PathEntry {
+        directory: path.to_path_buf(),
+        profile: None,
+        line: None,
+    }))
+}
+
+/// Undoes a PATH modification recorded by [`add_to_path`]
+///
+/// Entries from installs that predate profile tracking carry no profile and are skipped.
+#[cfg(unix)]
+pub fn remove_from_path(entry: &PathEntry) -> std::io::Result<()> {
+    match (&entry.profile, &entry.line) {
+        (Some(profile), Some(line)) => crate::shell::remove_path_line(profile, line),
+        _ => Ok(()),
+    }
+}
+
+/// Undoes a PATH modification recorded by [`add_to_path`]
+#[cfg(windows)]
+pub fn remove_from_path(entry: &PathEntry) -> std::io::Result<()> {
+    use winreg::RegKey;
+    use winreg::enums::*;
+
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    let path_key = hkcu.open_subkey_with_flags("Environment", KEY_READ | KEY_SET_VALUE)?;
+
+    let current_path: String = path_key.get_value("Path").unwrap_or_default();
+    let directory = entry.directory.to_string_lossy();
+    let remaining = current_path
+        .split(';')
+        .filter(|existing| !existing.is_empty() && *existing != directory)
+        .collect::<Vec<_>>()
+        .join(";");
+    if remaining != current_path {
+        path_key.set_value("Path", &remaining)?;
+        broadcast_environment_change();
+    }
+
+    Ok(())
+}
 
+/// The registry key the uninstall entry in "Apps & features" is written to
+#[cfg(windows)]
+pub const UNINSTALL_KEY: &str =
+    "Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\DOSDisassm";
+
+/// Registers the install with "Apps & features" so it can be removed from the control panel
+///
+/// Returns the registry key that was created, if any.
+#[cfg(windows)]
+pub fn register_uninstaller(
+    version: &semver::Version,
+    install_path: &Path,
+    installer: &Path,
+) -> std::io::Result<Option<String>> {
+    use winreg::RegKey;
+    use winreg::enums::*;
+
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    let (key, _) = hkcu.create_subkey(UNINSTALL_KEY)?;
+    key.set_value("DisplayName", &"DosDisassm")?;
+    key.set_value("DisplayVersion", &version.to_string())?;
+    key.set_value("Publisher", &"sk337")?;
+    key.set_value(
+        "InstallLocation",
+        &install_path.to_string_lossy().to_string(),
+    )?;
+    key.set_value(
+        "UninstallString",
+        &format!(
+            "\"{}\" --uninstall --install-path \"{}\"",
+            installer.display(),
+            install_path.display()
+        ),
+    )?;
+    key.set_value("NoModify", &1u32)?;
+    key.set_value("NoRepair", &1u32)?;
+
+    Ok(Some(UNINSTALL_KEY.to_string()))
+}
+
+#[cfg(unix)]
+pub fn register_uninstaller(
+    _version: &semver::Version,
+    _install_path: &Path,
+    _installer: &Path,
+) -> std::io::Result<Option<String>> {
+    Ok(None)
+}
+
+#[cfg(windows)]
+pub fn delete_registry_key(path: &str) -> std::io::Result<()> {
+    use winreg::RegKey;
+    use winreg::enums::*;
+
+    match RegKey::predef(HKEY_CURRENT_USER).delete_subkey_all(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+pub fn delete_registry_key(_path: &str) -> std::io::Result<()> {
     Ok(())
 }
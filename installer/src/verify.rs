@@ -0,0 +1,66 @@
+use minisign_verify::{PublicKey, Signature};
+
+use crate::error::Error;
+
+/// The minisign public key release checksum manifests are signed with, as
+/// written by `minisign -G`
+///
+/// The matching secret key lives in the `MINISIGN_SECRET_KEY` secret of the
+/// release workflow, which signs `SHA256SUMS` into `SHA256SUMS.minisig`.
+/// Until the maintainer's key is committed the file holds only its comment,
+/// and every release is refused unless `--insecure` is given.
+pub const PUBLIC_KEY: &str = include_str!("../minisign.pub");
+
+/// The name of the detached signature uploaded next to the checksum manifest
+pub const SIGNATURE_ASSET: &str = "SHA256SUMS.minisig";
+
+/// Checks a minisign `signature` of `data` against `public_key`, the
+/// contents of a minisign public key file such as [`PUBLIC_KEY`]
+///
+/// A key file without a key fails with [`Error::NoReleaseKey`].
+pub fn verify_signature(data: &[u8], signature: &str, public_key: &str) -> Result<(), Error> {
+    let public_key = PublicKey::decode(public_key).map_err(|_| Error::NoReleaseKey)?;
+    let signature =
+        Signature::decode(signature.trim()).map_err(|err| Error::BadSignature(err.to_string()))?;
+    public_key
+        .verify(data, &signature, false)
+        .map_err(|err| Error::BadSignature(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example key and signature of `test` from the minisign-verify docs
+    const KEY: &str = "untrusted comment: minisign public key\n\
+                       RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3\n";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key\n\
+        RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=\n\
+        trusted comment: timestamp:1633700835\tfile:test\tprehashed\n\
+        wLMDjy9FLAuxZ3q4NlEvkgtyhrr0gtTu6KC4KBJdITbbOeAi1zBIYo0v4iTgt8jJpIidRJnp94ABQkJAgAooBQ==\n";
+
+    #[test]
+    fn signatures_verify_only_against_their_key_and_data() {
+        assert!(verify_signature(b"test", SIGNATURE, KEY).is_ok());
+        assert!(matches!(
+            verify_signature(b"tampered", SIGNATURE, KEY),
+            Err(Error::BadSignature(_))
+        ));
+    }
+
+    #[test]
+    fn a_key_file_without_a_key_verifies_nothing() {
+        let no_key = "untrusted comment: no release signing key yet\n";
+        assert!(matches!(
+            verify_signature(b"test", SIGNATURE, no_key),
+            Err(Error::NoReleaseKey)
+        ));
+    }
+
+    #[test]
+    fn the_committed_key_refuses_foreign_signatures() {
+        let err = verify_signature(b"test", SIGNATURE, PUBLIC_KEY).unwrap_err();
+        let has_key = PublicKey::decode(PUBLIC_KEY).is_ok();
+        assert_eq!(matches!(err, Error::NoReleaseKey), !has_key, "{err}");
+    }
+}